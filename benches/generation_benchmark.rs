@@ -35,6 +35,7 @@ fn create_text_generation_model() -> TextGenerationModel {
         no_repeat_ngram_size: 3,
         num_return_sequences: 5,
         device: Device::cuda_if_available(),
+        ..Default::default()
     };
     TextGenerationModel::new(config).unwrap()
 }
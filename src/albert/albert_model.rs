@@ -13,10 +13,18 @@
 
 use crate::albert::encoder::AlbertTransformer;
 use crate::common::activations::Activation;
+use crate::common::classification_head::{
+    ClassificationHead, ClassificationHeadConfig, ClassifierOption,
+};
+use crate::common::config::{
+    check_divisible, check_positive, check_token_id, deserialize_id2label,
+};
 use crate::common::dropout::Dropout;
+use crate::common::model_output::{tensor_refs, ModelOutput};
 use crate::{albert::embeddings::AlbertEmbeddings, common::activations::TensorFunction};
 use crate::{Config, RustBertError};
 use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
 use std::{borrow::Borrow, collections::HashMap};
 use tch::nn::Module;
 use tch::{nn, Kind, Tensor};
@@ -69,12 +77,15 @@ pub struct AlbertConfig {
     pub hidden_dropout_prob: f64,
     pub hidden_size: i64,
     pub initializer_range: f32,
+    /// Number of layers within each group of shared parameters
     pub inner_group_num: i64,
     pub intermediate_size: i64,
     pub layer_norm_eps: Option<f64>,
     pub max_position_embeddings: i64,
     pub net_structure_type: i64,
     pub num_attention_heads: i64,
+    /// Number of groups of layers sharing their parameters. The `num_hidden_layers` layers are split into
+    /// `num_hidden_groups` contiguous blocks (the pre-trained ALBERT models share a single group across all layers)
     pub num_hidden_groups: i64,
     pub num_hidden_layers: i64,
     pub num_memory_blocks: i64,
@@ -84,11 +95,37 @@ pub struct AlbertConfig {
     pub output_attentions: Option<bool>,
     pub output_hidden_states: Option<bool>,
     pub is_decoder: Option<bool>,
+    #[serde(default, deserialize_with = "deserialize_id2label")]
     pub id2label: Option<HashMap<i64, String>>,
     pub label2id: Option<HashMap<String, i64>>,
+    /// Additional fields of the configuration file not used by the model
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
 }
 
-impl Config<AlbertConfig> for AlbertConfig {}
+impl Config<AlbertConfig> for AlbertConfig {
+    fn extra_fields(&self) -> Option<&Map<String, Value>> {
+        Some(&self.extra)
+    }
+
+    fn validate(&self) -> Result<(), RustBertError> {
+        check_divisible(
+            "hidden_size",
+            self.hidden_size,
+            "num_attention_heads",
+            self.num_attention_heads,
+        )?;
+        check_positive("num_hidden_groups", self.num_hidden_groups)?;
+        check_positive("inner_group_num", self.inner_group_num)?;
+        if self.num_hidden_groups > self.num_hidden_layers {
+            return Err(RustBertError::InvalidConfigurationError(format!(
+                "`num_hidden_groups` ({}) must not exceed `num_hidden_layers` ({})",
+                self.num_hidden_groups, self.num_hidden_layers
+            )));
+        }
+        check_token_id("pad_token_id", Some(self.pad_token_id), self.vocab_size)
+    }
+}
 
 /// # ALBERT Base model
 /// Base architecture for ALBERT models. Task-specific models will be built from this common base model
@@ -444,11 +481,11 @@ impl AlbertForMaskedLM {
 /// It is made of the following blocks:
 /// - `albert`: Base AlbertModel
 /// - `dropout`: Dropout layer
-/// - `classifier`: linear layer for classification
+/// - `classifier`: linear layer for classification, or a `ClassificationHead` configured with `new_with_head`
 pub struct AlbertForSequenceClassification {
     albert: AlbertModel,
     dropout: Dropout,
-    classifier: nn::Linear,
+    classifier: ClassifierOption<nn::Linear>,
 }
 
 impl AlbertForSequenceClassification {
@@ -478,8 +515,33 @@ impl AlbertForSequenceClassification {
     where
         P: Borrow<nn::Path<'p>>,
     {
-        let p = p.borrow();
+        AlbertForSequenceClassification::build(p.borrow(), config, None)
+    }
 
+    /// Build a new `AlbertForSequenceClassification` with a custom classification head, for checkpoints fine-tuned
+    /// with a head different from the linear projection of the pooled output
+    ///
+    /// # Arguments
+    ///
+    /// * `p` - Variable store path for the root of the AlbertForSequenceClassification model
+    /// * `config` - `AlbertConfig` object defining the model architecture and number of classes
+    /// * `head_config` - `ClassificationHeadConfig` defining the classification head architecture
+    pub fn new_with_head<'p, P>(
+        p: P,
+        config: &AlbertConfig,
+        head_config: &ClassificationHeadConfig,
+    ) -> AlbertForSequenceClassification
+    where
+        P: Borrow<nn::Path<'p>>,
+    {
+        AlbertForSequenceClassification::build(p.borrow(), config, Some(head_config))
+    }
+
+    fn build(
+        p: &nn::Path,
+        config: &AlbertConfig,
+        head_config: Option<&ClassificationHeadConfig>,
+    ) -> AlbertForSequenceClassification {
         let albert = AlbertModel::new(p / "albert", config);
         let classifier_dropout_prob = config.classifier_dropout_prob.unwrap_or(0.1);
         let dropout = Dropout::new(classifier_dropout_prob);
@@ -488,12 +550,21 @@ impl AlbertForSequenceClassification {
             .as_ref()
             .expect("num_labels not provided in configuration")
             .len() as i64;
-        let classifier = nn::linear(
-            p / "classifier",
-            config.hidden_size,
-            num_labels,
-            Default::default(),
-        );
+        let classifier = match head_config {
+            Some(head_config) => ClassifierOption::Custom(ClassificationHead::new(
+                p / "classifier",
+                config.hidden_size,
+                num_labels,
+                head_config,
+                classifier_dropout_prob,
+            )),
+            None => ClassifierOption::Default(nn::linear(
+                p / "classifier",
+                config.hidden_size,
+                num_labels,
+                Default::default(),
+            )),
+        };
 
         AlbertForSequenceClassification {
             albert,
@@ -558,6 +629,7 @@ impl AlbertForSequenceClassification {
         input_embeds: Option<Tensor>,
         train: bool,
     ) -> AlbertSequenceClassificationOutput {
+        let head_mask = mask.as_ref().map(Tensor::shallow_clone);
         let base_model_output = self
             .albert
             .forward_t(
@@ -569,10 +641,15 @@ impl AlbertForSequenceClassification {
                 train,
             )
             .unwrap();
-        let logits = base_model_output
-            .pooled_output
-            .apply_t(&self.dropout, train)
-            .apply(&self.classifier);
+        let logits = match &self.classifier {
+            ClassifierOption::Default(classifier) => base_model_output
+                .pooled_output
+                .apply_t(&self.dropout, train)
+                .apply(classifier),
+            ClassifierOption::Custom(head) => {
+                head.forward_t(&base_model_output.hidden_state, head_mask.as_ref(), train)
+            }
+        };
         AlbertSequenceClassificationOutput {
             logits,
             all_hidden_states: base_model_output.all_hidden_states,
@@ -1045,6 +1122,22 @@ pub struct AlbertOutput {
     pub all_attentions: Option<Vec<Vec<Tensor>>>,
 }
 
+impl ModelOutput for AlbertOutput {
+    fn hidden_states(&self) -> Option<Vec<&Tensor>> {
+        tensor_refs(&self.all_hidden_states)
+    }
+
+    fn attentions(&self) -> Option<Vec<&Tensor>> {
+        self.all_attentions
+            .as_ref()
+            .map(|groups| groups.iter().flatten().collect())
+    }
+
+    fn logits(&self) -> Option<&Tensor> {
+        None
+    }
+}
+
 /// Container for the ALBERT masked LM model output.
 pub struct AlbertMaskedLMOutput {
     /// Logits for the vocabulary items at each sequence position
@@ -1055,6 +1148,22 @@ pub struct AlbertMaskedLMOutput {
     pub all_attentions: Option<Vec<Vec<Tensor>>>,
 }
 
+impl ModelOutput for AlbertMaskedLMOutput {
+    fn hidden_states(&self) -> Option<Vec<&Tensor>> {
+        tensor_refs(&self.all_hidden_states)
+    }
+
+    fn attentions(&self) -> Option<Vec<&Tensor>> {
+        self.all_attentions
+            .as_ref()
+            .map(|groups| groups.iter().flatten().collect())
+    }
+
+    fn logits(&self) -> Option<&Tensor> {
+        Some(&self.prediction_scores)
+    }
+}
+
 /// Container for the ALBERT sequence classification model
 pub struct AlbertSequenceClassificationOutput {
     /// Logits for each input (sequence) for each target class
@@ -1065,6 +1174,22 @@ pub struct AlbertSequenceClassificationOutput {
     pub all_attentions: Option<Vec<Vec<Tensor>>>,
 }
 
+impl ModelOutput for AlbertSequenceClassificationOutput {
+    fn hidden_states(&self) -> Option<Vec<&Tensor>> {
+        tensor_refs(&self.all_hidden_states)
+    }
+
+    fn attentions(&self) -> Option<Vec<&Tensor>> {
+        self.all_attentions
+            .as_ref()
+            .map(|groups| groups.iter().flatten().collect())
+    }
+
+    fn logits(&self) -> Option<&Tensor> {
+        Some(&self.logits)
+    }
+}
+
 /// Container for the ALBERT token classification model
 pub struct AlbertTokenClassificationOutput {
     /// Logits for each sequence item (token) for each target class
@@ -1075,6 +1200,22 @@ pub struct AlbertTokenClassificationOutput {
     pub all_attentions: Option<Vec<Vec<Tensor>>>,
 }
 
+impl ModelOutput for AlbertTokenClassificationOutput {
+    fn hidden_states(&self) -> Option<Vec<&Tensor>> {
+        tensor_refs(&self.all_hidden_states)
+    }
+
+    fn attentions(&self) -> Option<Vec<&Tensor>> {
+        self.all_attentions
+            .as_ref()
+            .map(|groups| groups.iter().flatten().collect())
+    }
+
+    fn logits(&self) -> Option<&Tensor> {
+        Some(&self.logits)
+    }
+}
+
 /// Container for the ALBERT question answering model
 pub struct AlbertQuestionAnsweringOutput {
     /// Logits for the start position for token of each input sequence
@@ -1086,3 +1227,23 @@ pub struct AlbertQuestionAnsweringOutput {
     /// Attention weights for all intermediate layers
     pub all_attentions: Option<Vec<Vec<Tensor>>>,
 }
+
+impl ModelOutput for AlbertQuestionAnsweringOutput {
+    fn hidden_states(&self) -> Option<Vec<&Tensor>> {
+        tensor_refs(&self.all_hidden_states)
+    }
+
+    fn attentions(&self) -> Option<Vec<&Tensor>> {
+        self.all_attentions
+            .as_ref()
+            .map(|groups| groups.iter().flatten().collect())
+    }
+
+    fn logits(&self) -> Option<&Tensor> {
+        None
+    }
+
+    fn start_end_logits(&self) -> Option<(&Tensor, &Tensor)> {
+        Some((&self.start_logits, &self.end_logits))
+    }
+}
@@ -178,8 +178,8 @@ impl AlbertTransformer {
         );
 
         let mut layers: Vec<AlbertLayerGroup> = vec![];
-        for layer_index in 0..config.inner_group_num {
-            layers.push(AlbertLayerGroup::new(&p_layers / layer_index, config));
+        for group_index in 0..config.num_hidden_groups {
+            layers.push(AlbertLayerGroup::new(&p_layers / group_index, config));
         }
 
         AlbertTransformer {
@@ -212,7 +212,9 @@ impl AlbertTransformer {
         };
 
         for i in 0..self.num_hidden_layers {
-            let group_idx = i / (self.num_hidden_layers / self.num_hidden_groups);
+            // Layers are split into `num_hidden_groups` contiguous blocks sharing the same parameters. The number of
+            // layers does not have to be a multiple of the number of groups.
+            let group_idx = i * self.num_hidden_groups / self.num_hidden_layers;
             let layer = &self.layers[group_idx as usize];
 
             if let Some(hidden_states) = all_hidden_states.borrow_mut() {
@@ -221,7 +223,7 @@ impl AlbertTransformer {
 
             let temp = layer.forward_t(&hidden_state, &mask, train);
             hidden_state = temp.0;
-            let attention_weights = temp.1;
+            let attention_weights = temp.2;
             if let Some(attentions) = all_attentions.borrow_mut() {
                 attentions.push(attention_weights.unwrap());
             };
@@ -15,10 +15,18 @@ use crate::bart::attention::LayerState;
 use crate::bart::decoder::BartDecoder;
 use crate::bart::encoder::BartEncoder;
 use crate::common::activations::Activation;
+use crate::common::adapters::Adapters;
+use crate::common::config::{check_divisible, check_token_id, deserialize_id2label};
 use crate::common::dropout::Dropout;
-use crate::pipelines::generation_utils::{Cache, LMHeadModel, LMModelOutput};
+use crate::common::lm_head::LMHead;
+use crate::common::model_output::{tensor_refs, ModelOutput};
+use crate::common::weights::resize_token_dimension;
+use crate::pipelines::generation_utils::{
+    Cache, EncoderOnlyModel, LMHeadModel, LMModelOutput, Seq2SeqGenerationModel,
+};
 use crate::{Config, RustBertError};
 use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
 use std::borrow::Borrow;
 use std::collections::HashMap;
 use tch::kind::Kind::{Float, Int64};
@@ -192,6 +200,7 @@ pub struct BartConfig {
     pub bos_token_id: Option<i64>,
     pub eos_token_id: Option<i64>,
     pub pad_token_id: Option<i64>,
+    #[serde(default, deserialize_with = "deserialize_id2label")]
     pub id2label: Option<HashMap<i64, String>>,
     pub label2id: Option<HashMap<String, i64>>,
     pub init_std: f64,
@@ -208,9 +217,39 @@ pub struct BartConfig {
     pub static_position_embeddings: Option<bool>,
     pub scale_embedding: Option<bool>,
     pub vocab_size: i64,
+    /// Additional fields of the configuration file not used by the model
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
 }
 
-impl Config<BartConfig> for BartConfig {}
+impl Config<BartConfig> for BartConfig {
+    fn extra_fields(&self) -> Option<&Map<String, Value>> {
+        Some(&self.extra)
+    }
+
+    fn validate(&self) -> Result<(), RustBertError> {
+        check_divisible(
+            "d_model",
+            self.d_model,
+            "encoder_attention_heads",
+            self.encoder_attention_heads,
+        )?;
+        check_divisible(
+            "d_model",
+            self.d_model,
+            "decoder_attention_heads",
+            self.decoder_attention_heads,
+        )?;
+        check_token_id("bos_token_id", self.bos_token_id, self.vocab_size)?;
+        check_token_id("eos_token_id", self.eos_token_id, self.vocab_size)?;
+        check_token_id("pad_token_id", self.pad_token_id, self.vocab_size)?;
+        check_token_id(
+            "decoder_start_token_id",
+            self.decoder_start_token_id,
+            self.vocab_size,
+        )
+    }
+}
 
 fn _prepare_bart_decoder_inputs(
     pad_token_id: i64,
@@ -254,20 +293,73 @@ fn _shift_tokens_right(input_ids: &Tensor, pad_token_id: i64) -> Tensor {
     output
 }
 
+/// Builds the decoder input ids of encoder-decoder models (e.g. BART, Marian or T5) from the target labels, for
+/// training with teacher forcing: the labels are shifted one position to the right and the decoder start token is
+/// inserted at the first position. The last label is dropped, so that the decoder predicts each label from the
+/// previous ones. Label positions ignored by the loss (`-100`) are replaced by the padding token id.
+///
+/// BART and Marian models start decoding with the `decoder_start_token_id` of their configuration (often the EOS token
+/// for BART), while T5 starts with its padding token.
+///
+/// # Arguments
+///
+/// * `labels` - Target token ids of shape (*batch size*, *target_sequence_length*)
+/// * `pad_token_id` - Padding token id, replacing the ignored label positions
+/// * `decoder_start_token_id` - First decoder input token id
+///
+/// # Returns
+///
+/// * `Tensor` of shape (*batch size*, *target_sequence_length*) with the decoder input ids
+///
+/// # Example
+///
+/// ```no_run
+/// use rust_bert::bart::shift_tokens_right;
+/// use tch::Tensor;
+///
+/// // Labels are padded with -100, ignored by the cross-entropy loss
+/// let labels = Tensor::of_slice(&[0, 31414, 232, 2, -100, 0, 713, 16, 10, 2]).view((2, 5));
+/// let decoder_input_ids = shift_tokens_right(&labels, 1, 2);
+/// // [[2, 0, 31414, 232, 2], [2, 0, 713, 16, 10]]
+/// ```
+pub fn shift_tokens_right(
+    labels: &Tensor,
+    pad_token_id: i64,
+    decoder_start_token_id: i64,
+) -> Tensor {
+    let size = labels.size();
+    let (batch_size, length) = (size[0], size[1]);
+    let decoder_start = Tensor::full(
+        &[batch_size, 1],
+        decoder_start_token_id,
+        (Int64, labels.device()),
+    );
+    let shifted = Tensor::cat(
+        &[
+            decoder_start,
+            labels.narrow(1, 0, (length - 1).max(0)).to_kind(Int64),
+        ],
+        1,
+    );
+    shifted.masked_fill(&shifted.eq(-100), pad_token_id)
+}
+
 /// # BART Base model
 /// Base architecture for BART model. Usually complemented with a task-specific head, such as a language model head.
 /// It is made of the following blocks:
 /// - `encoder`: `BartEncoder` (transformer) made of a vector of encoding layers
 /// - `decoder`: `BartDecoder` (transformer)  made of a vector of decoding layers with self attention and encoder cross-attention.
-/// caching is implemented for the decoder to avoid recalculating static states (encoder key/values and previously calculated decoder key/values)
+/// caching is implemented for the decoder to avoid recalculating static states (encoder key/values and previously calculated decoder key/values).
+/// The decoder is optional and may be loaded lazily for encoder-only usage (see `new_encoder_only` and `load_decoder`)
 /// - `generation_mode`: flag indicating if the model should run in generation mode (a decoder start token must then be provided)
 /// - `pad_token_id`: padding token id
 pub struct BartModel {
     pub(crate) encoder: BartEncoder,
-    decoder: BartDecoder,
+    decoder: Option<BartDecoder>,
     pub(crate) embeddings: nn::Embedding,
     generation_mode: bool,
     pad_token_id: i64,
+    init_std: f64,
 }
 
 impl BartModel {
@@ -313,7 +405,7 @@ impl BartModel {
         );
 
         let encoder = BartEncoder::new(p / "encoder", config);
-        let decoder = BartDecoder::new(p / "decoder", config, generation_mode);
+        let decoder = Some(BartDecoder::new(p / "decoder", config, generation_mode));
 
         BartModel {
             encoder,
@@ -321,9 +413,100 @@ impl BartModel {
             generation_mode,
             pad_token_id,
             embeddings,
+            init_std: config.init_std,
+        }
+    }
+
+    /// Build a new `BartModel` without decoder. The decoder variables are not created in the variable store,
+    /// halving the memory footprint for encoder-only usage. Weights loaded with `VarStore::load` from a full
+    /// checkpoint are then only materialized for the encoder and embeddings. The decoder can be added later using `load_decoder`.
+    ///
+    /// # Arguments
+    ///
+    /// * `p` - Variable store path for the root of the BART model
+    /// * `config` - `BartConfig` object defining the model architecture
+    /// * `generation_mode` - flag indicating if the model should run in generation mode once the decoder is loaded
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use rust_bert::bart::{BartConfig, BartModel};
+    /// use rust_bert::Config;
+    /// use std::path::Path;
+    /// use tch::{nn, Device};
+    ///
+    /// let config_path = Path::new("path/to/config.json");
+    /// let device = Device::Cpu;
+    /// let p = nn::VarStore::new(device);
+    /// let config = BartConfig::from_file(config_path);
+    /// let bart: BartModel = BartModel::new_encoder_only(&p.root() / "bart", &config, true);
+    /// ```
+    pub fn new_encoder_only<'p, P>(p: P, config: &BartConfig, generation_mode: bool) -> BartModel
+    where
+        P: Borrow<nn::Path<'p>>,
+    {
+        let p = p.borrow();
+
+        let pad_token_id = config.pad_token_id.unwrap_or(1);
+        let embedding_config = EmbeddingConfig {
+            padding_idx: pad_token_id,
+            ..Default::default()
+        };
+        let embeddings: nn::Embedding = embedding(
+            p / "shared",
+            config.vocab_size,
+            config.d_model,
+            embedding_config,
+        );
+
+        let encoder = BartEncoder::new(p / "encoder", config);
+
+        BartModel {
+            encoder,
+            decoder: None,
+            generation_mode,
+            pad_token_id,
+            embeddings,
+            init_std: config.init_std,
         }
     }
 
+    /// Creates the decoder of a model built with `new_encoder_only`. This has no effect if the decoder is already loaded.
+    /// The decoder variables are created with their default initialization: the weights need to be loaded
+    /// into the variable store (e.g. using `VarStore::load`) after calling this method.
+    ///
+    /// # Arguments
+    ///
+    /// * `p` - Variable store path for the root of the BART model (should match the path used for the model creation)
+    /// * `config` - `BartConfig` object defining the model architecture
+    pub fn load_decoder<'p, P>(&mut self, p: P, config: &BartConfig)
+    where
+        P: Borrow<nn::Path<'p>>,
+    {
+        if self.decoder.is_none() {
+            self.decoder = Some(BartDecoder::new(
+                p.borrow() / "decoder",
+                config,
+                self.generation_mode,
+            ));
+        }
+    }
+
+    /// Returns `true` if the decoder of the model is loaded
+    pub fn has_decoder(&self) -> bool {
+        self.decoder.is_some()
+    }
+
+    /// Returns the adapters registered on the encoder, allowing to add, remove or switch the active adapter
+    pub fn encoder_adapters_mut(&mut self) -> &mut Adapters {
+        self.encoder.adapters_mut()
+    }
+
+    /// Returns the adapters registered on the decoder, or `None` if the decoder is not loaded
+    pub fn decoder_adapters_mut(&mut self) -> Option<&mut Adapters> {
+        self.decoder.as_mut().map(|decoder| decoder.adapters_mut())
+    }
+
     /// Forward pass through the model
     ///
     /// # Arguments
@@ -346,6 +529,7 @@ impl BartModel {
     ///   - `all_encoder_attentions` - `Option<Vec<Tensor>>` of length *num_encoder_layers* with shape (*batch size*, *source_sequence_length*, *hidden_size*)
     ///   - `all_decoder_hidden_states` - `Option<Vec<Tensor>>` of length *num_decoder_layers* with shape (*batch size*, *target_sequence_length*, *hidden_size*)
     ///   - `all_decoder_attentions` - `Option<Vec<Tensor>>` of length *num_decoder_layers* with shape (*batch size*, *target_sequence_length*, *hidden_size*)
+    ///   - `all_cross_attentions` - `Option<Vec<Tensor>>` of length *num_decoder_layers* with shape (*batch size*, *num_heads*, *target_sequence_length*, *source_sequence_length*)
     ///
     /// # Example
     ///
@@ -430,35 +614,52 @@ impl BartModel {
 
         let encoder_output = encoder_output.unwrap_or_else(|| calc_hidden_states.as_ref().unwrap());
 
-        let decoder_output = self.decoder.forward_t(
-            &decoder_input_ids,
-            &encoder_output,
-            attention_mask,
-            decoder_padding_mask.as_ref(),
-            causal_mask.as_ref(),
-            &self.embeddings,
-            layer_states,
-            train,
-        );
+        let decoder_output = self
+            .decoder
+            .as_ref()
+            .expect("The BART decoder must be loaded (see `load_decoder`)")
+            .forward_t(
+                &decoder_input_ids,
+                &encoder_output,
+                attention_mask,
+                decoder_padding_mask.as_ref(),
+                causal_mask.as_ref(),
+                &self.embeddings,
+                layer_states,
+                train,
+            );
         BartModelOutput {
             decoder_output: decoder_output.hidden_state,
             encoder_hidden_state: calc_hidden_states,
             cache: decoder_output.next_decoder_cache,
             all_decoder_hidden_states: decoder_output.all_hidden_states,
             all_decoder_attentions: decoder_output.all_attentions,
+            all_cross_attentions: decoder_output.all_cross_attentions,
             all_encoder_hidden_states,
             all_encoder_attentions,
         }
     }
+
+    /// Resizes the shared embeddings matrix of the encoder and decoder, e.g. after adding domain-specific tokens to
+    /// the vocabulary. The embeddings of the existing tokens are kept and the new embeddings are initialized from a
+    /// normal distribution with the standard deviation `init_std` of the configuration.
+    ///
+    /// # Arguments
+    ///
+    /// * `new_num_tokens` - new size of the vocabulary (smaller sizes remove the last tokens of the vocabulary)
+    pub fn resize_token_embeddings(&mut self, new_num_tokens: i64) -> Result<(), RustBertError> {
+        resize_token_dimension(&mut self.embeddings.ws, new_num_tokens, Some(self.init_std))
+    }
 }
 
 /// # BART Model for conditional generation
 /// BART model with a vocabulary decoding head
 /// It is made of the following blocks:
 /// - `base_model`: `BartModel` Base BART model
-/// - `linear`: Linear layer without bias tied to the weights of the token id embeddings
+/// - `lm_head`: `LMHead` language model head, tied by default to the weights of the token id embeddings
 pub struct BartForConditionalGeneration {
     base_model: BartModel,
+    lm_head: LMHead,
 }
 
 impl BartForConditionalGeneration {
@@ -495,7 +696,78 @@ impl BartForConditionalGeneration {
         P: Borrow<nn::Path<'p>>,
     {
         let base_model = BartModel::new(p.borrow() / "model", config, generation_mode);
-        BartForConditionalGeneration { base_model }
+        BartForConditionalGeneration {
+            base_model,
+            lm_head: LMHead::Tied,
+        }
+    }
+
+    /// Build a new `BartForConditionalGeneration` without decoder (see `BartModel::new_encoder_only`).
+    /// The model can be used for encoding but the decoder needs to be loaded with `load_decoder` before generating.
+    ///
+    /// # Arguments
+    ///
+    /// * `p` - Variable store path for the root of the BART model
+    /// * `config` - `BartConfig` object defining the model architecture
+    /// * `generation_mode` - flag indicating if the model should run in generation mode once the decoder is loaded
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use rust_bert::bart::{BartConfig, BartForConditionalGeneration};
+    /// use rust_bert::Config;
+    /// use std::path::Path;
+    /// use tch::{nn, Device};
+    ///
+    /// let config_path = Path::new("path/to/config.json");
+    /// let weights_path = Path::new("path/to/model.ot");
+    /// let device = Device::Cpu;
+    /// let mut vs = nn::VarStore::new(device);
+    /// let config = BartConfig::from_file(config_path);
+    /// let mut bart = BartForConditionalGeneration::new_encoder_only(&vs.root(), &config, true);
+    /// vs.load(weights_path)?;
+    /// // ... encoder-only usage ...
+    /// bart.load_decoder(&vs.root(), &config);
+    /// vs.load(weights_path)?;
+    /// # Ok::<(), tch::TchError>(())
+    /// ```
+    pub fn new_encoder_only<'p, P>(
+        p: P,
+        config: &BartConfig,
+        generation_mode: bool,
+    ) -> BartForConditionalGeneration
+    where
+        P: Borrow<nn::Path<'p>>,
+    {
+        let base_model = BartModel::new_encoder_only(p.borrow() / "model", config, generation_mode);
+        BartForConditionalGeneration {
+            base_model,
+            lm_head: LMHead::Tied,
+        }
+    }
+
+    /// Creates the decoder of a model built with `new_encoder_only` (see `BartModel::load_decoder`).
+    /// The weights need to be loaded into the variable store after calling this method.
+    pub fn load_decoder<'p, P>(&mut self, p: P, config: &BartConfig)
+    where
+        P: Borrow<nn::Path<'p>>,
+    {
+        self.base_model.load_decoder(p.borrow() / "model", config);
+    }
+
+    /// Returns `true` if the decoder of the model is loaded
+    pub fn has_decoder(&self) -> bool {
+        self.base_model.has_decoder()
+    }
+
+    /// Returns the adapters registered on the encoder (see `BartModel::encoder_adapters_mut`)
+    pub fn encoder_adapters_mut(&mut self) -> &mut Adapters {
+        self.base_model.encoder_adapters_mut()
+    }
+
+    /// Returns the adapters registered on the decoder (see `BartModel::decoder_adapters_mut`)
+    pub fn decoder_adapters_mut(&mut self) -> Option<&mut Adapters> {
+        self.base_model.decoder_adapters_mut()
     }
 
     /// Forward pass through the model
@@ -520,6 +792,7 @@ impl BartForConditionalGeneration {
     ///   - `all_encoder_attentions` - `Option<Vec<Tensor>>` of length *num_encoder_layers* with shape (*batch size*, *source_sequence_length*, *hidden_size*)
     ///   - `all_decoder_hidden_states` - `Option<Vec<Tensor>>` of length *num_decoder_layers* with shape (*batch size*, *target_sequence_length*, *hidden_size*)
     ///   - `all_decoder_attentions` - `Option<Vec<Tensor>>` of length *num_decoder_layers* with shape (*batch size*, *target_sequence_length*, *hidden_size*)
+    ///   - `all_cross_attentions` - `Option<Vec<Tensor>>` of length *num_decoder_layers* with shape (*batch size*, *num_heads*, *target_sequence_length*, *source_sequence_length*)
     ///
     /// # Example
     ///
@@ -572,9 +845,10 @@ impl BartForConditionalGeneration {
             train,
         );
 
-        let lm_logits = base_model_output
-            .decoder_output
-            .linear::<Tensor>(&self.base_model.embeddings.ws, None);
+        let lm_logits = self.lm_head.forward(
+            &base_model_output.decoder_output,
+            &self.base_model.embeddings.ws,
+        );
         BartModelOutput {
             decoder_output: lm_logits,
             ..base_model_output
@@ -592,6 +866,53 @@ impl BartForConditionalGeneration {
             )
             .hidden_state
     }
+
+    /// Resizes the shared embeddings matrix and, if untied, the language model head of the model to `new_num_tokens`
+    /// tokens, keeping the weights of the existing tokens.
+    pub fn resize_token_embeddings(&mut self, new_num_tokens: i64) -> Result<(), RustBertError> {
+        self.base_model.resize_token_embeddings(new_num_tokens)?;
+        self.lm_head
+            .resize(new_num_tokens, self.base_model.init_std)
+    }
+
+    /// Returns the language model head of the model
+    pub fn lm_head(&self) -> &LMHead {
+        &self.lm_head
+    }
+
+    /// Ties the language model head to the shared embeddings of the encoder and decoder (default for BART checkpoints)
+    pub fn tie_lm_head(&mut self) {
+        self.lm_head = LMHead::Tied;
+    }
+
+    /// Unties the language model head from the shared embeddings, creating a linear layer initialized with a copy of
+    /// the embeddings, so that the head can be fine-tuned independently of the input embeddings. The variables of the
+    /// head are created under the path provided (`{p}.weight`). This has no effect if the head is not tied.
+    ///
+    /// # Arguments
+    ///
+    /// * `p` - Variable store path for the new language model head
+    pub fn untie_lm_head<'p, P>(&mut self, p: P) -> Result<(), RustBertError>
+    where
+        P: Borrow<nn::Path<'p>>,
+    {
+        if self.lm_head.is_tied() {
+            self.lm_head = LMHead::untied_from(p, &self.base_model.embeddings.ws)?;
+        }
+        Ok(())
+    }
+
+    /// Replaces the language model head with the linear layer provided, of shape (*output_vocabulary_size*, *d_model*),
+    /// for example a projection on a restricted vocabulary. The generated ids are then positions in the output vocabulary.
+    ///
+    /// # Arguments
+    ///
+    /// * `lm_head` - Linear layer projecting the decoder output on the output vocabulary
+    pub fn set_lm_head(&mut self, lm_head: nn::Linear) -> Result<(), RustBertError> {
+        LMHead::check_linear(&lm_head, self.base_model.embeddings.ws.size()[1])?;
+        self.lm_head = LMHead::Linear(lm_head);
+        Ok(())
+    }
 }
 
 pub struct BartClassificationHead {
@@ -715,6 +1036,7 @@ impl BartForSequenceClassification {
     ///   - `all_encoder_attentions` - `Option<Vec<Tensor>>` of length *num_encoder_layers* with shape (*batch size*, *source_sequence_length*, *hidden_size*)
     ///   - `all_decoder_hidden_states` - `Option<Vec<Tensor>>` of length *num_decoder_layers* with shape (*batch size*, *target_sequence_length*, *hidden_size*)
     ///   - `all_decoder_attentions` - `Option<Vec<Tensor>>` of length *num_decoder_layers* with shape (*batch size*, *target_sequence_length*, *hidden_size*)
+    ///   - `all_cross_attentions` - `Option<Vec<Tensor>>` of length *num_decoder_layers* with shape (*batch size*, *num_heads*, *target_sequence_length*, *source_sequence_length*)
     ///
     /// # Example
     ///
@@ -789,10 +1111,21 @@ impl BartForSequenceClassification {
             cache: None,
             all_decoder_hidden_states: base_model_output.all_decoder_hidden_states,
             all_decoder_attentions: base_model_output.all_decoder_attentions,
+            all_cross_attentions: base_model_output.all_cross_attentions,
             all_encoder_hidden_states: base_model_output.all_encoder_hidden_states,
             all_encoder_attentions: base_model_output.all_encoder_attentions,
         }
     }
+
+    /// Returns the adapters registered on the encoder (see `BartModel::encoder_adapters_mut`)
+    pub fn encoder_adapters_mut(&mut self) -> &mut Adapters {
+        self.base_model.encoder_adapters_mut()
+    }
+
+    /// Returns the adapters registered on the decoder (see `BartModel::decoder_adapters_mut`)
+    pub fn decoder_adapters_mut(&mut self) -> Option<&mut Adapters> {
+        self.base_model.decoder_adapters_mut()
+    }
 }
 
 impl LMHeadModel for BartForConditionalGeneration {
@@ -862,6 +1195,11 @@ impl LMHeadModel for BartForConditionalGeneration {
         decoder_input_ids: &Option<Tensor>,
         train: bool,
     ) -> Result<LMModelOutput, RustBertError> {
+        if !self.base_model.has_decoder() {
+            return Err(RustBertError::ValueError(
+                "The BART decoder must be loaded (see `load_decoder`) before generating".into(),
+            ));
+        }
         let base_model_output = match cache {
             Cache::BARTCache(cached_layer_states) => self.base_model.forward_t(
                 input_ids.as_ref(),
@@ -889,9 +1227,10 @@ impl LMHeadModel for BartForConditionalGeneration {
             }
         };
 
-        let lm_logits = base_model_output
-            .decoder_output
-            .linear::<Tensor>(&self.base_model.embeddings.ws, None);
+        let lm_logits = self.lm_head.forward(
+            &base_model_output.decoder_output,
+            &self.base_model.embeddings.ws,
+        );
         Ok(LMModelOutput {
             lm_logits,
             cache: Cache::BARTCache(base_model_output.cache),
@@ -899,6 +1238,58 @@ impl LMHeadModel for BartForConditionalGeneration {
     }
 }
 
+impl EncoderOnlyModel for BartForConditionalGeneration {
+    fn encode(&self, input_ids: &Tensor, attention_mask: Option<&Tensor>) -> Tensor {
+        BartForConditionalGeneration::encode(self, input_ids, attention_mask)
+    }
+}
+
+impl Seq2SeqGenerationModel for BartForConditionalGeneration {
+    fn decode_step(
+        &self,
+        decoder_input_ids: &Tensor,
+        encoder_outputs: &Tensor,
+        attention_mask: Option<&Tensor>,
+        cache: Cache,
+    ) -> Result<LMModelOutput, RustBertError> {
+        let cache = match cache {
+            Cache::None => Cache::BARTCache(None),
+            cache => cache,
+        };
+        LMHeadModel::forward_t(
+            self,
+            &None,
+            cache,
+            &attention_mask.map(|mask| mask.shallow_clone()),
+            &None,
+            &None,
+            &None,
+            Some(encoder_outputs),
+            &Some(decoder_input_ids.shallow_clone()),
+            false,
+        )
+    }
+
+    fn reorder_cache(&self, cache: &mut Cache, beam_indices: &Tensor) {
+        match cache {
+            Cache::BARTCache(Some(old_cache)) => {
+                for (self_layer_state, encoder_layer_state) in old_cache.iter_mut() {
+                    if let Some(self_layer_state) = self_layer_state {
+                        self_layer_state.reorder_cache(beam_indices)
+                    };
+                    if let Some(encoder_layer_state) = encoder_layer_state {
+                        encoder_layer_state.reorder_cache(beam_indices)
+                    };
+                }
+            }
+            Cache::BARTCache(None) | Cache::None => {}
+            _ => {
+                panic!("Invalid cache for BART model");
+            }
+        };
+    }
+}
+
 /// Container holding a BART model output. The decoder output may hold the hidden state of
 /// the last layer of the decoder, or may hold logits for a custom head module after the
 /// decoder (e.g. for classification or language modeling tasks)
@@ -914,12 +1305,28 @@ pub struct BartModelOutput {
     pub all_decoder_hidden_states: Option<Vec<Tensor>>,
     /// Attention weights for all layers of the decoder
     pub all_decoder_attentions: Option<Vec<Tensor>>,
+    /// Cross-attention weights of the decoder over the encoder positions for all layers of the decoder
+    pub all_cross_attentions: Option<Vec<Tensor>>,
     /// Hidden states for all layers of the encoder
     pub all_encoder_hidden_states: Option<Vec<Tensor>>,
     /// Attention weights for all layers of the encoder
     pub all_encoder_attentions: Option<Vec<Tensor>>,
 }
 
+impl ModelOutput for BartModelOutput {
+    fn hidden_states(&self) -> Option<Vec<&Tensor>> {
+        tensor_refs(&self.all_decoder_hidden_states)
+    }
+
+    fn attentions(&self) -> Option<Vec<&Tensor>> {
+        tensor_refs(&self.all_decoder_attentions)
+    }
+
+    fn logits(&self) -> Option<&Tensor> {
+        Some(&self.decoder_output)
+    }
+}
+
 #[cfg(test)]
 mod test {
     use tch::Device;
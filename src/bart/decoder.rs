@@ -16,6 +16,7 @@ use crate::bart::embeddings::{
 };
 use crate::bart::BartConfig;
 use crate::common::activations::Activation;
+use crate::common::adapters::{AdapterLayer, Adapters};
 use crate::common::dropout::Dropout;
 use crate::{
     bart::attention::{LayerState, SelfAttention},
@@ -124,10 +125,12 @@ impl DecoderLayer {
         causal_mask: Option<&Tensor>,
         decoder_padding_mask: Option<&Tensor>,
         layer_states: (Option<LayerState>, Option<LayerState>),
+        adapter: Option<&AdapterLayer>,
         train: bool,
     ) -> (
         Tensor,
         Option<Tensor>,
+        Option<Tensor>,
         (Option<LayerState>, Option<LayerState>),
     ) {
         let (output, attention_weights, new_self_layer_states) = self.self_attention.forward_t(
@@ -138,16 +141,22 @@ impl DecoderLayer {
             layer_states.0,
             train,
         );
-        let output: Tensor = output.apply_t(&self.dropout, train) + x;
+        let output = output.apply_t(&self.dropout, train);
+        let output = match adapter {
+            Some(adapter) => adapter.forward_attention(output),
+            None => output,
+        };
+        let output: Tensor = output + x;
         let output = output.apply(&self.self_attention_layer_norm);
-        let (output1, _, new_encoder_layer_states) = self.encoder_attention.forward_t(
-            &output,
-            Some(encoder_hidden_states),
-            encoder_attn_mask,
-            None,
-            layer_states.1,
-            train,
-        );
+        let (output1, cross_attention_weights, new_encoder_layer_states) =
+            self.encoder_attention.forward_t(
+                &output,
+                Some(encoder_hidden_states),
+                encoder_attn_mask,
+                None,
+                layer_states.1,
+                train,
+            );
         let output1: Tensor = output1.apply_t(&self.dropout, train) + output;
         let output1 = output1.apply(&self.encoder_attention_layer_norm);
         let output2 = (self.activation.get_fn())(&output1.apply(&self.fc1));
@@ -155,10 +164,15 @@ impl DecoderLayer {
             .apply_t(&self.activation_dropout, train)
             .apply(&self.fc2)
             .apply_t(&self.dropout, train);
+        let output2 = match adapter {
+            Some(adapter) => adapter.forward_output(output2),
+            None => output2,
+        };
         let output2: Tensor = output2 + output1;
         (
             output2.apply(&self.final_layer_norm),
             attention_weights,
+            cross_attention_weights,
             (new_self_layer_states, new_encoder_layer_states),
         )
     }
@@ -174,6 +188,7 @@ pub struct BartDecoder {
     output_past: bool,
     generation_mode: bool,
     scale_embedding: f64,
+    adapters: Adapters,
 }
 
 impl BartDecoder {
@@ -247,9 +262,14 @@ impl BartDecoder {
             output_past,
             generation_mode,
             scale_embedding,
+            adapters: Adapters::new(config.d_model, config.decoder_layers),
         }
     }
 
+    pub fn adapters_mut(&mut self) -> &mut Adapters {
+        &mut self.adapters
+    }
+
     pub fn forward_t(
         &self,
         input_ids: &Tensor,
@@ -294,6 +314,11 @@ impl BartDecoder {
         } else {
             None
         };
+        let mut all_cross_attentions: Option<Vec<Tensor>> = if self.output_attentions {
+            Some(Vec::with_capacity(self.layers.len()))
+        } else {
+            None
+        };
         let mut next_decoder_cache: Option<Vec<(Option<LayerState>, Option<LayerState>)>> =
             if self.output_past {
                 if old_layer_states.is_some() {
@@ -306,6 +331,7 @@ impl BartDecoder {
             };
         let encoder_hidden_states = encoder_hidden_states.transpose(0, 1);
         let mut attention_weights: Option<Tensor>;
+        let mut cross_attention_weights: Option<Tensor>;
 
         for (layer_idx, layer) in self.layers.iter().enumerate() {
             let layer_state = match &next_decoder_cache {
@@ -319,18 +345,23 @@ impl BartDecoder {
                 decoder_causal_mask,
                 decoder_padding_mask,
                 layer_state,
+                self.adapters.active_layer(layer_idx),
                 train,
             );
             hidden_state = temp.0;
             attention_weights = temp.1;
+            cross_attention_weights = temp.2;
             if let Some(hidden_states) = all_hidden_states.borrow_mut() {
                 hidden_states.push(hidden_state.as_ref().copy().transpose(0, 1));
             };
             if let Some(attentions) = all_attentions.borrow_mut() {
                 attentions.push(attention_weights.as_ref().unwrap().copy());
             };
+            if let Some(cross_attentions) = all_cross_attentions.borrow_mut() {
+                cross_attentions.push(cross_attention_weights.as_ref().unwrap().copy());
+            };
             if let Some(value) = &mut next_decoder_cache {
-                value[layer_idx] = temp.2
+                value[layer_idx] = temp.3
             };
         }
 
@@ -340,6 +371,7 @@ impl BartDecoder {
             next_decoder_cache,
             all_hidden_states,
             all_attentions,
+            all_cross_attentions,
         }
     }
 }
@@ -356,4 +388,6 @@ pub struct BartDecoderOutput {
     pub all_hidden_states: Option<Vec<Tensor>>,
     /// Attention weights for all intermediate layers
     pub all_attentions: Option<Vec<Tensor>>,
+    /// Cross-attention weights over the encoder positions for all intermediate layers
+    pub all_cross_attentions: Option<Vec<Tensor>>,
 }
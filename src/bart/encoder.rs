@@ -17,6 +17,7 @@ use crate::bart::embeddings::{
 };
 use crate::bart::BartConfig;
 use crate::common::activations::{Activation, TensorFunction};
+use crate::common::adapters::{AdapterLayer, Adapters};
 use crate::common::dropout::Dropout;
 use std::borrow::{Borrow, BorrowMut};
 use tch::kind::Kind::Bool;
@@ -101,12 +102,18 @@ impl EncoderLayer {
         &self,
         x: &Tensor,
         encoder_padding_mask: Option<&Tensor>,
+        adapter: Option<&AdapterLayer>,
         train: bool,
     ) -> (Tensor, Option<Tensor>) {
         let (output, attention_weights, _) =
             self.self_attention
                 .forward_t(x, None, encoder_padding_mask, None, None, train);
-        let output: Tensor = output.apply_t(&self.dropout, train) + x;
+        let output = output.apply_t(&self.dropout, train);
+        let output = match adapter {
+            Some(adapter) => adapter.forward_attention(output),
+            None => output,
+        };
+        let output: Tensor = output + x;
         let output = output.apply(&self.self_attention_layer_norm);
 
         let residual = output.copy();
@@ -115,6 +122,10 @@ impl EncoderLayer {
             .apply_t(&self.activation_dropout, train)
             .apply(&self.fc2)
             .apply_t(&self.dropout, train);
+        let output = match adapter {
+            Some(adapter) => adapter.forward_output(output),
+            None => output,
+        };
         let output: Tensor = output + residual;
         (output.apply(&self.final_layer_norm), attention_weights)
     }
@@ -128,6 +139,7 @@ pub struct BartEncoder {
     output_attentions: bool,
     output_hidden_states: bool,
     scale_embedding: f64,
+    adapters: Adapters,
 }
 
 impl BartEncoder {
@@ -198,9 +210,14 @@ impl BartEncoder {
             output_attentions,
             output_hidden_states,
             scale_embedding,
+            adapters: Adapters::new(config.d_model, config.encoder_layers),
         }
     }
 
+    pub fn adapters_mut(&mut self) -> &mut Adapters {
+        &mut self.adapters
+    }
+
     pub fn forward_t(
         &self,
         input_ids: &Tensor,
@@ -236,12 +253,17 @@ impl BartEncoder {
         let mut hidden_state = x.copy();
         let mut attention_weights: Option<Tensor>;
 
-        for layer in &self.layers {
+        for (layer_index, layer) in self.layers.iter().enumerate() {
             if let Some(hidden_states) = all_hidden_states.borrow_mut() {
                 hidden_states.push(hidden_state.as_ref().copy().transpose(0, 1));
             };
 
-            let temp = layer.forward_t(&hidden_state, attention_mask.as_ref(), train);
+            let temp = layer.forward_t(
+                &hidden_state,
+                attention_mask.as_ref(),
+                self.adapters.active_layer(layer_index),
+                train,
+            );
             hidden_state = temp.0;
             attention_weights = temp.1;
             if let Some(attentions) = all_attentions.borrow_mut() {
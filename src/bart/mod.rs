@@ -56,6 +56,12 @@
 //! # Ok(())
 //! # }
 //! ```
+//!
+//! # Fine-tuning
+//!
+//! When training with teacher forcing, the decoder inputs are the target labels shifted one position to the right,
+//! starting with the decoder start token. `bart::shift_tokens_right` builds them from the labels (replacing the
+//! positions ignored by the loss with padding), to be passed as `decoder_input_ids` to `BartForConditionalGeneration::forward_t`.
 
 mod attention;
 mod bart_model;
@@ -65,6 +71,7 @@ mod encoder;
 
 pub use attention::LayerState;
 pub use bart_model::{
-    BartConfig, BartConfigResources, BartForConditionalGeneration, BartForSequenceClassification,
-    BartMergesResources, BartModel, BartModelOutput, BartModelResources, BartVocabResources,
+    shift_tokens_right, BartConfig, BartConfigResources, BartForConditionalGeneration,
+    BartForSequenceClassification, BartMergesResources, BartModel, BartModelOutput,
+    BartModelResources, BartVocabResources,
 };
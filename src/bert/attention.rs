@@ -13,6 +13,7 @@
 
 use crate::bert::bert_model::BertConfig;
 use crate::common::activations::TensorFunction;
+use crate::common::adapters::AdapterLayer;
 use crate::common::dropout::Dropout;
 use std::borrow::Borrow;
 use tch::kind::Kind::Float;
@@ -170,12 +171,21 @@ impl BertSelfOutput {
         }
     }
 
-    pub fn forward_t(&self, hidden_states: &Tensor, input_tensor: &Tensor, train: bool) -> Tensor {
-        let hidden_states: Tensor = input_tensor
-            + hidden_states
-                .apply(&self.linear)
-                .apply_t(&self.dropout, train);
-        hidden_states.apply(&self.layer_norm)
+    pub fn forward_t(
+        &self,
+        hidden_states: &Tensor,
+        input_tensor: &Tensor,
+        adapter: Option<&AdapterLayer>,
+        train: bool,
+    ) -> Tensor {
+        let hidden_states = hidden_states
+            .apply(&self.linear)
+            .apply_t(&self.dropout, train);
+        let hidden_states = match adapter {
+            Some(adapter) => adapter.forward_attention(hidden_states),
+            None => hidden_states,
+        };
+        (input_tensor + hidden_states).apply(&self.layer_norm)
     }
 }
 
@@ -203,6 +213,7 @@ impl BertAttention {
         mask: &Option<Tensor>,
         encoder_hidden_states: &Option<Tensor>,
         encoder_mask: &Option<Tensor>,
+        adapter: Option<&AdapterLayer>,
         train: bool,
     ) -> (Tensor, Option<Tensor>) {
         let (self_output, attention_weights) = self._self.forward_t(
@@ -213,7 +224,9 @@ impl BertAttention {
             train,
         );
 
-        let self_output = self.output.forward_t(&self_output, hidden_states, train);
+        let self_output = self
+            .output
+            .forward_t(&self_output, hidden_states, adapter, train);
         (self_output, attention_weights)
     }
 }
@@ -279,9 +292,18 @@ impl BertOutput {
         }
     }
 
-    pub fn forward_t(&self, hidden_states: &Tensor, input_tensor: &Tensor, train: bool) -> Tensor {
-        let hidden_states: Tensor =
-            input_tensor + hidden_states.apply(&self.lin).apply_t(&self.dropout, train);
-        hidden_states.apply(&self.layer_norm)
+    pub fn forward_t(
+        &self,
+        hidden_states: &Tensor,
+        input_tensor: &Tensor,
+        adapter: Option<&AdapterLayer>,
+        train: bool,
+    ) -> Tensor {
+        let hidden_states = hidden_states.apply(&self.lin).apply_t(&self.dropout, train);
+        let hidden_states = match adapter {
+            Some(adapter) => adapter.forward_output(hidden_states),
+            None => hidden_states,
+        };
+        (input_tensor + hidden_states).apply(&self.layer_norm)
     }
 }
@@ -13,14 +13,24 @@
 
 use crate::bert::encoder::{BertEncoder, BertPooler};
 use crate::common::activations::Activation;
+use crate::common::adapters::Adapters;
+use crate::common::classification_head::{
+    ClassificationHead, ClassificationHeadConfig, ClassifierOption,
+};
+use crate::common::config::{check_divisible, deserialize_id2label};
 use crate::common::dropout::Dropout;
+use crate::common::hooks::ForwardHooks;
 use crate::common::linear::{linear_no_bias, LinearNoBias};
+use crate::common::model_output::{tensor_refs, ModelOutput};
+use crate::common::profiling;
+use crate::common::weights::resize_token_dimension;
 use crate::{
     bert::embeddings::{BertEmbedding, BertEmbeddings},
     common::activations::TensorFunction,
 };
 use crate::{Config, RustBertError};
 use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
 use std::borrow::Borrow;
 use std::collections::HashMap;
 use tch::kind::Kind::Float;
@@ -108,11 +118,28 @@ pub struct BertConfig {
     pub output_attentions: Option<bool>,
     pub output_hidden_states: Option<bool>,
     pub is_decoder: Option<bool>,
+    #[serde(default, deserialize_with = "deserialize_id2label")]
     pub id2label: Option<HashMap<i64, String>>,
     pub label2id: Option<HashMap<String, i64>>,
+    /// Additional fields of the configuration file not used by the model
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
 }
 
-impl Config<BertConfig> for BertConfig {}
+impl Config<BertConfig> for BertConfig {
+    fn extra_fields(&self) -> Option<&Map<String, Value>> {
+        Some(&self.extra)
+    }
+
+    fn validate(&self) -> Result<(), RustBertError> {
+        check_divisible(
+            "hidden_size",
+            self.hidden_size,
+            "num_attention_heads",
+            self.num_attention_heads,
+        )
+    }
+}
 
 /// # BERT Base model
 /// Base architecture for BERT models. Task-specific models will be built from this common base model
@@ -366,6 +393,13 @@ impl<T: BertEmbedding> BertModel<T> {
                 None
             };
 
+        let embeddings_scope = profiling::scope(
+            || "embeddings".to_string(),
+            &input_ids
+                .iter()
+                .chain(input_embeds.iter())
+                .collect::<Vec<&Tensor>>(),
+        );
         let embedding_output = self.embeddings.forward_t(
             input_ids,
             token_type_ids,
@@ -373,6 +407,10 @@ impl<T: BertEmbedding> BertModel<T> {
             input_embeds,
             train,
         )?;
+        drop(embeddings_scope);
+        self.encoder
+            .hooks()
+            .run(|| "embeddings".to_string(), &embedding_output);
 
         let encoder_output = self.encoder.forward_t(
             &embedding_output,
@@ -382,10 +420,15 @@ impl<T: BertEmbedding> BertModel<T> {
             train,
         );
 
-        let pooled_output = self
-            .pooler
-            .as_ref()
-            .map(|pooler| pooler.forward(&encoder_output.hidden_state));
+        let pooled_output = self.pooler.as_ref().map(|pooler| {
+            let _scope = profiling::scope(|| "pooler".to_string(), &[&encoder_output.hidden_state]);
+            pooler.forward(&encoder_output.hidden_state)
+        });
+        if let Some(pooled_output) = &pooled_output {
+            self.encoder
+                .hooks()
+                .run(|| "pooler".to_string(), pooled_output);
+        }
 
         Ok(BertModelOutput {
             hidden_state: encoder_output.hidden_state,
@@ -394,6 +437,50 @@ impl<T: BertEmbedding> BertModel<T> {
             all_attentions: encoder_output.all_attentions,
         })
     }
+
+    /// Returns the adapters registered on the encoder (see `adapters`)
+    pub fn adapters(&self) -> &Adapters {
+        self.encoder.adapters()
+    }
+
+    /// Returns the adapters registered on the encoder, allowing to add, remove or switch the active adapter
+    pub fn adapters_mut(&mut self) -> &mut Adapters {
+        self.encoder.adapters_mut()
+    }
+
+    /// Returns the forward hooks of the model, allowing to register or remove hooks (see `hooks`)
+    pub fn hooks_mut(&mut self) -> &mut ForwardHooks {
+        self.encoder.hooks_mut()
+    }
+
+    /// Resizes the word embeddings matrix of the model, e.g. after adding domain-specific tokens to the vocabulary.
+    /// The embeddings of the existing tokens are kept and the new embeddings are initialized from a normal distribution
+    /// with the standard deviation `initializer_range` of the configuration. The model should be fine-tuned afterwards.
+    ///
+    /// # Arguments
+    ///
+    /// * `new_num_tokens` - new size of the vocabulary (smaller sizes remove the last tokens of the vocabulary)
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// # fn main() -> anyhow::Result<()> {
+    /// use rust_bert::bert::{BertConfig, BertEmbeddings, BertModel};
+    /// use rust_bert::Config;
+    /// use std::path::Path;
+    /// use tch::{nn, Device};
+    ///
+    /// let mut vs = nn::VarStore::new(Device::Cpu);
+    /// let config = BertConfig::from_file(Path::new("path/to/config.json"));
+    /// let mut bert_model: BertModel<BertEmbeddings> = BertModel::new(&vs.root(), &config);
+    /// vs.load("path/to/model.ot")?;
+    /// bert_model.resize_token_embeddings(config.vocab_size + 10)?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn resize_token_embeddings(&mut self, new_num_tokens: i64) -> Result<(), RustBertError> {
+        self.embeddings.resize_word_embeddings(new_num_tokens)
+    }
 }
 
 pub struct BertPredictionHeadTransform {
@@ -439,6 +526,7 @@ pub struct BertLMPredictionHead {
     transform: BertPredictionHeadTransform,
     decoder: LinearNoBias,
     bias: Tensor,
+    initializer_range: f64,
 }
 
 impl BertLMPredictionHead {
@@ -460,12 +548,23 @@ impl BertLMPredictionHead {
             transform,
             decoder,
             bias,
+            initializer_range: config.initializer_range as f64,
         }
     }
 
     pub fn forward(&self, hidden_states: &Tensor) -> Tensor {
         self.transform.forward(&hidden_states).apply(&self.decoder) + &self.bias
     }
+
+    /// Resizes the output vocabulary of the prediction head (decoder weights and bias)
+    pub fn resize_token_embeddings(&mut self, new_num_tokens: i64) -> Result<(), RustBertError> {
+        resize_token_dimension(
+            &mut self.decoder.ws,
+            new_num_tokens,
+            Some(self.initializer_range),
+        )?;
+        resize_token_dimension(&mut self.bias, new_num_tokens, None)
+    }
 }
 
 /// # BERT for masked language model
@@ -597,17 +696,34 @@ impl BertForMaskedLM {
             all_attentions: base_model_output.all_attentions,
         }
     }
+
+    /// Returns the adapters registered on the base model, allowing to add, remove or switch the active adapter
+    pub fn adapters_mut(&mut self) -> &mut Adapters {
+        self.bert.adapters_mut()
+    }
+
+    /// Returns the forward hooks registered on the base model, allowing to register or remove hooks
+    pub fn hooks_mut(&mut self) -> &mut ForwardHooks {
+        self.bert.hooks_mut()
+    }
+
+    /// Resizes the word embeddings matrix and the language model head of the model to `new_num_tokens` tokens,
+    /// keeping the weights of the existing tokens.
+    pub fn resize_token_embeddings(&mut self, new_num_tokens: i64) -> Result<(), RustBertError> {
+        self.bert.resize_token_embeddings(new_num_tokens)?;
+        self.cls.resize_token_embeddings(new_num_tokens)
+    }
 }
 
 /// # BERT for sequence classification
 /// Base BERT model with a classifier head to perform sentence or document-level classification
 /// It is made of the following blocks:
 /// - `bert`: Base BertModel
-/// - `classifier`: BERT linear layer for classification
+/// - `classifier`: BERT linear layer for classification, or a `ClassificationHead` configured with `new_with_head`
 pub struct BertForSequenceClassification {
     bert: BertModel<BertEmbeddings>,
     dropout: Dropout,
-    classifier: nn::Linear,
+    classifier: ClassifierOption<nn::Linear>,
 }
 
 impl BertForSequenceClassification {
@@ -636,8 +752,33 @@ impl BertForSequenceClassification {
     where
         P: Borrow<nn::Path<'p>>,
     {
-        let p = p.borrow();
+        BertForSequenceClassification::build(p.borrow(), config, None)
+    }
 
+    /// Build a new `BertForSequenceClassification` with a custom classification head, for checkpoints fine-tuned with
+    /// a head different from the linear projection of the pooled output
+    ///
+    /// # Arguments
+    ///
+    /// * `p` - Variable store path for the root of the BertForSequenceClassification model
+    /// * `config` - `BertConfig` object defining the model architecture and number of classes
+    /// * `head_config` - `ClassificationHeadConfig` defining the classification head architecture
+    pub fn new_with_head<'p, P>(
+        p: P,
+        config: &BertConfig,
+        head_config: &ClassificationHeadConfig,
+    ) -> BertForSequenceClassification
+    where
+        P: Borrow<nn::Path<'p>>,
+    {
+        BertForSequenceClassification::build(p.borrow(), config, Some(head_config))
+    }
+
+    fn build(
+        p: &nn::Path,
+        config: &BertConfig,
+        head_config: Option<&ClassificationHeadConfig>,
+    ) -> BertForSequenceClassification {
         let bert = BertModel::new(p / "bert", config);
         let dropout = Dropout::new(config.hidden_dropout_prob);
         let num_labels = config
@@ -645,12 +786,21 @@ impl BertForSequenceClassification {
             .as_ref()
             .expect("num_labels not provided in configuration")
             .len() as i64;
-        let classifier = nn::linear(
-            p / "classifier",
-            config.hidden_size,
-            num_labels,
-            Default::default(),
-        );
+        let classifier = match head_config {
+            Some(head_config) => ClassifierOption::Custom(ClassificationHead::new(
+                p / "classifier",
+                config.hidden_size,
+                num_labels,
+                head_config,
+                config.hidden_dropout_prob,
+            )),
+            None => ClassifierOption::Default(nn::linear(
+                p / "classifier",
+                config.hidden_size,
+                num_labels,
+                Default::default(),
+            )),
+        };
 
         BertForSequenceClassification {
             bert,
@@ -717,6 +867,7 @@ impl BertForSequenceClassification {
         input_embeds: Option<Tensor>,
         train: bool,
     ) -> BertSequenceClassificationOutput {
+        let head_mask = mask.as_ref().map(Tensor::shallow_clone);
         let base_model_output = self
             .bert
             .forward_t(
@@ -731,17 +882,171 @@ impl BertForSequenceClassification {
             )
             .unwrap();
 
-        let logits = base_model_output
-            .pooled_output
-            .unwrap()
-            .apply_t(&self.dropout, train)
-            .apply(&self.classifier);
+        let logits = match &self.classifier {
+            ClassifierOption::Default(classifier) => base_model_output
+                .pooled_output
+                .unwrap()
+                .apply_t(&self.dropout, train)
+                .apply(classifier),
+            ClassifierOption::Custom(head) => {
+                head.forward_t(&base_model_output.hidden_state, head_mask.as_ref(), train)
+            }
+        };
+        BertSequenceClassificationOutput {
+            logits,
+            all_hidden_states: base_model_output.all_hidden_states,
+            all_attentions: base_model_output.all_attentions,
+        }
+    }
+
+    /// Returns the adapters registered on the base model, allowing to add, remove or switch the active adapter
+    pub fn adapters_mut(&mut self) -> &mut Adapters {
+        self.bert.adapters_mut()
+    }
+
+    /// Returns the forward hooks registered on the base model, allowing to register or remove hooks
+    pub fn hooks_mut(&mut self) -> &mut ForwardHooks {
+        self.bert.hooks_mut()
+    }
+}
+
+/// # BERT for next sentence prediction
+/// Base BERT model with the next sentence prediction head used during pre-training. The head predicts whether the
+/// second segment of a `[CLS] Sentence A [SEP] Sentence B [SEP]` input follows the first one.
+/// It is made of the following blocks:
+/// - `bert`: Base BertModel
+/// - `cls`: Linear layer projecting the pooled output on the two classes (index 0: *is next*, index 1: *random*)
+pub struct BertForNextSentencePrediction {
+    bert: BertModel<BertEmbeddings>,
+    cls: nn::Linear,
+}
+
+impl BertForNextSentencePrediction {
+    /// Build a new `BertForNextSentencePrediction`
+    ///
+    /// # Arguments
+    ///
+    /// * `p` - Variable store path for the root of the BertForNextSentencePrediction model
+    /// * `config` - `BertConfig` object defining the model architecture
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use rust_bert::bert::{BertConfig, BertForNextSentencePrediction};
+    /// use rust_bert::Config;
+    /// use std::path::Path;
+    /// use tch::{nn, Device};
+    ///
+    /// let config_path = Path::new("path/to/config.json");
+    /// let device = Device::Cpu;
+    /// let p = nn::VarStore::new(device);
+    /// let config = BertConfig::from_file(config_path);
+    /// let bert = BertForNextSentencePrediction::new(&p.root(), &config);
+    /// ```
+    pub fn new<'p, P>(p: P, config: &BertConfig) -> BertForNextSentencePrediction
+    where
+        P: Borrow<nn::Path<'p>>,
+    {
+        let p = p.borrow();
+
+        let bert = BertModel::new(p / "bert", config);
+        let cls = nn::linear(
+            &(p / "cls") / "seq_relationship",
+            config.hidden_size,
+            2,
+            Default::default(),
+        );
+
+        BertForNextSentencePrediction { bert, cls }
+    }
+
+    /// Forward pass through the model
+    ///
+    /// # Arguments
+    ///
+    /// * `input_ids` - Optional input tensor of shape (*batch size*, *sequence_length*). If None, pre-computed embeddings must be provided (see `input_embeds`)
+    /// * `mask` - Optional mask of shape (*batch size*, *sequence_length*). Masked position have value 0, non-masked value 1. If None set to 1
+    /// * `token_type_ids` -Optional segment id of shape (*batch size*, *sequence_length*). Convention is value of 0 for the first sentence (incl. *[SEP]*) and 1 for the second sentence. If None set to 0.
+    /// * `position_ids` - Optional position ids of shape (*batch size*, *sequence_length*). If None, will be incremented from 0.
+    /// * `input_embeds` - Optional pre-computed input embeddings of shape (*batch size*, *sequence_length*, *hidden_size*). If None, input ids must be provided (see `input_ids`)
+    /// * `train` - boolean flag to turn on/off the dropout layers in the model. Should be set to false for inference.
+    ///
+    /// # Returns
+    ///
+    /// * `BertSequenceClassificationOutput` containing:
+    ///   - `logits` - `Tensor` of shape (*batch size*, 2)
+    ///   - `all_hidden_states` - `Option<Vec<Tensor>>` of length *num_hidden_layers* with shape (*batch size*, *sequence_length*, *hidden_size*)
+    ///   - `all_attentions` - `Option<Vec<Tensor>>` of length *num_hidden_layers* with shape (*batch size*, *sequence_length*, *hidden_size*)
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// # use rust_bert::bert::{BertForNextSentencePrediction, BertConfig};
+    /// # use tch::{nn, Device, Tensor, no_grad};
+    /// # use rust_bert::Config;
+    /// # use std::path::Path;
+    /// # use tch::kind::Kind::Int64;
+    /// # let config_path = Path::new("path/to/config.json");
+    /// # let device = Device::Cpu;
+    /// # let vs = nn::VarStore::new(device);
+    /// # let config = BertConfig::from_file(config_path);
+    /// # let bert_model = BertForNextSentencePrediction::new(&vs.root(), &config);
+    /// let (batch_size, sequence_length) = (64, 128);
+    /// let input_tensor = Tensor::rand(&[batch_size, sequence_length], (Int64, device));
+    /// let mask = Tensor::zeros(&[batch_size, sequence_length], (Int64, device));
+    /// let token_type_ids = Tensor::zeros(&[batch_size, sequence_length], (Int64, device));
+    ///
+    /// let model_output = no_grad(|| {
+    ///     bert_model.forward_t(
+    ///         Some(input_tensor),
+    ///         Some(mask),
+    ///         Some(token_type_ids),
+    ///         None,
+    ///         None,
+    ///         false,
+    ///     )
+    /// });
+    /// ```
+    pub fn forward_t(
+        &self,
+        input_ids: Option<Tensor>,
+        mask: Option<Tensor>,
+        token_type_ids: Option<Tensor>,
+        position_ids: Option<Tensor>,
+        input_embeds: Option<Tensor>,
+        train: bool,
+    ) -> BertSequenceClassificationOutput {
+        let base_model_output = self
+            .bert
+            .forward_t(
+                input_ids,
+                mask,
+                token_type_ids,
+                position_ids,
+                input_embeds,
+                &None,
+                &None,
+                train,
+            )
+            .unwrap();
+
+        let logits = base_model_output.pooled_output.unwrap().apply(&self.cls);
         BertSequenceClassificationOutput {
             logits,
             all_hidden_states: base_model_output.all_hidden_states,
             all_attentions: base_model_output.all_attentions,
         }
     }
+
+    /// Returns the adapters registered on the base model, allowing to add, remove or switch the active adapter
+    pub fn adapters_mut(&mut self) -> &mut Adapters {
+        self.bert.adapters_mut()
+    }
+
+    /// Returns the forward hooks registered on the base model, allowing to register or remove hooks
+    pub fn hooks_mut(&mut self) -> &mut ForwardHooks {
+        self.bert.hooks_mut()
+    }
 }
 
 /// # BERT for multiple choices
@@ -886,6 +1191,16 @@ impl BertForMultipleChoice {
             all_attentions: base_model_output.all_attentions,
         }
     }
+
+    /// Returns the adapters registered on the base model, allowing to add, remove or switch the active adapter
+    pub fn adapters_mut(&mut self) -> &mut Adapters {
+        self.bert.adapters_mut()
+    }
+
+    /// Returns the forward hooks registered on the base model, allowing to register or remove hooks
+    pub fn hooks_mut(&mut self) -> &mut ForwardHooks {
+        self.bert.hooks_mut()
+    }
 }
 
 /// # BERT for token classification (e.g. NER, POS)
@@ -1031,6 +1346,16 @@ impl BertForTokenClassification {
             all_attentions: base_model_output.all_attentions,
         }
     }
+
+    /// Returns the adapters registered on the base model, allowing to add, remove or switch the active adapter
+    pub fn adapters_mut(&mut self) -> &mut Adapters {
+        self.bert.adapters_mut()
+    }
+
+    /// Returns the forward hooks registered on the base model, allowing to register or remove hooks
+    pub fn hooks_mut(&mut self) -> &mut ForwardHooks {
+        self.bert.hooks_mut()
+    }
 }
 
 /// # BERT for question answering
@@ -1171,6 +1496,16 @@ impl BertForQuestionAnswering {
             all_attentions: base_model_output.all_attentions,
         }
     }
+
+    /// Returns the adapters registered on the base model, allowing to add, remove or switch the active adapter
+    pub fn adapters_mut(&mut self) -> &mut Adapters {
+        self.bert.adapters_mut()
+    }
+
+    /// Returns the forward hooks registered on the base model, allowing to register or remove hooks
+    pub fn hooks_mut(&mut self) -> &mut ForwardHooks {
+        self.bert.hooks_mut()
+    }
 }
 
 /// Container for the BERT model output.
@@ -1185,6 +1520,20 @@ pub struct BertModelOutput {
     pub all_attentions: Option<Vec<Tensor>>,
 }
 
+impl ModelOutput for BertModelOutput {
+    fn hidden_states(&self) -> Option<Vec<&Tensor>> {
+        tensor_refs(&self.all_hidden_states)
+    }
+
+    fn attentions(&self) -> Option<Vec<&Tensor>> {
+        tensor_refs(&self.all_attentions)
+    }
+
+    fn logits(&self) -> Option<&Tensor> {
+        None
+    }
+}
+
 /// Container for the BERT masked LM model output.
 pub struct BertMaskedLMOutput {
     /// Logits for the vocabulary items at each sequence position
@@ -1195,6 +1544,20 @@ pub struct BertMaskedLMOutput {
     pub all_attentions: Option<Vec<Tensor>>,
 }
 
+impl ModelOutput for BertMaskedLMOutput {
+    fn hidden_states(&self) -> Option<Vec<&Tensor>> {
+        tensor_refs(&self.all_hidden_states)
+    }
+
+    fn attentions(&self) -> Option<Vec<&Tensor>> {
+        tensor_refs(&self.all_attentions)
+    }
+
+    fn logits(&self) -> Option<&Tensor> {
+        Some(&self.prediction_scores)
+    }
+}
+
 /// Container for the BERT sequence classification model output.
 pub struct BertSequenceClassificationOutput {
     /// Logits for each input (sequence) for each target class
@@ -1205,6 +1568,20 @@ pub struct BertSequenceClassificationOutput {
     pub all_attentions: Option<Vec<Tensor>>,
 }
 
+impl ModelOutput for BertSequenceClassificationOutput {
+    fn hidden_states(&self) -> Option<Vec<&Tensor>> {
+        tensor_refs(&self.all_hidden_states)
+    }
+
+    fn attentions(&self) -> Option<Vec<&Tensor>> {
+        tensor_refs(&self.all_attentions)
+    }
+
+    fn logits(&self) -> Option<&Tensor> {
+        Some(&self.logits)
+    }
+}
+
 /// Container for the BERT token classification model output.
 pub struct BertTokenClassificationOutput {
     /// Logits for each sequence item (token) for each target class
@@ -1215,6 +1592,20 @@ pub struct BertTokenClassificationOutput {
     pub all_attentions: Option<Vec<Tensor>>,
 }
 
+impl ModelOutput for BertTokenClassificationOutput {
+    fn hidden_states(&self) -> Option<Vec<&Tensor>> {
+        tensor_refs(&self.all_hidden_states)
+    }
+
+    fn attentions(&self) -> Option<Vec<&Tensor>> {
+        tensor_refs(&self.all_attentions)
+    }
+
+    fn logits(&self) -> Option<&Tensor> {
+        Some(&self.logits)
+    }
+}
+
 /// Container for the BERT question answering model output.
 pub struct BertQuestionAnsweringOutput {
     /// Logits for the start position for token of each input sequence
@@ -1226,6 +1617,24 @@ pub struct BertQuestionAnsweringOutput {
     /// Attention weights for all intermediate layers
     pub all_attentions: Option<Vec<Tensor>>,
 }
+
+impl ModelOutput for BertQuestionAnsweringOutput {
+    fn hidden_states(&self) -> Option<Vec<&Tensor>> {
+        tensor_refs(&self.all_hidden_states)
+    }
+
+    fn attentions(&self) -> Option<Vec<&Tensor>> {
+        tensor_refs(&self.all_attentions)
+    }
+
+    fn logits(&self) -> Option<&Tensor> {
+        None
+    }
+
+    fn start_end_logits(&self) -> Option<(&Tensor, &Tensor)> {
+        Some((&self.start_logits, &self.end_logits))
+    }
+}
 #[cfg(test)]
 mod test {
     use tch::Device;
@@ -13,6 +13,7 @@
 
 use crate::bert::bert_model::BertConfig;
 use crate::common::dropout::Dropout;
+use crate::common::weights::resize_token_dimension;
 use crate::RustBertError;
 use std::borrow::Borrow;
 use tch::nn::{embedding, EmbeddingConfig};
@@ -33,6 +34,14 @@ pub trait BertEmbedding {
         input_embeds: Option<Tensor>,
         train: bool,
     ) -> Result<Tensor, RustBertError>;
+
+    /// Resizes the word embeddings matrix to `new_num_tokens` rows, keeping the embeddings of the existing tokens.
+    /// Embedding layers that do not support resizing return an error.
+    fn resize_word_embeddings(&mut self, _new_num_tokens: i64) -> Result<(), RustBertError> {
+        Err(RustBertError::ValueError(
+            "Resizing the word embeddings is not supported by this embedding layer".into(),
+        ))
+    }
 }
 
 #[derive(Debug)]
@@ -44,6 +53,7 @@ pub struct BertEmbeddings {
     token_type_embeddings: nn::Embedding,
     layer_norm: nn::LayerNorm,
     dropout: Dropout,
+    initializer_range: f64,
 }
 
 impl BertEmbedding for BertEmbeddings {
@@ -113,6 +123,7 @@ impl BertEmbedding for BertEmbeddings {
             token_type_embeddings,
             layer_norm,
             dropout,
+            initializer_range: config.initializer_range as f64,
         }
     }
 
@@ -216,4 +227,12 @@ impl BertEmbedding for BertEmbeddings {
             .apply(&self.layer_norm)
             .apply_t(&self.dropout, train))
     }
+
+    fn resize_word_embeddings(&mut self, new_num_tokens: i64) -> Result<(), RustBertError> {
+        resize_token_dimension(
+            &mut self.word_embeddings.ws,
+            new_num_tokens,
+            Some(self.initializer_range),
+        )
+    }
 }
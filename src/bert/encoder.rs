@@ -13,6 +13,9 @@
 
 use crate::bert::attention::{BertAttention, BertIntermediate, BertOutput};
 use crate::bert::bert_model::BertConfig;
+use crate::common::adapters::{AdapterLayer, Adapters};
+use crate::common::hooks::ForwardHooks;
+use crate::common::profiling;
 use std::borrow::{Borrow, BorrowMut};
 use tch::{nn, Tensor};
 
@@ -130,30 +133,62 @@ impl BertLayer {
         encoder_hidden_states: &Option<Tensor>,
         encoder_mask: &Option<Tensor>,
         train: bool,
+    ) -> BertLayerOutput {
+        self.forward_with_adapter_t(
+            hidden_states,
+            mask,
+            encoder_hidden_states,
+            encoder_mask,
+            None,
+            train,
+        )
+    }
+
+    /// Forward pass through the layer, applying the adapter modules provided (see `adapters`)
+    ///
+    /// # Arguments
+    ///
+    /// * `hidden_states` - input tensor of shape (*batch size*, *sequence_length*, *hidden_size*).
+    /// * `mask` - Optional mask of shape (*batch size*, *sequence_length*). Masked position have value 0, non-masked value 1. If None set to 1
+    /// * `encoder_hidden_states` - Optional encoder hidden state of shape (*batch size*, *encoder_sequence_length*, *hidden_size*).
+    /// * `encoder_mask` - Optional encoder attention mask of shape (*batch size*, *encoder_sequence_length*).
+    /// * `adapter` - Optional `AdapterLayer` applied after the attention and feed-forward blocks of the layer
+    /// * `train` - boolean flag to turn on/off the dropout layers in the model. Should be set to false for inference.
+    pub fn forward_with_adapter_t(
+        &self,
+        hidden_states: &Tensor,
+        mask: &Option<Tensor>,
+        encoder_hidden_states: &Option<Tensor>,
+        encoder_mask: &Option<Tensor>,
+        adapter: Option<&AdapterLayer>,
+        train: bool,
     ) -> BertLayerOutput {
         let (attention_output, attention_scores, cross_attention_scores) =
             if self.is_decoder & encoder_hidden_states.is_some() {
                 let (attention_output, attention_weights) =
                     self.attention
-                        .forward_t(hidden_states, mask, &None, &None, train);
+                        .forward_t(hidden_states, mask, &None, &None, adapter, train);
                 let (attention_output, cross_attention_weights) =
                     self.cross_attention.as_ref().unwrap().forward_t(
                         &attention_output,
                         mask,
                         encoder_hidden_states,
                         encoder_mask,
+                        None,
                         train,
                     );
                 (attention_output, attention_weights, cross_attention_weights)
             } else {
                 let (attention_output, attention_weights) =
                     self.attention
-                        .forward_t(hidden_states, mask, &None, &None, train);
+                        .forward_t(hidden_states, mask, &None, &None, adapter, train);
                 (attention_output, attention_weights, None)
             };
 
         let output = self.intermediate.forward(&attention_output);
-        let output = self.output.forward_t(&output, &attention_output, train);
+        let output = self
+            .output
+            .forward_t(&output, &attention_output, adapter, train);
 
         BertLayerOutput {
             hidden_state: output,
@@ -171,6 +206,8 @@ pub struct BertEncoder {
     output_attentions: bool,
     output_hidden_states: bool,
     layers: Vec<BertLayer>,
+    adapters: Adapters,
+    hooks: ForwardHooks,
 }
 
 impl BertEncoder {
@@ -208,13 +245,37 @@ impl BertEncoder {
             layers.push(BertLayer::new(&p / layer_index, config));
         }
 
+        let adapters = Adapters::new(config.hidden_size, config.num_hidden_layers);
+
         BertEncoder {
             output_attentions,
             output_hidden_states,
             layers,
+            adapters,
+            hooks: ForwardHooks::new(),
         }
     }
 
+    /// Returns the adapters registered on the encoder
+    pub fn adapters(&self) -> &Adapters {
+        &self.adapters
+    }
+
+    /// Returns the adapters registered on the encoder, allowing to add, remove or switch the active adapter
+    pub fn adapters_mut(&mut self) -> &mut Adapters {
+        &mut self.adapters
+    }
+
+    /// Returns the forward hooks registered on the encoder
+    pub fn hooks(&self) -> &ForwardHooks {
+        &self.hooks
+    }
+
+    /// Returns the forward hooks registered on the encoder, allowing to register or remove hooks
+    pub fn hooks_mut(&mut self) -> &mut ForwardHooks {
+        &mut self.hooks
+    }
+
     /// Forward pass through the encoder
     ///
     /// # Arguments
@@ -274,20 +335,34 @@ impl BertEncoder {
         let mut hidden_state = hidden_states.copy();
         let mut attention_weights: Option<Tensor>;
 
-        for layer in &self.layers {
+        for (layer_index, layer) in self.layers.iter().enumerate() {
             if let Some(hidden_states) = all_hidden_states.borrow_mut() {
                 hidden_states.push(hidden_state.as_ref().copy());
             };
 
-            let layer_output = layer.forward_t(
+            let layer_scope = profiling::scope(
+                || format!("encoder.layer.{}", layer_index),
+                &[&hidden_state],
+            );
+            let layer_output = layer.forward_with_adapter_t(
                 &hidden_state,
                 &mask,
                 encoder_hidden_states,
                 encoder_mask,
+                self.adapters.active_layer(layer_index),
                 train,
             );
+            drop(layer_scope);
             hidden_state = layer_output.hidden_state;
             attention_weights = layer_output.attention_weights;
+            self.hooks
+                .run(|| format!("encoder.layer.{}", layer_index), &hidden_state);
+            if let Some(attention_weights) = &attention_weights {
+                self.hooks.run(
+                    || format!("encoder.layer.{}.attention_weights", layer_index),
+                    attention_weights,
+                );
+            }
             if let Some(attentions) = all_attentions.borrow_mut() {
                 attentions.push(attention_weights.as_ref().unwrap().copy());
             };
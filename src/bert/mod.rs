@@ -4,6 +4,7 @@
 //! The base model is implemented in the `bert::BertModel` struct. Several language model heads have also been implemented, including:
 //! - Masked language model: `bert::BertForMaskedLM`
 //! - Multiple choices: `bert:BertForMultipleChoice`
+//! - Next sentence prediction: `bert::BertForNextSentencePrediction`
 //! - Question answering: `bert::BertForQuestionAnswering`
 //! - Sequence classification: `bert::BertForSequenceClassification`
 //! - Token classification (e.g. NER, POS tagging): `bert::BertForTokenClassification`
@@ -59,8 +60,8 @@ pub(crate) mod encoder;
 
 pub use bert_model::{
     BertConfig, BertConfigResources, BertForMaskedLM, BertForMultipleChoice,
-    BertForQuestionAnswering, BertForSequenceClassification, BertForTokenClassification,
-    BertMaskedLMOutput, BertModel, BertModelOutput, BertModelResources,
+    BertForNextSentencePrediction, BertForQuestionAnswering, BertForSequenceClassification,
+    BertForTokenClassification, BertMaskedLMOutput, BertModel, BertModelOutput, BertModelResources,
     BertQuestionAnsweringOutput, BertSequenceClassificationOutput, BertTokenClassificationOutput,
     BertVocabResources,
 };
@@ -0,0 +1,332 @@
+// Copyright 2021 Guillaume Becquin
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//     http://www.apache.org/licenses/LICENSE-2.0
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! # Bottleneck adapters
+//!
+//! Small bottleneck modules inserted in the layers of a transformer, trained for a task while the weights of the
+//! base model are frozen ([Parameter-Efficient Transfer Learning for NLP](https://arxiv.org/abs/1902.00751) Houlsby et al., 2019,
+//! [AdapterFusion](https://arxiv.org/abs/2005.00247) Pfeiffer et al., 2020). Two variants are available:
+//! - Houlsby: adapters after both the attention and feed-forward blocks of each layer
+//! - Pfeiffer: a single adapter after the feed-forward block of each layer
+//!
+//! Each `Adapter` holds its variables in its own variable store and can be saved and loaded independently of the base model.
+//! Adapters are registered by name on the encoder/decoder stacks of BERT, RoBERTa and BART models (`Adapters` returned by
+//! `adapters_mut`), and the active adapter can be switched at runtime, allowing a single base model to serve several tasks.
+//!
+//! ```no_run
+//! # fn main() -> anyhow::Result<()> {
+//! use rust_bert::adapters::{Adapter, AdapterConfig};
+//! use rust_bert::bert::{BertConfig, BertForSequenceClassification};
+//! use rust_bert::Config;
+//! use std::path::Path;
+//! use tch::{nn, Device};
+//!
+//! let device = Device::Cpu;
+//! let mut vs = nn::VarStore::new(device);
+//! let config = BertConfig::from_file(Path::new("path/to/config.json"));
+//! let mut bert_model = BertForSequenceClassification::new(&vs.root(), &config);
+//! vs.load("path/to/model.ot")?;
+//!
+//! let adapter_config = AdapterConfig::from_file(Path::new("path/to/adapter_config.json"));
+//! let sentiment_adapter = Adapter::load(&adapter_config, "path/to/sentiment_adapter.ot", device)?;
+//! let topic_adapter = Adapter::load(&adapter_config, "path/to/topic_adapter.ot", device)?;
+//!
+//! let adapters = bert_model.adapters_mut();
+//! adapters.add("sentiment", sentiment_adapter)?;
+//! adapters.add("topic", topic_adapter)?;
+//! adapters.set_active(Some("sentiment"))?;
+//! # Ok(())
+//! # }
+//! ```
+
+use crate::common::activations::{Activation, TensorFunction};
+use crate::common::config::{check_divisible, check_positive};
+use crate::{Config, RustBertError};
+use serde::{Deserialize, Serialize};
+use std::borrow::Borrow;
+use std::collections::HashMap;
+use std::path::Path;
+use tch::{nn, Device, Tensor};
+
+#[derive(Clone, Copy, Debug, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "lowercase")]
+/// # Adapter architecture
+pub enum AdapterType {
+    /// Adapters after the attention and the feed-forward blocks of each layer
+    Houlsby,
+    /// Single adapter after the feed-forward block of each layer
+    Pfeiffer,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+/// # Adapter configuration
+/// Defines the adapter architecture and the dimensions of the base model it is inserted into
+pub struct AdapterConfig {
+    pub adapter_type: AdapterType,
+    /// Ratio between the hidden size of the model and the bottleneck dimension of the adapters
+    pub reduction_factor: i64,
+    pub non_linearity: Option<Activation>,
+    pub hidden_size: i64,
+    pub num_layers: i64,
+}
+
+impl Config<AdapterConfig> for AdapterConfig {
+    fn validate(&self) -> Result<(), RustBertError> {
+        check_positive("num_layers", self.num_layers)?;
+        check_divisible(
+            "hidden_size",
+            self.hidden_size,
+            "reduction_factor",
+            self.reduction_factor,
+        )
+    }
+}
+
+/// # Bottleneck adapter block
+/// Down-projection, non-linearity and up-projection with a residual connection
+pub struct BottleneckAdapter {
+    down_projection: nn::Linear,
+    up_projection: nn::Linear,
+    activation: TensorFunction,
+}
+
+impl BottleneckAdapter {
+    pub fn new<'p, P>(p: P, config: &AdapterConfig) -> BottleneckAdapter
+    where
+        P: Borrow<nn::Path<'p>>,
+    {
+        let p = p.borrow();
+        let bottleneck_size = config.hidden_size / config.reduction_factor;
+        let down_projection = nn::linear(
+            p / "down_projection",
+            config.hidden_size,
+            bottleneck_size,
+            Default::default(),
+        );
+        let up_projection = nn::linear(
+            p / "up_projection",
+            bottleneck_size,
+            config.hidden_size,
+            nn::LinearConfig {
+                ws_init: nn::Init::Const(0.),
+                ..Default::default()
+            },
+        );
+        let activation = config
+            .non_linearity
+            .unwrap_or(Activation::relu)
+            .get_function();
+        BottleneckAdapter {
+            down_projection,
+            up_projection,
+            activation,
+        }
+    }
+
+    pub fn forward(&self, hidden_states: &Tensor) -> Tensor {
+        let bottleneck = (self.activation.get_fn())(&hidden_states.apply(&self.down_projection));
+        hidden_states + bottleneck.apply(&self.up_projection)
+    }
+}
+
+/// # Adapters of a single transformer layer
+pub struct AdapterLayer {
+    attention: Option<BottleneckAdapter>,
+    output: BottleneckAdapter,
+}
+
+impl AdapterLayer {
+    pub fn new<'p, P>(p: P, config: &AdapterConfig) -> AdapterLayer
+    where
+        P: Borrow<nn::Path<'p>>,
+    {
+        let p = p.borrow();
+        let attention = match config.adapter_type {
+            AdapterType::Houlsby => Some(BottleneckAdapter::new(p / "attention", config)),
+            AdapterType::Pfeiffer => None,
+        };
+        let output = BottleneckAdapter::new(p / "output", config);
+        AdapterLayer { attention, output }
+    }
+
+    /// Applies the adapter following the attention block (identity for Pfeiffer adapters)
+    pub fn forward_attention(&self, hidden_states: Tensor) -> Tensor {
+        match &self.attention {
+            Some(adapter) => adapter.forward(&hidden_states),
+            None => hidden_states,
+        }
+    }
+
+    /// Applies the adapter following the feed-forward block
+    pub fn forward_output(&self, hidden_states: Tensor) -> Tensor {
+        self.output.forward(&hidden_states)
+    }
+}
+
+/// # Adapter for a transformer stack
+/// Contains one `AdapterLayer` per layer of the stack, stored in a dedicated variable store.
+pub struct Adapter {
+    config: AdapterConfig,
+    var_store: nn::VarStore,
+    layers: Vec<AdapterLayer>,
+}
+
+impl Adapter {
+    /// Creates a new adapter. The up-projections are initialized to zero, so that a new adapter does not modify the
+    /// output of the base model before it is trained.
+    ///
+    /// # Arguments
+    ///
+    /// * `config` - `AdapterConfig` defining the adapter architecture
+    /// * `device` - device on which the adapter variables are created
+    pub fn new(config: &AdapterConfig, device: Device) -> Adapter {
+        let var_store = nn::VarStore::new(device);
+        let root = var_store.root();
+        let p = &root / "layer";
+        let layers = (0..config.num_layers)
+            .map(|layer_index| AdapterLayer::new(&p / layer_index, config))
+            .collect();
+        Adapter {
+            config: config.clone(),
+            var_store,
+            layers,
+        }
+    }
+
+    /// Loads an adapter saved with `save`
+    ///
+    /// # Arguments
+    ///
+    /// * `config` - `AdapterConfig` defining the adapter architecture
+    /// * `path` - path to the adapter weights
+    /// * `device` - device on which the adapter variables are loaded
+    pub fn load<P: AsRef<Path>>(
+        config: &AdapterConfig,
+        path: P,
+        device: Device,
+    ) -> Result<Adapter, RustBertError> {
+        let mut adapter = Adapter::new(config, device);
+        adapter.var_store.load(path)?;
+        Ok(adapter)
+    }
+
+    /// Saves the adapter weights to a file
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<(), RustBertError> {
+        Ok(self.var_store.save(path)?)
+    }
+
+    /// Returns the adapter configuration
+    pub fn config(&self) -> &AdapterConfig {
+        &self.config
+    }
+
+    /// Returns the variable store holding the adapter weights (e.g. to create an optimizer training the adapter only)
+    pub fn var_store(&self) -> &nn::VarStore {
+        &self.var_store
+    }
+
+    pub(crate) fn layer(&self, layer_index: usize) -> Option<&AdapterLayer> {
+        self.layers.get(layer_index)
+    }
+}
+
+/// # Collection of adapters registered on a transformer stack
+/// At most one adapter is active at a time. The base model is used without adapter if none is active.
+pub struct Adapters {
+    hidden_size: i64,
+    num_layers: i64,
+    adapters: HashMap<String, Adapter>,
+    active: Option<String>,
+}
+
+impl Adapters {
+    pub(crate) fn new(hidden_size: i64, num_layers: i64) -> Adapters {
+        Adapters {
+            hidden_size,
+            num_layers,
+            adapters: HashMap::new(),
+            active: None,
+        }
+    }
+
+    /// Registers an adapter under the name provided, replacing any adapter with the same name.
+    /// Fails if the adapter dimensions do not match the stack it is added to.
+    pub fn add(&mut self, name: &str, adapter: Adapter) -> Result<(), RustBertError> {
+        if (adapter.config.hidden_size != self.hidden_size)
+            | (adapter.config.num_layers != self.num_layers)
+        {
+            return Err(RustBertError::InvalidConfigurationError(format!(
+                "Adapter dimensions (hidden_size: {}, num_layers: {}) do not match the model (hidden_size: {}, num_layers: {})",
+                adapter.config.hidden_size,
+                adapter.config.num_layers,
+                self.hidden_size,
+                self.num_layers
+            )));
+        }
+        self.adapters.insert(name.to_string(), adapter);
+        Ok(())
+    }
+
+    /// Removes an adapter, deactivating it if it was active
+    pub fn remove(&mut self, name: &str) -> Option<Adapter> {
+        if self.active.as_deref() == Some(name) {
+            self.active = None;
+        }
+        self.adapters.remove(name)
+    }
+
+    /// Sets the active adapter. `None` disables the adapters and uses the base model only.
+    pub fn set_active(&mut self, name: Option<&str>) -> Result<(), RustBertError> {
+        if let Some(name) = name {
+            if !self.adapters.contains_key(name) {
+                return Err(RustBertError::ValueError(format!(
+                    "Adapter {} is not registered",
+                    name
+                )));
+            }
+        }
+        self.active = name.map(|value| value.to_string());
+        Ok(())
+    }
+
+    /// Returns the name of the active adapter, if any
+    pub fn active(&self) -> Option<&str> {
+        self.active.as_deref()
+    }
+
+    /// Returns the names of the registered adapters
+    pub fn names(&self) -> Vec<&str> {
+        self.adapters.keys().map(|name| name.as_str()).collect()
+    }
+
+    /// Returns a registered adapter
+    pub fn get(&self, name: &str) -> Option<&Adapter> {
+        self.adapters.get(name)
+    }
+
+    pub(crate) fn active_layer(&self, layer_index: usize) -> Option<&AdapterLayer> {
+        self.active
+            .as_ref()
+            .and_then(|name| self.adapters.get(name))
+            .and_then(|adapter| adapter.layer(layer_index))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    #[ignore] // no need to run, compilation is enough to verify it is Send
+    fn test() {
+        let _: Box<dyn Send> = Box::new(Adapters::new(768, 12));
+    }
+}
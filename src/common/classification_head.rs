@@ -0,0 +1,215 @@
+// Copyright 2021 Guillaume Becquin
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//     http://www.apache.org/licenses/LICENSE-2.0
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! # Configurable sequence classification heads
+//!
+//! The sequence classification models (BERT, RoBERTa, DistilBERT and ALBERT) are built by default with the head of the
+//! original implementation. Fine-tuned checkpoints often use a different head, which can be described by a
+//! `ClassificationHeadConfig` passed to the `new_with_head` constructors of the models (or to the sequence
+//! classification pipeline):
+//! - `pooling`: representation of the sequence, from the hidden state of the first token (`Cls`), the average (`Mean`)
+//! or maximum (`Max`) of the token hidden states, or their average weighted by a learnt attention score (`Attention`).
+//! Padding positions are excluded using the attention mask.
+//! - `hidden_dims`: dimensions of the hidden layers between the pooled representation and the output projection,
+//! each followed by `activation`
+//! - `dropout`: dropout probability applied before each linear layer, defaulting to the hidden dropout of the model
+//!
+//! The head variables are created under the `classifier` path of the model: `attention` for the attention pooling
+//! scores, `layers.{i}` for the hidden layers and `out_proj` for the output projection.
+//!
+//! ```no_run
+//! use rust_bert::bert::{BertConfig, BertForSequenceClassification};
+//! use rust_bert::classification_head::{ClassificationHeadConfig, PoolingStrategy};
+//! use rust_bert::{Activation, Config};
+//! use std::path::Path;
+//! use tch::{nn, Device};
+//!
+//! let config = BertConfig::from_file(Path::new("path/to/config.json"));
+//! let head_config = ClassificationHeadConfig {
+//!     pooling: PoolingStrategy::Mean,
+//!     hidden_dims: vec![256],
+//!     activation: Activation::relu,
+//!     dropout: Some(0.2),
+//! };
+//! let vs = nn::VarStore::new(Device::Cpu);
+//! let model = BertForSequenceClassification::new_with_head(&vs.root(), &config, &head_config);
+//! ```
+
+use crate::common::activations::{Activation, TensorFunction};
+use crate::common::dropout::Dropout;
+use serde::{Deserialize, Serialize};
+use std::borrow::Borrow;
+use tch::{nn, Kind, Tensor};
+
+#[derive(Clone, Copy, Debug, Serialize, Deserialize, PartialEq)]
+/// # Pooling of the token hidden states into a sequence representation
+pub enum PoolingStrategy {
+    /// Hidden state of the first token (`[CLS]` or `<s>`)
+    Cls,
+    /// Average of the token hidden states
+    Mean,
+    /// Element-wise maximum of the token hidden states
+    Max,
+    /// Average of the token hidden states weighted by a learnt attention score
+    Attention,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
+/// # Architecture of a sequence classification head
+pub struct ClassificationHeadConfig {
+    /// Pooling of the token hidden states (default: `Cls`)
+    pub pooling: PoolingStrategy,
+    /// Dimensions of the hidden layers preceding the output projection (default: none)
+    pub hidden_dims: Vec<i64>,
+    /// Activation applied after each hidden layer (default: tanh)
+    pub activation: Activation,
+    /// Dropout probability applied before each linear layer (default: hidden dropout of the model)
+    pub dropout: Option<f64>,
+}
+
+impl Default for ClassificationHeadConfig {
+    fn default() -> ClassificationHeadConfig {
+        ClassificationHeadConfig {
+            pooling: PoolingStrategy::Cls,
+            hidden_dims: vec![],
+            activation: Activation::tanh,
+            dropout: None,
+        }
+    }
+}
+
+/// # Sequence classification head built from a `ClassificationHeadConfig`
+pub struct ClassificationHead {
+    pooling: PoolingStrategy,
+    attention: Option<nn::Linear>,
+    layers: Vec<nn::Linear>,
+    activation: TensorFunction,
+    dropout: Dropout,
+    out_proj: nn::Linear,
+}
+
+impl ClassificationHead {
+    /// Build a new `ClassificationHead`
+    ///
+    /// # Arguments
+    ///
+    /// * `p` - Variable store path for the root of the head
+    /// * `hidden_size` - Dimension of the token hidden states
+    /// * `num_labels` - Number of outputs of the head
+    /// * `config` - `ClassificationHeadConfig` defining the head architecture
+    /// * `default_dropout` - Dropout probability used if the configuration does not set one
+    pub fn new<'p, P>(
+        p: P,
+        hidden_size: i64,
+        num_labels: i64,
+        config: &ClassificationHeadConfig,
+        default_dropout: f64,
+    ) -> ClassificationHead
+    where
+        P: Borrow<nn::Path<'p>>,
+    {
+        let p = p.borrow();
+        let attention = match config.pooling {
+            PoolingStrategy::Attention => Some(nn::linear(
+                p / "attention",
+                hidden_size,
+                1,
+                Default::default(),
+            )),
+            _ => None,
+        };
+        let layers_path = p / "layers";
+        let mut input_size = hidden_size;
+        let mut layers = Vec::with_capacity(config.hidden_dims.len());
+        for (layer_index, &hidden_dim) in config.hidden_dims.iter().enumerate() {
+            layers.push(nn::linear(
+                &layers_path / layer_index,
+                input_size,
+                hidden_dim,
+                Default::default(),
+            ));
+            input_size = hidden_dim;
+        }
+        let out_proj = nn::linear(p / "out_proj", input_size, num_labels, Default::default());
+
+        ClassificationHead {
+            pooling: config.pooling,
+            attention,
+            layers,
+            activation: config.activation.get_function(),
+            dropout: Dropout::new(config.dropout.unwrap_or(default_dropout)),
+            out_proj,
+        }
+    }
+
+    /// Pools the token hidden states of shape (*batch size*, *sequence_length*, *hidden_size*) into a tensor of shape
+    /// (*batch size*, *hidden_size*), ignoring the positions where the mask is 0.
+    pub fn pool(&self, hidden_states: &Tensor, mask: Option<&Tensor>) -> Tensor {
+        let padding = mask.map(|mask| mask.eq(0));
+        match self.pooling {
+            PoolingStrategy::Cls => hidden_states.select(1, 0),
+            PoolingStrategy::Mean => match mask {
+                Some(mask) => {
+                    let mask = mask.unsqueeze(-1).to_kind(hidden_states.kind());
+                    (hidden_states * &mask).sum1(&[1], false, Kind::Float)
+                        / mask.sum1(&[1], false, Kind::Float).clamp_min(1.0)
+                }
+                None => hidden_states.mean1(&[1], false, Kind::Float),
+            },
+            PoolingStrategy::Max => match &padding {
+                Some(padding) => {
+                    hidden_states
+                        .masked_fill(&padding.unsqueeze(-1), std::f64::NEG_INFINITY)
+                        .max2(1, false)
+                        .0
+                }
+                None => hidden_states.max2(1, false).0,
+            },
+            PoolingStrategy::Attention => {
+                let mut scores = hidden_states
+                    .apply(self.attention.as_ref().unwrap())
+                    .squeeze1(-1);
+                if let Some(padding) = &padding {
+                    scores = scores.masked_fill(padding, std::f64::NEG_INFINITY);
+                }
+                let weights = scores.softmax(1, Kind::Float).unsqueeze(-1);
+                (hidden_states * weights).sum1(&[1], false, Kind::Float)
+            }
+        }
+    }
+
+    /// Forward pass through the head
+    ///
+    /// # Arguments
+    ///
+    /// * `hidden_states` - Token hidden states of shape (*batch size*, *sequence_length*, *hidden_size*)
+    /// * `mask` - Optional attention mask of shape (*batch size*, *sequence_length*), with value 0 for padding positions
+    /// * `train` - boolean flag to turn on/off the dropout layers
+    ///
+    /// # Returns
+    ///
+    /// * `Tensor` of shape (*batch size*, *num_labels*) containing the logits
+    pub fn forward_t(&self, hidden_states: &Tensor, mask: Option<&Tensor>, train: bool) -> Tensor {
+        let mut output = self.pool(hidden_states, mask);
+        for layer in &self.layers {
+            output = self.activation.get_fn()(&output.apply_t(&self.dropout, train).apply(layer));
+        }
+        output.apply_t(&self.dropout, train).apply(&self.out_proj)
+    }
+}
+
+/// # Classification head of a model
+/// Either the default head of the model or a head configured with a `ClassificationHeadConfig`.
+pub(crate) enum ClassifierOption<T> {
+    Default(T),
+    Custom(ClassificationHead),
+}
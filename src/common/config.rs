@@ -9,7 +9,12 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
-use serde::Deserialize;
+use crate::common::error::RustBertError;
+use crate::common::task_registry::TaskRegistry;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Deserializer};
+use serde_json::{Map, Value};
+use std::collections::HashMap;
 use std::fs::File;
 use std::io::BufReader;
 use std::path::Path;
@@ -21,6 +26,7 @@ where
 {
     /// Loads a `Config` object from a JSON file. The format is expected to be aligned with the [Transformers library](https://github.com/huggingface/transformers) configuration files for each model.
     /// The parsing will fail if non-optional keys expected by the model are missing.
+    /// The configuration is not validated: use `try_from_file` to load and validate it, or call `validate`.
     ///
     /// # Arguments
     ///
@@ -36,10 +42,207 @@ where
     /// let config_path = Path::new("path/to/config.json");
     /// let config = Gpt2Config::from_file(config_path);
     /// ```
-    fn from_file<P: AsRef<Path>>(path: P) -> T {
+    fn from_file<P: AsRef<Path>>(path: P) -> T
+    where
+        T: Config<T>,
+    {
         let f = File::open(path).expect("Could not open configuration file.");
         let br = BufReader::new(f);
         let config: T = serde_json::from_reader(br).expect("could not parse configuration");
         config
     }
+
+    /// Loads and validates a `Config` object from a JSON file, returning an error instead of panicking if the file
+    /// cannot be read, parsed or if the configuration is invalid.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - `Path` to the configuration JSON file.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// # fn main() -> anyhow::Result<()> {
+    /// use rust_bert::gpt2::Gpt2Config;
+    /// use rust_bert::Config;
+    /// use std::path::Path;
+    ///
+    /// let config_path = Path::new("path/to/config.json");
+    /// let config = Gpt2Config::try_from_file(config_path)?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    fn try_from_file<P: AsRef<Path>>(path: P) -> Result<T, RustBertError>
+    where
+        T: Config<T>,
+    {
+        let f = File::open(path)?;
+        let br = BufReader::new(f);
+        let config: T = serde_json::from_reader(br).map_err(|error| {
+            RustBertError::InvalidConfigurationError(format!(
+                "could not parse configuration: {}",
+                error
+            ))
+        })?;
+        config.validate()?;
+        Ok(config)
+    }
+
+    /// Checks the consistency of the configuration (e.g. hidden size divisible by the number of attention heads),
+    /// returning an `InvalidConfigurationError` naming the offending field if the model cannot be built from it.
+    fn validate(&self) -> Result<(), RustBertError> {
+        Ok(())
+    }
+
+    /// Returns the fields of the configuration file that are not used by the model. These are kept on deserialization,
+    /// serialized back when saving the configuration, and can be queried with the `get_extra_*` methods.
+    fn extra_fields(&self) -> Option<&Map<String, Value>> {
+        None
+    }
+
+    /// Returns the raw JSON value of an additional configuration field, if present
+    ///
+    /// # Arguments
+    ///
+    /// * `key` - name of the field in the configuration file
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use rust_bert::bert::BertConfig;
+    /// use rust_bert::Config;
+    /// use std::path::Path;
+    ///
+    /// let config = BertConfig::from_file(Path::new("path/to/config.json"));
+    /// let architectures = config.get_extra_value("architectures");
+    /// ```
+    fn get_extra_value(&self, key: &str) -> Option<&Value> {
+        self.extra_fields().and_then(|extra| extra.get(key))
+    }
+
+    /// Returns an additional string configuration field, if present and a string
+    fn get_extra_str(&self, key: &str) -> Option<&str> {
+        self.get_extra_value(key).and_then(Value::as_str)
+    }
+
+    /// Returns an additional integer configuration field, if present and an integer
+    fn get_extra_i64(&self, key: &str) -> Option<i64> {
+        self.get_extra_value(key).and_then(Value::as_i64)
+    }
+
+    /// Returns an additional floating point configuration field, if present and a number
+    fn get_extra_f64(&self, key: &str) -> Option<f64> {
+        self.get_extra_value(key).and_then(Value::as_f64)
+    }
+
+    /// Returns an additional boolean configuration field, if present and a boolean
+    fn get_extra_bool(&self, key: &str) -> Option<bool> {
+        self.get_extra_value(key).and_then(Value::as_bool)
+    }
+
+    /// Deserializes an additional configuration field into the type requested. Returns `Ok(None)` if the field is
+    /// absent and an `InvalidConfigurationError` if it cannot be converted.
+    fn get_extra<D: DeserializeOwned>(&self, key: &str) -> Result<Option<D>, RustBertError> {
+        self.get_extra_value(key)
+            .map(|value| {
+                serde_json::from_value(value.clone()).map_err(|error| {
+                    RustBertError::InvalidConfigurationError(format!(
+                        "could not parse configuration field `{}`: {}",
+                        key, error
+                    ))
+                })
+            })
+            .transpose()
+    }
+
+    /// Returns the task-specific parameters (prefix and generation defaults) stored in the `task_specific_params`
+    /// field of the configuration, or an empty registry if the field is absent.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// # fn main() -> anyhow::Result<()> {
+    /// use rust_bert::bart::BartConfig;
+    /// use rust_bert::Config;
+    /// use std::path::Path;
+    ///
+    /// let config = BartConfig::from_file(Path::new("path/to/config.json"));
+    /// let summarization_prefix = config.task_registry()?.prefix("summarization");
+    /// # Ok(())
+    /// # }
+    /// ```
+    fn task_registry(&self) -> Result<TaskRegistry, RustBertError> {
+        Ok(self
+            .get_extra::<TaskRegistry>("task_specific_params")?
+            .unwrap_or_default())
+    }
+}
+
+/// Deserializes a label mapping with integer keys stored as strings in the JSON configuration files.
+/// Required for configurations capturing their additional fields with `#[serde(flatten)]`, which buffers
+/// the map keys as strings.
+pub(crate) fn deserialize_id2label<'de, D>(
+    deserializer: D,
+) -> Result<Option<HashMap<i64, String>>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let id2label: Option<HashMap<String, String>> = Option::deserialize(deserializer)?;
+    id2label
+        .map(|id2label| {
+            id2label
+                .into_iter()
+                .map(|(id, label)| {
+                    id.parse::<i64>()
+                        .map(|id| (id, label))
+                        .map_err(serde::de::Error::custom)
+                })
+                .collect()
+        })
+        .transpose()
+}
+
+/// Returns an error naming the fields if `value` is not a multiple of `divisor`
+pub(crate) fn check_divisible(
+    value_name: &str,
+    value: i64,
+    divisor_name: &str,
+    divisor: i64,
+) -> Result<(), RustBertError> {
+    check_positive(divisor_name, divisor)?;
+    if value % divisor != 0 {
+        return Err(RustBertError::InvalidConfigurationError(format!(
+            "`{}` ({}) must be a multiple of `{}` ({})",
+            value_name, value, divisor_name, divisor
+        )));
+    }
+    Ok(())
+}
+
+/// Returns an error naming the field if `value` is not strictly positive
+pub(crate) fn check_positive(value_name: &str, value: i64) -> Result<(), RustBertError> {
+    if value <= 0 {
+        return Err(RustBertError::InvalidConfigurationError(format!(
+            "`{}` must be strictly positive, got {}",
+            value_name, value
+        )));
+    }
+    Ok(())
+}
+
+/// Returns an error naming the field if the token id provided is outside of the vocabulary
+pub(crate) fn check_token_id(
+    value_name: &str,
+    value: Option<i64>,
+    vocab_size: i64,
+) -> Result<(), RustBertError> {
+    if let Some(value) = value {
+        if (value < 0) | (value >= vocab_size) {
+            return Err(RustBertError::InvalidConfigurationError(format!(
+                "`{}` ({}) must be a valid token id for the vocabulary size `vocab_size` ({})",
+                value_name, value, vocab_size
+            )));
+        }
+    }
+    Ok(())
 }
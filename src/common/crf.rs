@@ -0,0 +1,356 @@
+// Copyright 2021 Guillaume Becquin
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//     http://www.apache.org/licenses/LICENSE-2.0
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! # Linear-chain conditional random field
+//!
+//! Layer scoring label sequences on top of the per-token logits (emissions) of a token classification model, with
+//! learnt transition scores between consecutive labels and scores for the first and last label of a sequence. The most
+//! likely label sequence is found with Viterbi decoding, which lets a checkpoint learn to avoid invalid sequences
+//! (e.g. `I-PER` following `B-LOC`). `TransitionScores` can also be used without a CRF layer, with fixed scores
+//! forbidding the invalid transitions.
+//!
+//! The variables follow the naming of the `pytorch-crf` package, so that CRF checkpoints converted from PyTorch can be
+//! loaded: `transitions` of shape (*num_labels*, *num_labels*), indexed by (previous label, next label),
+//! `start_transitions` and `end_transitions` of shape (*num_labels*). The layer is trained by minimizing the negative
+//! log-likelihood returned by `CRF::loss`.
+//!
+//! The token classification pipeline decodes its outputs with a CRF when `crf` is set in its configuration.
+//!
+//! ```no_run
+//! use rust_bert::crf::CRF;
+//! use tch::{nn, Device, Kind, Tensor};
+//!
+//! let vs = nn::VarStore::new(Device::Cpu);
+//! let crf = CRF::new(&vs.root() / "crf", 9);
+//! // Logits of a token classification model, of shape (batch size, sequence length, number of labels)
+//! let emissions = Tensor::rand(&[2, 12, 9], (Kind::Float, Device::Cpu));
+//! let labels: Vec<Vec<i64>> = crf.decode(&emissions, None);
+//! ```
+
+use std::borrow::Borrow;
+use tch::kind::Kind::{Double, Float, Int64};
+use tch::{nn, Device, Tensor};
+
+fn best(scores: impl Iterator<Item = f64>) -> (usize, f64) {
+    scores
+        .enumerate()
+        .fold((0, f64::NEG_INFINITY), |best, candidate| {
+            if candidate.1 > best.1 {
+                candidate
+            } else {
+                best
+            }
+        })
+}
+
+#[derive(Debug, Clone, PartialEq)]
+/// # Label transition scores of a linear-chain model
+/// The score of a label sequence is the sum of the emission scores of its labels, of the transition scores between
+/// consecutive labels and of the start and end scores of its first and last labels. Forbidden transitions can be given
+/// a score of `f64::NEG_INFINITY`.
+pub struct TransitionScores {
+    /// Transition scores, indexed by (previous label, next label)
+    pub transitions: Vec<Vec<f64>>,
+    /// Scores of the labels at the start of a sequence
+    pub start: Vec<f64>,
+    /// Scores of the labels at the end of a sequence
+    pub end: Vec<f64>,
+}
+
+impl TransitionScores {
+    /// Creates transition scores allowing all the label sequences, with a score of 0
+    pub fn zeros(num_labels: usize) -> TransitionScores {
+        TransitionScores {
+            transitions: vec![vec![0.0; num_labels]; num_labels],
+            start: vec![0.0; num_labels],
+            end: vec![0.0; num_labels],
+        }
+    }
+
+    /// Adds other transition scores of the same number of labels (e.g. constraints on the learnt scores of a CRF)
+    pub fn add(&mut self, other: &TransitionScores) {
+        for (row, other_row) in self.transitions.iter_mut().zip(other.transitions.iter()) {
+            for (score, other_score) in row.iter_mut().zip(other_row.iter()) {
+                *score += other_score;
+            }
+        }
+        for (score, other_score) in self
+            .start
+            .iter_mut()
+            .chain(self.end.iter_mut())
+            .zip(other.start.iter().chain(other.end.iter()))
+        {
+            *score += other_score;
+        }
+    }
+
+    /// Finds the label sequence of highest score with the Viterbi algorithm
+    ///
+    /// # Arguments
+    ///
+    /// * `emissions` - Scores of each label at each position of the sequence
+    ///
+    /// # Returns
+    ///
+    /// * `Vec<usize>` label indices of the best sequence, of the same length as `emissions`
+    pub fn viterbi_decode(&self, emissions: &[Vec<f64>]) -> Vec<usize> {
+        if emissions.is_empty() {
+            return vec![];
+        }
+        let mut scores = self
+            .start
+            .iter()
+            .zip(emissions[0].iter())
+            .map(|(start, emission)| start + emission)
+            .collect::<Vec<f64>>();
+        let mut backpointers: Vec<Vec<usize>> = Vec::with_capacity(emissions.len() - 1);
+        for position_emissions in &emissions[1..] {
+            let (position_backpointers, next_scores): (Vec<usize>, Vec<f64>) =
+                position_emissions
+                    .iter()
+                    .enumerate()
+                    .map(|(label, emission)| {
+                        let (best_previous, best_score) =
+                            best(scores.iter().enumerate().map(|(previous, score)| {
+                                score + self.transitions[previous][label]
+                            }));
+                        (best_previous, best_score + emission)
+                    })
+                    .unzip();
+            scores = next_scores;
+            backpointers.push(position_backpointers);
+        }
+
+        let mut best_label = best(
+            scores
+                .iter()
+                .zip(self.end.iter())
+                .map(|(score, end)| score + end),
+        )
+        .0;
+        let mut labels = vec![best_label];
+        for position_backpointers in backpointers.iter().rev() {
+            best_label = position_backpointers[best_label];
+            labels.push(best_label);
+        }
+        labels.reverse();
+        labels
+    }
+
+    /// Picks the labels from left to right, each label being the best one given the previous label. Faster than
+    /// `viterbi_decode` but not guaranteed to find the best sequence.
+    ///
+    /// # Arguments
+    ///
+    /// * `emissions` - Scores of each label at each position of the sequence
+    ///
+    /// # Returns
+    ///
+    /// * `Vec<usize>` label indices of the sequence, of the same length as `emissions`
+    pub fn greedy_decode(&self, emissions: &[Vec<f64>]) -> Vec<usize> {
+        let mut labels: Vec<usize> = Vec::with_capacity(emissions.len());
+        for (position, position_emissions) in emissions.iter().enumerate() {
+            let is_last = position + 1 == emissions.len();
+            let label = best(
+                position_emissions
+                    .iter()
+                    .enumerate()
+                    .map(|(label, emission)| {
+                        let transition = match labels.last() {
+                            Some(&previous) => self.transitions[previous][label],
+                            None => self.start[label],
+                        };
+                        let end = if is_last { self.end[label] } else { 0.0 };
+                        emission + transition + end
+                    }),
+            )
+            .0;
+            labels.push(label);
+        }
+        labels
+    }
+}
+
+fn to_vec(tensor: &Tensor) -> Vec<f64> {
+    Vec::<f64>::from(tensor.detach().to_kind(Double).to(Device::Cpu))
+}
+
+/// # Linear-chain CRF layer
+pub struct CRF {
+    num_labels: i64,
+    transitions: Tensor,
+    start_transitions: Tensor,
+    end_transitions: Tensor,
+}
+
+impl CRF {
+    /// Build a new `CRF`
+    ///
+    /// # Arguments
+    ///
+    /// * `p` - Variable store path for the root of the CRF layer
+    /// * `num_labels` - Number of labels of the token classification model
+    pub fn new<'p, P>(p: P, num_labels: i64) -> CRF
+    where
+        P: Borrow<nn::Path<'p>>,
+    {
+        let p = p.borrow();
+        let init = nn::Init::Uniform { lo: -0.1, up: 0.1 };
+        let transitions = p.var("transitions", &[num_labels, num_labels], init);
+        let start_transitions = p.var("start_transitions", &[num_labels], init);
+        let end_transitions = p.var("end_transitions", &[num_labels], init);
+        CRF {
+            num_labels,
+            transitions,
+            start_transitions,
+            end_transitions,
+        }
+    }
+
+    fn float_mask(emissions: &Tensor, mask: Option<&Tensor>) -> Tensor {
+        match mask {
+            Some(mask) => mask.to_kind(Float),
+            None => {
+                let size = emissions.size();
+                Tensor::ones(&[size[0], size[1]], (Float, emissions.device()))
+            }
+        }
+    }
+
+    /// Computes the log-likelihood of label sequences
+    ///
+    /// # Arguments
+    ///
+    /// * `emissions` - Label scores of shape (*batch size*, *sequence_length*, *num_labels*)
+    /// * `labels` - Label indices of shape (*batch size*, *sequence_length*)
+    /// * `mask` - Optional mask of shape (*batch size*, *sequence_length*), with value 1 for the positions to score and
+    /// 0 for the padding positions at the end of the sequences. The first position of each sequence must be valid.
+    ///
+    /// # Returns
+    ///
+    /// * `Tensor` of shape (*batch size*) with the log-likelihood of each sequence
+    pub fn log_likelihood(
+        &self,
+        emissions: &Tensor,
+        labels: &Tensor,
+        mask: Option<&Tensor>,
+    ) -> Tensor {
+        let emissions = emissions.to_kind(Float);
+        let mask = CRF::float_mask(&emissions, mask);
+        let labels = labels.to_kind(Int64);
+        let sequence_length = emissions.size()[1];
+        let flat_transitions = self.transitions.reshape(&[-1]);
+
+        let first_labels = labels.select(1, 0);
+        let mut score = self.start_transitions.index_select(0, &first_labels)
+            + emissions
+                .select(1, 0)
+                .gather(1, &first_labels.unsqueeze(-1), false)
+                .squeeze1(-1);
+        let mut alpha = self.start_transitions.unsqueeze(0) + emissions.select(1, 0);
+
+        for position in 1..sequence_length {
+            let position_mask = mask.select(1, position);
+            let previous_labels = labels.select(1, position - 1);
+            let position_labels = labels.select(1, position);
+            let position_emissions = emissions.select(1, position);
+            let transition_scores = flat_transitions
+                .index_select(0, &(previous_labels * self.num_labels + &position_labels));
+            let emission_scores = position_emissions
+                .gather(1, &position_labels.unsqueeze(-1), false)
+                .squeeze1(-1);
+            score = score + (transition_scores + emission_scores) * &position_mask;
+
+            let next_alpha = (alpha.unsqueeze(2)
+                + self.transitions.unsqueeze(0)
+                + position_emissions.unsqueeze(1))
+            .logsumexp(&[1], false);
+            let position_mask = position_mask.unsqueeze(-1);
+            alpha =
+                next_alpha * &position_mask + alpha * (position_mask.ones_like() - &position_mask);
+        }
+
+        let last_positions = mask.sum1(&[1], false, Float).to_kind(Int64) - 1;
+        let last_labels = labels
+            .gather(1, &last_positions.unsqueeze(-1), false)
+            .squeeze1(-1);
+        score = score + self.end_transitions.index_select(0, &last_labels);
+        let log_partition = (alpha + self.end_transitions.unsqueeze(0)).logsumexp(&[1], false);
+        score - log_partition
+    }
+
+    /// Training loss of the layer: negative log-likelihood of the label sequences, averaged over the batch. The
+    /// gradients flow to the transition scores and to the emissions.
+    ///
+    /// # Arguments
+    ///
+    /// * `emissions` - Label scores of shape (*batch size*, *sequence_length*, *num_labels*)
+    /// * `labels` - Label indices of shape (*batch size*, *sequence_length*)
+    /// * `mask` - Optional mask of shape (*batch size*, *sequence_length*), with value 0 for the padding positions
+    ///
+    /// # Returns
+    ///
+    /// * Scalar `Tensor` with the mean negative log-likelihood
+    pub fn loss(&self, emissions: &Tensor, labels: &Tensor, mask: Option<&Tensor>) -> Tensor {
+        -self.log_likelihood(emissions, labels, mask).mean(Float)
+    }
+
+    /// Returns the current transition scores of the layer
+    pub fn transition_scores(&self) -> TransitionScores {
+        TransitionScores {
+            transitions: to_vec(&self.transitions)
+                .chunks(self.num_labels as usize)
+                .map(<[f64]>::to_vec)
+                .collect(),
+            start: to_vec(&self.start_transitions),
+            end: to_vec(&self.end_transitions),
+        }
+    }
+
+    /// Decodes the most likely label sequences with the Viterbi algorithm
+    ///
+    /// # Arguments
+    ///
+    /// * `emissions` - Label scores of shape (*batch size*, *sequence_length*, *num_labels*)
+    /// * `mask` - Optional mask of shape (*batch size*, *sequence_length*), with value 0 for the padding positions at
+    /// the end of the sequences
+    ///
+    /// # Returns
+    ///
+    /// * `Vec<Vec<i64>>` label indices of each sequence, excluding the padding positions
+    pub fn decode(&self, emissions: &Tensor, mask: Option<&Tensor>) -> Vec<Vec<i64>> {
+        let num_labels = self.num_labels as usize;
+        let transition_scores = self.transition_scores();
+        let lengths = Vec::<i64>::from(
+            CRF::float_mask(emissions, mask)
+                .sum1(&[1], false, Float)
+                .to_kind(Int64)
+                .to(Device::Cpu),
+        );
+
+        lengths
+            .iter()
+            .enumerate()
+            .map(|(sequence_index, &length)| {
+                let sequence_emissions =
+                    to_vec(&emissions.get(sequence_index as i64).narrow(0, 0, length))
+                        .chunks(num_labels)
+                        .map(|row| row.to_vec())
+                        .collect::<Vec<Vec<f64>>>();
+                transition_scores
+                    .viterbi_decode(&sequence_emissions)
+                    .into_iter()
+                    .map(|label| label as i64)
+                    .collect()
+            })
+            .collect()
+    }
+}
@@ -0,0 +1,156 @@
+// Copyright 2021 Guillaume Becquin
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//     http://www.apache.org/licenses/LICENSE-2.0
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! # Forward hooks
+//!
+//! Closures registered on a model and called with the intermediate tensors computed during the forward pass, for
+//! debugging, feature extraction or probing without modifying the model code. Each intermediate tensor is identified
+//! by its module path relative to the base model, following the variable names of the weights. The BERT base model
+//! (and the RoBERTa and Splinter models built upon it) exposes the following tensors:
+//! - `embeddings`: output of the embeddings layer
+//! - `encoder.layer.{i}`: output hidden state of the encoder layer `i`
+//! - `encoder.layer.{i}.attention_weights`: attention weights of the encoder layer `i`, if the model outputs attentions
+//! - `pooler`: output of the pooler, if the model has one
+//!
+//! Hooks are registered for a module path, or for all paths starting with a prefix using a trailing `*`
+//! (e.g. `encoder.layer.*`). The tensors are passed by reference: hooks that need to keep them should store a
+//! (shallow) copy.
+//!
+//! ```no_run
+//! # fn main() -> anyhow::Result<()> {
+//! use rust_bert::bert::{BertConfig, BertForSequenceClassification};
+//! use rust_bert::Config;
+//! use std::path::Path;
+//! use std::sync::{Arc, Mutex};
+//! use tch::{nn, Device, Tensor};
+//!
+//! let vs = nn::VarStore::new(Device::Cpu);
+//! let config = BertConfig::from_file(Path::new("path/to/config.json"));
+//! let mut bert_model = BertForSequenceClassification::new(&vs.root(), &config);
+//!
+//! let layer_outputs: Arc<Mutex<Vec<(String, Tensor)>>> = Arc::new(Mutex::new(vec![]));
+//! let captured = layer_outputs.clone();
+//! let hook_id = bert_model.hooks_mut().register("encoder.layer.*", move |name, tensor| {
+//!     captured
+//!         .lock()
+//!         .unwrap()
+//!         .push((name.to_string(), tensor.copy()));
+//! });
+//! // ... run the model, layer_outputs contains the output of each layer
+//! bert_model.hooks_mut().remove(hook_id);
+//! # Ok(())
+//! # }
+//! ```
+
+use tch::Tensor;
+
+/// Identifier of a registered hook, used to remove it
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct HookId(usize);
+
+struct ForwardHook {
+    id: HookId,
+    pattern: String,
+    function: Box<dyn Fn(&str, &Tensor) + Send>,
+}
+
+impl ForwardHook {
+    fn matches(&self, name: &str) -> bool {
+        match self.pattern.strip_suffix('*') {
+            Some(prefix) => name.starts_with(prefix),
+            None => name == self.pattern,
+        }
+    }
+}
+
+/// # Collection of forward hooks registered on a model
+/// Hooks are called in their registration order. Models without registered hooks do not pay for the naming of the
+/// intermediate tensors.
+#[derive(Default)]
+pub struct ForwardHooks {
+    hooks: Vec<ForwardHook>,
+    next_id: usize,
+}
+
+impl ForwardHooks {
+    /// Creates an empty hook collection
+    pub fn new() -> ForwardHooks {
+        ForwardHooks::default()
+    }
+
+    /// Registers a hook called with the name and value of the tensors matching `pattern`: an exact module path, or a
+    /// prefix followed by `*`
+    ///
+    /// # Arguments
+    ///
+    /// * `pattern` - module path or prefix pattern of the tensors to hook
+    /// * `function` - closure called with the module path and value of each matching tensor
+    pub fn register<F>(&mut self, pattern: &str, function: F) -> HookId
+    where
+        F: Fn(&str, &Tensor) + Send + 'static,
+    {
+        let id = HookId(self.next_id);
+        self.next_id += 1;
+        self.hooks.push(ForwardHook {
+            id,
+            pattern: pattern.to_string(),
+            function: Box::new(function),
+        });
+        id
+    }
+
+    /// Removes a hook, returning true if it was registered
+    pub fn remove(&mut self, id: HookId) -> bool {
+        let num_hooks = self.hooks.len();
+        self.hooks.retain(|hook| hook.id != id);
+        self.hooks.len() != num_hooks
+    }
+
+    /// Removes all hooks
+    pub fn clear(&mut self) {
+        self.hooks.clear();
+    }
+
+    /// Returns the number of registered hooks
+    pub fn len(&self) -> usize {
+        self.hooks.len()
+    }
+
+    /// Returns true if no hook is registered
+    pub fn is_empty(&self) -> bool {
+        self.hooks.is_empty()
+    }
+
+    /// Calls the hooks matching the tensor name. The name is only built if at least one hook is registered.
+    pub(crate) fn run<N>(&self, name: N, tensor: &Tensor)
+    where
+        N: FnOnce() -> String,
+    {
+        if self.hooks.is_empty() {
+            return;
+        }
+        let name = name();
+        for hook in self.hooks.iter().filter(|hook| hook.matches(&name)) {
+            (hook.function)(&name, tensor);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    #[ignore] // no need to run, compilation is enough to verify it is Send
+    fn test() {
+        let _: Box<dyn Send> = Box::new(ForwardHooks::new());
+    }
+}
@@ -0,0 +1,124 @@
+// Copyright 2021 Guillaume Becquin
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//     http://www.apache.org/licenses/LICENSE-2.0
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! # Language model heads
+//!
+//! The output projection of the GPT2, BART and T5 generation models is either tied to the input embeddings of the model
+//! or an independent linear layer. The models expose `tie_lm_head` and `untie_lm_head` to switch between the two, and
+//! `set_lm_head` to replace the head with a user-provided linear layer, for example a projection restricted to a
+//! subset of the vocabulary (the logits are then indexed by position in the restricted vocabulary).
+//!
+//! ```no_run
+//! # fn main() -> anyhow::Result<()> {
+//! use rust_bert::gpt2::{GPT2LMHeadModel, Gpt2Config};
+//! use rust_bert::Config;
+//! use std::path::Path;
+//! use tch::{nn, Device};
+//!
+//! let mut vs = nn::VarStore::new(Device::Cpu);
+//! let config = Gpt2Config::from_file(Path::new("path/to/config.json"));
+//! let mut gpt2_model = GPT2LMHeadModel::new(&vs.root(), &config);
+//! vs.load("path/to/model.ot")?;
+//!
+//! // Share the weights of the input embeddings and of the output projection
+//! gpt2_model.tie_lm_head();
+//! assert!(gpt2_model.lm_head().is_tied());
+//! # Ok(())
+//! # }
+//! ```
+
+use crate::common::weights::resize_token_dimension;
+use crate::RustBertError;
+use std::borrow::Borrow;
+use tch::{nn, no_grad, Tensor};
+
+/// # Output projection of a language model
+pub enum LMHead {
+    /// Projection on the input embeddings of the model
+    Tied,
+    /// Linear layer independent of the input embeddings
+    Linear(nn::Linear),
+}
+
+impl LMHead {
+    /// Creates a linear head initialized with a copy of the embeddings provided, with variables created under the
+    /// variable store path provided.
+    pub(crate) fn untied_from<'p, P>(p: P, embeddings: &Tensor) -> Result<LMHead, RustBertError>
+    where
+        P: Borrow<nn::Path<'p>>,
+    {
+        let size = embeddings.size();
+        let mut linear = nn::linear(
+            p,
+            size[1],
+            size[0],
+            nn::LinearConfig {
+                bias: false,
+                ..Default::default()
+            },
+        );
+        no_grad(|| linear.ws.f_copy_(embeddings))?;
+        Ok(LMHead::Linear(linear))
+    }
+
+    /// Checks that a linear layer can be used as a head for hidden states of dimension `hidden_size`
+    pub(crate) fn check_linear(linear: &nn::Linear, hidden_size: i64) -> Result<(), RustBertError> {
+        let input_size = linear.ws.size()[1];
+        if input_size != hidden_size {
+            return Err(RustBertError::ValueError(format!(
+                "The input dimension of the language model head ({}) does not match the hidden size of the model ({})",
+                input_size, hidden_size
+            )));
+        }
+        Ok(())
+    }
+
+    /// Returns `true` if the head is tied to the input embeddings of the model
+    pub fn is_tied(&self) -> bool {
+        matches!(self, LMHead::Tied)
+    }
+
+    /// Projects the hidden states provided on the vocabulary
+    ///
+    /// # Arguments
+    ///
+    /// * `hidden_states` - hidden states of shape (*batch size*, *sequence_length*, *hidden_size*)
+    /// * `embeddings` - input embeddings of the model, used if the head is tied
+    pub fn forward(&self, hidden_states: &Tensor, embeddings: &Tensor) -> Tensor {
+        match self {
+            LMHead::Tied => hidden_states.linear::<Tensor>(embeddings, None),
+            LMHead::Linear(linear) => hidden_states.apply(linear),
+        }
+    }
+
+    pub(crate) fn resize(
+        &mut self,
+        new_num_tokens: i64,
+        init_std: f64,
+    ) -> Result<(), RustBertError> {
+        if let LMHead::Linear(linear) = self {
+            resize_token_dimension(&mut linear.ws, new_num_tokens, Some(init_std))?;
+            resize_token_dimension(&mut linear.bs, new_num_tokens, None)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    #[ignore] // no need to run, compilation is enough to verify it is Send
+    fn test() {
+        let _: Box<dyn Send> = Box::new(LMHead::Tied);
+    }
+}
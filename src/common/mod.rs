@@ -1,10 +1,24 @@
 pub(crate) mod activations;
+pub mod adapters;
+pub mod classification_head;
 pub mod config;
+pub mod crf;
 pub(crate) mod dropout;
 pub mod error;
+pub mod hooks;
 pub(crate) mod linear;
+pub mod lm_head;
+pub mod model_output;
+pub mod prefix_tuning;
+pub mod profiling;
 pub mod resources;
+pub mod siamese;
+pub mod soft_prompt;
 pub(crate) mod summary;
+pub mod task_registry;
+pub mod testing;
+pub(crate) mod weights;
 
 pub use activations::Activation;
 pub use config::Config;
+pub use model_output::ModelOutput;
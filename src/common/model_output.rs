@@ -0,0 +1,52 @@
+// Copyright 2021 Guillaume Becquin
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//     http://www.apache.org/licenses/LICENSE-2.0
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! # Common accessors for model outputs
+//!
+//! Each architecture defines its own output containers, with fields named after the head that produced them
+//! (`logits`, `prediction_scores`, `decoder_output`...). The `ModelOutput` trait gives a common read-only view on these
+//! outputs, so that downstream code (e.g. an explanation module or a benchmarking harness) can be written once for
+//! all architectures.
+//!
+//! ```no_run
+//! use rust_bert::ModelOutput;
+//!
+//! fn num_attention_layers<O: ModelOutput>(output: &O) -> usize {
+//!     output.attentions().map_or(0, |attentions| attentions.len())
+//! }
+//! ```
+
+use tch::Tensor;
+
+/// # Common trait for the outputs of the models
+/// Hidden states and attentions are only returned if the model was configured to output them. For encoder-decoder
+/// models they refer to the decoder layers.
+pub trait ModelOutput {
+    /// Returns the hidden states of all layers, if the model was configured to output them
+    fn hidden_states(&self) -> Option<Vec<&Tensor>>;
+
+    /// Returns the attention weights of all layers, if the model was configured to output them. Layer groups
+    /// (e.g. ALBERT) are flattened.
+    fn attentions(&self) -> Option<Vec<&Tensor>>;
+
+    /// Returns the output logits (or scores) of the task-specific head. `None` for base models without a head and for
+    /// question answering outputs (see `start_end_logits`).
+    fn logits(&self) -> Option<&Tensor>;
+
+    /// Returns the start and end logits of question answering outputs
+    fn start_end_logits(&self) -> Option<(&Tensor, &Tensor)> {
+        None
+    }
+}
+
+pub(crate) fn tensor_refs(values: &Option<Vec<Tensor>>) -> Option<Vec<&Tensor>> {
+    values.as_ref().map(|values| values.iter().collect())
+}
@@ -0,0 +1,177 @@
+// Copyright 2021 Guillaume Becquin
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//     http://www.apache.org/licenses/LICENSE-2.0
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! # Prefix-tuning
+//!
+//! Prefix-tuning ([Prefix-Tuning: Optimizing Continuous Prompts for Generation](https://arxiv.org/abs/2101.00190) Li, Liang, 2021)
+//! learns key and value vectors prepended to the keys and values of the self-attention of each decoder layer.
+//! The prefix is injected as the initial cached state of the decoder layers (past keys and values), so that every decoding
+//! step attends to the prefix in addition to the generated positions. The prefix can be set on `GPT2LMHeadModel` and
+//! `T5ForConditionalGeneration` models and on the corresponding generators.
+//!
+//! The prefix weights are expected to be stored with the names `key.{layer_index}` and `value.{layer_index}`, each
+//! of shape (*num_heads*, *prefix_length*, *head_dim*).
+//!
+//! ```no_run
+//! # fn main() -> anyhow::Result<()> {
+//! use rust_bert::pipelines::generation_utils::{GPT2Generator, GenerateConfig, LanguageGenerator};
+//! use rust_bert::prefix_tuning::PrefixTuning;
+//! use tch::Device;
+//!
+//! let mut gpt2_generator = GPT2Generator::new(GenerateConfig::default())?;
+//! let prefix = PrefixTuning::load("path/to/prefix.ot", Device::cuda_if_available())?;
+//! gpt2_generator.set_prefix(Some(prefix))?;
+//! let output = gpt2_generator.generate(Some(vec!["The dog"]), None);
+//! # Ok(())
+//! # }
+//! ```
+
+use crate::common::error::RustBertError;
+use std::collections::HashMap;
+use std::path::Path;
+use tch::{Device, Tensor};
+
+/// # Prefix-tuning key and value vectors
+/// Holds for each layer a key and value tensor of shape (*num_heads*, *prefix_length*, *head_dim*)
+pub struct PrefixTuning {
+    key_values: Vec<(Tensor, Tensor)>,
+}
+
+impl PrefixTuning {
+    /// Creates a new prefix from the key and value tensors of each layer
+    ///
+    /// # Arguments
+    ///
+    /// * `key_values` - vector of (key, value) tensors of shape (*num_heads*, *prefix_length*, *head_dim*) for each layer
+    pub fn new(key_values: Vec<(Tensor, Tensor)>) -> Result<PrefixTuning, RustBertError> {
+        let expected_size = match key_values.first() {
+            Some((key, _)) => key.size(),
+            None => {
+                return Err(RustBertError::ValueError(
+                    "The prefix must contain at least one layer".into(),
+                ));
+            }
+        };
+        if expected_size.len() != 3 {
+            return Err(RustBertError::ValueError(format!(
+                "Prefix keys and values must be of shape (num_heads, prefix_length, head_dim), got {:?}",
+                expected_size
+            )));
+        }
+        for (layer_index, (key, value)) in key_values.iter().enumerate() {
+            if (key.size() != expected_size) | (value.size() != expected_size) {
+                return Err(RustBertError::ValueError(format!(
+                    "Inconsistent prefix shapes for layer {}: expected {:?}, got {:?} (key) and {:?} (value)",
+                    layer_index,
+                    expected_size,
+                    key.size(),
+                    value.size()
+                )));
+            }
+        }
+        Ok(PrefixTuning { key_values })
+    }
+
+    /// Loads prefix-tuning weights stored as `key.{layer_index}` and `value.{layer_index}` tensors
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - path to the prefix weights
+    /// * `device` - device on which the weights are loaded
+    pub fn load<P: AsRef<Path>>(path: P, device: Device) -> Result<PrefixTuning, RustBertError> {
+        let mut tensors: HashMap<String, Tensor> = Tensor::load_multi_with_device(path, device)?
+            .into_iter()
+            .collect();
+        let mut key_values = vec![];
+        let mut layer_index = 0;
+        while let Some(key) = tensors.remove(&format!("key.{}", layer_index)) {
+            let value = tensors
+                .remove(&format!("value.{}", layer_index))
+                .ok_or_else(|| {
+                    RustBertError::ValueError(format!(
+                        "Missing prefix value for layer {}",
+                        layer_index
+                    ))
+                })?;
+            key_values.push((key, value));
+            layer_index += 1;
+        }
+        PrefixTuning::new(key_values)
+    }
+
+    /// Saves the prefix weights to a file
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<(), RustBertError> {
+        let mut named_tensors = Vec::with_capacity(2 * self.key_values.len());
+        for (layer_index, (key, value)) in self.key_values.iter().enumerate() {
+            named_tensors.push((format!("key.{}", layer_index), key));
+            named_tensors.push((format!("value.{}", layer_index), value));
+        }
+        Ok(Tensor::save_multi(&named_tensors, path)?)
+    }
+
+    /// Returns the number of layers of the prefix
+    pub fn num_layers(&self) -> usize {
+        self.key_values.len()
+    }
+
+    /// Returns the number of prefix positions
+    pub fn prefix_length(&self) -> i64 {
+        self.key_values[0].0.size()[1]
+    }
+
+    /// Returns the (key, value) tensors of each layer expanded to the batch size provided, of shape
+    /// (*batch size*, *num_heads*, *prefix_length*, *head_dim*)
+    pub fn batch_key_values(&self, batch_size: i64) -> Vec<(Tensor, Tensor)> {
+        self.key_values
+            .iter()
+            .map(|(key, value)| {
+                (
+                    key.unsqueeze(0).expand(&[batch_size, -1, -1, -1], true),
+                    value.unsqueeze(0).expand(&[batch_size, -1, -1, -1], true),
+                )
+            })
+            .collect()
+    }
+
+    /// Extends an attention mask of shape (*batch size*, *sequence_length*) to cover the prefix positions
+    pub fn extend_attention_mask(&self, attention_mask: &Tensor) -> Tensor {
+        let prefix_mask = Tensor::ones(
+            &[attention_mask.size()[0], self.prefix_length()],
+            (attention_mask.kind(), attention_mask.device()),
+        );
+        Tensor::cat(&[&prefix_mask, attention_mask], 1)
+    }
+
+    pub(crate) fn check_num_layers(&self, num_layers: usize) -> Result<(), RustBertError> {
+        if self.num_layers() != num_layers {
+            return Err(RustBertError::ValueError(format!(
+                "The prefix has {} layers, the model has {} layers",
+                self.num_layers(),
+                num_layers
+            )));
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use tch::Kind;
+
+    #[test]
+    #[ignore] // no need to run, compilation is enough to verify it is Send
+    fn test() {
+        let key = Tensor::zeros(&[12, 10, 64], (Kind::Float, Device::Cpu));
+        let value = Tensor::zeros(&[12, 10, 64], (Kind::Float, Device::Cpu));
+        let _: Box<dyn Send> = Box::new(PrefixTuning::new(vec![(key, value)]));
+    }
+}
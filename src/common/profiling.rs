@@ -0,0 +1,311 @@
+// Copyright 2021 Guillaume Becquin
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//     http://www.apache.org/licenses/LICENSE-2.0
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! # Profiling
+//!
+//! Opt-in profiler recording the forward time and input shapes of the instrumented modules during a run, to locate
+//! the bottlenecks of a model or pipeline. The modules open a `ProfileScope` for the duration of their forward pass;
+//! scopes are only recorded on the thread running `Profiler::profile`, and cost a thread-local lookup otherwise.
+//! The following modules are instrumented:
+//! - T5: `encoder` and `decoder` stacks, `block.{i}`, `self_attention`, `cross_attention`, `relative_attention_bias`
+//! and `feed_forward`
+//! - BERT (and the models built upon it): `embeddings`, `encoder.layer.{i}` and `pooler`
+//! - text generation: `decode_step`, for each forward pass of the generation loop
+//!
+//! Custom models can be instrumented with `scope`. The report can be exported as folded stacks (one line per call
+//! stack with its self time in microseconds, the input format of `inferno` and of the `flamegraph.pl` script), or as
+//! JSON with the individual calls and a per-module summary.
+//!
+//! CUDA kernels are executed asynchronously: by default the profiler waits for the device to complete the queued
+//! work at the start and end of each scope, so that the times are attributed to the right module. This adds some
+//! overhead, and can be disabled with `synchronize: false`.
+//!
+//! ```no_run
+//! # fn main() -> anyhow::Result<()> {
+//! use rust_bert::pipelines::common::ModelType;
+//! use rust_bert::pipelines::summarization::{SummarizationConfig, SummarizationModel};
+//! use rust_bert::profiling::Profiler;
+//! use rust_bert::resources::{RemoteResource, Resource};
+//! use rust_bert::t5::{T5ConfigResources, T5ModelResources, T5VocabResources};
+//!
+//! let summarization_model = SummarizationModel::new(SummarizationConfig {
+//!     model_type: ModelType::T5,
+//!     model_resource: Resource::Remote(RemoteResource::from_pretrained(T5ModelResources::T5_SMALL)),
+//!     config_resource: Resource::Remote(RemoteResource::from_pretrained(T5ConfigResources::T5_SMALL)),
+//!     vocab_resource: Resource::Remote(RemoteResource::from_pretrained(T5VocabResources::T5_SMALL)),
+//!     merges_resource: Resource::Remote(RemoteResource::from_pretrained(T5VocabResources::T5_SMALL)),
+//!     ..Default::default()
+//! })?;
+//!
+//! let (_summaries, report) = Profiler::default().profile(|| {
+//!     summarization_model.summarize(&["In findings published Tuesday in Cornell University's arXiv..."])
+//! });
+//! for module in report.summary().iter().take(5) {
+//!     println!("{}: {} calls, {:?} self time", module.path, module.calls, module.self_time);
+//! }
+//! std::fs::write("profile.folded", report.to_folded_stacks())?;
+//! # Ok(())
+//! # }
+//! ```
+
+use serde_json::{json, Value};
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+use tch::{Device, Kind, Tensor};
+
+struct Frame {
+    name: String,
+    start: Instant,
+    input_shapes: Vec<Vec<i64>>,
+    device: Option<Device>,
+    children: Duration,
+}
+
+struct ProfilerState {
+    synchronize: bool,
+    origin: Instant,
+    stack: Vec<Frame>,
+    events: Vec<ProfileEvent>,
+}
+
+thread_local! {
+    static PROFILER: RefCell<Option<ProfilerState>> = RefCell::new(None);
+}
+
+// Waits for the kernels queued on a CUDA device: reading a value back to the host only completes once the work
+// queued before it is done
+fn synchronize(device: Option<Device>) {
+    if let Some(device @ Device::Cuda(_)) = device {
+        let _ = Tensor::zeros(&[1], (Kind::Float, device)).double_value(&[0]);
+    }
+}
+
+/// # Call of an instrumented module
+#[derive(Debug, Clone, PartialEq)]
+pub struct ProfileEvent {
+    /// Names of the enclosing scopes, from the outermost one, ending with the name of the module
+    pub path: Vec<String>,
+    /// Shapes of the inputs of the module
+    pub input_shapes: Vec<Vec<i64>>,
+    /// Start of the call, relative to the start of the profiled run
+    pub start: Duration,
+    /// Duration of the call, including the nested scopes
+    pub duration: Duration,
+    /// Duration of the call, excluding the nested scopes
+    pub self_duration: Duration,
+}
+
+/// # Calls of a module aggregated over a run
+#[derive(Debug, Clone, PartialEq)]
+pub struct ModuleSummary {
+    /// Path of the module, with the scope names separated by `;`
+    pub path: String,
+    /// Number of calls
+    pub calls: usize,
+    /// Total duration of the calls, including the nested scopes
+    pub total_time: Duration,
+    /// Total duration of the calls, excluding the nested scopes
+    pub self_time: Duration,
+}
+
+/// # Report of a profiled run
+#[derive(Debug, Clone, Default)]
+pub struct ProfileReport {
+    /// Calls of the instrumented modules, sorted by start time
+    pub events: Vec<ProfileEvent>,
+}
+
+impl ProfileReport {
+    /// Aggregates the calls per module path, sorted by decreasing self time
+    pub fn summary(&self) -> Vec<ModuleSummary> {
+        let mut summaries: HashMap<String, ModuleSummary> = HashMap::new();
+        for event in &self.events {
+            let path = event.path.join(";");
+            let summary = summaries
+                .entry(path.clone())
+                .or_insert_with(|| ModuleSummary {
+                    path,
+                    calls: 0,
+                    total_time: Duration::default(),
+                    self_time: Duration::default(),
+                });
+            summary.calls += 1;
+            summary.total_time += event.duration;
+            summary.self_time += event.self_duration;
+        }
+        let mut summaries = summaries
+            .into_iter()
+            .map(|(_, summary)| summary)
+            .collect::<Vec<_>>();
+        summaries.sort_by(|summary_1, summary_2| {
+            summary_2
+                .self_time
+                .cmp(&summary_1.self_time)
+                .then_with(|| summary_1.path.cmp(&summary_2.path))
+        });
+        summaries
+    }
+
+    /// Exports the report as folded stacks: one line per module path with its total self time in microseconds,
+    /// e.g. `decoder;block.0;self_attention 1520`. This is the input format of flame graph generators.
+    pub fn to_folded_stacks(&self) -> String {
+        let mut summaries = self.summary();
+        summaries.sort_by(|summary_1, summary_2| summary_1.path.cmp(&summary_2.path));
+        summaries
+            .iter()
+            .map(|summary| format!("{} {}\n", summary.path, summary.self_time.as_micros()))
+            .collect()
+    }
+
+    /// Exports the report as JSON, with the individual calls (`events`) and the per-module `summary`. Times are in
+    /// microseconds.
+    pub fn to_json(&self) -> Value {
+        let micros = |duration: Duration| duration.as_secs_f64() * 1e6;
+        let events = self
+            .events
+            .iter()
+            .map(|event| {
+                json!({
+                    "path": event.path.join(";"),
+                    "input_shapes": event.input_shapes,
+                    "start_us": micros(event.start),
+                    "duration_us": micros(event.duration),
+                    "self_duration_us": micros(event.self_duration),
+                })
+            })
+            .collect::<Vec<Value>>();
+        let summary = self
+            .summary()
+            .iter()
+            .map(|summary| {
+                json!({
+                    "path": summary.path,
+                    "calls": summary.calls,
+                    "total_time_us": micros(summary.total_time),
+                    "self_time_us": micros(summary.self_time),
+                })
+            })
+            .collect::<Vec<Value>>();
+        json!({ "events": events, "summary": summary })
+    }
+}
+
+/// # Profiler recording the instrumented modules called during a run
+#[derive(Debug, Clone, Copy)]
+pub struct Profiler {
+    /// Wait for the CUDA devices to complete the queued work at the boundaries of each scope (default: true)
+    pub synchronize: bool,
+}
+
+impl Default for Profiler {
+    fn default() -> Profiler {
+        Profiler { synchronize: true }
+    }
+}
+
+impl Profiler {
+    /// Runs a closure, recording the instrumented modules called on the current thread
+    ///
+    /// # Arguments
+    ///
+    /// * `run` - closure to profile (e.g. a call to a pipeline)
+    ///
+    /// # Returns
+    ///
+    /// * Output of the closure and `ProfileReport` of the run
+    pub fn profile<R, F: FnOnce() -> R>(&self, run: F) -> (R, ProfileReport) {
+        let state = ProfilerState {
+            synchronize: self.synchronize,
+            origin: Instant::now(),
+            stack: vec![],
+            events: vec![],
+        };
+        let previous = PROFILER.with(|profiler| profiler.borrow_mut().replace(state));
+        let output = run();
+        let state =
+            PROFILER.with(|profiler| std::mem::replace(&mut *profiler.borrow_mut(), previous));
+        let mut events = state.map(|state| state.events).unwrap_or_default();
+        events.sort_by_key(|event| event.start);
+        (output, ProfileReport { events })
+    }
+}
+
+/// # Scope of an instrumented module
+/// The module call is recorded when the scope is dropped.
+#[must_use]
+pub struct ProfileScope {
+    active: bool,
+}
+
+/// Opens the profiling scope of a module, recorded until the returned `ProfileScope` is dropped. The name is only
+/// computed if a `Profiler` is running on the current thread.
+///
+/// # Arguments
+///
+/// * `name` - closure returning the name of the module (e.g. `|| format!("block.{}", layer_index)`)
+/// * `inputs` - inputs of the module, whose shapes are recorded and whose device is synchronized
+pub fn scope<N>(name: N, inputs: &[&Tensor]) -> ProfileScope
+where
+    N: FnOnce() -> String,
+{
+    PROFILER.with(|profiler| match profiler.borrow_mut().as_mut() {
+        Some(state) => {
+            let device = inputs.first().map(|input| input.device());
+            if state.synchronize {
+                synchronize(device);
+            }
+            state.stack.push(Frame {
+                name: name(),
+                start: Instant::now(),
+                input_shapes: inputs.iter().map(|input| input.size()).collect(),
+                device,
+                children: Duration::default(),
+            });
+            ProfileScope { active: true }
+        }
+        None => ProfileScope { active: false },
+    })
+}
+
+impl Drop for ProfileScope {
+    fn drop(&mut self) {
+        if !self.active {
+            return;
+        }
+        PROFILER.with(|profiler| {
+            if let Some(state) = profiler.borrow_mut().as_mut() {
+                if let Some(frame) = state.stack.pop() {
+                    if state.synchronize {
+                        synchronize(frame.device);
+                    }
+                    let duration = frame.start.elapsed();
+                    if let Some(parent) = state.stack.last_mut() {
+                        parent.children += duration;
+                    }
+                    let mut path = state
+                        .stack
+                        .iter()
+                        .map(|parent| parent.name.clone())
+                        .collect::<Vec<String>>();
+                    path.push(frame.name);
+                    state.events.push(ProfileEvent {
+                        path,
+                        input_shapes: frame.input_shapes,
+                        start: frame.start.duration_since(state.origin),
+                        duration,
+                        self_duration: duration.checked_sub(frame.children).unwrap_or_default(),
+                    });
+                }
+            }
+        });
+    }
+}
@@ -0,0 +1,151 @@
+// Copyright 2021 Guillaume Becquin
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//     http://www.apache.org/licenses/LICENSE-2.0
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use crate::common::error::RustBertError;
+use crate::common::resources::backend::{
+    download_response, metadata_access_token, percent_encode, request_version, split_object_url,
+    ResourceBackend,
+};
+use std::env;
+use std::io::Write;
+
+const STORAGE_API_VERSION: &str = "2020-04-08";
+
+#[derive(Clone, PartialEq)]
+/// # Credentials of the Azure Blob Storage requests
+pub enum AzureCredentials {
+    /// Shared access signature appended to the blob URLs (without the leading `?`)
+    SasToken(String),
+    /// OAuth 2.0 access token for the `https://storage.azure.com/` resource
+    AccessToken(String),
+    /// Token of the managed identity of the instance, requested from the instance metadata
+    /// service for each download. The client ID selects a user-assigned identity. Requests are
+    /// unauthenticated outside of Azure.
+    ManagedIdentity { client_id: Option<String> },
+    /// Unauthenticated requests, for public containers
+    Anonymous,
+}
+
+impl AzureCredentials {
+    /// Resolves the credentials from the `AZURE_STORAGE_SAS_TOKEN` or `AZURE_STORAGE_ACCESS_TOKEN`
+    /// environment variables, then from the managed identity of the instance (user-assigned if
+    /// `AZURE_CLIENT_ID` is set)
+    pub fn from_default_chain() -> AzureCredentials {
+        if let Ok(sas_token) = env::var("AZURE_STORAGE_SAS_TOKEN") {
+            AzureCredentials::SasToken(sas_token.trim_start_matches('?').to_string())
+        } else if let Ok(token) = env::var("AZURE_STORAGE_ACCESS_TOKEN") {
+            AzureCredentials::AccessToken(token)
+        } else {
+            AzureCredentials::ManagedIdentity {
+                client_id: env::var("AZURE_CLIENT_ID").ok(),
+            }
+        }
+    }
+
+    fn access_token(&self) -> Option<String> {
+        match self {
+            AzureCredentials::AccessToken(token) => Some(token.clone()),
+            AzureCredentials::ManagedIdentity { client_id } => {
+                let mut token_url = "http://169.254.169.254/metadata/identity/oauth2/token\
+                     ?api-version=2018-02-01&resource=https%3A%2F%2Fstorage.azure.com%2F"
+                    .to_string();
+                if let Some(client_id) = client_id {
+                    token_url.push_str(&format!("&client_id={}", percent_encode(client_id, false)));
+                }
+                metadata_access_token(&token_url, ("Metadata", "true"))
+            }
+            AzureCredentials::SasToken(_) | AzureCredentials::Anonymous => None,
+        }
+    }
+}
+
+/// # Azure Blob Storage backend
+/// Downloads the resources with URLs `az://<container>/<blob>` from a storage account.
+pub struct AzureBackend {
+    endpoint: String,
+    credentials: AzureCredentials,
+    client: reqwest::blocking::Client,
+}
+
+impl AzureBackend {
+    /// Creates a new Azure Blob Storage backend
+    ///
+    /// # Arguments
+    ///
+    /// * `account` - Name of the storage account
+    /// * `credentials` - `AzureCredentials` used to authenticate the requests
+    pub fn new(account: &str, credentials: AzureCredentials) -> AzureBackend {
+        AzureBackend {
+            endpoint: format!("https://{}.blob.core.windows.net", account),
+            credentials,
+            client: reqwest::blocking::Client::new(),
+        }
+    }
+
+    /// Creates a backend for the storage account `AZURE_STORAGE_ACCOUNT` with the credentials of
+    /// the default chain (see `AzureCredentials::from_default_chain`)
+    pub fn from_env() -> AzureBackend {
+        let account = env::var("AZURE_STORAGE_ACCOUNT").unwrap_or_default();
+        AzureBackend::new(&account, AzureCredentials::from_default_chain())
+    }
+
+    /// Sets a custom endpoint (e.g. an Azurite emulator `http://127.0.0.1:10000/devstoreaccount1`)
+    pub fn with_endpoint(mut self, endpoint: &str) -> AzureBackend {
+        self.endpoint = endpoint.trim_end_matches('/').to_string();
+        self
+    }
+
+    /// Builds an authenticated request for a blob
+    fn blob_request(
+        &self,
+        method: reqwest::Method,
+        url: &str,
+    ) -> Result<reqwest::blocking::RequestBuilder, RustBertError> {
+        let (container, blob) = split_object_url(url, "az")?;
+        let mut request_url = format!(
+            "{}/{}/{}",
+            self.endpoint,
+            container,
+            percent_encode(blob, true)
+        );
+        if let AzureCredentials::SasToken(sas_token) = &self.credentials {
+            request_url.push('?');
+            request_url.push_str(sas_token);
+        }
+        let mut request = self
+            .client
+            .request(method, &request_url)
+            .header("x-ms-version", STORAGE_API_VERSION);
+        if let Some(token) = self.credentials.access_token() {
+            request = request.bearer_auth(token);
+        }
+        Ok(request)
+    }
+}
+
+impl ResourceBackend for AzureBackend {
+    fn scheme(&self) -> &str {
+        "az"
+    }
+
+    fn version(&self, url: &str) -> Result<Option<String>, RustBertError> {
+        request_version(self.blob_request(reqwest::Method::HEAD, url)?, url, "etag")
+    }
+
+    fn download(&self, url: &str, writer: &mut dyn Write) -> Result<Option<String>, RustBertError> {
+        download_response(
+            self.blob_request(reqwest::Method::GET, url)?,
+            url,
+            "etag",
+            writer,
+        )
+    }
+}
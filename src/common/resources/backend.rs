@@ -0,0 +1,307 @@
+// Copyright 2021 Guillaume Becquin
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//     http://www.apache.org/licenses/LICENSE-2.0
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use crate::common::error::RustBertError;
+use crate::common::resources::{register_cached_file, CacheLayout, CACHE_LAYOUT};
+use lazy_static::lazy_static;
+use sha2::{Digest, Sha256};
+use std::fs::{self, File};
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, RwLock};
+
+/// # Storage backend for remote resources
+/// Downloads the resources whose URL starts with `<scheme>://`, for example from an object storage
+/// service. The backends for S3 (`s3://bucket/key`), Google Cloud Storage (`gs://bucket/object`)
+/// and Azure Blob Storage (`az://container/blob`) are available with the `s3`, `gcs` and `azure`
+/// features, and additional backends can be added with `register_backend`.
+///
+/// URLs without a registered scheme (such as `https://`) are downloaded from the Hugging Face hub
+/// or any HTTP server.
+///
+/// The version of a resource (ETag) is checked each time it is used: a new version is downloaded
+/// next to the cached ones, and the cached copy is used if the backend cannot be reached. The
+/// resources of backends without versions are expected to be immutable, and are never downloaded
+/// again once cached.
+pub trait ResourceBackend: Send + Sync {
+    /// URL scheme handled by the backend, without the `://` separator
+    fn scheme(&self) -> &str;
+
+    /// Returns the current version (ETag) of the resource at `url`, or `None` if the backend does
+    /// not version its resources
+    fn version(&self, _url: &str) -> Result<Option<String>, RustBertError> {
+        Ok(None)
+    }
+
+    /// Downloads the resource at `url`, writing its content to `writer`, and returns the version
+    /// of the content downloaded (`None` if not available)
+    fn download(&self, url: &str, writer: &mut dyn Write) -> Result<Option<String>, RustBertError>;
+}
+
+/// Counter making the names of the temporary download files unique within a process
+static DOWNLOADS: AtomicUsize = AtomicUsize::new(0);
+
+lazy_static! {
+    static ref BACKENDS: RwLock<Vec<Arc<dyn ResourceBackend>>> = RwLock::new(default_backends());
+}
+
+#[allow(unused_mut)]
+fn default_backends() -> Vec<Arc<dyn ResourceBackend>> {
+    let mut backends: Vec<Arc<dyn ResourceBackend>> = Vec::new();
+    #[cfg(feature = "s3")]
+    backends.push(Arc::new(super::s3::S3Backend::from_env()));
+    #[cfg(feature = "gcs")]
+    backends.push(Arc::new(super::gcs::GcsBackend::from_env()));
+    #[cfg(feature = "azure")]
+    backends.push(Arc::new(super::azure::AzureBackend::from_env()));
+    backends
+}
+
+/// Registers a backend for the resources with its URL scheme, replacing any backend previously
+/// registered for this scheme (including the default backends enabled by features).
+///
+/// # Arguments
+///
+/// * `backend` - `ResourceBackend` to use for the resources with its scheme
+///
+/// # Example
+///
+/// ```no_run
+/// # #[cfg(feature = "s3")]
+/// # fn main() -> anyhow::Result<()> {
+/// use rust_bert::resources::{register_backend, RemoteResource, Resource, S3Backend, S3Credentials};
+///
+/// let credentials = S3Credentials::from_profile("models")?;
+/// register_backend(S3Backend::new("eu-west-1", Some(credentials)));
+/// let weights_resource = Resource::Remote(RemoteResource::new(
+///     "s3://internal-models/distilbert-sst2/rust_model.ot",
+///     "distilbert-sst2",
+/// ));
+/// let weights_path = weights_resource.get_local_path()?;
+/// # Ok(())
+/// # }
+/// # #[cfg(not(feature = "s3"))]
+/// # fn main() {}
+/// ```
+pub fn register_backend<B: ResourceBackend + 'static>(backend: B) {
+    let mut backends = BACKENDS.write().unwrap();
+    backends.retain(|registered| registered.scheme() != backend.scheme());
+    backends.push(Arc::new(backend));
+}
+
+/// Returns the backend registered for the scheme of a URL, if any
+pub(crate) fn backend_for_url(url: &str) -> Option<Arc<dyn ResourceBackend>> {
+    let scheme = url.split("://").next()?;
+    if scheme.len() == url.len() {
+        return None;
+    }
+    BACKENDS
+        .read()
+        .unwrap()
+        .iter()
+        .find(|backend| backend.scheme() == scheme)
+        .cloned()
+}
+
+/// Downloads a resource with a backend if its current version is not present in the cache, and
+/// returns its local path. The cached files are named `<SHA-256 of the URL>.<SHA-256 of the version>`
+/// (`<SHA-256 of the URL>` for unversioned resources), in the resource subdirectory or at the root
+/// of a content-addressed cache. If the version cannot be retrieved (e.g. offline), the most
+/// recently downloaded version of the resource is returned.
+pub(crate) fn cached_backend_path(
+    backend: &dyn ResourceBackend,
+    url: &str,
+    cache_dir: PathBuf,
+    cache_subdir: &str,
+) -> Result<PathBuf, RustBertError> {
+    let cache_dir = match *CACHE_LAYOUT {
+        CacheLayout::Subdirectories => cache_dir.join(cache_subdir),
+        CacheLayout::ContentAddressed => cache_dir,
+    };
+    let url_digest = format!("{:x}", Sha256::digest(url.as_bytes()));
+    let cached_path = |version: &Option<String>| match version {
+        Some(version) => cache_dir.join(format!(
+            "{}.{:x}",
+            url_digest,
+            Sha256::digest(version.as_bytes())
+        )),
+        None => cache_dir.join(&url_digest),
+    };
+    match backend.version(url) {
+        Ok(version) => {
+            let path = cached_path(&version);
+            if path.is_file() {
+                return Ok(path);
+            }
+        }
+        Err(error) => return latest_cached_version(&cache_dir, &url_digest).ok_or(error),
+    }
+
+    fs::create_dir_all(&cache_dir)?;
+    let temporary_path = cache_dir.join(format!(
+        ".{}.{}.{}.tmp",
+        url_digest,
+        std::process::id(),
+        DOWNLOADS.fetch_add(1, Ordering::SeqCst)
+    ));
+    let download = File::create(&temporary_path)
+        .map_err(RustBertError::from)
+        .and_then(|file| {
+            let mut writer = BufWriter::new(file);
+            let version = backend.download(url, &mut writer)?;
+            writer.flush()?;
+            writer.get_ref().sync_all()?;
+            Ok(version)
+        });
+    let path = match download {
+        Ok(version) => cached_path(&version),
+        Err(error) => {
+            let _ = fs::remove_file(&temporary_path);
+            return Err(error);
+        }
+    };
+    fs::rename(&temporary_path, &path)?;
+    if *CACHE_LAYOUT == CacheLayout::ContentAddressed {
+        register_cached_file(&path)?;
+    }
+    Ok(path)
+}
+
+/// Returns the most recently modified cached version of the resource with the URL digest provided
+fn latest_cached_version(cache_dir: &Path, url_digest: &str) -> Option<PathBuf> {
+    let versioned_prefix = format!("{}.", url_digest);
+    fs::read_dir(cache_dir)
+        .ok()?
+        .filter_map(|entry| entry.ok())
+        .filter(|entry| {
+            let file_name = entry.file_name();
+            let file_name = file_name.to_string_lossy();
+            (file_name == url_digest || file_name.starts_with(&versioned_prefix))
+                && !file_name.ends_with(".json")
+                && !file_name.ends_with(".lock")
+        })
+        .filter_map(|entry| Some((entry.metadata().ok()?.modified().ok()?, entry.path())))
+        .max()
+        .map(|(_, path)| path)
+}
+
+#[cfg(any(feature = "s3", feature = "gcs", feature = "azure"))]
+/// Splits an object URL `<scheme>://<container>/<key>` into its container (bucket) and key
+pub(crate) fn split_object_url<'a>(
+    url: &'a str,
+    scheme: &str,
+) -> Result<(&'a str, &'a str), RustBertError> {
+    let invalid_url = || {
+        RustBertError::InvalidConfigurationError(format!(
+            "Invalid URL {}, expected {}://<bucket>/<key>",
+            url, scheme
+        ))
+    };
+    let path = url
+        .strip_prefix(scheme)
+        .and_then(|path| path.strip_prefix("://"))
+        .ok_or_else(invalid_url)?;
+    let mut parts = path.splitn(2, '/');
+    match (parts.next(), parts.next()) {
+        (Some(container), Some(key)) if !container.is_empty() && !key.is_empty() => {
+            Ok((container, key))
+        }
+        _ => Err(invalid_url()),
+    }
+}
+
+#[cfg(any(feature = "s3", feature = "gcs", feature = "azure"))]
+/// Percent-encodes a string, leaving the unreserved characters (and `/` if `keep_slash`) unchanged
+pub(crate) fn percent_encode(value: &str, keep_slash: bool) -> String {
+    let mut encoded = String::with_capacity(value.len());
+    for byte in value.bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => {
+                encoded.push(byte as char)
+            }
+            b'/' if keep_slash => encoded.push('/'),
+            _ => encoded.push_str(&format!("%{:02X}", byte)),
+        }
+    }
+    encoded
+}
+
+#[cfg(any(feature = "s3", feature = "gcs", feature = "azure"))]
+/// Sends a request, returning the successful response
+fn send_request(
+    request: reqwest::blocking::RequestBuilder,
+    url: &str,
+) -> Result<reqwest::blocking::Response, RustBertError> {
+    let response = request
+        .send()
+        .map_err(|error| RustBertError::FileDownloadError(format!("{}: {}", url, error)))?;
+    if !response.status().is_success() {
+        return Err(RustBertError::FileDownloadError(format!(
+            "{}: status {}",
+            url,
+            response.status()
+        )));
+    }
+    Ok(response)
+}
+
+#[cfg(any(feature = "s3", feature = "gcs", feature = "azure"))]
+fn header_value(response: &reqwest::blocking::Response, header: &str) -> Option<String> {
+    response
+        .headers()
+        .get(header)
+        .and_then(|value| value.to_str().ok())
+        .map(String::from)
+}
+
+#[cfg(any(feature = "s3", feature = "azure"))]
+/// Sends a request (e.g. `HEAD`) and returns the value of the version header of the response
+pub(crate) fn request_version(
+    request: reqwest::blocking::RequestBuilder,
+    url: &str,
+    version_header: &str,
+) -> Result<Option<String>, RustBertError> {
+    Ok(header_value(&send_request(request, url)?, version_header))
+}
+
+#[cfg(any(feature = "s3", feature = "gcs", feature = "azure"))]
+/// Sends a request and writes the body of a successful response, returning the value of its
+/// version header
+pub(crate) fn download_response(
+    request: reqwest::blocking::RequestBuilder,
+    url: &str,
+    version_header: &str,
+    writer: &mut dyn Write,
+) -> Result<Option<String>, RustBertError> {
+    let mut response = send_request(request, url)?;
+    let version = header_value(&response, version_header);
+    response
+        .copy_to(writer)
+        .map_err(|error| RustBertError::FileDownloadError(format!("{}: {}", url, error)))?;
+    Ok(version)
+}
+
+#[cfg(any(feature = "gcs", feature = "azure"))]
+/// Requests an OAuth access token from an instance metadata endpoint, returning `None` outside of
+/// a cloud instance or if the instance has no identity
+pub(crate) fn metadata_access_token(url: &str, header: (&str, &str)) -> Option<String> {
+    let client = reqwest::blocking::Client::builder()
+        .timeout(std::time::Duration::from_secs(2))
+        .build()
+        .ok()?;
+    let response = client.get(url).header(header.0, header.1).send().ok()?;
+    if !response.status().is_success() {
+        return None;
+    }
+    let body: serde_json::Value = serde_json::from_str(&response.text().ok()?).ok()?;
+    body.get("access_token")?.as_str().map(String::from)
+}
@@ -0,0 +1,150 @@
+// Copyright 2021 Guillaume Becquin
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//     http://www.apache.org/licenses/LICENSE-2.0
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use crate::common::error::RustBertError;
+use crate::common::resources::backend::{
+    download_response, metadata_access_token, percent_encode, split_object_url, ResourceBackend,
+};
+use std::env;
+use std::io::Write;
+
+const METADATA_TOKEN_URL: &str =
+    "http://metadata.google.internal/computeMetadata/v1/instance/service-accounts/default/token";
+const GENERATION_HEADER: &str = "x-goog-generation";
+
+#[derive(Clone, PartialEq)]
+/// # Credentials of the Google Cloud Storage requests
+pub enum GcsCredentials {
+    /// OAuth 2.0 access token (e.g. from `gcloud auth print-access-token`)
+    AccessToken(String),
+    /// Token of the default service account of the instance, requested from the metadata server
+    /// for each download. Requests are unauthenticated outside of Google Cloud.
+    InstanceMetadata,
+    /// Unauthenticated requests, for public buckets
+    Anonymous,
+}
+
+impl GcsCredentials {
+    /// Resolves the credentials from the `GOOGLE_OAUTH_ACCESS_TOKEN` environment variable, then
+    /// from the instance metadata server (see `GcsCredentials::InstanceMetadata`)
+    pub fn from_default_chain() -> GcsCredentials {
+        match env::var("GOOGLE_OAUTH_ACCESS_TOKEN") {
+            Ok(token) => GcsCredentials::AccessToken(token),
+            Err(_) => GcsCredentials::InstanceMetadata,
+        }
+    }
+
+    fn access_token(&self) -> Option<String> {
+        match self {
+            GcsCredentials::AccessToken(token) => Some(token.clone()),
+            GcsCredentials::InstanceMetadata => {
+                metadata_access_token(METADATA_TOKEN_URL, ("Metadata-Flavor", "Google"))
+            }
+            GcsCredentials::Anonymous => None,
+        }
+    }
+}
+
+/// # Google Cloud Storage backend
+/// Downloads the resources with URLs `gs://<bucket>/<object>` with the JSON API.
+pub struct GcsBackend {
+    endpoint: String,
+    credentials: GcsCredentials,
+    client: reqwest::blocking::Client,
+}
+
+impl GcsBackend {
+    /// Creates a new Google Cloud Storage backend
+    ///
+    /// # Arguments
+    ///
+    /// * `credentials` - `GcsCredentials` used to authenticate the requests
+    pub fn new(credentials: GcsCredentials) -> GcsBackend {
+        GcsBackend {
+            endpoint: "https://storage.googleapis.com".to_string(),
+            credentials,
+            client: reqwest::blocking::Client::new(),
+        }
+    }
+
+    /// Creates a backend with the credentials of the default chain (see
+    /// `GcsCredentials::from_default_chain`), using the endpoint of `STORAGE_EMULATOR_HOST` if set
+    pub fn from_env() -> GcsBackend {
+        let backend = GcsBackend::new(GcsCredentials::from_default_chain());
+        match env::var("STORAGE_EMULATOR_HOST") {
+            Ok(endpoint) => backend.with_endpoint(&endpoint),
+            Err(_) => backend,
+        }
+    }
+
+    /// Sets a custom endpoint (e.g. a storage emulator)
+    pub fn with_endpoint(mut self, endpoint: &str) -> GcsBackend {
+        self.endpoint = endpoint.trim_end_matches('/').to_string();
+        self
+    }
+
+    /// Builds an authenticated request for the metadata (or content if `media`) of an object
+    fn object_request(
+        &self,
+        url: &str,
+        media: bool,
+    ) -> Result<reqwest::blocking::RequestBuilder, RustBertError> {
+        let (bucket, object) = split_object_url(url, "gs")?;
+        let mut request_url = format!(
+            "{}/storage/v1/b/{}/o/{}",
+            self.endpoint,
+            bucket,
+            percent_encode(object, false)
+        );
+        if media {
+            request_url.push_str("?alt=media");
+        }
+        let mut request = self.client.get(&request_url);
+        if let Some(token) = self.credentials.access_token() {
+            request = request.bearer_auth(token);
+        }
+        Ok(request)
+    }
+}
+
+impl ResourceBackend for GcsBackend {
+    fn scheme(&self) -> &str {
+        "gs"
+    }
+
+    /// The version of an object is its generation: the metadata `etag` differs from the `ETag`
+    /// header of the content downloads
+    fn version(&self, url: &str) -> Result<Option<String>, RustBertError> {
+        let mut metadata = Vec::new();
+        download_response(
+            self.object_request(url, false)?,
+            url,
+            GENERATION_HEADER,
+            &mut metadata,
+        )?;
+        let metadata: serde_json::Value = serde_json::from_slice(&metadata).map_err(|error| {
+            RustBertError::FileDownloadError(format!("{}: invalid metadata: {}", url, error))
+        })?;
+        Ok(metadata
+            .get("generation")
+            .and_then(|generation| generation.as_str())
+            .map(String::from))
+    }
+
+    fn download(&self, url: &str, writer: &mut dyn Write) -> Result<Option<String>, RustBertError> {
+        download_response(
+            self.object_request(url, true)?,
+            url,
+            GENERATION_HEADER,
+            writer,
+        )
+    }
+}
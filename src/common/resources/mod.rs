@@ -0,0 +1,641 @@
+//! # Resource definitions for model weights, vocabularies and configuration files
+//!
+//! This crate relies on the concept of Resources to access the files used by the models.
+//! This includes:
+//! - model weights
+//! - configuration files
+//! - vocabularies
+//! - (optional) merges files for BPE-based tokenizers
+//!
+//! These are expected in the pipelines configurations or are used as utilities to reference to the
+//! resource location. Two types of resources exist:
+//! - LocalResource: points to a local file
+//! - RemoteResource: points to a remote file via a URL and a local cached file
+//!
+//! For both types of resources, the local location of teh file can be retrieved using
+//! `get_local_path`, allowing to reference the resource file location regardless if it is a remote
+//! or local resource. Default implementations for a number of `RemoteResources` are available as
+//! pre-trained models in each model module.
+//!
+//! Remote resources are downloaded over HTTP, or with the `ResourceBackend` registered for the
+//! scheme of their URL. Backends for Amazon S3 (`s3://bucket/key`), Google Cloud Storage
+//! (`gs://bucket/object`) and Azure Blob Storage (`az://container/blob`) are enabled with the `s3`,
+//! `gcs` and `azure` features, allowing to load converted weights hosted in private buckets. They
+//! are configured from the environment and resolve their credentials from the standard chains of
+//! each provider (environment variables, shared credentials files, instance metadata). The version
+//! (ETag) of a resource downloaded with a backend is checked each time it is used, and a new
+//! version is downloaded when the object changes. The cached version is used when the backend
+//! cannot be reached.
+//!
+//! By default, remote resources are cached in a subdirectory named after the model. Setting the
+//! environment variable `RUSTBERT_CACHE_LAYOUT=content-addressed` switches to a content-addressed
+//! layout, more suitable for caches shared between users: files are saved at the root of the cache
+//! and named `<SHA-256 of the URL>.<SHA-256 of the ETag>` (or `<SHA-256 of the URL>` if the server
+//! does not return an ETag), so that paths do not reveal the models used and a new version of a file
+//! never overwrites the previous one. The SHA-256 digest and size of each downloaded file are
+//! recorded in an integrity manifest (`manifest.json`), updated under an exclusive lock
+//! (`manifest.json.lock`) by concurrent processes. The cache can be checked with `verify_cache`, and
+//! the corrupted or unknown files removed with `collect_garbage`.
+//!
+//! ```no_run
+//! # fn main() -> anyhow::Result<()> {
+//! use rust_bert::resources::{collect_garbage, verify_cache};
+//!
+//! let verification = verify_cache()?;
+//! if !verification.is_valid() {
+//!     let removed_files = collect_garbage(&verification)?;
+//!     println!("removed {} files", removed_files.len());
+//! }
+//! # Ok(())
+//! # }
+//! ```
+
+use crate::common::error::RustBertError;
+use cached_path::{Cache, Options, ProgressBar};
+use fs2::FileExt;
+use lazy_static::lazy_static;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::BTreeMap;
+use std::env;
+use std::fs::{self, File, OpenOptions};
+use std::io::{BufReader, Read, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+extern crate dirs;
+
+#[cfg(feature = "azure")]
+mod azure;
+mod backend;
+#[cfg(feature = "gcs")]
+mod gcs;
+#[cfg(feature = "s3")]
+mod s3;
+
+#[cfg(feature = "azure")]
+pub use azure::{AzureBackend, AzureCredentials};
+use backend::{backend_for_url, cached_backend_path};
+pub use backend::{register_backend, ResourceBackend};
+#[cfg(feature = "gcs")]
+pub use gcs::{GcsBackend, GcsCredentials};
+#[cfg(feature = "s3")]
+pub use s3::{S3Backend, S3Credentials};
+
+/// # Resource Enum pointing to model, configuration or vocabulary resources
+/// Can be of type:
+/// - LocalResource
+/// - RemoteResource
+#[derive(PartialEq, Clone)]
+pub enum Resource {
+    Local(LocalResource),
+    Remote(RemoteResource),
+}
+
+impl Resource {
+    /// Gets the local path for a given resource.
+    ///
+    /// If the resource is a remote resource, it is downloaded and cached. Then the path
+    /// to the local cache is returned.
+    ///
+    /// # Returns
+    ///
+    /// * `PathBuf` pointing to the resource file
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use rust_bert::resources::{LocalResource, Resource};
+    /// use std::path::PathBuf;
+    /// let config_resource = Resource::Local(LocalResource {
+    ///     local_path: PathBuf::from("path/to/config.json"),
+    /// });
+    /// let config_path = config_resource.get_local_path();
+    /// ```
+    pub fn get_local_path(&self) -> Result<PathBuf, RustBertError> {
+        match self {
+            Resource::Local(resource) => Ok(resource.local_path.clone()),
+            Resource::Remote(resource) => match backend_for_url(&resource.url) {
+                Some(backend) => cached_backend_path(
+                    backend.as_ref(),
+                    &resource.url,
+                    _get_cache_directory(),
+                    &resource.cache_subdir,
+                ),
+                None => cached_remote_path(&CACHE, resource),
+            },
+        }
+    }
+
+    /// Gets the local path for a given resource, caching remote resources in the directory
+    /// provided instead of the global cache directory (see `CACHE`).
+    ///
+    /// # Arguments
+    ///
+    /// * `cache_dir` - `&Path` Root of the cache for remote resources
+    ///
+    /// # Returns
+    ///
+    /// * `PathBuf` pointing to the resource file
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use rust_bert::resources::{RemoteResource, Resource};
+    /// use std::path::Path;
+    /// let config_resource = Resource::Remote(RemoteResource::from_pretrained((
+    ///     "distilbert-sst2",
+    ///     "https://huggingface.co/distilbert-base-uncased-finetuned-sst-2-english/resolve/main/config.json",
+    /// )));
+    /// let config_path = config_resource.get_local_path_in(Path::new("/mnt/models"));
+    /// ```
+    pub fn get_local_path_in(&self, cache_dir: &Path) -> Result<PathBuf, RustBertError> {
+        match self {
+            Resource::Local(resource) => Ok(resource.local_path.clone()),
+            Resource::Remote(resource) => match backend_for_url(&resource.url) {
+                Some(backend) => cached_backend_path(
+                    backend.as_ref(),
+                    &resource.url,
+                    cache_dir.to_path_buf(),
+                    &resource.cache_subdir,
+                ),
+                None => {
+                    let cache = Cache::builder()
+                        .dir(cache_dir.to_path_buf())
+                        .progress_bar(Some(ProgressBar::Light))
+                        .build()?;
+                    cached_remote_path(&cache, resource)
+                }
+            },
+        }
+    }
+}
+
+/// Downloads a remote resource over HTTP into a cache if not present, and returns its local path
+fn cached_remote_path(cache: &Cache, resource: &RemoteResource) -> Result<PathBuf, RustBertError> {
+    match *CACHE_LAYOUT {
+        CacheLayout::Subdirectories => {
+            let cached_path = cache.cached_path_with_options(
+                &resource.url,
+                &Options::default().subdir(&resource.cache_subdir),
+            )?;
+            Ok(cached_path)
+        }
+        CacheLayout::ContentAddressed => {
+            let cached_path = cache.cached_path(&resource.url)?;
+            register_cached_file(&cached_path)?;
+            Ok(cached_path)
+        }
+    }
+}
+
+/// # Local resource
+#[derive(PartialEq, Clone)]
+pub struct LocalResource {
+    /// Local path for the resource
+    pub local_path: PathBuf,
+}
+
+/// # Remote resource
+#[derive(PartialEq, Clone)]
+pub struct RemoteResource {
+    /// Remote path/url for the resource
+    pub url: String,
+    /// Local subdirectory of the cache root where this resource is saved
+    pub cache_subdir: String,
+}
+
+impl RemoteResource {
+    /// Creates a new RemoteResource from an URL and a custom local path. Note that this does not
+    /// download the resource (only declares the remote and local locations)
+    ///
+    /// # Arguments
+    ///
+    /// * `url` - `&str` Location of the remote resource
+    /// * `cache_subdir` - `&str` Local subdirectory of the cache root to save the resource to
+    ///
+    /// # Returns
+    ///
+    /// * `RemoteResource` RemoteResource object
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use rust_bert::resources::{RemoteResource, Resource};
+    /// let config_resource = Resource::Remote(RemoteResource::new(
+    ///     "configs",
+    ///     "http://config_json_location",
+    /// ));
+    /// ```
+    pub fn new(url: &str, cache_subdir: &str) -> RemoteResource {
+        RemoteResource {
+            url: url.to_string(),
+            cache_subdir: cache_subdir.to_string(),
+        }
+    }
+
+    /// Creates a new RemoteResource from an URL and local name. Will define a local path pointing to
+    /// ~/.cache/.rusbert/model_name. Note that this does not download the resource (only declares
+    /// the remote and local locations)
+    ///
+    /// # Arguments
+    ///
+    /// * `name_url_tuple` - `(&str, &str)` Location of the name of model and remote resource
+    ///
+    /// # Returns
+    ///
+    /// * `RemoteResource` RemoteResource object
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use rust_bert::resources::{RemoteResource, Resource};
+    /// let model_resource = Resource::Remote(RemoteResource::from_pretrained((
+    ///     "distilbert-sst2",
+    ///     "https://huggingface.co/distilbert-base-uncased-finetuned-sst-2-english/resolve/main/rust_model.ot",
+    /// )));
+    /// ```
+    pub fn from_pretrained(name_url_tuple: (&str, &str)) -> RemoteResource {
+        let cache_subdir = name_url_tuple.0.to_string();
+        let url = name_url_tuple.1.to_string();
+        RemoteResource { cache_subdir, url }
+    }
+}
+
+lazy_static! {
+    #[derive(Copy, Clone, Debug)]
+/// # Global cache directory
+/// If the environment variable `RUSTBERT_CACHE` is set, will save the cache model files at that
+/// location. Otherwise defaults to `~/.cache/.rustbert`.
+    pub static ref CACHE: Cache = Cache::builder()
+        .dir(_get_cache_directory())
+        .progress_bar(Some(ProgressBar::Light))
+        .build().unwrap();
+}
+
+lazy_static! {
+/// # Global cache layout
+/// Set to `CacheLayout::ContentAddressed` if the environment variable `RUSTBERT_CACHE_LAYOUT` is
+/// `content-addressed`, defaults to `CacheLayout::Subdirectories` otherwise.
+    pub static ref CACHE_LAYOUT: CacheLayout = match env::var("RUSTBERT_CACHE_LAYOUT") {
+        Ok(value) if value == "content-addressed" => CacheLayout::ContentAddressed,
+        _ => CacheLayout::Subdirectories,
+    };
+}
+
+fn _get_cache_directory() -> PathBuf {
+    match env::var("RUSTBERT_CACHE") {
+        Ok(value) => PathBuf::from(value),
+        Err(_) => {
+            let mut home = dirs::home_dir().unwrap();
+            home.push(".cache");
+            home.push(".rustbert");
+            home
+        }
+    }
+}
+
+#[deprecated(
+    since = "0.9.1",
+    note = "Please use `Resource.get_local_path()` instead"
+)]
+/// # (Download) the resource and return a path to its local path
+/// This function will download remote resource to their local path if they do not exist yet.
+/// Then for both `LocalResource` and `RemoteResource`, it will the local path to the resource.
+/// For `LocalResource` only the resource path is returned.
+///
+/// # Arguments
+///
+/// * `resource` - Pointer to the `&Resource` to optionally download and get the local path.
+///
+/// # Returns
+///
+/// * `&PathBuf` Local path for the resource
+///
+/// # Example
+///
+/// ```no_run
+/// use rust_bert::resources::{RemoteResource, Resource};
+/// let model_resource = Resource::Remote(RemoteResource::from_pretrained((
+///     "distilbert-sst2/model.ot",
+///     "https://huggingface.co/distilbert-base-uncased-finetuned-sst-2-english/resolve/main/rust_model.ot",
+/// )));
+/// let local_path = model_resource.get_local_path();
+/// ```
+pub fn download_resource(resource: &Resource) -> Result<PathBuf, RustBertError> {
+    resource.get_local_path()
+}
+
+/// Name of the integrity manifest at the root of a content-addressed cache
+pub const MANIFEST_FILE_NAME: &str = "manifest.json";
+
+/// Name of the lock file held while updating the manifest
+pub const MANIFEST_LOCK_FILE_NAME: &str = "manifest.json.lock";
+
+/// Counter making the names of the temporary manifest files unique within a process
+static MANIFEST_WRITES: AtomicUsize = AtomicUsize::new(0);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+/// # Layout of the cached remote resources
+pub enum CacheLayout {
+    /// Files saved in a subdirectory named after the resource `cache_subdir`
+    Subdirectories,
+    /// Files saved at the root of the cache, named after the SHA-256 of their URL and ETag, and
+    /// recorded in an integrity manifest
+    ContentAddressed,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+/// # Integrity record of a cached file
+pub struct ManifestEntry {
+    /// Hexadecimal SHA-256 digest of the file content
+    pub sha256: String,
+    /// Size of the file in bytes
+    pub size: u64,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+/// # Integrity manifest of a content-addressed cache
+/// Maps the name of each cached file to its digest and size.
+pub struct CacheManifest {
+    /// Integrity records, indexed by file name
+    pub entries: BTreeMap<String, ManifestEntry>,
+}
+
+impl CacheManifest {
+    /// Loads the manifest of a cache directory, returning an empty manifest if it does not exist
+    pub fn load(cache_dir: &Path) -> Result<CacheManifest, RustBertError> {
+        let path = cache_dir.join(MANIFEST_FILE_NAME);
+        if !path.exists() {
+            return Ok(CacheManifest::default());
+        }
+        let file = File::open(&path)?;
+        serde_json::from_reader(BufReader::new(file)).map_err(|error| {
+            RustBertError::ValueError(format!(
+                "Invalid cache manifest {}: {}",
+                path.display(),
+                error
+            ))
+        })
+    }
+
+    /// Saves the manifest in a cache directory, writing to a temporary file first so that
+    /// concurrent readers never see a truncated manifest. Use `CacheManifest::update` to modify the
+    /// manifest of a cache that may be used concurrently.
+    pub fn save(&self, cache_dir: &Path) -> Result<(), RustBertError> {
+        let path = cache_dir.join(MANIFEST_FILE_NAME);
+        let temporary_path = cache_dir.join(format!(
+            ".{}.{}.{}.tmp",
+            MANIFEST_FILE_NAME,
+            std::process::id(),
+            MANIFEST_WRITES.fetch_add(1, Ordering::SeqCst)
+        ));
+        let contents = serde_json::to_string_pretty(self)
+            .map_err(|error| RustBertError::ValueError(error.to_string()))?;
+        let mut file = File::create(&temporary_path)?;
+        file.write_all(contents.as_bytes())?;
+        file.sync_all()?;
+        fs::rename(&temporary_path, &path)?;
+        Ok(())
+    }
+
+    /// Computes the digest of a file and records it under its file name
+    pub fn record(&mut self, path: &Path) -> Result<(), RustBertError> {
+        let file_name = cache_file_name(path)?;
+        let entry = hash_file(path)?;
+        self.entries.insert(file_name, entry);
+        Ok(())
+    }
+
+    /// Loads, modifies and saves the manifest of a cache directory while holding an exclusive lock
+    /// on `MANIFEST_LOCK_FILE_NAME`, so that concurrent updates by other threads or processes are
+    /// never lost. The manifest is only saved if modified.
+    ///
+    /// # Arguments
+    ///
+    /// * `cache_dir` - Root directory of the cache
+    /// * `modify` - Closure modifying the manifest
+    ///
+    /// # Returns
+    ///
+    /// * The value returned by `modify`
+    pub fn update<T, F>(cache_dir: &Path, modify: F) -> Result<T, RustBertError>
+    where
+        F: FnOnce(&mut CacheManifest) -> Result<T, RustBertError>,
+    {
+        fs::create_dir_all(cache_dir)?;
+        let lock_file = OpenOptions::new()
+            .create(true)
+            .write(true)
+            .truncate(false)
+            .open(cache_dir.join(MANIFEST_LOCK_FILE_NAME))?;
+        lock_file.lock_exclusive()?;
+        let result = CacheManifest::load(cache_dir).and_then(|initial_manifest| {
+            let mut manifest = initial_manifest.clone();
+            let output = modify(&mut manifest)?;
+            if manifest != initial_manifest {
+                manifest.save(cache_dir)?;
+            }
+            Ok(output)
+        });
+        lock_file.unlock()?;
+        result
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+/// # Result of the verification of a content-addressed cache
+pub struct CacheVerification {
+    /// Root directory of the cache verified
+    pub cache_dir: PathBuf,
+    /// Files matching their manifest entry
+    pub verified: Vec<PathBuf>,
+    /// Files whose digest or size differs from their manifest entry
+    pub corrupted: Vec<PathBuf>,
+    /// Files recorded in the manifest but absent from the cache
+    pub missing: Vec<PathBuf>,
+    /// Cached files not recorded in the manifest
+    pub untracked: Vec<PathBuf>,
+}
+
+impl CacheVerification {
+    /// Returns `true` if all files in the cache match the manifest
+    pub fn is_valid(&self) -> bool {
+        self.corrupted.is_empty() && self.missing.is_empty() && self.untracked.is_empty()
+    }
+}
+
+/// Verifies the global cache against its integrity manifest (see `verify_cache_dir`)
+pub fn verify_cache() -> Result<CacheVerification, RustBertError> {
+    verify_cache_dir(&_get_cache_directory())
+}
+
+/// Verifies the files of a content-addressed cache against its integrity manifest
+///
+/// # Arguments
+///
+/// * `cache_dir` - Root directory of the cache
+///
+/// # Returns
+///
+/// * `CacheVerification` listing the verified, corrupted, missing and untracked files. Only the
+/// files named after the content-addressed layout are considered, leaving out the metadata, lock
+/// and temporary files.
+pub fn verify_cache_dir(cache_dir: &Path) -> Result<CacheVerification, RustBertError> {
+    let manifest = CacheManifest::load(cache_dir)?;
+    let mut verification = CacheVerification {
+        cache_dir: cache_dir.to_path_buf(),
+        ..Default::default()
+    };
+    for (file_name, entry) in manifest.entries.iter() {
+        let path = cache_dir.join(file_name);
+        if !path.is_file() {
+            verification.missing.push(path);
+        } else if &hash_file(&path)? == entry {
+            verification.verified.push(path);
+        } else {
+            verification.corrupted.push(path);
+        }
+    }
+    if cache_dir.is_dir() {
+        for dir_entry in fs::read_dir(cache_dir)? {
+            let path = dir_entry?.path();
+            let file_name = path
+                .file_name()
+                .and_then(|name| name.to_str())
+                .unwrap_or("");
+            if path.is_file()
+                && is_resource_file_name(file_name)
+                && !manifest.entries.contains_key(file_name)
+            {
+                verification.untracked.push(path);
+            }
+        }
+    }
+    verification.untracked.sort();
+    Ok(verification)
+}
+
+/// Removes the corrupted and untracked files of a verified cache, along with their metadata, and
+/// drops the entries of the corrupted and missing files from the manifest. The removed files are
+/// downloaded again the next time they are used.
+///
+/// The manifest lock is held during the removal: files reported as untracked that were recorded
+/// in the manifest since the verification (e.g. finishing to download in another process) are
+/// kept, and the corrupted files are checked again before being removed.
+///
+/// # Arguments
+///
+/// * `verification` - `CacheVerification` returned by `verify_cache` or `verify_cache_dir`
+///
+/// # Returns
+///
+/// * `Vec<PathBuf>` Paths of the files removed
+pub fn collect_garbage(verification: &CacheVerification) -> Result<Vec<PathBuf>, RustBertError> {
+    CacheManifest::update(&verification.cache_dir, |manifest| {
+        let mut removed_files = Vec::new();
+        for path in verification.corrupted.iter() {
+            let file_name = cache_file_name(path)?;
+            let still_corrupted = match manifest.entries.get(&file_name) {
+                Some(entry) => !path.is_file() || &hash_file(path)? != entry,
+                None => false,
+            };
+            if still_corrupted {
+                manifest.entries.remove(&file_name);
+                remove_cached_file(path, &mut removed_files)?;
+            }
+        }
+        for path in verification.untracked.iter() {
+            if !manifest.entries.contains_key(&cache_file_name(path)?) {
+                remove_cached_file(path, &mut removed_files)?;
+            }
+        }
+        for path in verification.missing.iter() {
+            if !path.is_file() {
+                manifest.entries.remove(&cache_file_name(path)?);
+            }
+        }
+        Ok(removed_files)
+    })
+}
+
+/// Removes a cached file along with its metadata and lock files
+fn remove_cached_file(path: &Path, removed_files: &mut Vec<PathBuf>) -> Result<(), RustBertError> {
+    for suffix in &["", ".json", ".lock"] {
+        let mut file_path = path.as_os_str().to_owned();
+        file_path.push(suffix);
+        let file_path = PathBuf::from(file_path);
+        if file_path.is_file() {
+            fs::remove_file(&file_path)?;
+            removed_files.push(file_path);
+        }
+    }
+    Ok(())
+}
+
+/// Records a downloaded file in the manifest of its cache directory, unless already present
+fn register_cached_file(path: &Path) -> Result<(), RustBertError> {
+    let cache_dir = path.parent().ok_or_else(|| {
+        RustBertError::IOError(format!("Invalid cached file path {}", path.display()))
+    })?;
+    let file_name = cache_file_name(path)?;
+    let size = fs::metadata(path)?.len();
+    let is_recorded = |manifest: &CacheManifest| {
+        manifest
+            .entries
+            .get(&file_name)
+            .map_or(false, |entry| entry.size == size)
+    };
+    if is_recorded(&CacheManifest::load(cache_dir)?) {
+        return Ok(());
+    }
+    // The file is hashed before taking the lock, not to block the other processes
+    let entry = hash_file(path)?;
+    CacheManifest::update(cache_dir, |manifest| {
+        if !is_recorded(manifest) {
+            manifest.entries.insert(file_name.clone(), entry);
+        }
+        Ok(())
+    })
+}
+
+fn cache_file_name(path: &Path) -> Result<String, RustBertError> {
+    path.file_name()
+        .and_then(|name| name.to_str())
+        .map(|name| name.to_string())
+        .ok_or_else(|| {
+            RustBertError::IOError(format!("Invalid cached file path {}", path.display()))
+        })
+}
+
+/// Resource files are named `<SHA-256 of the URL>[.<SHA-256 of the ETag>]` (hexadecimal digests)
+fn is_resource_file_name(file_name: &str) -> bool {
+    let mut parts = file_name.split('.');
+    let is_digest =
+        |part: &str| part.len() == 64 && part.bytes().all(|byte| byte.is_ascii_hexdigit());
+    match (parts.next(), parts.next(), parts.next()) {
+        (Some(url_digest), etag_digest, None) => {
+            is_digest(url_digest) && etag_digest.map_or(true, is_digest)
+        }
+        _ => false,
+    }
+}
+
+fn hash_file(path: &Path) -> Result<ManifestEntry, RustBertError> {
+    let mut file = File::open(path)?;
+    let mut hasher = Sha256::new();
+    let mut buffer = vec![0u8; 1 << 16];
+    let mut size = 0u64;
+    loop {
+        let read_bytes = file.read(&mut buffer)?;
+        if read_bytes == 0 {
+            break;
+        }
+        hasher.update(&buffer[..read_bytes]);
+        size += read_bytes as u64;
+    }
+    Ok(ManifestEntry {
+        sha256: format!("{:x}", hasher.finalize()),
+        size,
+    })
+}
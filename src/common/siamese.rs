@@ -0,0 +1,300 @@
+// Copyright 2021 Guillaume Becquin
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//     http://www.apache.org/licenses/LICENSE-2.0
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! # Siamese training objectives
+//!
+//! Loss functions and batch builders to fine-tune sentence embedding models on domain data. The texts of each
+//! training example (e.g. a question and its answer, or an anchor, a positive and a negative) are embedded by the
+//! same model, and the losses compare the resulting embeddings:
+//! - `multiple_negatives_ranking_loss`: for (anchor, positive) pairs, the positives of the other anchors of the batch
+//! are used as negatives. Only positive pairs are needed, and larger batches give harder objectives.
+//! - `cosine_embedding_loss`: brings the embeddings of similar pairs (label 1) together and pushes the dissimilar
+//! ones (label -1) below a cosine similarity margin.
+//! - `triplet_loss`: the anchor must be closer to the positive than to the negative by a margin.
+//!
+//! The losses are differentiable and can be used in a training loop with a `tch` optimizer. `BatchBuilder` groups the
+//! examples into batches of texts, one column per position in the examples, optionally avoiding duplicate texts in a
+//! batch (a duplicate of the positive would otherwise be used as a negative by the multiple negatives ranking loss).
+//!
+//! ```no_run
+//! # fn main() -> anyhow::Result<()> {
+//! use rust_bert::siamese::{multiple_negatives_ranking_loss, BatchBuilder, InputExample};
+//! use tch::nn::OptimizerConfig;
+//! use tch::{nn, Device, Tensor};
+//! # let vs = nn::VarStore::new(Device::Cpu);
+//! # let embed = |texts: &[String]| -> Tensor { unimplemented!() };
+//!
+//! let examples = vec![
+//!     InputExample::new(&["How do I reset my password?", "Open the settings and select Reset."], 1.0),
+//!     InputExample::new(&["Where are the invoices?", "Invoices are in the Billing tab."], 1.0),
+//! ];
+//! let mut optimizer = nn::Adam::default().build(&vs, 2e-5)?;
+//! for batch in BatchBuilder::new(32).with_no_duplicates(true).batches(&examples)? {
+//!     // `embed` runs the forward pass of the embedding model (with gradients) on a column of texts
+//!     let loss = multiple_negatives_ranking_loss(&embed(&batch.columns[0]), &embed(&batch.columns[1]), 20.0);
+//!     optimizer.backward_step(&loss);
+//! }
+//! # Ok(())
+//! # }
+//! ```
+
+use crate::RustBertError;
+use std::collections::HashSet;
+use tch::{Kind, Tensor};
+
+fn normalize(embeddings: &Tensor) -> Tensor {
+    embeddings
+        / (embeddings * embeddings)
+            .sum1(&[-1], true, Kind::Float)
+            .sqrt()
+            .clamp_min(1e-8)
+}
+
+/// Returns the matrix of cosine similarities of shape (*n_1*, *n_2*) between embeddings of shape (*n_1*, *dim*) and
+/// (*n_2*, *dim*)
+pub fn cosine_similarity_matrix(embeddings_1: &Tensor, embeddings_2: &Tensor) -> Tensor {
+    normalize(embeddings_1).matmul(&normalize(embeddings_2).transpose(0, 1))
+}
+
+fn cosine_similarity(embeddings_1: &Tensor, embeddings_2: &Tensor) -> Tensor {
+    (normalize(embeddings_1) * normalize(embeddings_2)).sum1(&[-1], false, Kind::Float)
+}
+
+/// Multiple negatives ranking loss: cross-entropy of the scaled cosine similarities between each anchor and the
+/// candidates, the candidate at the same index being the positive of the anchor and the other candidates its negatives.
+///
+/// # Arguments
+///
+/// * `anchors` - Anchor embeddings of shape (*batch size*, *dim*)
+/// * `candidates` - Candidate embeddings of shape (*n_candidates*, *dim*), starting with the positive of each anchor.
+/// Hard negatives can be appended after the positives (`Tensor::cat(&[positives, hard_negatives], 0)`).
+/// * `scale` - Multiplier of the similarities (inverse temperature), typically 20
+///
+/// # Returns
+///
+/// * Scalar `Tensor` with the mean loss over the anchors
+pub fn multiple_negatives_ranking_loss(
+    anchors: &Tensor,
+    candidates: &Tensor,
+    scale: f64,
+) -> Tensor {
+    let scores = cosine_similarity_matrix(anchors, candidates) * scale;
+    let labels = Tensor::arange(anchors.size()[0], (Kind::Int64, anchors.device()));
+    scores.cross_entropy_for_logits(&labels)
+}
+
+/// Cosine embedding loss: `1 - cos(x_1, x_2)` for similar pairs (positive label) and `max(0, cos(x_1, x_2) - margin)`
+/// for dissimilar pairs (negative label).
+///
+/// # Arguments
+///
+/// * `embeddings_1` - Embeddings of the first texts of the pairs, of shape (*batch size*, *dim*)
+/// * `embeddings_2` - Embeddings of the second texts of the pairs, of shape (*batch size*, *dim*)
+/// * `labels` - Labels of shape (*batch size*), 1 for similar pairs and -1 for dissimilar pairs
+/// * `margin` - Cosine similarity below which dissimilar pairs are not penalized, typically between 0 and 0.5
+///
+/// # Returns
+///
+/// * Scalar `Tensor` with the mean loss over the pairs
+pub fn cosine_embedding_loss(
+    embeddings_1: &Tensor,
+    embeddings_2: &Tensor,
+    labels: &Tensor,
+    margin: f64,
+) -> Tensor {
+    let similarity = cosine_similarity(embeddings_1, embeddings_2);
+    let similar = labels.gt(0).to_kind(Kind::Float);
+    let similar_loss = similarity.ones_like() - &similarity;
+    let dissimilar_loss = (similarity - margin).clamp_min(0.0);
+    (&similar * similar_loss + (similar.ones_like() - &similar) * dissimilar_loss).mean(Kind::Float)
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+/// # Distance between embeddings used by the triplet loss
+pub enum TripletDistance {
+    /// Euclidean distance
+    Euclidean,
+    /// One minus the cosine similarity
+    Cosine,
+}
+
+impl TripletDistance {
+    fn distance(&self, embeddings_1: &Tensor, embeddings_2: &Tensor) -> Tensor {
+        match self {
+            TripletDistance::Euclidean => {
+                let difference = embeddings_1 - embeddings_2;
+                ((&difference * &difference).sum1(&[-1], false, Kind::Float) + 1e-12).sqrt()
+            }
+            TripletDistance::Cosine => {
+                let similarity = cosine_similarity(embeddings_1, embeddings_2);
+                similarity.ones_like() - similarity
+            }
+        }
+    }
+}
+
+/// Triplet loss: `max(0, d(anchor, positive) - d(anchor, negative) + margin)`
+///
+/// # Arguments
+///
+/// * `anchors` - Anchor embeddings of shape (*batch size*, *dim*)
+/// * `positives` - Positive embeddings of shape (*batch size*, *dim*)
+/// * `negatives` - Negative embeddings of shape (*batch size*, *dim*)
+/// * `distance` - `TripletDistance` between the embeddings
+/// * `margin` - Minimum difference between the negative and positive distances (e.g. 5 for the Euclidean distance)
+///
+/// # Returns
+///
+/// * Scalar `Tensor` with the mean loss over the triplets
+pub fn triplet_loss(
+    anchors: &Tensor,
+    positives: &Tensor,
+    negatives: &Tensor,
+    distance: TripletDistance,
+    margin: f64,
+) -> Tensor {
+    let positive_distance = distance.distance(anchors, positives);
+    let negative_distance = distance.distance(anchors, negatives);
+    (positive_distance - negative_distance + margin)
+        .clamp_min(0.0)
+        .mean(Kind::Float)
+}
+
+#[derive(Debug, Clone, PartialEq)]
+/// # Training example for a sentence embedding model
+pub struct InputExample {
+    /// Texts of the example, e.g. (anchor, positive) or (anchor, positive, negative)
+    pub texts: Vec<String>,
+    /// Label of the example (e.g. 1 or -1 for the cosine embedding loss), ignored by the ranking and triplet losses
+    pub label: f64,
+}
+
+impl InputExample {
+    /// Creates a new training example
+    pub fn new(texts: &[&str], label: f64) -> InputExample {
+        InputExample {
+            texts: texts.iter().map(|text| text.to_string()).collect(),
+            label,
+        }
+    }
+}
+
+#[derive(Debug)]
+/// # Batch of training examples
+pub struct EmbeddingBatch {
+    /// Texts of the batch, one column per position in the examples (e.g. the anchors and the positives)
+    pub columns: Vec<Vec<String>>,
+    /// Labels of the examples, of shape (*batch size*)
+    pub labels: Tensor,
+}
+
+/// # Builder of training batches
+pub struct BatchBuilder {
+    batch_size: usize,
+    no_duplicates: bool,
+    drop_last: bool,
+}
+
+impl BatchBuilder {
+    /// Creates a new batch builder, keeping the order of the examples and the last incomplete batch
+    pub fn new(batch_size: usize) -> BatchBuilder {
+        BatchBuilder {
+            batch_size,
+            no_duplicates: false,
+            drop_last: false,
+        }
+    }
+
+    /// Avoids duplicate texts within a batch: an example sharing a text with the examples already in the batch is
+    /// moved to a following batch. Recommended for the multiple negatives ranking loss.
+    pub fn with_no_duplicates(mut self, no_duplicates: bool) -> BatchBuilder {
+        self.no_duplicates = no_duplicates;
+        self
+    }
+
+    /// Drops the last batch if it contains fewer than `batch_size` examples
+    pub fn with_drop_last(mut self, drop_last: bool) -> BatchBuilder {
+        self.drop_last = drop_last;
+        self
+    }
+
+    /// Groups the examples into batches
+    ///
+    /// # Arguments
+    ///
+    /// * `examples` - `&[InputExample]` training examples, all with the same number of texts
+    ///
+    /// # Returns
+    ///
+    /// * `Vec<EmbeddingBatch>` with the texts and labels of each batch
+    pub fn batches(&self, examples: &[InputExample]) -> Result<Vec<EmbeddingBatch>, RustBertError> {
+        if self.batch_size == 0 {
+            return Err(RustBertError::ValueError(
+                "The batch size must be positive".to_string(),
+            ));
+        }
+        let num_texts = examples.first().map_or(0, |example| example.texts.len());
+        if let Some(example) = examples
+            .iter()
+            .find(|example| example.texts.len() != num_texts)
+        {
+            return Err(RustBertError::ValueError(format!(
+                "All the examples must have the same number of texts, got {} and {}",
+                num_texts,
+                example.texts.len()
+            )));
+        }
+
+        let mut remaining = examples.iter().collect::<Vec<&InputExample>>();
+        let mut batches = vec![];
+        while !remaining.is_empty() {
+            let mut batch = Vec::with_capacity(self.batch_size);
+            let mut batch_texts: HashSet<&str> = HashSet::new();
+            let mut deferred = vec![];
+            for example in remaining {
+                let duplicate = self.no_duplicates
+                    && example
+                        .texts
+                        .iter()
+                        .any(|text| batch_texts.contains(text.as_str()));
+                if batch.len() == self.batch_size || duplicate {
+                    deferred.push(example);
+                    continue;
+                }
+                if self.no_duplicates {
+                    batch_texts.extend(example.texts.iter().map(String::as_str));
+                }
+                batch.push(example);
+            }
+            remaining = deferred;
+            if batch.len() < self.batch_size && self.drop_last {
+                continue;
+            }
+            let columns = (0..num_texts)
+                .map(|column| {
+                    batch
+                        .iter()
+                        .map(|example| example.texts[column].clone())
+                        .collect()
+                })
+                .collect();
+            let labels = Tensor::of_slice(
+                &batch
+                    .iter()
+                    .map(|example| example.label)
+                    .collect::<Vec<f64>>(),
+            )
+            .to_kind(Kind::Float);
+            batches.push(EmbeddingBatch { columns, labels });
+        }
+        Ok(batches)
+    }
+}
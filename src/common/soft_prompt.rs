@@ -0,0 +1,256 @@
+// Copyright 2021 Guillaume Becquin
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//     http://www.apache.org/licenses/LICENSE-2.0
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! # Soft prompts (prompt tuning)
+//!
+//! Learnable embeddings prepended to the input embeddings of a model ([The Power of Scale for Parameter-Efficient Prompt Tuning](https://arxiv.org/abs/2104.08691) Lester, Al-Rfou, Constant, 2021).
+//! The soft prompt variables are held in their own variable store: they can be trained while the model weights are frozen,
+//! and saved or loaded independently of the model. The extended embeddings are passed to the models via their `input_embeds`
+//! (or `decoder_input_embeds`) argument, together with an attention mask extended to cover the virtual tokens.
+//!
+//! ```no_run
+//! # fn main() -> anyhow::Result<()> {
+//! use rust_bert::bert::{BertConfig, BertEmbeddings, BertModel};
+//! use rust_bert::soft_prompt::SoftPrompt;
+//! use rust_bert::Config;
+//! use std::path::Path;
+//! use tch::{nn, Device, Kind, Tensor};
+//!
+//! let device = Device::Cpu;
+//! let mut vs = nn::VarStore::new(device);
+//! let config = BertConfig::from_file(Path::new("path/to/config.json"));
+//! let bert_model: BertModel<BertEmbeddings> = BertModel::new(&vs.root(), &config);
+//! vs.load("path/to/model.ot")?;
+//! vs.freeze();
+//!
+//! let word_embeddings = &vs.variables()["embeddings.word_embeddings.weight"];
+//! let soft_prompt = SoftPrompt::new(20, config.hidden_size, device);
+//!
+//! let input_ids = Tensor::of_slice(&[101i64, 7592, 102]).unsqueeze(0);
+//! let attention_mask = Tensor::ones(&[1, 3], (Kind::Int64, device));
+//! let input_embeds = soft_prompt.embed_input_ids(&input_ids, word_embeddings);
+//! let attention_mask = soft_prompt.extend_attention_mask(&attention_mask);
+//!
+//! let output = bert_model.forward_t(
+//!     None,
+//!     Some(attention_mask),
+//!     None,
+//!     None,
+//!     Some(input_embeds),
+//!     &None,
+//!     &None,
+//!     false,
+//! )?;
+//! soft_prompt.save("path/to/task_prompt.ot")?;
+//! # Ok(())
+//! # }
+//! ```
+
+use crate::common::error::RustBertError;
+use std::collections::HashMap;
+use std::path::Path;
+use tch::{nn, no_grad, Device, Tensor};
+
+/// # Learnable soft prompt
+/// Holds `num_virtual_tokens` embeddings of dimension `embedding_dim` in a dedicated variable store.
+pub struct SoftPrompt {
+    var_store: nn::VarStore,
+    embeddings: Tensor,
+}
+
+impl SoftPrompt {
+    /// Creates a new randomly initialized soft prompt
+    ///
+    /// # Arguments
+    ///
+    /// * `num_virtual_tokens` - number of virtual tokens prepended to the inputs
+    /// * `embedding_dim` - dimension of the model embeddings
+    /// * `device` - device on which the prompt embeddings are created
+    pub fn new(num_virtual_tokens: i64, embedding_dim: i64, device: Device) -> SoftPrompt {
+        let var_store = nn::VarStore::new(device);
+        let embeddings = var_store.root().var(
+            "embeddings",
+            &[num_virtual_tokens, embedding_dim],
+            nn::Init::Randn {
+                mean: 0.,
+                stdev: 0.02,
+            },
+        );
+        SoftPrompt {
+            var_store,
+            embeddings,
+        }
+    }
+
+    /// Creates a new soft prompt initialized from the embeddings of existing vocabulary tokens, which usually
+    /// converges faster than a random initialization.
+    ///
+    /// # Arguments
+    ///
+    /// * `word_embeddings` - word embeddings matrix of the model, of shape (*vocab_size*, *embedding_dim*)
+    /// * `token_ids` - ids of the tokens used to initialize each virtual token
+    pub fn from_vocabulary(
+        word_embeddings: &Tensor,
+        token_ids: &[i64],
+    ) -> Result<SoftPrompt, RustBertError> {
+        let embedding_dim = word_embeddings.size()[1];
+        let mut soft_prompt = SoftPrompt::new(
+            token_ids.len() as i64,
+            embedding_dim,
+            word_embeddings.device(),
+        );
+        let initial_values = word_embeddings
+            .index_select(0, &Tensor::of_slice(token_ids).to(word_embeddings.device()));
+        no_grad(|| soft_prompt.embeddings.f_copy_(&initial_values))?;
+        Ok(soft_prompt)
+    }
+
+    /// Loads a soft prompt previously saved with `save`
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - path to the saved prompt
+    /// * `device` - device on which the prompt embeddings are loaded
+    pub fn load<P: AsRef<Path>>(path: P, device: Device) -> Result<SoftPrompt, RustBertError> {
+        let mut tensors = Tensor::load_multi_with_device(path, device)?;
+        match tensors.iter().position(|(name, _)| name == "embeddings") {
+            Some(index) => Ok(SoftPrompt::from_tensor(&tensors.remove(index).1)?),
+            None => Err(RustBertError::ValueError(
+                "No soft prompt embeddings found in the file provided".into(),
+            )),
+        }
+    }
+
+    fn from_tensor(value: &Tensor) -> Result<SoftPrompt, RustBertError> {
+        let size = value.size();
+        if size.len() != 2 {
+            return Err(RustBertError::ValueError(format!(
+                "Soft prompt embeddings must be of shape (num_virtual_tokens, embedding_dim), got {:?}",
+                size
+            )));
+        }
+        let mut soft_prompt = SoftPrompt::new(size[0], size[1], value.device());
+        no_grad(|| soft_prompt.embeddings.f_copy_(value))?;
+        Ok(soft_prompt)
+    }
+
+    /// Saves the soft prompt embeddings to a file
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<(), RustBertError> {
+        Ok(self.var_store.save(path)?)
+    }
+
+    /// Returns the number of virtual tokens of the prompt
+    pub fn num_virtual_tokens(&self) -> i64 {
+        self.embeddings.size()[0]
+    }
+
+    /// Returns the prompt embeddings, of shape (*num_virtual_tokens*, *embedding_dim*)
+    pub fn embeddings(&self) -> &Tensor {
+        &self.embeddings
+    }
+
+    /// Returns the variable store holding the prompt embeddings (e.g. to create an optimizer training the prompt only)
+    pub fn var_store(&self) -> &nn::VarStore {
+        &self.var_store
+    }
+
+    /// Prepends the prompt embeddings to a batch of input embeddings
+    ///
+    /// # Arguments
+    ///
+    /// * `input_embeds` - input embeddings of shape (*batch size*, *sequence_length*, *embedding_dim*)
+    ///
+    /// # Returns
+    ///
+    /// * `Tensor` of shape (*batch size*, *num_virtual_tokens* + *sequence_length*, *embedding_dim*)
+    pub fn prepend(&self, input_embeds: &Tensor) -> Tensor {
+        let batch_size = input_embeds.size()[0];
+        let prompt = self
+            .embeddings
+            .to_kind(input_embeds.kind())
+            .unsqueeze(0)
+            .expand(&[batch_size, -1, -1], true);
+        Tensor::cat(&[&prompt, input_embeds], 1)
+    }
+
+    /// Looks up the input ids in the word embeddings matrix provided and prepends the prompt embeddings
+    ///
+    /// # Arguments
+    ///
+    /// * `input_ids` - input ids of shape (*batch size*, *sequence_length*)
+    /// * `word_embeddings` - word embeddings matrix of the model, of shape (*vocab_size*, *embedding_dim*)
+    ///
+    /// # Returns
+    ///
+    /// * `Tensor` of shape (*batch size*, *num_virtual_tokens* + *sequence_length*, *embedding_dim*)
+    pub fn embed_input_ids(&self, input_ids: &Tensor, word_embeddings: &Tensor) -> Tensor {
+        self.prepend(&Tensor::embedding(
+            word_embeddings,
+            input_ids,
+            -1,
+            false,
+            false,
+        ))
+    }
+
+    /// Extends an attention mask of shape (*batch size*, *sequence_length*) to cover the virtual tokens of the prompt
+    pub fn extend_attention_mask(&self, attention_mask: &Tensor) -> Tensor {
+        let prompt_mask = Tensor::ones(
+            &[attention_mask.size()[0], self.num_virtual_tokens()],
+            (attention_mask.kind(), attention_mask.device()),
+        );
+        Tensor::cat(&[&prompt_mask, attention_mask], 1)
+    }
+}
+
+/// Saves the soft prompts of several tasks to a single file
+///
+/// # Arguments
+///
+/// * `soft_prompts` - soft prompts indexed by task name
+/// * `path` - path of the file to create
+pub fn save_soft_prompts<P: AsRef<Path>>(
+    soft_prompts: &HashMap<String, SoftPrompt>,
+    path: P,
+) -> Result<(), RustBertError> {
+    let named_tensors = soft_prompts
+        .iter()
+        .map(|(task, soft_prompt)| (task.as_str(), soft_prompt.embeddings()))
+        .collect::<Vec<(&str, &Tensor)>>();
+    Ok(Tensor::save_multi(&named_tensors, path)?)
+}
+
+/// Loads the soft prompts of several tasks saved with `save_soft_prompts`
+///
+/// # Arguments
+///
+/// * `path` - path to the saved prompts
+/// * `device` - device on which the prompt embeddings are loaded
+pub fn load_soft_prompts<P: AsRef<Path>>(
+    path: P,
+    device: Device,
+) -> Result<HashMap<String, SoftPrompt>, RustBertError> {
+    Tensor::load_multi_with_device(path, device)?
+        .into_iter()
+        .map(|(task, value)| Ok((task, SoftPrompt::from_tensor(&value)?)))
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    #[ignore] // no need to run, compilation is enough to verify it is Send
+    fn test() {
+        let _: Box<dyn Send> = Box::new(SoftPrompt::new(4, 8, Device::Cpu));
+    }
+}
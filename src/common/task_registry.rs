@@ -0,0 +1,158 @@
+// Copyright 2021 Guillaume Becquin
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//     http://www.apache.org/licenses/LICENSE-2.0
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! # Task-specific parameters registry
+//! Sequence-to-sequence checkpoints trained on several tasks (e.g. T5) ship the input prefix and generation defaults
+//! of each task in the `task_specific_params` field of their configuration. The `TaskRegistry` collects these
+//! parameters, keyed by task name (`summarization`, `translation_en_to_fr`...), and can be populated from the
+//! configuration of any model with `Config::task_registry` or extended manually.
+//!
+//! ```no_run
+//! # fn main() -> anyhow::Result<()> {
+//! use rust_bert::pipelines::generation_utils::GenerateConfig;
+//! use rust_bert::t5::T5Config;
+//! use rust_bert::Config;
+//! use std::path::Path;
+//!
+//! let config = T5Config::from_file(Path::new("path/to/config.json"));
+//! let task_registry = config.task_registry()?;
+//! let available_tasks = task_registry.tasks();
+//!
+//! let mut generate_config = GenerateConfig::default();
+//! if let Some(summarization) = task_registry.get("summarization") {
+//!     summarization.update_generate_config(&mut generate_config);
+//!     let prefix = summarization.prefix.as_deref();
+//! }
+//! # Ok(())
+//! # }
+//! ```
+
+use crate::pipelines::generation_utils::GenerateConfig;
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use std::collections::BTreeMap;
+
+/// # Parameters of a task
+/// Input prefix and generation defaults of a task. All fields are optional: the parameters not provided by the
+/// checkpoint are left unchanged when updating a `GenerateConfig`.
+#[derive(Debug, Serialize, Deserialize, Clone, Default, PartialEq)]
+pub struct TaskParams {
+    /// Prefix prepended to the input text (e.g. `summarize: `)
+    pub prefix: Option<String>,
+    pub min_length: Option<i64>,
+    pub max_length: Option<i64>,
+    pub num_beams: Option<i64>,
+    pub early_stopping: Option<bool>,
+    pub length_penalty: Option<f64>,
+    pub no_repeat_ngram_size: Option<i64>,
+    pub do_sample: Option<bool>,
+    pub temperature: Option<f64>,
+    pub top_k: Option<i64>,
+    pub top_p: Option<f64>,
+    pub repetition_penalty: Option<f64>,
+    /// Additional parameters of the task not mapped to a generation option
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+}
+
+impl TaskParams {
+    /// Overwrites the generation options of `generate_config` with the parameters defined for this task
+    ///
+    /// # Arguments
+    ///
+    /// * `generate_config` - `GenerateConfig` to update
+    pub fn update_generate_config(&self, generate_config: &mut GenerateConfig) {
+        macro_rules! update {
+            ($($field:ident),*) => {
+                $(if let Some(value) = self.$field {
+                    generate_config.$field = value;
+                })*
+            };
+        }
+        update!(
+            min_length,
+            max_length,
+            num_beams,
+            early_stopping,
+            length_penalty,
+            no_repeat_ngram_size,
+            do_sample,
+            temperature,
+            top_k,
+            top_p,
+            repetition_penalty
+        );
+    }
+
+    /// Prepends the task prefix (if any) to an input text
+    pub fn apply_prefix(&self, input: &str) -> String {
+        match &self.prefix {
+            Some(prefix) => format!("{}{}", prefix, input),
+            None => input.to_string(),
+        }
+    }
+}
+
+/// # Registry of task-specific parameters
+/// Maps task names to their `TaskParams`. Serializes to and deserializes from the `task_specific_params` format of
+/// the configuration files.
+#[derive(Debug, Serialize, Deserialize, Clone, Default, PartialEq)]
+#[serde(transparent)]
+pub struct TaskRegistry {
+    tasks: BTreeMap<String, TaskParams>,
+}
+
+impl TaskRegistry {
+    /// Creates an empty registry
+    pub fn new() -> TaskRegistry {
+        TaskRegistry::default()
+    }
+
+    /// Returns the name of the tasks available, in alphabetical order
+    pub fn tasks(&self) -> Vec<&str> {
+        self.tasks.keys().map(String::as_str).collect()
+    }
+
+    /// Returns true if the registry contains parameters for `task`
+    pub fn contains(&self, task: &str) -> bool {
+        self.tasks.contains_key(task)
+    }
+
+    /// Returns the parameters of a task, if registered
+    pub fn get(&self, task: &str) -> Option<&TaskParams> {
+        self.tasks.get(task)
+    }
+
+    /// Returns the input prefix of a task, if registered and defining a prefix
+    pub fn prefix(&self, task: &str) -> Option<&str> {
+        self.get(task).and_then(|params| params.prefix.as_deref())
+    }
+
+    /// Registers the parameters of a task, returning the parameters previously registered under this name
+    pub fn register(&mut self, task: &str, params: TaskParams) -> Option<TaskParams> {
+        self.tasks.insert(task.to_string(), params)
+    }
+
+    /// Adds the tasks of another registry, overwriting the tasks registered under the same name
+    pub fn extend(&mut self, other: TaskRegistry) {
+        self.tasks.extend(other.tasks);
+    }
+
+    /// Name of the translation task between two languages, following the configuration files naming convention
+    ///
+    /// # Arguments
+    ///
+    /// * `source_language` - ISO code of the source language (e.g. `en`)
+    /// * `target_language` - ISO code of the target language (e.g. `fr`)
+    pub fn translation_task(source_language: &str, target_language: &str) -> String {
+        format!("translation_{}_to_{}", source_language, target_language)
+    }
+}
@@ -0,0 +1,325 @@
+// Copyright 2021 Guillaume Becquin
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//     http://www.apache.org/licenses/LICENSE-2.0
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! # Numerical parity utilities
+//! Helpers to compare the activations of a model between two set-ups (e.g. CPU/GPU, single/half precision, or the
+//! original and a re-converted checkpoint). `run_parity_test` seeds the random generator before each run, so that
+//! seeded inputs and randomly initialized weights are identical across runs, collects the named activations returned by
+//! the forward closure and reports the differences of each tensor against the reference run.
+//!
+//! Some CUDA kernels are not deterministic: the tolerances of GPU comparisons should not be set below the expected
+//! run-to-run variation of the device.
+//!
+//! ```no_run
+//! # fn main() -> anyhow::Result<()> {
+//! use rust_bert::bert::{BertConfig, BertEmbeddings, BertModel};
+//! use rust_bert::testing::{
+//!     named_outputs, run_parity_test, seeded_input_ids, set_var_store_kind, Tolerance,
+//! };
+//! use rust_bert::Config;
+//! use tch::{nn, Device, Kind};
+//!
+//! let config = BertConfig::from_file("path/to/config.json");
+//! let report = run_parity_test(
+//!     (Device::Cpu, Kind::Float),
+//!     (Device::Cuda(0), Kind::Half),
+//!     Tolerance::for_kind(Kind::Half),
+//!     42,
+//!     |device, kind| {
+//!         let mut vs = nn::VarStore::new(device);
+//!         let model = BertModel::<BertEmbeddings>::new(&vs.root(), &config);
+//!         vs.load("path/to/model.ot")?;
+//!         set_var_store_kind(&vs, kind)?;
+//!         let input_ids = seeded_input_ids(&[2, 16], config.vocab_size).to(device);
+//!         let output = model.forward_t(Some(input_ids), None, None, None, None, &None, &None, false)?;
+//!         Ok(named_outputs(&output))
+//!     },
+//! )?;
+//! println!("{}", report);
+//! assert!(report.passed());
+//! # Ok(())
+//! # }
+//! ```
+
+use crate::common::error::RustBertError;
+use crate::ModelOutput;
+use std::fmt;
+use tch::nn::VarStore;
+use tch::{no_grad, Device, Kind, Tensor};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+/// # Tolerance of a parity test
+/// A candidate value `c` matches the reference value `r` if `|c - r| <= atol + rtol * |r|`
+pub struct Tolerance {
+    /// Absolute tolerance
+    pub atol: f64,
+    /// Relative tolerance
+    pub rtol: f64,
+}
+
+impl Tolerance {
+    /// Default tolerance for a candidate run in the precision provided
+    pub fn for_kind(kind: Kind) -> Tolerance {
+        match kind {
+            Kind::Half => Tolerance {
+                atol: 1e-2,
+                rtol: 1e-2,
+            },
+            Kind::Double => Tolerance {
+                atol: 1e-8,
+                rtol: 1e-6,
+            },
+            _ => Tolerance {
+                atol: 1e-4,
+                rtol: 1e-4,
+            },
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+/// # Difference between the reference and candidate values of a named tensor
+pub struct TensorDiff {
+    /// Name of the activation (e.g. `hidden_states.3`)
+    pub name: String,
+    /// Shape of the activation
+    pub shape: Vec<i64>,
+    /// Largest absolute difference
+    pub max_abs_diff: f64,
+    /// Mean absolute difference
+    pub mean_abs_diff: f64,
+    /// True if all values are within the tolerance (false if the candidate contains non-finite values)
+    pub within_tolerance: bool,
+}
+
+#[derive(Debug, Clone)]
+/// # Report of a parity test
+/// Contains the differences of each activation, in the order returned by the forward closure. The `Display`
+/// implementation prints one line per activation.
+pub struct ParityReport {
+    pub tolerance: Tolerance,
+    pub diffs: Vec<TensorDiff>,
+}
+
+impl ParityReport {
+    /// Returns true if all activations are within the tolerance
+    pub fn passed(&self) -> bool {
+        self.diffs.iter().all(|diff| diff.within_tolerance)
+    }
+
+    /// Returns the activations exceeding the tolerance
+    pub fn failures(&self) -> Vec<&TensorDiff> {
+        self.diffs
+            .iter()
+            .filter(|diff| !diff.within_tolerance)
+            .collect()
+    }
+
+    /// Returns the first activation exceeding the tolerance. For layer-wise outputs this is usually the layer
+    /// introducing the discrepancy.
+    pub fn first_failure(&self) -> Option<&TensorDiff> {
+        self.diffs.iter().find(|diff| !diff.within_tolerance)
+    }
+}
+
+impl fmt::Display for ParityReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "Parity report (atol: {:e}, rtol: {:e})",
+            self.tolerance.atol, self.tolerance.rtol
+        )?;
+        for diff in &self.diffs {
+            writeln!(
+                f,
+                "{} {:?}: max abs diff {:.3e}, mean abs diff {:.3e} [{}]",
+                diff.name,
+                diff.shape,
+                diff.max_abs_diff,
+                diff.mean_abs_diff,
+                if diff.within_tolerance { "OK" } else { "FAIL" }
+            )?;
+        }
+        write!(
+            f,
+            "{}/{} activations within tolerance",
+            self.diffs.len() - self.failures().len(),
+            self.diffs.len()
+        )
+    }
+}
+
+/// Generates deterministic input ids on the CPU, uniformly sampled in `[0, vocab_size)` after seeding the generator
+/// with the seed of the current parity run. Generating the inputs on the CPU keeps them identical across devices.
+///
+/// # Arguments
+///
+/// * `shape` - shape of the input ids (e.g. `[batch size, sequence length]`)
+/// * `vocab_size` - size of the vocabulary of the model
+pub fn seeded_input_ids(shape: &[i64], vocab_size: i64) -> Tensor {
+    Tensor::randint(vocab_size, shape, (Kind::Int64, Device::Cpu))
+}
+
+/// Casts the floating point variables of a variable store to `kind` (e.g. `Kind::Half`) in place, so that the models
+/// created on the store run with this precision. Integer variables (e.g. position ids buffers) are left unchanged.
+///
+/// # Arguments
+///
+/// * `var_store` - variable store of the model
+/// * `kind` - floating point kind of the variables
+pub fn set_var_store_kind(var_store: &VarStore, kind: Kind) -> Result<(), RustBertError> {
+    no_grad(|| -> Result<(), RustBertError> {
+        for (_, mut variable) in var_store.variables() {
+            if let Kind::Half | Kind::Float | Kind::Double | Kind::BFloat16 = variable.kind() {
+                let converted = variable.f_to_kind(kind)?;
+                let _ = variable.f_set_1(&converted)?;
+            }
+        }
+        Ok(())
+    })
+}
+
+/// Collects the activations of a model output as named tensors: `hidden_states.{layer}`, `attentions.{layer}` and the
+/// outputs of the head (`logits`, or `start_logits` and `end_logits`)
+pub fn named_outputs<O: ModelOutput>(output: &O) -> Vec<(String, Tensor)> {
+    let mut activations = vec![];
+    if let Some(hidden_states) = output.hidden_states() {
+        for (layer, hidden_state) in hidden_states.into_iter().enumerate() {
+            activations.push((
+                format!("hidden_states.{}", layer),
+                hidden_state.shallow_clone(),
+            ));
+        }
+    }
+    if let Some(attentions) = output.attentions() {
+        for (layer, attention) in attentions.into_iter().enumerate() {
+            activations.push((format!("attentions.{}", layer), attention.shallow_clone()));
+        }
+    }
+    if let Some(logits) = output.logits() {
+        activations.push(("logits".to_string(), logits.shallow_clone()));
+    }
+    if let Some((start_logits, end_logits)) = output.start_end_logits() {
+        activations.push(("start_logits".to_string(), start_logits.shallow_clone()));
+        activations.push(("end_logits".to_string(), end_logits.shallow_clone()));
+    }
+    activations
+}
+
+/// Compares two sets of named activations. Both sets must contain the same names with the same shapes.
+///
+/// # Arguments
+///
+/// * `reference` - reference activations
+/// * `candidate` - activations to compare to the reference
+/// * `tolerance` - `Tolerance` of the comparison
+pub fn compare_activations(
+    reference: &[(String, Tensor)],
+    candidate: &[(String, Tensor)],
+    tolerance: Tolerance,
+) -> Result<ParityReport, RustBertError> {
+    if reference.len() != candidate.len() {
+        return Err(RustBertError::ValueError(format!(
+            "Reference and candidate runs returned a different number of activations ({} and {})",
+            reference.len(),
+            candidate.len()
+        )));
+    }
+    let mut diffs = Vec::with_capacity(reference.len());
+    for ((name, reference_value), (candidate_name, candidate_value)) in
+        reference.iter().zip(candidate.iter())
+    {
+        if name != candidate_name {
+            return Err(RustBertError::ValueError(format!(
+                "Activation names do not match: expected {}, got {}",
+                name, candidate_name
+            )));
+        }
+        if reference_value.size() != candidate_value.size() {
+            return Err(RustBertError::ValueError(format!(
+                "Shape mismatch for {}: expected {:?}, got {:?}",
+                name,
+                reference_value.size(),
+                candidate_value.size()
+            )));
+        }
+        diffs.push(tensor_diff(
+            name,
+            reference_value,
+            candidate_value,
+            tolerance,
+        ));
+    }
+    Ok(ParityReport { tolerance, diffs })
+}
+
+fn tensor_diff(
+    name: &str,
+    reference: &Tensor,
+    candidate: &Tensor,
+    tolerance: Tolerance,
+) -> TensorDiff {
+    let reference = reference.to(Device::Cpu).to_kind(Kind::Double);
+    let candidate = candidate.to(Device::Cpu).to_kind(Kind::Double);
+    let (max_abs_diff, mean_abs_diff, within_tolerance) = if reference.numel() == 0 {
+        (0.0, 0.0, true)
+    } else {
+        let abs_diff = (&candidate - &reference).abs();
+        let threshold = reference.abs() * tolerance.rtol + tolerance.atol;
+        // NaN values fail the comparison
+        let num_within_tolerance = abs_diff.le1(&threshold).sum(Kind::Int64).int64_value(&[]);
+        (
+            abs_diff.max().double_value(&[]),
+            abs_diff.mean(Kind::Double).double_value(&[]),
+            num_within_tolerance == reference.numel() as i64,
+        )
+    };
+    TensorDiff {
+        name: name.to_string(),
+        shape: reference.size(),
+        max_abs_diff,
+        mean_abs_diff,
+        within_tolerance,
+    }
+}
+
+/// Runs a model in a reference and a candidate set-up and compares their activations.
+/// The `tch` random generator is seeded with `seed` before each run: inputs created with `seeded_input_ids` and layers
+/// initialized randomly are identical for both runs. The forward closure runs without gradient tracking.
+///
+/// # Arguments
+///
+/// * `reference` - (`Device`, `Kind`) of the reference run
+/// * `candidate` - (`Device`, `Kind`) of the candidate run
+/// * `tolerance` - `Tolerance` of the comparison
+/// * `seed` - seed of the random generator
+/// * `forward` - closure building the model on the device and precision provided and returning its named activations
+/// (e.g. with `named_outputs`)
+///
+/// # Returns
+///
+/// * `ParityReport` with the differences for each activation
+pub fn run_parity_test<F>(
+    reference: (Device, Kind),
+    candidate: (Device, Kind),
+    tolerance: Tolerance,
+    seed: i64,
+    mut forward: F,
+) -> Result<ParityReport, RustBertError>
+where
+    F: FnMut(Device, Kind) -> Result<Vec<(String, Tensor)>, RustBertError>,
+{
+    tch::manual_seed(seed);
+    let reference_activations = no_grad(|| forward(reference.0, reference.1))?;
+    tch::manual_seed(seed);
+    let candidate_activations = no_grad(|| forward(candidate.0, candidate.1))?;
+    compare_activations(&reference_activations, &candidate_activations, tolerance)
+}
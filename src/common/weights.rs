@@ -0,0 +1,145 @@
+// Copyright 2021 Guillaume Becquin
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//     http://www.apache.org/licenses/LICENSE-2.0
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use crate::common::error::RustBertError;
+use crate::common::resources::Resource;
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use tch::nn::VarStore;
+use tch::{no_grad, Tensor};
+
+/// Version of the model weights, incremented each time the weights of a variable store are replaced in place. The
+/// values derived from the weights and cached by the models (e.g. the T5 relative attention biases) are computed
+/// again when it changes.
+static WEIGHTS_VERSION: AtomicUsize = AtomicUsize::new(0);
+
+pub(crate) fn weights_version() -> usize {
+    WEIGHTS_VERSION.load(Ordering::SeqCst)
+}
+
+/// Replaces the content of the variables of an existing `VarStore` with the weights stored in the resource provided.
+///
+/// The new weights are fully loaded and checked against the variables of the store (names and shapes) before any
+/// variable is updated: the store is either entirely updated or left untouched. Variables are updated in place, so
+/// that models referencing them pick up the new weights without being re-created.
+pub(crate) fn reload_var_store(
+    var_store: &mut VarStore,
+    weights_resource: &Resource,
+) -> Result<(), RustBertError> {
+    let weights_path = weights_resource.get_local_path()?;
+    let new_weights: HashMap<String, Tensor> =
+        Tensor::load_multi_with_device(&weights_path, var_store.device())?
+            .into_iter()
+            .collect();
+    let variables = var_store.variables();
+
+    for (name, variable) in variables.iter() {
+        match new_weights.get(name) {
+            Some(new_value) => {
+                if new_value.size() != variable.size() {
+                    return Err(RustBertError::ValueError(format!(
+                        "Shape mismatch for variable {}: expected {:?}, got {:?} in {:?}",
+                        name,
+                        variable.size(),
+                        new_value.size(),
+                        weights_path
+                    )));
+                }
+            }
+            None => {
+                return Err(RustBertError::ValueError(format!(
+                    "Variable {} not found in {:?}",
+                    name, weights_path
+                )));
+            }
+        }
+    }
+
+    let updated = no_grad(|| -> Result<(), RustBertError> {
+        for (name, mut variable) in variables {
+            variable.f_copy_(&new_weights[&name])?;
+        }
+        Ok(())
+    });
+    WEIGHTS_VERSION.fetch_add(1, Ordering::SeqCst);
+    updated
+}
+
+/// Makes the variables of a `VarStore` share the memory of the variables with the same names in `source`, so that the
+/// models created on both variable stores use the same weights. Updates of the weights of one of the stores in place
+/// (e.g. with `reload_var_store`) are visible from the other store.
+pub(crate) fn share_var_store(
+    var_store: &mut VarStore,
+    source: &VarStore,
+) -> Result<(), RustBertError> {
+    let source_variables = source.variables();
+    no_grad(|| -> Result<(), RustBertError> {
+        for (name, mut variable) in var_store.variables() {
+            let source_variable = source_variables.get(&name).ok_or_else(|| {
+                RustBertError::ValueError(format!(
+                    "Variable {} not found in the shared weights",
+                    name
+                ))
+            })?;
+            let _ = variable.f_set_1(source_variable)?;
+        }
+        Ok(())
+    })
+}
+
+/// Resizes the first (vocabulary) dimension of a variable in place, e.g. an embedding matrix, language model head or
+/// bias. Existing rows are kept, additional rows are drawn from a normal distribution with the standard deviation
+/// provided (or set to zero if `None`). The variable is modified in place, so that the variable store and the modules
+/// sharing the variable (e.g. tied embeddings and language model heads) see the resized tensor.
+pub(crate) fn resize_token_dimension(
+    variable: &mut Tensor,
+    new_num_tokens: i64,
+    init_std: Option<f64>,
+) -> Result<(), RustBertError> {
+    if new_num_tokens <= 0 {
+        return Err(RustBertError::ValueError(format!(
+            "The number of tokens must be positive, got {}",
+            new_num_tokens
+        )));
+    }
+    let mut new_size = variable.size();
+    let old_num_tokens = new_size[0];
+    if old_num_tokens == new_num_tokens {
+        return Ok(());
+    }
+    new_size[0] = new_num_tokens;
+    let requires_grad = variable.requires_grad();
+    let old_values = variable.detach().copy();
+    let num_copied = old_num_tokens.min(new_num_tokens);
+
+    no_grad(|| -> Result<(), RustBertError> {
+        // resizing is not allowed for variables requiring gradients
+        let _ = variable.set_requires_grad(false);
+        let _ = variable.f_resize_(&new_size)?;
+        variable
+            .f_narrow(0, 0, num_copied)?
+            .f_copy_(&old_values.f_narrow(0, 0, num_copied)?)?;
+        if new_num_tokens > old_num_tokens {
+            let mut new_rows =
+                variable.f_narrow(0, old_num_tokens, new_num_tokens - old_num_tokens)?;
+            match init_std {
+                Some(std) => {
+                    let _ = new_rows.f_normal_(0.0, std)?;
+                }
+                None => {
+                    let _ = new_rows.f_zero_()?;
+                }
+            };
+        }
+        let _ = variable.set_requires_grad(requires_grad);
+        Ok(())
+    })
+}
@@ -14,11 +14,17 @@ extern crate tch;
 
 use self::tch::{nn, Tensor};
 use crate::common::activations::Activation;
+use crate::common::classification_head::{
+    ClassificationHead, ClassificationHeadConfig, ClassifierOption,
+};
+use crate::common::config::{check_divisible, deserialize_id2label};
 use crate::common::dropout::Dropout;
+use crate::common::model_output::{tensor_refs, ModelOutput};
 use crate::distilbert::embeddings::DistilBertEmbedding;
 use crate::distilbert::transformer::{DistilBertTransformerOutput, Transformer};
 use crate::{Config, RustBertError};
 use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
 use std::{borrow::Borrow, collections::HashMap};
 
 /// # DistilBERT Pretrained model weight files
@@ -93,6 +99,7 @@ pub struct DistilBertConfig {
     pub dim: i64,
     pub dropout: f64,
     pub hidden_dim: i64,
+    #[serde(default, deserialize_with = "deserialize_id2label")]
     pub id2label: Option<HashMap<i64, String>>,
     pub initializer_range: f32,
     pub is_decoder: Option<bool>,
@@ -110,9 +117,20 @@ pub struct DistilBertConfig {
     pub torchscript: Option<bool>,
     pub use_bfloat16: Option<bool>,
     pub vocab_size: i64,
+    /// Additional fields of the configuration file not used by the model
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
 }
 
-impl Config<DistilBertConfig> for DistilBertConfig {}
+impl Config<DistilBertConfig> for DistilBertConfig {
+    fn extra_fields(&self) -> Option<&Map<String, Value>> {
+        Some(&self.extra)
+    }
+
+    fn validate(&self) -> Result<(), RustBertError> {
+        check_divisible("dim", self.dim, "n_heads", self.n_heads)
+    }
+}
 
 /// # DistilBERT Base model
 /// Base architecture for DistilBERT models. Task-specific models will be built from this common base model
@@ -237,10 +255,11 @@ impl DistilBertModel {
 /// - `distil_bert_model`: Base DistilBertModel
 /// - `pre_classifier`: DistilBERT linear layer for classification
 /// - `classifier`: DistilBERT linear layer for classification
+///
+/// The pre-classifier and classifier are replaced by a `ClassificationHead` when built with `new_with_head`.
 pub struct DistilBertModelClassifier {
     distil_bert_model: DistilBertModel,
-    pre_classifier: nn::Linear,
-    classifier: nn::Linear,
+    classifier: ClassifierOption<(nn::Linear, nn::Linear)>,
     dropout: Dropout,
 }
 
@@ -271,8 +290,33 @@ impl DistilBertModelClassifier {
     where
         P: Borrow<nn::Path<'p>>,
     {
-        let p = p.borrow();
+        DistilBertModelClassifier::build(p.borrow(), config, None)
+    }
 
+    /// Build a new `DistilBertModelClassifier` with a custom classification head, for checkpoints fine-tuned with a
+    /// head different from the default pre-classifier and classifier layers
+    ///
+    /// # Arguments
+    ///
+    /// * `p` - Variable store path for the root of the DistilBertModelClassifier model
+    /// * `config` - `DistilBertConfig` object defining the model architecture
+    /// * `head_config` - `ClassificationHeadConfig` defining the classification head architecture
+    pub fn new_with_head<'p, P>(
+        p: P,
+        config: &DistilBertConfig,
+        head_config: &ClassificationHeadConfig,
+    ) -> DistilBertModelClassifier
+    where
+        P: Borrow<nn::Path<'p>>,
+    {
+        DistilBertModelClassifier::build(p.borrow(), config, Some(head_config))
+    }
+
+    fn build(
+        p: &nn::Path,
+        config: &DistilBertConfig,
+        head_config: Option<&ClassificationHeadConfig>,
+    ) -> DistilBertModelClassifier {
         let distil_bert_model = DistilBertModel::new(p, config);
 
         let num_labels = config
@@ -281,18 +325,30 @@ impl DistilBertModelClassifier {
             .expect("id2label must be provided for classifiers")
             .len() as i64;
 
-        let pre_classifier = nn::linear(
-            p / "pre_classifier",
-            config.dim,
-            config.dim,
-            Default::default(),
-        );
-        let classifier = nn::linear(p / "classifier", config.dim, num_labels, Default::default());
+        let classifier = match head_config {
+            Some(head_config) => ClassifierOption::Custom(ClassificationHead::new(
+                p / "classifier",
+                config.dim,
+                num_labels,
+                head_config,
+                config.seq_classif_dropout,
+            )),
+            None => {
+                let pre_classifier = nn::linear(
+                    p / "pre_classifier",
+                    config.dim,
+                    config.dim,
+                    Default::default(),
+                );
+                let classifier =
+                    nn::linear(p / "classifier", config.dim, num_labels, Default::default());
+                ClassifierOption::Default((pre_classifier, classifier))
+            }
+        };
         let dropout = Dropout::new(config.seq_classif_dropout);
 
         DistilBertModelClassifier {
             distil_bert_model,
-            pre_classifier,
             classifier,
             dropout,
         }
@@ -347,17 +403,23 @@ impl DistilBertModelClassifier {
         input_embeds: Option<Tensor>,
         train: bool,
     ) -> Result<DistilBertSequenceClassificationOutput, RustBertError> {
+        let head_mask = mask.as_ref().map(Tensor::shallow_clone);
         let base_model_output =
             self.distil_bert_model
                 .forward_t(input, mask, input_embeds, train)?;
 
-        let logits = base_model_output
-            .hidden_state
-            .select(1, 0)
-            .apply(&self.pre_classifier)
-            .relu()
-            .apply_t(&self.dropout, train)
-            .apply(&self.classifier);
+        let logits = match &self.classifier {
+            ClassifierOption::Default((pre_classifier, classifier)) => base_model_output
+                .hidden_state
+                .select(1, 0)
+                .apply(pre_classifier)
+                .relu()
+                .apply_t(&self.dropout, train)
+                .apply(classifier),
+            ClassifierOption::Custom(head) => {
+                head.forward_t(&base_model_output.hidden_state, head_mask.as_ref(), train)
+            }
+        };
 
         Ok(DistilBertSequenceClassificationOutput {
             logits,
@@ -758,6 +820,20 @@ pub struct DistilBertMaskedLMOutput {
     pub all_attentions: Option<Vec<Tensor>>,
 }
 
+impl ModelOutput for DistilBertMaskedLMOutput {
+    fn hidden_states(&self) -> Option<Vec<&Tensor>> {
+        tensor_refs(&self.all_hidden_states)
+    }
+
+    fn attentions(&self) -> Option<Vec<&Tensor>> {
+        tensor_refs(&self.all_attentions)
+    }
+
+    fn logits(&self) -> Option<&Tensor> {
+        Some(&self.prediction_scores)
+    }
+}
+
 /// Container for the DistilBERT sequence classification model output
 pub struct DistilBertSequenceClassificationOutput {
     /// Logits for each input (sequence) for each target class
@@ -767,6 +843,20 @@ pub struct DistilBertSequenceClassificationOutput {
     /// Attention weights for all intermediate layers
     pub all_attentions: Option<Vec<Tensor>>,
 }
+
+impl ModelOutput for DistilBertSequenceClassificationOutput {
+    fn hidden_states(&self) -> Option<Vec<&Tensor>> {
+        tensor_refs(&self.all_hidden_states)
+    }
+
+    fn attentions(&self) -> Option<Vec<&Tensor>> {
+        tensor_refs(&self.all_attentions)
+    }
+
+    fn logits(&self) -> Option<&Tensor> {
+        Some(&self.logits)
+    }
+}
 /// Container for the DistilBERT token classification model output
 pub struct DistilBertTokenClassificationOutput {
     /// Logits for each sequence item (token) for each target class
@@ -776,6 +866,20 @@ pub struct DistilBertTokenClassificationOutput {
     /// Attention weights for all intermediate layers
     pub all_attentions: Option<Vec<Tensor>>,
 }
+
+impl ModelOutput for DistilBertTokenClassificationOutput {
+    fn hidden_states(&self) -> Option<Vec<&Tensor>> {
+        tensor_refs(&self.all_hidden_states)
+    }
+
+    fn attentions(&self) -> Option<Vec<&Tensor>> {
+        tensor_refs(&self.all_attentions)
+    }
+
+    fn logits(&self) -> Option<&Tensor> {
+        Some(&self.logits)
+    }
+}
 /// Container for the DistilBERT question answering model output
 pub struct DistilBertQuestionAnsweringOutput {
     /// Logits for the start position for token of each input sequence
@@ -787,3 +891,21 @@ pub struct DistilBertQuestionAnsweringOutput {
     /// Attention weights for all intermediate layers
     pub all_attentions: Option<Vec<Tensor>>,
 }
+
+impl ModelOutput for DistilBertQuestionAnsweringOutput {
+    fn hidden_states(&self) -> Option<Vec<&Tensor>> {
+        tensor_refs(&self.all_hidden_states)
+    }
+
+    fn attentions(&self) -> Option<Vec<&Tensor>> {
+        tensor_refs(&self.all_attentions)
+    }
+
+    fn logits(&self) -> Option<&Tensor> {
+        None
+    }
+
+    fn start_end_logits(&self) -> Option<(&Tensor, &Tensor)> {
+        Some((&self.start_logits, &self.end_logits))
+    }
+}
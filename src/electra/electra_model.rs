@@ -14,11 +14,14 @@
 
 use crate::bert::BertConfig;
 use crate::common::activations::Activation;
+use crate::common::config::{check_divisible, check_token_id, deserialize_id2label};
 use crate::common::dropout::Dropout;
+use crate::common::model_output::{tensor_refs, ModelOutput};
 use crate::electra::embeddings::ElectraEmbeddings;
 use crate::{bert::encoder::BertEncoder, common::activations::TensorFunction};
 use crate::{Config, RustBertError};
 use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
 use std::{borrow::Borrow, collections::HashMap};
 use tch::{nn, Kind, Tensor};
 
@@ -91,11 +94,29 @@ pub struct ElectraConfig {
     pub output_past: Option<bool>,
     pub output_attentions: Option<bool>,
     pub output_hidden_states: Option<bool>,
+    #[serde(default, deserialize_with = "deserialize_id2label")]
     pub id2label: Option<HashMap<i64, String>>,
     pub label2id: Option<HashMap<String, i64>>,
+    /// Additional fields of the configuration file not used by the model
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
 }
 
-impl Config<ElectraConfig> for ElectraConfig {}
+impl Config<ElectraConfig> for ElectraConfig {
+    fn extra_fields(&self) -> Option<&Map<String, Value>> {
+        Some(&self.extra)
+    }
+
+    fn validate(&self) -> Result<(), RustBertError> {
+        check_divisible(
+            "hidden_size",
+            self.hidden_size,
+            "num_attention_heads",
+            self.num_attention_heads,
+        )?;
+        check_token_id("pad_token_id", Some(self.pad_token_id), self.vocab_size)
+    }
+}
 
 /// # Electra Base model
 /// Base architecture for Electra models.
@@ -166,6 +187,7 @@ impl ElectraModel {
             is_decoder: None,
             id2label: config.id2label.clone(),
             label2id: config.label2id.clone(),
+            extra: Map::new(),
         };
         let encoder = BertEncoder::new(p / "encoder", &bert_config);
         ElectraModel {
@@ -908,6 +930,20 @@ pub struct ElectraModelOutput {
     pub all_attentions: Option<Vec<Tensor>>,
 }
 
+impl ModelOutput for ElectraModelOutput {
+    fn hidden_states(&self) -> Option<Vec<&Tensor>> {
+        tensor_refs(&self.all_hidden_states)
+    }
+
+    fn attentions(&self) -> Option<Vec<&Tensor>> {
+        tensor_refs(&self.all_attentions)
+    }
+
+    fn logits(&self) -> Option<&Tensor> {
+        None
+    }
+}
+
 /// Container for the Electra discriminator model output.
 pub struct ElectraDiscriminatorOutput {
     /// Probabilities for each sequence item (token) to be generated by a language model
@@ -918,6 +954,20 @@ pub struct ElectraDiscriminatorOutput {
     pub all_attentions: Option<Vec<Tensor>>,
 }
 
+impl ModelOutput for ElectraDiscriminatorOutput {
+    fn hidden_states(&self) -> Option<Vec<&Tensor>> {
+        tensor_refs(&self.all_hidden_states)
+    }
+
+    fn attentions(&self) -> Option<Vec<&Tensor>> {
+        tensor_refs(&self.all_attentions)
+    }
+
+    fn logits(&self) -> Option<&Tensor> {
+        Some(&self.probabilities)
+    }
+}
+
 /// Container for the Electra masked LM model output.
 pub struct ElectraMaskedLMOutput {
     /// Logits for the vocabulary items at each sequence position
@@ -928,6 +978,20 @@ pub struct ElectraMaskedLMOutput {
     pub all_attentions: Option<Vec<Tensor>>,
 }
 
+impl ModelOutput for ElectraMaskedLMOutput {
+    fn hidden_states(&self) -> Option<Vec<&Tensor>> {
+        tensor_refs(&self.all_hidden_states)
+    }
+
+    fn attentions(&self) -> Option<Vec<&Tensor>> {
+        tensor_refs(&self.all_attentions)
+    }
+
+    fn logits(&self) -> Option<&Tensor> {
+        Some(&self.prediction_scores)
+    }
+}
+
 /// Container for the Electra token classification model output.
 pub struct ElectraTokenClassificationOutput {
     /// Logits for each sequence item (token) for each target class
@@ -937,3 +1001,17 @@ pub struct ElectraTokenClassificationOutput {
     /// Attention weights for all intermediate layers
     pub all_attentions: Option<Vec<Tensor>>,
 }
+
+impl ModelOutput for ElectraTokenClassificationOutput {
+    fn hidden_states(&self) -> Option<Vec<&Tensor>> {
+        tensor_refs(&self.all_hidden_states)
+    }
+
+    fn attentions(&self) -> Option<Vec<&Tensor>> {
+        tensor_refs(&self.all_attentions)
+    }
+
+    fn logits(&self) -> Option<&Tensor> {
+        Some(&self.logits)
+    }
+}
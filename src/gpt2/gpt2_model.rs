@@ -13,12 +13,17 @@
 // limitations under the License.
 
 use crate::common::activations::Activation;
+use crate::common::config::check_divisible;
 use crate::common::dropout::Dropout;
-use crate::common::linear::{linear_no_bias, LinearNoBias};
+use crate::common::lm_head::LMHead;
+use crate::common::model_output::{tensor_refs, ModelOutput};
+use crate::common::prefix_tuning::PrefixTuning;
+use crate::common::weights::resize_token_dimension;
 use crate::gpt2::transformer::Block;
 use crate::pipelines::generation_utils::{Cache, LMHeadModel, LMModelOutput};
 use crate::{Config, RustBertError};
 use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
 use std::borrow::{Borrow, BorrowMut};
 use tch::kind::Kind::Int64;
 use tch::nn::embedding;
@@ -190,9 +195,20 @@ pub struct Gpt2Config {
     pub output_hidden_states: Option<bool>,
     pub resid_pdrop: Option<f64>,
     pub vocab_size: i64,
+    /// Additional fields of the configuration file not used by the model
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
 }
 
-impl Config<Gpt2Config> for Gpt2Config {}
+impl Config<Gpt2Config> for Gpt2Config {
+    fn extra_fields(&self) -> Option<&Map<String, Value>> {
+        Some(&self.extra)
+    }
+
+    fn validate(&self) -> Result<(), RustBertError> {
+        check_divisible("n_embd", self.n_embd, "n_head", self.n_head)
+    }
+}
 
 /// # GPT2 Base model
 /// Base architecture for GPT2 model. Usually complemented with a task-specific head, such as a language model head.
@@ -212,6 +228,7 @@ pub struct Gpt2Model {
     output_past: bool,
     output_hidden_states: bool,
     output_attentions: bool,
+    initializer_range: f64,
 }
 
 impl Gpt2Model {
@@ -280,6 +297,7 @@ impl Gpt2Model {
             output_past,
             output_hidden_states,
             output_attentions,
+            initializer_range: config.initializer_range,
         }
     }
 
@@ -471,16 +489,34 @@ impl Gpt2Model {
             all_attentions,
         })
     }
+
+    /// Resizes the token embeddings matrix (`wte`) of the model, e.g. after adding domain-specific tokens to the
+    /// vocabulary. The embeddings of the existing tokens are kept and the new embeddings are initialized from a normal
+    /// distribution with the standard deviation `initializer_range` of the configuration.
+    ///
+    /// # Arguments
+    ///
+    /// * `new_num_tokens` - new size of the vocabulary (smaller sizes remove the last tokens of the vocabulary)
+    pub fn resize_token_embeddings(&mut self, new_num_tokens: i64) -> Result<(), RustBertError> {
+        resize_token_dimension(
+            &mut self.wte.ws,
+            new_num_tokens,
+            Some(self.initializer_range),
+        )
+    }
 }
 
 /// # GPT2 Language Modeling head
 /// GPT2 model with a decoding head (linear layer without bias). The weights of the linear layer are tied to the word embeddings
 /// It is made of the following blocks:
 /// - `transformer`: Base Gpt2Model
-/// - `lm_head`: Linear layer without bias tied to the weights of the token id embeddings
+/// - `lm_head`: `LMHead` projecting the hidden states on the vocabulary. The pretrained checkpoints store a linear layer
+/// without bias with weights equal to the token id embeddings, see `tie_lm_head` to share the variables.
+/// - `prefix`: Optional prefix-tuning keys and values prepended to the past of each layer
 pub struct GPT2LMHeadModel {
     transformer: Gpt2Model,
-    lm_head: LinearNoBias,
+    lm_head: LMHead,
+    prefix: Option<PrefixTuning>,
 }
 
 impl GPT2LMHeadModel {
@@ -512,16 +548,102 @@ impl GPT2LMHeadModel {
         let p = p.borrow();
 
         let transformer = Gpt2Model::new(p, config);
-        let lm_head = linear_no_bias(
+        let lm_head = LMHead::Linear(nn::linear(
             p / "lm_head",
             config.n_embd,
             config.vocab_size,
-            Default::default(),
-        );
+            nn::LinearConfig {
+                bias: false,
+                ..Default::default()
+            },
+        ));
         GPT2LMHeadModel {
             transformer,
             lm_head,
+            prefix: None,
+        }
+    }
+
+    /// Sets (or removes if `None`) the prefix-tuning keys and values used by the model. When a prefix is set,
+    /// its keys and values are prepended to the past of each layer at the first forward pass, and the attention
+    /// mask provided (covering the input positions) is extended to cover the prefix.
+    ///
+    /// # Arguments
+    ///
+    /// * `prefix` - Optional `PrefixTuning` with one key/value pair per layer of the model
+    pub fn set_prefix(&mut self, prefix: Option<PrefixTuning>) -> Result<(), RustBertError> {
+        if let Some(prefix) = &prefix {
+            prefix.check_num_layers(self.transformer.h.len())?;
         }
+        self.prefix = prefix;
+        Ok(())
+    }
+
+    /// Resizes the token embeddings matrix and the language model head of the model to `new_num_tokens` tokens,
+    /// keeping the weights of the existing tokens.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// # fn main() -> anyhow::Result<()> {
+    /// use rust_bert::gpt2::{GPT2LMHeadModel, Gpt2Config};
+    /// use rust_bert::Config;
+    /// use std::path::Path;
+    /// use tch::{nn, Device};
+    ///
+    /// let mut vs = nn::VarStore::new(Device::Cpu);
+    /// let config = Gpt2Config::from_file(Path::new("path/to/config.json"));
+    /// let mut gpt2_model = GPT2LMHeadModel::new(&vs.root(), &config);
+    /// vs.load("path/to/model.ot")?;
+    /// gpt2_model.resize_token_embeddings(config.vocab_size + 2)?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn resize_token_embeddings(&mut self, new_num_tokens: i64) -> Result<(), RustBertError> {
+        self.transformer.resize_token_embeddings(new_num_tokens)?;
+        self.lm_head
+            .resize(new_num_tokens, self.transformer.initializer_range)
+    }
+
+    /// Returns the language model head of the model
+    pub fn lm_head(&self) -> &LMHead {
+        &self.lm_head
+    }
+
+    /// Ties the language model head to the token embeddings (`wte`) of the model: the logits are computed by projecting
+    /// the hidden states on the embeddings matrix, and updates of the embeddings during fine-tuning apply to both.
+    pub fn tie_lm_head(&mut self) {
+        self.lm_head = LMHead::Tied;
+    }
+
+    /// Unties the language model head from the token embeddings, creating a linear layer initialized with a copy of
+    /// the embeddings. The variables of the head are created under the path provided (`{p}.weight`), which should not
+    /// already contain a `weight` variable. This has no effect if the head is not tied.
+    ///
+    /// # Arguments
+    ///
+    /// * `p` - Variable store path for the new language model head
+    pub fn untie_lm_head<'p, P>(&mut self, p: P) -> Result<(), RustBertError>
+    where
+        P: Borrow<nn::Path<'p>>,
+    {
+        if self.lm_head.is_tied() {
+            self.lm_head = LMHead::untied_from(p, &self.transformer.wte.ws)?;
+        }
+        Ok(())
+    }
+
+    /// Replaces the language model head with the linear layer provided, of shape (*output_vocabulary_size*, *n_embd*).
+    /// The output vocabulary can differ from the input vocabulary (e.g. a restricted vocabulary), the generated ids
+    /// are then positions in the output vocabulary.
+    ///
+    /// # Arguments
+    ///
+    /// * `lm_head` - Linear layer projecting the hidden states on the output vocabulary
+    pub fn set_lm_head(&mut self, lm_head: nn::Linear) -> Result<(), RustBertError> {
+        LMHead::check_linear(&lm_head, self.transformer.wte.ws.size()[1])?;
+        self.lm_head = LMHead::Linear(lm_head);
+        Ok(())
     }
 }
 
@@ -610,6 +732,43 @@ impl LMHeadModel for GPT2LMHeadModel {
         _decoder_input_ids: &Option<Tensor>,
         train: bool,
     ) -> Result<LMModelOutput, RustBertError> {
+        let (layer_past, prefix_attention_mask) = match &self.prefix {
+            Some(prefix) => {
+                let layer_past = match layer_past {
+                    Cache::GPT2Cache(Some(layer_past)) => Cache::GPT2Cache(Some(layer_past)),
+                    Cache::GPT2Cache(None) | Cache::None => {
+                        let batch_size = match (input_ids, input_embeds) {
+                            (Some(input_ids), _) => input_ids.size()[0],
+                            (None, Some(input_embeds)) => input_embeds.size()[0],
+                            (None, None) => {
+                                return Err(RustBertError::ValueError(
+                                    "At least one of input ids or input embeddings must be set"
+                                        .into(),
+                                ));
+                            }
+                        };
+                        Cache::GPT2Cache(Some(
+                            prefix
+                                .batch_key_values(batch_size)
+                                .iter()
+                                .map(|(key, value)| Tensor::stack(&[key, value], 0))
+                                .collect(),
+                        ))
+                    }
+                    other => other,
+                };
+                let prefix_attention_mask = attention_mask
+                    .as_ref()
+                    .map(|mask| prefix.extend_attention_mask(mask));
+                (layer_past, prefix_attention_mask)
+            }
+            None => (layer_past, None),
+        };
+        let attention_mask = if prefix_attention_mask.is_some() {
+            &prefix_attention_mask
+        } else {
+            attention_mask
+        };
         let base_model_output = match layer_past {
             Cache::GPT2Cache(layer_past) => self.transformer.forward_t(
                 input_ids,
@@ -636,7 +795,9 @@ impl LMHeadModel for GPT2LMHeadModel {
             }
         }?;
 
-        let lm_logits = base_model_output.output.apply(&self.lm_head);
+        let lm_logits = self
+            .lm_head
+            .forward(&base_model_output.output, &self.transformer.wte.ws);
         Ok(LMModelOutput {
             lm_logits,
             cache: Cache::GPT2Cache(base_model_output.cache),
@@ -656,3 +817,17 @@ pub struct Gpt2ModelOutput {
     /// Attention weights for all intermediate layers
     pub all_attentions: Option<Vec<Tensor>>,
 }
+
+impl ModelOutput for Gpt2ModelOutput {
+    fn hidden_states(&self) -> Option<Vec<&Tensor>> {
+        tensor_refs(&self.all_hidden_states)
+    }
+
+    fn attentions(&self) -> Option<Vec<&Tensor>> {
+        tensor_refs(&self.all_attentions)
+    }
+
+    fn logits(&self) -> Option<&Tensor> {
+        None
+    }
+}
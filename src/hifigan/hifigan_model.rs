@@ -0,0 +1,330 @@
+// Copyright 2020 Jungil Kong, Jaehyeon Kim, Jaekyoung Bae and the HuggingFace Inc. team.
+// Copyright 2021 Guillaume Becquin
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//     http://www.apache.org/licenses/LICENSE-2.0
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use crate::common::config::check_positive;
+use crate::{Config, RustBertError};
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use std::borrow::Borrow;
+use tch::{nn, Tensor};
+
+fn default_leaky_relu_slope() -> f64 {
+    0.1
+}
+
+fn default_normalize_before() -> bool {
+    true
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+/// # HiFi-GAN vocoder configuration
+/// Defines the HiFi-GAN architecture (number of mel bins, upsampling layers, residual blocks...)
+pub struct HifiGanConfig {
+    /// Number of mel bins of the input spectrogram
+    pub model_in_dim: i64,
+    /// Sampling rate of the generated waveform
+    pub sampling_rate: i64,
+    pub upsample_initial_channel: i64,
+    /// Upsampling factor of each transposed convolution. Their product is the number of samples generated per
+    /// spectrogram frame
+    pub upsample_rates: Vec<i64>,
+    pub upsample_kernel_sizes: Vec<i64>,
+    pub resblock_kernel_sizes: Vec<i64>,
+    pub resblock_dilation_sizes: Vec<Vec<i64>>,
+    #[serde(default = "default_leaky_relu_slope")]
+    pub leaky_relu_slope: f64,
+    /// Normalize the spectrogram with the `mean` and `scale` statistics stored with the weights
+    #[serde(default = "default_normalize_before")]
+    pub normalize_before: bool,
+    /// Additional fields of the configuration file not used by the model
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+}
+
+impl Config<HifiGanConfig> for HifiGanConfig {
+    fn extra_fields(&self) -> Option<&Map<String, Value>> {
+        Some(&self.extra)
+    }
+
+    fn validate(&self) -> Result<(), RustBertError> {
+        check_positive("model_in_dim", self.model_in_dim)?;
+        check_positive("sampling_rate", self.sampling_rate)?;
+        if self.upsample_rates.len() != self.upsample_kernel_sizes.len() {
+            return Err(RustBertError::InvalidConfigurationError(format!(
+                "`upsample_rates` ({}) and `upsample_kernel_sizes` ({}) must have the same length",
+                self.upsample_rates.len(),
+                self.upsample_kernel_sizes.len()
+            )));
+        }
+        if (self.resblock_kernel_sizes.len() != self.resblock_dilation_sizes.len())
+            | self.resblock_kernel_sizes.is_empty()
+        {
+            return Err(RustBertError::InvalidConfigurationError(format!(
+                "`resblock_kernel_sizes` ({}) and `resblock_dilation_sizes` ({}) must have the same non-zero length",
+                self.resblock_kernel_sizes.len(),
+                self.resblock_dilation_sizes.len()
+            )));
+        }
+        if self.upsample_initial_channel >> self.upsample_rates.len() < 1 {
+            return Err(RustBertError::InvalidConfigurationError(format!(
+                "`upsample_initial_channel` ({}) is too small for {} upsampling layers",
+                self.upsample_initial_channel,
+                self.upsample_rates.len()
+            )));
+        }
+        Ok(())
+    }
+}
+
+impl HifiGanConfig {
+    /// Number of waveform samples generated for each spectrogram frame
+    pub fn hop_length(&self) -> i64 {
+        self.upsample_rates.iter().product()
+    }
+}
+
+fn leaky_relu(x: &Tensor, slope: f64) -> Tensor {
+    x.relu() - x.neg().relu() * slope
+}
+
+struct HifiGanResidualBlock {
+    convs1: Vec<nn::Conv1D>,
+    convs2: Vec<nn::Conv1D>,
+    leaky_relu_slope: f64,
+}
+
+impl HifiGanResidualBlock {
+    fn new<'p, P>(
+        p: P,
+        channels: i64,
+        kernel_size: i64,
+        dilations: &[i64],
+        leaky_relu_slope: f64,
+    ) -> HifiGanResidualBlock
+    where
+        P: Borrow<nn::Path<'p>>,
+    {
+        let p = p.borrow();
+        let conv = |p: nn::Path, dilation: i64| {
+            let conv_config = nn::ConvConfig {
+                padding: (kernel_size * dilation - dilation) / 2,
+                dilation,
+                ..Default::default()
+            };
+            nn::conv1d(p, channels, channels, kernel_size, conv_config)
+        };
+
+        let p_convs1 = p / "convs1";
+        let p_convs2 = p / "convs2";
+        let convs1 = dilations
+            .iter()
+            .enumerate()
+            .map(|(index, &dilation)| conv(&p_convs1 / index as i64, dilation))
+            .collect();
+        let convs2 = (0..dilations.len())
+            .map(|index| conv(&p_convs2 / index as i64, 1))
+            .collect();
+
+        HifiGanResidualBlock {
+            convs1,
+            convs2,
+            leaky_relu_slope,
+        }
+    }
+
+    fn forward(&self, hidden_states: &Tensor) -> Tensor {
+        let mut hidden_states = hidden_states.shallow_clone();
+        for (conv1, conv2) in self.convs1.iter().zip(self.convs2.iter()) {
+            let residual = hidden_states.shallow_clone();
+            hidden_states = leaky_relu(&hidden_states, self.leaky_relu_slope).apply(conv1);
+            hidden_states = leaky_relu(&hidden_states, self.leaky_relu_slope).apply(conv2);
+            hidden_states = hidden_states + residual;
+        }
+        hidden_states
+    }
+}
+
+/// # HiFi-GAN vocoder
+/// Generative adversarial network vocoder converting log-mel spectrograms into waveforms
+/// ([HiFi-GAN: Generative Adversarial Networks for Efficient and High Fidelity Speech Synthesis](https://arxiv.org/abs/2010.05646) Kong, Kim, Bae, 2020).
+/// The variable names follow the SpeechT5 HiFi-GAN checkpoints (e.g. `microsoft/speecht5_hifigan`).
+/// It is made of the following blocks:
+/// - `conv_pre`: input convolution from the mel bins to `upsample_initial_channel` channels
+/// - `upsampler`: transposed convolutions, each followed by a multi-receptive field fusion of residual blocks
+/// - `conv_post`: output convolution to a single channel
+pub struct HifiGan {
+    mean: Option<Tensor>,
+    scale: Option<Tensor>,
+    conv_pre: nn::Conv1D,
+    upsampler: Vec<nn::ConvTranspose1D>,
+    resblocks: Vec<HifiGanResidualBlock>,
+    conv_post: nn::Conv1D,
+    num_kernels: usize,
+    leaky_relu_slope: f64,
+}
+
+impl HifiGan {
+    /// Build a new `HifiGan`
+    ///
+    /// # Arguments
+    ///
+    /// * `p` - Variable store path for the root of the vocoder
+    /// * `config` - `HifiGanConfig` object defining the model architecture
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use rust_bert::hifigan::{HifiGan, HifiGanConfig};
+    /// use rust_bert::Config;
+    /// use std::path::Path;
+    /// use tch::{nn, Device};
+    ///
+    /// let config_path = Path::new("path/to/config.json");
+    /// let device = Device::Cpu;
+    /// let p = nn::VarStore::new(device);
+    /// let config = HifiGanConfig::from_file(config_path);
+    /// let vocoder = HifiGan::new(&p.root(), &config);
+    /// ```
+    pub fn new<'p, P>(p: P, config: &HifiGanConfig) -> HifiGan
+    where
+        P: Borrow<nn::Path<'p>>,
+    {
+        let p = p.borrow();
+
+        let (mean, scale) = if config.normalize_before {
+            (
+                Some(p.zeros_no_train("mean", &[config.model_in_dim])),
+                Some(p.ones_no_train("scale", &[config.model_in_dim])),
+            )
+        } else {
+            (None, None)
+        };
+        let conv_pre = nn::conv1d(
+            p / "conv_pre",
+            config.model_in_dim,
+            config.upsample_initial_channel,
+            7,
+            nn::ConvConfig {
+                padding: 3,
+                ..Default::default()
+            },
+        );
+
+        let p_upsampler = p / "upsampler";
+        let p_resblocks = p / "resblocks";
+        let mut upsampler = Vec::with_capacity(config.upsample_rates.len());
+        let mut resblocks = vec![];
+        let mut channels = config.upsample_initial_channel;
+        for (layer_index, (&rate, &kernel_size)) in config
+            .upsample_rates
+            .iter()
+            .zip(config.upsample_kernel_sizes.iter())
+            .enumerate()
+        {
+            let conv_config = nn::ConvTransposeConfig {
+                stride: rate,
+                padding: (kernel_size - rate) / 2,
+                ..Default::default()
+            };
+            upsampler.push(nn::conv_transpose1d(
+                &p_upsampler / layer_index as i64,
+                channels,
+                channels / 2,
+                kernel_size,
+                conv_config,
+            ));
+            channels /= 2;
+            for (&kernel_size, dilations) in config
+                .resblock_kernel_sizes
+                .iter()
+                .zip(config.resblock_dilation_sizes.iter())
+            {
+                resblocks.push(HifiGanResidualBlock::new(
+                    &p_resblocks / resblocks.len() as i64,
+                    channels,
+                    kernel_size,
+                    dilations,
+                    config.leaky_relu_slope,
+                ));
+            }
+        }
+        let conv_post = nn::conv1d(
+            p / "conv_post",
+            channels,
+            1,
+            7,
+            nn::ConvConfig {
+                padding: 3,
+                ..Default::default()
+            },
+        );
+
+        HifiGan {
+            mean,
+            scale,
+            conv_pre,
+            upsampler,
+            resblocks,
+            conv_post,
+            num_kernels: config.resblock_kernel_sizes.len(),
+            leaky_relu_slope: config.leaky_relu_slope,
+        }
+    }
+
+    /// Forward pass through the vocoder
+    ///
+    /// # Arguments
+    ///
+    /// * `spectrogram` - Log-mel spectrogram of shape (*batch size*, *num_frames*, *model_in_dim*)
+    ///
+    /// # Returns
+    ///
+    /// * `Tensor` of shape (*batch size*, *num_frames* x *hop_length*) containing the waveforms in [-1, 1]
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// # use rust_bert::hifigan::{HifiGan, HifiGanConfig};
+    /// # use tch::{nn, Device, Tensor, no_grad, Kind};
+    /// # use rust_bert::Config;
+    /// # use std::path::Path;
+    /// # let config_path = Path::new("path/to/config.json");
+    /// # let device = Device::Cpu;
+    /// # let vs = nn::VarStore::new(device);
+    /// # let config = HifiGanConfig::from_file(config_path);
+    /// # let vocoder = HifiGan::new(&vs.root(), &config);
+    /// let spectrogram = Tensor::randn(&[1, 100, 80], (Kind::Float, device));
+    /// let waveform = no_grad(|| vocoder.forward(&spectrogram));
+    /// ```
+    pub fn forward(&self, spectrogram: &Tensor) -> Tensor {
+        let spectrogram = match (&self.mean, &self.scale) {
+            (Some(mean), Some(scale)) => (spectrogram - mean) / scale,
+            _ => spectrogram.shallow_clone(),
+        };
+        let mut hidden_states = spectrogram.transpose(1, 2).apply(&self.conv_pre);
+        for (layer_index, upsampler) in self.upsampler.iter().enumerate() {
+            hidden_states = leaky_relu(&hidden_states, self.leaky_relu_slope).apply(upsampler);
+            let resblocks = &self.resblocks
+                [layer_index * self.num_kernels..(layer_index + 1) * self.num_kernels];
+            let mut residual_state = resblocks[0].forward(&hidden_states);
+            for resblock in &resblocks[1..] {
+                residual_state = residual_state + resblock.forward(&hidden_states);
+            }
+            hidden_states = residual_state / self.num_kernels as f64;
+        }
+        // The last activation uses the default slope of the reference implementation
+        leaky_relu(&hidden_states, 0.01)
+            .apply(&self.conv_post)
+            .tanh()
+            .squeeze1(1)
+    }
+}
@@ -0,0 +1,44 @@
+//! # HiFi-GAN (Kong et al.)
+//!
+//! Implementation of the HiFi-GAN vocoder ([HiFi-GAN: Generative Adversarial Networks for Efficient and High Fidelity Speech Synthesis](https://arxiv.org/abs/2010.05646) Kong, Kim, Bae, 2020).
+//! The vocoder converts log-mel spectrograms into waveforms and is used as the last stage of speech synthesis (see
+//! the `TextToSpeechModel` pipeline). The variable names and configuration follow the SpeechT5 HiFi-GAN checkpoints.
+//!
+//! # Model set-up and pre-trained weights loading
+//!
+//! No pretrained weights are currently provided as remote resources: the weights (e.g. `microsoft/speecht5_hifigan`)
+//! need to be converted using the `utils/convert_model.py` script.
+//!
+//! ```no_run
+//! # fn main() -> anyhow::Result<()> {
+//! #
+//! use tch::{nn, no_grad, Device, Kind, Tensor};
+//! #
+//! use rust_bert::hifigan::{HifiGan, HifiGanConfig};
+//! use rust_bert::resources::{LocalResource, Resource};
+//! use rust_bert::Config;
+//! use std::path::PathBuf;
+//!
+//! let config_resource = Resource::Local(LocalResource {
+//!     local_path: PathBuf::from("path/to/config.json"),
+//! });
+//! let weights_resource = Resource::Local(LocalResource {
+//!     local_path: PathBuf::from("path/to/model.ot"),
+//! });
+//! let config_path = config_resource.get_local_path()?;
+//! let weights_path = weights_resource.get_local_path()?;
+//! let device = Device::cuda_if_available();
+//! let mut vs = nn::VarStore::new(device);
+//! let config = HifiGanConfig::from_file(config_path);
+//! let vocoder = HifiGan::new(&vs.root(), &config);
+//! vs.load(weights_path)?;
+//!
+//! let spectrogram = Tensor::zeros(&[1, 200, config.model_in_dim], (Kind::Float, device));
+//! let waveform = no_grad(|| vocoder.forward(&spectrogram));
+//! # Ok(())
+//! # }
+//! ```
+
+mod hifigan_model;
+
+pub use hifigan_model::{HifiGan, HifiGanConfig};
@@ -46,6 +46,7 @@
 //! T5 | | | |✅ |✅|✅| |
 //! XLNet|✅|✅|✅|✅ | | |✅|
 //! Reformer|✅| |✅|✅ | | |✅|
+//! Splinter| | |✅| | | | |
 //!
 //! # Loading pre-trained models
 //!
@@ -69,15 +70,29 @@ mod common;
 pub mod distilbert;
 pub mod electra;
 pub mod gpt2;
+pub mod hifigan;
 pub mod marian;
 pub mod mobilebert;
 pub mod openai_gpt;
 pub mod pipelines;
 pub mod reformer;
 pub mod roberta;
+pub mod splinter;
 pub mod t5;
+pub mod wav2vec2;
 pub mod xlnet;
 
+pub use common::adapters;
+pub use common::classification_head;
+pub use common::crf;
 pub use common::error::RustBertError;
+pub use common::hooks;
+pub use common::lm_head;
+pub use common::prefix_tuning;
+pub use common::profiling;
 pub use common::resources;
-pub use common::{Activation, Config};
+pub use common::siamese;
+pub use common::soft_prompt;
+pub use common::task_registry;
+pub use common::testing;
+pub use common::{Activation, Config, ModelOutput};
@@ -12,7 +12,9 @@
 // limitations under the License.
 
 use crate::bart::{BartConfig, BartModel, BartModelOutput, LayerState};
-use crate::pipelines::generation_utils::{Cache, LMHeadModel, LMModelOutput};
+use crate::pipelines::generation_utils::{
+    Cache, EncoderOnlyModel, LMHeadModel, LMModelOutput, Seq2SeqGenerationModel,
+};
 use crate::RustBertError;
 use std::borrow::Borrow;
 use tch::nn::Init;
@@ -301,6 +303,7 @@ impl MarianForConditionalGeneration {
     ///   - `cache` - `(Option<Tensor>, Option<Vec<&LayerState, &LayerState>>)` of length *n_layer* containing the encoder padding mask and past keys and values for both the self attention and the encoder cross attention of each layer of the decoder.
     ///   - `all_decoder_hidden_states` - `Option<Vec<Tensor>>` of length *num_decoder_layers* with shape (*batch size*, *target_sequence_length*, *hidden_size*)
     ///   - `all_decoder_attentions` - `Option<Vec<Tensor>>` of length *num_decoder_layers* with shape (*batch size*, *target_sequence_length*, *hidden_size*)
+    ///   - `all_cross_attentions` - `Option<Vec<Tensor>>` of length *num_decoder_layers* with shape (*batch size*, *num_heads*, *target_sequence_length*, *source_sequence_length*)
     ///
     /// # Example
     ///
@@ -486,3 +489,55 @@ impl LMHeadModel for MarianForConditionalGeneration {
         })
     }
 }
+
+impl EncoderOnlyModel for MarianForConditionalGeneration {
+    fn encode(&self, input_ids: &Tensor, attention_mask: Option<&Tensor>) -> Tensor {
+        MarianForConditionalGeneration::encode(self, input_ids, attention_mask)
+    }
+}
+
+impl Seq2SeqGenerationModel for MarianForConditionalGeneration {
+    fn decode_step(
+        &self,
+        decoder_input_ids: &Tensor,
+        encoder_outputs: &Tensor,
+        attention_mask: Option<&Tensor>,
+        cache: Cache,
+    ) -> Result<LMModelOutput, RustBertError> {
+        let cache = match cache {
+            Cache::None => Cache::BARTCache(None),
+            cache => cache,
+        };
+        LMHeadModel::forward_t(
+            self,
+            &None,
+            cache,
+            &attention_mask.map(|mask| mask.shallow_clone()),
+            &None,
+            &None,
+            &None,
+            Some(encoder_outputs),
+            &Some(decoder_input_ids.shallow_clone()),
+            false,
+        )
+    }
+
+    fn reorder_cache(&self, cache: &mut Cache, beam_indices: &Tensor) {
+        match cache {
+            Cache::BARTCache(Some(old_cache)) => {
+                for (self_layer_state, encoder_layer_state) in old_cache.iter_mut() {
+                    if let Some(self_layer_state) = self_layer_state {
+                        self_layer_state.reorder_cache(beam_indices)
+                    };
+                    if let Some(encoder_layer_state) = encoder_layer_state {
+                        encoder_layer_state.reorder_cache(beam_indices)
+                    };
+                }
+            }
+            Cache::BARTCache(None) | Cache::None => {}
+            _ => {
+                panic!("Invalid cache for Marian model");
+            }
+        };
+    }
+}
@@ -11,11 +11,14 @@
 // limitations under the License.
 
 use crate::common::activations::{Activation, TensorFunction};
+use crate::common::config::{check_divisible, deserialize_id2label};
 use crate::common::dropout::Dropout;
+use crate::common::model_output::{tensor_refs, ModelOutput};
 use crate::mobilebert::embeddings::MobileBertEmbeddings;
 use crate::mobilebert::encoder::{MobileBertEncoder, MobileBertPooler};
 use crate::{Config, RustBertError};
 use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
 use std::borrow::Borrow;
 use std::collections::HashMap;
 use tch::nn::{Init, LayerNormConfig, Module};
@@ -175,11 +178,37 @@ pub struct MobileBertConfig {
     pub output_hidden_states: Option<bool>,
     pub classifier_activation: Option<bool>,
     pub is_decoder: Option<bool>,
+    #[serde(default, deserialize_with = "deserialize_id2label")]
     pub id2label: Option<HashMap<i64, String>>,
     pub label2id: Option<HashMap<String, i64>>,
+    /// Additional fields of the configuration file not used by the model
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
 }
 
-impl Config<MobileBertConfig> for MobileBertConfig {}
+impl Config<MobileBertConfig> for MobileBertConfig {
+    fn extra_fields(&self) -> Option<&Map<String, Value>> {
+        Some(&self.extra)
+    }
+
+    fn validate(&self) -> Result<(), RustBertError> {
+        if self.use_bottleneck.unwrap_or(true) {
+            check_divisible(
+                "intra_bottleneck_size",
+                self.intra_bottleneck_size.unwrap_or(128),
+                "num_attention_heads",
+                self.num_attention_heads,
+            )
+        } else {
+            check_divisible(
+                "hidden_size",
+                self.hidden_size,
+                "num_attention_heads",
+                self.num_attention_heads,
+            )
+        }
+    }
+}
 
 pub struct MobileBertPredictionHeadTransform {
     dense: nn::Linear,
@@ -1212,6 +1241,20 @@ pub struct MobileBertOutput {
     pub all_attentions: Option<Vec<Tensor>>,
 }
 
+impl ModelOutput for MobileBertOutput {
+    fn hidden_states(&self) -> Option<Vec<&Tensor>> {
+        tensor_refs(&self.all_hidden_states)
+    }
+
+    fn attentions(&self) -> Option<Vec<&Tensor>> {
+        tensor_refs(&self.all_attentions)
+    }
+
+    fn logits(&self) -> Option<&Tensor> {
+        None
+    }
+}
+
 /// Container for the MobileBert masked LM model output.
 pub struct MobileBertMaskedLMOutput {
     /// Logits for the vocabulary items at each sequence position
@@ -1222,6 +1265,20 @@ pub struct MobileBertMaskedLMOutput {
     pub all_attentions: Option<Vec<Tensor>>,
 }
 
+impl ModelOutput for MobileBertMaskedLMOutput {
+    fn hidden_states(&self) -> Option<Vec<&Tensor>> {
+        tensor_refs(&self.all_hidden_states)
+    }
+
+    fn attentions(&self) -> Option<Vec<&Tensor>> {
+        tensor_refs(&self.all_attentions)
+    }
+
+    fn logits(&self) -> Option<&Tensor> {
+        Some(&self.logits)
+    }
+}
+
 /// Container for the MobileBert sequence classification model output.
 pub struct MobileBertSequenceClassificationOutput {
     /// Logits for each input (sequence) for each target class
@@ -1232,6 +1289,20 @@ pub struct MobileBertSequenceClassificationOutput {
     pub all_attentions: Option<Vec<Tensor>>,
 }
 
+impl ModelOutput for MobileBertSequenceClassificationOutput {
+    fn hidden_states(&self) -> Option<Vec<&Tensor>> {
+        tensor_refs(&self.all_hidden_states)
+    }
+
+    fn attentions(&self) -> Option<Vec<&Tensor>> {
+        tensor_refs(&self.all_attentions)
+    }
+
+    fn logits(&self) -> Option<&Tensor> {
+        Some(&self.logits)
+    }
+}
+
 /// Container for the MobileBert token classification model output.
 pub struct MobileBertTokenClassificationOutput {
     /// Logits for each sequence item (token) for each target class
@@ -1242,6 +1313,20 @@ pub struct MobileBertTokenClassificationOutput {
     pub all_attentions: Option<Vec<Tensor>>,
 }
 
+impl ModelOutput for MobileBertTokenClassificationOutput {
+    fn hidden_states(&self) -> Option<Vec<&Tensor>> {
+        tensor_refs(&self.all_hidden_states)
+    }
+
+    fn attentions(&self) -> Option<Vec<&Tensor>> {
+        tensor_refs(&self.all_attentions)
+    }
+
+    fn logits(&self) -> Option<&Tensor> {
+        Some(&self.logits)
+    }
+}
+
 /// Container for the MobileBert question answering model output.
 pub struct MobileBertQuestionAnsweringOutput {
     /// Logits for the start position for token of each input sequence
@@ -1253,3 +1338,21 @@ pub struct MobileBertQuestionAnsweringOutput {
     /// Attention weights for all intermediate layers
     pub all_attentions: Option<Vec<Tensor>>,
 }
+
+impl ModelOutput for MobileBertQuestionAnsweringOutput {
+    fn hidden_states(&self) -> Option<Vec<&Tensor>> {
+        tensor_refs(&self.all_hidden_states)
+    }
+
+    fn attentions(&self) -> Option<Vec<&Tensor>> {
+        tensor_refs(&self.all_attentions)
+    }
+
+    fn logits(&self) -> Option<&Tensor> {
+        None
+    }
+
+    fn start_end_logits(&self) -> Option<(&Tensor, &Tensor)> {
+        Some((&self.start_logits, &self.end_logits))
+    }
+}
@@ -0,0 +1,446 @@
+// Copyright 2021 Guillaume Becquin
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//     http://www.apache.org/licenses/LICENSE-2.0
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! # Adversarial word substitution attacks
+//! Evaluates sequence classifiers against adversarial examples crafted by substituting words, following
+//! [TextFooler, Jin et al.](https://arxiv.org/abs/1907.11932):
+//! 1. The words of the input are ranked by importance: the drop in confidence of the original prediction when the
+//! word is deleted
+//! 2. The most important words are replaced in turn by their synonyms. The synonym flipping the prediction ends the
+//! attack, otherwise the synonym reducing the confidence the most is kept and the next word is attacked.
+//!
+//! The attack stops when the prediction flips or when the maximum fraction of words has been replaced. Synonyms are
+//! provided by a `SynonymProvider`: a `SynonymDictionary` (for example built from counter-fitted word embeddings as
+//! in TextFooler), or a `TextAugmenter` predicting the substitutes with a masked language model as in
+//! [BERT-Attack, Li et al.](https://arxiv.org/abs/2004.09984). Any `TextClassifier`, such as a
+//! `SequenceClassificationModel`, can be attacked.
+//!
+//! ```no_run
+//! # fn main() -> anyhow::Result<()> {
+//! use rust_bert::pipelines::attacks::{AttackConfig, WordSubstitutionAttack};
+//! use rust_bert::pipelines::augmentation::TextAugmenter;
+//! use rust_bert::pipelines::sequence_classification::SequenceClassificationModel;
+//!
+//! let classifier = SequenceClassificationModel::new(Default::default())?;
+//! let synonyms = TextAugmenter::new(Default::default())?;
+//! let attack = WordSubstitutionAttack::new(classifier, synonyms, AttackConfig::default());
+//!
+//! let report = attack.attack(&[
+//!     "The service was excellent and the food delicious.",
+//!     "I waited an hour for a cold meal.",
+//! ])?;
+//! println!("Attack success rate: {:.2}", report.success_rate());
+//! for result in report.successes() {
+//!     println!(
+//!         "{} ({}) -> {} ({})",
+//!         result.original_text,
+//!         result.original_label.text,
+//!         result.perturbed_text,
+//!         result.perturbed_label.text
+//!     );
+//! }
+//! # Ok(())
+//! # }
+//! ```
+
+use crate::common::error::RustBertError;
+use crate::pipelines::augmentation::TextAugmenter;
+use crate::pipelines::robustness::TextClassifier;
+use crate::pipelines::sequence_classification::Label;
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::path::Path;
+
+/// # Source of substitutes for the words of a text
+pub trait SynonymProvider {
+    /// Returns the substitutes of the word at `index` in `words`, the most relevant first
+    fn synonyms(&self, words: &[String], index: usize) -> Result<Vec<String>, RustBertError>;
+}
+
+impl SynonymProvider for TextAugmenter {
+    fn synonyms(&self, words: &[String], index: usize) -> Result<Vec<String>, RustBertError> {
+        self.predict_substitutes(words, index)
+    }
+}
+
+/// # Dictionary of synonyms
+/// Words are looked up ignoring their case, and the synonyms returned follow the capitalization of the word.
+#[derive(Debug, Clone, Default)]
+pub struct SynonymDictionary {
+    synonyms: HashMap<String, Vec<String>>,
+}
+
+impl SynonymDictionary {
+    /// Creates an empty dictionary
+    pub fn new() -> SynonymDictionary {
+        SynonymDictionary::default()
+    }
+
+    /// Loads a dictionary from a text file with a word followed by its synonyms on each line, separated by spaces
+    /// (for example the nearest neighbours of each word in a counter-fitted embedding space)
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - Path to the dictionary file
+    pub fn from_file<P: AsRef<Path>>(path: P) -> Result<SynonymDictionary, RustBertError> {
+        let content = fs::read_to_string(path)?;
+        let mut dictionary = SynonymDictionary::new();
+        for line in content.lines() {
+            let mut words = line.split_whitespace();
+            if let Some(word) = words.next() {
+                dictionary.insert(word, &words.collect::<Vec<&str>>());
+            }
+        }
+        Ok(dictionary)
+    }
+
+    /// Adds synonyms for a word, after the ones already registered
+    pub fn insert(&mut self, word: &str, synonyms: &[&str]) {
+        let entry = self
+            .synonyms
+            .entry(word.to_lowercase())
+            .or_insert_with(Vec::new);
+        for synonym in synonyms {
+            let synonym = synonym.to_lowercase();
+            if !entry.contains(&synonym) {
+                entry.push(synonym);
+            }
+        }
+    }
+}
+
+impl SynonymProvider for SynonymDictionary {
+    fn synonyms(&self, words: &[String], index: usize) -> Result<Vec<String>, RustBertError> {
+        let word = &words[index];
+        let capitalized = word.chars().next().map_or(false, char::is_uppercase);
+        Ok(self
+            .synonyms
+            .get(&word.to_lowercase())
+            .map(|synonyms| {
+                synonyms
+                    .iter()
+                    .map(|synonym| {
+                        if capitalized {
+                            capitalize(synonym)
+                        } else {
+                            synonym.clone()
+                        }
+                    })
+                    .collect()
+            })
+            .unwrap_or_default())
+    }
+}
+
+const STOP_WORDS: [&str; 40] = [
+    "a", "an", "the", "and", "or", "but", "of", "to", "in", "on", "at", "by", "for", "with",
+    "from", "as", "is", "are", "was", "were", "be", "been", "it", "its", "this", "that", "these",
+    "those", "i", "you", "he", "she", "we", "they", "me", "him", "her", "us", "them", "my",
+];
+
+/// # Configuration for word substitution attacks
+#[derive(Debug, Clone)]
+pub struct AttackConfig {
+    /// Maximum fraction of the words of a text that may be replaced (default: 0.3)
+    pub max_perturbation_rate: f64,
+    /// Maximum number of synonyms tried for each word (default: 20)
+    pub max_candidates: usize,
+    /// Words never replaced, compared ignoring their case (default: common English function words)
+    pub stop_words: HashSet<String>,
+}
+
+impl Default for AttackConfig {
+    fn default() -> AttackConfig {
+        AttackConfig {
+            max_perturbation_rate: 0.3,
+            max_candidates: 20,
+            stop_words: STOP_WORDS.iter().map(|word| word.to_string()).collect(),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+/// # Outcome of the attack of a text
+pub struct AttackResult {
+    /// Original input
+    pub original_text: String,
+    /// Input after the substitutions (identical to the original if no substitution was kept)
+    pub perturbed_text: String,
+    /// Prediction for the original input
+    pub original_label: Label,
+    /// Prediction for the perturbed input
+    pub perturbed_label: Label,
+    /// Number of words replaced
+    pub num_substitutions: usize,
+    /// Number of words of the input
+    pub num_words: usize,
+    /// Number of texts classified during the attack (including the original input)
+    pub num_queries: usize,
+}
+
+impl AttackResult {
+    /// Returns true if the attack flipped the prediction
+    pub fn is_success(&self) -> bool {
+        self.original_label.id != self.perturbed_label.id
+    }
+
+    /// Fraction of the words of the input replaced
+    pub fn perturbation_rate(&self) -> f64 {
+        if self.num_words == 0 {
+            0.0
+        } else {
+            self.num_substitutions as f64 / self.num_words as f64
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+/// # Results of an attack against a classifier
+pub struct AttackReport {
+    /// Outcome of the attack of each input, in the order of the inputs
+    pub results: Vec<AttackResult>,
+}
+
+impl AttackReport {
+    /// Returns the successful attacks, with their adversarial examples
+    pub fn successes(&self) -> Vec<&AttackResult> {
+        self.results
+            .iter()
+            .filter(|result| result.is_success())
+            .collect()
+    }
+
+    /// Fraction of the inputs whose prediction was flipped (0 if there are no results)
+    pub fn success_rate(&self) -> f64 {
+        mean(
+            self.results
+                .iter()
+                .map(|result| result.is_success() as usize as f64),
+        )
+    }
+
+    /// Average fraction of the words replaced in the successful attacks (0 if there are none)
+    pub fn mean_perturbation_rate(&self) -> f64 {
+        mean(
+            self.successes()
+                .into_iter()
+                .map(AttackResult::perturbation_rate),
+        )
+    }
+
+    /// Average number of classifier queries per input (0 if there are no results)
+    pub fn mean_queries(&self) -> f64 {
+        mean(self.results.iter().map(|result| result.num_queries as f64))
+    }
+}
+
+fn mean(values: impl Iterator<Item = f64>) -> f64 {
+    let (sum, count) = values.fold((0.0, 0usize), |(sum, count), value| {
+        (sum + value, count + 1)
+    });
+    if count == 0 {
+        0.0
+    } else {
+        sum / count as f64
+    }
+}
+
+/// # Word importance ranking and synonym substitution attack (TextFooler)
+pub struct WordSubstitutionAttack<C: TextClassifier, S: SynonymProvider> {
+    classifier: C,
+    synonym_provider: S,
+    config: AttackConfig,
+}
+
+impl<C: TextClassifier, S: SynonymProvider> WordSubstitutionAttack<C, S> {
+    /// Creates a new attack
+    ///
+    /// # Arguments
+    ///
+    /// * `classifier` - `TextClassifier` to attack
+    /// * `synonym_provider` - `SynonymProvider` proposing the substitutes of the words
+    /// * `config` - `AttackConfig` limiting the substitutions
+    pub fn new(classifier: C, synonym_provider: S, config: AttackConfig) -> Self {
+        WordSubstitutionAttack {
+            classifier,
+            synonym_provider,
+            config,
+        }
+    }
+
+    /// Attacks each text independently
+    ///
+    /// # Arguments
+    ///
+    /// * `texts` - `&[&str]` Array of inputs to attack
+    ///
+    /// # Returns
+    ///
+    /// * `AttackReport` with the outcome of the attack of each input
+    pub fn attack(&self, texts: &[&str]) -> Result<AttackReport, RustBertError> {
+        let original_labels = self.classifier.classify(texts)?;
+        let mut results = Vec::with_capacity(texts.len());
+        for (text, original_label) in texts.iter().zip(original_labels) {
+            results.push(self.attack_text(text, original_label)?);
+        }
+        Ok(AttackReport { results })
+    }
+
+    fn attack_text(
+        &self,
+        text: &str,
+        original_label: Label,
+    ) -> Result<AttackResult, RustBertError> {
+        let mut words = text
+            .split_whitespace()
+            .map(String::from)
+            .collect::<Vec<String>>();
+        let mut result = AttackResult {
+            original_text: text.to_string(),
+            perturbed_text: text.to_string(),
+            original_label: original_label.clone(),
+            perturbed_label: original_label.clone(),
+            num_substitutions: 0,
+            num_words: words.len(),
+            num_queries: 1,
+        };
+        let max_substitutions =
+            (self.config.max_perturbation_rate * words.len() as f64).ceil() as usize;
+
+        // Ranks the words by the drop of confidence in the original label when they are deleted
+        let attackable_words = (0..words.len())
+            .filter(|index| {
+                let (_, core, _) = split_affixes(&words[*index]);
+                !core.is_empty() && !self.config.stop_words.contains(&core.to_lowercase())
+            })
+            .collect::<Vec<usize>>();
+        let deletions = attackable_words
+            .iter()
+            .map(|index| {
+                let mut remaining = words.clone();
+                remaining.remove(*index);
+                remaining.join(" ")
+            })
+            .collect::<Vec<String>>();
+        let deletion_labels = self.classify(&deletions, &mut result)?;
+        let mut ranked_words = attackable_words
+            .into_iter()
+            .zip(
+                deletion_labels
+                    .iter()
+                    .map(|label| confidence_drop(&original_label, label)),
+            )
+            .collect::<Vec<(usize, f64)>>();
+        ranked_words.sort_by(|(_, left), (_, right)| right.partial_cmp(left).unwrap());
+
+        let mut current_confidence = original_label.score;
+        for (index, _) in ranked_words {
+            if result.num_substitutions >= max_substitutions {
+                break;
+            }
+            let (prefix, core, suffix) = split_affixes(&words[index]);
+            let (prefix, core, suffix) = (prefix.to_string(), core.to_string(), suffix.to_string());
+            let mut context = words.clone();
+            context[index] = core.clone();
+            let candidates = self
+                .synonym_provider
+                .synonyms(&context, index)?
+                .into_iter()
+                .filter(|synonym| !synonym.is_empty() && !synonym.eq_ignore_ascii_case(&core))
+                .take(self.config.max_candidates)
+                .map(|synonym| format!("{}{}{}", prefix, synonym, suffix))
+                .collect::<Vec<String>>();
+            if candidates.is_empty() {
+                continue;
+            }
+            let candidate_texts = candidates
+                .iter()
+                .map(|candidate| {
+                    let mut perturbed = words.clone();
+                    perturbed[index] = candidate.clone();
+                    perturbed.join(" ")
+                })
+                .collect::<Vec<String>>();
+            let candidate_labels = self.classify(&candidate_texts, &mut result)?;
+
+            // The first synonym flipping the prediction is kept, otherwise the one reducing the confidence the most
+            let mut best: Option<(usize, f64)> = None;
+            for (candidate_index, label) in candidate_labels.iter().enumerate() {
+                let confidence = if label.id == original_label.id {
+                    label.score
+                } else {
+                    best = Some((candidate_index, f64::NEG_INFINITY));
+                    break;
+                };
+                if confidence
+                    < best.map_or(current_confidence, |(_, best_confidence)| best_confidence)
+                {
+                    best = Some((candidate_index, confidence));
+                }
+            }
+            if let Some((candidate_index, confidence)) = best {
+                words[index] = candidates[candidate_index].clone();
+                result.num_substitutions += 1;
+                result.perturbed_label = candidate_labels[candidate_index].clone();
+                result.perturbed_text = candidate_texts[candidate_index].clone();
+                current_confidence = confidence;
+                if result.is_success() {
+                    break;
+                }
+            }
+        }
+        Ok(result)
+    }
+
+    fn classify(
+        &self,
+        texts: &[String],
+        result: &mut AttackResult,
+    ) -> Result<Vec<Label>, RustBertError> {
+        if texts.is_empty() {
+            return Ok(vec![]);
+        }
+        result.num_queries += texts.len();
+        self.classifier
+            .classify(&texts.iter().map(String::as_str).collect::<Vec<&str>>())
+    }
+}
+
+/// Drop of the confidence in the original label. The classifier only returns the confidence of its prediction: if
+/// the prediction changed, the confidence in the original label is bounded by the complement of the new one.
+fn confidence_drop(original_label: &Label, label: &Label) -> f64 {
+    if label.id == original_label.id {
+        original_label.score - label.score
+    } else {
+        original_label.score - (1.0 - label.score)
+    }
+}
+
+/// Splits a word into its leading punctuation, alphanumeric core and trailing punctuation
+fn split_affixes(word: &str) -> (&str, &str, &str) {
+    let start = word
+        .find(|c: char| c.is_alphanumeric())
+        .unwrap_or(word.len());
+    let end = word
+        .rfind(|c: char| c.is_alphanumeric())
+        .map_or(start, |position| {
+            position + word[position..].chars().next().unwrap().len_utf8()
+        });
+    (&word[..start], &word[start..end], &word[end..])
+}
+
+fn capitalize(word: &str) -> String {
+    let mut chars = word.chars();
+    match chars.next() {
+        Some(first) => first.to_uppercase().chain(chars).collect(),
+        None => String::new(),
+    }
+}
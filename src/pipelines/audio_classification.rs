@@ -0,0 +1,317 @@
+// Copyright 2021 Guillaume Becquin
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//     http://www.apache.org/licenses/LICENSE-2.0
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! # Audio classification pipeline
+//! Classifies audio clips (e.g. keyword spotting, spoken language identification or audio event detection) using a
+//! Wav2Vec2 model with a sequence classification head. The labels are read from the `id2label` mapping of the model
+//! configuration. The input is raw PCM audio: mono samples at 16kHz, as `f32` values in [-1, 1]. Clips with a
+//! different sampling rate or multiple channels should be resampled and down-mixed beforehand.
+//!
+//! No default model is provided: a fine-tuned checkpoint (for example `superb/wav2vec2-base-superb-ks` for keyword
+//! spotting) needs to be converted using `utils/convert_model.py`. Audio Spectrogram Transformer models are not
+//! supported.
+//!
+//! ```no_run
+//! # fn main() -> anyhow::Result<()> {
+//! use rust_bert::pipelines::audio_classification::{
+//!     AudioClassificationConfig, AudioClassificationModel,
+//! };
+//! use rust_bert::resources::{LocalResource, Resource};
+//! use std::path::PathBuf;
+//!
+//! let config = AudioClassificationConfig::new(
+//!     Resource::Local(LocalResource {
+//!         local_path: PathBuf::from("path/to/model.ot"),
+//!     }),
+//!     Resource::Local(LocalResource {
+//!         local_path: PathBuf::from("path/to/config.json"),
+//!     }),
+//! );
+//! let audio_classification_model = AudioClassificationModel::new(config)?;
+//!
+//! let clip: Vec<f32> = vec![0.0; 16000];
+//! let output = audio_classification_model.predict(&[clip.as_slice()], 3)?;
+//! # Ok(())
+//! # }
+//! ```
+
+use crate::common::error::RustBertError;
+use crate::common::resources::Resource;
+use crate::common::weights::reload_var_store;
+use crate::pipelines::sequence_classification::Label;
+use crate::wav2vec2::{FeatureExtractNorm, Wav2Vec2Config, Wav2Vec2ForSequenceClassification};
+use crate::Config;
+use std::collections::HashMap;
+use tch::kind::Kind::{Float, Int64};
+use tch::nn::VarStore;
+use tch::{no_grad, Device, Tensor};
+
+/// # Configuration for AudioClassificationModel
+/// Contains information regarding the model to load and device to place the model on.
+pub struct AudioClassificationConfig {
+    /// Model weights resource
+    pub model_resource: Resource,
+    /// Config resource
+    pub config_resource: Resource,
+    /// Normalize each clip to zero mean and unit variance before the forward pass (default: true, required by
+    /// most Wav2Vec2 checkpoints)
+    pub do_normalize: bool,
+    /// Device to place the model on (default: CUDA/GPU when available)
+    pub device: Device,
+}
+
+impl AudioClassificationConfig {
+    /// Instantiate a new audio classification configuration from the model weights and configuration.
+    ///
+    /// # Arguments
+    ///
+    /// * `model_resource` - The `Resource` pointing to the model weights to load (e.g.  model.ot)
+    /// * `config_resource` - The `Resource' pointing to the model configuration to load (e.g. config.json)
+    pub fn new(model_resource: Resource, config_resource: Resource) -> AudioClassificationConfig {
+        AudioClassificationConfig {
+            model_resource,
+            config_resource,
+            do_normalize: true,
+            device: Device::cuda_if_available(),
+        }
+    }
+}
+
+/// # AudioClassificationModel for classification of raw audio clips
+pub struct AudioClassificationModel {
+    audio_classifier: Wav2Vec2ForSequenceClassification,
+    model_config: Wav2Vec2Config,
+    label_mapping: HashMap<i64, String>,
+    do_normalize: bool,
+    var_store: VarStore,
+}
+
+impl AudioClassificationModel {
+    /// Build a new `AudioClassificationModel`
+    ///
+    /// # Arguments
+    ///
+    /// * `config` - `AudioClassificationConfig` object containing the resource references (model, configuration) and device placement (CPU/GPU)
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// # fn main() -> anyhow::Result<()> {
+    /// use rust_bert::pipelines::audio_classification::{
+    ///     AudioClassificationConfig, AudioClassificationModel,
+    /// };
+    /// use rust_bert::resources::{LocalResource, Resource};
+    /// use std::path::PathBuf;
+    ///
+    /// let config = AudioClassificationConfig::new(
+    ///     Resource::Local(LocalResource {
+    ///         local_path: PathBuf::from("path/to/model.ot"),
+    ///     }),
+    ///     Resource::Local(LocalResource {
+    ///         local_path: PathBuf::from("path/to/config.json"),
+    ///     }),
+    /// );
+    /// let audio_classification_model = AudioClassificationModel::new(config)?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn new(
+        config: AudioClassificationConfig,
+    ) -> Result<AudioClassificationModel, RustBertError> {
+        let config_path = config.config_resource.get_local_path()?;
+        let weights_path = config.model_resource.get_local_path()?;
+
+        let model_config = Wav2Vec2Config::try_from_file(config_path)?;
+        let label_mapping = model_config.id2label.clone().ok_or_else(|| {
+            RustBertError::InvalidConfigurationError(
+                "`id2label` must be provided in the configuration for audio classification"
+                    .to_string(),
+            )
+        })?;
+        let mut var_store = VarStore::new(config.device);
+        let audio_classifier =
+            Wav2Vec2ForSequenceClassification::new(&var_store.root(), &model_config);
+        var_store.load(weights_path)?;
+        Ok(AudioClassificationModel {
+            audio_classifier,
+            model_config,
+            label_mapping,
+            do_normalize: config.do_normalize,
+            var_store,
+        })
+    }
+
+    fn prepare_for_model(&self, input: &[&[f32]]) -> Result<(Tensor, Tensor), RustBertError> {
+        let min_length = self
+            .model_config
+            .conv_kernel
+            .iter()
+            .zip(self.model_config.conv_stride.iter())
+            .rev()
+            .fold(1, |length, (kernel, stride)| (length - 1) * stride + kernel);
+        if let Some(clip) = input.iter().find(|clip| (clip.len() as i64) < min_length) {
+            return Err(RustBertError::ValueError(format!(
+                "Audio clips must contain at least {} samples, got {}",
+                min_length,
+                clip.len()
+            )));
+        }
+        let max_len = input.iter().map(|clip| clip.len()).max().unwrap();
+
+        let input_values = input
+            .iter()
+            .map(|clip| {
+                let mut values = if self.do_normalize {
+                    let num_samples = clip.len() as f64;
+                    let mean = clip.iter().map(|&v| v as f64).sum::<f64>() / num_samples;
+                    let variance =
+                        clip.iter().map(|&v| (v as f64 - mean).powi(2)).sum::<f64>() / num_samples;
+                    let std = (variance + 1e-7).sqrt();
+                    clip.iter()
+                        .map(|&v| ((v as f64 - mean) / std) as f32)
+                        .collect::<Vec<f32>>()
+                } else {
+                    clip.to_vec()
+                };
+                values.extend(vec![0f32; max_len - values.len()]);
+                Tensor::of_slice(&values)
+            })
+            .collect::<Vec<Tensor>>();
+        let input_lengths = input
+            .iter()
+            .map(|clip| clip.len() as i64)
+            .collect::<Vec<i64>>();
+
+        let device = self.var_store.device();
+        let input_values = Tensor::stack(&input_values, 0).to(device);
+        let frame_lengths = self
+            .model_config
+            .feature_lengths(&Tensor::of_slice(&input_lengths))
+            .to(device);
+        let num_frames = self
+            .model_config
+            .feature_lengths(&Tensor::of_slice(&[max_len as i64]))
+            .int64_value(&[0]);
+        let frame_mask = Tensor::arange(num_frames, (Int64, device))
+            .unsqueeze(0)
+            .lt1(&frame_lengths.unsqueeze(1))
+            .to_kind(Int64);
+        Ok((input_values, frame_mask))
+    }
+
+    /// Classify audio clips
+    ///
+    /// # Arguments
+    ///
+    /// * `input` - `&[&[f32]]` Array of mono 16kHz PCM clips to classify
+    /// * `top_k` - `usize` number of labels to return for each clip, by decreasing score. All labels are returned if `top_k` is larger than the number of labels
+    ///
+    /// # Returns
+    ///
+    /// * `Vec<Vec<Label>>` containing the `top_k` labels and their probability for each clip. The `sentence` field
+    /// of the labels contains the index of the clip in the input.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// # fn main() -> anyhow::Result<()> {
+    /// # use rust_bert::pipelines::audio_classification::{AudioClassificationConfig, AudioClassificationModel};
+    /// # use rust_bert::resources::{LocalResource, Resource};
+    /// # use std::path::PathBuf;
+    /// # let config = AudioClassificationConfig::new(
+    /// #     Resource::Local(LocalResource { local_path: PathBuf::from("path/to/model.ot") }),
+    /// #     Resource::Local(LocalResource { local_path: PathBuf::from("path/to/config.json") }),
+    /// # );
+    /// let audio_classification_model = AudioClassificationModel::new(config)?;
+    /// let short_clip: Vec<f32> = vec![0.0; 8000];
+    /// let long_clip: Vec<f32> = vec![0.0; 32000];
+    /// let output =
+    ///     audio_classification_model.predict(&[short_clip.as_slice(), long_clip.as_slice()], 1)?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn predict(
+        &self,
+        input: &[&[f32]],
+        top_k: usize,
+    ) -> Result<Vec<Vec<Label>>, RustBertError> {
+        if input.is_empty() {
+            return Ok(vec![]);
+        }
+        let (input_values, frame_mask) = self.prepare_for_model(input)?;
+        // Checkpoints with group normalization in the feature encoder are trained without attention mask, the
+        // padding values are zeros after normalization
+        let frame_mask = match self.model_config.feat_extract_norm {
+            FeatureExtractNorm::layer => Some(frame_mask),
+            FeatureExtractNorm::group => None,
+        };
+        let output = no_grad(|| {
+            self.audio_classifier
+                .forward_t(&input_values, frame_mask.as_ref(), false)
+                .logits
+                .softmax(-1, Float)
+                .to(Device::Cpu)
+        });
+
+        let top_k = (top_k as i64).min(output.size()[1]);
+        let (scores, indices) = output.topk(top_k, -1, true, true);
+        Ok((0..input.len())
+            .map(|clip_index| {
+                let clip_scores = Vec::<f64>::from(scores.get(clip_index as i64));
+                let clip_indices = Vec::<i64>::from(indices.get(clip_index as i64));
+                clip_scores
+                    .into_iter()
+                    .zip(clip_indices.into_iter())
+                    .map(|(score, id)| Label {
+                        text: self
+                            .label_mapping
+                            .get(&id)
+                            .cloned()
+                            .unwrap_or_else(|| id.to_string()),
+                        score,
+                        id,
+                        sentence: clip_index,
+                    })
+                    .collect()
+            })
+            .collect())
+    }
+
+    /// Reloads the weights of the model from the resource provided, keeping the configuration unchanged.
+    ///
+    /// # Arguments
+    ///
+    /// * `weights_resource` - `Resource` pointing to the new model weights (e.g. model.ot)
+    pub fn reload_weights(&mut self, weights_resource: &Resource) -> Result<(), RustBertError> {
+        reload_var_store(&mut self.var_store, weights_resource)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::resources::LocalResource;
+    use std::path::PathBuf;
+
+    #[test]
+    #[ignore] // no need to run, compilation is enough to verify it is Send
+    fn test() {
+        let config = AudioClassificationConfig::new(
+            Resource::Local(LocalResource {
+                local_path: PathBuf::from("path/to/model.ot"),
+            }),
+            Resource::Local(LocalResource {
+                local_path: PathBuf::from("path/to/config.json"),
+            }),
+        );
+        let _: Box<dyn Send> = Box::new(AudioClassificationModel::new(config));
+    }
+}
@@ -0,0 +1,751 @@
+// Copyright 2021 Guillaume Becquin
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//     http://www.apache.org/licenses/LICENSE-2.0
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! # Text augmentation
+//! Generates variants of training texts for low-resource classification, combining the operations of
+//! [EDA, Wei and Zou (2019)](https://arxiv.org/abs/1901.11196) with the contextual augmentation of
+//! [Kobayashi (2018)](https://arxiv.org/abs/1805.06201):
+//! - substitution: words are masked and replaced by a word predicted by a masked language model, sampled among its
+//! top predictions so that the substitutes fit the context
+//! - insertion: a mask is inserted after random words and replaced by a predicted word
+//! - random swap: two random words exchange their positions
+//! - random deletion: each word is removed with a given probability
+//!
+//! The operations are drawn from a generator seeded by the configuration, so that the augmentations of a corpus are
+//! reproducible. The words are separated by whitespace, and only the words made of letters and digits are substituted.
+//!
+//! Supported architectures: BERT, DistilBERT, RoBERTa and XLM-RoBERTa.
+//!
+//! ```no_run
+//! # fn main() -> anyhow::Result<()> {
+//! use rust_bert::pipelines::augmentation::{AugmentationConfig, TextAugmenter};
+//!
+//! let augmenter = TextAugmenter::new(AugmentationConfig {
+//!     substitution_rate: 0.2,
+//!     swap_rate: 0.1,
+//!     num_augmentations: 4,
+//!     seed: 7,
+//!     ..Default::default()
+//! })?;
+//! let augmentations = augmenter.augment(&["The delivery was late and the package was damaged."])?;
+//! for text in &augmentations[0] {
+//!     println!("{}", text);
+//! }
+//! # Ok(())
+//! # }
+//! ```
+
+use crate::bert::{BertConfigResources, BertForMaskedLM, BertModelResources, BertVocabResources};
+use crate::common::error::RustBertError;
+use crate::common::resources::{RemoteResource, Resource};
+use crate::distilbert::DistilBertModelMaskedLM;
+use crate::pipelines::common::{ConfigOption, ModelType, TensorBatch, TokenizerOption};
+use crate::roberta::RobertaForMaskedLM;
+use rust_tokenizers::{Mask, TokenIdsWithOffsets};
+use std::borrow::Borrow;
+use std::collections::HashMap;
+use tch::nn::VarStore;
+use tch::{nn, no_grad, Device, Kind, Tensor};
+
+/// # Configuration for TextAugmenter
+/// Contains information regarding the masked language model to load, the rates of the augmentation operations and
+/// device to place the model on.
+pub struct AugmentationConfig {
+    /// Model type
+    pub model_type: ModelType,
+    /// Model weights resource (default: pretrained BERT model)
+    pub model_resource: Resource,
+    /// Config resource (default: pretrained BERT model)
+    pub config_resource: Resource,
+    /// Vocab resource (default: pretrained BERT model)
+    pub vocab_resource: Resource,
+    /// Merges resource (default: None)
+    pub merges_resource: Option<Resource>,
+    /// Automatically lower case all input upon tokenization (assumes a lower-cased model)
+    pub lower_case: bool,
+    /// Flag indicating if the tokenizer should strip accents (normalization). Only used for BERT models
+    pub strip_accents: Option<bool>,
+    /// Flag indicating if the tokenizer should add a white space before each tokenized input (needed for some Roberta models)
+    pub add_prefix_space: Option<bool>,
+    /// Device to place the model on (default: CUDA/GPU when available)
+    pub device: Device,
+    /// Fraction of the words replaced by a word predicted by the masked language model (default: 0.1)
+    pub substitution_rate: f64,
+    /// Fraction of the words followed by an inserted word predicted by the masked language model (default: 0.0)
+    pub insertion_rate: f64,
+    /// Fraction of the words swapped with another word of the text (default: 0.0)
+    pub swap_rate: f64,
+    /// Probability of removing each word (default: 0.0)
+    pub deletion_rate: f64,
+    /// Number of predictions of the masked language model the substitutes are sampled from (default: 10)
+    pub top_k: i64,
+    /// Number of augmented texts generated for each input (default: 1)
+    pub num_augmentations: usize,
+    /// Seed of the random operations (default: 42)
+    pub seed: u64,
+}
+
+impl AugmentationConfig {
+    /// Instantiate a new augmentation configuration of the supplied type, substituting 10% of the words.
+    ///
+    /// # Arguments
+    ///
+    /// * `model_type` - `ModelType` indicating the model type to load (must match with the actual data to be loaded!)
+    /// * model - The `Resource` pointing to the masked language model to load (e.g.  model.ot)
+    /// * config - The `Resource' pointing to the model configuration to load (e.g. config.json)
+    /// * vocab - The `Resource' pointing to the tokenizer's vocabulary to load (e.g.  vocab.txt/vocab.json)
+    /// * merges - An optional `Resource` (`Option<Resource>`) pointing to the tokenizer's merge file to load (e.g.  merges.txt), needed only for Roberta.
+    /// * lower_case - A `bool' indicating whether the tokenizer should lower case all input (in case of a lower-cased model)
+    pub fn new(
+        model_type: ModelType,
+        model_resource: Resource,
+        config_resource: Resource,
+        vocab_resource: Resource,
+        merges_resource: Option<Resource>,
+        lower_case: bool,
+        strip_accents: impl Into<Option<bool>>,
+        add_prefix_space: impl Into<Option<bool>>,
+    ) -> AugmentationConfig {
+        AugmentationConfig {
+            model_type,
+            model_resource,
+            config_resource,
+            vocab_resource,
+            merges_resource,
+            lower_case,
+            strip_accents: strip_accents.into(),
+            add_prefix_space: add_prefix_space.into(),
+            device: Device::cuda_if_available(),
+            substitution_rate: 0.1,
+            insertion_rate: 0.0,
+            swap_rate: 0.0,
+            deletion_rate: 0.0,
+            top_k: 10,
+            num_augmentations: 1,
+            seed: 42,
+        }
+    }
+}
+
+impl Default for AugmentationConfig {
+    /// Provides a default BERT base (uncased) masked language model substituting 10% of the words
+    fn default() -> AugmentationConfig {
+        AugmentationConfig::new(
+            ModelType::Bert,
+            Resource::Remote(RemoteResource::from_pretrained(BertModelResources::BERT)),
+            Resource::Remote(RemoteResource::from_pretrained(BertConfigResources::BERT)),
+            Resource::Remote(RemoteResource::from_pretrained(BertVocabResources::BERT)),
+            None,
+            true,
+            None,
+            None,
+        )
+    }
+}
+
+/// # Abstraction that holds one particular masked language model, for any of the supported models
+pub enum MaskedLanguageOption {
+    /// Bert for masked language modeling
+    Bert(BertForMaskedLM),
+    /// DistilBert for masked language modeling
+    DistilBert(DistilBertModelMaskedLM),
+    /// Roberta for masked language modeling
+    Roberta(RobertaForMaskedLM),
+    /// XLM Roberta for masked language modeling
+    XLMRoberta(RobertaForMaskedLM),
+}
+
+impl MaskedLanguageOption {
+    /// Instantiate a new masked language model of the supplied type
+    ///
+    /// # Arguments
+    ///
+    /// * `model_type` - `ModelType` indicating the model type to load (must match with the actual data to be loaded)
+    /// * `p` - `tch::nn::Path` path to the model file to load (e.g. model.ot)
+    /// * `config` - A configuration (the model type of the configuration must be compatible with the value for
+    /// `model_type`)
+    pub fn new<'p, P>(
+        model_type: ModelType,
+        p: P,
+        config: &ConfigOption,
+    ) -> Result<Self, RustBertError>
+    where
+        P: Borrow<nn::Path<'p>>,
+    {
+        let p = p.borrow();
+        match (model_type, config) {
+            (ModelType::Bert, ConfigOption::Bert(config)) => {
+                Ok(MaskedLanguageOption::Bert(BertForMaskedLM::new(p, config)))
+            }
+            (ModelType::DistilBert, ConfigOption::DistilBert(config)) => Ok(
+                MaskedLanguageOption::DistilBert(DistilBertModelMaskedLM::new(p, config)),
+            ),
+            (ModelType::Roberta, ConfigOption::Bert(config)) => Ok(
+                MaskedLanguageOption::Roberta(RobertaForMaskedLM::new(p, config)),
+            ),
+            (ModelType::XLMRoberta, ConfigOption::Bert(config)) => Ok(
+                MaskedLanguageOption::XLMRoberta(RobertaForMaskedLM::new(p, config)),
+            ),
+            _ => Err(RustBertError::InvalidConfigurationError(format!(
+                "Masked language modeling is not implemented for {:?}, or the configuration provided does not match this model type",
+                model_type
+            ))),
+        }
+    }
+
+    /// Returns the `ModelType` for this MaskedLanguageOption
+    pub fn model_type(&self) -> ModelType {
+        match *self {
+            Self::Bert(_) => ModelType::Bert,
+            Self::DistilBert(_) => ModelType::DistilBert,
+            Self::Roberta(_) => ModelType::Roberta,
+            Self::XLMRoberta(_) => ModelType::XLMRoberta,
+        }
+    }
+
+    /// Forward pass through the model, returning the prediction scores of shape (*batch size*, *sequence_length*,
+    /// *vocab_size*)
+    pub fn forward_t(
+        &self,
+        input_ids: &Tensor,
+        mask: &Tensor,
+        train: bool,
+    ) -> Result<Tensor, RustBertError> {
+        Ok(match self {
+            Self::Bert(ref model) => {
+                model
+                    .forward_t(
+                        Some(input_ids.copy()),
+                        Some(mask.copy()),
+                        None,
+                        None,
+                        None,
+                        &None,
+                        &None,
+                        train,
+                    )
+                    .prediction_scores
+            }
+            Self::DistilBert(ref model) => {
+                model
+                    .forward_t(Some(input_ids.copy()), Some(mask.copy()), None, train)?
+                    .prediction_scores
+            }
+            Self::Roberta(ref model) | Self::XLMRoberta(ref model) => {
+                model
+                    .forward_t(
+                        Some(input_ids.copy()),
+                        Some(mask.copy()),
+                        None,
+                        None,
+                        None,
+                        &None,
+                        &None,
+                        train,
+                    )
+                    .prediction_scores
+            }
+        })
+    }
+}
+
+/// # Seedable generator of the augmentation operations
+/// Xorshift generator, independent from the `tch` random state.
+#[derive(Debug, Clone)]
+pub struct AugmentationRng {
+    state: u64,
+}
+
+impl AugmentationRng {
+    /// Creates a new generator from a seed
+    pub fn new(seed: u64) -> AugmentationRng {
+        AugmentationRng {
+            state: (seed ^ 0x9E37_79B9_7F4A_7C15) | 1,
+        }
+    }
+
+    /// Returns a uniform sample in [0, 1)
+    pub fn next_uniform(&mut self) -> f64 {
+        self.state ^= self.state >> 12;
+        self.state ^= self.state << 25;
+        self.state ^= self.state >> 27;
+        let value = self.state.wrapping_mul(0x2545_F491_4F6C_DD1D);
+        (value >> 11) as f64 / (1u64 << 53) as f64
+    }
+
+    /// Returns a uniform index in [0, upper_bound)
+    pub fn next_index(&mut self, upper_bound: usize) -> usize {
+        ((self.next_uniform() * upper_bound as f64) as usize).min(upper_bound.saturating_sub(1))
+    }
+
+    /// Returns `count` distinct indices of [0, upper_bound), in random order
+    pub fn sample_indices(&mut self, upper_bound: usize, count: usize) -> Vec<usize> {
+        let mut indices = (0..upper_bound).collect::<Vec<usize>>();
+        let count = count.min(upper_bound);
+        for position in 0..count {
+            let swapped = position + self.next_index(upper_bound - position);
+            indices.swap(position, swapped);
+        }
+        indices.truncate(count);
+        indices
+    }
+}
+
+/// Number of words affected by an operation: the rate applied to the number of words, at least one if the rate is
+/// positive
+fn operation_count(rate: f64, num_words: usize) -> usize {
+    if (rate <= 0.0) | (num_words == 0) {
+        0
+    } else {
+        ((rate * num_words as f64).round() as usize).max(1)
+    }
+}
+
+/// Swaps random pairs of words, `num_swaps` times
+pub fn random_swap(words: &mut [String], num_swaps: usize, rng: &mut AugmentationRng) {
+    if words.len() < 2 {
+        return;
+    }
+    for _ in 0..num_swaps {
+        let first = rng.next_index(words.len());
+        let second = (first + 1 + rng.next_index(words.len() - 1)) % words.len();
+        words.swap(first, second);
+    }
+}
+
+/// Removes each word with the given probability, keeping a random word if all of them would be removed
+pub fn random_deletion(
+    words: Vec<String>,
+    probability: f64,
+    rng: &mut AugmentationRng,
+) -> Vec<String> {
+    if (probability <= 0.0) | (words.len() < 2) {
+        return words;
+    }
+    let kept_word = rng.next_index(words.len());
+    let kept = words
+        .iter()
+        .map(|_| rng.next_uniform() >= probability)
+        .collect::<Vec<bool>>();
+    if kept.iter().any(|kept| *kept) {
+        words
+            .into_iter()
+            .zip(kept)
+            .filter(|(_, kept)| *kept)
+            .map(|(word, _)| word)
+            .collect()
+    } else {
+        vec![words[kept_word].clone()]
+    }
+}
+
+/// Position in the text where a masked word is predicted
+enum MaskedPosition {
+    Substitution(usize),
+    Insertion(usize),
+}
+
+/// # TextAugmenter generating variants of texts with a masked language model and random operations
+pub struct TextAugmenter {
+    tokenizer: TokenizerOption,
+    model: MaskedLanguageOption,
+    var_store: VarStore,
+    mask_token_id: i64,
+    candidate_words: HashMap<i64, String>,
+    candidate_mask: Tensor,
+    substitution_rate: f64,
+    insertion_rate: f64,
+    swap_rate: f64,
+    deletion_rate: f64,
+    top_k: i64,
+    num_augmentations: usize,
+    seed: u64,
+}
+
+impl TextAugmenter {
+    /// Build a new `TextAugmenter`
+    ///
+    /// # Arguments
+    ///
+    /// * `config` - `AugmentationConfig` object containing the resource references (model, vocabulary, configuration), operation rates and device placement (CPU/GPU)
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// # fn main() -> anyhow::Result<()> {
+    /// use rust_bert::pipelines::augmentation::TextAugmenter;
+    ///
+    /// let augmenter = TextAugmenter::new(Default::default())?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn new(config: AugmentationConfig) -> Result<TextAugmenter, RustBertError> {
+        for (name, rate) in &[
+            ("substitution_rate", config.substitution_rate),
+            ("insertion_rate", config.insertion_rate),
+            ("swap_rate", config.swap_rate),
+            ("deletion_rate", config.deletion_rate),
+        ] {
+            if !(0.0..=1.0).contains(rate) {
+                return Err(RustBertError::InvalidConfigurationError(format!(
+                    "{} must be between 0 and 1, got {}",
+                    name, rate
+                )));
+            }
+        }
+        if config.top_k < 1 {
+            return Err(RustBertError::InvalidConfigurationError(
+                "top_k must be at least 1".to_string(),
+            ));
+        }
+
+        let config_path = config.config_resource.get_local_path()?;
+        let vocab_path = config.vocab_resource.get_local_path()?;
+        let weights_path = config.model_resource.get_local_path()?;
+        let merges_path = if let Some(merges_resource) = &config.merges_resource {
+            Some(merges_resource.get_local_path()?)
+        } else {
+            None
+        };
+
+        let tokenizer = TokenizerOption::from_file(
+            config.model_type,
+            vocab_path.to_str().unwrap(),
+            merges_path.as_deref().map(|path| path.to_str().unwrap()),
+            config.lower_case,
+            config.strip_accents,
+            config.add_prefix_space,
+        )?;
+        let mut var_store = VarStore::new(config.device);
+        let model_config = ConfigOption::try_from_file(config.model_type, config_path)?;
+        let vocab_size = model_config.get_vocab_size();
+        tokenizer.check_model_compatibility(config.model_type, vocab_size)?;
+        let model = MaskedLanguageOption::new(config.model_type, &var_store.root(), &model_config)?;
+        var_store.load(weights_path)?;
+
+        let mask_token = match config.model_type {
+            ModelType::Bert | ModelType::DistilBert => "[MASK]",
+            _ => "<mask>",
+        };
+        let mask_token_id = tokenizer.convert_tokens_to_ids(&[mask_token])[0];
+        if mask_token_id == tokenizer.get_unk_id() {
+            return Err(RustBertError::InvalidConfigurationError(format!(
+                "The vocabulary does not contain the mask token {}",
+                mask_token
+            )));
+        }
+
+        // Only the tokens forming complete words (not sub-word continuations or punctuation) may be predicted
+        let candidate_words = tokenizer
+            .get_vocab_indices()
+            .into_iter()
+            .filter_map(|(token_id, token)| {
+                let word = match config.model_type {
+                    ModelType::Bert | ModelType::DistilBert if token.starts_with("##") => None,
+                    ModelType::Bert | ModelType::DistilBert => Some(token.as_str()),
+                    ModelType::Roberta => token.strip_prefix('\u{0120}'),
+                    _ => token.strip_prefix('\u{2581}'),
+                }?;
+                if (token_id < vocab_size)
+                    & !word.is_empty()
+                    & word.chars().all(char::is_alphanumeric)
+                {
+                    Some((token_id, word.to_string()))
+                } else {
+                    None
+                }
+            })
+            .collect::<HashMap<i64, String>>();
+        let candidate_ids = candidate_words.keys().cloned().collect::<Vec<i64>>();
+        let candidate_mask = Tensor::full(
+            &[vocab_size],
+            std::f64::NEG_INFINITY,
+            (Kind::Float, var_store.device()),
+        )
+        .index_fill(
+            0,
+            &Tensor::of_slice(&candidate_ids).to_device(var_store.device()),
+            0.0,
+        );
+
+        Ok(TextAugmenter {
+            tokenizer,
+            model,
+            var_store,
+            mask_token_id,
+            candidate_words,
+            candidate_mask,
+            substitution_rate: config.substitution_rate,
+            insertion_rate: config.insertion_rate,
+            swap_rate: config.swap_rate,
+            deletion_rate: config.deletion_rate,
+            top_k: config.top_k,
+            num_augmentations: config.num_augmentations,
+            seed: config.seed,
+        })
+    }
+
+    /// Generates augmented versions of texts. The generator is seeded at each call, so that the same inputs lead to
+    /// the same augmentations.
+    ///
+    /// # Arguments
+    ///
+    /// * `texts` - `&[&str]` Array of texts to augment
+    ///
+    /// # Returns
+    ///
+    /// * `Vec<Vec<String>>` `num_augmentations` augmented texts for each input
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// # fn main() -> anyhow::Result<()> {
+    /// # use rust_bert::pipelines::augmentation::TextAugmenter;
+    /// let augmenter = TextAugmenter::new(Default::default())?;
+    /// let augmentations = augmenter.augment(&["I would definitely order from this shop again."])?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn augment(&self, texts: &[&str]) -> Result<Vec<Vec<String>>, RustBertError> {
+        let mut rng = AugmentationRng::new(self.seed);
+        let mut augmentations = Vec::with_capacity(texts.len());
+        for text in texts {
+            let words = text
+                .split_whitespace()
+                .map(String::from)
+                .collect::<Vec<String>>();
+            let mut text_augmentations = Vec::with_capacity(self.num_augmentations);
+            for _ in 0..self.num_augmentations {
+                let mut augmented_words = self.contextual_augmentation(&words, &mut rng)?;
+                let num_swaps = operation_count(self.swap_rate, augmented_words.len());
+                random_swap(&mut augmented_words, num_swaps, &mut rng);
+                let augmented_words =
+                    random_deletion(augmented_words, self.deletion_rate, &mut rng);
+                text_augmentations.push(augmented_words.join(" "));
+            }
+            augmentations.push(text_augmentations);
+        }
+        Ok(augmentations)
+    }
+
+    /// Substitutes and inserts words predicted by the masked language model
+    fn contextual_augmentation(
+        &self,
+        words: &[String],
+        rng: &mut AugmentationRng,
+    ) -> Result<Vec<String>, RustBertError> {
+        let substitutable_words = words
+            .iter()
+            .enumerate()
+            .filter(|(_, word)| word.chars().all(char::is_alphanumeric))
+            .map(|(index, _)| index)
+            .collect::<Vec<usize>>();
+        let num_substitutions = operation_count(self.substitution_rate, words.len());
+        let mut positions = rng
+            .sample_indices(substitutable_words.len(), num_substitutions)
+            .into_iter()
+            .map(|index| MaskedPosition::Substitution(substitutable_words[index]))
+            .collect::<Vec<MaskedPosition>>();
+        let num_insertions = operation_count(self.insertion_rate, words.len());
+        positions.extend(
+            rng.sample_indices(words.len(), num_insertions)
+                .into_iter()
+                .map(MaskedPosition::Insertion),
+        );
+        if positions.is_empty() {
+            return Ok(words.to_vec());
+        }
+
+        // Each masked position is predicted independently, in the context of the original text
+        let mut token_ids = Vec::with_capacity(positions.len());
+        let mut mask_positions = Vec::with_capacity(positions.len());
+        for position in &positions {
+            let (left, right) = match *position {
+                MaskedPosition::Substitution(index) => (&words[..index], &words[index + 1..]),
+                MaskedPosition::Insertion(index) => (&words[..index + 1], &words[index + 1..]),
+            };
+            let (ids, mask_position) = self.masked_input(left, right);
+            token_ids.push(ids);
+            mask_positions.push(mask_position);
+        }
+        let prediction_scores = self.predict_masked_tokens(&token_ids)?;
+
+        let mut augmented_words = words
+            .iter()
+            .map(|word| vec![word.clone()])
+            .collect::<Vec<_>>();
+        for (row_index, (position, mask_position)) in
+            positions.iter().zip(mask_positions).enumerate()
+        {
+            let scores = prediction_scores
+                .get(row_index as i64)
+                .get(mask_position as i64)
+                .to_kind(Kind::Float)
+                + &self.candidate_mask;
+            let (top_scores, top_ids) = scores.topk(self.top_k, -1, true, true);
+            let probabilities = Vec::<f64>::from(top_scores.softmax(-1, Kind::Double));
+            let top_ids = Vec::<i64>::from(top_ids);
+            let original_word = match *position {
+                MaskedPosition::Substitution(index) => Some(words[index].to_lowercase()),
+                MaskedPosition::Insertion(_) => None,
+            };
+            let candidates = top_ids
+                .iter()
+                .zip(probabilities)
+                .filter_map(|(token_id, probability)| {
+                    let word = self.candidate_words.get(token_id)?;
+                    if original_word.as_deref() == Some(word.to_lowercase().as_str()) {
+                        None
+                    } else {
+                        Some((word.as_str(), probability))
+                    }
+                })
+                .collect::<Vec<(&str, f64)>>();
+            let word = match sample_word(&candidates, rng) {
+                Some(word) => word,
+                None => continue,
+            };
+            match *position {
+                MaskedPosition::Substitution(index) => {
+                    let capitalized = words[index]
+                        .chars()
+                        .next()
+                        .map_or(false, char::is_uppercase);
+                    augmented_words[index][0] = if capitalized {
+                        capitalize(word)
+                    } else {
+                        word.to_string()
+                    };
+                }
+                MaskedPosition::Insertion(index) => augmented_words[index].push(word.to_string()),
+            }
+        }
+        Ok(augmented_words.into_iter().flatten().collect())
+    }
+
+    /// Predicts substitutes for a word in the context of its text with the masked language model. At most `top_k`
+    /// substitutes are returned, by decreasing likelihood. The original word is excluded and the capitalization of
+    /// the substitutes follows the original word.
+    ///
+    /// # Arguments
+    ///
+    /// * `words` - `&[String]` Words of the text
+    /// * `index` - `usize` Index of the word to substitute
+    ///
+    /// # Returns
+    ///
+    /// * `Vec<String>` Substitutes of the word
+    pub fn predict_substitutes(
+        &self,
+        words: &[String],
+        index: usize,
+    ) -> Result<Vec<String>, RustBertError> {
+        if index >= words.len() {
+            return Err(RustBertError::ValueError(format!(
+                "Word index {} out of range for a text of {} words",
+                index,
+                words.len()
+            )));
+        }
+        let (ids, mask_position) = self.masked_input(&words[..index], &words[index + 1..]);
+        let prediction_scores = self.predict_masked_tokens(&[ids])?;
+        let scores = prediction_scores
+            .get(0)
+            .get(mask_position as i64)
+            .to_kind(Kind::Float)
+            + &self.candidate_mask;
+        let (_, top_ids) = scores.topk(self.top_k, -1, true, true);
+        let original_word = words[index].to_lowercase();
+        let capitalized = words[index]
+            .chars()
+            .next()
+            .map_or(false, char::is_uppercase);
+        Ok(Vec::<i64>::from(top_ids)
+            .iter()
+            .filter_map(|token_id| self.candidate_words.get(token_id))
+            .filter(|word| word.to_lowercase() != original_word)
+            .map(|word| {
+                if capitalized {
+                    capitalize(word)
+                } else {
+                    word.to_string()
+                }
+            })
+            .collect())
+    }
+
+    /// Builds the token ids of a text with a masked word between `left` and `right`, with the position of the mask
+    fn masked_input(&self, left: &[String], right: &[String]) -> (Vec<i64>, usize) {
+        let mut ids = self
+            .tokenizer
+            .convert_tokens_to_ids(&self.tokenizer.tokenize(&left.join(" ")));
+        ids.push(self.mask_token_id);
+        if !right.is_empty() {
+            let right_tokens = self.tokenizer.tokenize(&format!(" {}", right.join(" ")));
+            ids.extend(self.tokenizer.convert_tokens_to_ids(&right_tokens));
+        }
+        let tokenized_input = self.tokenizer.build_input_with_special_tokens(
+            TokenIdsWithOffsets {
+                offsets: vec![None; ids.len()],
+                reference_offsets: vec![vec![]; ids.len()],
+                masks: vec![Mask::None; ids.len()],
+                ids,
+            },
+            None,
+        );
+        let mask_position = tokenized_input
+            .token_ids
+            .iter()
+            .position(|token_id| *token_id == self.mask_token_id)
+            .unwrap();
+        (tokenized_input.token_ids, mask_position)
+    }
+
+    /// Returns the prediction scores of the masked language model for a batch of token ids
+    fn predict_masked_tokens(&self, token_ids: &[Vec<i64>]) -> Result<Tensor, RustBertError> {
+        let input_batch = TensorBatch::from_token_ids(
+            token_ids,
+            self.tokenizer.get_pad_id().unwrap_or(0),
+            self.var_store.device(),
+        );
+        no_grad(|| {
+            self.model.forward_t(
+                &input_batch.input_ids(),
+                &input_batch.attention_mask().unwrap(),
+                false,
+            )
+        })
+    }
+}
+
+/// Samples a word proportionally to the probabilities of the candidates
+fn sample_word<'a>(candidates: &[(&'a str, f64)], rng: &mut AugmentationRng) -> Option<&'a str> {
+    let total = candidates
+        .iter()
+        .map(|(_, probability)| probability)
+        .sum::<f64>();
+    let mut threshold = rng.next_uniform() * total;
+    for (word, probability) in candidates {
+        if threshold < *probability {
+            return Some(word);
+        }
+        threshold -= probability;
+    }
+    candidates.last().map(|(word, _)| *word)
+}
+
+fn capitalize(word: &str) -> String {
+    let mut chars = word.chars();
+    match chars.next() {
+        Some(first) => first.to_uppercase().chain(chars).collect(),
+        None => String::new(),
+    }
+}
@@ -0,0 +1,461 @@
+// Copyright 2021 Guillaume Becquin
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//     http://www.apache.org/licenses/LICENSE-2.0
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! # Batch processing of files
+//! Runs a pipeline over a large JSON lines or CSV file for offline processing. Records are read lazily from the input
+//! file, passed to the pipeline in batches of `batch_size` texts and the results written incrementally to a JSON lines
+//! output file, one line per input record: `{"index": 0, "id": ..., "output": ...}`.
+//!
+//! Reading and parsing of the input file run in a background thread, preparing the next batches while the model
+//! processes the current one. The tokenization of each batch is parallelized by the pipelines themselves (the batch
+//! encoding of the tokenizers is multi-threaded), so that a larger `batch_size` helps keeping all cores busy.
+//!
+//! After each batch, a checkpoint records the number of records processed and the size of the output file. If the job
+//! is interrupted (crash, out of memory, pre-emption), running it again with the same arguments resumes after the last
+//! completed batch, discarding any partially written output. Delete the checkpoint file to process the input again
+//! from the beginning.
+//!
+//! ```no_run
+//! # fn main() -> anyhow::Result<()> {
+//! use rust_bert::pipelines::batch_jobs::{run_batch_job, BatchJobConfig, InputFormat};
+//! use rust_bert::pipelines::summarization::SummarizationModel;
+//! use std::path::Path;
+//!
+//! let summarization_model = SummarizationModel::new(Default::default())?;
+//! let config = BatchJobConfig {
+//!     input_format: InputFormat::Csv { delimiter: ',' },
+//!     text_field: "article".to_string(),
+//!     id_field: Some("article_id".to_string()),
+//!     batch_size: 16,
+//!     ..Default::default()
+//! };
+//! let report = run_batch_job(
+//!     Path::new("path/to/articles.csv"),
+//!     Path::new("path/to/summaries.jsonl"),
+//!     &config,
+//!     |texts| Ok(summarization_model.summarize(texts)),
+//! )?;
+//! println!("{} records processed", report.processed_records);
+//! # Ok(())
+//! # }
+//! ```
+
+use crate::common::error::RustBertError;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::fs::{File, OpenOptions};
+use std::io::{BufRead, BufReader, BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{sync_channel, Receiver};
+use std::thread;
+
+#[derive(Debug, Clone, PartialEq)]
+/// # Format of the input file
+pub enum InputFormat {
+    /// One JSON object per line
+    JsonLines,
+    /// Delimiter separated values, with a header row naming the columns. Fields may be quoted with double quotes
+    /// (including delimiters and line breaks), doubled quotes standing for a literal quote.
+    Csv { delimiter: char },
+}
+
+/// # Configuration for a batch processing job
+#[derive(Debug, Clone)]
+pub struct BatchJobConfig {
+    /// Format of the input file (default: JSON lines)
+    pub input_format: InputFormat,
+    /// Name of the field (JSON lines) or column (CSV) holding the text to process (default: `text`)
+    pub text_field: String,
+    /// Optional name of a field or column copied to the output to identify the records
+    pub id_field: Option<String>,
+    /// Number of records passed to the pipeline at once (default: 32)
+    pub batch_size: usize,
+    /// Number of batches prepared in advance by the reading thread (default: 2)
+    pub prefetch_batches: usize,
+    /// Path of the checkpoint file (default: path of the output file with a `.checkpoint` extension appended)
+    pub checkpoint_path: Option<PathBuf>,
+}
+
+impl Default for BatchJobConfig {
+    fn default() -> BatchJobConfig {
+        BatchJobConfig {
+            input_format: InputFormat::JsonLines,
+            text_field: "text".to_string(),
+            id_field: None,
+            batch_size: 32,
+            prefetch_batches: 2,
+            checkpoint_path: None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+/// # Progress of a batch job, saved after each batch
+pub struct BatchJobCheckpoint {
+    /// Number of input records processed
+    pub records: usize,
+    /// Size in bytes of the output file after the last completed batch
+    pub output_bytes: u64,
+}
+
+impl BatchJobCheckpoint {
+    /// Loads a checkpoint, returning `None` if the file does not exist
+    pub fn load(path: &Path) -> Result<Option<BatchJobCheckpoint>, RustBertError> {
+        if !path.exists() {
+            return Ok(None);
+        }
+        let file = File::open(path)?;
+        serde_json::from_reader(BufReader::new(file))
+            .map(Some)
+            .map_err(|error| {
+                RustBertError::ValueError(format!(
+                    "Invalid checkpoint file {}: {}",
+                    path.display(),
+                    error
+                ))
+            })
+    }
+
+    /// Saves the checkpoint, writing to a temporary file first so that an interruption never leaves a truncated
+    /// checkpoint behind
+    pub fn save(&self, path: &Path) -> Result<(), RustBertError> {
+        let mut temporary_path = path.as_os_str().to_owned();
+        temporary_path.push(".tmp");
+        let temporary_path = PathBuf::from(temporary_path);
+        let mut file = File::create(&temporary_path)?;
+        file.write_all(json!(self).to_string().as_bytes())?;
+        file.sync_all()?;
+        std::fs::rename(&temporary_path, path)?;
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+/// # Summary of a batch job run
+pub struct BatchJobReport {
+    /// Number of records skipped because they were processed by a previous run
+    pub resumed_records: usize,
+    /// Number of records processed by this run
+    pub processed_records: usize,
+    /// Number of batches processed by this run
+    pub processed_batches: usize,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+/// # Record read from the input file
+pub struct InputRecord {
+    /// Text to process
+    pub text: String,
+    /// Value of the identifier field, if configured
+    pub id: Option<Value>,
+}
+
+/// # Iterator over the records of an input file
+pub struct RecordReader<R: BufRead> {
+    lines: std::io::Lines<R>,
+    format: InputFormat,
+    text_field: String,
+    id_field: Option<String>,
+    columns: Option<(usize, Option<usize>)>,
+    line_number: usize,
+}
+
+impl<R: BufRead> RecordReader<R> {
+    /// Creates a reader extracting the text and identifier fields of the configuration
+    pub fn new(reader: R, config: &BatchJobConfig) -> RecordReader<R> {
+        RecordReader {
+            lines: reader.lines(),
+            format: config.input_format.clone(),
+            text_field: config.text_field.clone(),
+            id_field: config.id_field.clone(),
+            columns: None,
+            line_number: 0,
+        }
+    }
+
+    fn next_line(&mut self) -> Option<Result<String, RustBertError>> {
+        self.line_number += 1;
+        self.lines
+            .next()
+            .map(|line| line.map_err(RustBertError::from))
+    }
+
+    fn error(&self, message: String) -> RustBertError {
+        RustBertError::ValueError(format!(
+            "Line {} of the input file: {}",
+            self.line_number, message
+        ))
+    }
+
+    fn read_json_record(&mut self) -> Option<Result<InputRecord, RustBertError>> {
+        loop {
+            let line = match self.next_line()? {
+                Ok(line) => line,
+                Err(error) => return Some(Err(error)),
+            };
+            if line.trim().is_empty() {
+                continue;
+            }
+            let value: Value = match serde_json::from_str(&line) {
+                Ok(value) => value,
+                Err(error) => return Some(Err(self.error(format!("invalid JSON ({})", error)))),
+            };
+            let text = match value.get(&self.text_field).and_then(Value::as_str) {
+                Some(text) => text.to_string(),
+                None => {
+                    return Some(Err(
+                        self.error(format!("missing string field {}", self.text_field))
+                    ));
+                }
+            };
+            let id = self
+                .id_field
+                .as_ref()
+                .map(|id_field| value.get(id_field).cloned().unwrap_or(Value::Null));
+            return Some(Ok(InputRecord { text, id }));
+        }
+    }
+
+    // Reads the lines of a CSV record, joining the lines of quoted fields spanning several lines
+    fn read_csv_fields(&mut self, delimiter: char) -> Option<Result<Vec<String>, RustBertError>> {
+        let mut record = loop {
+            match self.next_line()? {
+                Ok(line) if line.trim().is_empty() => continue,
+                Ok(line) => break line,
+                Err(error) => return Some(Err(error)),
+            }
+        };
+        loop {
+            if let Some(fields) = split_csv_record(&record, delimiter) {
+                return Some(Ok(fields));
+            }
+            match self.next_line() {
+                Some(Ok(line)) => {
+                    record.push('\n');
+                    record.push_str(&line);
+                }
+                Some(Err(error)) => return Some(Err(error)),
+                None => return Some(Err(self.error("unclosed quoted field".to_string()))),
+            }
+        }
+    }
+
+    fn read_csv_record(&mut self, delimiter: char) -> Option<Result<InputRecord, RustBertError>> {
+        let (text_column, id_column) = match self.columns {
+            Some(columns) => columns,
+            None => {
+                let header = match self.read_csv_fields(delimiter)? {
+                    Ok(header) => header,
+                    Err(error) => return Some(Err(error)),
+                };
+                let position = |name: &str| header.iter().position(|column| column == name);
+                let text_column = match position(&self.text_field) {
+                    Some(text_column) => text_column,
+                    None => {
+                        return Some(Err(
+                            self.error(format!("missing column {}", self.text_field))
+                        ));
+                    }
+                };
+                let id_column = match &self.id_field {
+                    Some(id_field) => match position(id_field) {
+                        Some(id_column) => Some(id_column),
+                        None => {
+                            return Some(Err(self.error(format!("missing column {}", id_field))))
+                        }
+                    },
+                    None => None,
+                };
+                self.columns = Some((text_column, id_column));
+                (text_column, id_column)
+            }
+        };
+        let mut fields = match self.read_csv_fields(delimiter)? {
+            Ok(fields) => fields,
+            Err(error) => return Some(Err(error)),
+        };
+        if fields.len() <= text_column.max(id_column.unwrap_or(0)) {
+            return Some(Err(self.error(format!(
+                "expected at least {} fields, got {}",
+                text_column.max(id_column.unwrap_or(0)) + 1,
+                fields.len()
+            ))));
+        }
+        let id = id_column.map(|id_column| Value::String(fields[id_column].clone()));
+        let text = fields.swap_remove(text_column);
+        Some(Ok(InputRecord { text, id }))
+    }
+}
+
+impl<R: BufRead> Iterator for RecordReader<R> {
+    type Item = Result<InputRecord, RustBertError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        match self.format {
+            InputFormat::JsonLines => self.read_json_record(),
+            InputFormat::Csv { delimiter } => self.read_csv_record(delimiter),
+        }
+    }
+}
+
+/// Splits a CSV record into its fields, unquoting them. Returns `None` if the record ends within a quoted field.
+pub fn split_csv_record(record: &str, delimiter: char) -> Option<Vec<String>> {
+    let mut fields = vec![];
+    let mut field = String::new();
+    let mut in_quotes = false;
+    let mut chars = record.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '"' if in_quotes && chars.peek() == Some(&'"') => {
+                chars.next();
+                field.push('"');
+            }
+            '"' if in_quotes => in_quotes = false,
+            '"' if field.is_empty() => in_quotes = true,
+            c if c == delimiter && !in_quotes => fields.push(std::mem::take(&mut field)),
+            '\r' if !in_quotes && chars.peek().is_none() => {}
+            c => field.push(c),
+        }
+    }
+    if in_quotes {
+        return None;
+    }
+    fields.push(field);
+    Some(fields)
+}
+
+// Reads the input file in a background thread, sending batches of records after skipping the records already processed
+fn spawn_reader(
+    input_path: &Path,
+    config: &BatchJobConfig,
+    skip: usize,
+) -> Result<Receiver<Result<Vec<InputRecord>, RustBertError>>, RustBertError> {
+    let reader = RecordReader::new(BufReader::new(File::open(input_path)?), config);
+    let batch_size = config.batch_size.max(1);
+    let (sender, receiver) = sync_channel(config.prefetch_batches.max(1));
+    thread::spawn(move || {
+        let mut records = reader.skip(skip);
+        loop {
+            let batch = (&mut records)
+                .take(batch_size)
+                .collect::<Result<Vec<InputRecord>, RustBertError>>();
+            let done = match &batch {
+                Ok(batch) => batch.len() < batch_size,
+                Err(_) => true,
+            };
+            if sender.send(batch).is_err() || done {
+                break;
+            }
+        }
+    });
+    Ok(receiver)
+}
+
+/// Runs a pipeline over the records of a file, resuming from the checkpoint of a previous run if it exists.
+///
+/// # Arguments
+///
+/// * `input_path` - JSON lines or CSV file to process
+/// * `output_path` - JSON lines file receiving the results
+/// * `config` - `BatchJobConfig` with the format of the input and the batching options
+/// * `process` - function running the pipeline on a batch of texts, returning one serializable output per text
+///
+/// # Returns
+///
+/// * `BatchJobReport` on completion. On error (invalid record, failure of the pipeline), the output and checkpoint
+/// of the last completed batch are kept so that the job can be resumed.
+pub fn run_batch_job<F, O>(
+    input_path: &Path,
+    output_path: &Path,
+    config: &BatchJobConfig,
+    mut process: F,
+) -> Result<BatchJobReport, RustBertError>
+where
+    F: FnMut(&[&str]) -> Result<Vec<O>, RustBertError>,
+    O: Serialize,
+{
+    let checkpoint_path = match &config.checkpoint_path {
+        Some(checkpoint_path) => checkpoint_path.clone(),
+        None => {
+            let mut checkpoint_path = output_path.as_os_str().to_owned();
+            checkpoint_path.push(".checkpoint");
+            PathBuf::from(checkpoint_path)
+        }
+    };
+    let mut checkpoint =
+        BatchJobCheckpoint::load(&checkpoint_path)?.unwrap_or(BatchJobCheckpoint {
+            records: 0,
+            output_bytes: 0,
+        });
+    // Results written after the last checkpoint belong to an interrupted batch and are processed again
+    let output_file = OpenOptions::new()
+        .create(true)
+        .write(true)
+        .truncate(false)
+        .open(output_path)?;
+    if output_file.metadata()?.len() < checkpoint.output_bytes {
+        return Err(RustBertError::ValueError(format!(
+            "The output file {} is shorter than recorded in the checkpoint {}",
+            output_path.display(),
+            checkpoint_path.display()
+        )));
+    }
+    output_file.set_len(checkpoint.output_bytes)?;
+    let mut output_file = OpenOptions::new().append(true).open(output_path)?;
+
+    let resumed_records = checkpoint.records;
+    let mut report = BatchJobReport {
+        resumed_records,
+        processed_records: 0,
+        processed_batches: 0,
+    };
+    for batch in spawn_reader(input_path, config, resumed_records)? {
+        let batch = batch?;
+        if batch.is_empty() {
+            break;
+        }
+        let texts = batch
+            .iter()
+            .map(|record| record.text.as_str())
+            .collect::<Vec<&str>>();
+        let outputs = process(&texts)?;
+        if outputs.len() != batch.len() {
+            return Err(RustBertError::ValueError(format!(
+                "The pipeline returned {} outputs for a batch of {} records",
+                outputs.len(),
+                batch.len()
+            )));
+        }
+        let mut buffer = vec![];
+        {
+            let mut writer = BufWriter::new(&mut buffer);
+            for (position, (record, output)) in batch.iter().zip(outputs).enumerate() {
+                let line = match &record.id {
+                    Some(id) => {
+                        json!({"index": checkpoint.records + position, "id": id, "output": output})
+                    }
+                    None => json!({"index": checkpoint.records + position, "output": output}),
+                };
+                writeln!(writer, "{}", line)?;
+            }
+            writer.flush()?;
+        }
+        output_file.write_all(&buffer)?;
+        output_file.sync_data()?;
+
+        checkpoint.records += batch.len();
+        checkpoint.output_bytes += buffer.len() as u64;
+        checkpoint.save(&checkpoint_path)?;
+        report.processed_records += batch.len();
+        report.processed_batches += 1;
+    }
+    Ok(report)
+}
@@ -0,0 +1,537 @@
+// Copyright 2021 Guillaume Becquin
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//     http://www.apache.org/licenses/LICENSE-2.0
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! # OpenAI-compatible chat completions
+//! Components to serve local models behind an OpenAI-compatible `/v1/chat/completions` endpoint, independently of the
+//! HTTP framework used:
+//! - `ChatCompletionRequest` and `ChatCompletionResponse`: (de)serializable request and response bodies
+//! - `ChatTemplate`: rendering of the conversation messages into the prompt format of the model
+//! - `ChatCompletionRequest::generate_config`: translation of the request parameters (`temperature`, `top_p`, `n`,
+//! `max_tokens` and `logit_bias`) onto a `GenerateConfig`
+//! - `ChatCompletionResponse::from_generation`: response building, truncating the completions at the `stop` sequences
+//! - `ChatCompletionChunk`: server-sent events for the requests with `stream: true`. The generation pipelines return
+//! complete sequences, the chunks of a response (role, content and finish reason) are therefore sent at once.
+//!
+//! The `logit_bias` values are limited to the ones expressible as token restrictions: -100 bans a token, 100 restricts
+//! the generation to the tokens with this bias. Other values, as well as non-zero `presence_penalty` and
+//! `frequency_penalty`, are rejected with a `RustBertError::ValueError`.
+//!
+//! ```no_run
+//! # fn main() -> anyhow::Result<()> {
+//! use rust_bert::pipelines::chat_completions::{
+//!     ChatCompletionChunk, ChatCompletionRequest, ChatCompletionResponse, ChatTemplate,
+//! };
+//! use rust_bert::pipelines::generation_utils::{GPT2Generator, GenerateConfig, LanguageGenerator};
+//!
+//! let body = r#"{"model": "local", "messages": [{"role": "user", "content": "Hello!"}], "max_tokens": 32, "stop": "\n"}"#;
+//! let request: ChatCompletionRequest = serde_json::from_str(body)?;
+//! let prompt = ChatTemplate::ChatMl.render(&request.messages);
+//!
+//! let base_config = GenerateConfig::default();
+//! let prompt_tokens = 16; // number of tokens of the prompt, for decoder-only models
+//! let generator = GPT2Generator::new(request.generate_config(&base_config, prompt_tokens, 50257)?)?;
+//! let (generated, usage) = generator.generate_with_usage(Some(&[prompt.as_str()]), None, None, None, None);
+//! let response = ChatCompletionResponse::from_generation(&request, &prompt, generated, &usage);
+//! if request.stream {
+//!     for chunk in ChatCompletionChunk::from_response(&response) {
+//!         print!("{}", chunk.to_server_sent_event()?);
+//!     }
+//!     print!("{}", ChatCompletionChunk::SERVER_SENT_EVENTS_END);
+//! } else {
+//!     println!("{}", serde_json::to_string(&response)?);
+//! }
+//! # Ok(())
+//! # }
+//! ```
+
+use crate::common::error::RustBertError;
+use crate::pipelines::generation_utils::{GenerateConfig, GenerationUsage};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::time::{SystemTime, UNIX_EPOCH};
+use uuid::Uuid;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+/// # Author of a chat message
+pub enum ChatRole {
+    System,
+    User,
+    Assistant,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+/// # Message of a conversation
+pub struct ChatMessage {
+    pub role: ChatRole,
+    pub content: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+}
+
+impl ChatMessage {
+    pub fn new(role: ChatRole, content: &str) -> ChatMessage {
+        ChatMessage {
+            role,
+            content: content.to_string(),
+            name: None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(untagged)]
+/// # Stop sequences of a request, given as a single string or a list of strings
+pub enum StopSequences {
+    Single(String),
+    Multiple(Vec<String>),
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+/// # Body of a chat completion request
+pub struct ChatCompletionRequest {
+    /// Name of the model requested
+    pub model: String,
+    /// Messages of the conversation
+    pub messages: Vec<ChatMessage>,
+    #[serde(default)]
+    pub temperature: Option<f64>,
+    #[serde(default)]
+    pub top_p: Option<f64>,
+    /// Number of completions to generate
+    #[serde(default)]
+    pub n: Option<i64>,
+    /// Maximum number of tokens generated
+    #[serde(default)]
+    pub max_tokens: Option<i64>,
+    #[serde(default)]
+    pub stop: Option<StopSequences>,
+    /// Bias added to the logits of tokens, indexed by token id
+    #[serde(default)]
+    pub logit_bias: Option<HashMap<String, f64>>,
+    #[serde(default)]
+    pub presence_penalty: Option<f64>,
+    #[serde(default)]
+    pub frequency_penalty: Option<f64>,
+    /// Return the response as server-sent events
+    #[serde(default)]
+    pub stream: bool,
+    #[serde(default)]
+    pub user: Option<String>,
+}
+
+impl ChatCompletionRequest {
+    /// Returns the stop sequences of the request
+    pub fn stop_sequences(&self) -> Vec<String> {
+        match &self.stop {
+            Some(StopSequences::Single(stop)) => vec![stop.clone()],
+            Some(StopSequences::Multiple(stops)) => stops.clone(),
+            None => vec![],
+        }
+        .into_iter()
+        .filter(|stop| !stop.is_empty())
+        .collect()
+    }
+
+    /// Translates the parameters of the request onto a generation configuration
+    ///
+    /// # Arguments
+    ///
+    /// * `base_config` - `GenerateConfig` providing the resources and the parameters not set by the request
+    /// * `prompt_tokens` - number of tokens of the prompt for decoder-only models (for which `max_length` includes the prompt), 0 for encoder-decoder models
+    /// * `vocab_size` - size of the vocabulary of the model, used to ban the tokens with a bias of -100
+    ///
+    /// # Returns
+    ///
+    /// * `GenerateConfig` for the request, or a `RustBertError::ValueError` for invalid or unsupported parameters
+    pub fn generate_config(
+        &self,
+        base_config: &GenerateConfig,
+        prompt_tokens: i64,
+        vocab_size: i64,
+    ) -> Result<GenerateConfig, RustBertError> {
+        let mut config = base_config.clone();
+        if let Some(temperature) = self.temperature {
+            if !(0.0..=2.0).contains(&temperature) {
+                return Err(RustBertError::ValueError(format!(
+                    "`temperature` ({}) must be between 0 and 2",
+                    temperature
+                )));
+            }
+            if temperature == 0.0 {
+                config.do_sample = false;
+            } else {
+                config.do_sample = true;
+                config.temperature = temperature;
+            }
+        }
+        if let Some(top_p) = self.top_p {
+            if top_p <= 0.0 || top_p > 1.0 {
+                return Err(RustBertError::ValueError(format!(
+                    "`top_p` ({}) must be in ]0, 1]",
+                    top_p
+                )));
+            }
+            config.top_p = top_p;
+            if self.temperature.is_none() {
+                config.do_sample = true;
+            }
+        }
+        if let Some(n) = self.n {
+            if n < 1 {
+                return Err(RustBertError::ValueError(format!(
+                    "`n` ({}) must be positive",
+                    n
+                )));
+            }
+            config.num_return_sequences = n;
+            if !config.do_sample && config.num_beams < n {
+                config.num_beams = n;
+            }
+        }
+        if let Some(max_tokens) = self.max_tokens {
+            if max_tokens < 1 {
+                return Err(RustBertError::ValueError(format!(
+                    "`max_tokens` ({}) must be positive",
+                    max_tokens
+                )));
+            }
+            config.max_length = prompt_tokens + max_tokens;
+            config.min_length = config.min_length.min(config.max_length);
+        }
+        for (name, penalty) in &[
+            ("presence_penalty", self.presence_penalty),
+            ("frequency_penalty", self.frequency_penalty),
+        ] {
+            if penalty.map_or(false, |penalty| penalty != 0.0) {
+                return Err(RustBertError::ValueError(format!(
+                    "`{}` is not supported",
+                    name
+                )));
+            }
+        }
+        if let Some(logit_bias) = &self.logit_bias {
+            if let Some(allowed_token_ids) = allowed_token_ids(logit_bias, vocab_size)? {
+                config.allowed_token_ids = Some(allowed_token_ids);
+            }
+        }
+        Ok(config)
+    }
+}
+
+fn allowed_token_ids(
+    logit_bias: &HashMap<String, f64>,
+    vocab_size: i64,
+) -> Result<Option<Vec<i64>>, RustBertError> {
+    let mut forced = vec![];
+    let mut banned = HashSet::new();
+    for (token, &bias) in logit_bias {
+        let token_id = token.parse::<i64>().map_err(|_| {
+            RustBertError::ValueError(format!("Invalid token id {} in `logit_bias`", token))
+        })?;
+        if token_id < 0 || token_id >= vocab_size {
+            return Err(RustBertError::ValueError(format!(
+                "Token id {} of `logit_bias` is out of the vocabulary",
+                token_id
+            )));
+        }
+        if bias >= 100.0 {
+            forced.push(token_id);
+        } else if bias <= -100.0 {
+            banned.insert(token_id);
+        } else if bias != 0.0 {
+            return Err(RustBertError::ValueError(format!(
+                "Unsupported `logit_bias` value {} for token {}, only -100 (ban) and 100 (force) are supported",
+                bias, token_id
+            )));
+        }
+    }
+    Ok(if !forced.is_empty() {
+        forced.sort_unstable();
+        Some(forced)
+    } else if !banned.is_empty() {
+        if banned.len() as i64 == vocab_size {
+            return Err(RustBertError::ValueError(
+                "`logit_bias` bans all the tokens of the vocabulary".to_string(),
+            ));
+        }
+        Some(
+            (0..vocab_size)
+                .filter(|token_id| !banned.contains(token_id))
+                .collect(),
+        )
+    } else {
+        None
+    })
+}
+
+#[derive(Debug, Clone, PartialEq)]
+/// # Prompt format of chat models
+pub enum ChatTemplate {
+    /// `<|im_start|>role\ncontent<|im_end|>` messages
+    ChatMl,
+    /// `[INST] ... [/INST]` turns of Llama 2 chat models, with the system message in a `<<SYS>>` block
+    Llama2,
+    /// `<|role|>\ncontent</s>` messages of Zephyr models
+    Zephyr,
+    /// `Role: content` lines
+    Plain,
+}
+
+impl ChatTemplate {
+    /// Renders the messages of a conversation, followed by the start of the assistant answer
+    pub fn render(&self, messages: &[ChatMessage]) -> String {
+        let role_name = |role: ChatRole| match role {
+            ChatRole::System => "system",
+            ChatRole::User => "user",
+            ChatRole::Assistant => "assistant",
+        };
+        let mut prompt = String::new();
+        match self {
+            ChatTemplate::ChatMl => {
+                for message in messages {
+                    prompt.push_str(&format!(
+                        "<|im_start|>{}\n{}<|im_end|>\n",
+                        role_name(message.role),
+                        message.content
+                    ));
+                }
+                prompt.push_str("<|im_start|>assistant\n");
+            }
+            ChatTemplate::Zephyr => {
+                for message in messages {
+                    prompt.push_str(&format!(
+                        "<|{}|>\n{}</s>\n",
+                        role_name(message.role),
+                        message.content
+                    ));
+                }
+                prompt.push_str("<|assistant|>\n");
+            }
+            ChatTemplate::Llama2 => {
+                let mut system = None;
+                for message in messages {
+                    match message.role {
+                        ChatRole::System => system = Some(message.content.as_str()),
+                        ChatRole::User => {
+                            let content = match system.take() {
+                                Some(system) => {
+                                    format!("<<SYS>>\n{}\n<</SYS>>\n\n{}", system, message.content)
+                                }
+                                None => message.content.clone(),
+                            };
+                            prompt.push_str(&format!("<s>[INST] {} [/INST]", content));
+                        }
+                        ChatRole::Assistant => {
+                            prompt.push_str(&format!(" {} </s>", message.content))
+                        }
+                    }
+                }
+            }
+            ChatTemplate::Plain => {
+                for message in messages {
+                    let role = role_name(message.role);
+                    prompt.push_str(&format!(
+                        "{}{}: {}\n",
+                        role[..1].to_uppercase(),
+                        &role[1..],
+                        message.content
+                    ));
+                }
+                prompt.push_str("Assistant:");
+            }
+        }
+        prompt
+    }
+}
+
+/// Truncates a completion at the first occurrence of any of the stop sequences, returning true if it was truncated
+pub fn truncate_at_stop_sequences(completion: &str, stop_sequences: &[String]) -> (String, bool) {
+    match stop_sequences
+        .iter()
+        .filter_map(|stop| completion.find(stop.as_str()))
+        .min()
+    {
+        Some(position) => (completion[..position].to_string(), true),
+        None => (completion.to_string(), false),
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+/// # Reason of the end of a completion
+pub enum FinishReason {
+    /// End of sequence token or stop sequence
+    Stop,
+    /// Maximum number of tokens reached
+    Length,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ChatCompletionChoice {
+    pub index: usize,
+    pub message: ChatMessage,
+    pub finish_reason: FinishReason,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+/// # Token counts of a chat completion
+pub struct ChatCompletionUsage {
+    pub prompt_tokens: usize,
+    pub completion_tokens: usize,
+    pub total_tokens: usize,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+/// # Body of a chat completion response
+pub struct ChatCompletionResponse {
+    pub id: String,
+    /// Always `chat.completion`
+    pub object: String,
+    /// Creation time, in seconds since the Unix epoch
+    pub created: u64,
+    pub model: String,
+    pub choices: Vec<ChatCompletionChoice>,
+    pub usage: ChatCompletionUsage,
+}
+
+impl ChatCompletionResponse {
+    /// Builds the response from the output of a generation pipeline
+    ///
+    /// # Arguments
+    ///
+    /// * `request` - `ChatCompletionRequest` the completions were generated for
+    /// * `prompt` - rendered prompt, removed from the start of the generated sequences if present
+    /// * `generated` - sequences generated for the request
+    /// * `usage` - `GenerationUsage` of the generation
+    pub fn from_generation(
+        request: &ChatCompletionRequest,
+        prompt: &str,
+        generated: Vec<String>,
+        usage: &GenerationUsage,
+    ) -> ChatCompletionResponse {
+        let stop_sequences = request.stop_sequences();
+        // Sequences are not truncated when reaching the maximum length: all of them reached it if the total number of
+        // generated tokens does
+        let length_reached = match request.max_tokens {
+            Some(max_tokens) => {
+                usage.generated_tokens >= max_tokens as usize * generated.len().max(1)
+            }
+            None => false,
+        };
+        let choices = generated
+            .into_iter()
+            .enumerate()
+            .map(|(index, sequence)| {
+                let completion = sequence.strip_prefix(prompt).unwrap_or(&sequence);
+                let (content, stopped) = truncate_at_stop_sequences(completion, &stop_sequences);
+                ChatCompletionChoice {
+                    index,
+                    message: ChatMessage::new(ChatRole::Assistant, content.trim_start()),
+                    finish_reason: if length_reached && !stopped {
+                        FinishReason::Length
+                    } else {
+                        FinishReason::Stop
+                    },
+                }
+            })
+            .collect();
+        ChatCompletionResponse {
+            id: format!("chatcmpl-{}", Uuid::new_v4().to_simple()),
+            object: "chat.completion".to_string(),
+            created: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map_or(0, |duration| duration.as_secs()),
+            model: request.model.clone(),
+            choices,
+            usage: ChatCompletionUsage {
+                prompt_tokens: usage.prompt_tokens,
+                completion_tokens: usage.generated_tokens,
+                total_tokens: usage.prompt_tokens + usage.generated_tokens,
+            },
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize)]
+/// # Incremental message of a streamed choice
+pub struct ChatMessageDelta {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub role: Option<ChatRole>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub content: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ChatCompletionChunkChoice {
+    pub index: usize,
+    pub delta: ChatMessageDelta,
+    pub finish_reason: Option<FinishReason>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+/// # Chunk of a streamed chat completion response
+pub struct ChatCompletionChunk {
+    pub id: String,
+    /// Always `chat.completion.chunk`
+    pub object: String,
+    pub created: u64,
+    pub model: String,
+    pub choices: Vec<ChatCompletionChunkChoice>,
+}
+
+impl ChatCompletionChunk {
+    /// Event ending a stream of server-sent events
+    pub const SERVER_SENT_EVENTS_END: &'static str = "data: [DONE]\n\n";
+
+    /// Splits a response in the chunks of a stream: the role, the content and the finish reason of each choice
+    pub fn from_response(response: &ChatCompletionResponse) -> Vec<ChatCompletionChunk> {
+        let chunk = |choice: ChatCompletionChunkChoice| ChatCompletionChunk {
+            id: response.id.clone(),
+            object: "chat.completion.chunk".to_string(),
+            created: response.created,
+            model: response.model.clone(),
+            choices: vec![choice],
+        };
+        let mut chunks = vec![];
+        for choice in &response.choices {
+            chunks.push(chunk(ChatCompletionChunkChoice {
+                index: choice.index,
+                delta: ChatMessageDelta {
+                    role: Some(choice.message.role),
+                    content: None,
+                },
+                finish_reason: None,
+            }));
+            chunks.push(chunk(ChatCompletionChunkChoice {
+                index: choice.index,
+                delta: ChatMessageDelta {
+                    role: None,
+                    content: Some(choice.message.content.clone()),
+                },
+                finish_reason: None,
+            }));
+            chunks.push(chunk(ChatCompletionChunkChoice {
+                index: choice.index,
+                delta: ChatMessageDelta::default(),
+                finish_reason: Some(choice.finish_reason),
+            }));
+        }
+        chunks
+    }
+
+    /// Serializes the chunk as a server-sent event (`data: {...}` followed by a blank line)
+    pub fn to_server_sent_event(&self) -> Result<String, RustBertError> {
+        let data = serde_json::to_string(self)
+            .map_err(|error| RustBertError::ValueError(error.to_string()))?;
+        Ok(format!("data: {}\n\n", data))
+    }
+}
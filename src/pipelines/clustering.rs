@@ -0,0 +1,447 @@
+// Copyright 2021 Guillaume Becquin
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//     http://www.apache.org/licenses/LICENSE-2.0
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! # Text clustering
+//! Groups texts by similarity of their embeddings, computed by a `TextEmbedder` (e.g. a `FeatureExtractionModel`).
+//! Two algorithms are available:
+//! - k-means, initialized deterministically with the farthest-first traversal of the embeddings
+//! - agglomerative clustering with average linkage, merging the closest clusters until the target number of clusters
+//! is reached (quadratic memory in the number of texts)
+//!
+//! The embeddings are L2-normalized by default, so that the euclidean distance between embeddings is a monotonic
+//! function of their cosine similarity. When the number of clusters is not provided, it is selected in a range of
+//! values by maximizing the mean silhouette coefficient of the clustering.
+//!
+//! ```no_run
+//! # fn main() -> anyhow::Result<()> {
+//! use rust_bert::pipelines::clustering::{cluster_texts, ClusteringAlgorithm, ClusteringOptions};
+//! use rust_bert::pipelines::feature_extraction::FeatureExtractionModel;
+//!
+//! let embedder = FeatureExtractionModel::new(Default::default())?;
+//! let texts = [
+//!     "The stock market fell sharply today.",
+//!     "Shares dropped after the earnings report.",
+//!     "The team won the championship final.",
+//!     "A last-minute goal decided the match.",
+//! ];
+//! let options = ClusteringOptions {
+//!     algorithm: ClusteringAlgorithm::Agglomerative,
+//!     max_clusters: 3,
+//!     ..Default::default()
+//! };
+//! let clustering = cluster_texts(&embedder, &texts, &options)?;
+//! for (cluster, exemplar) in clustering.exemplars.iter().enumerate() {
+//!     println!("Cluster {}: {}", cluster, texts[*exemplar]);
+//! }
+//! # Ok(())
+//! # }
+//! ```
+
+use crate::common::error::RustBertError;
+use crate::pipelines::feature_extraction::TextEmbedder;
+use std::collections::HashMap;
+use tch::{Device, Kind, Tensor};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+/// # Clustering algorithm
+pub enum ClusteringAlgorithm {
+    /// k-means (Lloyd's algorithm)
+    KMeans,
+    /// Agglomerative clustering with average linkage
+    Agglomerative,
+}
+
+/// # Options for the text clustering
+#[derive(Debug, Clone)]
+pub struct ClusteringOptions {
+    /// Clustering algorithm (default: `KMeans`)
+    pub algorithm: ClusteringAlgorithm,
+    /// Number of clusters. If `None`, the number of clusters between `min_clusters` and `max_clusters` with the highest silhouette score is selected (default: None)
+    pub num_clusters: Option<usize>,
+    /// Smallest number of clusters considered for the automatic selection (default: 2)
+    pub min_clusters: usize,
+    /// Largest number of clusters considered for the automatic selection (default: 10)
+    pub max_clusters: usize,
+    /// Maximum number of k-means iterations (default: 100)
+    pub max_iterations: usize,
+    /// L2-normalize the embeddings before clustering (default: true)
+    pub normalize: bool,
+    /// Number of texts embedded at once (default: 64)
+    pub batch_size: usize,
+}
+
+impl Default for ClusteringOptions {
+    fn default() -> ClusteringOptions {
+        ClusteringOptions {
+            algorithm: ClusteringAlgorithm::KMeans,
+            num_clusters: None,
+            min_clusters: 2,
+            max_clusters: 10,
+            max_iterations: 100,
+            normalize: true,
+            batch_size: 64,
+        }
+    }
+}
+
+/// # Result of a clustering
+#[derive(Debug, Clone)]
+pub struct Clustering {
+    /// Cluster of each text. Clusters are numbered in the order of their first text
+    pub assignments: Vec<usize>,
+    /// Number of clusters
+    pub num_clusters: usize,
+    /// Index of the representative text of each cluster (the text closest to the cluster centroid)
+    pub exemplars: Vec<usize>,
+    /// Mean silhouette coefficient of the clustering, in [-1, 1] (0 for a single cluster)
+    pub silhouette_score: f64,
+}
+
+/// Clusters texts by similarity of their embeddings
+///
+/// # Arguments
+///
+/// * `embedder` - `TextEmbedder` computing the embeddings of the texts
+/// * `texts` - `&[&str]` Array of texts to cluster
+/// * `options` - `ClusteringOptions` with the algorithm and number of clusters
+///
+/// # Returns
+///
+/// * `Clustering` with the cluster assignments and exemplars
+pub fn cluster_texts<E>(
+    embedder: &E,
+    texts: &[&str],
+    options: &ClusteringOptions,
+) -> Result<Clustering, RustBertError>
+where
+    E: TextEmbedder + ?Sized,
+{
+    let embeddings = embedder.embed_in_batches(texts, options.batch_size)?;
+    cluster_embeddings(&embeddings, options)
+}
+
+/// Clusters embeddings
+///
+/// # Arguments
+///
+/// * `embeddings` - `Tensor` of shape (*number of elements*, *embedding dimension*)
+/// * `options` - `ClusteringOptions` with the algorithm and number of clusters
+///
+/// # Returns
+///
+/// * `Clustering` with the cluster assignments and exemplars
+pub fn cluster_embeddings(
+    embeddings: &Tensor,
+    options: &ClusteringOptions,
+) -> Result<Clustering, RustBertError> {
+    let size = embeddings.size();
+    if size.len() != 2 {
+        return Err(RustBertError::ValueError(format!(
+            "Expected embeddings of shape (number of elements, embedding dimension), got {:?}",
+            size
+        )));
+    }
+    let embeddings = embeddings.to(Device::Cpu).to_kind(Kind::Float);
+    let mut points = (0..size[0])
+        .map(|row| Vec::<f32>::from(embeddings.get(row)))
+        .collect::<Vec<_>>();
+    if options.normalize {
+        for point in points.iter_mut() {
+            let norm = point
+                .iter()
+                .map(|value| value * value)
+                .sum::<f32>()
+                .sqrt()
+                .max(f32::EPSILON);
+            point.iter_mut().for_each(|value| *value /= norm);
+        }
+    }
+    if points.is_empty() {
+        return Ok(Clustering {
+            assignments: vec![],
+            num_clusters: 0,
+            exemplars: vec![],
+            silhouette_score: 0.0,
+        });
+    }
+
+    let candidates = match options.num_clusters {
+        Some(0) => {
+            return Err(RustBertError::ValueError(
+                "The number of clusters must be positive".to_string(),
+            ));
+        }
+        Some(num_clusters) => vec![num_clusters.min(points.len())],
+        None => {
+            if options.min_clusters == 0 || options.min_clusters > options.max_clusters {
+                return Err(RustBertError::ValueError(format!(
+                    "Invalid range of number of clusters: {} to {}",
+                    options.min_clusters, options.max_clusters
+                )));
+            }
+            // The silhouette is only defined for 2 to (number of elements - 1) clusters
+            let max_clusters = options.max_clusters.min(points.len().saturating_sub(1));
+            let min_clusters = options.min_clusters.max(2);
+            if min_clusters > max_clusters {
+                vec![options.min_clusters.min(points.len())]
+            } else {
+                (min_clusters..=max_clusters).collect()
+            }
+        }
+    };
+
+    let distances = pairwise_distances(&points);
+    let mut best: Option<(Vec<usize>, f64)> = None;
+    for num_clusters in candidates {
+        let assignments = match options.algorithm {
+            ClusteringAlgorithm::KMeans => kmeans(&points, num_clusters, options.max_iterations),
+            ClusteringAlgorithm::Agglomerative => agglomerative(&distances, num_clusters),
+        };
+        let assignments = relabel(&assignments);
+        let score = silhouette_score(&distances, &assignments);
+        if best
+            .as_ref()
+            .map_or(true, |(_, best_score)| score > *best_score)
+        {
+            best = Some((assignments, score));
+        }
+    }
+    let (assignments, silhouette_score) = best.unwrap();
+    let num_clusters = assignments.iter().max().map_or(0, |max| max + 1);
+    let exemplars = exemplars(&points, &assignments, num_clusters);
+    Ok(Clustering {
+        assignments,
+        num_clusters,
+        exemplars,
+        silhouette_score,
+    })
+}
+
+fn squared_distance(point_1: &[f32], point_2: &[f32]) -> f32 {
+    point_1
+        .iter()
+        .zip(point_2.iter())
+        .map(|(value_1, value_2)| (value_1 - value_2) * (value_1 - value_2))
+        .sum()
+}
+
+fn pairwise_distances(points: &[Vec<f32>]) -> Vec<Vec<f32>> {
+    let mut distances = vec![vec![0f32; points.len()]; points.len()];
+    for i in 0..points.len() {
+        for j in i + 1..points.len() {
+            let distance = squared_distance(&points[i], &points[j]).sqrt();
+            distances[i][j] = distance;
+            distances[j][i] = distance;
+        }
+    }
+    distances
+}
+
+fn compute_centroids(
+    points: &[Vec<f32>],
+    assignments: &[usize],
+    num_clusters: usize,
+) -> Vec<Vec<f32>> {
+    let dim = points[0].len();
+    let mut centroids = vec![vec![0f32; dim]; num_clusters];
+    let mut counts = vec![0usize; num_clusters];
+    for (point, &cluster) in points.iter().zip(assignments.iter()) {
+        counts[cluster] += 1;
+        for (sum, value) in centroids[cluster].iter_mut().zip(point.iter()) {
+            *sum += value;
+        }
+    }
+    for (centroid, count) in centroids.iter_mut().zip(counts.into_iter()) {
+        centroid
+            .iter_mut()
+            .for_each(|value| *value /= count.max(1) as f32);
+    }
+    centroids
+}
+
+fn nearest(point: &[f32], centroids: &[Vec<f32>]) -> usize {
+    centroids
+        .iter()
+        .map(|centroid| squared_distance(point, centroid))
+        .enumerate()
+        .fold((0, f32::INFINITY), |best, (index, distance)| {
+            if distance < best.1 {
+                (index, distance)
+            } else {
+                best
+            }
+        })
+        .0
+}
+
+fn kmeans(points: &[Vec<f32>], num_clusters: usize, max_iterations: usize) -> Vec<usize> {
+    // Farthest-first initialization, starting from the first point
+    let mut centroids = vec![points[0].clone()];
+    let mut min_distances = points
+        .iter()
+        .map(|point| squared_distance(point, &points[0]))
+        .collect::<Vec<_>>();
+    while centroids.len() < num_clusters {
+        let (farthest, _) = min_distances.iter().enumerate().fold(
+            (0, f32::NEG_INFINITY),
+            |best, (index, &distance)| {
+                if distance > best.1 {
+                    (index, distance)
+                } else {
+                    best
+                }
+            },
+        );
+        for (min_distance, point) in min_distances.iter_mut().zip(points.iter()) {
+            *min_distance = min_distance.min(squared_distance(point, &points[farthest]));
+        }
+        centroids.push(points[farthest].clone());
+    }
+
+    let mut assignments = points
+        .iter()
+        .map(|point| nearest(point, &centroids))
+        .collect::<Vec<_>>();
+    for _ in 0..max_iterations {
+        let updated_centroids = centroids_or_previous(
+            &centroids,
+            compute_centroids(points, &assignments, num_clusters),
+            &assignments,
+        );
+        let updated_assignments = points
+            .iter()
+            .map(|point| nearest(point, &updated_centroids))
+            .collect::<Vec<_>>();
+        centroids = updated_centroids;
+        if updated_assignments == assignments {
+            break;
+        }
+        assignments = updated_assignments;
+    }
+    assignments
+}
+
+// Keeps the previous position of the centroids of empty clusters
+fn centroids_or_previous(
+    previous: &[Vec<f32>],
+    updated: Vec<Vec<f32>>,
+    assignments: &[usize],
+) -> Vec<Vec<f32>> {
+    updated
+        .into_iter()
+        .enumerate()
+        .map(|(cluster, centroid)| {
+            if assignments.contains(&cluster) {
+                centroid
+            } else {
+                previous[cluster].clone()
+            }
+        })
+        .collect()
+}
+
+fn agglomerative(distances: &[Vec<f32>], num_clusters: usize) -> Vec<usize> {
+    let num_points = distances.len();
+    let mut cluster_distances = distances.to_vec();
+    let mut sizes = vec![1usize; num_points];
+    let mut active = vec![true; num_points];
+    let mut assignments = (0..num_points).collect::<Vec<_>>();
+    for _ in num_clusters..num_points {
+        let mut closest = (0, 0, f32::INFINITY);
+        for i in (0..num_points).filter(|&i| active[i]) {
+            for j in (i + 1..num_points).filter(|&j| active[j]) {
+                if cluster_distances[i][j] < closest.2 {
+                    closest = (i, j, cluster_distances[i][j]);
+                }
+            }
+        }
+        let (kept, merged, _) = closest;
+        // Average linkage (Lance-Williams update)
+        for other in (0..num_points).filter(|&other| active[other]) {
+            let distance = (sizes[kept] as f32 * cluster_distances[kept][other]
+                + sizes[merged] as f32 * cluster_distances[merged][other])
+                / (sizes[kept] + sizes[merged]) as f32;
+            cluster_distances[kept][other] = distance;
+            cluster_distances[other][kept] = distance;
+        }
+        cluster_distances[kept][kept] = 0.0;
+        sizes[kept] += sizes[merged];
+        active[merged] = false;
+        assignments
+            .iter_mut()
+            .filter(|cluster| **cluster == merged)
+            .for_each(|cluster| *cluster = kept);
+    }
+    assignments
+}
+
+fn relabel(assignments: &[usize]) -> Vec<usize> {
+    let mut labels = HashMap::new();
+    assignments
+        .iter()
+        .map(|cluster| {
+            let next_label = labels.len();
+            *labels.entry(*cluster).or_insert(next_label)
+        })
+        .collect()
+}
+
+fn silhouette_score(distances: &[Vec<f32>], assignments: &[usize]) -> f64 {
+    let num_clusters = assignments.iter().max().map_or(0, |max| max + 1);
+    if num_clusters < 2 {
+        return 0.0;
+    }
+    let mut sizes = vec![0usize; num_clusters];
+    for &cluster in assignments {
+        sizes[cluster] += 1;
+    }
+    let total: f64 = (0..assignments.len())
+        .map(|i| {
+            let own_cluster = assignments[i];
+            if sizes[own_cluster] == 1 {
+                return 0.0;
+            }
+            let mut sums = vec![0f64; num_clusters];
+            for (j, &cluster) in assignments.iter().enumerate() {
+                sums[cluster] += distances[i][j] as f64;
+            }
+            let a = sums[own_cluster] / (sizes[own_cluster] - 1) as f64;
+            let b = (0..num_clusters)
+                .filter(|&cluster| cluster != own_cluster)
+                .map(|cluster| sums[cluster] / sizes[cluster] as f64)
+                .fold(f64::INFINITY, f64::min);
+            if a.max(b) > 0.0 {
+                (b - a) / a.max(b)
+            } else {
+                0.0
+            }
+        })
+        .sum();
+    total / assignments.len() as f64
+}
+
+fn exemplars(points: &[Vec<f32>], assignments: &[usize], num_clusters: usize) -> Vec<usize> {
+    let centroids = compute_centroids(points, assignments, num_clusters);
+    (0..num_clusters)
+        .map(|cluster| {
+            let mut members = assignments
+                .iter()
+                .enumerate()
+                .filter(|(_, assignment)| **assignment == cluster)
+                .map(|(index, _)| (index, squared_distance(&points[index], &centroids[cluster])))
+                .collect::<Vec<_>>();
+            members.sort_by(|(_, distance_1), (_, distance_2)| {
+                distance_1.partial_cmp(distance_2).unwrap()
+            });
+            members[0].0
+        })
+        .collect()
+}
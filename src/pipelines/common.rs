@@ -19,6 +19,7 @@
 use crate::albert::AlbertConfig;
 use crate::bart::BartConfig;
 use crate::bert::BertConfig;
+use crate::common::config::check_token_id;
 use crate::common::error::RustBertError;
 use crate::distilbert::DistilBertConfig;
 use crate::electra::ElectraConfig;
@@ -37,12 +38,14 @@ use rust_tokenizers::vocab::{
     AlbertVocab, BertVocab, Gpt2Vocab, MarianVocab, OpenAiGptVocab, ReformerVocab, RobertaVocab,
     T5Vocab, Vocab, XLMRobertaVocab, XLNetVocab,
 };
-use rust_tokenizers::{TokenIdsWithOffsets, TokenizedInput};
+use rust_tokenizers::{Mask, TokenIdsWithOffsets, TokenizedInput};
 use serde::{Deserialize, Serialize};
+use std::cell::RefCell;
 use std::collections::HashMap;
 use std::path::Path;
+use tch::{Device, Kind, Tensor};
 
-#[derive(Clone, Copy, Serialize, Deserialize, Debug)]
+#[derive(Clone, Copy, Serialize, Deserialize, Debug, PartialEq, Eq)]
 /// # Identifies the type of model
 pub enum ModelType {
     Bart,
@@ -59,6 +62,7 @@ pub enum ModelType {
     GPT2,
     OpenAiGpt,
     Reformer,
+    Splinter,
 }
 
 /// # Abstraction that holds a model configuration, can be of any of the supported models
@@ -111,12 +115,65 @@ pub enum TokenizerOption {
     Reformer(ReformerTokenizer),
 }
 
+#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
+/// # Subword regularization for training-time data augmentation
+/// Samples alternative segmentations of the inputs: each sub-word produced by the tokenizer is split with probability
+/// `dropout` into two pieces of the vocabulary, recursively. For BPE tokenizers (GPT2, RoBERTa, OpenAI GPT) this
+/// reverts the last merges of a token as in BPE-dropout ([BPE-Dropout: Simple and Effective Subword Regularization](https://arxiv.org/abs/1910.13267) Provilkov et al., 2019).
+/// For SentencePiece tokenizers (T5, ALBERT, XLNet, XLM-RoBERTa, Marian, Reformer) it samples segmentations from the
+/// vocabulary pieces, similar to the sampling mode of SentencePiece. Random values are drawn from the `tch` generator
+/// and can be seeded with `tch::manual_seed`.
+pub struct SubwordRegularization {
+    dropout: f64,
+}
+
+impl SubwordRegularization {
+    /// Creates a new subword regularization with the dropout probability provided (between 0 and 1)
+    pub fn new(dropout: f64) -> Result<SubwordRegularization, RustBertError> {
+        if !(0.0..=1.0).contains(&dropout) {
+            return Err(RustBertError::InvalidConfigurationError(format!(
+                "Subword regularization dropout must be between 0 and 1, got {}",
+                dropout
+            )));
+        }
+        Ok(SubwordRegularization { dropout })
+    }
+
+    /// Returns the probability of splitting each sub-word
+    pub fn dropout(&self) -> f64 {
+        self.dropout
+    }
+}
+
+/// Uniform random values in [0, 1) drawn from the `tch` generator by blocks
+struct UniformSampler {
+    values: Vec<f64>,
+}
+
+impl UniformSampler {
+    const BLOCK_SIZE: i64 = 1024;
+
+    fn new() -> UniformSampler {
+        UniformSampler { values: vec![] }
+    }
+
+    fn sample(&mut self) -> f64 {
+        if self.values.is_empty() {
+            let random_values = Tensor::rand(&[Self::BLOCK_SIZE], (Kind::Double, Device::Cpu));
+            self.values = (0..Self::BLOCK_SIZE)
+                .map(|index| random_values.double_value(&[index]))
+                .collect();
+        }
+        self.values.pop().unwrap()
+    }
+}
+
 impl ConfigOption {
     /// Interface method to load a configuration from file
     pub fn from_file<P: AsRef<Path>>(model_type: ModelType, path: P) -> Self {
         match model_type {
             ModelType::Bart => ConfigOption::Bart(BartConfig::from_file(path)),
-            ModelType::Bert | ModelType::Roberta | ModelType::XLMRoberta => {
+            ModelType::Bert | ModelType::Roberta | ModelType::XLMRoberta | ModelType::Splinter => {
                 ConfigOption::Bert(BertConfig::from_file(path))
             }
             ModelType::DistilBert => ConfigOption::DistilBert(DistilBertConfig::from_file(path)),
@@ -132,6 +189,51 @@ impl ConfigOption {
         }
     }
 
+    /// Interface method to load and validate a configuration from file, returning an error if the configuration
+    /// cannot be read or is inconsistent
+    pub fn try_from_file<P: AsRef<Path>>(
+        model_type: ModelType,
+        path: P,
+    ) -> Result<Self, RustBertError> {
+        Ok(match model_type {
+            ModelType::Bart => ConfigOption::Bart(BartConfig::try_from_file(path)?),
+            ModelType::Bert | ModelType::Roberta | ModelType::XLMRoberta | ModelType::Splinter => {
+                ConfigOption::Bert(BertConfig::try_from_file(path)?)
+            }
+            ModelType::DistilBert => {
+                ConfigOption::DistilBert(DistilBertConfig::try_from_file(path)?)
+            }
+            ModelType::Electra => ConfigOption::Electra(ElectraConfig::try_from_file(path)?),
+            ModelType::Marian => ConfigOption::Marian(BartConfig::try_from_file(path)?),
+            ModelType::MobileBert => {
+                ConfigOption::MobileBert(MobileBertConfig::try_from_file(path)?)
+            }
+            ModelType::T5 => ConfigOption::T5(T5Config::try_from_file(path)?),
+            ModelType::Albert => ConfigOption::Albert(AlbertConfig::try_from_file(path)?),
+            ModelType::XLNet => ConfigOption::XLNet(XLNetConfig::try_from_file(path)?),
+            ModelType::GPT2 => ConfigOption::GPT2(Gpt2Config::try_from_file(path)?),
+            ModelType::OpenAiGpt => ConfigOption::GPT2(Gpt2Config::try_from_file(path)?),
+            ModelType::Reformer => ConfigOption::Reformer(ReformerConfig::try_from_file(path)?),
+        })
+    }
+
+    /// Returns the size of the vocabulary of the model (size of the word embeddings)
+    pub fn get_vocab_size(&self) -> i64 {
+        match self {
+            Self::Bart(config) => config.vocab_size,
+            Self::Bert(config) => config.vocab_size,
+            Self::DistilBert(config) => config.vocab_size,
+            Self::Electra(config) => config.vocab_size,
+            Self::Marian(config) => config.vocab_size,
+            Self::MobileBert(config) => config.vocab_size,
+            Self::T5(config) => config.vocab_size,
+            Self::Albert(config) => config.vocab_size,
+            Self::XLNet(config) => config.vocab_size,
+            Self::GPT2(config) => config.vocab_size,
+            Self::Reformer(config) => config.vocab_size,
+        }
+    }
+
     pub fn get_label_mapping(self) -> HashMap<i64, String> {
         match self {
             Self::Bart(config) => config
@@ -184,7 +286,8 @@ impl TokenizerOption {
             ModelType::Bert
             | ModelType::DistilBert
             | ModelType::Electra
-            | ModelType::MobileBert => {
+            | ModelType::MobileBert
+            | ModelType::Splinter => {
                 if add_prefix_space.is_some() {
                     return Err(RustBertError::InvalidConfigurationError(
                         format!("Optional input `add_prefix_space` set to value {} but cannot be used by {:?}",
@@ -336,6 +439,82 @@ impl TokenizerOption {
         }
     }
 
+    /// Returns the type of tokenizer expected by a model type, as the `ModelType` of the matching `TokenizerOption`
+    /// (e.g. `ModelType::Roberta` for BART models)
+    pub fn expected_tokenizer_type(model_type: ModelType) -> ModelType {
+        match model_type {
+            ModelType::Bert
+            | ModelType::DistilBert
+            | ModelType::Electra
+            | ModelType::MobileBert
+            | ModelType::Splinter => ModelType::Bert,
+            ModelType::Roberta | ModelType::Bart => ModelType::Roberta,
+            ModelType::XLMRoberta => ModelType::XLMRoberta,
+            ModelType::Marian => ModelType::Marian,
+            ModelType::T5 => ModelType::T5,
+            ModelType::Albert => ModelType::Albert,
+            ModelType::XLNet => ModelType::XLNet,
+            ModelType::GPT2 => ModelType::GPT2,
+            ModelType::OpenAiGpt => ModelType::OpenAiGpt,
+            ModelType::Reformer => ModelType::Reformer,
+        }
+    }
+
+    /// Returns the size of the vocabulary of the tokenizer (largest token id + 1, including special tokens)
+    pub fn vocab_size(&self) -> i64 {
+        match *self {
+            Self::Bert(ref tokenizer) => vocab_size(MultiThreadedTokenizer::vocab(tokenizer)),
+            Self::Roberta(ref tokenizer) => vocab_size(MultiThreadedTokenizer::vocab(tokenizer)),
+            Self::XLMRoberta(ref tokenizer) => vocab_size(MultiThreadedTokenizer::vocab(tokenizer)),
+            Self::Marian(ref tokenizer) => vocab_size(MultiThreadedTokenizer::vocab(tokenizer)),
+            Self::T5(ref tokenizer) => vocab_size(MultiThreadedTokenizer::vocab(tokenizer)),
+            Self::Albert(ref tokenizer) => vocab_size(MultiThreadedTokenizer::vocab(tokenizer)),
+            Self::XLNet(ref tokenizer) => vocab_size(MultiThreadedTokenizer::vocab(tokenizer)),
+            Self::GPT2(ref tokenizer) => vocab_size(MultiThreadedTokenizer::vocab(tokenizer)),
+            Self::OpenAiGpt(ref tokenizer) => vocab_size(MultiThreadedTokenizer::vocab(tokenizer)),
+            Self::Reformer(ref tokenizer) => vocab_size(MultiThreadedTokenizer::vocab(tokenizer)),
+        }
+    }
+
+    /// Checks that the tokenizer can be used with a model, returning an `InvalidConfigurationError` if:
+    /// - the tokenizer type does not match the tokenizer expected by the model architecture (e.g. a SentencePiece
+    /// tokenizer for a BERT model)
+    /// - the tokenizer vocabulary is larger than the model vocabulary
+    /// - a special token id of the tokenizer is not a valid model token id
+    ///
+    /// # Arguments
+    ///
+    /// * `model_type` - `ModelType` of the model the tokenizer is used with
+    /// * `model_vocab_size` - size of the model vocabulary (`vocab_size` of its configuration)
+    pub fn check_model_compatibility(
+        &self,
+        model_type: ModelType,
+        model_vocab_size: i64,
+    ) -> Result<(), RustBertError> {
+        let expected_type = Self::expected_tokenizer_type(model_type);
+        if self.model_type() != expected_type {
+            return Err(RustBertError::InvalidConfigurationError(format!(
+                "A {:?} model requires a {:?} tokenizer, got a {:?} tokenizer",
+                model_type,
+                expected_type,
+                self.model_type()
+            )));
+        }
+        let tokenizer_vocab_size = self.vocab_size();
+        if tokenizer_vocab_size > model_vocab_size {
+            return Err(RustBertError::InvalidConfigurationError(format!(
+                "The tokenizer vocabulary ({} tokens) is larger than the model vocabulary `vocab_size` ({}), \
+                the tokenizer files may not match the model weights",
+                tokenizer_vocab_size, model_vocab_size
+            )));
+        }
+        check_token_id("unk_token_id", Some(self.get_unk_id()), model_vocab_size)?;
+        check_token_id("pad_token_id", self.get_pad_id(), model_vocab_size)?;
+        check_token_id("sep_token_id", self.get_sep_id(), model_vocab_size)?;
+        check_token_id("bos_token_id", self.get_bos_id(), model_vocab_size)?;
+        check_token_id("eos_token_id", self.get_eos_id(), model_vocab_size)
+    }
+
     /// Interface method
     pub fn encode_list(
         &self,
@@ -794,4 +973,489 @@ impl TokenizerOption {
             Self::Reformer(_) => None,
         }
     }
+
+    /// Returns the id of the beginning of sequence token, if defined by the vocabulary
+    pub fn get_bos_id(&self) -> Option<i64> {
+        match *self {
+            Self::Roberta(ref tokenizer) => MultiThreadedTokenizer::vocab(tokenizer)
+                .special_values
+                .get(RobertaVocab::bos_value())
+                .copied(),
+            Self::XLMRoberta(ref tokenizer) => MultiThreadedTokenizer::vocab(tokenizer)
+                .special_values
+                .get(XLMRobertaVocab::bos_value())
+                .copied(),
+            Self::XLNet(ref tokenizer) => MultiThreadedTokenizer::vocab(tokenizer)
+                .special_values
+                .get(XLNetVocab::bos_value())
+                .copied(),
+            Self::GPT2(ref tokenizer) => MultiThreadedTokenizer::vocab(tokenizer)
+                .special_values
+                .get(Gpt2Vocab::bos_value())
+                .copied(),
+            Self::Bert(_) => None,
+            Self::Marian(_) => None,
+            Self::T5(_) => None,
+            Self::Albert(_) => None,
+            Self::OpenAiGpt(_) => None,
+            Self::Reformer(_) => None,
+        }
+    }
+
+    /// Returns the id of the end of sequence token, if defined by the vocabulary
+    pub fn get_eos_id(&self) -> Option<i64> {
+        match *self {
+            Self::Roberta(ref tokenizer) => MultiThreadedTokenizer::vocab(tokenizer)
+                .special_values
+                .get(RobertaVocab::eos_value())
+                .copied(),
+            Self::XLMRoberta(ref tokenizer) => MultiThreadedTokenizer::vocab(tokenizer)
+                .special_values
+                .get(XLMRobertaVocab::eos_value())
+                .copied(),
+            Self::Marian(ref tokenizer) => MultiThreadedTokenizer::vocab(tokenizer)
+                .special_values
+                .get(MarianVocab::eos_value())
+                .copied(),
+            Self::T5(ref tokenizer) => MultiThreadedTokenizer::vocab(tokenizer)
+                .special_values
+                .get(T5Vocab::eos_value())
+                .copied(),
+            Self::XLNet(ref tokenizer) => MultiThreadedTokenizer::vocab(tokenizer)
+                .special_values
+                .get(XLNetVocab::eos_value())
+                .copied(),
+            Self::GPT2(ref tokenizer) => MultiThreadedTokenizer::vocab(tokenizer)
+                .special_values
+                .get(Gpt2Vocab::eos_value())
+                .copied(),
+            Self::Reformer(ref tokenizer) => MultiThreadedTokenizer::vocab(tokenizer)
+                .special_values
+                .get(ReformerVocab::eos_value())
+                .copied(),
+            Self::Bert(_) => None,
+            Self::Albert(_) => None,
+            Self::OpenAiGpt(_) => None,
+        }
+    }
+
+    /// Returns the ids of the tokens of the vocabulary starting with the token provided (including the token
+    /// itself), comparing the raw vocabulary entries (e.g. byte-level representation for GPT2)
+    ///
+    /// # Arguments
+    ///
+    /// * `token_id` - id of the prefix token
+    pub fn get_prefixed_token_ids(&self, token_id: i64) -> Vec<i64> {
+        match *self {
+            Self::Bert(ref tokenizer) => {
+                prefixed_token_ids(MultiThreadedTokenizer::vocab(tokenizer), token_id)
+            }
+            Self::Roberta(ref tokenizer) => {
+                prefixed_token_ids(MultiThreadedTokenizer::vocab(tokenizer), token_id)
+            }
+            Self::XLMRoberta(ref tokenizer) => {
+                prefixed_token_ids(MultiThreadedTokenizer::vocab(tokenizer), token_id)
+            }
+            Self::Marian(ref tokenizer) => {
+                prefixed_token_ids(MultiThreadedTokenizer::vocab(tokenizer), token_id)
+            }
+            Self::T5(ref tokenizer) => {
+                prefixed_token_ids(MultiThreadedTokenizer::vocab(tokenizer), token_id)
+            }
+            Self::Albert(ref tokenizer) => {
+                prefixed_token_ids(MultiThreadedTokenizer::vocab(tokenizer), token_id)
+            }
+            Self::XLNet(ref tokenizer) => {
+                prefixed_token_ids(MultiThreadedTokenizer::vocab(tokenizer), token_id)
+            }
+            Self::GPT2(ref tokenizer) => {
+                prefixed_token_ids(MultiThreadedTokenizer::vocab(tokenizer), token_id)
+            }
+            Self::OpenAiGpt(ref tokenizer) => {
+                prefixed_token_ids(MultiThreadedTokenizer::vocab(tokenizer), token_id)
+            }
+            Self::Reformer(ref tokenizer) => {
+                prefixed_token_ids(MultiThreadedTokenizer::vocab(tokenizer), token_id)
+            }
+        }
+    }
+
+    /// Returns the mapping from token ids to the raw vocabulary entries (e.g. byte-level representation for GPT2)
+    pub fn get_vocab_indices(&self) -> HashMap<i64, String> {
+        match *self {
+            Self::Bert(ref tokenizer) => MultiThreadedTokenizer::vocab(tokenizer).indices().clone(),
+            Self::Roberta(ref tokenizer) => {
+                MultiThreadedTokenizer::vocab(tokenizer).indices().clone()
+            }
+            Self::XLMRoberta(ref tokenizer) => {
+                MultiThreadedTokenizer::vocab(tokenizer).indices().clone()
+            }
+            Self::Marian(ref tokenizer) => {
+                MultiThreadedTokenizer::vocab(tokenizer).indices().clone()
+            }
+            Self::T5(ref tokenizer) => MultiThreadedTokenizer::vocab(tokenizer).indices().clone(),
+            Self::Albert(ref tokenizer) => {
+                MultiThreadedTokenizer::vocab(tokenizer).indices().clone()
+            }
+            Self::XLNet(ref tokenizer) => {
+                MultiThreadedTokenizer::vocab(tokenizer).indices().clone()
+            }
+            Self::GPT2(ref tokenizer) => MultiThreadedTokenizer::vocab(tokenizer).indices().clone(),
+            Self::OpenAiGpt(ref tokenizer) => {
+                MultiThreadedTokenizer::vocab(tokenizer).indices().clone()
+            }
+            Self::Reformer(ref tokenizer) => {
+                MultiThreadedTokenizer::vocab(tokenizer).indices().clone()
+            }
+        }
+    }
+
+    /// Tokenizes the text provided, sampling an alternative segmentation of the sub-words
+    ///
+    /// # Arguments
+    ///
+    /// * `text` - text to tokenize
+    /// * `regularization` - `SubwordRegularization` defining the probability of splitting each sub-word
+    pub fn tokenize_with_regularization(
+        &self,
+        text: &str,
+        regularization: &SubwordRegularization,
+    ) -> Vec<String> {
+        let mut sampler = UniformSampler::new();
+        let unk_id = self.get_unk_id();
+        self.tokenize(text)
+            .iter()
+            .flat_map(|token| {
+                self.sample_subword_split(token, regularization.dropout, unk_id, &mut sampler)
+            })
+            .collect()
+    }
+
+    /// Encodes a list of texts, sampling an alternative segmentation of the sub-words of each text. The sequences are
+    /// truncated from the end to `max_len` tokens (including special tokens). Offsets are not available for the sampled
+    /// segmentations.
+    ///
+    /// # Arguments
+    ///
+    /// * `text_list` - texts to encode
+    /// * `max_len` - maximum length of the encoded sequences
+    /// * `regularization` - `SubwordRegularization` defining the probability of splitting each sub-word
+    pub fn encode_list_with_regularization(
+        &self,
+        text_list: &[&str],
+        max_len: usize,
+        regularization: &SubwordRegularization,
+    ) -> Vec<TokenizedInput> {
+        let num_special_tokens = self
+            .build_input_with_special_tokens(
+                TokenIdsWithOffsets {
+                    ids: vec![],
+                    offsets: vec![],
+                    reference_offsets: vec![],
+                    masks: vec![],
+                },
+                None,
+            )
+            .token_ids
+            .len();
+        let max_tokens = max_len.saturating_sub(num_special_tokens);
+        text_list
+            .iter()
+            .map(|text| {
+                let mut ids = self
+                    .convert_tokens_to_ids(self.tokenize_with_regularization(text, regularization));
+                let num_truncated_tokens = ids.len().saturating_sub(max_tokens);
+                ids.truncate(max_tokens);
+                let num_tokens = ids.len();
+                let mut tokenized_input = self.build_input_with_special_tokens(
+                    TokenIdsWithOffsets {
+                        ids,
+                        offsets: vec![None; num_tokens],
+                        reference_offsets: vec![vec![]; num_tokens],
+                        masks: vec![Mask::None; num_tokens],
+                    },
+                    None,
+                );
+                tokenized_input.num_truncated_tokens = num_truncated_tokens;
+                tokenized_input
+            })
+            .collect()
+    }
+
+    fn sample_subword_split(
+        &self,
+        token: &str,
+        dropout: f64,
+        unk_id: i64,
+        sampler: &mut UniformSampler,
+    ) -> Vec<String> {
+        // WordPiece marks the sub-words continuing a word with a prefix, which must be kept on the right piece
+        let continuation_prefix = match *self {
+            Self::Bert(_) => "##",
+            _ => "",
+        };
+        let (prefix, body) =
+            if !continuation_prefix.is_empty() && token.starts_with(continuation_prefix) {
+                (continuation_prefix, &token[continuation_prefix.len()..])
+            } else {
+                ("", token)
+            };
+        if (body.chars().count() < 2) | (sampler.sample() >= dropout) {
+            return vec![token.to_string()];
+        }
+        if self.convert_tokens_to_ids(&[token])[0] == unk_id {
+            return vec![token.to_string()];
+        }
+        let candidates = body
+            .char_indices()
+            .skip(1)
+            .map(|(position, _)| {
+                (
+                    format!("{}{}", prefix, &body[..position]),
+                    format!("{}{}", continuation_prefix, &body[position..]),
+                )
+            })
+            .filter(|(left, right)| {
+                self.convert_tokens_to_ids(&[left.as_str(), right.as_str()])
+                    .iter()
+                    .all(|&id| id != unk_id)
+            })
+            .collect::<Vec<(String, String)>>();
+        if candidates.is_empty() {
+            return vec![token.to_string()];
+        }
+        let candidate_index =
+            ((sampler.sample() * candidates.len() as f64) as usize).min(candidates.len() - 1);
+        let (left, right) = &candidates[candidate_index];
+        let mut output = self.sample_subword_split(left, dropout, unk_id, sampler);
+        output.extend(self.sample_subword_split(right, dropout, unk_id, sampler));
+        output
+    }
+}
+
+/// Prepares (e.g. tokenizes) and processes the batches of an input in order. The batches of texts are usually
+/// tokenized on the CPU and processed by a model on the GPU: with the `parallel-tokenization` feature, the next batch
+/// is prepared on the rayon thread pool while the current batch is processed on the calling thread, keeping both
+/// devices busy. Without the feature, the batches are prepared and processed in turn.
+///
+/// This function should not be called from within the rayon thread pool (e.g. from a parallel iterator), as the
+/// preparation of the batches would compete with the processing for the worker threads.
+///
+/// # Arguments
+///
+/// * `inputs` - texts to process
+/// * `batch_size` - number of texts per batch
+/// * `prepare` - preparation of a batch, run in parallel with the processing of the previous batch
+/// * `process` - processing of a batch and of its preparation, stopping at the first error
+///
+/// # Returns
+///
+/// * `Vec<R>` outputs of `process` for each batch
+pub fn prefetch_batches<'a, T, R, P, F>(
+    inputs: &[&'a str],
+    batch_size: usize,
+    prepare: P,
+    mut process: F,
+) -> Result<Vec<R>, RustBertError>
+where
+    P: Fn(&[&'a str]) -> T + Sync,
+    T: Send,
+    F: FnMut(&[&'a str], T) -> Result<R, RustBertError>,
+{
+    let batch_size = batch_size.max(1);
+    #[cfg(feature = "parallel-tokenization")]
+    {
+        let (sender, receiver) = std::sync::mpsc::sync_channel(1);
+        let prepare = &prepare;
+        rayon::in_place_scope(|scope| {
+            scope.spawn(move |_| {
+                for batch in inputs.chunks(batch_size) {
+                    if sender.send(prepare(batch)).is_err() {
+                        break;
+                    }
+                }
+            });
+            // Dropping the receiver on error stops the preparation of the remaining batches
+            inputs
+                .chunks(batch_size)
+                .zip(receiver)
+                .map(|(batch, prepared)| process(batch, prepared))
+                .collect()
+        })
+    }
+    #[cfg(not(feature = "parallel-tokenization"))]
+    {
+        inputs
+            .chunks(batch_size)
+            .map(|batch| process(batch, prepare(batch)))
+            .collect()
+    }
+}
+
+/// Moves an input tensor built on the CPU to the device of a model. With `pinned_memory`, tensors moved to a CUDA
+/// device are first copied to page-locked (pinned) host memory and transferred asynchronously, so that the copy
+/// overlaps with the computations already queued on the device. Pinned memory cannot be swapped out and is allocated
+/// in addition to the pageable host buffers, which is why the pipelines only use it when configured to.
+///
+/// # Arguments
+///
+/// * `tensor` - input tensor, on the CPU
+/// * `device` - target device
+/// * `pinned_memory` - use a pinned buffer and a non-blocking copy for CUDA devices
+pub fn to_device(tensor: &Tensor, device: Device, pinned_memory: bool) -> Tensor {
+    match device {
+        Device::Cuda(_) if pinned_memory => {
+            tensor.pin_memory().to4(device, tensor.kind(), true, false)
+        }
+        _ => tensor.to(device),
+    }
+}
+
+/// # Batch of model inputs
+/// Built on the CPU by the pre-processing of the pipelines for the device of the model. The tensors are moved to the
+/// device (through pinned memory if requested, see `to_device`) and cast the first time they are accessed, and the
+/// device tensors are returned by the subsequent accesses, so that the pipelines do not have to track where each input
+/// tensor lives.
+#[derive(Debug)]
+pub struct TensorBatch {
+    input_ids: RefCell<Tensor>,
+    attention_mask: Option<RefCell<Tensor>>,
+    token_type_ids: Option<RefCell<Tensor>>,
+    device: Device,
+    pinned_memory: bool,
+}
+
+impl TensorBatch {
+    /// Creates a batch from input ids of shape (*batch size*, *sequence length*), for a model on `device`
+    pub fn new(input_ids: Tensor, device: Device) -> TensorBatch {
+        TensorBatch {
+            input_ids: RefCell::new(input_ids),
+            attention_mask: None,
+            token_type_ids: None,
+            device,
+            pinned_memory: false,
+        }
+    }
+
+    /// Creates a batch from sequences of token ids, padded on the right with `pad_id` to the longest sequence. The
+    /// attention mask is set to 1 for the tokens of the sequences and 0 for the padding.
+    ///
+    /// # Arguments
+    ///
+    /// * `token_ids` - token ids of each sequence of the batch
+    /// * `pad_id` - padding token id
+    /// * `device` - device of the model the batch is meant for
+    pub fn from_token_ids(token_ids: &[Vec<i64>], pad_id: i64, device: Device) -> TensorBatch {
+        let max_len = token_ids.iter().map(Vec::len).max().unwrap_or(0);
+        if token_ids.is_empty() {
+            let empty = Tensor::zeros(&[0, 0], (Kind::Int64, Device::Cpu));
+            return TensorBatch::new(empty.copy(), device).with_attention_mask(empty);
+        }
+        let (input_ids, attention_mask): (Vec<Tensor>, Vec<Tensor>) = token_ids
+            .iter()
+            .map(|sequence| {
+                let mut padded_ids = sequence.clone();
+                padded_ids.resize(max_len, pad_id);
+                let mut mask = vec![1i64; sequence.len()];
+                mask.resize(max_len, 0);
+                (Tensor::of_slice(&padded_ids), Tensor::of_slice(&mask))
+            })
+            .unzip();
+        TensorBatch::new(Tensor::stack(&input_ids, 0), device)
+            .with_attention_mask(Tensor::stack(&attention_mask, 0))
+    }
+
+    /// Sets the attention mask of the batch
+    pub fn with_attention_mask(mut self, attention_mask: Tensor) -> TensorBatch {
+        self.attention_mask = Some(RefCell::new(attention_mask));
+        self
+    }
+
+    /// Sets the token type ids (segment ids) of the batch
+    pub fn with_token_type_ids(mut self, token_type_ids: Tensor) -> TensorBatch {
+        self.token_type_ids = Some(RefCell::new(token_type_ids));
+        self
+    }
+
+    /// Copies the tensors to CUDA devices through pinned memory with non-blocking transfers
+    pub fn with_pinned_memory(mut self, pinned_memory: bool) -> TensorBatch {
+        self.pinned_memory = pinned_memory;
+        self
+    }
+
+    /// Device the tensors are moved to when accessed
+    pub fn device(&self) -> Device {
+        self.device
+    }
+
+    /// Number of sequences in the batch
+    pub fn batch_size(&self) -> i64 {
+        self.input_ids.borrow().size()[0]
+    }
+
+    /// Returns the input ids on the device, as `Kind::Int64`
+    pub fn input_ids(&self) -> Tensor {
+        self.on_device(&self.input_ids, Some(Kind::Int64))
+    }
+
+    /// Returns the attention mask on the device, if any
+    pub fn attention_mask(&self) -> Option<Tensor> {
+        self.attention_mask
+            .as_ref()
+            .map(|attention_mask| self.on_device(attention_mask, None))
+    }
+
+    /// Returns the attention mask on the device, cast to `kind` (e.g. `Kind::Bool` or the kind of the model weights).
+    /// The cast is performed on the device for each call.
+    pub fn attention_mask_as(&self, kind: Kind) -> Option<Tensor> {
+        self.attention_mask().map(|attention_mask| {
+            if attention_mask.kind() != kind {
+                attention_mask.to_kind(kind)
+            } else {
+                attention_mask
+            }
+        })
+    }
+
+    /// Returns the token type ids on the device, as `Kind::Int64`, if any
+    pub fn token_type_ids(&self) -> Option<Tensor> {
+        self.token_type_ids
+            .as_ref()
+            .map(|token_type_ids| self.on_device(token_type_ids, Some(Kind::Int64)))
+    }
+
+    /// Moves (and casts) a tensor of the batch to the device on its first access, replacing the host tensor
+    fn on_device(&self, tensor: &RefCell<Tensor>, kind: Option<Kind>) -> Tensor {
+        let mut tensor = tensor.borrow_mut();
+        if let Some(kind) = kind {
+            if tensor.kind() != kind {
+                *tensor = tensor.to_kind(kind);
+            }
+        }
+        if tensor.device() != self.device {
+            *tensor = to_device(&tensor, self.device, self.pinned_memory);
+        }
+        tensor.shallow_clone()
+    }
+}
+
+fn vocab_size<V: Vocab>(vocab: &V) -> i64 {
+    vocab
+        .indices()
+        .keys()
+        .chain(vocab.values().values())
+        .max()
+        .map_or(0, |max_id| max_id + 1)
+}
+
+fn prefixed_token_ids<V: Vocab>(vocab: &V, token_id: i64) -> Vec<i64> {
+    match vocab.indices().get(&token_id) {
+        Some(prefix) => vocab
+            .values()
+            .iter()
+            .filter(|(token, _)| token.starts_with(prefix.as_str()))
+            .map(|(_, id)| *id)
+            .collect(),
+        None => vec![token_id],
+    }
 }
@@ -51,9 +51,14 @@ use crate::gpt2::{
 };
 use crate::pipelines::common::{ModelType, TokenizerOption};
 use crate::pipelines::generation_utils::private_generation_utils::PrivateLanguageGenerator;
-use crate::pipelines::generation_utils::{GPT2Generator, GenerateConfig, LanguageGenerator};
+use crate::pipelines::generation_utils::{
+    GPT2Generator, GenerateConfig, GenerationUsage, LanguageGenerator, PrefixAllowedTokensFn,
+    StoppingCriteria, StreamedToken,
+};
+use crate::pipelines::grammar::Grammar;
 use itertools::Itertools;
 use std::collections::HashMap;
+use std::sync::Arc;
 use tch::{Device, Tensor};
 use uuid::Uuid;
 
@@ -75,20 +80,36 @@ pub struct ConversationConfig {
     pub min_length: i64,
     /// Maximum sequence length (default: 20)
     pub max_length: i64,
+    /// Maximum number of generated tokens, excluding the prompt. Takes precedence over `max_length` when set (default: None)
+    pub max_new_tokens: Option<i64>,
+    /// Minimum number of generated tokens, excluding the prompt. Takes precedence over `min_length` when set (default: None)
+    pub min_new_tokens: Option<i64>,
     /// Minimum free length available for generated responses (default: 32)
     pub min_length_for_response: i64,
     /// Sampling flag. If true, will perform top-k and/or nucleus sampling on generated tokens, otherwise greedy (deterministic) decoding (default: true)
     pub do_sample: bool,
+    /// Seed of the sampling. When set, the sampled tokens are drawn from a generator seeded at each call instead of the `tch` random state, so that sampled outputs are reproducible across runs and threads for the same inputs (default: None)
+    pub seed: Option<u64>,
     /// Early stopping flag indicating if the beam search should stop as soon as `num_beam` hypotheses have been generated (default: false)
     pub early_stopping: bool,
     /// Number of beams for beam search (default: 5)
     pub num_beams: i64,
+    /// Number of groups of beams for [diverse beam search, Vijayakumar et al.](https://arxiv.org/abs/1610.02424). `num_beams` must be a multiple of the number of groups (default: 1)
+    pub num_beam_groups: i64,
+    /// Penalty subtracted from the scores of the tokens selected by the previous groups of beams at the same step, for diverse beam search (default: 0.0)
+    pub diversity_penalty: f64,
     /// Temperature setting. Values higher than 1 will improve originality at the risk of reducing relevance (default: 1.0)
     pub temperature: f64,
     /// Top_k values for sampling tokens. Value higher than 0 will enable the feature (default: 0)
     pub top_k: i64,
     /// Top_p value for [Nucleus sampling, Holtzman et al.](http://arxiv.org/abs/1904.09751). Keep top tokens until cumulative probability reaches top_p (default: 0.9)
     pub top_p: f64,
+    /// Probability mass of [typical decoding, Meister et al.](https://arxiv.org/abs/2202.00666): only the tokens with an information content closest to the entropy of the distribution are sampled, up to this cumulative probability. Values lower than 1 enable the feature (default: 1.0)
+    pub typical_p: f64,
+    /// Cutoff of [epsilon sampling, Hewitt et al.](https://arxiv.org/abs/2210.15191): tokens with a probability lower than the cutoff are not sampled. Values higher than 0 enable the feature, typically between 3e-4 and 9e-4 (default: 0.0)
+    pub epsilon_cutoff: f64,
+    /// Cutoff of [eta sampling, Hewitt et al.](https://arxiv.org/abs/2210.15191): tokens with a probability lower than `min(eta_cutoff, sqrt(eta_cutoff) * exp(-entropy))` are not sampled. Values higher than 0 enable the feature, typically between 3e-4 and 2e-3 (default: 0.0)
+    pub eta_cutoff: f64,
     /// Repetition penalty (mostly useful for CTRL decoders). Values higher than 1 will penalize tokens that have been already generated. (default: 1.0)
     pub repetition_penalty: f64,
     /// Exponential penalty based on the length of the hypotheses generated (default: 1.0)
@@ -97,6 +118,32 @@ pub struct ConversationConfig {
     pub no_repeat_ngram_size: i64,
     /// Number of sequences to return for each prompt text (default: 1)
     pub num_return_sequences: i64,
+    /// Token ids allowed at each generation step, restricting the vocabulary of the generated sequences (default: None)
+    pub allowed_token_ids: Option<Vec<i64>>,
+    /// Function returning the token ids allowed at the next step given the prompt index and the ids generated so far (default: None)
+    pub prefix_allowed_tokens_fn: Option<PrefixAllowedTokensFn>,
+    /// Criteria ending the generation of a sequence before the end of sequence token, e.g. `StopSequences` stopping on user-provided strings or token sequences (default: None)
+    pub stopping_criteria: Option<Arc<dyn StoppingCriteria>>,
+    /// Token sequences that must all appear in the generated sequences (e.g. the tokens of required terms), enforced by a constrained beam search (default: None)
+    pub force_words_ids: Option<Vec<Vec<i64>>>,
+    /// Token sequences that may not appear in the generated sequences (e.g. profanity or personal information markers): the last token of a sequence is banned whenever its previous tokens have just been generated (default: None)
+    pub bad_words_ids: Option<Vec<Vec<i64>>>,
+    /// Grammar the generated text must follow (e.g. `Grammar::from_json_schema` for structured outputs), enforced by masking the tokens that cannot extend the text into a valid prefix of the grammar (default: None)
+    pub grammar: Option<Arc<Grammar>>,
+    /// Bias added to the scores of the tokens present in the source (prompt) at each generation step, favouring copying from the input over hallucinated tokens (default: 0.0)
+    pub source_copy_bias: f64,
+    /// Size of the n-grams of the source (encoder input) that may not be copied in the generated sequences, for encoder-decoder models (default: 0)
+    pub encoder_no_repeat_ngram_size: i64,
+    /// Multiplier of the DRY ("don't repeat yourself") penalty applied to tokens extending a repetition of a previous sequence (default: 0.0, disabled)
+    pub dry_multiplier: f64,
+    /// Base of the DRY penalty, growing exponentially with the length of the repeated sequence (default: 1.75)
+    pub dry_base: f64,
+    /// Length of the repeated sequences not penalized by the DRY penalty (default: 2)
+    pub dry_allowed_length: i64,
+    /// Token ids interrupting the matching of repeated sequences for the DRY penalty, e.g. newlines or punctuation (default: None)
+    pub dry_sequence_breakers: Option<Vec<i64>>,
+    /// Remove the last token of the prompts and constrain the first generated token to start with it, for decoder-only models. Avoids starting the generation with a broken word when the prompt ends mid-token (default: false)
+    pub token_healing: bool,
     /// Device to place the model on (default: CUDA/GPU when available)
     pub device: Device,
 }
@@ -119,17 +166,38 @@ impl Default for ConversationConfig {
             )),
             min_length: 0,
             max_length: 1000,
+            max_new_tokens: None,
+            min_new_tokens: None,
             min_length_for_response: 32,
             do_sample: true,
+            seed: None,
             early_stopping: false,
             num_beams: 1,
+            num_beam_groups: 1,
+            diversity_penalty: 0.0,
             temperature: 1.0,
             top_k: 50,
             top_p: 0.9,
+            typical_p: 1.0,
+            epsilon_cutoff: 0.0,
+            eta_cutoff: 0.0,
             repetition_penalty: 1.0,
             length_penalty: 1.0,
             no_repeat_ngram_size: 0,
             num_return_sequences: 1,
+            allowed_token_ids: None,
+            prefix_allowed_tokens_fn: None,
+            stopping_criteria: None,
+            force_words_ids: None,
+            bad_words_ids: None,
+            grammar: None,
+            source_copy_bias: 0.0,
+            encoder_no_repeat_ngram_size: 0,
+            dry_multiplier: 0.0,
+            dry_base: 1.75,
+            dry_allowed_length: 2,
+            dry_sequence_breakers: None,
+            token_healing: false,
             device: Device::cuda_if_available(),
         }
     }
@@ -144,16 +212,43 @@ impl From<ConversationConfig> for GenerateConfig {
             vocab_resource: config.vocab_resource,
             min_length: config.min_length,
             max_length: config.max_length,
+            max_new_tokens: config.max_new_tokens,
+            min_new_tokens: config.min_new_tokens,
             do_sample: config.do_sample,
+            seed: config.seed,
             early_stopping: config.early_stopping,
             num_beams: config.num_beams,
+            num_beam_groups: config.num_beam_groups,
+            diversity_penalty: config.diversity_penalty,
             temperature: config.temperature,
             top_k: config.top_k,
             top_p: config.top_p,
+            typical_p: config.typical_p,
+            epsilon_cutoff: config.epsilon_cutoff,
+            eta_cutoff: config.eta_cutoff,
             repetition_penalty: config.repetition_penalty,
             length_penalty: config.length_penalty,
             no_repeat_ngram_size: config.no_repeat_ngram_size,
             num_return_sequences: config.num_return_sequences,
+            allowed_token_ids: config.allowed_token_ids,
+            prefix_allowed_tokens_fn: config.prefix_allowed_tokens_fn,
+            stopping_criteria: config.stopping_criteria,
+            force_words_ids: config.force_words_ids,
+            bad_words_ids: config.bad_words_ids,
+            grammar: config.grammar,
+            source_copy_bias: config.source_copy_bias,
+            encoder_no_repeat_ngram_size: config.encoder_no_repeat_ngram_size,
+            dry_multiplier: config.dry_multiplier,
+            dry_base: config.dry_base,
+            dry_allowed_length: config.dry_allowed_length,
+            dry_sequence_breakers: config.dry_sequence_breakers,
+            token_healing: config.token_healing,
+            echo_prompt: true,
+            bos_token_id: None,
+            eos_token_ids: None,
+            pad_token_id: None,
+            decoder_start_token_id: None,
+            cache_check_tolerance: None,
             device: config.device,
         }
     }
@@ -714,6 +809,62 @@ impl ConversationOption {
             }
         }
     }
+
+    /// Interface method to generate_from_ids_and_past() of the particular models, returning the usage statistics
+    /// of the generation
+    pub fn generate_from_ids_and_past_with_usage(
+        &self,
+        input_ids: Tensor,
+        attention_mask: Option<Tensor>,
+    ) -> (Vec<Vec<i64>>, GenerationUsage) {
+        match *self {
+            Self::GPT2(ref model) => {
+                let (generated, _, usage, _) = model.generate_with_encoder_output(
+                    input_ids,
+                    attention_mask,
+                    None,
+                    None,
+                    None,
+                    None,
+                    None,
+                    None,
+                    None,
+                    None,
+                    false,
+                );
+                (generated, usage)
+            }
+        }
+    }
+
+    /// Interface method to generate_from_ids_with_callback() of the particular models.
+    pub fn generate_from_ids_with_callback<F>(
+        &self,
+        input_ids: Tensor,
+        attention_mask: Option<Tensor>,
+        callback: F,
+    ) -> Result<(Vec<Vec<i64>>, GenerationUsage), RustBertError>
+    where
+        F: FnMut(StreamedToken),
+    {
+        match *self {
+            Self::GPT2(ref model) => model.generate_from_ids_with_callback(
+                input_ids,
+                attention_mask,
+                None,
+                None,
+                None,
+                callback,
+            ),
+        }
+    }
+
+    /// Interface method to reload_weights() of the particular models.
+    pub fn reload_weights(&mut self, weights_resource: &Resource) -> Result<(), RustBertError> {
+        match self {
+            Self::GPT2(model_ref) => model_ref.reload_weights(weights_resource),
+        }
+    }
 }
 
 /// # Conversation model
@@ -786,6 +937,105 @@ impl ConversationModel {
         &self,
         conversation_manager: &'a mut ConversationManager,
     ) -> HashMap<&'a Uuid, &'a str> {
+        self.generate_responses_with_usage(conversation_manager).0
+    }
+
+    /// Perform a multi-turn conversation based on user input, and returns the usage statistics of the request. The
+    /// prompt tokens include the history of the active conversations.
+    ///
+    /// # Arguments
+    ///
+    /// * `conversation_manager` - `&mut ConversationManager` Conversation manager keeping track of active conversations
+    ///
+    /// # Returns
+    /// * `HashMap<&Uuid, &str>` Responses from the model for each active conversation, referenced by Uuid
+    /// * `Option<GenerationUsage>` usage statistics of the request, `None` if no conversation was active
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// # fn main() -> anyhow::Result<()> {
+    /// use rust_bert::pipelines::conversation::{ConversationManager, ConversationModel};
+    /// let model = ConversationModel::new(Default::default())?;
+    ///
+    /// let mut conversation_manager = ConversationManager::new();
+    /// conversation_manager.create("Hello, how are you?");
+    ///
+    /// let (output, usage) = model.generate_responses_with_usage(&mut conversation_manager);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn generate_responses_with_usage<'a>(
+        &self,
+        conversation_manager: &'a mut ConversationManager,
+    ) -> (HashMap<&'a Uuid, &'a str>, Option<GenerationUsage>) {
+        self.process_active_conversations(conversation_manager, |input_tensor, _| {
+            Ok(self
+                .model
+                .generate_from_ids_and_past_with_usage(input_tensor, None))
+        })
+        .unwrap()
+    }
+
+    /// Perform a multi-turn conversation based on user input, calling `callback` with each token of the responses as
+    /// soon as it is generated, along with the id of its conversation. Streaming is not available with beam search
+    /// (`num_beams` must be 1).
+    ///
+    /// # Arguments
+    ///
+    /// * `conversation_manager` - `&mut ConversationManager` Conversation manager keeping track of active conversations
+    /// * `callback` - Closure called with the `Uuid` of the conversation and each `StreamedToken` of its response
+    ///
+    /// # Returns
+    /// * `HashMap<&Uuid, &str>` Responses from the model for each active conversation, referenced by Uuid
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// # fn main() -> anyhow::Result<()> {
+    /// use rust_bert::pipelines::conversation::{ConversationManager, ConversationModel};
+    /// use std::io::Write;
+    ///
+    /// let model = ConversationModel::new(Default::default())?;
+    ///
+    /// let mut conversation_manager = ConversationManager::new();
+    /// conversation_manager.create("Hello, how are you?");
+    ///
+    /// let output = model.generate_responses_with_callback(&mut conversation_manager, |_, token| {
+    ///     print!("{}", token.text);
+    ///     std::io::stdout().flush().unwrap();
+    /// })?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn generate_responses_with_callback<'a, F>(
+        &self,
+        conversation_manager: &'a mut ConversationManager,
+        mut callback: F,
+    ) -> Result<HashMap<&'a Uuid, &'a str>, RustBertError>
+    where
+        F: FnMut(&Uuid, StreamedToken),
+    {
+        Ok(self
+            .process_active_conversations(conversation_manager, |input_tensor, uuids| {
+                self.model
+                    .generate_from_ids_with_callback(input_tensor, None, |token| {
+                        callback(uuids[token.sequence_index], token)
+                    })
+            })?
+            .0)
+    }
+
+    // Generates the responses of the active conversations from their history and new user input, and updates the
+    // conversations with them
+    fn process_active_conversations<'a, G>(
+        &self,
+        conversation_manager: &'a mut ConversationManager,
+        generate: G,
+    ) -> Result<(HashMap<&'a Uuid, &'a str>, Option<GenerationUsage>), RustBertError>
+    where
+        G: FnOnce(Tensor, &[&Uuid]) -> Result<(Vec<Vec<i64>>, GenerationUsage), RustBertError>,
+    {
         let (active_uuid, active_conversations) = conversation_manager.get_active_conversations();
         if !active_uuid.is_empty() {
             let texts = active_conversations
@@ -801,7 +1051,7 @@ impl ConversationModel {
             let prompt_ids = self.encode_prompts(texts.as_ref());
             let input_tensor = self.concat_input_history(prompt_ids.as_ref(), history);
             let input_length = *input_tensor.size().last().unwrap() as usize;
-            let mut generated = self.model.generate_from_ids_and_past(input_tensor, None);
+            let (mut generated, usage) = generate(input_tensor, &active_uuid)?;
             let removed_padding_quantities = self.clean_padding_indices(&mut generated);
 
             let mut output = HashMap::with_capacity(active_uuid.len());
@@ -828,9 +1078,9 @@ impl ConversationModel {
                 conversation.mark_processed();
                 output.insert(uuid, conversation.get_last_response().unwrap());
             }
-            output
+            Ok((output, Some(usage)))
         } else {
-            HashMap::new()
+            Ok((HashMap::new(), None))
         }
     }
 
@@ -966,6 +1216,16 @@ impl ConversationModel {
             })
             .collect::<Vec<Vec<i64>>>()
     }
+
+    /// Reloads the weights of the conversation model from the resource provided, keeping the tokenizer and configuration
+    /// unchanged. The weights must match the architecture of the model currently loaded.
+    ///
+    /// # Arguments
+    ///
+    /// * `weights_resource` - `Resource` pointing to the new model weights (e.g. model.ot)
+    pub fn reload_weights(&mut self, weights_resource: &Resource) -> Result<(), RustBertError> {
+        self.model.reload_weights(weights_resource)
+    }
 }
 
 #[cfg(test)]
@@ -0,0 +1,368 @@
+// Copyright 2021 Guillaume Becquin
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//     http://www.apache.org/licenses/LICENSE-2.0
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! # Registry of custom model architectures
+//! The pipelines are limited to the architectures defined in the crate (`ModelType`). The `CustomModelRegistry` allows
+//! registering constructors for architectures defined outside of the crate under a name, and building the sequence
+//! classification, summarization and translation pipelines with these models. The tokenizer is still created from the
+//! `model_type` of the pipeline configuration, which should match the vocabulary of the custom model.
+//!
+//! ```no_run
+//! # fn main() -> anyhow::Result<()> {
+//! use rust_bert::bert::{BertConfig, BertForSequenceClassification};
+//! use rust_bert::pipelines::custom_models::{CustomModelRegistry, CustomSequenceClassifier};
+//! use rust_bert::pipelines::sequence_classification::{
+//!     SequenceClassificationConfig, SequenceClassificationModel,
+//! };
+//! use rust_bert::Config;
+//! use std::collections::HashMap;
+//! use tch::Tensor;
+//!
+//! struct MyClassifier {
+//!     model: BertForSequenceClassification,
+//!     id2label: HashMap<i64, String>,
+//! }
+//!
+//! impl CustomSequenceClassifier for MyClassifier {
+//!     fn forward_t(
+//!         &self,
+//!         input_ids: Option<Tensor>,
+//!         mask: Option<Tensor>,
+//!         token_type_ids: Option<Tensor>,
+//!         position_ids: Option<Tensor>,
+//!         input_embeds: Option<Tensor>,
+//!         train: bool,
+//!     ) -> Tensor {
+//!         let logits = self
+//!             .model
+//!             .forward_t(input_ids, mask, token_type_ids, position_ids, input_embeds, train)
+//!             .logits;
+//!         logits * 0.5
+//!     }
+//!
+//!     fn label_mapping(&self) -> HashMap<i64, String> {
+//!         self.id2label.clone()
+//!     }
+//! }
+//!
+//! let mut registry = CustomModelRegistry::new();
+//! registry.register_sequence_classifier("my_classifier", |p, config_path| {
+//!     let config = BertConfig::try_from_file(config_path)?;
+//!     Ok(Box::new(MyClassifier {
+//!         model: BertForSequenceClassification::new(p, &config),
+//!         id2label: config.id2label.clone().unwrap_or_default(),
+//!     }))
+//! });
+//!
+//! let model = SequenceClassificationModel::new_with_custom_model(
+//!     SequenceClassificationConfig::default(),
+//!     &registry,
+//!     "my_classifier",
+//! )?;
+//! # Ok(())
+//! # }
+//! ```
+
+use crate::common::error::RustBertError;
+use crate::common::resources::Resource;
+use crate::pipelines::generation_utils::{
+    GenerateConfig, GenerateOptions, GenerationUsage, LanguageGenerator, ScoredGeneration,
+    Seq2SeqGenerationModel, Seq2SeqGenerator,
+};
+use std::collections::HashMap;
+use std::path::Path;
+use tch::{nn, Tensor};
+
+/// # Sequence classification model defined outside of the crate
+pub trait CustomSequenceClassifier: Send {
+    /// Forward pass through the model, returning the logits of shape (*batch size*, *num_labels*)
+    fn forward_t(
+        &self,
+        input_ids: Option<Tensor>,
+        mask: Option<Tensor>,
+        token_type_ids: Option<Tensor>,
+        position_ids: Option<Tensor>,
+        input_embeds: Option<Tensor>,
+        train: bool,
+    ) -> Tensor;
+
+    /// Returns the mapping between the label indices and their names
+    fn label_mapping(&self) -> HashMap<i64, String>;
+}
+
+/// # Text generation model defined outside of the crate
+/// Implemented for all `Seq2SeqGenerator` wrapping a custom `Seq2SeqGenerationModel`.
+pub trait CustomTextGenerator: Send {
+    /// Generates text for the prompts provided
+    fn generate(
+        &self,
+        prompt_texts: Option<&[&str]>,
+        attention_mask: Option<Tensor>,
+    ) -> Vec<String>;
+
+    /// Generates text for the prompts provided, overriding the generation configuration for this call
+    fn generate_with_options(
+        &self,
+        prompt_texts: Option<&[&str]>,
+        attention_mask: Option<Tensor>,
+        generate_options: &GenerateOptions,
+    ) -> Vec<String>;
+
+    /// Generates text for the prompts provided, and returns the pooled encoder embeddings of each prompt
+    fn generate_with_encoder_embeddings(
+        &self,
+        prompt_texts: Option<&[&str]>,
+        attention_mask: Option<Tensor>,
+    ) -> Result<(Vec<String>, Tensor), RustBertError>;
+
+    /// Generates text for the prompts provided, and returns the usage statistics of the request
+    fn generate_with_usage(
+        &self,
+        prompt_texts: Option<&[&str]>,
+        attention_mask: Option<Tensor>,
+    ) -> (Vec<String>, GenerationUsage);
+
+    /// Generates text for the prompts provided, biasing the generation towards the tokens of each prompt
+    fn generate_with_source_copy_bias(
+        &self,
+        prompt_texts: Option<&[&str]>,
+        attention_mask: Option<Tensor>,
+        source_copy_bias: f64,
+    ) -> Vec<String>;
+
+    /// Generates text for the prompts provided, requiring the words provided to appear in each output
+    fn generate_with_forced_words(
+        &self,
+        prompt_texts: Option<&[&str]>,
+        attention_mask: Option<Tensor>,
+        forced_words: &[&str],
+    ) -> Result<Vec<String>, RustBertError>;
+
+    /// Generates text for the prompts provided, with the log-probabilities of the generated tokens
+    fn generate_with_scores(
+        &self,
+        prompt_texts: Option<&[&str]>,
+        attention_mask: Option<Tensor>,
+    ) -> Vec<ScoredGeneration>;
+
+    /// Replaces the weights of the model with the weights from the resource provided
+    fn reload_weights(&mut self, weights_resource: &Resource) -> Result<(), RustBertError>;
+}
+
+impl<M: Seq2SeqGenerationModel + Send> CustomTextGenerator for Seq2SeqGenerator<M> {
+    fn generate(
+        &self,
+        prompt_texts: Option<&[&str]>,
+        attention_mask: Option<Tensor>,
+    ) -> Vec<String> {
+        LanguageGenerator::generate(self, prompt_texts, attention_mask, None, None, None)
+    }
+
+    fn generate_with_options(
+        &self,
+        prompt_texts: Option<&[&str]>,
+        attention_mask: Option<Tensor>,
+        generate_options: &GenerateOptions,
+    ) -> Vec<String> {
+        LanguageGenerator::generate_with_options(
+            self,
+            prompt_texts,
+            attention_mask,
+            generate_options,
+        )
+    }
+
+    fn generate_with_encoder_embeddings(
+        &self,
+        prompt_texts: Option<&[&str]>,
+        attention_mask: Option<Tensor>,
+    ) -> Result<(Vec<String>, Tensor), RustBertError> {
+        LanguageGenerator::generate_with_encoder_embeddings(
+            self,
+            prompt_texts,
+            attention_mask,
+            None,
+            None,
+            None,
+        )
+    }
+
+    fn generate_with_usage(
+        &self,
+        prompt_texts: Option<&[&str]>,
+        attention_mask: Option<Tensor>,
+    ) -> (Vec<String>, GenerationUsage) {
+        LanguageGenerator::generate_with_usage(self, prompt_texts, attention_mask, None, None, None)
+    }
+
+    fn generate_with_source_copy_bias(
+        &self,
+        prompt_texts: Option<&[&str]>,
+        attention_mask: Option<Tensor>,
+        source_copy_bias: f64,
+    ) -> Vec<String> {
+        LanguageGenerator::generate_with_source_copy_bias(
+            self,
+            prompt_texts,
+            attention_mask,
+            source_copy_bias,
+        )
+    }
+
+    fn generate_with_forced_words(
+        &self,
+        prompt_texts: Option<&[&str]>,
+        attention_mask: Option<Tensor>,
+        forced_words: &[&str],
+    ) -> Result<Vec<String>, RustBertError> {
+        LanguageGenerator::generate_with_forced_words(
+            self,
+            prompt_texts,
+            attention_mask,
+            forced_words,
+        )
+    }
+
+    fn generate_with_scores(
+        &self,
+        prompt_texts: Option<&[&str]>,
+        attention_mask: Option<Tensor>,
+    ) -> Vec<ScoredGeneration> {
+        LanguageGenerator::generate_with_scores(
+            self,
+            prompt_texts,
+            attention_mask,
+            None,
+            None,
+            None,
+        )
+    }
+
+    fn reload_weights(&mut self, weights_resource: &Resource) -> Result<(), RustBertError> {
+        LanguageGenerator::reload_weights(self, weights_resource)
+    }
+}
+
+type SequenceClassifierBuilder = Box<
+    dyn Fn(&nn::Path<'_>, &Path) -> Result<Box<dyn CustomSequenceClassifier>, RustBertError>
+        + Send
+        + Sync,
+>;
+
+type TextGeneratorBuilder = Box<
+    dyn Fn(GenerateConfig) -> Result<Box<dyn CustomTextGenerator>, RustBertError> + Send + Sync,
+>;
+
+/// # Registry of custom model constructors
+/// Constructors are identified by the name they are registered with. Registering a constructor under an existing
+/// name replaces the previous one.
+#[derive(Default)]
+pub struct CustomModelRegistry {
+    sequence_classifiers: HashMap<String, SequenceClassifierBuilder>,
+    text_generators: HashMap<String, TextGeneratorBuilder>,
+}
+
+impl CustomModelRegistry {
+    /// Creates an empty registry
+    pub fn new() -> CustomModelRegistry {
+        CustomModelRegistry {
+            sequence_classifiers: HashMap::new(),
+            text_generators: HashMap::new(),
+        }
+    }
+
+    /// Registers a sequence classification model constructor. The constructor receives the variable store path
+    /// to create the model variables into (the weights are loaded by the pipeline after the model is built) and the
+    /// path to the model configuration file.
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - name identifying the model
+    /// * `builder` - model constructor
+    pub fn register_sequence_classifier<F>(&mut self, name: &str, builder: F)
+    where
+        F: Fn(&nn::Path<'_>, &Path) -> Result<Box<dyn CustomSequenceClassifier>, RustBertError>
+            + Send
+            + Sync
+            + 'static,
+    {
+        self.sequence_classifiers
+            .insert(name.to_string(), Box::new(builder));
+    }
+
+    /// Registers a text generation model constructor, used by the summarization and translation pipelines. The
+    /// constructor receives the generation configuration (including the model resources) of the pipeline.
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - name identifying the model
+    /// * `builder` - generator constructor
+    pub fn register_text_generator<F>(&mut self, name: &str, builder: F)
+    where
+        F: Fn(GenerateConfig) -> Result<Box<dyn CustomTextGenerator>, RustBertError>
+            + Send
+            + Sync
+            + 'static,
+    {
+        self.text_generators
+            .insert(name.to_string(), Box::new(builder));
+    }
+
+    /// Returns `true` if a sequence classification model is registered under the name provided
+    pub fn has_sequence_classifier(&self, name: &str) -> bool {
+        self.sequence_classifiers.contains_key(name)
+    }
+
+    /// Returns `true` if a text generation model is registered under the name provided
+    pub fn has_text_generator(&self, name: &str) -> bool {
+        self.text_generators.contains_key(name)
+    }
+
+    pub(crate) fn build_sequence_classifier(
+        &self,
+        name: &str,
+        p: &nn::Path,
+        config_path: &Path,
+    ) -> Result<Box<dyn CustomSequenceClassifier>, RustBertError> {
+        match self.sequence_classifiers.get(name) {
+            Some(builder) => builder(p, config_path),
+            None => Err(RustBertError::InvalidConfigurationError(format!(
+                "No sequence classification model registered as {}",
+                name
+            ))),
+        }
+    }
+
+    pub(crate) fn build_text_generator(
+        &self,
+        name: &str,
+        generate_config: GenerateConfig,
+    ) -> Result<Box<dyn CustomTextGenerator>, RustBertError> {
+        match self.text_generators.get(name) {
+            Some(builder) => builder(generate_config),
+            None => Err(RustBertError::InvalidConfigurationError(format!(
+                "No text generation model registered as {}",
+                name
+            ))),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    #[ignore] // no need to run, compilation is enough to verify it is Send
+    fn test() {
+        let _: Box<dyn Send> = Box::new(CustomModelRegistry::new());
+    }
+}
@@ -0,0 +1,471 @@
+// Copyright 2021 Guillaume Becquin
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//     http://www.apache.org/licenses/LICENSE-2.0
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! # Near-duplicate detection
+//! Finds clusters of near-duplicate documents in a corpus. The documents are embedded in batches by a `TextEmbedder`
+//! (e.g. a `FeatureExtractionModel`) and indexed in an approximate nearest neighbours index (`LshIndex`, based on
+//! random hyperplanes locality-sensitive hashing): each document is only compared to the documents sharing a bucket
+//! with it in one of the hash tables. Documents with a cosine similarity above the threshold are linked, and the
+//! clusters are the connected components of these links (single linkage).
+//!
+//! The corpus is processed as a stream: the documents can be passed by batches to a `Deduplicator` and dropped once
+//! added. Only the normalized embeddings (4 bytes per dimension and document) and the hash tables are kept in memory,
+//! so that millions of documents can be processed with a compact embedding model.
+//!
+//! ```no_run
+//! # fn main() -> anyhow::Result<()> {
+//! use rust_bert::pipelines::deduplication::{find_near_duplicates, DeduplicationConfig};
+//! use rust_bert::pipelines::feature_extraction::FeatureExtractionModel;
+//!
+//! let embedder = FeatureExtractionModel::new(Default::default())?;
+//! let documents = [
+//!     "The cat sat on the mat.",
+//!     "A dog barked at the mailman.",
+//!     "The cat sat on the mat!",
+//! ];
+//! let config = DeduplicationConfig {
+//!     similarity_threshold: 0.95,
+//!     ..Default::default()
+//! };
+//! let clusters = find_near_duplicates(&embedder, documents.iter().copied(), config)?;
+//! for cluster in clusters {
+//!     println!("{} duplicated by {:?}", cluster.representative(), cluster.duplicates());
+//! }
+//! # Ok(())
+//! # }
+//! ```
+
+use crate::common::error::RustBertError;
+use crate::pipelines::feature_extraction::TextEmbedder;
+use std::collections::{HashMap, HashSet};
+use tch::{Device, Kind, Tensor};
+
+/// # Configuration for the near-duplicate detection
+#[derive(Debug, Clone, Copy)]
+pub struct DeduplicationConfig {
+    /// Minimum cosine similarity between the embeddings of two near-duplicates (default: 0.95)
+    pub similarity_threshold: f32,
+    /// Number of documents embedded at once (default: 64)
+    pub batch_size: usize,
+    /// Number of hash tables of the index. More tables find more near-duplicates, at the cost of more comparisons (default: 8)
+    pub num_tables: usize,
+    /// Number of hyperplanes (hash bits) per table, at most 64. More hyperplanes lead to smaller buckets (default: 12)
+    pub num_hyperplanes: usize,
+    /// Seed of the random hyperplanes (default: 42)
+    pub seed: u64,
+}
+
+impl Default for DeduplicationConfig {
+    fn default() -> DeduplicationConfig {
+        DeduplicationConfig {
+            similarity_threshold: 0.95,
+            batch_size: 64,
+            num_tables: 8,
+            num_hyperplanes: 12,
+            seed: 42,
+        }
+    }
+}
+
+/// Gaussian samples from a xorshift generator, keeping the hyperplanes independent from the `tch` random state
+struct NormalSampler {
+    state: u64,
+}
+
+impl NormalSampler {
+    fn new(seed: u64) -> NormalSampler {
+        NormalSampler {
+            state: (seed ^ 0x9E37_79B9_7F4A_7C15) | 1,
+        }
+    }
+
+    fn next_uniform(&mut self) -> f64 {
+        self.state ^= self.state >> 12;
+        self.state ^= self.state << 25;
+        self.state ^= self.state >> 27;
+        let value = self.state.wrapping_mul(0x2545_F491_4F6C_DD1D);
+        ((value >> 11) as f64 + 0.5) / (1u64 << 53) as f64
+    }
+
+    fn next_normal(&mut self) -> f32 {
+        let (u1, u2) = (self.next_uniform(), self.next_uniform());
+        ((-2.0 * u1.ln()).sqrt() * (2.0 * std::f64::consts::PI * u2).cos()) as f32
+    }
+}
+
+/// # Approximate nearest neighbours index
+/// Random hyperplanes locality-sensitive hashing index for the cosine similarity. The vectors are normalized when
+/// added, and the candidates found in the hash buckets are scored with the exact cosine similarity.
+pub struct LshIndex {
+    dim: usize,
+    num_hyperplanes: usize,
+    hyperplanes: Vec<f32>,
+    tables: Vec<HashMap<u64, Vec<usize>>>,
+    vectors: Vec<f32>,
+}
+
+impl LshIndex {
+    /// Creates an empty index
+    ///
+    /// # Arguments
+    ///
+    /// * `dim` - dimension of the indexed vectors
+    /// * `num_tables` - number of hash tables
+    /// * `num_hyperplanes` - number of hyperplanes (hash bits) per table, between 1 and 64
+    /// * `seed` - seed of the random hyperplanes
+    pub fn new(
+        dim: usize,
+        num_tables: usize,
+        num_hyperplanes: usize,
+        seed: u64,
+    ) -> Result<LshIndex, RustBertError> {
+        if dim == 0 || num_tables == 0 {
+            return Err(RustBertError::ValueError(
+                "The dimension and number of tables of the index must be positive".to_string(),
+            ));
+        }
+        if num_hyperplanes == 0 || num_hyperplanes > 64 {
+            return Err(RustBertError::ValueError(format!(
+                "The number of hyperplanes per table must be between 1 and 64, got {}",
+                num_hyperplanes
+            )));
+        }
+        let mut sampler = NormalSampler::new(seed);
+        let hyperplanes = (0..num_tables * num_hyperplanes * dim)
+            .map(|_| sampler.next_normal())
+            .collect();
+        Ok(LshIndex {
+            dim,
+            num_hyperplanes,
+            hyperplanes,
+            tables: vec![HashMap::new(); num_tables],
+            vectors: vec![],
+        })
+    }
+
+    /// Dimension of the indexed vectors
+    pub fn dim(&self) -> usize {
+        self.dim
+    }
+
+    /// Returns the number of vectors indexed
+    pub fn len(&self) -> usize {
+        self.vectors.len() / self.dim
+    }
+
+    /// Returns true if the index is empty
+    pub fn is_empty(&self) -> bool {
+        self.vectors.is_empty()
+    }
+
+    /// Returns the normalized vector of an indexed element
+    pub fn vector(&self, id: usize) -> &[f32] {
+        &self.vectors[id * self.dim..(id + 1) * self.dim]
+    }
+
+    /// Adds a vector to the index, returning its id (ids are assigned sequentially from 0)
+    pub fn add(&mut self, vector: &[f32]) -> Result<usize, RustBertError> {
+        let vector = self.normalize(vector)?;
+        let id = self.len();
+        for (table, code) in self.hash(&vector).into_iter().enumerate() {
+            self.tables[table]
+                .entry(code)
+                .or_insert_with(Vec::new)
+                .push(id);
+        }
+        self.vectors.extend(vector);
+        Ok(id)
+    }
+
+    /// Returns the indexed elements with a cosine similarity to the query of at least `threshold`, sorted by
+    /// decreasing similarity
+    pub fn search(
+        &self,
+        query: &[f32],
+        threshold: f32,
+    ) -> Result<Vec<(usize, f32)>, RustBertError> {
+        let query = self.normalize(query)?;
+        let mut seen = HashSet::new();
+        let mut neighbours = vec![];
+        for (table, code) in self.hash(&query).into_iter().enumerate() {
+            if let Some(bucket) = self.tables[table].get(&code) {
+                for &id in bucket {
+                    if seen.insert(id) {
+                        let similarity = dot(&query, self.vector(id));
+                        if similarity >= threshold {
+                            neighbours.push((id, similarity));
+                        }
+                    }
+                }
+            }
+        }
+        neighbours.sort_by(|(id_1, similarity_1), (id_2, similarity_2)| {
+            similarity_2
+                .partial_cmp(similarity_1)
+                .unwrap()
+                .then(id_1.cmp(id_2))
+        });
+        Ok(neighbours)
+    }
+
+    fn normalize(&self, vector: &[f32]) -> Result<Vec<f32>, RustBertError> {
+        if vector.len() != self.dim {
+            return Err(RustBertError::ValueError(format!(
+                "Expected a vector of dimension {}, got {}",
+                self.dim,
+                vector.len()
+            )));
+        }
+        let norm = dot(vector, vector).sqrt().max(f32::EPSILON);
+        Ok(vector.iter().map(|value| value / norm).collect())
+    }
+
+    fn hash(&self, vector: &[f32]) -> Vec<u64> {
+        self.hyperplanes
+            .chunks(self.dim * self.num_hyperplanes)
+            .map(|table_hyperplanes| {
+                table_hyperplanes.chunks(self.dim).enumerate().fold(
+                    0u64,
+                    |code, (bit, hyperplane)| {
+                        if dot(hyperplane, vector) > 0.0 {
+                            code | (1 << bit)
+                        } else {
+                            code
+                        }
+                    },
+                )
+            })
+            .collect()
+    }
+}
+
+fn dot(vector_1: &[f32], vector_2: &[f32]) -> f32 {
+    vector_1
+        .iter()
+        .zip(vector_2.iter())
+        .map(|(value_1, value_2)| value_1 * value_2)
+        .sum()
+}
+
+/// # Cluster of near-duplicate documents
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DuplicateCluster {
+    /// Ids (positions in the stream) of the documents of the cluster, in increasing order
+    pub documents: Vec<usize>,
+}
+
+impl DuplicateCluster {
+    /// First document of the cluster, usually kept when de-duplicating the corpus
+    pub fn representative(&self) -> usize {
+        self.documents[0]
+    }
+
+    /// Documents of the cluster following the representative
+    pub fn duplicates(&self) -> &[usize] {
+        &self.documents[1..]
+    }
+}
+
+/// # Streaming near-duplicate detector
+/// Documents (or their embeddings) are added by batches and identified by their position in the stream.
+pub struct Deduplicator {
+    config: DeduplicationConfig,
+    index: Option<LshIndex>,
+    parents: Vec<usize>,
+}
+
+impl Deduplicator {
+    /// Creates a new detector
+    ///
+    /// # Arguments
+    ///
+    /// * `config` - `DeduplicationConfig` with the similarity threshold and index settings
+    pub fn new(config: DeduplicationConfig) -> Deduplicator {
+        Deduplicator {
+            config,
+            index: None,
+            parents: vec![],
+        }
+    }
+
+    /// Returns the number of documents added
+    pub fn len(&self) -> usize {
+        self.parents.len()
+    }
+
+    /// Returns true if no document was added
+    pub fn is_empty(&self) -> bool {
+        self.parents.is_empty()
+    }
+
+    /// Embeds and adds a batch of documents (split in batches of `batch_size` documents)
+    ///
+    /// # Arguments
+    ///
+    /// * `embedder` - `TextEmbedder` computing the embeddings of the documents
+    /// * `documents` - `&[&str]` Array of documents
+    ///
+    /// # Returns
+    ///
+    /// * `Vec<Option<usize>>` with, for each document, the most similar document added before it if it is a near-duplicate
+    pub fn add_documents<E>(
+        &mut self,
+        embedder: &E,
+        documents: &[&str],
+    ) -> Result<Vec<Option<usize>>, RustBertError>
+    where
+        E: TextEmbedder + ?Sized,
+    {
+        let embeddings = embedder.embed_in_batches(documents, self.config.batch_size)?;
+        self.add_embeddings(&embeddings)
+    }
+
+    /// Adds a batch of document embeddings
+    ///
+    /// # Arguments
+    ///
+    /// * `embeddings` - `Tensor` of shape (*number of documents*, *embedding dimension*)
+    ///
+    /// # Returns
+    ///
+    /// * `Vec<Option<usize>>` with, for each document, the most similar document added before it if it is a near-duplicate
+    pub fn add_embeddings(
+        &mut self,
+        embeddings: &Tensor,
+    ) -> Result<Vec<Option<usize>>, RustBertError> {
+        let size = embeddings.size();
+        if size.len() != 2 {
+            return Err(RustBertError::ValueError(format!(
+                "Expected embeddings of shape (number of documents, embedding dimension), got {:?}",
+                size
+            )));
+        }
+        if size[0] == 0 {
+            return Ok(vec![]);
+        }
+        if self.index.is_none() {
+            self.index = Some(LshIndex::new(
+                size[1] as usize,
+                self.config.num_tables,
+                self.config.num_hyperplanes,
+                self.config.seed,
+            )?);
+        }
+        let embeddings = embeddings.to(Device::Cpu).to_kind(Kind::Float);
+        let mut matches = Vec::with_capacity(size[0] as usize);
+        for row in 0..size[0] {
+            let embedding = Vec::<f32>::from(embeddings.get(row));
+            let index = self.index.as_mut().unwrap();
+            let neighbours = index.search(&embedding, self.config.similarity_threshold)?;
+            let id = index.add(&embedding)?;
+            self.parents.push(id);
+            for &(neighbour, _) in &neighbours {
+                self.union(neighbour, id);
+            }
+            matches.push(neighbours.first().map(|&(neighbour, _)| neighbour));
+        }
+        Ok(matches)
+    }
+
+    /// Returns the clusters of near-duplicates (with at least two documents), sorted by representative
+    pub fn clusters(&self) -> Vec<DuplicateCluster> {
+        let mut clusters: Vec<Vec<usize>> = vec![vec![]; self.parents.len()];
+        for id in 0..self.parents.len() {
+            clusters[self.find(id)].push(id);
+        }
+        clusters
+            .into_iter()
+            .filter(|documents| documents.len() > 1)
+            .map(|documents| DuplicateCluster { documents })
+            .collect()
+    }
+
+    /// Returns the ids of the documents to drop to de-duplicate the corpus (all documents but the representative of
+    /// each cluster), in increasing order
+    pub fn duplicates(&self) -> Vec<usize> {
+        (0..self.parents.len())
+            .filter(|&id| self.find(id) != id)
+            .collect()
+    }
+
+    fn find(&self, mut id: usize) -> usize {
+        while self.parents[id] != id {
+            id = self.parents[id];
+        }
+        id
+    }
+
+    fn union(&mut self, id_1: usize, id_2: usize) {
+        let (root_1, root_2) = (self.find(id_1), self.find(id_2));
+        // The root of a cluster is always its first document
+        let (root, child) = if root_1 < root_2 {
+            (root_1, root_2)
+        } else {
+            (root_2, root_1)
+        };
+        self.parents[child] = root;
+        self.compress(id_1, root);
+        self.compress(id_2, root);
+    }
+
+    fn compress(&mut self, mut id: usize, root: usize) {
+        while self.parents[id] != root {
+            let parent = self.parents[id];
+            self.parents[id] = root;
+            id = parent;
+        }
+    }
+}
+
+/// Finds the clusters of near-duplicates of a stream of documents, embedded in batches of `batch_size` documents
+///
+/// # Arguments
+///
+/// * `embedder` - `TextEmbedder` computing the embeddings of the documents
+/// * `documents` - iterator over the documents
+/// * `config` - `DeduplicationConfig` with the similarity threshold and index settings
+///
+/// # Returns
+///
+/// * `Vec<DuplicateCluster>` clusters of near-duplicates, identified by the position of the documents in the stream
+pub fn find_near_duplicates<'a, E, I>(
+    embedder: &E,
+    documents: I,
+    config: DeduplicationConfig,
+) -> Result<Vec<DuplicateCluster>, RustBertError>
+where
+    E: TextEmbedder + ?Sized,
+    I: IntoIterator<Item = &'a str>,
+{
+    let batch_size = config.batch_size.max(1);
+    let mut deduplicator = Deduplicator::new(config);
+    let mut batch = Vec::with_capacity(batch_size);
+    for document in documents {
+        batch.push(document);
+        if batch.len() == batch_size {
+            deduplicator.add_documents(embedder, &batch)?;
+            batch.clear();
+        }
+    }
+    deduplicator.add_documents(embedder, &batch)?;
+    Ok(deduplicator.clusters())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    #[ignore] // no need to run, compilation is enough to verify it is Send
+    fn test() {
+        let _: Box<dyn Send> = Box::new(Deduplicator::new(DeduplicationConfig::default()));
+    }
+}
@@ -0,0 +1,230 @@
+// Copyright 2021 Guillaume Becquin
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//     http://www.apache.org/licenses/LICENSE-2.0
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! # Differentially private embeddings
+//! Releases text embeddings with a Gaussian mechanism: each embedding is clipped to a maximum L2 norm, bounding the
+//! difference between the embeddings of any two texts (sensitivity of twice the clipping norm), before Gaussian noise
+//! proportional to this sensitivity is added. Each released embedding of a text is then (epsilon, delta)-differentially
+//! private with respect to the replacement of the text.
+//!
+//! The privacy loss is tracked with zero-concentrated differential privacy
+//! ([zCDP, Bun & Steinke, 2016](https://arxiv.org/abs/1605.02065)), composing linearly over the releases of the same
+//! text. The `PrivacyAccountant` converts the accumulated loss into an (epsilon, delta) guarantee, and
+//! `GaussianMechanism::for_budget` calibrates the noise for a target guarantee.
+//!
+//! Any `TextEmbedder` can be wrapped in a `PrivateEmbedder` returning noisy embeddings, for example to index
+//! sensitive documents for retrieval. The noise is sampled from the `tch` random generator, which is not
+//! cryptographically secure.
+//!
+//! ```no_run
+//! # fn main() -> anyhow::Result<()> {
+//! use rust_bert::pipelines::differential_privacy::{
+//!     GaussianMechanism, PrivacyAccountant, PrivateEmbedder,
+//! };
+//! use rust_bert::pipelines::feature_extraction::{FeatureExtractionModel, TextEmbedder};
+//!
+//! // Each embedding release is (1.0, 1e-5)-differentially private
+//! let mechanism = GaussianMechanism::for_budget(1.0, 1.0, 1e-5)?;
+//! let embedder = PrivateEmbedder::new(FeatureExtractionModel::new(Default::default())?, mechanism);
+//! let embeddings = embedder.embed(&["Patient reported chest pain after exercise."])?;
+//!
+//! // Embedding the same texts 4 times composes the privacy loss
+//! let mut accountant = PrivacyAccountant::new();
+//! accountant.record(&mechanism, 4);
+//! println!("epsilon after 4 releases: {:.2}", accountant.epsilon(1e-5));
+//! # Ok(())
+//! # }
+//! ```
+
+use crate::common::error::RustBertError;
+use crate::pipelines::feature_extraction::TextEmbedder;
+use tch::{Kind, Tensor};
+
+/// Clips each embedding (row) to a maximum L2 norm, leaving the shorter embeddings unchanged
+///
+/// # Arguments
+///
+/// * `embeddings` - `Tensor` of shape (*number of embeddings*, *embedding dimension*)
+/// * `clip_norm` - Maximum L2 norm of the embeddings
+pub fn clip_embeddings(embeddings: &Tensor, clip_norm: f64) -> Tensor {
+    let norms = (embeddings * embeddings)
+        .sum1(&[-1], true, Kind::Float)
+        .sqrt()
+        .clamp_min(1e-12);
+    embeddings * (norms.reciprocal() * clip_norm).clamp_max(1.0)
+}
+
+#[derive(Debug, Clone, Copy)]
+/// # Gaussian mechanism for the release of embeddings
+pub struct GaussianMechanism {
+    /// Maximum L2 norm of the embeddings
+    pub clip_norm: f64,
+    /// Standard deviation of the noise relative to the sensitivity (twice the clipping norm)
+    pub noise_multiplier: f64,
+}
+
+impl GaussianMechanism {
+    /// Creates a new mechanism
+    ///
+    /// # Arguments
+    ///
+    /// * `clip_norm` - Maximum L2 norm of the embeddings
+    /// * `noise_multiplier` - Standard deviation of the noise relative to the sensitivity (twice the clipping norm)
+    pub fn new(clip_norm: f64, noise_multiplier: f64) -> Result<GaussianMechanism, RustBertError> {
+        if !(clip_norm > 0.0 && noise_multiplier > 0.0) {
+            return Err(RustBertError::InvalidConfigurationError(format!(
+                "clip_norm ({}) and noise_multiplier ({}) must be strictly positive",
+                clip_norm, noise_multiplier
+            )));
+        }
+        Ok(GaussianMechanism {
+            clip_norm,
+            noise_multiplier,
+        })
+    }
+
+    /// Creates a mechanism adding the minimum noise for each release to be (epsilon, delta)-differentially private
+    ///
+    /// # Arguments
+    ///
+    /// * `clip_norm` - Maximum L2 norm of the embeddings
+    /// * `epsilon` - Privacy budget of a release (strictly positive)
+    /// * `delta` - Probability of exceeding the budget (between 0 and 1, exclusive)
+    pub fn for_budget(
+        clip_norm: f64,
+        epsilon: f64,
+        delta: f64,
+    ) -> Result<GaussianMechanism, RustBertError> {
+        check_budget(epsilon, delta)?;
+        // Largest rho such that rho + 2 * sqrt(rho * ln(1 / delta)) <= epsilon
+        let log_delta = (1.0 / delta).ln();
+        let rho = ((log_delta + epsilon).sqrt() - log_delta.sqrt()).powi(2);
+        GaussianMechanism::new(clip_norm, (1.0 / (2.0 * rho)).sqrt())
+    }
+
+    /// L2 sensitivity of a release: maximum distance between the clipped embeddings of two texts
+    pub fn sensitivity(&self) -> f64 {
+        2.0 * self.clip_norm
+    }
+
+    /// Standard deviation of the noise added to each dimension of the embeddings
+    pub fn noise_std(&self) -> f64 {
+        self.noise_multiplier * self.sensitivity()
+    }
+
+    /// Privacy loss of a release in zero-concentrated differential privacy
+    pub fn rho(&self) -> f64 {
+        1.0 / (2.0 * self.noise_multiplier * self.noise_multiplier)
+    }
+
+    /// Epsilon of a single release for the `delta` provided
+    pub fn epsilon(&self, delta: f64) -> f64 {
+        zcdp_to_epsilon(self.rho(), delta)
+    }
+
+    /// Clips the embeddings and adds Gaussian noise
+    ///
+    /// # Arguments
+    ///
+    /// * `embeddings` - `Tensor` of shape (*number of embeddings*, *embedding dimension*)
+    ///
+    /// # Returns
+    ///
+    /// * `Tensor` of noisy embeddings with the same shape, kind and device
+    pub fn privatize(&self, embeddings: &Tensor) -> Tensor {
+        let clipped = clip_embeddings(embeddings, self.clip_norm);
+        let noise = clipped.randn_like() * self.noise_std();
+        clipped + noise
+    }
+}
+
+/// # Tracker of the privacy loss of successive releases
+/// Accumulates the zCDP privacy loss of the releases of the same texts, which composes additively.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct PrivacyAccountant {
+    rho: f64,
+}
+
+impl PrivacyAccountant {
+    /// Creates an accountant without releases
+    pub fn new() -> PrivacyAccountant {
+        PrivacyAccountant::default()
+    }
+
+    /// Records `releases` releases of the same texts by a mechanism
+    pub fn record(&mut self, mechanism: &GaussianMechanism, releases: usize) {
+        self.rho += mechanism.rho() * releases as f64;
+    }
+
+    /// Accumulated privacy loss in zero-concentrated differential privacy
+    pub fn rho(&self) -> f64 {
+        self.rho
+    }
+
+    /// Epsilon of all the releases recorded for the `delta` provided
+    pub fn epsilon(&self, delta: f64) -> f64 {
+        zcdp_to_epsilon(self.rho, delta)
+    }
+}
+
+/// # Embedder releasing differentially private embeddings
+/// Wraps a `TextEmbedder`, clipping and adding Gaussian noise to the embeddings it computes.
+pub struct PrivateEmbedder<E: TextEmbedder> {
+    embedder: E,
+    mechanism: GaussianMechanism,
+}
+
+impl<E: TextEmbedder> PrivateEmbedder<E> {
+    /// Creates a new private embedder
+    ///
+    /// # Arguments
+    ///
+    /// * `embedder` - `TextEmbedder` computing the embeddings
+    /// * `mechanism` - `GaussianMechanism` clipping and adding noise to the embeddings
+    pub fn new(embedder: E, mechanism: GaussianMechanism) -> PrivateEmbedder<E> {
+        PrivateEmbedder {
+            embedder,
+            mechanism,
+        }
+    }
+
+    /// Returns the mechanism applied to the embeddings
+    pub fn mechanism(&self) -> &GaussianMechanism {
+        &self.mechanism
+    }
+}
+
+impl<E: TextEmbedder> TextEmbedder for PrivateEmbedder<E> {
+    fn embed(&self, texts: &[&str]) -> Result<Tensor, RustBertError> {
+        Ok(self.mechanism.privatize(&self.embedder.embed(texts)?))
+    }
+
+    fn embed_in_batches(&self, texts: &[&str], batch_size: usize) -> Result<Tensor, RustBertError> {
+        Ok(self
+            .mechanism
+            .privatize(&self.embedder.embed_in_batches(texts, batch_size)?))
+    }
+}
+
+fn check_budget(epsilon: f64, delta: f64) -> Result<(), RustBertError> {
+    if !(epsilon > 0.0 && delta > 0.0 && delta < 1.0) {
+        return Err(RustBertError::InvalidConfigurationError(format!(
+            "epsilon ({}) must be strictly positive and delta ({}) between 0 and 1",
+            epsilon, delta
+        )));
+    }
+    Ok(())
+}
+
+/// Converts a zCDP privacy loss to (epsilon, delta)-differential privacy (Bun & Steinke, 2016, Proposition 1.3)
+fn zcdp_to_epsilon(rho: f64, delta: f64) -> f64 {
+    rho + 2.0 * (rho * (1.0 / delta).ln()).sqrt()
+}
@@ -0,0 +1,211 @@
+// Copyright 2021 Guillaume Becquin
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//     http://www.apache.org/licenses/LICENSE-2.0
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! # Doc2query pipeline
+//! Generates synthetic queries a document could answer, with a T5 model fine-tuned for query prediction (such as the
+//! docTTTTTquery checkpoints trained on MS MARCO, e.g. `castorini/doc2query-t5-base-msmarco` converted with
+//! `utils/convert_model.py`). Appending the generated queries to the documents before indexing (document expansion)
+//! improves the recall of lexical search engines.
+//!
+//! Queries are sampled with top-k sampling, as in the original docTTTTTquery set-up. Duplicate queries (identical
+//! up to case, punctuation and white spaces) are removed, so that fewer than `num_queries` queries may be
+//! returned for a document.
+//!
+//! ```no_run
+//! # fn main() -> anyhow::Result<()> {
+//! use rust_bert::pipelines::doc2query::{Doc2QueryConfig, Doc2QueryModel};
+//! use rust_bert::resources::{LocalResource, Resource};
+//! use std::path::PathBuf;
+//!
+//! let resource = |path: &str| {
+//!     Resource::Local(LocalResource {
+//!         local_path: PathBuf::from(path),
+//!     })
+//! };
+//! let config = Doc2QueryConfig::new(
+//!     resource("path/to/doc2query/rust_model.ot"),
+//!     resource("path/to/doc2query/config.json"),
+//!     resource("path/to/doc2query/spiece.model"),
+//! );
+//! let doc2query_model = Doc2QueryModel::new(config)?;
+//! let documents = ["The Manhattan Project was a research and development undertaking during World War II \
+//!     that produced the first nuclear weapons."];
+//! let queries = doc2query_model.generate_queries(&documents);
+//! let expanded_document = format!("{} {}", documents[0], queries[0].join(" "));
+//! # Ok(())
+//! # }
+//! ```
+
+use crate::common::error::RustBertError;
+use crate::common::resources::Resource;
+use crate::pipelines::generation_utils::{GenerateConfig, LanguageGenerator, T5Generator};
+use std::collections::HashSet;
+use tch::Device;
+
+/// # Configuration for doc2query generation
+pub struct Doc2QueryConfig {
+    /// Model weights resource
+    pub model_resource: Resource,
+    /// Config resource
+    pub config_resource: Resource,
+    /// SentencePiece model resource
+    pub vocab_resource: Resource,
+    /// Number of queries sampled for each document, before de-duplication (default: 10)
+    pub num_queries: i64,
+    /// Maximum length of the generated queries (default: 64)
+    pub max_length: i64,
+    /// Top_k values for sampling tokens (default: 10)
+    pub top_k: i64,
+    /// Temperature of the sampling (default: 1.0)
+    pub temperature: f64,
+    /// Device to place the model on (default: CUDA/GPU when available)
+    pub device: Device,
+}
+
+impl Doc2QueryConfig {
+    /// Instantiate a new doc2query configuration.
+    ///
+    /// # Arguments
+    ///
+    /// * model_resource - The `Resource` pointing to the model to load (e.g.  model.ot)
+    /// * config_resource - The `Resource' pointing to the model configuration to load (e.g. config.json)
+    /// * vocab_resource - The `Resource' pointing to the SentencePiece model to load (e.g. spiece.model)
+    pub fn new(
+        model_resource: Resource,
+        config_resource: Resource,
+        vocab_resource: Resource,
+    ) -> Doc2QueryConfig {
+        Doc2QueryConfig {
+            model_resource,
+            config_resource,
+            vocab_resource,
+            num_queries: 10,
+            max_length: 64,
+            top_k: 10,
+            temperature: 1.0,
+            device: Device::cuda_if_available(),
+        }
+    }
+}
+
+impl From<Doc2QueryConfig> for GenerateConfig {
+    fn from(config: Doc2QueryConfig) -> GenerateConfig {
+        GenerateConfig {
+            merges_resource: config.vocab_resource.clone(),
+            model_resource: config.model_resource,
+            config_resource: config.config_resource,
+            vocab_resource: config.vocab_resource,
+            min_length: 0,
+            max_length: config.max_length,
+            do_sample: true,
+            num_beams: 1,
+            temperature: config.temperature,
+            top_k: config.top_k,
+            top_p: 1.0,
+            no_repeat_ngram_size: 0,
+            num_return_sequences: config.num_queries,
+            device: config.device,
+            ..Default::default()
+        }
+    }
+}
+
+/// # Doc2QueryModel to generate synthetic queries for documents
+pub struct Doc2QueryModel {
+    generator: T5Generator,
+    num_queries: usize,
+}
+
+impl Doc2QueryModel {
+    /// Build a new `Doc2QueryModel`
+    ///
+    /// # Arguments
+    ///
+    /// * `config` - `Doc2QueryConfig` object containing the resource references (model, vocabulary, configuration), sampling options and device placement (CPU/GPU)
+    pub fn new(config: Doc2QueryConfig) -> Result<Doc2QueryModel, RustBertError> {
+        if config.num_queries < 1 {
+            return Err(RustBertError::InvalidConfigurationError(format!(
+                "The number of queries must be positive, got {}",
+                config.num_queries
+            )));
+        }
+        let num_queries = config.num_queries as usize;
+        let generator = T5Generator::new(config.into())?;
+        Ok(Doc2QueryModel {
+            generator,
+            num_queries,
+        })
+    }
+
+    /// Generates queries for a batch of documents
+    ///
+    /// # Arguments
+    ///
+    /// * `documents` - `&[&str]` Array of documents
+    ///
+    /// # Returns
+    ///
+    /// * `Vec<Vec<String>>` de-duplicated queries generated for each document, in generation order
+    pub fn generate_queries<'a, S>(&self, documents: S) -> Vec<Vec<String>>
+    where
+        S: AsRef<[&'a str]>,
+    {
+        if documents.as_ref().is_empty() {
+            return vec![];
+        }
+        let generated = self
+            .generator
+            .generate(Some(documents), None, None, None, None);
+        generated
+            .chunks(self.num_queries)
+            .map(deduplicate_queries)
+            .collect()
+    }
+}
+
+/// Removes empty and duplicate queries, two queries being duplicates if they are identical up to case, punctuation
+/// and white spaces. The first occurrence of each query is kept.
+pub fn deduplicate_queries<S: AsRef<str>>(queries: &[S]) -> Vec<String> {
+    let mut seen = HashSet::new();
+    queries
+        .iter()
+        .map(|query| query.as_ref().trim())
+        .filter(|query| {
+            let key = query
+                .split(|c: char| !c.is_alphanumeric())
+                .filter(|word| !word.is_empty())
+                .map(str::to_lowercase)
+                .collect::<Vec<_>>()
+                .join(" ");
+            !key.is_empty() && seen.insert(key)
+        })
+        .map(str::to_string)
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::resources::LocalResource;
+    use std::path::PathBuf;
+
+    #[test]
+    #[ignore] // no need to run, compilation is enough to verify it is Send
+    fn test() {
+        let resource = || {
+            Resource::Local(LocalResource {
+                local_path: PathBuf::from("path/to/resource"),
+            })
+        };
+        let config = Doc2QueryConfig::new(resource(), resource(), resource());
+        let _: Box<dyn Send> = Box::new(Doc2QueryModel::new(config));
+    }
+}
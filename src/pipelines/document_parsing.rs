@@ -0,0 +1,329 @@
+// Copyright 2021 Guillaume Becquin
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//     http://www.apache.org/licenses/LICENSE-2.0
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! # OCR-free document parsing pipeline
+//! Parses page images (receipts, forms...) into structured data without an external OCR step, following the approach
+//! of Donut ([OCR-free Document Understanding Transformer](https://arxiv.org/abs/2111.15664) Kim et al., 2021).
+//! A vision encoder-decoder generates a sequence of the form `<s_menu><s_nm>Latte</s_nm><s_price>4.50</s_price></s_menu>`
+//! from the page image and a task prompt, which is converted into a `serde_json::Value`:
+//! - `<s_{key}>...</s_{key}>` delimit the value of the field `key`. Nested fields produce JSON objects
+//! - `<sep/>` separates the elements of a list
+//! - categorical values are represented as `<{value}/>` tokens
+//!
+//! The crate does not provide a vision encoder yet: the encoder-decoder is supplied by implementing the
+//! `VisionEncoderDecoder` trait, and the pipeline handles the image pre-processing, the task prompt and the parsing of
+//! the generated sequence. The parser is also available on its own with `token_sequence_to_json`.
+//!
+//! ```no_run
+//! # fn main() -> anyhow::Result<()> {
+//! use rust_bert::pipelines::document_parsing::{
+//!     DocumentParsingConfig, DocumentParsingModel, VisionEncoderDecoder,
+//! };
+//! use rust_bert::RustBertError;
+//! use tch::{Device, Kind, Tensor};
+//!
+//! struct MyDonut;
+//!
+//! impl VisionEncoderDecoder for MyDonut {
+//!     fn generate(
+//!         &self,
+//!         pixel_values: &Tensor,
+//!         task_prompt: &str,
+//!     ) -> Result<Vec<String>, RustBertError> {
+//!         unimplemented!()
+//!     }
+//! }
+//!
+//! let model = DocumentParsingModel::new(DocumentParsingConfig::default(), Box::new(MyDonut));
+//! let page = Tensor::zeros(&[3, 1280, 960], (Kind::Uint8, Device::Cpu));
+//! let output = model.parse(&[page])?;
+//! # Ok(())
+//! # }
+//! ```
+
+use crate::common::error::RustBertError;
+use serde_json::{Map, Value};
+use tch::{Device, Kind, Tensor};
+
+/// # Vision encoder-decoder model generating a token sequence from page images
+pub trait VisionEncoderDecoder: Send {
+    /// Generates the decoded sequence (including the special tokens) for a batch of pre-processed images
+    ///
+    /// # Arguments
+    ///
+    /// * `pixel_values` - normalized images of shape (*batch size*, *channels*, *height*, *width*)
+    /// * `task_prompt` - prompt the decoder should start the generation from (e.g. `<s_cord-v2>`)
+    fn generate(
+        &self,
+        pixel_values: &Tensor,
+        task_prompt: &str,
+    ) -> Result<Vec<String>, RustBertError>;
+}
+
+/// # Configuration for DocumentParsingModel
+pub struct DocumentParsingConfig {
+    /// Image size (height, width) expected by the encoder. Pages are resized to fit, keeping their aspect ratio,
+    /// and padded (default: (2560, 1920))
+    pub image_size: (i64, i64),
+    /// Mean of each channel used for normalization (default: 0.5)
+    pub image_mean: [f64; 3],
+    /// Standard deviation of each channel used for normalization (default: 0.5)
+    pub image_std: [f64; 3],
+    /// Task prompt the decoder starts from (default: `<s_cord-v2>`, receipt parsing)
+    pub task_prompt: String,
+    /// Special tokens removed from the generated sequence before parsing (default: `</s>` and `<pad>`)
+    pub special_tokens: Vec<String>,
+    /// Device to place the pre-processed images on (default: CUDA/GPU when available)
+    pub device: Device,
+}
+
+impl Default for DocumentParsingConfig {
+    fn default() -> DocumentParsingConfig {
+        DocumentParsingConfig {
+            image_size: (2560, 1920),
+            image_mean: [0.5; 3],
+            image_std: [0.5; 3],
+            task_prompt: "<s_cord-v2>".to_string(),
+            special_tokens: vec!["</s>".to_string(), "<pad>".to_string()],
+            device: Device::cuda_if_available(),
+        }
+    }
+}
+
+/// # DocumentParsingModel to extract structured data from page images
+pub struct DocumentParsingModel {
+    config: DocumentParsingConfig,
+    model: Box<dyn VisionEncoderDecoder>,
+}
+
+impl DocumentParsingModel {
+    /// Build a new `DocumentParsingModel`
+    ///
+    /// # Arguments
+    ///
+    /// * `config` - `DocumentParsingConfig` defining the pre-processing and task prompt
+    /// * `model` - vision encoder-decoder generating the token sequences
+    pub fn new(
+        config: DocumentParsingConfig,
+        model: Box<dyn VisionEncoderDecoder>,
+    ) -> DocumentParsingModel {
+        DocumentParsingModel { config, model }
+    }
+
+    /// Resizes, pads and normalizes page images into a batch of pixel values
+    ///
+    /// # Arguments
+    ///
+    /// * `images` - images of shape (*channels*, *height*, *width*) with values in [0, 255]. Grayscale images (1 channel) are converted to RGB.
+    ///
+    /// # Returns
+    ///
+    /// * `Tensor` of shape (*batch size*, 3, *image height*, *image width*)
+    pub fn preprocess(&self, images: &[Tensor]) -> Result<Tensor, RustBertError> {
+        let (target_height, target_width) = self.config.image_size;
+        let mean = Tensor::of_slice(&self.config.image_mean).view((3, 1, 1));
+        let std = Tensor::of_slice(&self.config.image_std).view((3, 1, 1));
+        let mut pixel_values = Vec::with_capacity(images.len());
+        for image in images {
+            let size = image.size();
+            if size.len() != 3 || !(size[0] == 1 || size[0] == 3) {
+                return Err(RustBertError::ValueError(format!(
+                    "Images must be of shape (channels, height, width) with 1 or 3 channels, got {:?}",
+                    size
+                )));
+            }
+            let image = image
+                .to_kind(Kind::Double)
+                .expand(&[3, size[1], size[2]], false)
+                / 255.0;
+            let scale =
+                (target_height as f64 / size[1] as f64).min(target_width as f64 / size[2] as f64);
+            let height = ((size[1] as f64 * scale).round() as i64)
+                .max(1)
+                .min(target_height);
+            let width = ((size[2] as f64 * scale).round() as i64)
+                .max(1)
+                .min(target_width);
+            let image = image
+                .unsqueeze(0)
+                .upsample_bilinear2d(&[height, width], false, None, None)
+                .squeeze1(0);
+            let device = image.device();
+            let image = (image - mean.to_device(device)) / std.to_device(device);
+            let pad_top = (target_height - height) / 2;
+            let pad_left = (target_width - width) / 2;
+            let image = image.constant_pad_nd(&[
+                pad_left,
+                target_width - width - pad_left,
+                pad_top,
+                target_height - height - pad_top,
+            ]);
+            pixel_values.push(image.to_kind(Kind::Float));
+        }
+        Ok(Tensor::stack(&pixel_values, 0).to(self.config.device))
+    }
+
+    /// Parses page images into structured data
+    ///
+    /// # Arguments
+    ///
+    /// * `images` - images of shape (*channels*, *height*, *width*) with values in [0, 255]
+    ///
+    /// # Returns
+    ///
+    /// * `Vec<Value>` containing the parsed document for each image. If the generated sequence contains no field,
+    /// the value is an object with the raw sequence under `text_sequence`.
+    pub fn parse(&self, images: &[Tensor]) -> Result<Vec<Value>, RustBertError> {
+        if images.is_empty() {
+            return Ok(vec![]);
+        }
+        let pixel_values = self.preprocess(images)?;
+        let sequences = self
+            .model
+            .generate(&pixel_values, &self.config.task_prompt)?;
+        Ok(sequences
+            .iter()
+            .map(|sequence| token_sequence_to_json(&self.clean_sequence(sequence)))
+            .collect())
+    }
+
+    fn clean_sequence(&self, sequence: &str) -> String {
+        let mut sequence = sequence.to_string();
+        for special_token in &self.config.special_tokens {
+            sequence = sequence.replace(special_token.as_str(), "");
+        }
+        let sequence = sequence.trim();
+        match sequence.strip_prefix(self.config.task_prompt.as_str()) {
+            Some(stripped) => stripped.trim().to_string(),
+            None => sequence.to_string(),
+        }
+    }
+}
+
+/// Converts a sequence generated by a Donut-style model into a JSON value.
+///
+/// # Arguments
+///
+/// * `sequence` - generated sequence, without the task prompt and end of sequence tokens
+///
+/// # Example
+///
+/// ```no_run
+/// use rust_bert::pipelines::document_parsing::token_sequence_to_json;
+///
+/// let value = token_sequence_to_json(
+///     "<s_menu><s_nm>Latte</s_nm><s_cnt>2</s_cnt></s_menu><s_total><s_total_price>9.00</s_total_price></s_total>",
+/// );
+/// assert_eq!(value["menu"]["nm"], "Latte");
+/// assert_eq!(value["total"]["total_price"], "9.00");
+/// ```
+pub fn token_sequence_to_json(sequence: &str) -> Value {
+    match parse_fields(sequence.trim(), false) {
+        Value::Object(fields) if fields.is_empty() => {
+            let mut output = Map::new();
+            output.insert("text_sequence".to_string(), Value::String(sequence.into()));
+            Value::Object(output)
+        }
+        value => value,
+    }
+}
+
+fn parse_fields(sequence: &str, is_inner_value: bool) -> Value {
+    let mut output = Map::new();
+    let mut remaining = sequence.to_string();
+    while let Some(start_position) = remaining.find("<s_") {
+        let key_end = match remaining[start_position..].find('>') {
+            Some(offset) => start_position + offset,
+            None => break,
+        };
+        let key = remaining[start_position + 3..key_end].to_string();
+        let start_token = format!("<s_{}>", key);
+        let end_token = format!("</s_{}>", key);
+        let end_position = match remaining[key_end..].find(end_token.as_str()) {
+            Some(offset) => key_end + offset,
+            None => {
+                // Unclosed field: the start token is dropped and the parsing continues
+                remaining = remaining.replacen(start_token.as_str(), "", 1);
+                continue;
+            }
+        };
+        let content = remaining[key_end + 1..end_position].trim();
+        if content.contains("<s_") && content.contains("</s_") {
+            match parse_fields(content, true) {
+                Value::Array(mut values) if !values.is_empty() => {
+                    let value = if values.len() == 1 {
+                        values.remove(0)
+                    } else {
+                        Value::Array(values)
+                    };
+                    output.insert(key, value);
+                }
+                _ => {}
+            }
+        } else {
+            let mut leaves: Vec<Value> = content
+                .split("<sep/>")
+                .map(|leaf| {
+                    let leaf = leaf.trim();
+                    // Categorical values are represented as special tokens `<{value}/>`
+                    let leaf = if leaf.len() > 3 && leaf.starts_with('<') && leaf.ends_with("/>") {
+                        &leaf[1..leaf.len() - 2]
+                    } else {
+                        leaf
+                    };
+                    Value::String(leaf.to_string())
+                })
+                .collect();
+            let value = if leaves.len() == 1 {
+                leaves.remove(0)
+            } else {
+                Value::Array(leaves)
+            };
+            output.insert(key, value);
+        }
+        remaining = remaining[end_position + end_token.len()..]
+            .trim()
+            .to_string();
+        if let Some(next_elements) = remaining.strip_prefix("<sep/>") {
+            let mut values = vec![Value::Object(output)];
+            if let Value::Array(next_values) = parse_fields(next_elements, true) {
+                values.extend(next_values);
+            }
+            return Value::Array(values);
+        }
+    }
+    match (output.is_empty(), is_inner_value) {
+        (false, true) => Value::Array(vec![Value::Object(output)]),
+        (false, false) => Value::Object(output),
+        (true, true) => Value::Array(vec![]),
+        (true, false) => Value::Object(Map::new()),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    #[ignore] // no need to run, compilation is enough to verify it is Send
+    fn test() {
+        struct DummyModel;
+        impl VisionEncoderDecoder for DummyModel {
+            fn generate(&self, _: &Tensor, _: &str) -> Result<Vec<String>, RustBertError> {
+                Ok(vec![])
+            }
+        }
+        let _: Box<dyn Send> = Box::new(DocumentParsingModel::new(
+            DocumentParsingConfig::default(),
+            Box::new(DummyModel),
+        ));
+    }
+}
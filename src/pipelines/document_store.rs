@@ -0,0 +1,289 @@
+// Copyright 2021 Guillaume Becquin
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//     http://www.apache.org/licenses/LICENSE-2.0
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! # Document stores
+//! Storage of documents and their embeddings, searched by embedding similarity for the retrieval components of the
+//! pipelines (e.g. `rag::EmbeddingRetriever`). The `DocumentStore` trait is the extension point for external vector
+//! databases: an adapter implementing it (for example on top of a qdrant or pgvector client) can be used in place of
+//! the `InMemoryDocumentStore` reference implementation.
+//!
+//! Documents carry JSON metadata, that can be used to restrict the search with a `MetadataFilter`.
+//!
+//! ```no_run
+//! # fn main() -> anyhow::Result<()> {
+//! use rust_bert::pipelines::document_store::{
+//!     Document, DocumentStore, InMemoryDocumentStore, MetadataFilter,
+//! };
+//! use serde_json::json;
+//!
+//! let mut store = InMemoryDocumentStore::new();
+//! store.add(vec![
+//!     Document::new("1", "Paris is the capital of France.", vec![0.9, 0.1])
+//!         .with_metadata("year", json!(2021)),
+//!     Document::new("2", "Amsterdam is the capital of the Netherlands.", vec![0.1, 0.9])
+//!         .with_metadata("year", json!(2019)),
+//! ])?;
+//! let filter = MetadataFilter::Range {
+//!     key: "year".to_string(),
+//!     min: Some(2020.0),
+//!     max: None,
+//! };
+//! let results = store.search(&[1.0, 0.0], 5, Some(&filter))?;
+//! # Ok(())
+//! # }
+//! ```
+
+use crate::common::error::RustBertError;
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use std::collections::HashMap;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+/// # Document stored with its embedding
+pub struct Document {
+    /// Unique identifier of the document. Adding a document with an existing identifier replaces it
+    pub id: String,
+    /// Text of the document
+    pub text: String,
+    /// Metadata of the document
+    pub metadata: Map<String, Value>,
+    /// Embedding of the document
+    pub embedding: Vec<f32>,
+}
+
+impl Document {
+    pub fn new(id: &str, text: &str, embedding: Vec<f32>) -> Document {
+        Document {
+            id: id.to_string(),
+            text: text.to_string(),
+            metadata: Map::new(),
+            embedding,
+        }
+    }
+
+    /// Sets a metadata value of the document
+    pub fn with_metadata(mut self, key: &str, value: Value) -> Document {
+        self.metadata.insert(key.to_string(), value);
+        self
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+/// # Document returned by a search
+pub struct ScoredDocument {
+    pub document: Document,
+    /// Cosine similarity between the query and document embeddings
+    pub score: f64,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+/// # Filter on the metadata of the documents
+pub enum MetadataFilter {
+    /// The metadata value is equal to the value provided
+    Equals(String, Value),
+    /// The metadata value is one of the values provided
+    In(String, Vec<Value>),
+    /// The metadata value is a number within the bounds (inclusive)
+    Range {
+        key: String,
+        min: Option<f64>,
+        max: Option<f64>,
+    },
+    /// The metadata contains the key
+    Exists(String),
+    /// All the filters match
+    And(Vec<MetadataFilter>),
+    /// At least one of the filters matches
+    Or(Vec<MetadataFilter>),
+    /// The filter does not match
+    Not(Box<MetadataFilter>),
+}
+
+impl MetadataFilter {
+    /// Returns true if the metadata provided matches the filter
+    pub fn matches(&self, metadata: &Map<String, Value>) -> bool {
+        match self {
+            MetadataFilter::Equals(key, value) => metadata.get(key) == Some(value),
+            MetadataFilter::In(key, values) => metadata
+                .get(key)
+                .map_or(false, |value| values.contains(value)),
+            MetadataFilter::Range { key, min, max } => {
+                match metadata.get(key).and_then(Value::as_f64) {
+                    Some(value) => {
+                        min.map_or(true, |min| value >= min) && max.map_or(true, |max| value <= max)
+                    }
+                    None => false,
+                }
+            }
+            MetadataFilter::Exists(key) => metadata.contains_key(key),
+            MetadataFilter::And(filters) => filters.iter().all(|filter| filter.matches(metadata)),
+            MetadataFilter::Or(filters) => filters.iter().any(|filter| filter.matches(metadata)),
+            MetadataFilter::Not(filter) => !filter.matches(metadata),
+        }
+    }
+}
+
+/// # Storage of documents searchable by embedding
+pub trait DocumentStore: Send {
+    /// Adds documents to the store, replacing the documents with the same identifiers
+    fn add(&mut self, documents: Vec<Document>) -> Result<(), RustBertError>;
+
+    /// Returns a document by identifier
+    fn get(&self, id: &str) -> Result<Option<Document>, RustBertError>;
+
+    /// Deletes a document, returning true if it was stored
+    fn delete(&mut self, id: &str) -> Result<bool, RustBertError>;
+
+    /// Returns the number of documents stored
+    fn count(&self) -> Result<usize, RustBertError>;
+
+    /// Returns the documents most similar to an embedding
+    ///
+    /// # Arguments
+    ///
+    /// * `embedding` - query embedding
+    /// * `top_k` - maximum number of documents to return
+    /// * `filter` - optional `MetadataFilter` the documents must match
+    ///
+    /// # Returns
+    ///
+    /// * `Vec<ScoredDocument>` sorted by decreasing similarity
+    fn search(
+        &self,
+        embedding: &[f32],
+        top_k: usize,
+        filter: Option<&MetadataFilter>,
+    ) -> Result<Vec<ScoredDocument>, RustBertError>;
+}
+
+/// # In-memory document store
+/// Reference `DocumentStore` implementation, searching the documents exhaustively.
+#[derive(Debug, Clone, Default)]
+pub struct InMemoryDocumentStore {
+    documents: Vec<Document>,
+    norms: Vec<f32>,
+    positions: HashMap<String, usize>,
+}
+
+impl InMemoryDocumentStore {
+    /// Creates an empty store
+    pub fn new() -> InMemoryDocumentStore {
+        InMemoryDocumentStore::default()
+    }
+}
+
+fn norm(vector: &[f32]) -> f32 {
+    vector.iter().map(|value| value * value).sum::<f32>().sqrt()
+}
+
+impl DocumentStore for InMemoryDocumentStore {
+    fn add(&mut self, documents: Vec<Document>) -> Result<(), RustBertError> {
+        for document in documents {
+            if let Some(stored) = self.documents.first() {
+                if stored.embedding.len() != document.embedding.len() {
+                    return Err(RustBertError::ValueError(format!(
+                        "Expected an embedding of dimension {} for document {}, got {}",
+                        stored.embedding.len(),
+                        document.id,
+                        document.embedding.len()
+                    )));
+                }
+            }
+            let document_norm = norm(&document.embedding);
+            match self.positions.get(&document.id) {
+                Some(&position) => {
+                    self.documents[position] = document;
+                    self.norms[position] = document_norm;
+                }
+                None => {
+                    self.positions
+                        .insert(document.id.clone(), self.documents.len());
+                    self.documents.push(document);
+                    self.norms.push(document_norm);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn get(&self, id: &str) -> Result<Option<Document>, RustBertError> {
+        Ok(self
+            .positions
+            .get(id)
+            .map(|&position| self.documents[position].clone()))
+    }
+
+    fn delete(&mut self, id: &str) -> Result<bool, RustBertError> {
+        match self.positions.remove(id) {
+            Some(position) => {
+                self.documents.swap_remove(position);
+                self.norms.swap_remove(position);
+                if let Some(moved) = self.documents.get(position) {
+                    self.positions.insert(moved.id.clone(), position);
+                }
+                Ok(true)
+            }
+            None => Ok(false),
+        }
+    }
+
+    fn count(&self) -> Result<usize, RustBertError> {
+        Ok(self.documents.len())
+    }
+
+    fn search(
+        &self,
+        embedding: &[f32],
+        top_k: usize,
+        filter: Option<&MetadataFilter>,
+    ) -> Result<Vec<ScoredDocument>, RustBertError> {
+        if let Some(stored) = self.documents.first() {
+            if stored.embedding.len() != embedding.len() {
+                return Err(RustBertError::ValueError(format!(
+                    "Expected a query embedding of dimension {}, got {}",
+                    stored.embedding.len(),
+                    embedding.len()
+                )));
+            }
+        }
+        let query_norm = norm(embedding);
+        let mut scores = self
+            .documents
+            .iter()
+            .zip(self.norms.iter())
+            .enumerate()
+            .filter(|(_, (document, _))| {
+                filter.map_or(true, |filter| filter.matches(&document.metadata))
+            })
+            .map(|(position, (document, document_norm))| {
+                let dot = document
+                    .embedding
+                    .iter()
+                    .zip(embedding.iter())
+                    .map(|(value_1, value_2)| value_1 * value_2)
+                    .sum::<f32>();
+                (
+                    position,
+                    (dot / (document_norm * query_norm).max(f32::EPSILON)) as f64,
+                )
+            })
+            .collect::<Vec<_>>();
+        scores.sort_by(|(_, score_1), (_, score_2)| score_2.partial_cmp(score_1).unwrap());
+        Ok(scores
+            .into_iter()
+            .take(top_k)
+            .map(|(position, score)| ScoredDocument {
+                document: self.documents[position].clone(),
+                score,
+            })
+            .collect())
+    }
+}
@@ -0,0 +1,339 @@
+// Copyright 2021 Guillaume Becquin
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//     http://www.apache.org/licenses/LICENSE-2.0
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! # Entity linking
+//! Maps the entities mentioned in a text to the identifiers of a knowledge base, in two stages:
+//! - candidate retrieval: the mention, marked within its context (`... [START] Paris [END] ...`), is used as a query
+//! to a `Retriever` over the knowledge base entries. An `EmbeddingRetriever` with a bi-encoder gives a dense index
+//! of the entries, and can be built over an existing embedding index with `EmbeddingRetriever::with_store`.
+//! - disambiguation (optional): the candidates are re-scored by a `CrossEncoder` reading each mention and entry
+//! pair jointly. Regression `SequenceClassificationModel`s (cross-encoder rerankers with a single output) and the
+//! `NextSentencePredictionModel` can be used as cross-encoders, and custom pair classifiers can implement the trait.
+//!
+//! The knowledge base entries are `Passage`s: the passage identifier is the knowledge base identifier, the title the
+//! name of the entity and the text its description. Mentions can be built from the output of the `NERModel`, or
+//! extracted and linked in a single call with `EntityLinker::link_text`.
+//!
+//! ```no_run
+//! # fn main() -> anyhow::Result<()> {
+//! use rust_bert::pipelines::entity_linking::{EntityLinker, EntityLinkingConfig};
+//! use rust_bert::pipelines::feature_extraction::{FeatureExtractionConfig, FeatureExtractionModel};
+//! use rust_bert::pipelines::ner::NERModel;
+//! use rust_bert::pipelines::next_sentence_prediction::NextSentencePredictionModel;
+//! use rust_bert::pipelines::rag::{EmbeddingRetriever, Passage};
+//!
+//! let embedder = FeatureExtractionModel::new(FeatureExtractionConfig::default())?;
+//! let mut retriever = EmbeddingRetriever::new(embedder);
+//! retriever.add_passages(vec![
+//!     Passage {
+//!         title: Some("Paris".to_string()),
+//!         ..Passage::new("Q90", "Capital and largest city of France.")
+//!     },
+//!     Passage {
+//!         title: Some("Paris Hilton".to_string()),
+//!         ..Passage::new("Q47899", "American media personality and businesswoman.")
+//!     },
+//! ])?;
+//!
+//! let linker = EntityLinker::new(EntityLinkingConfig::default(), Box::new(retriever))
+//!     .with_cross_encoder(Box::new(NextSentencePredictionModel::new(Default::default())?));
+//! let ner_model = NERModel::new(Default::default())?;
+//! let linked_entities = linker.link_text(&ner_model, &["Amy lives in Paris, France."])?;
+//! # Ok(())
+//! # }
+//! ```
+
+use crate::common::error::RustBertError;
+use crate::pipelines::ner::{Entity, NERModel};
+use crate::pipelines::next_sentence_prediction::NextSentencePredictionModel;
+use crate::pipelines::rag::{RetrievedPassage, Retriever};
+use crate::pipelines::sequence_classification::SequenceClassificationModel;
+
+/// # Scoring of text pairs read jointly by a model
+pub trait CrossEncoder: Send {
+    /// Returns a relevance score for each (mention, knowledge base entry) pair, higher is more relevant
+    fn score_pairs(&self, pairs: &[(&str, &str)]) -> Result<Vec<f64>, RustBertError>;
+}
+
+impl CrossEncoder for NextSentencePredictionModel {
+    fn score_pairs(&self, pairs: &[(&str, &str)]) -> Result<Vec<f64>, RustBertError> {
+        Ok(self.predict(pairs, 256))
+    }
+}
+
+impl CrossEncoder for SequenceClassificationModel {
+    fn score_pairs(&self, pairs: &[(&str, &str)]) -> Result<Vec<f64>, RustBertError> {
+        self.predict_pair_regression(pairs)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+/// # Mention of an entity in a text
+pub struct Mention {
+    /// Text of the mention
+    pub text: String,
+    /// Entity type predicted for the mention (e.g. `LOC`), if known
+    pub label: Option<String>,
+    /// Text containing the mention
+    pub context: String,
+    /// Byte offsets (start, end) of the mention in the context, if known
+    pub offset: Option<(usize, usize)>,
+}
+
+impl Mention {
+    /// Creates a new mention, locating its first occurrence in the context
+    pub fn new(text: &str, context: &str) -> Mention {
+        Mention {
+            text: text.to_string(),
+            label: None,
+            context: context.to_string(),
+            offset: context.find(text).map(|start| (start, start + text.len())),
+        }
+    }
+
+    /// Builds the mentions from the entities predicted by a `NERModel` for a text. Consecutive entities of the same
+    /// type separated by whitespace only are merged into a single mention, unless the second one starts a new entity
+    /// (`B-` label).
+    ///
+    /// # Arguments
+    ///
+    /// * `entities` - entities predicted for the text, in order
+    /// * `context` - text the entities were predicted for
+    pub fn from_entities(entities: &[Entity], context: &str) -> Vec<Mention> {
+        let mut mentions: Vec<Mention> = vec![];
+        let mut cursor = 0;
+        for entity in entities {
+            let (begins, entity_type) = split_label(&entity.label);
+            let offset = context[cursor..]
+                .find(entity.word.as_str())
+                .map(|start| (cursor + start, cursor + start + entity.word.len()));
+            if let Some((start, end)) = offset {
+                cursor = end;
+                if let Some(previous) = mentions.last_mut() {
+                    if let Some((previous_start, previous_end)) = previous.offset {
+                        if !begins
+                            && previous.label.as_deref() == Some(entity_type)
+                            && context[previous_end..start].trim().is_empty()
+                        {
+                            previous.offset = Some((previous_start, end));
+                            previous.text = context[previous_start..end].to_string();
+                            continue;
+                        }
+                    }
+                }
+            }
+            mentions.push(Mention {
+                text: entity.word.clone(),
+                label: Some(entity_type.to_string()),
+                context: context.to_string(),
+                offset,
+            });
+        }
+        mentions
+    }
+
+    /// Builds the retrieval query of the mention: the mention surrounded by `[START]` and `[END]` markers, with up
+    /// to `context_window` characters of context on each side. Mentions without offsets are followed by the context.
+    pub fn query(&self, context_window: usize) -> String {
+        match self.offset {
+            Some((start, end)) => {
+                let left = self.context[..start].chars().rev().take(context_window);
+                let left = left
+                    .collect::<Vec<char>>()
+                    .into_iter()
+                    .rev()
+                    .collect::<String>();
+                let right = self.context[end..]
+                    .chars()
+                    .take(context_window)
+                    .collect::<String>();
+                format!(
+                    "{}[START] {} [END]{}",
+                    left,
+                    &self.context[start..end],
+                    right
+                )
+                .trim()
+                .to_string()
+            }
+            None if self.context.is_empty() => self.text.clone(),
+            None => format!("[START] {} [END] {}", self.text, self.context),
+        }
+    }
+}
+
+fn split_label(label: &str) -> (bool, &str) {
+    match label.split_at(label.find('-').map_or(0, |position| position + 1)) {
+        ("B-", entity_type) => (true, entity_type),
+        ("I-", entity_type) | ("E-", entity_type) | ("S-", entity_type) => (false, entity_type),
+        _ => (false, label),
+    }
+}
+
+/// # Configuration for entity linking
+#[derive(Debug, Clone)]
+pub struct EntityLinkingConfig {
+    /// Number of knowledge base candidates retrieved for each mention (default: 10)
+    pub num_candidates: usize,
+    /// Number of characters of context kept on each side of the mention in the queries (default: 100)
+    pub context_window: usize,
+    /// Minimum score of the best candidate for the mention to be linked, the mention being left unlinked (NIL)
+    /// otherwise. The score is the cross-encoder score if a cross-encoder is set, the retrieval score otherwise.
+    /// (default: None, the best candidate is always linked)
+    pub min_score: Option<f64>,
+}
+
+impl Default for EntityLinkingConfig {
+    fn default() -> EntityLinkingConfig {
+        EntityLinkingConfig {
+            num_candidates: 10,
+            context_window: 100,
+            min_score: None,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+/// # Mention linked to a knowledge base entry
+pub struct LinkedEntity {
+    /// Mention of the entity
+    pub mention: Mention,
+    /// Identifier of the knowledge base entry, None if no candidate reached the minimum score
+    pub kb_id: Option<String>,
+    /// Score of the best candidate
+    pub score: Option<f64>,
+    /// Candidates retrieved for the mention, with their final scores, sorted by decreasing score
+    pub candidates: Vec<RetrievedPassage>,
+}
+
+impl LinkedEntity {
+    /// Returns the knowledge base entry the mention is linked to
+    pub fn entry(&self) -> Option<&RetrievedPassage> {
+        self.kb_id.as_ref().and(self.candidates.first())
+    }
+}
+
+/// # EntityLinker mapping entity mentions to knowledge base identifiers
+pub struct EntityLinker {
+    retriever: Box<dyn Retriever>,
+    cross_encoder: Option<Box<dyn CrossEncoder>>,
+    num_candidates: usize,
+    context_window: usize,
+    min_score: Option<f64>,
+}
+
+impl EntityLinker {
+    /// Build a new `EntityLinker`, linking the mentions to the best candidate retrieved
+    ///
+    /// # Arguments
+    ///
+    /// * `config` - `EntityLinkingConfig` object containing the linking settings
+    /// * `retriever` - `Retriever` returning the knowledge base candidates of each mention
+    pub fn new(config: EntityLinkingConfig, retriever: Box<dyn Retriever>) -> EntityLinker {
+        EntityLinker {
+            retriever,
+            cross_encoder: None,
+            num_candidates: config.num_candidates,
+            context_window: config.context_window,
+            min_score: config.min_score,
+        }
+    }
+
+    /// Sets the cross-encoder re-scoring the retrieved candidates
+    pub fn with_cross_encoder(mut self, cross_encoder: Box<dyn CrossEncoder>) -> EntityLinker {
+        self.cross_encoder = Some(cross_encoder);
+        self
+    }
+
+    /// Links mentions to the knowledge base
+    ///
+    /// # Arguments
+    ///
+    /// * `mentions` - `&[Mention]` Array of mentions to link
+    ///
+    /// # Returns
+    ///
+    /// * `Vec<LinkedEntity>` with the knowledge base identifier and candidates of each mention
+    pub fn link(&self, mentions: &[Mention]) -> Result<Vec<LinkedEntity>, RustBertError> {
+        mentions
+            .iter()
+            .map(|mention| {
+                let query = mention.query(self.context_window);
+                let mut candidates = self.retriever.retrieve(&query, self.num_candidates)?;
+                if let (Some(cross_encoder), false) = (&self.cross_encoder, candidates.is_empty()) {
+                    let entries = candidates
+                        .iter()
+                        .map(|candidate| match &candidate.passage.title {
+                            Some(title) => format!("{}: {}", title, candidate.passage.text),
+                            None => candidate.passage.text.clone(),
+                        })
+                        .collect::<Vec<String>>();
+                    let pairs = entries
+                        .iter()
+                        .map(|entry| (query.as_str(), entry.as_str()))
+                        .collect::<Vec<(&str, &str)>>();
+                    let scores = cross_encoder.score_pairs(&pairs)?;
+                    if scores.len() != candidates.len() {
+                        return Err(RustBertError::ValueError(format!(
+                            "The cross-encoder returned {} scores for {} candidates",
+                            scores.len(),
+                            candidates.len()
+                        )));
+                    }
+                    for (candidate, score) in candidates.iter_mut().zip(scores) {
+                        candidate.score = score;
+                    }
+                }
+                candidates.sort_by(|candidate_1, candidate_2| {
+                    candidate_2
+                        .score
+                        .partial_cmp(&candidate_1.score)
+                        .unwrap_or(std::cmp::Ordering::Equal)
+                });
+                let score = candidates.first().map(|candidate| candidate.score);
+                let kb_id = candidates
+                    .first()
+                    .filter(|candidate| self.min_score.map_or(true, |min| candidate.score >= min))
+                    .map(|candidate| candidate.passage.id.clone());
+                Ok(LinkedEntity {
+                    mention: mention.clone(),
+                    kb_id,
+                    score,
+                    candidates,
+                })
+            })
+            .collect()
+    }
+
+    /// Extracts the entities of texts with a `NERModel` and links them to the knowledge base
+    ///
+    /// # Arguments
+    ///
+    /// * `ner_model` - `NERModel` extracting the mentions
+    /// * `texts` - `&[&str]` Array of texts
+    ///
+    /// # Returns
+    ///
+    /// * `Vec<Vec<LinkedEntity>>` with the linked entities of each text
+    pub fn link_text(
+        &self,
+        ner_model: &NERModel,
+        texts: &[&str],
+    ) -> Result<Vec<Vec<LinkedEntity>>, RustBertError> {
+        texts
+            .iter()
+            .map(|text| {
+                let entities = ner_model.predict(&[*text]);
+                self.link(&Mention::from_entities(&entities, text))
+            })
+            .collect()
+    }
+}
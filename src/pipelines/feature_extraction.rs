@@ -0,0 +1,941 @@
+// Copyright 2021 Guillaume Becquin
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//     http://www.apache.org/licenses/LICENSE-2.0
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! # Feature extraction pipeline
+//! Extracts the hidden states of selected layers of an encoder for each sub-token of the input, and aggregates them at
+//! the word level. The features can be used as inputs of downstream models (CRF taggers, probing classifiers...).
+//! The layers are indexed from the output of the embeddings (layer 0) to the output of the last encoder layer
+//! (`num_hidden_layers`), negative indices counting from the last layer (e.g. `[-4, -3, -2, -1]` for the last 4 layers).
+//! The features of the selected layers are concatenated, averaged or summed.
+//!
+//! The input embedding matrix of the model, or static word embeddings averaged from the contextual features over a
+//! corpus, can be exported to the word2vec text format with `StaticEmbeddings`.
+//!
+//! Supported architectures: BERT, DistilBERT, RoBERTa, XLM-RoBERTa, ELECTRA and ALBERT.
+//!
+//! ```no_run
+//! # fn main() -> anyhow::Result<()> {
+//! use rust_bert::pipelines::feature_extraction::{
+//!     FeatureExtractionConfig, FeatureExtractionModel, LayerAggregation,
+//! };
+//!
+//! let config = FeatureExtractionConfig {
+//!     layers: vec![-4, -3, -2, -1],
+//!     layer_aggregation: LayerAggregation::Concatenate,
+//!     ..Default::default()
+//! };
+//! let feature_extraction_model = FeatureExtractionModel::new(config)?;
+//! let input = ["Tokenization is the first step."];
+//! let features = feature_extraction_model.extract_features(&input)?;
+//! // (number of words, 4 x hidden size)
+//! let word_features = &features[0].word_features;
+//! # Ok(())
+//! # }
+//! ```
+
+use crate::albert::AlbertModel;
+use crate::bert::{
+    BertConfigResources, BertEmbeddings, BertModel, BertModelResources, BertVocabResources,
+};
+use crate::common::error::RustBertError;
+use crate::common::resources::{RemoteResource, Resource};
+use crate::common::weights::reload_var_store;
+use crate::distilbert::DistilBertModel;
+use crate::electra::ElectraModel;
+use crate::pipelines::common::{
+    prefetch_batches, ConfigOption, ModelType, TensorBatch, TokenizerOption,
+};
+use crate::roberta::RobertaEmbeddings;
+use rust_tokenizers::tokenizer::TruncationStrategy;
+use rust_tokenizers::{Mask, Offset, TokenizedInput};
+use std::borrow::Borrow;
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+use tch::nn::VarStore;
+use tch::{nn, no_grad, Device, Kind, Tensor};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+/// # Aggregation of the features of the selected layers
+pub enum LayerAggregation {
+    /// Concatenates the features of the layers (feature dimension: number of layers x hidden size)
+    Concatenate,
+    /// Averages the features of the layers
+    Mean,
+    /// Sums the features of the layers
+    Sum,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+/// # Aggregation of the sub-token features into word features
+pub enum WordAggregation {
+    /// The features of the first sub-token are used for the entire word
+    First,
+    /// The features of the sub-tokens of the word are averaged
+    Mean,
+}
+
+/// # Configuration for FeatureExtractionModel
+/// Contains information regarding the model to load, the layers to extract and device to place the model on.
+pub struct FeatureExtractionConfig {
+    /// Model type
+    pub model_type: ModelType,
+    /// Model weights resource (default: pretrained BERT model)
+    pub model_resource: Resource,
+    /// Config resource (default: pretrained BERT model)
+    pub config_resource: Resource,
+    /// Vocab resource (default: pretrained BERT model)
+    pub vocab_resource: Resource,
+    /// Merges resource (default: None)
+    pub merges_resource: Option<Resource>,
+    /// Automatically lower case all input upon tokenization (assumes a lower-cased model)
+    pub lower_case: bool,
+    /// Flag indicating if the tokenizer should strip accents (normalization). Only used for BERT / ALBERT models
+    pub strip_accents: Option<bool>,
+    /// Flag indicating if the tokenizer should add a white space before each tokenized input (needed for some Roberta models)
+    pub add_prefix_space: Option<bool>,
+    /// Device to place the model on (default: CUDA/GPU when available)
+    pub device: Device,
+    /// Copy the inputs to the device through pinned host memory, asynchronously (default: false). Only used for
+    /// CUDA devices, see `pipelines::common::to_device`
+    pub pinned_memory: bool,
+    /// Layers to extract, from 0 (embeddings) to `num_hidden_layers` (last layer). Negative values index from the last
+    /// layer (default: `[-1]`)
+    pub layers: Vec<i64>,
+    /// Aggregation of the selected layers (default: `LayerAggregation::Concatenate`)
+    pub layer_aggregation: LayerAggregation,
+    /// Aggregation of the sub-tokens into words (default: `WordAggregation::Mean`)
+    pub word_aggregation: WordAggregation,
+    /// Maximum length of the tokenized inputs, longer inputs are truncated (default: 512)
+    pub max_length: usize,
+}
+
+impl FeatureExtractionConfig {
+    /// Instantiate a new feature extraction configuration of the supplied type, extracting the last layer.
+    ///
+    /// # Arguments
+    ///
+    /// * `model_type` - `ModelType` indicating the model type to load (must match with the actual data to be loaded!)
+    /// * model - The `Resource` pointing to the model to load (e.g.  model.ot)
+    /// * config - The `Resource' pointing to the model configuration to load (e.g. config.json)
+    /// * vocab - The `Resource' pointing to the tokenizer's vocabulary to load (e.g.  vocab.txt/vocab.json)
+    /// * merges - An optional `Resource` (`Option<Resource>`) pointing to the tokenizer's merge file to load (e.g.  merges.txt), needed only for Roberta.
+    /// * lower_case - A `bool' indicating whether the tokenizer should lower case all input (in case of a lower-cased model)
+    pub fn new(
+        model_type: ModelType,
+        model_resource: Resource,
+        config_resource: Resource,
+        vocab_resource: Resource,
+        merges_resource: Option<Resource>,
+        lower_case: bool,
+        strip_accents: impl Into<Option<bool>>,
+        add_prefix_space: impl Into<Option<bool>>,
+    ) -> FeatureExtractionConfig {
+        FeatureExtractionConfig {
+            model_type,
+            model_resource,
+            config_resource,
+            vocab_resource,
+            merges_resource,
+            lower_case,
+            strip_accents: strip_accents.into(),
+            add_prefix_space: add_prefix_space.into(),
+            device: Device::cuda_if_available(),
+            pinned_memory: false,
+            layers: vec![-1],
+            layer_aggregation: LayerAggregation::Concatenate,
+            word_aggregation: WordAggregation::Mean,
+            max_length: 512,
+        }
+    }
+}
+
+impl Default for FeatureExtractionConfig {
+    /// Provides a default BERT base (uncased) model extracting the last layer
+    fn default() -> FeatureExtractionConfig {
+        FeatureExtractionConfig::new(
+            ModelType::Bert,
+            Resource::Remote(RemoteResource::from_pretrained(BertModelResources::BERT)),
+            Resource::Remote(RemoteResource::from_pretrained(BertConfigResources::BERT)),
+            Resource::Remote(RemoteResource::from_pretrained(BertVocabResources::BERT)),
+            None,
+            true,
+            None,
+            None,
+        )
+    }
+}
+
+/// # Abstraction that holds one particular encoder model, for any of the supported models
+pub enum FeatureExtractionOption {
+    /// Bert encoder
+    Bert(BertModel<BertEmbeddings>),
+    /// DistilBert encoder
+    DistilBert(DistilBertModel),
+    /// Roberta encoder
+    Roberta(BertModel<RobertaEmbeddings>),
+    /// XLM Roberta encoder
+    XLMRoberta(BertModel<RobertaEmbeddings>),
+    /// Electra encoder
+    Electra(ElectraModel),
+    /// Albert encoder
+    Albert(AlbertModel),
+}
+
+impl FeatureExtractionOption {
+    /// Instantiate a new encoder of the supplied type. The configuration must enable the output of the hidden states.
+    ///
+    /// # Arguments
+    ///
+    /// * `model_type` - `ModelType` indicating the model type to load (must match with the actual data to be loaded)
+    /// * `p` - `tch::nn::Path` path to the model file to load (e.g. model.ot)
+    /// * `config` - A configuration (the model type of the configuration must be compatible with the value for
+    /// `model_type`)
+    pub fn new<'p, P>(
+        model_type: ModelType,
+        p: P,
+        config: &ConfigOption,
+    ) -> Result<Self, RustBertError>
+    where
+        P: Borrow<nn::Path<'p>>,
+    {
+        let p = p.borrow();
+        match (model_type, config) {
+            (ModelType::Bert, ConfigOption::Bert(config)) => Ok(FeatureExtractionOption::Bert(
+                BertModel::new_with_optional_pooler(p / "bert", config, false),
+            )),
+            (ModelType::DistilBert, ConfigOption::DistilBert(config)) => Ok(
+                FeatureExtractionOption::DistilBert(DistilBertModel::new(p, config)),
+            ),
+            (ModelType::Roberta, ConfigOption::Bert(config)) => {
+                Ok(FeatureExtractionOption::Roberta(
+                    BertModel::new_with_optional_pooler(p / "roberta", config, false),
+                ))
+            }
+            (ModelType::XLMRoberta, ConfigOption::Bert(config)) => {
+                Ok(FeatureExtractionOption::XLMRoberta(
+                    BertModel::new_with_optional_pooler(p / "roberta", config, false),
+                ))
+            }
+            (ModelType::Electra, ConfigOption::Electra(config)) => Ok(
+                FeatureExtractionOption::Electra(ElectraModel::new(p / "electra", config)),
+            ),
+            (ModelType::Albert, ConfigOption::Albert(config)) => Ok(
+                FeatureExtractionOption::Albert(AlbertModel::new(p / "albert", config)),
+            ),
+            _ => Err(RustBertError::InvalidConfigurationError(format!(
+                "Feature extraction is not implemented for {:?}, or the configuration provided does not match this model type",
+                model_type
+            ))),
+        }
+    }
+
+    /// Returns the `ModelType` for this FeatureExtractionOption
+    pub fn model_type(&self) -> ModelType {
+        match *self {
+            Self::Bert(_) => ModelType::Bert,
+            Self::DistilBert(_) => ModelType::DistilBert,
+            Self::Roberta(_) => ModelType::Roberta,
+            Self::XLMRoberta(_) => ModelType::XLMRoberta,
+            Self::Electra(_) => ModelType::Electra,
+            Self::Albert(_) => ModelType::Albert,
+        }
+    }
+
+    /// Forward pass through the encoder, returning the output of the embeddings followed by the output of each layer
+    pub fn forward_t(
+        &self,
+        input_ids: &Tensor,
+        mask: &Tensor,
+        train: bool,
+    ) -> Result<Vec<Tensor>, RustBertError> {
+        let (hidden_state, all_hidden_states) = match self {
+            Self::Bert(ref model) => {
+                let output = model.forward_t(
+                    Some(input_ids.copy()),
+                    Some(mask.copy()),
+                    None,
+                    None,
+                    None,
+                    &None,
+                    &None,
+                    train,
+                )?;
+                (output.hidden_state, output.all_hidden_states)
+            }
+            Self::Roberta(ref model) | Self::XLMRoberta(ref model) => {
+                let output = model.forward_t(
+                    Some(input_ids.copy()),
+                    Some(mask.copy()),
+                    None,
+                    None,
+                    None,
+                    &None,
+                    &None,
+                    train,
+                )?;
+                (output.hidden_state, output.all_hidden_states)
+            }
+            Self::DistilBert(ref model) => {
+                let output =
+                    model.forward_t(Some(input_ids.copy()), Some(mask.copy()), None, train)?;
+                (output.hidden_state, output.all_hidden_states)
+            }
+            Self::Electra(ref model) => {
+                let output = model.forward_t(
+                    Some(input_ids.copy()),
+                    Some(mask.copy()),
+                    None,
+                    None,
+                    None,
+                    train,
+                )?;
+                (output.hidden_state, output.all_hidden_states)
+            }
+            Self::Albert(ref model) => {
+                let output = model.forward_t(
+                    Some(input_ids.copy()),
+                    Some(mask.copy()),
+                    None,
+                    None,
+                    None,
+                    train,
+                )?;
+                (output.hidden_state, output.all_hidden_states)
+            }
+        };
+        let mut layers = all_hidden_states.unwrap_or_default();
+        layers.push(hidden_state);
+        Ok(layers)
+    }
+}
+
+/// # Features extracted for an input text
+pub struct Features {
+    /// Features of each sub-token, including special tokens, of shape (*number of tokens*, *feature dimension*)
+    pub token_features: Tensor,
+    /// Ids of the sub-tokens
+    pub token_ids: Vec<i64>,
+    /// Offsets of the sub-tokens in the input text (`None` for special tokens)
+    pub token_offsets: Vec<Option<Offset>>,
+    /// Index of the word each sub-token belongs to (`None` for special tokens)
+    pub word_ids: Vec<Option<usize>>,
+    /// Features of each word, of shape (*number of words*, *feature dimension*)
+    pub word_features: Tensor,
+}
+
+/// # FeatureExtractionModel to extract layer-wise features of texts
+pub struct FeatureExtractionModel {
+    tokenizer: TokenizerOption,
+    encoder: FeatureExtractionOption,
+    var_store: VarStore,
+    layers: Vec<usize>,
+    layer_aggregation: LayerAggregation,
+    word_aggregation: WordAggregation,
+    max_length: usize,
+    pinned_memory: bool,
+}
+
+impl FeatureExtractionModel {
+    /// Build a new `FeatureExtractionModel`
+    ///
+    /// # Arguments
+    ///
+    /// * `config` - `FeatureExtractionConfig` object containing the resource references (model, vocabulary, configuration), layers selection and device placement (CPU/GPU)
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// # fn main() -> anyhow::Result<()> {
+    /// use rust_bert::pipelines::feature_extraction::FeatureExtractionModel;
+    ///
+    /// let feature_extraction_model = FeatureExtractionModel::new(Default::default())?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn new(config: FeatureExtractionConfig) -> Result<FeatureExtractionModel, RustBertError> {
+        let config_path = config.config_resource.get_local_path()?;
+        let vocab_path = config.vocab_resource.get_local_path()?;
+        let weights_path = config.model_resource.get_local_path()?;
+        let merges_path = if let Some(merges_resource) = &config.merges_resource {
+            Some(merges_resource.get_local_path()?)
+        } else {
+            None
+        };
+
+        let tokenizer = TokenizerOption::from_file(
+            config.model_type,
+            vocab_path.to_str().unwrap(),
+            merges_path.as_deref().map(|path| path.to_str().unwrap()),
+            config.lower_case,
+            config.strip_accents,
+            config.add_prefix_space,
+        )?;
+        let mut var_store = VarStore::new(config.device);
+        let mut model_config = ConfigOption::try_from_file(config.model_type, config_path)?;
+        tokenizer.check_model_compatibility(config.model_type, model_config.get_vocab_size())?;
+        let num_hidden_layers = match model_config {
+            ConfigOption::Bert(ref mut config) => {
+                config.output_hidden_states = Some(true);
+                config.num_hidden_layers
+            }
+            ConfigOption::DistilBert(ref mut config) => {
+                config.output_hidden_states = Some(true);
+                config.n_layers
+            }
+            ConfigOption::Electra(ref mut config) => {
+                config.output_hidden_states = Some(true);
+                config.num_hidden_layers
+            }
+            ConfigOption::Albert(ref mut config) => {
+                config.output_hidden_states = Some(true);
+                config.num_hidden_layers
+            }
+            _ => 0,
+        };
+        let encoder =
+            FeatureExtractionOption::new(config.model_type, &var_store.root(), &model_config)?;
+        let layers = resolve_layers(&config.layers, num_hidden_layers)?;
+        var_store.load(weights_path)?;
+        Ok(FeatureExtractionModel {
+            tokenizer,
+            encoder,
+            var_store,
+            layers,
+            layer_aggregation: config.layer_aggregation,
+            word_aggregation: config.word_aggregation,
+            max_length: config.max_length,
+            pinned_memory: config.pinned_memory,
+        })
+    }
+
+    /// Reloads the weights of the model from the resource provided, keeping the configuration unchanged.
+    ///
+    /// # Arguments
+    ///
+    /// * `weights_resource` - `Resource` pointing to the new weights (e.g. model.ot)
+    pub fn reload_weights(&mut self, weights_resource: &Resource) -> Result<(), RustBertError> {
+        reload_var_store(&mut self.var_store, weights_resource)
+    }
+
+    /// Returns the input (static) embeddings of the vocabulary of the model
+    pub fn input_embeddings(&self) -> Result<StaticEmbeddings, RustBertError> {
+        StaticEmbeddings::from_var_store(&self.var_store, &self.tokenizer)
+    }
+
+    /// Computes static word embeddings by averaging the contextual word features extracted over a corpus. The words
+    /// are the surface forms found in the corpus, sorted by decreasing frequency.
+    ///
+    /// # Arguments
+    ///
+    /// * `corpus` - `&[&str]` Array of texts
+    /// * `batch_size` - number of texts processed at once
+    /// * `min_count` - minimum number of occurrences of a word to be included
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// # fn main() -> anyhow::Result<()> {
+    /// # use rust_bert::pipelines::feature_extraction::FeatureExtractionModel;
+    /// let feature_extraction_model = FeatureExtractionModel::new(Default::default())?;
+    /// let corpus = ["The bank of the river.", "The bank approved the loan."];
+    /// let embeddings = feature_extraction_model.contextual_embeddings(&corpus, 32, 1)?;
+    /// embeddings.save("path/to/embeddings.vec")?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn contextual_embeddings(
+        &self,
+        corpus: &[&str],
+        batch_size: usize,
+        min_count: usize,
+    ) -> Result<StaticEmbeddings, RustBertError> {
+        let tokenizer = &self.tokenizer;
+        let max_length = self.max_length;
+        let device = self.var_store.device();
+        let pinned_memory = self.pinned_memory;
+        let mut words: HashMap<String, (Tensor, usize)> = HashMap::new();
+        prefetch_batches(
+            corpus,
+            batch_size,
+            |batch| tokenize_batch(tokenizer, max_length, batch, device, pinned_memory),
+            |batch, (tokenized_input, input_batch)| {
+                let batch_features = self.features_from_input(tokenized_input, &input_batch)?;
+                for (text, features) in batch.iter().zip(batch_features) {
+                    let text_chars = text.chars().collect::<Vec<char>>();
+                    for (word_index, word) in word_surfaces(&features, &text_chars)
+                        .into_iter()
+                        .enumerate()
+                    {
+                        if word.is_empty() {
+                            continue;
+                        }
+                        let word_features = features.word_features.get(word_index as i64);
+                        let entry = words
+                            .entry(word)
+                            .or_insert_with(|| (word_features.zeros_like(), 0));
+                        entry.0 += word_features;
+                        entry.1 += 1;
+                    }
+                }
+                Ok(())
+            },
+        )?;
+        let mut words = words
+            .into_iter()
+            .filter(|(_, (_, count))| *count >= min_count)
+            .collect::<Vec<_>>();
+        words.sort_by(|(word_1, (_, count_1)), (word_2, (_, count_2))| {
+            count_2.cmp(count_1).then(word_1.cmp(word_2))
+        });
+        let (words, vectors): (Vec<String>, Vec<Tensor>) = words
+            .into_iter()
+            .map(|(word, (sum, count))| (word, sum / count as f64))
+            .unzip();
+        let vectors = if vectors.is_empty() {
+            Tensor::zeros(&[0, 0], (Kind::Float, Device::Cpu))
+        } else {
+            Tensor::stack(&vectors, 0)
+        };
+        StaticEmbeddings::new(words, vectors)
+    }
+
+    fn prepare_for_model(&self, input: &[&str]) -> (Vec<TokenizedInput>, TensorBatch) {
+        tokenize_batch(
+            &self.tokenizer,
+            self.max_length,
+            input,
+            self.var_store.device(),
+            self.pinned_memory,
+        )
+    }
+
+    /// Extracts the features of a batch of texts
+    ///
+    /// # Arguments
+    ///
+    /// * `input` - `&[&str]` Array of texts
+    ///
+    /// # Returns
+    ///
+    /// * `Vec<Features>` containing the sub-token and word features of each text, on the CPU
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// # fn main() -> anyhow::Result<()> {
+    /// # use rust_bert::pipelines::feature_extraction::FeatureExtractionModel;
+    /// let feature_extraction_model = FeatureExtractionModel::new(Default::default())?;
+    /// let input = ["Hello, world!", "Extracting features."];
+    /// let features = feature_extraction_model.extract_features(&input)?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn extract_features(&self, input: &[&str]) -> Result<Vec<Features>, RustBertError> {
+        if input.is_empty() {
+            return Ok(vec![]);
+        }
+        let (tokenized_input, input_batch) = self.prepare_for_model(input);
+        self.features_from_input(tokenized_input, &input_batch)
+    }
+
+    fn features_from_input(
+        &self,
+        tokenized_input: Vec<TokenizedInput>,
+        input_batch: &TensorBatch,
+    ) -> Result<Vec<Features>, RustBertError> {
+        let features = no_grad(|| self.layer_features(input_batch))?.to(Device::Cpu);
+
+        Ok(tokenized_input
+            .into_iter()
+            .enumerate()
+            .map(|(sentence_index, tokenized)| {
+                let num_tokens = tokenized.token_ids.len() as i64;
+                let token_features = features.get(sentence_index as i64).narrow(0, 0, num_tokens);
+                let word_ids = word_ids(&tokenized.mask);
+                let word_features = self.word_features(&token_features, &word_ids);
+                Features {
+                    token_features,
+                    token_ids: tokenized.token_ids,
+                    token_offsets: tokenized.token_offsets,
+                    word_ids,
+                    word_features,
+                }
+            })
+            .collect())
+    }
+
+    /// Computes a fixed-size embedding for each text by averaging the features of its sub-tokens (including special
+    /// tokens, excluding padding).
+    ///
+    /// # Arguments
+    ///
+    /// * `input` - `&[&str]` Array of texts
+    ///
+    /// # Returns
+    ///
+    /// * `Tensor` of shape (*number of texts*, *feature dimension*), on the CPU
+    pub fn encode(&self, input: &[&str]) -> Result<Tensor, RustBertError> {
+        if input.is_empty() {
+            return Ok(Tensor::zeros(&[0, 0], (Kind::Float, Device::Cpu)));
+        }
+        let (_, input_batch) = self.prepare_for_model(input);
+        self.embeddings_from_input(&input_batch)
+    }
+
+    fn embeddings_from_input(&self, input_batch: &TensorBatch) -> Result<Tensor, RustBertError> {
+        let embeddings = no_grad(|| -> Result<Tensor, RustBertError> {
+            let features = self.layer_features(input_batch)?;
+            let mask = input_batch
+                .attention_mask_as(features.kind())
+                .expect("tokenized batches have an attention mask")
+                .unsqueeze(-1);
+            Ok((features * &mask).sum1(&[1], false, Kind::Float)
+                / mask.sum1(&[1], false, Kind::Float))
+        })?;
+        Ok(embeddings.to(Device::Cpu))
+    }
+
+    fn layer_features(&self, input_batch: &TensorBatch) -> Result<Tensor, RustBertError> {
+        let input_ids = input_batch.input_ids();
+        let mask = input_batch
+            .attention_mask()
+            .expect("tokenized batches have an attention mask");
+        let hidden_states = self.encoder.forward_t(&input_ids, &mask, false)?;
+        let selected = self
+            .layers
+            .iter()
+            .map(|&layer| &hidden_states[layer])
+            .collect::<Vec<_>>();
+        Ok(match self.layer_aggregation {
+            LayerAggregation::Concatenate => Tensor::cat(&selected, -1),
+            LayerAggregation::Mean => Tensor::stack(&selected, 0).mean1(&[0], false, Kind::Float),
+            LayerAggregation::Sum => Tensor::stack(&selected, 0).sum1(&[0], false, Kind::Float),
+        })
+    }
+
+    fn word_features(&self, token_features: &Tensor, word_ids: &[Option<usize>]) -> Tensor {
+        let num_words = word_ids.iter().flatten().max().map_or(0, |&max| max + 1);
+        if num_words == 0 {
+            return token_features.narrow(0, 0, 0);
+        }
+        let mut word_positions: Vec<Vec<i64>> = vec![vec![]; num_words];
+        for (position, word_id) in word_ids.iter().enumerate() {
+            if let Some(word_id) = word_id {
+                word_positions[*word_id].push(position as i64);
+            }
+        }
+        let word_features = word_positions
+            .iter()
+            .map(|positions| match self.word_aggregation {
+                WordAggregation::First => token_features.get(positions[0]),
+                WordAggregation::Mean => token_features
+                    .index_select(0, &Tensor::of_slice(positions))
+                    .mean1(&[0], false, Kind::Float),
+            })
+            .collect::<Vec<_>>();
+        Tensor::stack(&word_features, 0)
+    }
+}
+
+/// # Model computing a fixed-size embedding for each text
+/// Implemented by `FeatureExtractionModel` (average of the sub-token features). Other encoders can implement it to be
+/// used by the corpus-level utilities (e.g. `deduplication`).
+pub trait TextEmbedder: Send {
+    /// Embeds a batch of texts
+    ///
+    /// # Arguments
+    ///
+    /// * `texts` - `&[&str]` Array of texts
+    ///
+    /// # Returns
+    ///
+    /// * `Tensor` of shape (*number of texts*, *embedding dimension*)
+    fn embed(&self, texts: &[&str]) -> Result<Tensor, RustBertError>;
+
+    /// Embeds texts in batches of `batch_size` texts
+    ///
+    /// # Arguments
+    ///
+    /// * `texts` - `&[&str]` Array of texts
+    /// * `batch_size` - number of texts embedded at once
+    ///
+    /// # Returns
+    ///
+    /// * `Tensor` of shape (*number of texts*, *embedding dimension*), on the CPU
+    fn embed_in_batches(&self, texts: &[&str], batch_size: usize) -> Result<Tensor, RustBertError> {
+        let embeddings = texts
+            .chunks(batch_size.max(1))
+            .map(|batch| Ok(self.embed(batch)?.to(Device::Cpu)))
+            .collect::<Result<Vec<Tensor>, RustBertError>>()?;
+        Ok(concatenate_embeddings(embeddings))
+    }
+}
+
+impl TextEmbedder for FeatureExtractionModel {
+    fn embed(&self, texts: &[&str]) -> Result<Tensor, RustBertError> {
+        self.encode(texts)
+    }
+
+    /// Embeds texts in batches, tokenizing the next batch while the current one is embedded with the
+    /// `parallel-tokenization` feature
+    fn embed_in_batches(&self, texts: &[&str], batch_size: usize) -> Result<Tensor, RustBertError> {
+        let tokenizer = &self.tokenizer;
+        let max_length = self.max_length;
+        let device = self.var_store.device();
+        let pinned_memory = self.pinned_memory;
+        let embeddings = prefetch_batches(
+            texts,
+            batch_size,
+            |batch| tokenize_batch(tokenizer, max_length, batch, device, pinned_memory),
+            |_, (_, input_batch)| Ok(self.embeddings_from_input(&input_batch)?.to(Device::Cpu)),
+        )?;
+        Ok(concatenate_embeddings(embeddings))
+    }
+}
+
+fn concatenate_embeddings(embeddings: Vec<Tensor>) -> Tensor {
+    if embeddings.is_empty() {
+        Tensor::zeros(&[0, 0], (Kind::Float, Device::Cpu))
+    } else {
+        Tensor::cat(&embeddings, 0)
+    }
+}
+
+// Tokenizes a batch of texts and builds the padded input ids and attention mask, on the CPU until accessed
+fn tokenize_batch(
+    tokenizer: &TokenizerOption,
+    max_length: usize,
+    input: &[&str],
+    device: Device,
+    pinned_memory: bool,
+) -> (Vec<TokenizedInput>, TensorBatch) {
+    let tokenized_input =
+        tokenizer.encode_list(input, max_length, &TruncationStrategy::LongestFirst, 0);
+    let token_ids = tokenized_input
+        .iter()
+        .map(|input| input.token_ids.clone())
+        .collect::<Vec<Vec<i64>>>();
+    let input_batch =
+        TensorBatch::from_token_ids(&token_ids, tokenizer.get_pad_id().unwrap_or(0), device)
+            .with_pinned_memory(pinned_memory);
+    (tokenized_input, input_batch)
+}
+
+/// # Static embeddings
+/// Vectors associated with words or vocabulary entries, that can be exported to the word2vec text format (also read
+/// by fastText as `.vec` files). Words containing white spaces cannot be represented in this format and are skipped
+/// on export.
+pub struct StaticEmbeddings {
+    /// Words or vocabulary entries
+    pub words: Vec<String>,
+    /// Vectors of the words, of shape (*number of words*, *embedding dimension*)
+    pub vectors: Tensor,
+}
+
+impl StaticEmbeddings {
+    /// Creates static embeddings from a list of words and their vectors
+    ///
+    /// # Arguments
+    ///
+    /// * `words` - words or vocabulary entries
+    /// * `vectors` - `Tensor` of shape (*number of words*, *embedding dimension*)
+    pub fn new(words: Vec<String>, vectors: Tensor) -> Result<StaticEmbeddings, RustBertError> {
+        if (vectors.dim() != 2) | (vectors.size()[0] != words.len() as i64) {
+            return Err(RustBertError::ValueError(format!(
+                "Expected vectors of shape ({}, embedding dimension), got {:?}",
+                words.len(),
+                vectors.size()
+            )));
+        }
+        Ok(StaticEmbeddings {
+            words,
+            vectors: vectors.to(Device::Cpu).to_kind(Kind::Float),
+        })
+    }
+
+    /// Creates static embeddings from an input embedding matrix, using the raw vocabulary entries of the tokenizer as
+    /// words (e.g. `##ing` for WordPiece or `▁the` for SentencePiece vocabularies). Rows of the matrix without
+    /// vocabulary entry (padding of the vocabulary) are ignored.
+    ///
+    /// # Arguments
+    ///
+    /// * `embeddings` - embedding matrix of shape (*model vocabulary size*, *embedding dimension*)
+    /// * `tokenizer` - `TokenizerOption` of the model
+    pub fn from_embedding_matrix(
+        embeddings: &Tensor,
+        tokenizer: &TokenizerOption,
+    ) -> Result<StaticEmbeddings, RustBertError> {
+        let vocab_size = embeddings.size()[0];
+        let mut vocabulary = tokenizer
+            .get_vocab_indices()
+            .into_iter()
+            .filter(|(id, _)| (*id >= 0) & (*id < vocab_size))
+            .collect::<Vec<_>>();
+        vocabulary.sort_by_key(|(id, _)| *id);
+        let (ids, words): (Vec<i64>, Vec<String>) = vocabulary.into_iter().unzip();
+        let vectors =
+            no_grad(|| embeddings.index_select(0, &Tensor::of_slice(&ids).to(embeddings.device())));
+        StaticEmbeddings::new(words, vectors)
+    }
+
+    /// Creates static embeddings from the input embedding matrix of a model loaded in a variable store. The matrix is
+    /// identified by the name of its variable: `word_embeddings.weight` (BERT-like encoders), `wte.weight` (GPT2),
+    /// `shared.weight` (T5) or `embed_tokens.weight` (BART).
+    ///
+    /// # Arguments
+    ///
+    /// * `var_store` - `VarStore` holding the model weights
+    /// * `tokenizer` - `TokenizerOption` of the model
+    pub fn from_var_store(
+        var_store: &VarStore,
+        tokenizer: &TokenizerOption,
+    ) -> Result<StaticEmbeddings, RustBertError> {
+        let variables = var_store.variables();
+        let mut candidates = variables
+            .iter()
+            .filter(|(name, _)| {
+                INPUT_EMBEDDING_NAMES.iter().any(|suffix| {
+                    (name.as_str() == *suffix) | name.ends_with(&format!(".{}", suffix))
+                })
+            })
+            .collect::<Vec<_>>();
+        candidates.sort_by_key(|(name, _)| name.len());
+        match candidates.first() {
+            Some((_, embeddings)) => StaticEmbeddings::from_embedding_matrix(embeddings, tokenizer),
+            None => Err(RustBertError::ValueError(
+                "Could not find the input embedding matrix in the variable store".to_string(),
+            )),
+        }
+    }
+
+    /// Writes the embeddings in the word2vec text format: a header line with the number of words and the embedding
+    /// dimension, followed by one line per word with its space-separated vector values
+    ///
+    /// # Arguments
+    ///
+    /// * `writer` - destination implementing `Write`
+    pub fn write<W: Write>(&self, writer: W) -> Result<(), RustBertError> {
+        let mut writer = BufWriter::new(writer);
+        let exported = self
+            .words
+            .iter()
+            .enumerate()
+            .filter(|(_, word)| !word.is_empty() & !word.chars().any(char::is_whitespace))
+            .collect::<Vec<_>>();
+        let dimension = self.vectors.size()[1];
+        writeln!(writer, "{} {}", exported.len(), dimension)?;
+        for (index, word) in exported {
+            let values = Vec::<f32>::from(self.vectors.get(index as i64));
+            write!(writer, "{}", word)?;
+            for value in values {
+                write!(writer, " {:.6}", value)?;
+            }
+            writeln!(writer)?;
+        }
+        writer.flush()?;
+        Ok(())
+    }
+
+    /// Saves the embeddings to a file in the word2vec text format (see `write`)
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - path of the file to create
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<(), RustBertError> {
+        self.write(File::create(path)?)
+    }
+}
+
+const INPUT_EMBEDDING_NAMES: [&str; 4] = [
+    "word_embeddings.weight",
+    "wte.weight",
+    "shared.weight",
+    "embed_tokens.weight",
+];
+
+/// Returns the surface form of each word of the extracted features, spanning from the first to the last character of
+/// its sub-tokens in the original text
+fn word_surfaces(features: &Features, text_chars: &[char]) -> Vec<String> {
+    let num_words = features.word_features.size()[0] as usize;
+    let mut spans: Vec<Option<(usize, usize)>> = vec![None; num_words];
+    for (word_id, offset) in features.word_ids.iter().zip(features.token_offsets.iter()) {
+        if let (Some(word_id), Some(offset)) = (word_id, offset) {
+            let (begin, end) = (offset.begin as usize, offset.end as usize);
+            spans[*word_id] = Some(match spans[*word_id] {
+                Some((span_begin, span_end)) => (span_begin.min(begin), span_end.max(end)),
+                None => (begin, end),
+            });
+        }
+    }
+    spans
+        .into_iter()
+        .map(|span| match span {
+            Some((begin, end)) => text_chars
+                [begin.min(text_chars.len())..end.min(text_chars.len())]
+                .iter()
+                .collect(),
+            None => String::new(),
+        })
+        .collect()
+}
+
+/// Assigns each sub-token to a word: a new word starts at each token that is neither a special token nor the
+/// continuation of the previous token
+fn word_ids(mask: &[Mask]) -> Vec<Option<usize>> {
+    let mut current_word: Option<usize> = None;
+    mask.iter()
+        .map(|mask| match mask {
+            Mask::Special => None,
+            Mask::Continuation if current_word.is_some() => current_word,
+            _ => {
+                current_word = Some(current_word.map_or(0, |word| word + 1));
+                current_word
+            }
+        })
+        .collect()
+}
+
+fn resolve_layers(layers: &[i64], num_hidden_layers: i64) -> Result<Vec<usize>, RustBertError> {
+    if layers.is_empty() {
+        return Err(RustBertError::InvalidConfigurationError(
+            "At least one layer must be selected for feature extraction".to_string(),
+        ));
+    }
+    let num_layers = num_hidden_layers + 1;
+    layers
+        .iter()
+        .map(|&layer| {
+            let index = if layer < 0 { num_layers + layer } else { layer };
+            if (index < 0) | (index >= num_layers) {
+                Err(RustBertError::InvalidConfigurationError(format!(
+                    "Layer {} out of range for a model with {} layers (including the embeddings)",
+                    layer, num_layers
+                )))
+            } else {
+                Ok(index as usize)
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    #[ignore] // no need to run, compilation is enough to verify it is Send
+    fn test() {
+        let config = FeatureExtractionConfig::default();
+        let _: Box<dyn Send> = Box::new(FeatureExtractionModel::new(config));
+    }
+}
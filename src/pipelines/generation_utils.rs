@@ -65,13 +65,16 @@
 //! # ;
 //! ```
 
-use self::ordered_float::OrderedFloat;
 use crate::bart::{
     BartConfig, BartConfigResources, BartForConditionalGeneration, BartMergesResources,
     BartModelResources, BartVocabResources, LayerState as BartLayerState,
 };
+use crate::common::config::{check_positive, check_token_id};
 use crate::common::error::RustBertError;
+use crate::common::model_output::ModelOutput;
+use crate::common::prefix_tuning::PrefixTuning;
 use crate::common::resources::{RemoteResource, Resource};
+use crate::common::weights::{reload_var_store, share_var_store};
 use crate::gpt2::{
     GPT2LMHeadModel, Gpt2Config, Gpt2ConfigResources, Gpt2MergesResources, Gpt2ModelResources,
     Gpt2VocabResources,
@@ -82,9 +85,8 @@ use crate::openai_gpt::{
     OpenAiGptModelResources, OpenAiGptVocabResources,
 };
 use crate::pipelines::common::{ModelType, TokenizerOption};
-use crate::pipelines::generation_utils::private_generation_utils::{
-    GenerateOptions, PrivateLanguageGenerator,
-};
+use crate::pipelines::generation_utils::private_generation_utils::PrivateLanguageGenerator;
+use crate::pipelines::grammar::Grammar;
 use crate::reformer::{
     LayerState as ReformerLayerState, ReformerConfig, ReformerConfigResources,
     ReformerModelResources, ReformerModelWithLMHead, ReformerVocabResources,
@@ -95,7 +97,6 @@ use crate::t5::{
 };
 use crate::xlnet::{LayerState, XLNetConfig, XLNetLMHeadModel};
 use crate::Config;
-use itertools::Itertools;
 use rust_tokenizers::tokenizer::{
     Gpt2Tokenizer, MarianTokenizer, OpenAiGptTokenizer, ReformerTokenizer, RobertaTokenizer,
     T5Tokenizer, Tokenizer, TruncationStrategy, XLNetTokenizer,
@@ -103,11 +104,219 @@ use rust_tokenizers::tokenizer::{
 use rust_tokenizers::vocab::{
     Gpt2Vocab, MarianVocab, OpenAiGptVocab, ReformerVocab, RobertaVocab, T5Vocab, Vocab, XLNetVocab,
 };
+use serde::{de, Deserialize, Deserializer, Serialize};
+use serde_json::{Map, Value};
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration;
 use tch::kind::Kind::Int64;
 use tch::{nn, no_grad, Device, Kind, Tensor};
 
 extern crate ordered_float;
 
+/// Maximum number of prompt tokens encoded when the prompts are not truncated to the maximum output length
+const MAX_PROMPT_LENGTH: i64 = 1024;
+
+/// # Function restricting the tokens allowed at each generation step
+/// Called with the index of the prompt in the batch and the token ids of the sequence generated so far (including the
+/// prompt for decoder-only models, or the decoder start token for encoder-decoder models), returns the token ids
+/// allowed at the next step. An empty list leaves the step unconstrained. This allows for example constraining the
+/// generation to entity names stored in a `PrefixTree`, as in [Autoregressive Entity Retrieval](https://arxiv.org/abs/2010.00904).
+pub type PrefixAllowedTokensFn = Arc<dyn Fn(i64, &[i64]) -> Vec<i64> + Send + Sync>;
+
+#[derive(Debug, Clone, Default)]
+/// # Prefix tree of token sequences
+/// Stores the token ids of the valid outputs (for example the names of the entities of a knowledge base) to constrain
+/// the generation to these outputs with a `PrefixAllowedTokensFn`. The sequences should end with the end of sequence
+/// token id, so that the generation stops once a complete sequence has been generated.
+///
+/// ```no_run
+/// # fn main() -> anyhow::Result<()> {
+/// use rust_bert::pipelines::generation_utils::{
+///     GPT2Generator, GenerateConfig, LanguageGenerator, PrefixTree,
+/// };
+///
+/// let prompt = "The capital of France is";
+/// let unconstrained_generator = GPT2Generator::new(Default::default())?;
+/// let tokenizer = unconstrained_generator.get_tokenizer();
+/// let mut prefix_tree = PrefixTree::new();
+/// for entity in &[" Paris", " Lyon", " Marseille"] {
+///     let mut token_ids = tokenizer.convert_tokens_to_ids(&tokenizer.tokenize(entity));
+///     token_ids.push(50256);
+///     prefix_tree.insert(&token_ids);
+/// }
+/// let prompt_length = tokenizer.tokenize(prompt).len();
+/// let generator = GPT2Generator::new(GenerateConfig {
+///     prefix_allowed_tokens_fn: Some(prefix_tree.into_prefix_allowed_tokens_fn(prompt_length)),
+///     ..Default::default()
+/// })?;
+/// let output = generator.generate(Some(&[prompt]), None, None, None, None);
+/// # Ok(())
+/// # }
+/// ```
+pub struct PrefixTree {
+    children: HashMap<i64, PrefixTree>,
+}
+
+impl PrefixTree {
+    /// Creates an empty prefix tree
+    pub fn new() -> PrefixTree {
+        PrefixTree::default()
+    }
+
+    /// Adds a sequence of token ids to the tree
+    pub fn insert(&mut self, token_ids: &[i64]) {
+        let mut node = self;
+        for token_id in token_ids {
+            node = node
+                .children
+                .entry(*token_id)
+                .or_insert_with(PrefixTree::new);
+        }
+    }
+
+    /// Returns the token ids following `prefix` in the sequences of the tree (empty if no sequence starts with
+    /// `prefix`, or if it is a complete sequence)
+    pub fn next_tokens(&self, prefix: &[i64]) -> Vec<i64> {
+        let mut node = self;
+        for token_id in prefix {
+            node = match node.children.get(token_id) {
+                Some(child) => child,
+                None => return vec![],
+            };
+        }
+        let mut next_tokens = node.children.keys().cloned().collect::<Vec<i64>>();
+        next_tokens.sort_unstable();
+        next_tokens
+    }
+
+    /// Converts the tree into a function restricting the generation to its sequences, for all the prompts
+    ///
+    /// # Arguments
+    ///
+    /// * `skipped_tokens` - Number of leading token ids of the generated sequences that are not part of the constrained
+    /// output: the length of the (padded) prompt for decoder-only models, or 1 (the decoder start token) for
+    /// encoder-decoder models
+    pub fn into_prefix_allowed_tokens_fn(self, skipped_tokens: usize) -> PrefixAllowedTokensFn {
+        Arc::new(move |_, token_ids: &[i64]| {
+            self.next_tokens(&token_ids[skipped_tokens.min(token_ids.len())..])
+        })
+    }
+}
+
+/// # Criteria ending the generation of a sequence
+/// Checked after each generation step for the sequences not finished yet, in addition to the end of sequence token.
+/// A sequence meeting the criteria is finished, and its output is truncated to the number of generated tokens
+/// returned by `stop_position` (the tokens streamed with the generation callbacks are not truncated).
+pub trait StoppingCriteria: Send + Sync {
+    /// Returns the number of generated tokens to keep if the generation of the sequence should stop, or `None` to
+    /// continue the generation.
+    ///
+    /// # Arguments
+    ///
+    /// * `generated_ids` - Token ids generated so far (excluding the prompt and decoder start token)
+    /// * `decode` - Function decoding token ids to text (without special tokens)
+    fn stop_position(
+        &self,
+        generated_ids: &[i64],
+        decode: &dyn Fn(&[i64]) -> String,
+    ) -> Option<usize>;
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+/// # Stop sequences
+/// `StoppingCriteria` ending the generation when the generated text contains one of the stop strings, or the
+/// generated token ids contain one of the stop token sequences. The output ends before the stop sequence: for stop
+/// strings, before the token where the stop string starts.
+///
+/// ```no_run
+/// # fn main() -> anyhow::Result<()> {
+/// use rust_bert::pipelines::generation_utils::{GPT2Generator, GenerateConfig, StopSequences};
+/// use std::sync::Arc;
+///
+/// let generate_config = GenerateConfig {
+///     stopping_criteria: Some(Arc::new(StopSequences::from_texts(&["\n\n", "###"]))),
+///     ..Default::default()
+/// };
+/// let gpt2_generator = GPT2Generator::new(generate_config)?;
+/// # Ok(())
+/// # }
+/// ```
+pub struct StopSequences {
+    /// Stop strings, searched in the decoded generated text
+    pub texts: Vec<String>,
+    /// Stop token sequences, searched in the generated token ids
+    pub token_ids: Vec<Vec<i64>>,
+}
+
+impl StopSequences {
+    /// Creates stop sequences from stop strings
+    pub fn from_texts(texts: &[&str]) -> StopSequences {
+        StopSequences {
+            texts: texts.iter().map(|text| text.to_string()).collect(),
+            token_ids: vec![],
+        }
+    }
+
+    /// Creates stop sequences from stop token sequences
+    pub fn from_token_ids(token_ids: Vec<Vec<i64>>) -> StopSequences {
+        StopSequences {
+            texts: vec![],
+            token_ids,
+        }
+    }
+
+    fn token_stop_position(&self, generated_ids: &[i64]) -> Option<usize> {
+        self.token_ids
+            .iter()
+            .filter(|stop_ids| !stop_ids.is_empty())
+            .filter_map(|stop_ids| {
+                generated_ids
+                    .windows(stop_ids.len())
+                    .position(|window| window == stop_ids.as_slice())
+            })
+            .min()
+    }
+
+    fn text_stop_position(
+        &self,
+        generated_ids: &[i64],
+        decode: &dyn Fn(&[i64]) -> String,
+    ) -> Option<usize> {
+        let text = decode(generated_ids);
+        let stop_offset = self
+            .texts
+            .iter()
+            .filter(|stop_text| !stop_text.is_empty())
+            .filter_map(|stop_text| text.find(stop_text.as_str()))
+            .min()?;
+        // Keeps the tokens decoding to the text before the stop string
+        (0..generated_ids.len())
+            .find(|&length| decode(&generated_ids[..length + 1]).len() > stop_offset)
+            .or(Some(generated_ids.len()))
+    }
+}
+
+impl StoppingCriteria for StopSequences {
+    fn stop_position(
+        &self,
+        generated_ids: &[i64],
+        decode: &dyn Fn(&[i64]) -> String,
+    ) -> Option<usize> {
+        let token_position = self.token_stop_position(generated_ids);
+        let text_position = if self.texts.is_empty() {
+            None
+        } else {
+            self.text_stop_position(generated_ids, decode)
+        };
+        match (token_position, text_position) {
+            (Some(token_position), Some(text_position)) => Some(token_position.min(text_position)),
+            (token_position, text_position) => token_position.or(text_position),
+        }
+    }
+}
+
+#[derive(Clone)]
 /// # Configuration for text generation
 pub struct GenerateConfig {
     /// Model weights resource (default: pretrained GPT2 model)
@@ -122,18 +331,34 @@ pub struct GenerateConfig {
     pub min_length: i64,
     /// Maximum sequence length (default: 20)
     pub max_length: i64,
+    /// Maximum number of generated tokens, excluding the prompt. Takes precedence over `max_length` when set (default: None)
+    pub max_new_tokens: Option<i64>,
+    /// Minimum number of generated tokens, excluding the prompt. Takes precedence over `min_length` when set (default: None)
+    pub min_new_tokens: Option<i64>,
     /// Sampling flag. If true, will perform top-k and/or nucleus sampling on generated tokens, otherwise greedy (deterministic) decoding (default: true)
     pub do_sample: bool,
+    /// Seed of the sampling. When set, the sampled tokens are drawn from a generator seeded at each call instead of the `tch` random state, so that sampled outputs are reproducible across runs and threads for the same inputs (default: None)
+    pub seed: Option<u64>,
     /// Early stopping flag indicating if the beam search should stop as soon as `num_beam` hypotheses have been generated (default: false)
     pub early_stopping: bool,
     /// Number of beams for beam search (default: 5)
     pub num_beams: i64,
+    /// Number of groups of beams for [diverse beam search, Vijayakumar et al.](https://arxiv.org/abs/1610.02424). `num_beams` must be a multiple of the number of groups (default: 1)
+    pub num_beam_groups: i64,
+    /// Penalty subtracted from the scores of the tokens selected by the previous groups of beams at the same step, for diverse beam search (default: 0.0)
+    pub diversity_penalty: f64,
     /// Temperature setting. Values higher than 1 will improve originality at the risk of reducing relevance (default: 1.0)
     pub temperature: f64,
     /// Top_k values for sampling tokens. Value higher than 0 will enable the feature (default: 0)
     pub top_k: i64,
     /// Top_p value for [Nucleus sampling, Holtzman et al.](http://arxiv.org/abs/1904.09751). Keep top tokens until cumulative probability reaches top_p (default: 0.9)
     pub top_p: f64,
+    /// Probability mass of [typical decoding, Meister et al.](https://arxiv.org/abs/2202.00666): only the tokens with an information content closest to the entropy of the distribution are sampled, up to this cumulative probability. Values lower than 1 enable the feature (default: 1.0)
+    pub typical_p: f64,
+    /// Cutoff of [epsilon sampling, Hewitt et al.](https://arxiv.org/abs/2210.15191): tokens with a probability lower than the cutoff are not sampled. Values higher than 0 enable the feature, typically between 3e-4 and 9e-4 (default: 0.0)
+    pub epsilon_cutoff: f64,
+    /// Cutoff of [eta sampling, Hewitt et al.](https://arxiv.org/abs/2210.15191): tokens with a probability lower than `min(eta_cutoff, sqrt(eta_cutoff) * exp(-entropy))` are not sampled. Values higher than 0 enable the feature, typically between 3e-4 and 2e-3 (default: 0.0)
+    pub eta_cutoff: f64,
     /// Repetition penalty (mostly useful for CTRL decoders). Values higher than 1 will penalize tokens that have been already generated. (default: 1.0)
     pub repetition_penalty: f64,
     /// Exponential penalty based on the length of the hypotheses generated (default: 1.0)
@@ -142,6 +367,44 @@ pub struct GenerateConfig {
     pub no_repeat_ngram_size: i64,
     /// Number of sequences to return for each prompt text (default: 1)
     pub num_return_sequences: i64,
+    /// Token ids allowed at each generation step, restricting the vocabulary of the generated sequences (default: None)
+    pub allowed_token_ids: Option<Vec<i64>>,
+    /// Function returning the token ids allowed at the next step given the prompt index and the ids generated so far (default: None)
+    pub prefix_allowed_tokens_fn: Option<PrefixAllowedTokensFn>,
+    /// Criteria ending the generation of a sequence before the end of sequence token, e.g. `StopSequences` stopping on user-provided strings or token sequences (default: None)
+    pub stopping_criteria: Option<Arc<dyn StoppingCriteria>>,
+    /// Token sequences that must all appear in the generated sequences (e.g. the tokens of required terms), enforced by a constrained beam search (default: None)
+    pub force_words_ids: Option<Vec<Vec<i64>>>,
+    /// Token sequences that may not appear in the generated sequences (e.g. profanity or personal information markers): the last token of a sequence is banned whenever its previous tokens have just been generated (default: None)
+    pub bad_words_ids: Option<Vec<Vec<i64>>>,
+    /// Grammar the generated text must follow (e.g. `Grammar::from_json_schema` for structured outputs), enforced by masking the tokens that cannot extend the text into a valid prefix of the grammar (default: None)
+    pub grammar: Option<Arc<Grammar>>,
+    /// Bias added to the scores of the tokens present in the source (prompt) at each generation step, favouring copying from the input over hallucinated tokens (default: 0.0)
+    pub source_copy_bias: f64,
+    /// Size of the n-grams of the source (encoder input) that may not be copied in the generated sequences, for encoder-decoder models (default: 0)
+    pub encoder_no_repeat_ngram_size: i64,
+    /// Multiplier of the DRY ("don't repeat yourself") penalty applied to tokens extending a repetition of a previous sequence (default: 0.0, disabled)
+    pub dry_multiplier: f64,
+    /// Base of the DRY penalty, growing exponentially with the length of the repeated sequence (default: 1.75)
+    pub dry_base: f64,
+    /// Length of the repeated sequences not penalized by the DRY penalty (default: 2)
+    pub dry_allowed_length: i64,
+    /// Token ids interrupting the matching of repeated sequences for the DRY penalty, e.g. newlines or punctuation (default: None)
+    pub dry_sequence_breakers: Option<Vec<i64>>,
+    /// Remove the last token of the prompts and constrain the first generated token to start with it, for decoder-only models. Avoids starting the generation with a broken word when the prompt ends mid-token (default: false)
+    pub token_healing: bool,
+    /// Include the prompt in the generated sequences of decoder-only models (default: true)
+    pub echo_prompt: bool,
+    /// Beginning of sequence token id, overriding the id derived from the model configuration and tokenizer (default: None)
+    pub bos_token_id: Option<i64>,
+    /// End of sequence token ids, overriding the ids derived from the model configuration and tokenizer (default: None)
+    pub eos_token_ids: Option<Vec<i64>>,
+    /// Padding token id, overriding the id derived from the model configuration and tokenizer (default: None)
+    pub pad_token_id: Option<i64>,
+    /// First decoder input token id of encoder-decoder models, overriding the id derived from the model configuration (default: None)
+    pub decoder_start_token_id: Option<i64>,
+    /// Debugging option checking the cache of the model: at each step, the logits are also computed without cache, and the generation panics if they differ from the cached ones by more than this tolerance. Slows down the generation significantly (default: None)
+    pub cache_check_tolerance: Option<f64>,
     /// Device to place the model on (default: CUDA/GPU when available)
     pub device: Device,
 }
@@ -163,28 +426,179 @@ impl Default for GenerateConfig {
             )),
             min_length: 0,
             max_length: 20,
+            max_new_tokens: None,
+            min_new_tokens: None,
             do_sample: true,
+            seed: None,
             early_stopping: true,
             num_beams: 5,
+            num_beam_groups: 1,
+            diversity_penalty: 0.0,
             temperature: 1.0,
             top_k: 0,
             top_p: 0.9,
+            typical_p: 1.0,
+            epsilon_cutoff: 0.0,
+            eta_cutoff: 0.0,
             repetition_penalty: 1.0,
             length_penalty: 1.0,
             no_repeat_ngram_size: 3,
             num_return_sequences: 1,
+            allowed_token_ids: None,
+            prefix_allowed_tokens_fn: None,
+            stopping_criteria: None,
+            force_words_ids: None,
+            bad_words_ids: None,
+            grammar: None,
+            source_copy_bias: 0.0,
+            encoder_no_repeat_ngram_size: 0,
+            dry_multiplier: 0.0,
+            dry_base: 1.75,
+            dry_allowed_length: 2,
+            dry_sequence_breakers: None,
+            token_healing: false,
+            echo_prompt: true,
+            bos_token_id: None,
+            eos_token_ids: None,
+            pad_token_id: None,
+            decoder_start_token_id: None,
+            cache_check_tolerance: None,
             device: Device::cuda_if_available(),
         }
     }
 }
 
+/// # Special token ids used for generation
+/// The ids are resolved by order of priority from the generation options (`GenerateConfig`), the model configuration
+/// and the special tokens of the tokenizer (see `or`). `resolve` then checks that the ids required by the model are
+/// available and valid, rather than silently falling back to arbitrary ids producing corrupted outputs.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct SpecialTokenIds {
+    /// Beginning of sequence token id, used to start the generation without prompt
+    pub bos_token_id: Option<i64>,
+    /// End of sequence token ids, stopping the generation
+    pub eos_token_ids: Option<Vec<i64>>,
+    /// Padding token id
+    pub pad_token_id: Option<i64>,
+    /// First decoder input token id of encoder-decoder models
+    pub decoder_start_id: Option<i64>,
+}
+
+impl SpecialTokenIds {
+    /// Returns the special token ids set explicitly in the generation options
+    pub fn from_generate_config(generate_config: &GenerateConfig) -> SpecialTokenIds {
+        SpecialTokenIds {
+            bos_token_id: generate_config.bos_token_id,
+            eos_token_ids: generate_config.eos_token_ids.clone(),
+            pad_token_id: generate_config.pad_token_id,
+            decoder_start_id: generate_config.decoder_start_token_id,
+        }
+    }
+
+    /// Returns the special token ids defined by the vocabulary of a tokenizer
+    pub fn from_tokenizer(tokenizer: &TokenizerOption) -> SpecialTokenIds {
+        SpecialTokenIds {
+            bos_token_id: tokenizer.get_bos_id(),
+            eos_token_ids: tokenizer
+                .get_eos_id()
+                .map(|eos_token_id| vec![eos_token_id]),
+            pad_token_id: tokenizer.get_pad_id(),
+            decoder_start_id: None,
+        }
+    }
+
+    /// Completes the ids missing with the ids of another (lower priority) source
+    ///
+    /// # Arguments
+    ///
+    /// * `other` - `SpecialTokenIds` used for the ids not set
+    pub fn or(self, other: SpecialTokenIds) -> SpecialTokenIds {
+        SpecialTokenIds {
+            bos_token_id: self.bos_token_id.or(other.bos_token_id),
+            eos_token_ids: self.eos_token_ids.or(other.eos_token_ids),
+            pad_token_id: self.pad_token_id.or(other.pad_token_id),
+            decoder_start_id: self.decoder_start_id.or(other.decoder_start_id),
+        }
+    }
+
+    /// Checks that the ids required for generation are available and are valid indices of the model vocabulary,
+    /// reporting missing ids and tokenizer/model mismatches before running the model.
+    /// Encoder-decoder models require end of sequence, padding and decoder start ids.
+    ///
+    /// # Arguments
+    ///
+    /// * `is_encoder_decoder` - flag indicating if the model is an encoder-decoder
+    /// * `vocab_size` - size of the vocabulary of the model output
+    pub fn resolve(
+        self,
+        is_encoder_decoder: bool,
+        vocab_size: i64,
+    ) -> Result<SpecialTokenIds, RustBertError> {
+        let missing_id_error = |name: &str, option: &str| {
+            RustBertError::InvalidConfigurationError(format!(
+                "Could not resolve the `{}` from the model configuration or tokenizer, set it with `GenerateConfig::{}`",
+                name, option
+            ))
+        };
+        if let Some(eos_token_ids) = &self.eos_token_ids {
+            if eos_token_ids.is_empty() {
+                return Err(RustBertError::InvalidConfigurationError(
+                    "`eos_token_ids` must contain at least one token id when provided".to_string(),
+                ));
+            }
+            for eos_token_id in eos_token_ids {
+                check_token_id("eos_token_id", Some(*eos_token_id), vocab_size)?;
+            }
+        } else if is_encoder_decoder {
+            return Err(missing_id_error("eos_token_id", "eos_token_ids"));
+        }
+        if is_encoder_decoder & self.pad_token_id.is_none() {
+            return Err(missing_id_error("pad_token_id", "pad_token_id"));
+        }
+        if is_encoder_decoder & self.decoder_start_id.is_none() {
+            return Err(missing_id_error(
+                "decoder_start_token_id",
+                "decoder_start_token_id",
+            ));
+        }
+        check_token_id("bos_token_id", self.bos_token_id, vocab_size)?;
+        check_token_id("pad_token_id", self.pad_token_id, vocab_size)?;
+        check_token_id("decoder_start_token_id", self.decoder_start_id, vocab_size)?;
+        Ok(self)
+    }
+}
+
 impl GenerateConfig {
+    /// Returns the configuration with the parameters of a generation preset (loaded from a `generation_config.json`
+    /// file) applied. Use with the struct update syntax to keep options set explicitly:
+    /// `GenerateConfig { num_beams: 1, ..GenerateConfig::default().with_preset(&preset) }`
+    ///
+    /// # Arguments
+    ///
+    /// * `preset` - `GenerationPreset` to apply
+    pub fn with_preset(mut self, preset: &GenerationPreset) -> GenerateConfig {
+        preset.update_generate_config(&mut self);
+        self
+    }
+
     fn validate(&self) {
         assert!(self.temperature > 0f64, "temperature must positive");
         assert!(
             (self.top_p >= 0f64) & (self.top_p <= 1f64),
             "top_p must be 0 and 1"
         );
+        assert!(
+            (self.typical_p > 0f64) & (self.typical_p <= 1f64),
+            "typical_p must be strictly greater than 0 and lower than 1"
+        );
+        assert!(
+            (self.epsilon_cutoff >= 0f64) & (self.epsilon_cutoff < 1f64),
+            "epsilon_cutoff must be between 0 and 1"
+        );
+        assert!(
+            (self.eta_cutoff >= 0f64) & (self.eta_cutoff < 1f64),
+            "eta_cutoff must be between 0 and 1"
+        );
         assert!(
             self.repetition_penalty >= 1f64,
             "repetition_penalty must be greater than 1"
@@ -201,6 +615,27 @@ impl GenerateConfig {
             self.num_beams > 0i64,
             "num_beams must be strictly greater than 0"
         );
+        if let Some(max_new_tokens) = self.max_new_tokens {
+            assert!(
+                max_new_tokens > 0i64,
+                "max_new_tokens must be strictly greater than 0"
+            );
+        }
+        if let Some(min_new_tokens) = self.min_new_tokens {
+            assert!(min_new_tokens >= 0i64, "min_new_tokens must be positive");
+            if let Some(max_new_tokens) = self.max_new_tokens {
+                assert!(
+                    min_new_tokens <= max_new_tokens,
+                    "min_new_tokens must be lower than max_new_tokens"
+                );
+            }
+        }
+        if let Some(allowed_token_ids) = &self.allowed_token_ids {
+            assert!(
+                !allowed_token_ids.is_empty(),
+                "allowed_token_ids must contain at least one token id"
+            );
+        }
 
         if !self.do_sample {
             if self.num_beams == 1 {
@@ -215,6 +650,507 @@ impl GenerateConfig {
                 )
             }
         }
+        assert!(
+            self.num_beam_groups > 0i64,
+            "num_beam_groups must be strictly greater than 0"
+        );
+        assert_eq!(
+            self.num_beams % self.num_beam_groups,
+            0,
+            "num_beams must be a multiple of num_beam_groups"
+        );
+        if self.num_beam_groups > 1 {
+            assert!(
+                !self.do_sample,
+                "diverse beam search (num_beam_groups > 1) is not available with sampling"
+            );
+        }
+        if let Some(bad_words_ids) = &self.bad_words_ids {
+            assert!(
+                bad_words_ids.iter().all(|word_ids| !word_ids.is_empty()),
+                "bad_words_ids must not contain empty token sequences"
+            );
+        }
+        if let Some(force_words_ids) = &self.force_words_ids {
+            assert!(
+                force_words_ids.iter().all(|word_ids| !word_ids.is_empty()),
+                "force_words_ids must not contain empty token sequences"
+            );
+            assert!(
+                (self.num_beams > 1) & (self.num_beam_groups == 1) & !self.do_sample,
+                "force_words_ids requires a beam search (num_beams > 1) without sampling or beam groups"
+            );
+        }
+    }
+}
+
+fn deserialize_token_ids<'de, D>(deserializer: D) -> Result<Option<Vec<i64>>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum TokenIds {
+        Single(i64),
+        Multiple(Vec<i64>),
+    }
+    Ok(
+        Option::<TokenIds>::deserialize(deserializer)?.map(|token_ids| match token_ids {
+            TokenIds::Single(token_id) => vec![token_id],
+            TokenIds::Multiple(token_ids) => token_ids,
+        }),
+    )
+}
+
+fn deserialize_early_stopping<'de, D>(deserializer: D) -> Result<Option<bool>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum EarlyStopping {
+        Flag(bool),
+        // The Transformers library also accepts the `"never"` heuristic, equivalent to `false` here
+        Heuristic(String),
+    }
+    Option::<EarlyStopping>::deserialize(deserializer)?
+        .map(|early_stopping| match early_stopping {
+            EarlyStopping::Flag(early_stopping) => Ok(early_stopping),
+            EarlyStopping::Heuristic(heuristic) if heuristic == "never" => Ok(false),
+            EarlyStopping::Heuristic(heuristic) => Err(de::Error::custom(format!(
+                "unsupported early stopping heuristic `{}`, expected a boolean or `never`",
+                heuristic
+            ))),
+        })
+        .transpose()
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+/// # Generation parameters preset
+/// Generation defaults shipped with a checkpoint in a `generation_config.json` file (format of the
+/// [Transformers library](https://github.com/huggingface/transformers)). The parameters present in the file replace
+/// the crate defaults, while keeping the options set explicitly by the user:
+///
+/// ```no_run
+/// # fn main() -> anyhow::Result<()> {
+/// use rust_bert::pipelines::generation_utils::{GPT2Generator, GenerateConfig, GenerationPreset};
+/// use rust_bert::resources::{RemoteResource, Resource};
+///
+/// let preset = GenerationPreset::from_resource(&Resource::Remote(RemoteResource::new(
+///     "https://huggingface.co/gpt2/resolve/main/generation_config.json",
+///     "gpt2/generation_config",
+/// )))?;
+/// let generate_config = GenerateConfig {
+///     max_length: 64,
+///     ..GenerateConfig::default().with_preset(&preset)
+/// };
+/// let gpt2_generator = GPT2Generator::new(generate_config)?;
+/// # Ok(())
+/// # }
+/// ```
+pub struct GenerationPreset {
+    pub bos_token_id: Option<i64>,
+    #[serde(default, deserialize_with = "deserialize_token_ids")]
+    pub eos_token_id: Option<Vec<i64>>,
+    pub pad_token_id: Option<i64>,
+    pub decoder_start_token_id: Option<i64>,
+    pub min_length: Option<i64>,
+    pub max_length: Option<i64>,
+    pub max_new_tokens: Option<i64>,
+    pub min_new_tokens: Option<i64>,
+    pub do_sample: Option<bool>,
+    #[serde(default, deserialize_with = "deserialize_early_stopping")]
+    pub early_stopping: Option<bool>,
+    pub num_beams: Option<i64>,
+    pub num_beam_groups: Option<i64>,
+    pub diversity_penalty: Option<f64>,
+    pub temperature: Option<f64>,
+    pub top_k: Option<i64>,
+    pub top_p: Option<f64>,
+    pub typical_p: Option<f64>,
+    pub epsilon_cutoff: Option<f64>,
+    pub eta_cutoff: Option<f64>,
+    pub repetition_penalty: Option<f64>,
+    pub length_penalty: Option<f64>,
+    pub no_repeat_ngram_size: Option<i64>,
+    pub encoder_no_repeat_ngram_size: Option<i64>,
+    pub num_return_sequences: Option<i64>,
+    /// Additional parameters of the file not supported by the crate
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+}
+
+impl Config<GenerationPreset> for GenerationPreset {
+    fn extra_fields(&self) -> Option<&Map<String, Value>> {
+        Some(&self.extra)
+    }
+
+    fn validate(&self) -> Result<(), RustBertError> {
+        if let Some(temperature) = self.temperature {
+            if temperature <= 0.0 {
+                return Err(RustBertError::InvalidConfigurationError(format!(
+                    "`temperature` ({}) must be strictly positive",
+                    temperature
+                )));
+            }
+        }
+        if let Some(top_p) = self.top_p {
+            if !(0.0..=1.0).contains(&top_p) {
+                return Err(RustBertError::InvalidConfigurationError(format!(
+                    "`top_p` ({}) must be between 0 and 1",
+                    top_p
+                )));
+            }
+        }
+        if let Some(typical_p) = self.typical_p {
+            if !(typical_p > 0.0 && typical_p <= 1.0) {
+                return Err(RustBertError::InvalidConfigurationError(format!(
+                    "`typical_p` ({}) must be strictly positive and at most 1",
+                    typical_p
+                )));
+            }
+        }
+        for (name, cutoff) in &[
+            ("epsilon_cutoff", self.epsilon_cutoff),
+            ("eta_cutoff", self.eta_cutoff),
+        ] {
+            if let Some(cutoff) = cutoff {
+                if !(0.0..1.0).contains(cutoff) {
+                    return Err(RustBertError::InvalidConfigurationError(format!(
+                        "`{}` ({}) must be between 0 and 1",
+                        name, cutoff
+                    )));
+                }
+            }
+        }
+        if let Some(num_beams) = self.num_beams {
+            check_positive("num_beams", num_beams)?;
+        }
+        if let Some(num_beam_groups) = self.num_beam_groups {
+            check_positive("num_beam_groups", num_beam_groups)?;
+        }
+        if let Some(max_new_tokens) = self.max_new_tokens {
+            check_positive("max_new_tokens", max_new_tokens)?;
+        }
+        Ok(())
+    }
+}
+
+impl GenerationPreset {
+    /// Loads a generation preset from a resource, downloading and caching it if remote
+    ///
+    /// # Arguments
+    ///
+    /// * `resource` - `Resource` pointing to a `generation_config.json` file
+    pub fn from_resource(resource: &Resource) -> Result<GenerationPreset, RustBertError> {
+        GenerationPreset::try_from_file(resource.get_local_path()?)
+    }
+
+    /// Overwrites the options of `generate_config` with the parameters present in the preset
+    ///
+    /// # Arguments
+    ///
+    /// * `generate_config` - `GenerateConfig` to update
+    pub fn update_generate_config(&self, generate_config: &mut GenerateConfig) {
+        macro_rules! update {
+            ($($field:ident),*) => {
+                $(if let Some(value) = self.$field {
+                    generate_config.$field = value;
+                })*
+            };
+        }
+        update!(
+            min_length,
+            max_length,
+            do_sample,
+            early_stopping,
+            num_beams,
+            num_beam_groups,
+            diversity_penalty,
+            temperature,
+            top_k,
+            top_p,
+            typical_p,
+            epsilon_cutoff,
+            eta_cutoff,
+            repetition_penalty,
+            length_penalty,
+            no_repeat_ngram_size,
+            encoder_no_repeat_ngram_size,
+            num_return_sequences
+        );
+        if self.bos_token_id.is_some() {
+            generate_config.bos_token_id = self.bos_token_id;
+        }
+        if self.eos_token_id.is_some() {
+            generate_config.eos_token_ids = self.eos_token_id.clone();
+        }
+        if self.pad_token_id.is_some() {
+            generate_config.pad_token_id = self.pad_token_id;
+        }
+        if self.decoder_start_token_id.is_some() {
+            generate_config.decoder_start_token_id = self.decoder_start_token_id;
+        }
+        if self.max_new_tokens.is_some() {
+            generate_config.max_new_tokens = self.max_new_tokens;
+        }
+        if self.min_new_tokens.is_some() {
+            generate_config.min_new_tokens = self.min_new_tokens;
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+/// # Per-call generation options
+/// Overrides of the `GenerateConfig` of a model for a single generation call, for example to change the sampling
+/// temperature or the output length of each request served by the same pipeline. The options set to `None` keep the
+/// value of the model configuration.
+///
+/// ```no_run
+/// # fn main() -> anyhow::Result<()> {
+/// use rust_bert::pipelines::generation_utils::{GPT2Generator, GenerateOptions, LanguageGenerator};
+///
+/// let gpt2_generator = GPT2Generator::new(Default::default())?;
+/// let generate_options = GenerateOptions {
+///     max_length: Some(64),
+///     do_sample: Some(true),
+///     temperature: Some(0.7),
+///     num_beams: Some(1),
+///     ..Default::default()
+/// };
+/// let output = gpt2_generator.generate_with_options(Some(&["The dog"]), None, &generate_options);
+/// # Ok(())
+/// # }
+/// ```
+pub struct GenerateOptions {
+    /// Minimum sequence length
+    pub min_length: Option<i64>,
+    /// Maximum sequence length
+    pub max_length: Option<i64>,
+    /// Maximum number of generated tokens, excluding the prompt. Takes precedence over `max_length`
+    pub max_new_tokens: Option<i64>,
+    /// Minimum number of generated tokens, excluding the prompt. Takes precedence over `min_length`
+    pub min_new_tokens: Option<i64>,
+    /// Sampling flag. If true, will perform top-k and/or nucleus sampling on generated tokens, otherwise greedy (deterministic) decoding
+    pub do_sample: Option<bool>,
+    /// Seed of the sampling
+    pub seed: Option<u64>,
+    /// Early stopping flag indicating if the beam search should stop as soon as `num_beam` hypotheses have been generated
+    pub early_stopping: Option<bool>,
+    /// Number of beams for beam search
+    pub num_beams: Option<i64>,
+    /// Number of groups of beams for diverse beam search
+    pub num_beam_groups: Option<i64>,
+    /// Penalty applied to the tokens selected by the previous beam groups
+    pub diversity_penalty: Option<f64>,
+    /// Temperature setting. Values higher than 1 will improve originality at the risk of reducing relevance
+    pub temperature: Option<f64>,
+    /// Top_k values for sampling tokens. Value higher than 0 will enable the feature
+    pub top_k: Option<i64>,
+    /// Top_p value for [Nucleus sampling, Holtzman et al.](http://arxiv.org/abs/1904.09751). Keep top tokens until cumulative probability reaches top_p
+    pub top_p: Option<f64>,
+    /// Typical_p value for [Locally typical sampling, Meister et al.](https://arxiv.org/abs/2202.00666)
+    pub typical_p: Option<f64>,
+    /// Repetition penalty (mostly useful for CTRL decoders). Values higher than 1 will penalize tokens that have been already generated.
+    pub repetition_penalty: Option<f64>,
+    /// Exponential penalty based on the length of the hypotheses generated
+    pub length_penalty: Option<f64>,
+    /// Number of allowed repetitions of n-grams. Values higher than 0 turn on this feature
+    pub no_repeat_ngram_size: Option<i64>,
+    /// Number of sequences to return for each prompt text
+    pub num_return_sequences: Option<i64>,
+}
+
+impl GenerateOptions {
+    /// Overwrites the options of `generate_config` with the options set
+    ///
+    /// # Arguments
+    ///
+    /// * `generate_config` - `GenerateConfig` to update
+    pub fn update_generate_config(&self, generate_config: &mut GenerateConfig) {
+        macro_rules! update {
+            ($($field:ident),*) => {
+                $(if let Some(value) = self.$field {
+                    generate_config.$field = value;
+                })*
+            };
+        }
+        update!(
+            min_length,
+            max_length,
+            do_sample,
+            early_stopping,
+            num_beams,
+            num_beam_groups,
+            diversity_penalty,
+            temperature,
+            top_k,
+            top_p,
+            typical_p,
+            repetition_penalty,
+            length_penalty,
+            no_repeat_ngram_size,
+            num_return_sequences
+        );
+        if self.seed.is_some() {
+            generate_config.seed = self.seed;
+        }
+        // An absolute length replaces the length relative to the prompt of the configuration, and conversely
+        if self.min_length.is_some() | self.min_new_tokens.is_some() {
+            generate_config.min_new_tokens = self.min_new_tokens;
+        }
+        if self.max_length.is_some() | self.max_new_tokens.is_some() {
+            generate_config.max_new_tokens = self.max_new_tokens;
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+/// # Usage statistics of a generation request
+pub struct GenerationUsage {
+    /// Number of prompt tokens processed (excluding padding)
+    pub prompt_tokens: usize,
+    /// Number of tokens generated, summed over all returned sequences (excluding padding)
+    pub generated_tokens: usize,
+    /// Time spent in the generation (encoding of the prompts and decoding)
+    pub total_time: Duration,
+    /// Generated tokens per second
+    pub tokens_per_second: f64,
+}
+
+impl GenerationUsage {
+    fn new(prompt_tokens: usize, generated_tokens: usize, total_time: Duration) -> GenerationUsage {
+        let seconds = total_time.as_secs_f64();
+        GenerationUsage {
+            prompt_tokens,
+            generated_tokens,
+            total_time,
+            tokens_per_second: if seconds > 0.0 {
+                generated_tokens as f64 / seconds
+            } else {
+                0.0
+            },
+        }
+    }
+}
+
+#[derive(Debug)]
+/// # Encoder output of a batch of prompts
+/// Computed once with `LanguageGenerator::encode_prompts` for encoder-decoder models, and passed to successive
+/// calls of `generate_from_encoded_prompts` to decode the same source texts with different generation options
+/// without running the encoder again.
+pub struct EncodedPrompts {
+    /// Token ids of the prompts, of shape (*number_of_prompts*, *source length*)
+    pub input_ids: Tensor,
+    /// Attention mask of the prompts (0 for padding positions), of shape (*number_of_prompts*, *source length*)
+    pub attention_mask: Tensor,
+    /// Encoder hidden states, of shape (*number_of_prompts*, *source length*, *hidden size*)
+    pub hidden_states: Tensor,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+/// # Token streamed during the generation
+pub struct StreamedToken {
+    /// Index of the sequence the token belongs to, in the order of the generated output (*number_of_prompts* x
+    /// *num_return_sequences*)
+    pub sequence_index: usize,
+    /// Token id
+    pub token_id: i64,
+    /// Text added to the decoded sequence by the token. Empty for special tokens, and for tokens ending in the middle
+    /// of a multi-byte character: the text of this character is streamed with the token completing it.
+    pub text: String,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+/// # Generated sequence with its scores
+pub struct ScoredGeneration {
+    /// Generated text
+    pub text: String,
+    /// Log-probability of each generated token under the model (without the prompt, and up to the end of sequence
+    /// token included)
+    pub token_scores: Vec<f64>,
+    /// Log-probability of the generated sequence (sum of the token log-probabilities)
+    pub score: f64,
+    /// Sequence score divided by the number of generated tokens raised to the power `length_penalty`, as used to
+    /// rank the beam search hypotheses
+    pub normalized_score: f64,
+}
+
+impl ScoredGeneration {
+    /// Creates a scored generation from the log-probabilities of its tokens
+    ///
+    /// # Arguments
+    ///
+    /// * `text` - Generated text
+    /// * `token_scores` - Log-probability of each generated token
+    /// * `length_penalty` - Exponent of the sequence length normalizing the score
+    pub fn from_token_scores(
+        text: String,
+        token_scores: Vec<f64>,
+        length_penalty: f64,
+    ) -> ScoredGeneration {
+        let score = token_scores.iter().sum::<f64>();
+        let normalized_score = if token_scores.is_empty() {
+            0.0
+        } else {
+            score / (token_scores.len() as f64).powf(length_penalty)
+        };
+        ScoredGeneration {
+            text,
+            token_scores,
+            score,
+            normalized_score,
+        }
+    }
+}
+
+// Decodes the sequences incrementally as their tokens are generated
+struct TokenStreamer {
+    token_ids: Vec<Vec<i64>>,
+    texts: Vec<String>,
+}
+
+impl TokenStreamer {
+    fn new() -> TokenStreamer {
+        TokenStreamer {
+            token_ids: vec![],
+            texts: vec![],
+        }
+    }
+
+    fn push<D>(&mut self, sequence_index: usize, token_id: i64, decode: D) -> StreamedToken
+    where
+        D: Fn(Vec<i64>) -> String,
+    {
+        if self.token_ids.len() <= sequence_index {
+            self.token_ids.resize(sequence_index + 1, vec![]);
+            self.texts.resize(sequence_index + 1, String::new());
+        }
+        self.token_ids[sequence_index].push(token_id);
+        // The sequence is decoded without cleaning up the tokenization spaces, which could change the text streamed
+        // so far
+        let text = decode(self.token_ids[sequence_index].clone());
+        let streamed = &mut self.texts[sequence_index];
+        let new_text = if text.ends_with('\u{FFFD}') {
+            String::new()
+        } else {
+            let common_prefix = streamed
+                .char_indices()
+                .zip(text.chars())
+                .take_while(|((_, streamed_char), text_char)| streamed_char == text_char)
+                .last()
+                .map_or(0, |((position, character), _)| {
+                    position + character.len_utf8()
+                });
+            let new_text = text[common_prefix..].to_string();
+            *streamed = text;
+            new_text
+        };
+        StreamedToken {
+            sequence_index,
+            token_id,
+            text: new_text,
+        }
     }
 }
 
@@ -315,28 +1251,28 @@ impl OpenAIGenerator {
             None,
             None,
         )?;
-        let config = Gpt2Config::from_file(config_path);
+        let config = Gpt2Config::try_from_file(config_path)?;
+        tokenizer.check_model_compatibility(ModelType::OpenAiGpt, config.vocab_size)?;
         let model = OpenAIGPTLMHeadModel::new(&var_store.root(), &config);
         var_store.load(weights_path)?;
 
-        let bos_token_id = None;
-        let eos_token_ids = None;
-        let pad_token_id = None;
         let is_encoder_decoder = false;
         let vocab_size = config.vocab_size;
-        let decoder_start_id = None;
+        let token_ids = SpecialTokenIds::from_generate_config(&generate_config)
+            .or(SpecialTokenIds::from_tokenizer(&tokenizer))
+            .resolve(is_encoder_decoder, vocab_size)?;
 
         Ok(OpenAIGenerator {
             model,
             tokenizer,
             var_store,
             generate_config,
-            bos_token_id,
-            eos_token_ids,
-            pad_token_id,
+            bos_token_id: token_ids.bos_token_id,
+            eos_token_ids: token_ids.eos_token_ids,
+            pad_token_id: token_ids.pad_token_id,
             is_encoder_decoder,
             vocab_size,
-            decoder_start_id,
+            decoder_start_id: token_ids.decoder_start_id,
         })
     }
 }
@@ -353,6 +1289,9 @@ impl PrivateLanguageGenerator<OpenAIGPTLMHeadModel, OpenAiGptVocab, OpenAiGptTok
     fn get_var_store(&self) -> &nn::VarStore {
         &self.var_store
     }
+    fn get_var_store_mut(&mut self) -> Result<&mut nn::VarStore, RustBertError> {
+        Ok(&mut self.var_store)
+    }
     fn get_config(&self) -> &GenerateConfig {
         &self.generate_config
     }
@@ -437,30 +1376,39 @@ impl GPT2Generator {
             None,
             None,
         )?;
-        let config = Gpt2Config::from_file(config_path);
+        let config = Gpt2Config::try_from_file(config_path)?;
+        tokenizer.check_model_compatibility(ModelType::GPT2, config.vocab_size)?;
         let model = GPT2LMHeadModel::new(&var_store.root(), &config);
         var_store.load(weights_path)?;
 
-        let bos_token_id = Some(tokenizer.convert_tokens_to_ids(&[Gpt2Vocab::bos_value()])[0]);
-        let eos_token_ids = Some(tokenizer.convert_tokens_to_ids(&[Gpt2Vocab::eos_value()]));
-        let pad_token_id = None;
         let is_encoder_decoder = false;
         let vocab_size = config.vocab_size;
-        let decoder_start_id = None;
+        let token_ids = SpecialTokenIds::from_generate_config(&generate_config)
+            .or(SpecialTokenIds::from_tokenizer(&tokenizer))
+            .resolve(is_encoder_decoder, vocab_size)?;
 
         Ok(GPT2Generator {
             model,
             tokenizer,
             var_store,
             generate_config,
-            bos_token_id,
-            eos_token_ids,
-            pad_token_id,
+            bos_token_id: token_ids.bos_token_id,
+            eos_token_ids: token_ids.eos_token_ids,
+            pad_token_id: token_ids.pad_token_id,
             is_encoder_decoder,
             vocab_size,
-            decoder_start_id,
+            decoder_start_id: token_ids.decoder_start_id,
         })
     }
+
+    /// Sets (or removes if `None`) the prefix-tuning keys and values used for generation
+    ///
+    /// # Arguments
+    ///
+    /// * `prefix` - Optional `PrefixTuning` with one key/value pair per layer of the model
+    pub fn set_prefix(&mut self, prefix: Option<PrefixTuning>) -> Result<(), RustBertError> {
+        self.model.set_prefix(prefix)
+    }
 }
 
 impl PrivateLanguageGenerator<GPT2LMHeadModel, Gpt2Vocab, Gpt2Tokenizer> for GPT2Generator {
@@ -473,6 +1421,9 @@ impl PrivateLanguageGenerator<GPT2LMHeadModel, Gpt2Vocab, Gpt2Tokenizer> for GPT
     fn get_var_store(&self) -> &nn::VarStore {
         &self.var_store
     }
+    fn get_var_store_mut(&mut self) -> Result<&mut nn::VarStore, RustBertError> {
+        Ok(&mut self.var_store)
+    }
     fn get_config(&self) -> &GenerateConfig {
         &self.generate_config
     }
@@ -568,8 +1519,9 @@ impl LanguageGenerator<GPT2LMHeadModel, Gpt2Vocab, Gpt2Tokenizer> for GPT2Genera
 /// # Language generation model based on the Bart architecture
 pub struct BartGenerator {
     model: BartForConditionalGeneration,
-    tokenizer: TokenizerOption,
+    tokenizer: Arc<TokenizerOption>,
     var_store: nn::VarStore,
+    config: BartConfig,
     generate_config: GenerateConfig,
     bos_token_id: Option<i64>,
     eos_token_ids: Option<Vec<i64>>,
@@ -667,31 +1619,42 @@ impl BartGenerator {
             None,
             false,
         )?;
-        let config = BartConfig::from_file(config_path);
+        let config = BartConfig::try_from_file(config_path)?;
+        tokenizer.check_model_compatibility(ModelType::Bart, config.vocab_size)?;
         let model = BartForConditionalGeneration::new(&var_store.root(), &config, true);
         var_store.load(weights_path)?;
 
-        let bos_token_id = Some(0);
-        let eos_token_ids = Some(match config.eos_token_id {
-            Some(value) => vec![value],
-            None => vec![2],
-        });
-        let pad_token_id = Some(config.pad_token_id.unwrap_or(1));
-        let vocab_size = config.vocab_size;
         let is_encoder_decoder = true;
-        let decoder_start_id = Some(2);
+        let vocab_size = config.vocab_size;
+        let token_ids = SpecialTokenIds::from_generate_config(&generate_config)
+            .or(SpecialTokenIds {
+                bos_token_id: config.bos_token_id,
+                eos_token_ids: config.eos_token_id.map(|eos_token_id| vec![eos_token_id]),
+                pad_token_id: config.pad_token_id,
+                decoder_start_id: config.decoder_start_token_id,
+            })
+            .or(SpecialTokenIds::from_tokenizer(&tokenizer));
+        //  BART decoders start from the end of sequence token
+        let decoder_start_id = token_ids.eos_token_ids.as_ref().map(|ids| ids[0]);
+        let token_ids = token_ids
+            .or(SpecialTokenIds {
+                decoder_start_id,
+                ..Default::default()
+            })
+            .resolve(is_encoder_decoder, vocab_size)?;
 
         Ok(BartGenerator {
             model,
-            tokenizer,
+            tokenizer: Arc::new(tokenizer),
             var_store,
+            config,
             generate_config,
-            bos_token_id,
-            eos_token_ids,
-            pad_token_id,
+            bos_token_id: token_ids.bos_token_id,
+            eos_token_ids: token_ids.eos_token_ids,
+            pad_token_id: token_ids.pad_token_id,
             is_encoder_decoder,
             vocab_size,
-            decoder_start_id,
+            decoder_start_id: token_ids.decoder_start_id,
         })
     }
 
@@ -702,6 +1665,39 @@ impl BartGenerator {
         let impossible_tokens = Tensor::of_slice(&impossible_tokens).to_device(scores.device());
         let _ = scores.index_fill_(1, &impossible_tokens, std::f64::NEG_INFINITY);
     }
+
+    /// Creates a new `BartGenerator` sharing the model weights and tokenizer of this generator, with a different
+    /// generation configuration. The resources and device of the configuration provided are ignored.
+    ///
+    /// The model of the new generator is created with its own variable store, whose variables share the memory of
+    /// the weights of this generator. Reloading the weights of one of the generators (see `reload_weights`) updates
+    /// the weights of all the generators sharing them.
+    ///
+    /// # Arguments
+    ///
+    /// * `generate_config` - `GenerateConfig` generation options for the new generator
+    pub fn with_generate_config(
+        &self,
+        generate_config: GenerateConfig,
+    ) -> Result<BartGenerator, RustBertError> {
+        generate_config.validate();
+        let mut var_store = nn::VarStore::new(self.var_store.device());
+        let model = BartForConditionalGeneration::new(&var_store.root(), &self.config, true);
+        share_var_store(&mut var_store, &self.var_store)?;
+        Ok(BartGenerator {
+            model,
+            tokenizer: self.tokenizer.clone(),
+            var_store,
+            config: self.config.clone(),
+            generate_config,
+            bos_token_id: self.bos_token_id,
+            eos_token_ids: self.eos_token_ids.clone(),
+            pad_token_id: self.pad_token_id,
+            is_encoder_decoder: self.is_encoder_decoder,
+            vocab_size: self.vocab_size,
+            decoder_start_id: self.decoder_start_id,
+        })
+    }
 }
 
 impl PrivateLanguageGenerator<BartForConditionalGeneration, RobertaVocab, RobertaTokenizer>
@@ -716,6 +1712,9 @@ impl PrivateLanguageGenerator<BartForConditionalGeneration, RobertaVocab, Robert
     fn get_var_store(&self) -> &nn::VarStore {
         &self.var_store
     }
+    fn get_var_store_mut(&mut self) -> Result<&mut nn::VarStore, RustBertError> {
+        Ok(&mut self.var_store)
+    }
     fn get_config(&self) -> &GenerateConfig {
         &self.generate_config
     }
@@ -839,31 +1838,7 @@ impl PrivateLanguageGenerator<BartForConditionalGeneration, RobertaVocab, Robert
             Some(value) => Some(value.index_select(0, beam_indices)),
             None => None,
         };
-        match past {
-            Cache::BARTCache(old_cache_option) => match old_cache_option {
-                Some(old_cache) => {
-                    for (self_layer_state, encoder_layer_state) in old_cache.iter_mut() {
-                        if self_layer_state.is_some() {
-                            self_layer_state
-                                .as_mut()
-                                .unwrap()
-                                .reorder_cache(beam_indices)
-                        };
-                        if encoder_layer_state.is_some() {
-                            encoder_layer_state
-                                .as_mut()
-                                .unwrap()
-                                .reorder_cache(beam_indices)
-                        };
-                    }
-                }
-                None => {}
-            },
-            Cache::None => {}
-            _ => {
-                panic!("Invalid cache for BART model");
-            }
-        };
+        Seq2SeqGenerationModel::reorder_cache(self.get_model(), past, beam_indices);
         encoder_outputs
     }
 }
@@ -876,8 +1851,9 @@ impl LanguageGenerator<BartForConditionalGeneration, RobertaVocab, RobertaTokeni
 /// # Language generation model based on the Marian architecture for machine translation
 pub struct MarianGenerator {
     model: MarianForConditionalGeneration,
-    tokenizer: TokenizerOption,
+    tokenizer: Arc<TokenizerOption>,
     var_store: nn::VarStore,
+    config: BartConfig,
     generate_config: GenerateConfig,
     bos_token_id: Option<i64>,
     eos_token_ids: Option<Vec<i64>>,
@@ -943,30 +1919,42 @@ impl MarianGenerator {
             None,
         )?;
 
-        let config = BartConfig::from_file(config_path);
+        let config = BartConfig::try_from_file(config_path)?;
+        tokenizer.check_model_compatibility(ModelType::Marian, config.vocab_size)?;
         let model = MarianForConditionalGeneration::new(&var_store.root(), &config, true);
         var_store.load(weights_path)?;
 
-        let bos_token_id = Some(0);
-        let eos_token_ids = Some(tokenizer.convert_tokens_to_ids(&[MarianVocab::eos_value()]));
-        let pad_token_id = Some(tokenizer.convert_tokens_to_ids(&[MarianVocab::pad_value()])[0]);
-
-        let vocab_size = config.vocab_size;
         let is_encoder_decoder = true;
-        let decoder_start_id =
-            Some(tokenizer.convert_tokens_to_ids(&[MarianVocab::pad_value()])[0]);
+        let vocab_size = config.vocab_size;
+        let token_ids = SpecialTokenIds::from_generate_config(&generate_config)
+            .or(SpecialTokenIds {
+                bos_token_id: config.bos_token_id,
+                eos_token_ids: config.eos_token_id.map(|eos_token_id| vec![eos_token_id]),
+                pad_token_id: config.pad_token_id,
+                decoder_start_id: config.decoder_start_token_id,
+            })
+            .or(SpecialTokenIds::from_tokenizer(&tokenizer));
+        //  Marian decoders start from the padding token
+        let decoder_start_id = token_ids.pad_token_id;
+        let token_ids = token_ids
+            .or(SpecialTokenIds {
+                decoder_start_id,
+                ..Default::default()
+            })
+            .resolve(is_encoder_decoder, vocab_size)?;
 
         Ok(MarianGenerator {
             model,
-            tokenizer,
+            tokenizer: Arc::new(tokenizer),
             var_store,
+            config,
             generate_config,
-            bos_token_id,
-            eos_token_ids,
-            pad_token_id,
+            bos_token_id: token_ids.bos_token_id,
+            eos_token_ids: token_ids.eos_token_ids,
+            pad_token_id: token_ids.pad_token_id,
             is_encoder_decoder,
             vocab_size,
-            decoder_start_id,
+            decoder_start_id: token_ids.decoder_start_id,
         })
     }
 
@@ -977,6 +1965,39 @@ impl MarianGenerator {
         let impossible_tokens = Tensor::of_slice(&impossible_tokens).to_device(scores.device());
         let _ = scores.index_fill_(1, &impossible_tokens, f64::NEG_INFINITY);
     }
+
+    /// Creates a new `MarianGenerator` sharing the model weights and tokenizer of this generator, with a different
+    /// generation configuration. The resources and device of the configuration provided are ignored.
+    ///
+    /// The model of the new generator is created with its own variable store, whose variables share the memory of
+    /// the weights of this generator. Reloading the weights of one of the generators (see `reload_weights`) updates
+    /// the weights of all the generators sharing them.
+    ///
+    /// # Arguments
+    ///
+    /// * `generate_config` - `GenerateConfig` generation options for the new generator
+    pub fn with_generate_config(
+        &self,
+        generate_config: GenerateConfig,
+    ) -> Result<MarianGenerator, RustBertError> {
+        generate_config.validate();
+        let mut var_store = nn::VarStore::new(self.var_store.device());
+        let model = MarianForConditionalGeneration::new(&var_store.root(), &self.config, true);
+        share_var_store(&mut var_store, &self.var_store)?;
+        Ok(MarianGenerator {
+            model,
+            tokenizer: self.tokenizer.clone(),
+            var_store,
+            config: self.config.clone(),
+            generate_config,
+            bos_token_id: self.bos_token_id,
+            eos_token_ids: self.eos_token_ids.clone(),
+            pad_token_id: self.pad_token_id,
+            is_encoder_decoder: self.is_encoder_decoder,
+            vocab_size: self.vocab_size,
+            decoder_start_id: self.decoder_start_id,
+        })
+    }
 }
 
 impl PrivateLanguageGenerator<MarianForConditionalGeneration, MarianVocab, MarianTokenizer>
@@ -991,6 +2012,9 @@ impl PrivateLanguageGenerator<MarianForConditionalGeneration, MarianVocab, Maria
     fn get_var_store(&self) -> &nn::VarStore {
         &self.var_store
     }
+    fn get_var_store_mut(&mut self) -> Result<&mut nn::VarStore, RustBertError> {
+        Ok(&mut self.var_store)
+    }
     fn get_config(&self) -> &GenerateConfig {
         &self.generate_config
     }
@@ -1117,31 +2141,7 @@ impl PrivateLanguageGenerator<MarianForConditionalGeneration, MarianVocab, Maria
             Some(value) => Some(value.index_select(0, beam_indices)),
             None => None,
         };
-        match past {
-            Cache::BARTCache(old_cache_option) => match old_cache_option {
-                Some(old_cache) => {
-                    for (self_layer_state, encoder_layer_state) in old_cache.iter_mut() {
-                        if self_layer_state.is_some() {
-                            self_layer_state
-                                .as_mut()
-                                .unwrap()
-                                .reorder_cache(beam_indices)
-                        };
-                        if encoder_layer_state.is_some() {
-                            encoder_layer_state
-                                .as_mut()
-                                .unwrap()
-                                .reorder_cache(beam_indices)
-                        };
-                    }
-                }
-                None => {}
-            },
-            Cache::None => {}
-            _ => {
-                panic!("Invalid cache for BART model");
-            }
-        };
+        Seq2SeqGenerationModel::reorder_cache(self.get_model(), past, beam_indices);
         encoder_outputs
     }
 }
@@ -1153,8 +2153,9 @@ impl LanguageGenerator<MarianForConditionalGeneration, MarianVocab, MarianTokeni
 
 pub struct T5Generator {
     model: T5ForConditionalGeneration,
-    tokenizer: TokenizerOption,
+    tokenizer: Arc<TokenizerOption>,
     var_store: nn::VarStore,
+    config: T5Config,
     generate_config: GenerateConfig,
     bos_token_id: Option<i64>,
     eos_token_ids: Option<Vec<i64>>,
@@ -1207,33 +2208,86 @@ impl T5Generator {
             None,
         )?;
 
-        let config = T5Config::from_file(config_path);
+        let config = T5Config::try_from_file(config_path)?;
+        tokenizer.check_model_compatibility(ModelType::T5, config.vocab_size)?;
         let model = T5ForConditionalGeneration::new(&var_store.root(), &config, false, false);
         var_store.load(weights_path)?;
 
-        let bos_token_id = Some(-1);
-        let eos_token_ids = Some(match config.eos_token_id {
-            Some(value) => vec![value],
-            None => vec![1],
-        });
-        let pad_token_id = Some(config.pad_token_id.unwrap_or(0));
-        let vocab_size = config.vocab_size;
         let is_encoder_decoder = true;
-        let decoder_start_id = Some(0);
+        let vocab_size = config.vocab_size;
+        let token_ids = SpecialTokenIds::from_generate_config(&generate_config)
+            .or(SpecialTokenIds {
+                bos_token_id: None,
+                eos_token_ids: config.eos_token_id.map(|eos_token_id| vec![eos_token_id]),
+                pad_token_id: config.pad_token_id,
+                decoder_start_id: config.decoder_start_token_id,
+            })
+            .or(SpecialTokenIds::from_tokenizer(&tokenizer));
+        //  T5 decoders start from the padding token
+        let decoder_start_id = token_ids.pad_token_id;
+        let token_ids = token_ids
+            .or(SpecialTokenIds {
+                decoder_start_id,
+                ..Default::default()
+            })
+            .resolve(is_encoder_decoder, vocab_size)?;
 
         Ok(T5Generator {
             model,
-            tokenizer,
+            tokenizer: Arc::new(tokenizer),
             var_store,
+            config,
             generate_config,
-            bos_token_id,
-            eos_token_ids,
-            pad_token_id,
+            bos_token_id: token_ids.bos_token_id,
+            eos_token_ids: token_ids.eos_token_ids,
+            pad_token_id: token_ids.pad_token_id,
             is_encoder_decoder,
             vocab_size,
-            decoder_start_id,
+            decoder_start_id: token_ids.decoder_start_id,
+        })
+    }
+
+    /// Creates a new `T5Generator` sharing the model weights and tokenizer of this generator, with a different
+    /// generation configuration. The resources and device of the configuration provided are ignored.
+    ///
+    /// The model of the new generator is created with its own variable store, whose variables share the memory of
+    /// the weights of this generator. Reloading the weights of one of the generators (see `reload_weights`) updates
+    /// the weights of all the generators sharing them.
+    ///
+    /// # Arguments
+    ///
+    /// * `generate_config` - `GenerateConfig` generation options for the new generator
+    pub fn with_generate_config(
+        &self,
+        generate_config: GenerateConfig,
+    ) -> Result<T5Generator, RustBertError> {
+        generate_config.validate();
+        let mut var_store = nn::VarStore::new(self.var_store.device());
+        let model = T5ForConditionalGeneration::new(&var_store.root(), &self.config, false, false);
+        share_var_store(&mut var_store, &self.var_store)?;
+        Ok(T5Generator {
+            model,
+            tokenizer: self.tokenizer.clone(),
+            var_store,
+            config: self.config.clone(),
+            generate_config,
+            bos_token_id: self.bos_token_id,
+            eos_token_ids: self.eos_token_ids.clone(),
+            pad_token_id: self.pad_token_id,
+            is_encoder_decoder: self.is_encoder_decoder,
+            vocab_size: self.vocab_size,
+            decoder_start_id: self.decoder_start_id,
         })
     }
+
+    /// Sets (or removes if `None`) the prefix-tuning keys and values used for generation
+    ///
+    /// # Arguments
+    ///
+    /// * `prefix` - Optional `PrefixTuning` with one key/value pair per decoder layer of the model
+    pub fn set_prefix(&mut self, prefix: Option<PrefixTuning>) -> Result<(), RustBertError> {
+        self.model.set_prefix(prefix)
+    }
 }
 
 impl PrivateLanguageGenerator<T5ForConditionalGeneration, T5Vocab, T5Tokenizer> for T5Generator {
@@ -1246,6 +2300,9 @@ impl PrivateLanguageGenerator<T5ForConditionalGeneration, T5Vocab, T5Tokenizer>
     fn get_var_store(&self) -> &nn::VarStore {
         &self.var_store
     }
+    fn get_var_store_mut(&mut self) -> Result<&mut nn::VarStore, RustBertError> {
+        Ok(&mut self.var_store)
+    }
     fn get_config(&self) -> &GenerateConfig {
         &self.generate_config
     }
@@ -1351,37 +2408,290 @@ impl PrivateLanguageGenerator<T5ForConditionalGeneration, T5Vocab, T5Tokenizer>
         encoder_outputs: Option<Tensor>,
         beam_indices: &Tensor,
     ) -> Option<Tensor> {
-        match past {
-            Cache::T5Cache(old_cache_option) => match old_cache_option {
-                Some(old_cache) => {
-                    for (self_layer_state, encoder_layer_state) in old_cache.iter_mut() {
-                        if self_layer_state.is_some() {
-                            self_layer_state
-                                .as_mut()
-                                .unwrap()
-                                .reorder_cache(beam_indices)
-                        };
-                        if encoder_layer_state.is_some() {
-                            encoder_layer_state
-                                .as_mut()
-                                .unwrap()
-                                .reorder_cache(beam_indices)
-                        };
-                    }
-                }
-                None => {}
-            },
-            Cache::None => {}
-            _ => {
-                panic!("Invalid cache for T5 model");
-            }
-        };
+        Seq2SeqGenerationModel::reorder_cache(self.get_model(), past, beam_indices);
         encoder_outputs
     }
 }
 
 impl LanguageGenerator<T5ForConditionalGeneration, T5Vocab, T5Tokenizer> for T5Generator {}
 
+/// # Language model head adapter for custom encoder-decoder models
+/// Wraps a model implementing `Seq2SeqGenerationModel` so that it can be used by the generation loop of a
+/// `Seq2SeqGenerator`.
+pub struct Seq2SeqLMHead<M: Seq2SeqGenerationModel> {
+    model: M,
+}
+
+impl<M: Seq2SeqGenerationModel> Seq2SeqLMHead<M> {
+    /// Returns the wrapped model
+    pub fn model(&self) -> &M {
+        &self.model
+    }
+}
+
+impl<M: Seq2SeqGenerationModel> LMHeadModel for Seq2SeqLMHead<M> {
+    fn forward_t(
+        &self,
+        _input_ids: &Option<Tensor>,
+        cache: Cache,
+        attention_mask: &Option<Tensor>,
+        _token_type_ids: &Option<Tensor>,
+        _position_ids: &Option<Tensor>,
+        _input_embeds: &Option<Tensor>,
+        encoder_outputs: Option<&Tensor>,
+        decoder_input_ids: &Option<Tensor>,
+        _train: bool,
+    ) -> Result<LMModelOutput, RustBertError> {
+        let decoder_input_ids = decoder_input_ids.as_ref().ok_or_else(|| {
+            RustBertError::ValueError("Decoder input ids must be provided".into())
+        })?;
+        let encoder_outputs = encoder_outputs
+            .ok_or_else(|| RustBertError::ValueError("Encoder outputs must be provided".into()))?;
+        self.model.decode_step(
+            decoder_input_ids,
+            encoder_outputs,
+            attention_mask.as_ref(),
+            cache,
+        )
+    }
+}
+
+/// # Special token ids for a custom encoder-decoder generator
+pub struct Seq2SeqTokenIds {
+    /// Token id used as the first decoder input
+    pub decoder_start_id: i64,
+    /// End of sequence token ids
+    pub eos_token_ids: Vec<i64>,
+    /// Padding token id (defaults to the first end of sequence token id)
+    pub pad_token_id: Option<i64>,
+}
+
+/// # Language generation model based on a custom encoder-decoder architecture
+/// Allows generating text with any model implementing `Seq2SeqGenerationModel`, using the generation options and
+/// decoding strategies (sampling, beam search...) of the crate. The `RobertaVocab` and `RobertaTokenizer` type parameters
+/// of the generation traits are not used: the inputs are tokenized using the `TokenizerOption` provided.
+pub struct Seq2SeqGenerator<M: Seq2SeqGenerationModel> {
+    model: Seq2SeqLMHead<M>,
+    tokenizer: TokenizerOption,
+    var_store: nn::VarStore,
+    generate_config: GenerateConfig,
+    bos_token_id: Option<i64>,
+    eos_token_ids: Option<Vec<i64>>,
+    pad_token_id: Option<i64>,
+    vocab_size: i64,
+    decoder_start_id: Option<i64>,
+}
+
+impl<M: Seq2SeqGenerationModel> Seq2SeqGenerator<M> {
+    /// Build a new `Seq2SeqGenerator` from a model whose weights are already loaded in the variable store provided.
+    /// The resources of the generation configuration are ignored.
+    ///
+    /// # Arguments
+    ///
+    /// * `model` - encoder-decoder model implementing `Seq2SeqGenerationModel`
+    /// * `var_store` - `VarStore` holding the model weights
+    /// * `tokenizer` - `TokenizerOption` used to tokenize the prompts and decode the generated sequences
+    /// * `generate_config` - `GenerateConfig` generation options
+    /// * `vocab_size` - size of the vocabulary of the model output
+    /// * `token_ids` - `Seq2SeqTokenIds` special token ids of the model
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// # fn main() -> anyhow::Result<()> {
+    /// use rust_bert::pipelines::common::{ModelType, TokenizerOption};
+    /// use rust_bert::pipelines::generation_utils::{
+    ///     GenerateConfig, LanguageGenerator, Seq2SeqGenerator, Seq2SeqTokenIds,
+    /// };
+    /// use rust_bert::t5::{T5Config, T5ForConditionalGeneration};
+    /// use rust_bert::Config;
+    /// use tch::{nn, Device};
+    ///
+    /// let mut var_store = nn::VarStore::new(Device::cuda_if_available());
+    /// let config = T5Config::from_file("path/to/config.json");
+    /// let model = T5ForConditionalGeneration::new(&var_store.root(), &config, false, false);
+    /// var_store.load("path/to/model.ot")?;
+    /// let tokenizer =
+    ///     TokenizerOption::from_file(ModelType::T5, "path/to/spiece.model", None, false, None, None)?;
+    ///
+    /// let generator = Seq2SeqGenerator::new(
+    ///     model,
+    ///     var_store,
+    ///     tokenizer,
+    ///     GenerateConfig::default(),
+    ///     config.vocab_size,
+    ///     Seq2SeqTokenIds {
+    ///         decoder_start_id: 0,
+    ///         eos_token_ids: vec![1],
+    ///         pad_token_id: Some(0),
+    ///     },
+    /// )?;
+    /// let output = generator.generate(Some(vec!["summarize: The dog"]), None, None, None, None);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn new(
+        model: M,
+        var_store: nn::VarStore,
+        tokenizer: TokenizerOption,
+        generate_config: GenerateConfig,
+        vocab_size: i64,
+        token_ids: Seq2SeqTokenIds,
+    ) -> Result<Seq2SeqGenerator<M>, RustBertError> {
+        generate_config.validate();
+        tokenizer.check_model_compatibility(tokenizer.model_type(), vocab_size)?;
+        let token_ids = SpecialTokenIds::from_generate_config(&generate_config)
+            .or(SpecialTokenIds {
+                bos_token_id: None,
+                eos_token_ids: Some(token_ids.eos_token_ids),
+                pad_token_id: token_ids.pad_token_id,
+                decoder_start_id: Some(token_ids.decoder_start_id),
+            })
+            .resolve(true, vocab_size)?;
+
+        Ok(Seq2SeqGenerator {
+            model: Seq2SeqLMHead { model },
+            tokenizer,
+            var_store,
+            generate_config,
+            bos_token_id: token_ids.bos_token_id,
+            eos_token_ids: token_ids.eos_token_ids,
+            pad_token_id: token_ids.pad_token_id,
+            vocab_size,
+            decoder_start_id: token_ids.decoder_start_id,
+        })
+    }
+
+    /// Returns the underlying model
+    pub fn model(&self) -> &M {
+        self.model.model()
+    }
+}
+
+impl<M: Seq2SeqGenerationModel>
+    PrivateLanguageGenerator<Seq2SeqLMHead<M>, RobertaVocab, RobertaTokenizer>
+    for Seq2SeqGenerator<M>
+{
+    fn get_model(&self) -> &Seq2SeqLMHead<M> {
+        &self.model
+    }
+    fn get_tokenizer(&self) -> &TokenizerOption {
+        &self.tokenizer
+    }
+    fn get_var_store(&self) -> &nn::VarStore {
+        &self.var_store
+    }
+    fn get_var_store_mut(&mut self) -> Result<&mut nn::VarStore, RustBertError> {
+        Ok(&mut self.var_store)
+    }
+    fn get_config(&self) -> &GenerateConfig {
+        &self.generate_config
+    }
+    fn get_bos_id(&self) -> &Option<i64> {
+        &self.bos_token_id
+    }
+    fn get_eos_ids(&self) -> &Option<Vec<i64>> {
+        &self.eos_token_ids
+    }
+    fn get_pad_id(&self) -> &Option<i64> {
+        &self.pad_token_id
+    }
+    fn is_encoder_decoder(&self) -> bool {
+        true
+    }
+    fn get_vocab_size(&self) -> i64 {
+        self.vocab_size
+    }
+    fn get_decoder_start_id(&self) -> Option<i64> {
+        self.decoder_start_id
+    }
+
+    fn encode(&self, input_ids: &Tensor, attention_mask: Option<&Tensor>) -> Option<Tensor> {
+        Some(self.model.model.encode(input_ids, attention_mask))
+    }
+
+    fn prepare_inputs_for_generation<'a>(
+        &self,
+        input_ids: Tensor,
+        encoder_outputs: Option<&'a Tensor>,
+        past: Cache,
+        attention_mask: Tensor,
+    ) -> (
+        Option<Tensor>,
+        Option<Tensor>,
+        Option<&'a Tensor>,
+        Option<Tensor>,
+        Cache,
+    ) {
+        (
+            None,
+            Some(attention_mask),
+            encoder_outputs,
+            Some(input_ids),
+            past,
+        )
+    }
+
+    fn encode_prompt_text<'a, S>(
+        &self,
+        prompt_text: S,
+        max_len: i64,
+        pad_token_id: Option<i64>,
+    ) -> Tensor
+    where
+        S: AsRef<[&'a str]>,
+    {
+        let tokens = self.get_tokenizer().encode_list(
+            prompt_text.as_ref(),
+            max_len as usize,
+            &TruncationStrategy::LongestFirst,
+            0,
+        );
+        let token_ids = tokens
+            .into_iter()
+            .map(|tokenized_input| tokenized_input.token_ids)
+            .collect::<Vec<Vec<i64>>>();
+
+        let max_len = token_ids.iter().map(|input| input.len()).max().unwrap();
+
+        let pad_token = match pad_token_id {
+            Some(value) => value,
+            None => self.get_tokenizer().get_unk_id(),
+        };
+
+        let token_ids = token_ids
+            .into_iter()
+            .map(|mut input| {
+                let temp = vec![pad_token; max_len - input.len()];
+                input.extend(temp);
+                input
+            })
+            .map(|tokens| Tensor::of_slice(&tokens).to(self.get_var_store().device()))
+            .collect::<Vec<Tensor>>();
+
+        Tensor::stack(&token_ids, 0)
+    }
+
+    fn reorder_cache(
+        &self,
+        past: &mut Cache,
+        encoder_outputs: Option<Tensor>,
+        beam_indices: &Tensor,
+    ) -> Option<Tensor> {
+        let encoder_outputs = match encoder_outputs {
+            Some(value) => Some(value.index_select(0, beam_indices)),
+            None => None,
+        };
+        self.model.model.reorder_cache(past, beam_indices);
+        encoder_outputs
+    }
+}
+
+impl<M: Seq2SeqGenerationModel> LanguageGenerator<Seq2SeqLMHead<M>, RobertaVocab, RobertaTokenizer>
+    for Seq2SeqGenerator<M>
+{
+}
+
 /// # Language generation model based on the XLNet architecture
 pub struct XLNetGenerator {
     model: XLNetLMHeadModel,
@@ -1438,28 +2748,33 @@ impl XLNetGenerator {
             None,
         )?;
 
-        let config = XLNetConfig::from_file(config_path);
+        let config = XLNetConfig::try_from_file(config_path)?;
+        tokenizer.check_model_compatibility(ModelType::XLNet, config.vocab_size)?;
         let model = XLNetLMHeadModel::new(&var_store.root(), &config);
         var_store.load(weights_path)?;
 
-        let bos_token_id = Some(config.bos_token_id);
-        let eos_token_ids = Some(vec![config.eos_token_id]);
-        let pad_token_id = Some(config.pad_token_id);
         let is_encoder_decoder = false;
         let vocab_size = config.vocab_size;
-        let decoder_start_id = None;
+        let token_ids = SpecialTokenIds::from_generate_config(&generate_config)
+            .or(SpecialTokenIds {
+                bos_token_id: Some(config.bos_token_id),
+                eos_token_ids: Some(vec![config.eos_token_id]),
+                pad_token_id: Some(config.pad_token_id),
+                decoder_start_id: None,
+            })
+            .resolve(is_encoder_decoder, vocab_size)?;
 
         Ok(XLNetGenerator {
             model,
             tokenizer,
             var_store,
             generate_config,
-            bos_token_id,
-            eos_token_ids,
-            pad_token_id,
+            bos_token_id: token_ids.bos_token_id,
+            eos_token_ids: token_ids.eos_token_ids,
+            pad_token_id: token_ids.pad_token_id,
             is_encoder_decoder,
             vocab_size,
-            decoder_start_id,
+            decoder_start_id: token_ids.decoder_start_id,
         })
     }
 }
@@ -1474,6 +2789,9 @@ impl PrivateLanguageGenerator<XLNetLMHeadModel, XLNetVocab, XLNetTokenizer> for
     fn get_var_store(&self) -> &nn::VarStore {
         &self.var_store
     }
+    fn get_var_store_mut(&mut self) -> Result<&mut nn::VarStore, RustBertError> {
+        Ok(&mut self.var_store)
+    }
     fn get_config(&self) -> &GenerateConfig {
         &self.generate_config
     }
@@ -1682,28 +3000,33 @@ impl ReformerGenerator {
             None,
             None,
         )?;
-        let config = ReformerConfig::from_file(config_path);
+        let config = ReformerConfig::try_from_file(config_path)?;
+        tokenizer.check_model_compatibility(ModelType::Reformer, config.vocab_size)?;
         let model = ReformerModelWithLMHead::new(&var_store.root(), &config)?;
         var_store.load(weights_path)?;
 
-        let bos_token_id = None;
-        let eos_token_ids = Some(vec![config.eos_token_id]);
-        let pad_token_id = Some(config.pad_token_id);
-        let vocab_size = config.vocab_size;
         let is_encoder_decoder = false;
-        let decoder_start_id = None;
+        let vocab_size = config.vocab_size;
+        let token_ids = SpecialTokenIds::from_generate_config(&generate_config)
+            .or(SpecialTokenIds {
+                bos_token_id: None,
+                eos_token_ids: Some(vec![config.eos_token_id]),
+                pad_token_id: Some(config.pad_token_id),
+                decoder_start_id: None,
+            })
+            .resolve(is_encoder_decoder, vocab_size)?;
 
         Ok(ReformerGenerator {
             model,
             tokenizer,
             var_store,
             generate_config,
-            bos_token_id,
-            eos_token_ids,
-            pad_token_id,
+            bos_token_id: token_ids.bos_token_id,
+            eos_token_ids: token_ids.eos_token_ids,
+            pad_token_id: token_ids.pad_token_id,
             is_encoder_decoder,
             vocab_size,
-            decoder_start_id,
+            decoder_start_id: token_ids.decoder_start_id,
         })
     }
 }
@@ -1720,6 +3043,9 @@ impl PrivateLanguageGenerator<ReformerModelWithLMHead, ReformerVocab, ReformerTo
     fn get_var_store(&self) -> &nn::VarStore {
         &self.var_store
     }
+    fn get_var_store_mut(&mut self) -> Result<&mut nn::VarStore, RustBertError> {
+        Ok(&mut self.var_store)
+    }
     fn get_config(&self) -> &GenerateConfig {
         &self.generate_config
     }
@@ -1817,23 +3143,36 @@ pub enum Cache {
 
 pub(crate) mod private_generation_utils {
     use super::ordered_float::OrderedFloat;
+    use crate::common::error::RustBertError;
+    use crate::common::profiling;
     use crate::pipelines::common::TokenizerOption;
-    use crate::pipelines::generation_utils::{BeamHypotheses, Cache, GenerateConfig, LMHeadModel};
+    use crate::pipelines::generation_utils::{
+        Cache, EncodedPrompts, GenerateConfig, GenerateOptions, GenerationUsage, LMHeadModel,
+        PrefixAllowedTokensFn, SeededSampler, StoppingCriteria, MAX_PROMPT_LENGTH,
+    };
+    use crate::pipelines::grammar::Grammar;
+    use itertools::Itertools;
     use rust_tokenizers::tokenizer::{truncate_sequences, Tokenizer, TruncationStrategy};
     use rust_tokenizers::vocab::Vocab;
     use rust_tokenizers::TokenIdsWithOffsets;
     use std::cmp::{max, min};
     use std::collections::HashMap;
-    use tch::kind::Kind::{Bool, Float, Int64};
-    use tch::{nn, Device, Tensor};
+    use std::sync::Arc;
+    use std::time::Instant;
+    use tch::kind::Kind::{Bool, Double, Float, Int64};
+    use tch::{nn, no_grad, Device, Tensor};
 
-    pub struct GenerateOptions {
+    pub struct ResolvedGenerateOptions {
         pub min_length: i64,
         pub max_length: i64,
         pub do_sample: bool,
+        pub seed: Option<u64>,
         pub temperature: f64,
         pub top_k: i64,
         pub top_p: f64,
+        pub typical_p: f64,
+        pub epsilon_cutoff: f64,
+        pub eta_cutoff: f64,
         pub repetition_penalty: f64,
         pub no_repeat_ngram_size: i64,
         pub pad_token_id: Option<i64>,
@@ -1841,13 +3180,158 @@ pub(crate) mod private_generation_utils {
         pub num_return_sequences: i64,
         pub early_stopping: bool,
         pub num_beams: i64,
+        pub num_beam_groups: i64,
+        pub diversity_penalty: f64,
         pub length_penalty: f64,
+        pub allowed_token_ids: Option<Vec<i64>>,
+        pub prefix_allowed_tokens_fn: Option<PrefixAllowedTokensFn>,
+        pub stopping_criteria: Option<Arc<dyn StoppingCriteria>>,
+        pub force_words_ids: Option<Vec<Vec<i64>>>,
+        pub bad_words_ids: Option<Vec<Vec<i64>>>,
+        pub grammar: Option<Arc<Grammar>>,
+        pub grammar_token_texts: Option<Vec<String>>,
+        pub source_copy_bias: f64,
+        pub source_ids: Option<Tensor>,
+        pub encoder_no_repeat_ngram_size: i64,
+        pub encoder_ngrams: Option<Vec<HashMap<Vec<i64>, Vec<i64>>>>,
+        pub dry_multiplier: f64,
+        pub dry_base: f64,
+        pub dry_allowed_length: i64,
+        pub dry_sequence_breakers: Option<Vec<i64>>,
+        pub token_healing: bool,
+        pub token_healing_ids: Option<Vec<Vec<i64>>>,
+        pub cache_check_tolerance: Option<f64>,
+    }
+
+    impl ResolvedGenerateOptions {
+        /// Number of generated rows (beams and returned sequences) for each prompt of the batch
+        pub fn rows_per_prompt(&self) -> i64 {
+            if self.do_sample {
+                self.num_beams * self.num_return_sequences
+            } else {
+                self.num_beams
+            }
+        }
+    }
+
+    // Progress of generated token ids toward the forced token sequences: number of constraint tokens met (all the
+    // tokens of the sequences present, and the prefix ending the generated ids for the others) and next token of each
+    // sequence not present yet
+    pub(crate) fn constraint_progress(
+        generated_ids: &[i64],
+        force_words_ids: &[Vec<i64>],
+    ) -> (usize, Vec<i64>) {
+        let mut num_met = 0;
+        let mut next_tokens = vec![];
+        for word_ids in force_words_ids {
+            if generated_ids
+                .windows(word_ids.len())
+                .any(|window| window == word_ids.as_slice())
+            {
+                num_met += word_ids.len();
+                continue;
+            }
+            let prefix_length = (1..word_ids.len().min(generated_ids.len() + 1))
+                .rev()
+                .find(|&length| generated_ids.ends_with(&word_ids[..length]))
+                .unwrap_or(0);
+            num_met += prefix_length;
+            if !next_tokens.contains(&word_ids[prefix_length]) {
+                next_tokens.push(word_ids[prefix_length]);
+            }
+        }
+        (num_met, next_tokens)
+    }
+
+    #[derive(Debug)]
+    pub struct BeamHypotheses {
+        max_length: i64,
+        length_penalty: f64,
+        early_stopping: bool,
+        num_beams: i64,
+        beams: Vec<(f64, Tensor)>,
+        worst_score: f64,
+    }
+
+    impl Clone for BeamHypotheses {
+        fn clone(&self) -> Self {
+            BeamHypotheses {
+                max_length: self.max_length,
+                length_penalty: self.length_penalty,
+                early_stopping: self.early_stopping,
+                num_beams: self.num_beams,
+                beams: self
+                    .beams
+                    .iter()
+                    .map(|(score, tensor)| (*score, tensor.copy()))
+                    .collect_vec(),
+                worst_score: self.worst_score,
+            }
+        }
+    }
+
+    impl BeamHypotheses {
+        fn new(
+            num_beams: i64,
+            max_length: i64,
+            length_penalty: f64,
+            early_stopping: bool,
+        ) -> BeamHypotheses {
+            BeamHypotheses {
+                max_length: max_length - 1,
+                length_penalty,
+                early_stopping,
+                num_beams,
+                beams: Vec::with_capacity(num_beams as usize + 1),
+                worst_score: 1e9f64,
+            }
+        }
+
+        fn len(&self) -> i64 {
+            self.beams.len() as i64
+        }
+
+        fn add(&mut self, hypothesis: Tensor, sum_log_probabilities: f64) {
+            let score =
+                sum_log_probabilities / ((hypothesis.size()[0] as f64).powf(self.length_penalty));
+            if (self.len() < self.num_beams) | (score > self.worst_score) {
+                self.beams.push((score, hypothesis));
+                if self.len() > self.num_beams {
+                    let (worst_score_position, _) = self
+                        .beams
+                        .iter()
+                        .enumerate()
+                        .min_by_key(|(_, (score, _))| OrderedFloat(*score))
+                        .unwrap();
+                    let _ = self.beams.remove(worst_score_position);
+                }
+                self.worst_score = self
+                    .beams
+                    .iter()
+                    .min_by_key(|(score, _)| OrderedFloat(*score))
+                    .unwrap()
+                    .0;
+            }
+        }
+
+        fn is_done(&self, best_sum_log_probabilities: f64, current_length: i64) -> bool {
+            if self.len() < self.num_beams {
+                false
+            } else if self.early_stopping {
+                true
+            } else {
+                self.worst_score
+                    >= best_sum_log_probabilities
+                        / (current_length as f64).powf(self.length_penalty)
+            }
+        }
     }
 
     pub trait PrivateLanguageGenerator<T: LMHeadModel, V: Vocab, U: Tokenizer<V>> {
         fn get_model(&self) -> &T;
         fn get_tokenizer(&self) -> &TokenizerOption;
         fn get_var_store(&self) -> &nn::VarStore;
+        fn get_var_store_mut(&mut self) -> Result<&mut nn::VarStore, RustBertError>;
         fn get_config(&self) -> &GenerateConfig;
         fn get_bos_id(&self) -> &Option<i64>;
         fn get_eos_ids(&self) -> &Option<Vec<i64>>;
@@ -1984,6 +3468,54 @@ pub(crate) mod private_generation_utils {
             }
         }
 
+        fn enforce_dry_penalty(
+            &self,
+            next_token_logits: &mut Tensor,
+            prev_output_tokens: &Tensor,
+            gen_opt: &ResolvedGenerateOptions,
+        ) {
+            //        Penalizes the tokens that would extend a repetition of an earlier part of the sequence, by
+            //        `multiplier * base ^ (match_length - allowed_length)` (https://github.com/oobabooga/text-generation-webui/pull/5677)
+            let prev_output_tokens = prev_output_tokens.to(Device::Cpu);
+            let sequence_breakers = gen_opt.dry_sequence_breakers.as_deref().unwrap_or(&[]);
+            for hypothesis_index in 0..prev_output_tokens.size()[0] {
+                let sequence = prev_output_tokens
+                    .get(hypothesis_index)
+                    .iter::<i64>()
+                    .unwrap()
+                    .collect::<Vec<i64>>();
+                let length = sequence.len();
+                let mut match_lengths: HashMap<i64, usize> = HashMap::new();
+                for position in 1..length {
+                    let next_token = sequence[position];
+                    if sequence_breakers.contains(&next_token) {
+                        continue;
+                    }
+                    let mut match_length = 0;
+                    while (match_length < position)
+                        && (sequence[position - 1 - match_length]
+                            == sequence[length - 1 - match_length])
+                        && !sequence_breakers.contains(&sequence[length - 1 - match_length])
+                    {
+                        match_length += 1;
+                    }
+                    let longest_match = match_lengths.entry(next_token).or_insert(0);
+                    *longest_match = max(*longest_match, match_length);
+                }
+                for (token, match_length) in match_lengths {
+                    if match_length as i64 >= gen_opt.dry_allowed_length {
+                        let penalty = gen_opt.dry_multiplier
+                            * gen_opt
+                                .dry_base
+                                .powi((match_length as i64 - gen_opt.dry_allowed_length) as i32);
+                        let mut token_logit =
+                            next_token_logits.get(hypothesis_index).narrow(0, token, 1);
+                        token_logit -= penalty;
+                    }
+                }
+            }
+        }
+
         fn get_banned_tokens(
             &self,
             input_ids: &Tensor,
@@ -2085,27 +3617,498 @@ pub(crate) mod private_generation_utils {
             }
         }
 
-        fn generate_no_beam_search(
+        fn typical_epsilon_eta_filtering(
             &self,
-            input_ids: Tensor,
-            encoder_outputs: Option<Tensor>,
+            logits: &mut Tensor,
+            typical_p: f64,
+            epsilon_cutoff: f64,
+            eta_cutoff: f64,
+            min_tokens_to_keep: i64,
+        ) {
+            //        Typical decoding (Meister et al., https://arxiv.org/abs/2202.00666), epsilon and eta sampling
+            //        (Hewitt et al., https://arxiv.org/abs/2210.15191)
+            let vocab_size = *logits.size().last().unwrap();
+            let min_tokens_to_keep = min(max(min_tokens_to_keep, 1), vocab_size);
+            let entropy = |logits: &Tensor| {
+                let log_probabilities = logits.log_softmax(-1, Float);
+                let probabilities = log_probabilities.exp();
+                -(&log_probabilities * &probabilities)
+                    .masked_fill(&probabilities.eq(0.0), 0.0)
+                    .sum1(&[-1], true, Float)
+            };
+            if typical_p < 1f64 {
+                let log_probabilities = logits.log_softmax(-1, Float);
+                let shifted_scores = (-&log_probabilities - entropy(logits)).abs();
+                let (sorted_scores, sorted_indices) = shifted_scores.sort(-1, false);
+                let cumulative_probabilities = logits
+                    .gather(-1, &sorted_indices, false)
+                    .softmax(-1, Float)
+                    .cumsum(-1, Float);
+                let last_index = cumulative_probabilities
+                    .lt(typical_p)
+                    .sum1(&[-1], true, Int64)
+                    .clamp_max(vocab_size - 1);
+                let sorted_indices_to_remove = sorted_scores
+                    .gt1(&sorted_scores.gather(-1, &last_index, false))
+                    .to_kind(Int64);
+                let _ = sorted_indices_to_remove
+                    .narrow(-1, 0, min_tokens_to_keep)
+                    .fill_(0);
+                let indices_to_remove = sorted_indices_to_remove
+                    .scatter(-1, &sorted_indices, &sorted_indices_to_remove)
+                    .to_kind(Bool);
+                let _ = logits.masked_fill_(&indices_to_remove, std::f64::NEG_INFINITY);
+            }
+            if (epsilon_cutoff > 0f64) | (eta_cutoff > 0f64) {
+                let probabilities = logits.softmax(-1, Float);
+                let mut indices_below_cutoff = probabilities.lt(epsilon_cutoff);
+                if eta_cutoff > 0f64 {
+                    let eta = ((-entropy(logits)).exp() * eta_cutoff.sqrt()).clamp_max(eta_cutoff);
+                    indices_below_cutoff =
+                        indices_below_cutoff.logical_or(&probabilities.lt1(&eta));
+                }
+                //        The most likely tokens are kept even if their probability is below the cutoff
+                let min_kept_logits = logits.topk(min_tokens_to_keep, -1, true, true).0.narrow(
+                    -1,
+                    min_tokens_to_keep - 1,
+                    1,
+                );
+                let indices_to_remove =
+                    indices_below_cutoff.logical_and(&logits.lt1(&min_kept_logits));
+                let _ = logits.masked_fill_(&indices_to_remove, std::f64::NEG_INFINITY);
+            }
+        }
+
+        fn get_source_ngrams(
+            &self,
+            source_ids: &Tensor,
+            attention_mask: &Tensor,
+            ngram_size: i64,
+        ) -> Vec<HashMap<Vec<i64>, Vec<i64>>> {
+            let source_ids = source_ids.to(Device::Cpu);
+            let attention_mask = attention_mask.to(Device::Cpu);
+            (0..source_ids.size()[0])
+                .map(|batch_index| {
+                    let sequence_ids = source_ids
+                        .get(batch_index)
+                        .masked_select(&attention_mask.get(batch_index).ne(0))
+                        .iter::<i64>()
+                        .unwrap()
+                        .collect::<Vec<i64>>();
+                    let mut source_ngrams: HashMap<Vec<i64>, Vec<i64>> = HashMap::new();
+                    for ngram in sequence_ids.windows(ngram_size as usize) {
+                        let (last, prefix) = ngram.split_last().unwrap();
+                        source_ngrams
+                            .entry(prefix.to_vec())
+                            .or_insert_with(Vec::new)
+                            .push(*last);
+                    }
+                    source_ngrams
+                })
+                .collect()
+        }
+
+        fn ban_source_ngrams(
+            &self,
+            scores: &mut Tensor,
+            input_ids: &Tensor,
+            gen_opt: &ResolvedGenerateOptions,
+        ) {
+            if let Some(encoder_ngrams) = &gen_opt.encoder_ngrams {
+                let prefix_length = gen_opt.encoder_no_repeat_ngram_size as usize - 1;
+                let rows_per_prompt = gen_opt.rows_per_prompt();
+                let input_ids = input_ids.to(Device::Cpu);
+                for row_index in 0..input_ids.size()[0] {
+                    //            The decoder start token is not part of the generated n-grams
+                    let generated_ids = input_ids
+                        .get(row_index)
+                        .iter::<i64>()
+                        .unwrap()
+                        .skip(1)
+                        .collect::<Vec<i64>>();
+                    if generated_ids.len() < prefix_length {
+                        continue;
+                    }
+                    let query = &generated_ids[generated_ids.len() - prefix_length..];
+                    if let Some(banned_tokens) =
+                        encoder_ngrams[(row_index / rows_per_prompt) as usize].get(query)
+                    {
+                        let _ = scores.get(row_index).index_fill_(
+                            0,
+                            &Tensor::of_slice(banned_tokens).to_device(scores.device()),
+                            std::f64::NEG_INFINITY,
+                        );
+                    }
+                }
+            }
+        }
+
+        fn ban_bad_words(
+            &self,
+            scores: &mut Tensor,
+            input_ids: &Tensor,
+            gen_opt: &ResolvedGenerateOptions,
+        ) {
+            if let Some(bad_words_ids) = &gen_opt.bad_words_ids {
+                let input_ids = input_ids.to(Device::Cpu);
+                for row_index in 0..input_ids.size()[0] {
+                    let sequence = Vec::<i64>::from(input_ids.get(row_index));
+                    //            Single tokens are always banned, longer sequences once their prefix is generated
+                    let banned_tokens = bad_words_ids
+                        .iter()
+                        .filter(|word_ids| sequence.ends_with(&word_ids[..word_ids.len() - 1]))
+                        .map(|word_ids| word_ids[word_ids.len() - 1])
+                        .collect::<Vec<i64>>();
+                    if !banned_tokens.is_empty() {
+                        let _ = scores.get(row_index).index_fill_(
+                            0,
+                            &Tensor::of_slice(&banned_tokens).to_device(scores.device()),
+                            std::f64::NEG_INFINITY,
+                        );
+                    }
+                }
+            }
+        }
+
+        fn heal_prompt_ids(
+            &self,
+            input_ids: &Tensor,
+            attention_mask: Option<Tensor>,
+            pad_token_id: Option<i64>,
+        ) -> (Tensor, Tensor, Vec<Vec<i64>>) {
+            //        Removes the last token of each (right-padded) prompt and collects the tokens of the vocabulary
+            //        starting with it, allowed for the first generation step
+            let device = input_ids.device();
+            let pad_token_id = pad_token_id.unwrap_or(0);
+            let attention_mask = match attention_mask {
+                Some(value) => value,
+                None => input_ids.ne(pad_token_id).to_kind(Int64),
+            };
+            let input_ids = input_ids.to(Device::Cpu);
+            let attention_mask = attention_mask.to(Device::Cpu);
+            let mut healed_ids = Vec::with_capacity(input_ids.size()[0] as usize);
+            let mut healed_mask = Vec::with_capacity(input_ids.size()[0] as usize);
+            let mut token_healing_ids = Vec::with_capacity(input_ids.size()[0] as usize);
+            for prompt_index in 0..input_ids.size()[0] {
+                let mut prompt_ids = input_ids
+                    .get(prompt_index)
+                    .iter::<i64>()
+                    .unwrap()
+                    .collect::<Vec<i64>>();
+                let mut prompt_mask = attention_mask
+                    .get(prompt_index)
+                    .iter::<i64>()
+                    .unwrap()
+                    .collect::<Vec<i64>>();
+                match prompt_mask.iter().rposition(|&value| value != 0) {
+                    //            The first token of the prompt is kept to condition the generation
+                    Some(last_position) if last_position > 0 => {
+                        token_healing_ids.push(
+                            self.get_tokenizer()
+                                .get_prefixed_token_ids(prompt_ids[last_position]),
+                        );
+                        prompt_ids[last_position] = pad_token_id;
+                        prompt_mask[last_position] = 0;
+                    }
+                    _ => token_healing_ids.push(vec![]),
+                }
+                healed_ids.push(prompt_ids);
+                healed_mask.push(prompt_mask);
+            }
+            if healed_mask.iter().all(|mask| mask.last() == Some(&0)) {
+                for (prompt_ids, prompt_mask) in healed_ids.iter_mut().zip(healed_mask.iter_mut()) {
+                    prompt_ids.pop();
+                    prompt_mask.pop();
+                }
+            }
+            let to_tensor = |values: Vec<Vec<i64>>| {
+                Tensor::stack(
+                    &values
+                        .iter()
+                        .map(|row| Tensor::of_slice(row))
+                        .collect::<Vec<Tensor>>(),
+                    0,
+                )
+                .to(device)
+            };
+            (
+                to_tensor(healed_ids),
+                to_tensor(healed_mask),
+                token_healing_ids,
+            )
+        }
+
+        fn restrict_healed_tokens(&self, scores: &mut Tensor, gen_opt: &ResolvedGenerateOptions) {
+            if let Some(token_healing_ids) = &gen_opt.token_healing_ids {
+                let rows_per_prompt = gen_opt.rows_per_prompt();
+                for row_index in 0..scores.size()[0] {
+                    let allowed_tokens = &token_healing_ids[(row_index / rows_per_prompt) as usize];
+                    if allowed_tokens.is_empty() {
+                        continue;
+                    }
+                    let mut row_mask = scores.get(row_index).full_like(std::f64::NEG_INFINITY);
+                    let _ = row_mask.index_fill_(
+                        0,
+                        &Tensor::of_slice(allowed_tokens).to_device(scores.device()),
+                        0.0,
+                    );
+                    let mut row_scores = scores.get(row_index);
+                    row_scores += row_mask;
+                }
+            }
+        }
+
+        fn apply_source_copy_bias(&self, scores: &mut Tensor, gen_opt: &ResolvedGenerateOptions) {
+            if let Some(source_ids) = &gen_opt.source_ids {
+                let mut bias = scores.zeros_like();
+                let _ = bias.scatter_(
+                    1,
+                    source_ids,
+                    &source_ids
+                        .full_like(gen_opt.source_copy_bias)
+                        .to_kind(scores.kind()),
+                );
+                if let Some(pad_token_id) = gen_opt.pad_token_id {
+                    let _ = bias.index_fill_(
+                        1,
+                        &Tensor::of_slice(&[pad_token_id]).to_device(scores.device()),
+                        0.0,
+                    );
+                }
+                *scores += bias;
+            }
+        }
+
+        fn restrict_allowed_tokens(
+            &self,
+            scores: &mut Tensor,
+            input_ids: &Tensor,
+            gen_opt: &ResolvedGenerateOptions,
+        ) {
+            if gen_opt.allowed_token_ids.is_none() & gen_opt.prefix_allowed_tokens_fn.is_none() {
+                return;
+            }
+            let rows_per_prompt = gen_opt.rows_per_prompt();
+            let mask = scores.full_like(std::f64::NEG_INFINITY);
+            for row_index in 0..scores.size()[0] {
+                let mut allowed_tokens = match &gen_opt.prefix_allowed_tokens_fn {
+                    Some(prefix_allowed_tokens_fn) => {
+                        let generated_ids = Vec::<i64>::from(input_ids.get(row_index));
+                        prefix_allowed_tokens_fn(row_index / rows_per_prompt, &generated_ids)
+                    }
+                    None => vec![],
+                };
+                if let Some(allowed_token_ids) = &gen_opt.allowed_token_ids {
+                    allowed_tokens = if allowed_tokens.is_empty() {
+                        allowed_token_ids.clone()
+                    } else {
+                        allowed_tokens
+                            .into_iter()
+                            .filter(|token_id| allowed_token_ids.contains(token_id))
+                            .collect()
+                    };
+                }
+                let mut row_mask = mask.get(row_index);
+                if allowed_tokens.is_empty() {
+                    let _ = row_mask.fill_(0.0);
+                } else {
+                    let _ = row_mask.index_fill_(
+                        0,
+                        &Tensor::of_slice(&allowed_tokens).to_device(scores.device()),
+                        0.0,
+                    );
+                }
+            }
+            *scores += mask;
+        }
+
+        /// Returns the text of each token of the vocabulary (indexed by token id), as decoded when following other
+        /// tokens. Special tokens are decoded as empty strings.
+        fn get_grammar_token_texts(&self) -> Vec<String> {
+            let tokenizer = self.get_tokenizer();
+            let vocab = tokenizer.get_vocab_indices();
+            let vocab_size = vocab.keys().max().map_or(0, |token_id| token_id + 1);
+            let mut token_texts = vec![String::new(); vocab_size as usize];
+            for (token_id, token) in vocab {
+                let mut text = tokenizer.decode(vec![token_id], true, false);
+                // SentencePiece tokenizers strip the leading space of the decoded text
+                if token.starts_with('\u{2581}') & !text.is_empty() & !text.starts_with(' ') {
+                    text.insert(0, ' ');
+                }
+                token_texts[token_id as usize] = text;
+            }
+            token_texts
+        }
+
+        fn restrict_grammar_tokens(
+            &self,
+            scores: &mut Tensor,
+            input_ids: &Tensor,
+            cur_len: i64,
+            gen_opt: &ResolvedGenerateOptions,
+        ) {
+            let (grammar, token_texts) = match (&gen_opt.grammar, &gen_opt.grammar_token_texts) {
+                (Some(grammar), Some(token_texts)) => (grammar, token_texts),
+                _ => return,
+            };
+            let eos_token_ids = gen_opt.eos_token_ids.clone().unwrap_or_default();
+            let vocab_size = scores.size()[1] as usize;
+            let sequence_length = *input_ids.size().last().unwrap();
+            let mask = scores.full_like(std::f64::NEG_INFINITY);
+            for row_index in 0..scores.size()[0] {
+                let generated_ids = Vec::<i64>::from(input_ids.get(row_index).slice(
+                    0,
+                    cur_len,
+                    sequence_length,
+                    1,
+                ));
+                let mut row_mask = mask.get(row_index);
+                //            Finished sequences are padded and left unconstrained
+                if generated_ids
+                    .iter()
+                    .any(|token_id| eos_token_ids.contains(token_id))
+                {
+                    let _ = row_mask.fill_(0.0);
+                    continue;
+                }
+                let mut chart = grammar.chart();
+                let mut allowed_tokens = vec![];
+                if generated_ids.iter().all(|&token_id| {
+                    chart.push_str(
+                        token_texts
+                            .get(token_id as usize)
+                            .map_or("", String::as_str),
+                    )
+                }) {
+                    let mut valid_first_chars: HashMap<char, bool> = HashMap::new();
+                    for (token_id, text) in token_texts.iter().enumerate().take(vocab_size) {
+                        let first_char = match text.chars().next() {
+                            Some(first_char) => first_char,
+                            None => continue,
+                        };
+                        if *valid_first_chars
+                            .entry(first_char)
+                            .or_insert_with(|| chart.accepts_next(first_char))
+                            && chart.accepts_str(text)
+                        {
+                            allowed_tokens.push(token_id as i64);
+                        }
+                    }
+                    //            The sequence may only end once the grammar is complete, or if it cannot be continued
+                    if chart.is_complete() | allowed_tokens.is_empty() {
+                        allowed_tokens.extend(&eos_token_ids);
+                    }
+                }
+                if allowed_tokens.is_empty() {
+                    let _ = row_mask.fill_(0.0);
+                } else {
+                    let _ = row_mask.index_fill_(
+                        0,
+                        &Tensor::of_slice(&allowed_tokens).to_device(scores.device()),
+                        0.0,
+                    );
+                }
+            }
+            *scores += mask;
+        }
+
+        /// Computes the logits of the next token without cache and panics if they differ from the logits computed
+        /// with the cache by more than the tolerance
+        fn check_cache_consistency(
+            &self,
+            cached_logits: &Tensor,
+            input_ids: &Tensor,
+            encoder_outputs: Option<&Tensor>,
+            attention_mask: &Tensor,
+            step: i64,
+            tolerance: f64,
+        ) {
+            let (
+                prepared_input,
+                prepared_attention_mask,
+                prepared_encoder_output,
+                prepared_decoder_input,
+                prepared_past,
+            ) = self.prepare_inputs_for_generation(
+                input_ids.copy(),
+                encoder_outputs,
+                Cache::None,
+                attention_mask.copy(),
+            );
+            let reference_logits = self
+                .get_model()
+                .forward_t(
+                    &prepared_input,
+                    prepared_past,
+                    &prepared_attention_mask,
+                    &None,
+                    &None,
+                    &None,
+                    prepared_encoder_output,
+                    &prepared_decoder_input,
+                    false,
+                )
+                .unwrap()
+                .lm_logits
+                .select(1, -1);
+            let difference = (cached_logits - reference_logits)
+                .abs()
+                .max()
+                .double_value(&[]);
+            assert!(
+                difference <= tolerance,
+                "Logits computed with and without cache differ by {} (tolerance: {}) at generation step {}",
+                difference,
+                tolerance,
+                step
+            );
+        }
+
+        // Number of generated tokens to keep if the sequence (prompt and generated tokens) meets the stopping criteria
+        fn stop_position(
+            &self,
+            stopping_criteria: &Option<Arc<dyn StoppingCriteria>>,
+            sequence: &[i64],
+            cur_len: i64,
+        ) -> Option<usize> {
+            let stopping_criteria = stopping_criteria.as_ref()?;
+            let generated_ids = sequence.get(cur_len as usize..)?;
+            let tokenizer = self.get_tokenizer();
+            let decode = |token_ids: &[i64]| tokenizer.decode(token_ids.to_vec(), true, false);
+            stopping_criteria.stop_position(generated_ids, &decode)
+        }
+
+        // Log-probabilities under the model of the tokens generated after `cur_len`, computed by teacher forcing with
+        // the same cached decoding as the generation. Returns a tensor of shape (num_sequences, sequence_length - cur_len)
+        fn score_generated_tokens(
+            &self,
+            sequences: &Tensor,
             cur_len: i64,
             batch_size: i64,
-            attention_mask: Tensor,
-            gen_opt: GenerateOptions,
+            encoder_output: Option<&(Tensor, Tensor)>,
+            prompt_attention_mask: Option<&Tensor>,
         ) -> Tensor {
-            let mut unfinished_sentences =
-                Tensor::ones(&[batch_size], (Int64, self.get_var_store().device()));
-            let mut sentence_lengths: Tensor =
-                Tensor::ones(&[batch_size], (Int64, self.get_var_store().device()))
-                    * gen_opt.max_length as i64;
-            let mut attention_mask = attention_mask.copy();
-            let mut input_ids = input_ids.copy();
+            let num_sequences = sequences.size()[0];
+            let sequence_length = sequences.size()[1];
+            let batch_indices = Tensor::arange(batch_size, (Int64, sequences.device()))
+                .view((-1, 1))
+                .repeat(&[1, num_sequences / batch_size])
+                .view(-1);
+            let (encoder_outputs, mut attention_mask) = match encoder_output {
+                Some((encoder_hidden_states, encoder_attention_mask)) => (
+                    Some(encoder_hidden_states.index_select(0, &batch_indices)),
+                    encoder_attention_mask.index_select(0, &batch_indices),
+                ),
+                None => (
+                    None,
+                    prompt_attention_mask
+                        .unwrap()
+                        .index_select(0, &batch_indices),
+                ),
+            };
             let mut past: Cache = Cache::None;
-            let mut outputs: Tensor;
-            let mut current_length = cur_len;
-
-            while current_length < gen_opt.max_length {
+            let mut token_scores = Vec::with_capacity((sequence_length - cur_len).max(0) as usize);
+            for position in cur_len..sequence_length {
                 let (
                     prepared_input,
                     prepared_attention_mask,
@@ -2113,12 +4116,12 @@ pub(crate) mod private_generation_utils {
                     prepared_decoder_input,
                     prepared_past,
                 ) = self.prepare_inputs_for_generation(
-                    input_ids.copy(),
+                    sequences.narrow(1, 0, position),
                     encoder_outputs.as_ref(),
                     past,
                     attention_mask.copy(),
                 );
-                let temp = self
+                let output = self
                     .get_model()
                     .forward_t(
                         &prepared_input,
@@ -2132,23 +4135,265 @@ pub(crate) mod private_generation_utils {
                         false,
                     )
                     .unwrap();
-                outputs = temp.lm_logits;
-                past = temp.cache;
-
-                let mut next_token_logits = outputs.select(1, -1);
-                //            Reduce probability for repeated inputs
-                if gen_opt.repetition_penalty > 1f64 {
-                    self.enforce_repetition_penalty(
-                        &mut next_token_logits,
-                        batch_size,
-                        1,
-                        &input_ids,
-                        gen_opt.repetition_penalty,
-                    )
+                past = output.cache;
+                let log_probabilities = output.lm_logits.select(1, -1).log_softmax(-1, Float);
+                token_scores.push(log_probabilities.gather(
+                    1,
+                    &sequences.narrow(1, position, 1),
+                    false,
+                ));
+                if !self.is_encoder_decoder() {
+                    attention_mask = Tensor::cat(
+                        &[
+                            attention_mask.as_ref(),
+                            Tensor::ones(&[num_sequences, 1], (Int64, attention_mask.device()))
+                                .as_ref(),
+                        ],
+                        -1,
+                    );
                 }
-                //            Get banned tokens and set their probability to 0
-                if gen_opt.no_repeat_ngram_size > 0 {
-                    let banned_tokens = self.get_banned_tokens(
+            }
+            if token_scores.is_empty() {
+                Tensor::zeros(&[num_sequences, 0], (Float, sequences.device()))
+            } else {
+                Tensor::cat(&token_scores, 1)
+                    .to(Device::Cpu)
+                    .to_kind(Double)
+            }
+        }
+
+        // Beam search step with forced token sequences (dynamic beam allocation, Post & Vilar 2018): the top candidates
+        // are extended with the next token of the incomplete constraints of each beam, and the beams are selected
+        // alternately from banks of candidates having met the same number of constraint tokens, starting from the
+        // most advanced bank. Finished candidates are only kept if they meet all the constraints.
+        // Returns the scores, tokens and beam indices of the selected candidates, of shape (batch_size, num_beams)
+        fn constrained_beam_step(
+            &self,
+            scores: &Tensor,
+            beam_scores: &Tensor,
+            input_ids: &Tensor,
+            cur_len: i64,
+            current_length: i64,
+            hypotheses: &mut [BeamHypotheses],
+            done: &mut [bool],
+            gen_opt: &ResolvedGenerateOptions,
+        ) -> (Tensor, Tensor, Tensor) {
+            let force_words_ids = gen_opt.force_words_ids.as_ref().unwrap();
+            let num_constraint_tokens = force_words_ids.iter().map(Vec::len).sum::<usize>();
+            let (batch_size, num_beams, vocab_size) = scores.size3().unwrap();
+            let pad_token_id = gen_opt.pad_token_id.unwrap_or(0);
+            let eos_token_ids = gen_opt.eos_token_ids.clone().unwrap_or_default();
+
+            let next_scores = (scores + beam_scores.unsqueeze(-1))
+                .contiguous()
+                .view((batch_size, num_beams * vocab_size))
+                .to(Device::Cpu)
+                .to_kind(Double);
+            let (top_scores, top_indices) = next_scores.topk(2 * num_beams, 1, true, true);
+            let beam_scores =
+                Vec::<f64>::from(beam_scores.to(Device::Cpu).to_kind(Double).view(-1));
+
+            let mut output_scores = Vec::with_capacity((batch_size * num_beams) as usize);
+            let mut output_tokens = Vec::with_capacity((batch_size * num_beams) as usize);
+            let mut output_indices = Vec::with_capacity((batch_size * num_beams) as usize);
+            for batch_index in 0..batch_size {
+                if done[batch_index as usize] {
+                    output_scores.extend(vec![0f64; num_beams as usize]);
+                    output_tokens.extend(vec![pad_token_id; num_beams as usize]);
+                    output_indices.extend(vec![0i64; num_beams as usize]);
+                    continue;
+                }
+                let sequences = (0..num_beams)
+                    .map(|beam_index| {
+                        input_ids
+                            .get(batch_index * num_beams + beam_index)
+                            .iter::<i64>()
+                            .unwrap()
+                            .collect::<Vec<i64>>()
+                    })
+                    .collect::<Vec<Vec<i64>>>();
+
+                //            (score, beam index, token) of the candidates
+                let mut candidates = (0..2 * num_beams)
+                    .map(|rank| {
+                        let index = top_indices.int64_value(&[batch_index, rank]);
+                        (
+                            top_scores.double_value(&[batch_index, rank]),
+                            index / vocab_size,
+                            index % vocab_size,
+                        )
+                    })
+                    .collect::<Vec<(f64, i64, i64)>>();
+                for (beam_index, sequence) in sequences.iter().enumerate() {
+                    //            The beams initialized with a -1e9 score duplicate the first beam
+                    if beam_scores[(batch_index * num_beams) as usize + beam_index] < -1e8 {
+                        continue;
+                    }
+                    let (_, next_tokens) =
+                        constraint_progress(&sequence[cur_len as usize..], force_words_ids);
+                    for token in next_tokens {
+                        let beam_index = beam_index as i64;
+                        if !candidates.iter().any(|&(_, beam, candidate)| {
+                            (beam == beam_index) & (candidate == token)
+                        }) {
+                            candidates.push((
+                                next_scores
+                                    .double_value(&[batch_index, beam_index * vocab_size + token]),
+                                beam_index,
+                                token,
+                            ));
+                        }
+                    }
+                }
+                candidates.retain(|(score, _, _)| score.is_finite());
+                candidates.sort_by(|a, b| b.0.partial_cmp(&a.0).unwrap());
+                let best_score = candidates.first().map_or(-1e9, |candidate| candidate.0);
+
+                let hypotheses_index = batch_index as usize;
+                let mut banks = vec![vec![]; num_constraint_tokens + 1];
+                for (rank, &(score, beam_index, token)) in candidates.iter().enumerate() {
+                    let mut sequence = sequences[beam_index as usize].clone();
+                    sequence.push(token);
+                    let (num_met, _) =
+                        constraint_progress(&sequence[cur_len as usize..], force_words_ids);
+                    let is_eos = eos_token_ids.contains(&token);
+                    let is_stopped = !is_eos
+                        & self
+                            .stop_position(&gen_opt.stopping_criteria, &sequence, cur_len)
+                            .is_some();
+                    if is_eos | is_stopped {
+                        if (num_met == num_constraint_tokens) & ((rank as i64) < num_beams) {
+                            if is_eos {
+                                sequence.pop();
+                            }
+                            hypotheses[hypotheses_index]
+                                .add(Tensor::of_slice(&sequence).to(input_ids.device()), score);
+                        }
+                        continue;
+                    }
+                    banks[num_met].push((score, beam_index, token));
+                }
+
+                let mut selected = Vec::with_capacity(num_beams as usize);
+                let mut bank_positions = vec![0; banks.len()];
+                while (selected.len() < num_beams as usize)
+                    & banks
+                        .iter()
+                        .zip(bank_positions.iter())
+                        .any(|(bank, &position)| position < bank.len())
+                {
+                    for (bank, position) in banks.iter().zip(bank_positions.iter_mut()).rev() {
+                        if (selected.len() < num_beams as usize) & (*position < bank.len()) {
+                            selected.push(bank[*position]);
+                            *position += 1;
+                        }
+                    }
+                }
+                selected.resize(num_beams as usize, (-1e9, 0, pad_token_id));
+                for (score, beam_index, token) in selected {
+                    output_scores.push(score);
+                    output_tokens.push(token);
+                    output_indices.push(batch_index * num_beams + beam_index);
+                }
+                done[hypotheses_index] |=
+                    hypotheses[hypotheses_index].is_done(best_score, current_length);
+            }
+            let device = input_ids.device();
+            (
+                Tensor::of_slice(&output_scores)
+                    .to_kind(Float)
+                    .view((batch_size, num_beams))
+                    .to(device),
+                Tensor::of_slice(&output_tokens)
+                    .view((batch_size, num_beams))
+                    .to(device),
+                Tensor::of_slice(&output_indices)
+                    .view((batch_size, num_beams))
+                    .to(device),
+            )
+        }
+
+        fn generate_no_beam_search(
+            &self,
+            input_ids: Tensor,
+            encoder_outputs: Option<Tensor>,
+            cur_len: i64,
+            batch_size: i64,
+            attention_mask: Tensor,
+            gen_opt: ResolvedGenerateOptions,
+            mut token_callback: Option<&mut dyn FnMut(usize, i64)>,
+        ) -> Tensor {
+            let mut unfinished_sentences =
+                Tensor::ones(&[batch_size], (Int64, self.get_var_store().device()));
+            let mut sentence_lengths: Tensor =
+                Tensor::ones(&[batch_size], (Int64, self.get_var_store().device()))
+                    * gen_opt.max_length as i64;
+            let mut attention_mask = attention_mask.copy();
+            let mut input_ids = input_ids.copy();
+            let mut past: Cache = Cache::None;
+            let mut outputs: Tensor;
+            let mut current_length = cur_len;
+            let mut sampler = gen_opt.seed.map(SeededSampler::new);
+
+            while current_length < gen_opt.max_length {
+                let (
+                    prepared_input,
+                    prepared_attention_mask,
+                    prepared_encoder_output,
+                    prepared_decoder_input,
+                    prepared_past,
+                ) = self.prepare_inputs_for_generation(
+                    input_ids.copy(),
+                    encoder_outputs.as_ref(),
+                    past,
+                    attention_mask.copy(),
+                );
+                let temp = {
+                    let _scope = profiling::scope(|| "decode_step".to_string(), &[&input_ids]);
+                    self.get_model()
+                        .forward_t(
+                            &prepared_input,
+                            prepared_past,
+                            &prepared_attention_mask,
+                            &None,
+                            &None,
+                            &None,
+                            prepared_encoder_output,
+                            &prepared_decoder_input,
+                            false,
+                        )
+                        .unwrap()
+                };
+                outputs = temp.lm_logits;
+                past = temp.cache;
+                if let Some(tolerance) = gen_opt.cache_check_tolerance {
+                    self.check_cache_consistency(
+                        &outputs.select(1, -1),
+                        &input_ids,
+                        encoder_outputs.as_ref(),
+                        &attention_mask,
+                        current_length - cur_len,
+                        tolerance,
+                    );
+                }
+
+                let mut next_token_logits = outputs.select(1, -1);
+                //            Reduce probability for repeated inputs
+                if gen_opt.repetition_penalty > 1f64 {
+                    self.enforce_repetition_penalty(
+                        &mut next_token_logits,
+                        batch_size,
+                        1,
+                        &input_ids,
+                        gen_opt.repetition_penalty,
+                    )
+                }
+                if gen_opt.dry_multiplier > 0f64 {
+                    self.enforce_dry_penalty(&mut next_token_logits, &input_ids, &gen_opt);
+                }
+                //            Get banned tokens and set their probability to 0
+                if gen_opt.no_repeat_ngram_size > 0 {
+                    let banned_tokens = self.get_banned_tokens(
                         &input_ids,
                         gen_opt.no_repeat_ngram_size as i64,
                         current_length as i64,
@@ -2165,6 +4410,15 @@ pub(crate) mod private_generation_utils {
                     }
                 }
 
+                if current_length == cur_len {
+                    self.restrict_healed_tokens(&mut next_token_logits, &gen_opt);
+                }
+                self.ban_source_ngrams(&mut next_token_logits, &input_ids, &gen_opt);
+                self.ban_bad_words(&mut next_token_logits, &input_ids, &gen_opt);
+                self.apply_source_copy_bias(&mut next_token_logits, &gen_opt);
+                self.restrict_allowed_tokens(&mut next_token_logits, &input_ids, &gen_opt);
+                self.restrict_grammar_tokens(&mut next_token_logits, &input_ids, cur_len, &gen_opt);
+
                 //            Do not allow eos token if min length is not reached
                 if (gen_opt.eos_token_ids.is_some()) & (current_length < gen_opt.min_length) {
                     let _ = next_token_logits.index_fill_(
@@ -2186,22 +4440,65 @@ pub(crate) mod private_generation_utils {
                         gen_opt.top_p,
                         1,
                     );
+                    self.typical_epsilon_eta_filtering(
+                        &mut next_token_logits,
+                        gen_opt.typical_p,
+                        gen_opt.epsilon_cutoff,
+                        gen_opt.eta_cutoff,
+                        1,
+                    );
                     let probabilities = next_token_logits.softmax(-1, Float);
-                    probabilities.multinomial(1, false).squeeze1(1)
+                    match sampler.as_mut() {
+                        Some(sampler) => sampler.multinomial(&probabilities, 1),
+                        None => probabilities.multinomial(1, false),
+                    }
+                    .squeeze1(1)
                 } else {
                     next_token_logits.argmax(-1, false)
                 };
 
                 //            Add tokens to unfinished sentences
-                let tokens_to_add = match &gen_opt.eos_token_ids {
-                    Some(_) => {
+                let tokens_to_add = match (&gen_opt.eos_token_ids, &gen_opt.stopping_criteria) {
+                    (None, None) => next_token,
+                    _ => {
                         next_token * &unfinished_sentences
                             - gen_opt.pad_token_id.unwrap() * (&unfinished_sentences - 1)
                     }
-                    None => next_token,
                 };
+                if let Some(token_callback) = token_callback.as_mut() {
+                    let tokens = Vec::<i64>::from(tokens_to_add.to(Device::Cpu));
+                    let unfinished = Vec::<i64>::from(unfinished_sentences.to(Device::Cpu));
+                    for (row_index, (token_id, unfinished)) in
+                        tokens.into_iter().zip(unfinished).enumerate()
+                    {
+                        if unfinished == 1 {
+                            token_callback(row_index, token_id);
+                        }
+                    }
+                }
 
                 input_ids = Tensor::cat(&[input_ids, tokens_to_add.unsqueeze(-1)], -1);
+                if gen_opt.stopping_criteria.is_some() {
+                    let unfinished = Vec::<i64>::from(unfinished_sentences.to(Device::Cpu));
+                    for (row_index, unfinished) in unfinished.into_iter().enumerate() {
+                        if unfinished == 1 {
+                            let sequence = input_ids
+                                .get(row_index as i64)
+                                .iter::<i64>()
+                                .unwrap()
+                                .collect::<Vec<i64>>();
+                            if self
+                                .stop_position(&gen_opt.stopping_criteria, &sequence, cur_len)
+                                .is_some()
+                            {
+                                let _ = unfinished_sentences.get(row_index as i64).fill_(0);
+                            }
+                        }
+                    }
+                    if i64::from(unfinished_sentences.max()) == 0 {
+                        break;
+                    }
+                }
                 if gen_opt.eos_token_ids.is_some() {
                     for eos_token_id in gen_opt.eos_token_ids.as_ref().unwrap() {
                         let sentence_with_eos = tokens_to_add.eq(*eos_token_id).to_kind(Int64);
@@ -2243,12 +4540,16 @@ pub(crate) mod private_generation_utils {
             cur_len: i64,
             batch_size: i64,
             mut attention_mask: Tensor,
-            gen_opt: GenerateOptions,
+            gen_opt: ResolvedGenerateOptions,
         ) -> Tensor {
-            let mut hypotheses = (0..batch_size)
+            // The beams of each prompt are split into `num_beam_groups` groups of `group_size` beams, each group
+            // keeping its own hypotheses (a single group for the standard beam search)
+            let num_groups = gen_opt.num_beam_groups;
+            let group_size = gen_opt.num_beams / num_groups;
+            let mut hypotheses = (0..batch_size * num_groups)
                 .map(|_| {
                     BeamHypotheses::new(
-                        gen_opt.num_beams,
+                        group_size,
                         gen_opt.max_length,
                         gen_opt.length_penalty,
                         gen_opt.early_stopping,
@@ -2257,25 +4558,29 @@ pub(crate) mod private_generation_utils {
                 .collect::<Vec<BeamHypotheses>>();
 
             let vocab_size = self.get_vocab_size();
-            let beam_scores = Tensor::zeros(
+            let mut beam_scores = Tensor::zeros(
                 &[batch_size, gen_opt.num_beams],
                 (Float, self.get_var_store().device()),
             );
             if !gen_opt.do_sample {
-                let _ = beam_scores
-                    .slice(1, 1, *beam_scores.size().last().unwrap(), 1)
-                    .fill_(-1e9);
+                let _ = beam_scores.fill_(-1e9);
+                for group_index in 0..num_groups {
+                    let _ = beam_scores
+                        .slice(1, group_index * group_size, group_index * group_size + 1, 1)
+                        .fill_(0f64);
+                }
             }
 
             let mut beam_scores = beam_scores.view_(&[-1]);
             let mut beam_tokens: Tensor;
             let mut beam_indices: Tensor;
             let mut past: Cache = Cache::None;
-            let mut done = vec![false; batch_size as usize];
+            let mut done = vec![false; (batch_size * num_groups) as usize];
 
             let mut outputs: Tensor;
             let mut encoder_outputs = encoder_outputs;
             let mut current_length = cur_len;
+            let mut sampler = gen_opt.seed.map(SeededSampler::new);
             while current_length < gen_opt.max_length {
                 let (
                     prepared_input,
@@ -2289,22 +4594,34 @@ pub(crate) mod private_generation_utils {
                     past,
                     attention_mask.copy(),
                 );
-                let temp = self
-                    .get_model()
-                    .forward_t(
-                        &prepared_input,
-                        prepared_past,
-                        &prepared_attention_mask,
-                        &None,
-                        &None,
-                        &None,
-                        prepared_encoder_output,
-                        &prepared_decoder_input,
-                        false,
-                    )
-                    .unwrap();
+                let temp = {
+                    let _scope = profiling::scope(|| "decode_step".to_string(), &[&input_ids]);
+                    self.get_model()
+                        .forward_t(
+                            &prepared_input,
+                            prepared_past,
+                            &prepared_attention_mask,
+                            &None,
+                            &None,
+                            &None,
+                            prepared_encoder_output,
+                            &prepared_decoder_input,
+                            false,
+                        )
+                        .unwrap()
+                };
                 outputs = temp.lm_logits;
                 past = temp.cache;
+                if let Some(tolerance) = gen_opt.cache_check_tolerance {
+                    self.check_cache_consistency(
+                        &outputs.select(1, -1),
+                        &input_ids,
+                        encoder_outputs.as_ref(),
+                        &attention_mask,
+                        current_length - cur_len,
+                        tolerance,
+                    );
+                }
                 let mut next_token_logits = outputs.select(1, -1);
                 //            Reduce probability for repeated inputs
                 if gen_opt.repetition_penalty > 1f64 {
@@ -2316,6 +4633,9 @@ pub(crate) mod private_generation_utils {
                         gen_opt.repetition_penalty,
                     )
                 }
+                if gen_opt.dry_multiplier > 0f64 {
+                    self.enforce_dry_penalty(&mut next_token_logits, &input_ids, &gen_opt);
+                }
 
                 if gen_opt.temperature > 1f64 {
                     next_token_logits /= gen_opt.temperature;
@@ -2355,90 +4675,229 @@ pub(crate) mod private_generation_utils {
                         );
                     }
                 }
-                let (next_scores, next_tokens) = if gen_opt.do_sample {
-                    let mut _scores: Tensor =
-                        &scores + &beam_scores.unsqueeze(-1).expand_as(&scores);
-                    self.top_k_top_p_filtering(&mut _scores, gen_opt.top_k, gen_opt.top_p, 2);
-                    let _scores = _scores
-                        .contiguous()
-                        .view((batch_size, gen_opt.num_beams * vocab_size));
-
-                    let probabilities = _scores.softmax(-1, Float);
-                    let next_tokens = probabilities.multinomial(2 * gen_opt.num_beams, false);
-                    let next_scores = _scores.gather(-1, &next_tokens, false);
-                    let (next_scores, next_scores_indices) = next_scores.sort(1, true);
-                    let next_tokens = next_tokens.gather(-1, &next_scores_indices, false);
-                    (next_scores, next_tokens)
-                } else {
-                    let next_scores: Tensor =
-                        &scores + &beam_scores.unsqueeze(-1).expand_as(&scores);
-                    let next_scores = next_scores
-                        .contiguous()
-                        .view((batch_size, gen_opt.num_beams * vocab_size));
-                    next_scores.topk(2 * gen_opt.num_beams, 1, true, true)
-                };
+                if current_length == cur_len {
+                    self.restrict_healed_tokens(&mut scores, &gen_opt);
+                }
+                self.ban_source_ngrams(&mut scores, &input_ids, &gen_opt);
+                self.ban_bad_words(&mut scores, &input_ids, &gen_opt);
+                self.apply_source_copy_bias(&mut scores, &gen_opt);
+                self.restrict_allowed_tokens(&mut scores, &input_ids, &gen_opt);
+                self.restrict_grammar_tokens(&mut scores, &input_ids, cur_len, &gen_opt);
+                let scores = scores
+                    .contiguous()
+                    .view((batch_size, num_groups, group_size, vocab_size));
+                let group_beam_scores = beam_scores.view((batch_size, num_groups, group_size));
+                let mut token_frequency =
+                    Tensor::zeros(&[batch_size, vocab_size], (Float, scores.device()));
+                let mut group_outputs = Vec::with_capacity(num_groups as usize);
+                for group_index in 0..num_groups {
+                    let mut scores = scores.select(1, group_index);
+                    //            Penalize the tokens already selected at this step by the previous groups
+                    if (group_index > 0) & (gen_opt.diversity_penalty > 0f64) {
+                        scores -= token_frequency.unsqueeze(1) * gen_opt.diversity_penalty;
+                    }
+                    let beam_scores = group_beam_scores.select(1, group_index);
+                    if gen_opt.force_words_ids.is_some() {
+                        group_outputs.push(self.constrained_beam_step(
+                            &scores,
+                            &beam_scores,
+                            &input_ids,
+                            cur_len,
+                            current_length,
+                            &mut hypotheses,
+                            &mut done,
+                            &gen_opt,
+                        ));
+                        continue;
+                    }
+                    let (next_scores, next_tokens) = if gen_opt.do_sample {
+                        let mut _scores: Tensor = (&scores + &beam_scores.unsqueeze(-1))
+                            .view((batch_size * group_size, vocab_size));
+                        self.top_k_top_p_filtering(&mut _scores, gen_opt.top_k, gen_opt.top_p, 2);
+                        self.typical_epsilon_eta_filtering(
+                            &mut _scores,
+                            gen_opt.typical_p,
+                            gen_opt.epsilon_cutoff,
+                            gen_opt.eta_cutoff,
+                            2,
+                        );
+                        let _scores = _scores
+                            .contiguous()
+                            .view((batch_size, group_size * vocab_size));
+
+                        let probabilities = _scores.softmax(-1, Float);
+                        let next_tokens = match sampler.as_mut() {
+                            Some(sampler) => sampler.multinomial(&probabilities, 2 * group_size),
+                            None => probabilities.multinomial(2 * group_size, false),
+                        };
+                        let next_scores = _scores.gather(-1, &next_tokens, false);
+                        let (next_scores, next_scores_indices) = next_scores.sort(1, true);
+                        let next_tokens = next_tokens.gather(-1, &next_scores_indices, false);
+                        (next_scores, next_tokens)
+                    } else {
+                        let next_scores: Tensor = &scores + &beam_scores.unsqueeze(-1);
+                        let next_scores = next_scores
+                            .contiguous()
+                            .view((batch_size, group_size * vocab_size));
+                        next_scores.topk(2 * group_size, 1, true, true)
+                    };
 
-                let eos_token_ids = gen_opt.eos_token_ids.as_ref();
-                let beam_ids_tensor = &next_tokens.floor_divide1(vocab_size);
-                let effective_beam_ids_tensor = (&next_tokens.ones_like().cumsum(0, Int64) - 1)
-                    * gen_opt.num_beams
-                    + beam_ids_tensor;
-                let token_id_tensor = &next_tokens - beam_ids_tensor * vocab_size;
-                let (max_scores, _) = next_scores.max2(1, false);
-                let mut eos_mask = token_id_tensor.ones_like();
-                if let Some(eos_token_id) = eos_token_ids {
-                    eos_mask -= token_id_tensor.eq(eos_token_id[0]).to_kind(Int64);
-                }
-                let eos_mask2 = eos_mask
-                    .cumsum(1, Int64)
-                    .le(gen_opt.num_beams)
-                    .to_kind(Bool)
-                    .logical_and(&eos_mask);
-
-                beam_scores = next_scores.masked_select(&eos_mask2);
-                beam_tokens = token_id_tensor.masked_select(&eos_mask2);
-                beam_indices = effective_beam_ids_tensor.masked_select(&eos_mask2);
-                let eos_pos = (eos_mask.ones_like() - eos_mask).nonzero();
-
-                for eos_idx in 0..eos_pos.size()[0] {
-                    let eos_data = eos_pos.get(eos_idx);
-                    let batch_index = eos_data.int64_value(&[0]);
-                    if !done[batch_index as usize] {
-                        let beam_index_pos = eos_data.int64_value(&[1]);
-                        let is_beam_token_worse_than_top_num_beams =
-                            beam_index_pos >= gen_opt.num_beams;
-                        if is_beam_token_worse_than_top_num_beams {
-                            continue;
+                    let eos_token_ids = gen_opt.eos_token_ids.as_ref();
+                    let beam_ids_tensor = &next_tokens.floor_divide1(vocab_size);
+                    let effective_beam_ids_tensor = (&next_tokens.ones_like().cumsum(0, Int64) - 1)
+                        * gen_opt.num_beams
+                        + group_index * group_size
+                        + beam_ids_tensor;
+                    let token_id_tensor = &next_tokens - beam_ids_tensor * vocab_size;
+                    let (max_scores, _) = next_scores.max2(1, false);
+                    let mut eos_mask = token_id_tensor.ones_like();
+                    if let Some(eos_token_id) = eos_token_ids {
+                        eos_mask -= token_id_tensor.eq(eos_token_id[0]).to_kind(Int64);
+                    }
+                    //            Candidates meeting the stopping criteria are finished, as for the EOS token
+                    let continue_mask = if gen_opt.stopping_criteria.is_some() {
+                        let num_candidates = 2 * group_size as usize;
+                        let mut continued = Vec::<i64>::from(eos_mask.view(-1).to(Device::Cpu));
+                        let tokens = Vec::<i64>::from(token_id_tensor.view(-1).to(Device::Cpu));
+                        let beam_ids =
+                            Vec::<i64>::from(effective_beam_ids_tensor.view(-1).to(Device::Cpu));
+                        for batch_index in 0..batch_size as usize {
+                            let hypotheses_index =
+                                batch_index * num_groups as usize + group_index as usize;
+                            if done[hypotheses_index] {
+                                continue;
+                            }
+                            let offset = batch_index * num_candidates;
+                            let mut stopped = vec![];
+                            for candidate in 0..num_candidates {
+                                if continued[offset + candidate] == 0 {
+                                    continue;
+                                }
+                                let mut sequence = input_ids
+                                    .get(beam_ids[offset + candidate])
+                                    .iter::<i64>()
+                                    .unwrap()
+                                    .collect::<Vec<i64>>();
+                                sequence.push(tokens[offset + candidate]);
+                                if self
+                                    .stop_position(&gen_opt.stopping_criteria, &sequence, cur_len)
+                                    .is_some()
+                                {
+                                    if (candidate as i64) < group_size {
+                                        hypotheses[hypotheses_index].add(
+                                            Tensor::of_slice(&sequence).to(input_ids.device()),
+                                            next_scores.double_value(&[
+                                                batch_index as i64,
+                                                candidate as i64,
+                                            ]),
+                                        );
+                                    }
+                                    stopped.push(candidate);
+                                }
+                            }
+                            // Stopped candidates are kept if there are not enough other candidates to continue
+                            let num_continued = continued[offset..offset + num_candidates]
+                                .iter()
+                                .sum::<i64>();
+                            if num_continued - stopped.len() as i64 >= group_size {
+                                for candidate in stopped {
+                                    continued[offset + candidate] = 0;
+                                }
+                            }
+                        }
+                        Tensor::of_slice(&continued)
+                            .view((batch_size, 2 * group_size))
+                            .to(eos_mask.device())
+                    } else {
+                        eos_mask.shallow_clone()
+                    };
+                    let eos_mask2 = continue_mask
+                        .cumsum(1, Int64)
+                        .le(group_size)
+                        .to_kind(Bool)
+                        .logical_and(&continue_mask);
+
+                    let group_scores = next_scores.masked_select(&eos_mask2);
+                    let group_tokens = token_id_tensor.masked_select(&eos_mask2);
+                    let group_indices = effective_beam_ids_tensor.masked_select(&eos_mask2);
+                    let eos_pos = (eos_mask.ones_like() - eos_mask).nonzero();
+
+                    for eos_idx in 0..eos_pos.size()[0] {
+                        let eos_data = eos_pos.get(eos_idx);
+                        let batch_index = eos_data.int64_value(&[0]);
+                        let hypotheses_index = (batch_index * num_groups + group_index) as usize;
+                        if !done[hypotheses_index] {
+                            let beam_index_pos = eos_data.int64_value(&[1]);
+                            let is_beam_token_worse_than_top_num_beams =
+                                beam_index_pos >= group_size;
+                            if is_beam_token_worse_than_top_num_beams {
+                                continue;
+                            }
+                            let effective_beam_id = effective_beam_ids_tensor
+                                .int64_value(&[batch_index, beam_index_pos]);
+                            let beam_token_score =
+                                next_scores.double_value(&[batch_index, beam_index_pos]);
+                            hypotheses[hypotheses_index]
+                                .add(input_ids.get(effective_beam_id).copy(), beam_token_score);
                         }
-                        let effective_beam_id =
-                            effective_beam_ids_tensor.int64_value(&[batch_index, beam_index_pos]);
-                        let beam_token_score =
-                            next_scores.double_value(&[batch_index, beam_index_pos]);
-                        hypotheses[batch_index as usize]
-                            .add(input_ids.get(effective_beam_id).copy(), beam_token_score);
                     }
-                }
 
-                for batch_index in 0..batch_size {
-                    if done[batch_index as usize] {
-                        let _ = beam_scores
-                            .narrow(0, batch_index * gen_opt.num_beams, gen_opt.num_beams)
-                            .fill_(0f64);
-                        let _ = beam_tokens
-                            .narrow(0, batch_index * gen_opt.num_beams, gen_opt.num_beams)
-                            .fill_(gen_opt.pad_token_id.unwrap());
-                        let _ = beam_indices
-                            .narrow(0, batch_index * gen_opt.num_beams, gen_opt.num_beams)
-                            .fill_(0);
-                        continue;
-                    } else {
-                        done[batch_index as usize] |= hypotheses[batch_index as usize]
-                            .is_done(max_scores.double_value(&[batch_index]), current_length);
+                    for batch_index in 0..batch_size {
+                        let hypotheses_index = (batch_index * num_groups + group_index) as usize;
+                        if done[hypotheses_index] {
+                            let _ = group_scores
+                                .narrow(0, batch_index * group_size, group_size)
+                                .fill_(0f64);
+                            let _ = group_tokens
+                                .narrow(0, batch_index * group_size, group_size)
+                                .fill_(gen_opt.pad_token_id.unwrap());
+                            let _ = group_indices
+                                .narrow(0, batch_index * group_size, group_size)
+                                .fill_(0);
+                            continue;
+                        } else {
+                            done[hypotheses_index] |= hypotheses[hypotheses_index]
+                                .is_done(max_scores.double_value(&[batch_index]), current_length);
+                        }
+                    }
+                    let group_tokens = group_tokens.view((batch_size, group_size));
+                    if num_groups > 1 {
+                        let _ = token_frequency.scatter_add_(
+                            1,
+                            &group_tokens,
+                            &group_tokens.ones_like().to_kind(Float),
+                        );
                     }
+                    group_outputs.push((
+                        group_scores.view((batch_size, group_size)),
+                        group_tokens,
+                        group_indices.view((batch_size, group_size)),
+                    ));
                 }
-                beam_scores = beam_scores.view(-1);
-                beam_tokens = beam_tokens.view(-1);
-                beam_indices = beam_indices.view(-1);
+                beam_scores = Tensor::cat(
+                    &group_outputs
+                        .iter()
+                        .map(|(scores, _, _)| scores)
+                        .collect::<Vec<&Tensor>>(),
+                    1,
+                )
+                .view(-1);
+                beam_tokens = Tensor::cat(
+                    &group_outputs
+                        .iter()
+                        .map(|(_, tokens, _)| tokens)
+                        .collect::<Vec<&Tensor>>(),
+                    1,
+                )
+                .view(-1);
+                beam_indices = Tensor::cat(
+                    &group_outputs
+                        .iter()
+                        .map(|(_, _, indices)| indices)
+                        .collect::<Vec<&Tensor>>(),
+                    1,
+                )
+                .view(-1);
                 if done.iter().all(|&x| x) {
                     break;
                 }
@@ -2469,23 +4928,48 @@ pub(crate) mod private_generation_utils {
                 current_length += 1;
             }
 
-            let mut batch_index = 0i64;
-
-            loop {
-                if batch_index == batch_size {
-                    break;
-                }
-                if done[batch_index as usize] {
-                    batch_index += 1;
-                    continue;
-                }
-                for beam_index in 0..gen_opt.num_beams {
-                    let effective_beam_id = batch_index * gen_opt.num_beams + beam_index;
-                    let final_score = f64::from(beam_scores.get(effective_beam_id));
-                    let final_tokens = input_ids.get(effective_beam_id);
-                    hypotheses[batch_index as usize].add(final_tokens, final_score);
+            for batch_index in 0..batch_size {
+                for group_index in 0..num_groups {
+                    let hypotheses_index = (batch_index * num_groups + group_index) as usize;
+                    if done[hypotheses_index] {
+                        continue;
+                    }
+                    //            With forced token sequences, only the beams meeting all the constraints are kept,
+                    //            unless no hypothesis meets them
+                    let mut final_beams = (0..group_size)
+                        .map(|beam_index| {
+                            batch_index * gen_opt.num_beams + group_index * group_size + beam_index
+                        })
+                        .collect::<Vec<i64>>();
+                    if let Some(force_words_ids) = &gen_opt.force_words_ids {
+                        let num_constraint_tokens =
+                            force_words_ids.iter().map(Vec::len).sum::<usize>();
+                        let constrained_beams = final_beams
+                            .iter()
+                            .cloned()
+                            .filter(|&effective_beam_id| {
+                                let sequence = input_ids
+                                    .get(effective_beam_id)
+                                    .iter::<i64>()
+                                    .unwrap()
+                                    .collect::<Vec<i64>>();
+                                constraint_progress(&sequence[cur_len as usize..], force_words_ids)
+                                    .0
+                                    == num_constraint_tokens
+                            })
+                            .collect::<Vec<i64>>();
+                        if !constrained_beams.is_empty()
+                            | !hypotheses[hypotheses_index].beams.is_empty()
+                        {
+                            final_beams = constrained_beams;
+                        }
+                    }
+                    for effective_beam_id in final_beams {
+                        let final_score = f64::from(beam_scores.get(effective_beam_id));
+                        let final_tokens = input_ids.get(effective_beam_id);
+                        hypotheses[hypotheses_index].add(final_tokens, final_score);
+                    }
                 }
-                batch_index += 1;
             }
             let (output_batch_size, output_num_return_sequences_per_batch) = if gen_opt.do_sample {
                 (batch_size, 1)
@@ -2500,15 +4984,24 @@ pub(crate) mod private_generation_utils {
                 Tensor::zeros(&[output_batch_size], (Int64, input_ids.device()));
             let mut best_ids = vec![];
 
-            for (hypothesis_index, hypothesis) in hypotheses.iter().enumerate() {
-                let mut sorted_hypotheses = hypothesis.clone();
-                sorted_hypotheses
-                    .beams
-                    .sort_by_key(|(score, _)| OrderedFloat(*score));
+            for (hypothesis_index, batch_hypotheses) in
+                hypotheses.chunks(num_groups as usize).enumerate()
+            {
+                //            The hypotheses of all groups are ranked together
+                let mut sorted_hypotheses = batch_hypotheses
+                    .iter()
+                    .flat_map(|hypothesis| {
+                        hypothesis
+                            .beams
+                            .iter()
+                            .map(|(score, tensor)| (*score, tensor.copy()))
+                    })
+                    .collect::<Vec<(f64, Tensor)>>();
+                sorted_hypotheses.sort_by_key(|(score, _)| OrderedFloat(*score));
                 for j in 0..output_num_return_sequences_per_batch {
                     let effective_batch_index =
                         output_num_return_sequences_per_batch * hypothesis_index as i64 + j;
-                    let (_, best_hyp) = sorted_hypotheses.beams.pop().unwrap();
+                    let (_, best_hyp) = sorted_hypotheses.pop().unwrap();
                     let _ = sentence_lengths.index_fill_(
                         0,
                         &Tensor::of_slice(&[effective_batch_index]).to(sentence_lengths.device()),
@@ -2552,84 +5045,471 @@ pub(crate) mod private_generation_utils {
             decoded
         }
 
-        fn reorder_cache(
+        fn prepare_prompt_ids<'a, S>(
             &self,
-            past: &mut Cache,
-            _encoder_outputs: Option<Tensor>,
-            _beam_indices: &Tensor,
-        ) -> Option<Tensor> {
-            match past {
-                Cache::None => None,
-                _ => {
-                    panic!("Not implemented");
-                }
+            prompt_texts: Option<S>,
+            max_length: Option<i64>,
+        ) -> Tensor
+        where
+            S: AsRef<[&'a str]>,
+        {
+            let eos_token_ids = PrivateLanguageGenerator::get_eos_ids(self).clone();
+
+            let config = PrivateLanguageGenerator::get_config(self);
+            // Prompts are not truncated to `max_length` if the output length is relative to the prompt
+            let encoding_max_len = if self.is_encoder_decoder()
+                | (max_length.is_none() & config.max_new_tokens.is_some())
+            {
+                MAX_PROMPT_LENGTH
+            } else {
+                max_length.unwrap_or(config.max_length)
+            };
+            let pad_token_id = match self.get_pad_id() {
+                Some(value) => Some(*value),
+                None => match &eos_token_ids {
+                    Some(eos_ids) => Some(eos_ids[0]),
+                    None => None,
+                },
+            };
+
+            match prompt_texts {
+                Some(text) => self.encode_prompt_text(text, encoding_max_len, pad_token_id),
+                None => match self.get_bos_id() {
+                    Some(bos_id) => {
+                        Tensor::ones(&[1, 1], (Int64, self.get_var_store().device())) * *bos_id
+                    }
+                    None => panic!(
+                        "A model with a BOS token must be used to start generation with an empty input"
+                    ),
+                },
             }
         }
-    }
-}
 
-/// # Common trait for text generation models.
-/// Main API for text generation
-pub trait LanguageGenerator<T: LMHeadModel, V: Vocab, U: Tokenizer<V>>:
-    PrivateLanguageGenerator<T, V, U>
-{
-    /// Generate text based on a vector of promp texts.
-    ///
-    /// # Arguments
-    ///
-    /// * `prompt_texts` - `Option<Vec<&str>>` Optional vector of text prompts. An empty prompt to the model may be passed if the model implement a `bos_id`.
-    /// * `attention_mask` - `Option<Tensor>` Optional attention mask to hide portions of the prompt.
-    ///
-    /// # Returns
-    /// * `Vec<String>` Vector of generated strings based on the prompts of length *number_of_prompts* x *num_return_sequences*.
-    ///
-    /// # Example
-    ///
-    /// ```no_run
-    /// # use std::path::PathBuf;
-    /// # use tch::Device;
-    /// # fn main() -> anyhow::Result<()> {
-    /// use rust_bert::pipelines::generation_utils::{
-    ///     GPT2Generator, GenerateConfig, LanguageGenerator,
-    /// };
-    /// # let mut home: PathBuf = dirs::home_dir().unwrap();
-    /// # home.push("rustbert");
-    /// # home.push("gpt2");
-    /// # let config_path = &home.as_path().join("config.json");
-    /// # let vocab_path = &home.as_path().join("vocab.txt");
-    /// # let merges_path = &home.as_path().join("merges.txt");
-    /// # let weights_path = &home.as_path().join("model.ot");
-    /// let device = Device::cuda_if_available();
-    /// let generate_config = GenerateConfig {
-    ///     max_length: 30,
-    ///     do_sample: true,
-    ///     num_beams: 5,
-    ///     temperature: 1.1,
-    ///     num_return_sequences: 3,
-    ///     ..Default::default()
-    /// };
-    /// let mut gpt2_generator = GPT2Generator::new(generate_config)?;
-    /// let input_context = "The dog";
-    /// let second_input_context = "The cat was";
-    ///
-    /// let attention_mask = None;
-    /// let min_length = 32;
-    /// let max_length = 128;
-    /// let decoder_start_token_id = None;
-    ///
-    /// let output = gpt2_generator.generate(
-    ///     Some(vec![input_context, second_input_context]),
-    ///     attention_mask,
-    ///     min_length,
-    ///     max_length,
-    ///     decoder_start_token_id,
-    /// );
-    /// # Ok(())
-    /// # }
-    /// ```
-    /// Example output: \
-    /// ```no_run
-    /// # let output =
+        #[allow(clippy::too_many_arguments)]
+        fn generate_with_encoder_output(
+            &self,
+            input_ids: Tensor,
+            attention_mask: Option<Tensor>,
+            min_length: Option<i64>,
+            max_length: Option<i64>,
+            decoder_start_token_id: Option<i64>,
+            generate_options: Option<&GenerateOptions>,
+            encoded_prompts: Option<&EncodedPrompts>,
+            source_copy_bias: Option<f64>,
+            force_words_ids: Option<Vec<Vec<i64>>>,
+            token_callback: Option<&mut dyn FnMut(usize, i64)>,
+            output_scores: bool,
+        ) -> (
+            Vec<Vec<i64>>,
+            Option<(Tensor, Tensor)>,
+            GenerationUsage,
+            Option<Vec<Vec<f64>>>,
+        ) {
+            let start = Instant::now();
+            let eos_token_ids = PrivateLanguageGenerator::get_eos_ids(self).clone();
+
+            let overridden_config;
+            let config = match generate_options {
+                Some(generate_options) => {
+                    let mut config = PrivateLanguageGenerator::get_config(self).clone();
+                    generate_options.update_generate_config(&mut config);
+                    config.validate();
+                    overridden_config = config;
+                    &overridden_config
+                }
+                None => PrivateLanguageGenerator::get_config(self),
+            };
+            let do_sample = config.do_sample;
+            let num_return_sequences = config.num_return_sequences;
+            let num_beams = config.num_beams;
+            let early_stopping = config.early_stopping;
+            let temperature = config.temperature;
+            let top_k = config.top_k;
+            let top_p = config.top_p;
+            let repetition_penalty = config.repetition_penalty;
+            let length_penalty = config.length_penalty;
+            let no_repeat_ngram_size = config.no_repeat_ngram_size;
+            let allowed_token_ids = config.allowed_token_ids.clone();
+            let prefix_allowed_tokens_fn = config.prefix_allowed_tokens_fn.clone();
+            let source_copy_bias = source_copy_bias.unwrap_or(config.source_copy_bias);
+            let force_words_ids = force_words_ids.or_else(|| config.force_words_ids.clone());
+            let encoder_no_repeat_ngram_size = config.encoder_no_repeat_ngram_size;
+            let dry_multiplier = config.dry_multiplier;
+            let dry_base = config.dry_base;
+            let dry_allowed_length = config.dry_allowed_length;
+            let dry_sequence_breakers = config.dry_sequence_breakers.clone();
+            let token_healing = config.token_healing;
+            let stopping_criteria = config.stopping_criteria.clone();
+            let grammar_token_texts = config
+                .grammar
+                .as_ref()
+                .map(|_| self.get_grammar_token_texts());
+
+            let pad_token_id = match self.get_pad_id() {
+                Some(value) => Some(*value),
+                None => match &eos_token_ids {
+                    Some(eos_ids) => Some(eos_ids[0]),
+                    None => None,
+                },
+            };
+
+            let (input_ids, attention_mask, token_healing_ids) =
+                if token_healing & !self.is_encoder_decoder() {
+                    let (input_ids, attention_mask, token_healing_ids) =
+                        self.heal_prompt_ids(&input_ids, attention_mask, pad_token_id);
+                    (input_ids, Some(attention_mask), Some(token_healing_ids))
+                } else {
+                    (input_ids, attention_mask, None)
+                };
+
+            let input_ids_len = *input_ids.size().last().unwrap();
+            let cur_len = if !self.is_encoder_decoder() {
+                *input_ids.size().last().unwrap()
+            } else {
+                1
+            };
+            // Lengths passed explicitly take precedence over the bounds relative to the prompt
+            let min_length = min_length.unwrap_or_else(|| match config.min_new_tokens {
+                Some(min_new_tokens) => cur_len + min_new_tokens,
+                None => config.min_length,
+            });
+            let max_length = max_length.unwrap_or_else(|| match config.max_new_tokens {
+                Some(max_new_tokens) => cur_len + max_new_tokens,
+                None => config.max_length,
+            });
+            let batch_size = *input_ids.size().first().unwrap();
+
+            let (effective_batch_size, effective_batch_mult) = match do_sample {
+                true => (
+                    batch_size * num_return_sequences as i64,
+                    num_return_sequences as i64,
+                ),
+                false => (batch_size, 1),
+            };
+
+            let attention_mask = match attention_mask {
+                Some(value) => value,
+                None => match self.get_pad_id() {
+                    Some(pad_id) => input_ids.ne(*pad_id).to_kind(Int64),
+                    None => input_ids.ones_like().to_kind(Int64),
+                },
+            };
+            let prompt_tokens = attention_mask.sum(Int64).int64_value(&[]) as usize;
+            let prompt_attention_mask = if output_scores & !self.is_encoder_decoder() {
+                Some(attention_mask.shallow_clone())
+            } else {
+                None
+            };
+
+            let source_ids = if source_copy_bias != 0.0 {
+                let expanded_batch_indices =
+                    Tensor::arange(batch_size, (Int64, input_ids.device()))
+                        .view((-1, 1))
+                        .repeat(&[1, num_beams as i64 * effective_batch_mult])
+                        .view(-1);
+                Some(
+                    input_ids
+                        .masked_fill(&attention_mask.eq(0), pad_token_id.unwrap_or(0))
+                        .index_select(0, &expanded_batch_indices),
+                )
+            } else {
+                None
+            };
+
+            let encoder_ngrams = if self.is_encoder_decoder() & (encoder_no_repeat_ngram_size > 0) {
+                Some(self.get_source_ngrams(
+                    &input_ids,
+                    &attention_mask,
+                    encoder_no_repeat_ngram_size,
+                ))
+            } else {
+                None
+            };
+
+            let (encoder_outputs, unexpanded_encoder_output) = if self.is_encoder_decoder() {
+                let encoder_outputs = match encoded_prompts {
+                    Some(encoded_prompts) => encoded_prompts.hidden_states.shallow_clone(),
+                    None => self.encode(&input_ids, Some(&attention_mask)).unwrap(),
+                };
+                let expanded_batch_indices =
+                    Tensor::arange(batch_size, (Int64, input_ids.device()))
+                        .view((-1, 1))
+                        .repeat(&[1, num_beams as i64 * effective_batch_mult])
+                        .view(-1);
+                (
+                    Some(encoder_outputs.index_select(0, &expanded_batch_indices)),
+                    Some((encoder_outputs, attention_mask.shallow_clone())),
+                )
+            } else {
+                (None, None)
+            };
+
+            let (input_ids, attention_mask) = if !self.is_encoder_decoder() {
+                if (num_return_sequences > 1) | (num_beams > 1) {
+                    (
+                        input_ids
+                            .unsqueeze(1)
+                            .expand(
+                                &[batch_size, effective_batch_mult * num_beams as i64, cur_len],
+                                true,
+                            )
+                            .contiguous()
+                            .view((effective_batch_size * num_beams as i64, cur_len)),
+                        attention_mask
+                            .unsqueeze(1)
+                            .expand(
+                                &[batch_size, effective_batch_mult * num_beams as i64, cur_len],
+                                true,
+                            )
+                            .contiguous()
+                            .view((effective_batch_size * num_beams as i64, cur_len)),
+                    )
+                } else {
+                    (input_ids, attention_mask)
+                }
+            } else {
+                let decoder_start_token_id = decoder_start_token_id.unwrap_or_else(|| {
+                    self.get_decoder_start_id()
+                        .expect("decoder start id must be specified for encoder decoders")
+                });
+                let input_ids = Tensor::full(
+                    &[effective_batch_size * num_beams as i64, 1],
+                    decoder_start_token_id,
+                    (Int64, input_ids.device()),
+                );
+                let attention_mask = if (num_return_sequences > 1) | (num_beams > 1) {
+                    attention_mask
+                        .unsqueeze(1)
+                        .expand(
+                            &[
+                                batch_size,
+                                effective_batch_mult * num_beams as i64,
+                                input_ids_len,
+                            ],
+                            true,
+                        )
+                        .contiguous()
+                        .view((effective_batch_size * num_beams as i64, input_ids_len))
+                } else {
+                    attention_mask
+                };
+                (input_ids, attention_mask)
+            };
+
+            let gen_opt = ResolvedGenerateOptions {
+                min_length,
+                max_length,
+                do_sample,
+                seed: config.seed,
+                temperature,
+                top_k,
+                top_p,
+                typical_p: config.typical_p,
+                epsilon_cutoff: config.epsilon_cutoff,
+                eta_cutoff: config.eta_cutoff,
+                repetition_penalty,
+                no_repeat_ngram_size,
+                pad_token_id,
+                eos_token_ids: eos_token_ids.clone(),
+                num_return_sequences,
+                early_stopping,
+                num_beams,
+                num_beam_groups: config.num_beam_groups,
+                diversity_penalty: config.diversity_penalty,
+                length_penalty,
+                allowed_token_ids,
+                prefix_allowed_tokens_fn,
+                source_copy_bias,
+                source_ids,
+                encoder_no_repeat_ngram_size,
+                encoder_ngrams,
+                dry_multiplier,
+                dry_base,
+                dry_allowed_length,
+                dry_sequence_breakers,
+                token_healing,
+                token_healing_ids,
+                stopping_criteria: stopping_criteria.clone(),
+                force_words_ids,
+                bad_words_ids: config.bad_words_ids.clone(),
+                grammar: config.grammar.clone(),
+                grammar_token_texts,
+                cache_check_tolerance: config.cache_check_tolerance,
+            };
+
+            let decoded = no_grad(|| {
+                if num_beams > 1 {
+                    self.generate_beam_search(
+                        input_ids,
+                        encoder_outputs,
+                        cur_len,
+                        effective_batch_size,
+                        attention_mask,
+                        gen_opt,
+                    )
+                } else {
+                    self.generate_no_beam_search(
+                        input_ids,
+                        encoder_outputs,
+                        cur_len,
+                        effective_batch_size,
+                        attention_mask,
+                        gen_opt,
+                        token_callback,
+                    )
+                }
+            });
+            let num_sequences = *decoded.size().first().unwrap();
+            let decoded_scores = if output_scores {
+                Some(no_grad(|| {
+                    self.score_generated_tokens(
+                        &decoded,
+                        cur_len,
+                        batch_size,
+                        unexpanded_encoder_output.as_ref(),
+                        prompt_attention_mask.as_ref(),
+                    )
+                }))
+            } else {
+                None
+            };
+            let mut output_ids = Vec::with_capacity(num_sequences as usize);
+            let mut output_scores = decoded_scores
+                .as_ref()
+                .map(|_| Vec::with_capacity(num_sequences as usize));
+            let mut generated_tokens = 0;
+            for sequence_index in 0..num_sequences {
+                let mut sequence_output_ids = decoded
+                    .as_ref()
+                    .get(sequence_index)
+                    .iter::<i64>()
+                    .unwrap()
+                    .collect::<Vec<i64>>();
+                if let Some(position) =
+                    self.stop_position(&stopping_criteria, &sequence_output_ids, cur_len)
+                {
+                    sequence_output_ids.truncate(cur_len as usize + position);
+                }
+                if let (Some(decoded_scores), Some(output_scores)) =
+                    (&decoded_scores, output_scores.as_mut())
+                {
+                    let generated_ids = &sequence_output_ids[cur_len as usize..];
+                    let num_scored = generated_ids
+                        .iter()
+                        .position(|token_id| {
+                            eos_token_ids
+                                .as_ref()
+                                .map_or(false, |eos_ids| eos_ids.contains(token_id))
+                        })
+                        .map(|position| position + 1)
+                        .or_else(|| {
+                            generated_ids
+                                .iter()
+                                .position(|&token_id| Some(token_id) == pad_token_id)
+                        })
+                        .unwrap_or(generated_ids.len());
+                    output_scores.push(
+                        decoded_scores
+                            .get(sequence_index)
+                            .iter::<f64>()
+                            .unwrap()
+                            .take(num_scored)
+                            .collect::<Vec<f64>>(),
+                    );
+                }
+                generated_tokens += sequence_output_ids
+                    .iter()
+                    .skip(cur_len as usize)
+                    .filter(|&&token_id| Some(token_id) != pad_token_id)
+                    .count();
+                if !self.is_encoder_decoder() & !config.echo_prompt {
+                    output_ids.push(sequence_output_ids[cur_len as usize..].to_vec());
+                } else {
+                    output_ids.push(sequence_output_ids);
+                }
+            }
+            let usage = GenerationUsage::new(prompt_tokens, generated_tokens, start.elapsed());
+            (output_ids, unexpanded_encoder_output, usage, output_scores)
+        }
+
+        fn reorder_cache(
+            &self,
+            past: &mut Cache,
+            _encoder_outputs: Option<Tensor>,
+            _beam_indices: &Tensor,
+        ) -> Option<Tensor> {
+            match past {
+                Cache::None => None,
+                _ => {
+                    panic!("Not implemented");
+                }
+            }
+        }
+    }
+}
+
+/// # Common trait for text generation models.
+/// Main API for text generation
+pub trait LanguageGenerator<T: LMHeadModel, V: Vocab, U: Tokenizer<V>>:
+    PrivateLanguageGenerator<T, V, U>
+{
+    /// Generate text based on a vector of promp texts.
+    ///
+    /// # Arguments
+    ///
+    /// * `prompt_texts` - `Option<Vec<&str>>` Optional vector of text prompts. An empty prompt to the model may be passed if the model implement a `bos_id`.
+    /// * `attention_mask` - `Option<Tensor>` Optional attention mask to hide portions of the prompt.
+    ///
+    /// # Returns
+    /// * `Vec<String>` Vector of generated strings based on the prompts of length *number_of_prompts* x *num_return_sequences*.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// # use std::path::PathBuf;
+    /// # use tch::Device;
+    /// # fn main() -> anyhow::Result<()> {
+    /// use rust_bert::pipelines::generation_utils::{
+    ///     GPT2Generator, GenerateConfig, LanguageGenerator,
+    /// };
+    /// # let mut home: PathBuf = dirs::home_dir().unwrap();
+    /// # home.push("rustbert");
+    /// # home.push("gpt2");
+    /// # let config_path = &home.as_path().join("config.json");
+    /// # let vocab_path = &home.as_path().join("vocab.txt");
+    /// # let merges_path = &home.as_path().join("merges.txt");
+    /// # let weights_path = &home.as_path().join("model.ot");
+    /// let device = Device::cuda_if_available();
+    /// let generate_config = GenerateConfig {
+    ///     max_length: 30,
+    ///     do_sample: true,
+    ///     num_beams: 5,
+    ///     temperature: 1.1,
+    ///     num_return_sequences: 3,
+    ///     ..Default::default()
+    /// };
+    /// let mut gpt2_generator = GPT2Generator::new(generate_config)?;
+    /// let input_context = "The dog";
+    /// let second_input_context = "The cat was";
+    ///
+    /// let attention_mask = None;
+    /// let min_length = 32;
+    /// let max_length = 128;
+    /// let decoder_start_token_id = None;
+    ///
+    /// let output = gpt2_generator.generate(
+    ///     Some(vec![input_context, second_input_context]),
+    ///     attention_mask,
+    ///     min_length,
+    ///     max_length,
+    ///     decoder_start_token_id,
+    /// );
+    /// # Ok(())
+    /// # }
+    /// ```
+    /// Example output: \
+    /// ```no_run
+    /// # let output =
     /// [
     ///     "The dog's owners, however, did not want to be named. According to the lawsuit, the animal's owner, a 29-year",
     ///     "The dog has always been part of the family. \"He was always going to be my dog and he was always looking out for me",
@@ -2640,384 +5520,994 @@ pub trait LanguageGenerator<T: LMHeadModel, V: Vocab, U: Tokenizer<V>>:
     /// ]
     /// # ;
     /// ```
-    fn generate<'a, S>(
+    fn generate<'a, S>(
+        &self,
+        prompt_texts: Option<S>,
+        attention_mask: Option<Tensor>,
+        min_length: impl Into<Option<i64>>,
+        max_length: impl Into<Option<i64>>,
+        decoder_start_token_id: impl Into<Option<i64>>,
+    ) -> Vec<String>
+    where
+        S: AsRef<[&'a str]>,
+    {
+        let generated = self.generate_indices(
+            prompt_texts,
+            attention_mask,
+            min_length,
+            max_length,
+            decoder_start_token_id,
+        );
+        let mut output = Vec::with_capacity(generated.len());
+        for generated_sequence in generated {
+            output.push(self.get_tokenizer().decode(generated_sequence, true, true));
+        }
+        output
+    }
+
+    /// Generate token indices without decoding (useful for token-level operations before returning final text or as validation step during training).
+    ///
+    /// # Arguments
+    ///
+    /// * `prompt_texts` - `Option<Vec<&str>>` Optional vector of text prompts. An empty prompt to the model may be passed if the model implement a `bos_id`.
+    /// * `attention_mask` - `Option<Tensor>` Optional attention mask to hide portions of the prompt.
+    ///
+    /// # Returns
+    /// * `Vec<Vec<i64>>` Vector of Vector of generated token indices based on the prompts of length *number_of_prompts* x *num_return_sequences*.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// # use std::path::PathBuf;
+    /// # use tch::Device;
+    /// # fn main() -> anyhow::Result<()> {
+    /// use rust_bert::pipelines::generation_utils::{
+    ///     GPT2Generator, GenerateConfig, LanguageGenerator,
+    /// };
+    /// # let mut home: PathBuf = dirs::home_dir().unwrap();
+    /// # home.push("rustbert");
+    /// # home.push("gpt2");
+    /// # let config_path = &home.as_path().join("config.json");
+    /// # let vocab_path = &home.as_path().join("vocab.txt");
+    /// # let merges_path = &home.as_path().join("merges.txt");
+    /// # let weights_path = &home.as_path().join("model.ot");
+    /// let device = Device::cuda_if_available();
+    /// let generate_config = GenerateConfig {
+    ///     max_length: 30,
+    ///     do_sample: true,
+    ///     num_beams: 5,
+    ///     temperature: 1.1,
+    ///     num_return_sequences: 3,
+    ///     ..Default::default()
+    /// };
+    /// let mut gpt2_generator = GPT2Generator::new(generate_config)?;
+    /// let input_context = "The dog";
+    /// let second_input_context = "The cat was";
+    /// let attention_mask = None;
+    /// let min_length = 32;
+    /// let max_length = 128;
+    /// let decoder_start_token_id = None;
+    ///
+    /// let output = gpt2_generator.generate_indices(
+    ///     Some(vec![input_context, second_input_context]),
+    ///     attention_mask,
+    ///     min_length,
+    ///     max_length,
+    ///     decoder_start_token_id,
+    /// );
+    /// # Ok(())
+    /// # }
+    /// ```
+    fn generate_indices<'a, S>(
+        &self,
+        prompt_texts: Option<S>,
+        attention_mask: Option<Tensor>,
+        min_length: impl Into<Option<i64>>,
+        max_length: impl Into<Option<i64>>,
+        decoder_start_token_id: impl Into<Option<i64>>,
+    ) -> Vec<Vec<i64>>
+    where
+        S: AsRef<[&'a str]>,
+    {
+        let max_length = max_length.into();
+        let input_ids = self.prepare_prompt_ids(prompt_texts, max_length);
+        self.generate_from_ids_and_past(
+            input_ids,
+            attention_mask,
+            min_length,
+            max_length,
+            decoder_start_token_id,
+        )
+    }
+
+    fn generate_from_ids_and_past(
+        &self,
+        input_ids: Tensor,
+        attention_mask: Option<Tensor>,
+        min_length: impl Into<Option<i64>>,
+        max_length: impl Into<Option<i64>>,
+        decoder_start_token_id: impl Into<Option<i64>>,
+    ) -> Vec<Vec<i64>> {
+        self.generate_with_encoder_output(
+            input_ids,
+            attention_mask,
+            min_length.into(),
+            max_length.into(),
+            decoder_start_token_id.into(),
+            None,
+            None,
+            None,
+            None,
+            None,
+            false,
+        )
+        .0
+    }
+
+    /// Generate text based on a vector of prompt texts, overriding the generation configuration of the model for this
+    /// call with the options provided. This avoids creating a new model for each set of generation parameters.
+    ///
+    /// # Arguments
+    ///
+    /// * `prompt_texts` - `Option<Vec<&str>>` Optional vector of text prompts. An empty prompt to the model may be passed if the model implement a `bos_id`.
+    /// * `attention_mask` - `Option<Tensor>` Optional attention mask to hide portions of the prompt.
+    /// * `generate_options` - `&GenerateOptions` options overriding the `GenerateConfig` of the model
+    ///
+    /// # Returns
+    /// * `Vec<String>` Vector of generated strings based on the prompts of length *number_of_prompts* x *num_return_sequences*.
+    ///
+    /// # Panics
+    /// If the configuration resulting from the overrides is invalid (for example `num_return_sequences` larger than
+    /// `num_beams` for a beam search)
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// # fn main() -> anyhow::Result<()> {
+    /// use rust_bert::pipelines::generation_utils::{GPT2Generator, GenerateOptions, LanguageGenerator};
+    ///
+    /// let gpt2_generator = GPT2Generator::new(Default::default())?;
+    /// let short_output = gpt2_generator.generate_with_options(
+    ///     Some(&["The dog"]),
+    ///     None,
+    ///     &GenerateOptions {
+    ///         max_length: Some(16),
+    ///         ..Default::default()
+    ///     },
+    /// );
+    /// let sampled_output = gpt2_generator.generate_with_options(
+    ///     Some(&["The dog"]),
+    ///     None,
+    ///     &GenerateOptions {
+    ///         do_sample: Some(true),
+    ///         num_beams: Some(1),
+    ///         temperature: Some(1.2),
+    ///         seed: Some(42),
+    ///         ..Default::default()
+    ///     },
+    /// );
+    /// # Ok(())
+    /// # }
+    /// ```
+    fn generate_with_options<'a, S>(
+        &self,
+        prompt_texts: Option<S>,
+        attention_mask: Option<Tensor>,
+        generate_options: &GenerateOptions,
+    ) -> Vec<String>
+    where
+        S: AsRef<[&'a str]>,
+    {
+        self.generate_indices_with_options(prompt_texts, attention_mask, generate_options)
+            .into_iter()
+            .map(|generated_sequence| self.get_tokenizer().decode(generated_sequence, true, true))
+            .collect()
+    }
+
+    /// Generate token indices without decoding, overriding the generation configuration of the model for this call
+    /// with the options provided (see `generate_with_options`).
+    ///
+    /// # Arguments
+    ///
+    /// * `prompt_texts` - `Option<Vec<&str>>` Optional vector of text prompts. An empty prompt to the model may be passed if the model implement a `bos_id`.
+    /// * `attention_mask` - `Option<Tensor>` Optional attention mask to hide portions of the prompt.
+    /// * `generate_options` - `&GenerateOptions` options overriding the `GenerateConfig` of the model
+    ///
+    /// # Returns
+    /// * `Vec<Vec<i64>>` Vector of Vector of generated token indices based on the prompts of length *number_of_prompts* x *num_return_sequences*.
+    fn generate_indices_with_options<'a, S>(
+        &self,
+        prompt_texts: Option<S>,
+        attention_mask: Option<Tensor>,
+        generate_options: &GenerateOptions,
+    ) -> Vec<Vec<i64>>
+    where
+        S: AsRef<[&'a str]>,
+    {
+        let prompt_max_length = if generate_options.max_new_tokens.is_some() {
+            Some(MAX_PROMPT_LENGTH)
+        } else {
+            generate_options.max_length
+        };
+        let input_ids = self.prepare_prompt_ids(prompt_texts, prompt_max_length);
+        self.generate_with_encoder_output(
+            input_ids,
+            attention_mask,
+            None,
+            None,
+            None,
+            Some(generate_options),
+            None,
+            None,
+            None,
+            None,
+            false,
+        )
+        .0
+    }
+
+    /// Generate text based on a vector of prompt texts, and returns the encoder hidden states of each prompt
+    /// pooled over its non-padding positions (masked mean). This re-uses the encoder pass computed for the
+    /// generation, for example to compare or index the inputs. Only available for encoder-decoder models.
+    ///
+    /// # Arguments
+    ///
+    /// * `prompt_texts` - `Option<Vec<&str>>` Optional vector of text prompts.
+    /// * `attention_mask` - `Option<Tensor>` Optional attention mask to hide portions of the prompt.
+    ///
+    /// # Returns
+    /// * `Vec<String>` Vector of generated strings based on the prompts of length *number_of_prompts* x *num_return_sequences*.
+    /// * `Tensor` of shape (*number_of_prompts*, *hidden_size*) containing the pooled encoder embeddings of each prompt
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// # fn main() -> anyhow::Result<()> {
+    /// use rust_bert::pipelines::generation_utils::{BartGenerator, LanguageGenerator};
+    ///
+    /// let bart_generator = BartGenerator::new(Default::default())?;
+    /// let (output, encoder_embeddings) = bart_generator.generate_with_encoder_embeddings(
+    ///     Some(vec!["The dog", "The cat was"]),
+    ///     None,
+    ///     None,
+    ///     None,
+    ///     None,
+    /// )?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    fn generate_with_encoder_embeddings<'a, S>(
+        &self,
+        prompt_texts: Option<S>,
+        attention_mask: Option<Tensor>,
+        min_length: impl Into<Option<i64>>,
+        max_length: impl Into<Option<i64>>,
+        decoder_start_token_id: impl Into<Option<i64>>,
+    ) -> Result<(Vec<String>, Tensor), RustBertError>
+    where
+        S: AsRef<[&'a str]>,
+    {
+        if !self.is_encoder_decoder() {
+            return Err(RustBertError::ValueError(
+                "Encoder embeddings are only available for encoder-decoder models".into(),
+            ));
+        }
+        let max_length = max_length.into();
+        let input_ids = self.prepare_prompt_ids(prompt_texts, max_length);
+        let (generated, encoder_output, _, _) = self.generate_with_encoder_output(
+            input_ids,
+            attention_mask,
+            min_length.into(),
+            max_length,
+            decoder_start_token_id.into(),
+            None,
+            None,
+            None,
+            None,
+            None,
+            false,
+        );
+        let (encoder_hidden_states, encoder_attention_mask) = encoder_output.unwrap();
+        let encoder_embeddings =
+            masked_mean_pooling(&encoder_hidden_states, &encoder_attention_mask);
+        let output = generated
+            .into_iter()
+            .map(|generated_sequence| self.get_tokenizer().decode(generated_sequence, true, true))
+            .collect();
+        Ok((output, encoder_embeddings))
+    }
+
+    /// Encodes a vector of prompt texts, returning the encoder output to be re-used by `generate_from_encoded_prompts`.
+    /// Only available for encoder-decoder models.
+    ///
+    /// # Arguments
+    ///
+    /// * `prompt_texts` - `S` vector of text prompts.
+    /// * `attention_mask` - `Option<Tensor>` Optional attention mask to hide portions of the prompt.
+    ///
+    /// # Returns
+    /// * `EncodedPrompts` containing the token ids, attention mask and encoder hidden states of the prompts
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// # fn main() -> anyhow::Result<()> {
+    /// use rust_bert::pipelines::generation_utils::{
+    ///     BartGenerator, GenerateOptions, LanguageGenerator,
+    /// };
+    ///
+    /// let bart_generator = BartGenerator::new(Default::default())?;
+    /// let encoded_prompts = bart_generator.encode_prompts(&["The dog", "The cat was"], None)?;
+    /// let greedy_output = bart_generator.generate_from_encoded_prompts(
+    ///     &encoded_prompts,
+    ///     &GenerateOptions {
+    ///         num_beams: Some(1),
+    ///         ..Default::default()
+    ///     },
+    /// );
+    /// let sampled_output = bart_generator.generate_from_encoded_prompts(
+    ///     &encoded_prompts,
+    ///     &GenerateOptions {
+    ///         do_sample: Some(true),
+    ///         num_beams: Some(1),
+    ///         seed: Some(42),
+    ///         ..Default::default()
+    ///     },
+    /// );
+    /// # Ok(())
+    /// # }
+    /// ```
+    fn encode_prompts<'a, S>(
+        &self,
+        prompt_texts: S,
+        attention_mask: Option<Tensor>,
+    ) -> Result<EncodedPrompts, RustBertError>
+    where
+        S: AsRef<[&'a str]>,
+    {
+        if !self.is_encoder_decoder() {
+            return Err(RustBertError::ValueError(
+                "Prompts can only be encoded for encoder-decoder models".into(),
+            ));
+        }
+        let input_ids = self.prepare_prompt_ids(Some(prompt_texts), None);
+        let attention_mask = match attention_mask {
+            Some(value) => value,
+            None => match self.get_pad_id() {
+                Some(pad_id) => input_ids.ne(*pad_id).to_kind(Int64),
+                None => input_ids.ones_like().to_kind(Int64),
+            },
+        };
+        let hidden_states = no_grad(|| self.encode(&input_ids, Some(&attention_mask)).unwrap());
+        Ok(EncodedPrompts {
+            input_ids,
+            attention_mask,
+            hidden_states,
+        })
+    }
+
+    /// Generate text from prompts encoded with `encode_prompts`, overriding the generation configuration of the model
+    /// with the options provided (see `generate_with_options`). The encoder is not run again.
+    ///
+    /// # Arguments
+    ///
+    /// * `encoded_prompts` - `&EncodedPrompts` encoder output of the prompts
+    /// * `generate_options` - `&GenerateOptions` options overriding the `GenerateConfig` of the model
+    ///
+    /// # Returns
+    /// * `Vec<String>` Vector of generated strings based on the prompts of length *number_of_prompts* x *num_return_sequences*.
+    fn generate_from_encoded_prompts(
+        &self,
+        encoded_prompts: &EncodedPrompts,
+        generate_options: &GenerateOptions,
+    ) -> Vec<String> {
+        self.generate_indices_from_encoded_prompts(encoded_prompts, generate_options)
+            .into_iter()
+            .map(|generated_sequence| self.get_tokenizer().decode(generated_sequence, true, true))
+            .collect()
+    }
+
+    /// Generate token indices without decoding from prompts encoded with `encode_prompts` (see
+    /// `generate_from_encoded_prompts`).
+    ///
+    /// # Arguments
+    ///
+    /// * `encoded_prompts` - `&EncodedPrompts` encoder output of the prompts
+    /// * `generate_options` - `&GenerateOptions` options overriding the `GenerateConfig` of the model
+    ///
+    /// # Returns
+    /// * `Vec<Vec<i64>>` Vector of Vector of generated token indices based on the prompts of length *number_of_prompts* x *num_return_sequences*.
+    fn generate_indices_from_encoded_prompts(
+        &self,
+        encoded_prompts: &EncodedPrompts,
+        generate_options: &GenerateOptions,
+    ) -> Vec<Vec<i64>> {
+        self.generate_with_encoder_output(
+            encoded_prompts.input_ids.shallow_clone(),
+            Some(encoded_prompts.attention_mask.shallow_clone()),
+            None,
+            None,
+            None,
+            Some(generate_options),
+            Some(encoded_prompts),
+            None,
+            None,
+            None,
+            false,
+        )
+        .0
+    }
+
+    /// Generate text based on a vector of prompt texts, biasing the generation towards the tokens present in each
+    /// prompt. The bias provided overrides the `source_copy_bias` of the generation configuration for this call.
+    /// Positive values favour copying from the source (for example to reduce hallucinated entities in abstractive
+    /// summaries), negative values discourage it.
+    ///
+    /// # Arguments
+    ///
+    /// * `prompt_texts` - `Option<Vec<&str>>` Optional vector of text prompts.
+    /// * `attention_mask` - `Option<Tensor>` Optional attention mask to hide portions of the prompt.
+    /// * `source_copy_bias` - `f64` bias added to the scores of the prompt tokens at each generation step
+    ///
+    /// # Returns
+    /// * `Vec<String>` Vector of generated strings based on the prompts of length *number_of_prompts* x *num_return_sequences*.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// # fn main() -> anyhow::Result<()> {
+    /// use rust_bert::pipelines::generation_utils::{BartGenerator, LanguageGenerator};
+    ///
+    /// let bart_generator = BartGenerator::new(Default::default())?;
+    /// let output = bart_generator.generate_with_source_copy_bias(
+    ///     Some(vec!["The Eiffel Tower was completed in 1889 for the World's Fair in Paris."]),
+    ///     None,
+    ///     1.5,
+    /// );
+    /// # Ok(())
+    /// # }
+    /// ```
+    fn generate_with_source_copy_bias<'a, S>(
+        &self,
+        prompt_texts: Option<S>,
+        attention_mask: Option<Tensor>,
+        source_copy_bias: f64,
+    ) -> Vec<String>
+    where
+        S: AsRef<[&'a str]>,
+    {
+        let input_ids = self.prepare_prompt_ids(prompt_texts, None);
+        let (generated, _, _, _) = self.generate_with_encoder_output(
+            input_ids,
+            attention_mask,
+            None,
+            None,
+            None,
+            None,
+            None,
+            Some(source_copy_bias),
+            None,
+            None,
+            false,
+        );
+        generated
+            .into_iter()
+            .map(|generated_sequence| self.get_tokenizer().decode(generated_sequence, true, true))
+            .collect()
+    }
+
+    /// Generate text based on a vector of prompt texts, requiring all the words provided to appear in each output.
+    /// The words are tokenized as if preceded by a space and override the `force_words_ids` of the generation
+    /// configuration for this call. They are enforced by a constrained beam search: the generation configuration must
+    /// use a beam search (`num_beams` > 1) without sampling or beam groups.
+    ///
+    /// # Arguments
+    ///
+    /// * `prompt_texts` - `Option<Vec<&str>>` Optional vector of text prompts.
+    /// * `attention_mask` - `Option<Tensor>` Optional attention mask to hide portions of the prompt.
+    /// * `forced_words` - `&[&str]` words (or phrases) required in the outputs
+    ///
+    /// # Returns
+    /// * `Vec<String>` Vector of generated strings based on the prompts of length *number_of_prompts* x *num_return_sequences*.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// # fn main() -> anyhow::Result<()> {
+    /// use rust_bert::pipelines::generation_utils::{BartGenerator, LanguageGenerator};
+    ///
+    /// let bart_generator = BartGenerator::new(Default::default())?;
+    /// let output = bart_generator.generate_with_forced_words(
+    ///     Some(vec!["The Eiffel Tower was completed in 1889 for the World's Fair in Paris."]),
+    ///     None,
+    ///     &["Paris"],
+    /// )?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    fn generate_with_forced_words<'a, S>(
+        &self,
+        prompt_texts: Option<S>,
+        attention_mask: Option<Tensor>,
+        forced_words: &[&str],
+    ) -> Result<Vec<String>, RustBertError>
+    where
+        S: AsRef<[&'a str]>,
+    {
+        let force_words_ids = forced_words
+            .iter()
+            .map(|word| forced_word_ids(self.get_tokenizer(), word))
+            .filter(|word_ids| !word_ids.is_empty())
+            .collect::<Vec<Vec<i64>>>();
+        let config = PrivateLanguageGenerator::get_config(self);
+        if !force_words_ids.is_empty()
+            & ((config.num_beams < 2) | (config.num_beam_groups > 1) | config.do_sample)
+        {
+            return Err(RustBertError::InvalidConfigurationError(
+                "Forced words require a beam search (num_beams > 1) without sampling or beam groups"
+                    .to_string(),
+            ));
+        }
+        let input_ids = self.prepare_prompt_ids(prompt_texts, None);
+        let (generated, _, _, _) = self.generate_with_encoder_output(
+            input_ids,
+            attention_mask,
+            None,
+            None,
+            None,
+            None,
+            None,
+            None,
+            if force_words_ids.is_empty() {
+                None
+            } else {
+                Some(force_words_ids)
+            },
+            None,
+            false,
+        );
+        Ok(generated
+            .into_iter()
+            .map(|generated_sequence| self.get_tokenizer().decode(generated_sequence, true, true))
+            .collect())
+    }
+
+    /// Generate token indices based on a vector of prompt texts, and returns the usage statistics of the request
+    /// (number of prompt and generated tokens, generation time and throughput).
+    ///
+    /// # Arguments
+    ///
+    /// * `prompt_texts` - `Option<Vec<&str>>` Optional vector of text prompts. An empty prompt to the model may be passed if the model implement a `bos_id`.
+    /// * `attention_mask` - `Option<Tensor>` Optional attention mask to hide portions of the prompt.
+    /// * `min_length` - `impl Into<Option<i64>>` Optional minimum output sequence length
+    /// * `max_length` - `impl Into<Option<i64>>` Optional maximum output sequence length
+    /// * `decoder_start_token_id` - Optional decoder start token id
+    ///
+    /// # Returns
+    /// * `Vec<Vec<i64>>` Vector of Vector of generated token indices based on the prompts of length *number_of_prompts* x *num_return_sequences*.
+    /// * `GenerationUsage` usage statistics of the request
+    fn generate_indices_with_usage<'a, S>(
+        &self,
+        prompt_texts: Option<S>,
+        attention_mask: Option<Tensor>,
+        min_length: impl Into<Option<i64>>,
+        max_length: impl Into<Option<i64>>,
+        decoder_start_token_id: impl Into<Option<i64>>,
+    ) -> (Vec<Vec<i64>>, GenerationUsage)
+    where
+        S: AsRef<[&'a str]>,
+    {
+        let max_length = max_length.into();
+        let input_ids = self.prepare_prompt_ids(prompt_texts, max_length);
+        let (generated, _, usage, _) = self.generate_with_encoder_output(
+            input_ids,
+            attention_mask,
+            min_length.into(),
+            max_length,
+            decoder_start_token_id.into(),
+            None,
+            None,
+            None,
+            None,
+            None,
+            false,
+        );
+        (generated, usage)
+    }
+
+    /// Generate text based on a vector of prompt texts, and returns the usage statistics of the request
+    /// (number of prompt and generated tokens, generation time and throughput).
+    ///
+    /// # Arguments
+    ///
+    /// * `prompt_texts` - `Option<Vec<&str>>` Optional vector of text prompts. An empty prompt to the model may be passed if the model implement a `bos_id`.
+    /// * `attention_mask` - `Option<Tensor>` Optional attention mask to hide portions of the prompt.
+    /// * `min_length` - `impl Into<Option<i64>>` Optional minimum output sequence length
+    /// * `max_length` - `impl Into<Option<i64>>` Optional maximum output sequence length
+    /// * `decoder_start_token_id` - Optional decoder start token id
+    ///
+    /// # Returns
+    /// * `Vec<String>` Vector of generated strings based on the prompts of length *number_of_prompts* x *num_return_sequences*.
+    /// * `GenerationUsage` usage statistics of the request
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// # fn main() -> anyhow::Result<()> {
+    /// use rust_bert::pipelines::generation_utils::{GPT2Generator, GenerateConfig, LanguageGenerator};
+    ///
+    /// let generate_config = GenerateConfig {
+    ///     echo_prompt: false,
+    ///     ..Default::default()
+    /// };
+    /// let gpt2_generator = GPT2Generator::new(generate_config)?;
+    /// let (output, usage) =
+    ///     gpt2_generator.generate_with_usage(Some(vec!["The dog"]), None, None, None, None);
+    /// println!(
+    ///     "{} prompt tokens, {} generated tokens ({:.1} tokens/s)",
+    ///     usage.prompt_tokens, usage.generated_tokens, usage.tokens_per_second
+    /// );
+    /// # Ok(())
+    /// # }
+    /// ```
+    fn generate_with_usage<'a, S>(
+        &self,
+        prompt_texts: Option<S>,
+        attention_mask: Option<Tensor>,
+        min_length: impl Into<Option<i64>>,
+        max_length: impl Into<Option<i64>>,
+        decoder_start_token_id: impl Into<Option<i64>>,
+    ) -> (Vec<String>, GenerationUsage)
+    where
+        S: AsRef<[&'a str]>,
+    {
+        let (generated, usage) = self.generate_indices_with_usage(
+            prompt_texts,
+            attention_mask,
+            min_length,
+            max_length,
+            decoder_start_token_id,
+        );
+        let output = generated
+            .into_iter()
+            .map(|generated_sequence| self.get_tokenizer().decode(generated_sequence, true, true))
+            .collect();
+        (output, usage)
+    }
+
+    /// Generate token indices based on a vector of prompt texts, and returns the log-probability under the model of
+    /// each generated token (see `generate_with_scores`).
+    ///
+    /// # Arguments
+    ///
+    /// * `prompt_texts` - `Option<Vec<&str>>` Optional vector of text prompts. An empty prompt to the model may be passed if the model implement a `bos_id`.
+    /// * `attention_mask` - `Option<Tensor>` Optional attention mask to hide portions of the prompt.
+    /// * `min_length` - `impl Into<Option<i64>>` Optional minimum output sequence length
+    /// * `max_length` - `impl Into<Option<i64>>` Optional maximum output sequence length
+    /// * `decoder_start_token_id` - Optional decoder start token id
+    ///
+    /// # Returns
+    /// * `Vec<Vec<i64>>` Vector of Vector of generated token indices based on the prompts of length *number_of_prompts* x *num_return_sequences*.
+    /// * `Vec<Vec<f64>>` Log-probabilities of the generated tokens of each sequence (excluding the prompt)
+    fn generate_indices_with_scores<'a, S>(
+        &self,
+        prompt_texts: Option<S>,
+        attention_mask: Option<Tensor>,
+        min_length: impl Into<Option<i64>>,
+        max_length: impl Into<Option<i64>>,
+        decoder_start_token_id: impl Into<Option<i64>>,
+    ) -> (Vec<Vec<i64>>, Vec<Vec<f64>>)
+    where
+        S: AsRef<[&'a str]>,
+    {
+        let max_length = max_length.into();
+        let input_ids = self.prepare_prompt_ids(prompt_texts, max_length);
+        let (generated, _, _, token_scores) = self.generate_with_encoder_output(
+            input_ids,
+            attention_mask,
+            min_length.into(),
+            max_length,
+            decoder_start_token_id.into(),
+            None,
+            None,
+            None,
+            None,
+            None,
+            true,
+        );
+        (generated, token_scores.unwrap_or_default())
+    }
+
+    /// Generate text based on a vector of prompt texts, and returns the log-probability of each generated token with
+    /// the sequence scores, e.g. to rank candidates or discard low-confidence outputs. The token log-probabilities are
+    /// the ones of the model, before the adjustments of the generation options (temperature, repetition penalties or
+    /// restricted tokens), and are computed after the generation with an additional decoding pass.
+    ///
+    /// # Arguments
+    ///
+    /// * `prompt_texts` - `Option<Vec<&str>>` Optional vector of text prompts. An empty prompt to the model may be passed if the model implement a `bos_id`.
+    /// * `attention_mask` - `Option<Tensor>` Optional attention mask to hide portions of the prompt.
+    /// * `min_length` - `impl Into<Option<i64>>` Optional minimum output sequence length
+    /// * `max_length` - `impl Into<Option<i64>>` Optional maximum output sequence length
+    /// * `decoder_start_token_id` - Optional decoder start token id
+    ///
+    /// # Returns
+    /// * `Vec<ScoredGeneration>` Vector of generated sequences with their scores, of length *number_of_prompts* x *num_return_sequences*.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// # fn main() -> anyhow::Result<()> {
+    /// use rust_bert::pipelines::generation_utils::{GPT2Generator, GenerateConfig, LanguageGenerator};
+    ///
+    /// let generate_config = GenerateConfig {
+    ///     num_beams: 4,
+    ///     num_return_sequences: 4,
+    ///     echo_prompt: false,
+    ///     ..Default::default()
+    /// };
+    /// let gpt2_generator = GPT2Generator::new(generate_config)?;
+    /// let output = gpt2_generator.generate_with_scores(Some(vec!["The dog"]), None, None, None, None);
+    /// let confident = output
+    ///     .iter()
+    ///     .filter(|generation| generation.normalized_score > -2.0)
+    ///     .collect::<Vec<_>>();
+    /// # Ok(())
+    /// # }
+    /// ```
+    fn generate_with_scores<'a, S>(
         &self,
         prompt_texts: Option<S>,
         attention_mask: Option<Tensor>,
         min_length: impl Into<Option<i64>>,
         max_length: impl Into<Option<i64>>,
         decoder_start_token_id: impl Into<Option<i64>>,
-    ) -> Vec<String>
+    ) -> Vec<ScoredGeneration>
     where
         S: AsRef<[&'a str]>,
     {
-        let generated = self.generate_indices(
+        let (generated, token_scores) = self.generate_indices_with_scores(
             prompt_texts,
             attention_mask,
             min_length,
             max_length,
             decoder_start_token_id,
         );
-        let mut output = Vec::with_capacity(generated.len());
-        for generated_sequence in generated {
-            output.push(self.get_tokenizer().decode(generated_sequence, true, true));
+        let length_penalty = PrivateLanguageGenerator::get_config(self).length_penalty;
+        generated
+            .into_iter()
+            .zip(token_scores)
+            .map(|(generated_sequence, token_scores)| {
+                ScoredGeneration::from_token_scores(
+                    self.get_tokenizer().decode(generated_sequence, true, true),
+                    token_scores,
+                    length_penalty,
+                )
+            })
+            .collect()
+    }
+
+    /// Generate token indices from token ids, calling `callback` with each token as soon as it is generated (e.g. to
+    /// display the output of an interactive application as it is produced). Streaming is only available without beam
+    /// search, the best beams being only known at the end of the generation.
+    ///
+    /// # Arguments
+    ///
+    /// * `input_ids` - `Tensor` of shape (*batch_size*, *sequence_length*) with the token ids of the prompts
+    /// * `attention_mask` - `Option<Tensor>` Optional attention mask to hide portions of the prompt.
+    /// * `min_length` - `impl Into<Option<i64>>` Optional minimum output sequence length
+    /// * `max_length` - `impl Into<Option<i64>>` Optional maximum output sequence length
+    /// * `decoder_start_token_id` - Optional decoder start token id
+    /// * `callback` - Closure called with each `StreamedToken`, until the end of its sequence (included)
+    ///
+    /// # Returns
+    /// * `Vec<Vec<i64>>` Vector of Vector of generated token indices based on the prompts of length *number_of_prompts* x *num_return_sequences*.
+    /// * `GenerationUsage` usage statistics of the request
+    fn generate_from_ids_with_callback<F>(
+        &self,
+        input_ids: Tensor,
+        attention_mask: Option<Tensor>,
+        min_length: impl Into<Option<i64>>,
+        max_length: impl Into<Option<i64>>,
+        decoder_start_token_id: impl Into<Option<i64>>,
+        mut callback: F,
+    ) -> Result<(Vec<Vec<i64>>, GenerationUsage), RustBertError>
+    where
+        F: FnMut(StreamedToken),
+    {
+        if PrivateLanguageGenerator::get_config(self).num_beams > 1 {
+            return Err(RustBertError::ValueError(
+                "Token streaming is not available with beam search (num_beams > 1)".into(),
+            ));
         }
-        output
+        let tokenizer = self.get_tokenizer();
+        let mut streamer = TokenStreamer::new();
+        let mut token_callback = |sequence_index: usize, token_id: i64| {
+            callback(streamer.push(sequence_index, token_id, |token_ids| {
+                tokenizer.decode(token_ids, true, false)
+            }))
+        };
+        let (generated, _, usage, _) = self.generate_with_encoder_output(
+            input_ids,
+            attention_mask,
+            min_length.into(),
+            max_length.into(),
+            decoder_start_token_id.into(),
+            None,
+            None,
+            None,
+            None,
+            Some(&mut token_callback),
+            false,
+        );
+        Ok((generated, usage))
     }
 
-    /// Generate token indices without decoding (useful for token-level operations before returning final text or as validation step during training).
+    /// Generate text based on a vector of prompt texts, calling `callback` with each token as soon as it is
+    /// generated. Streaming is only available without beam search.
     ///
     /// # Arguments
     ///
     /// * `prompt_texts` - `Option<Vec<&str>>` Optional vector of text prompts. An empty prompt to the model may be passed if the model implement a `bos_id`.
     /// * `attention_mask` - `Option<Tensor>` Optional attention mask to hide portions of the prompt.
+    /// * `min_length` - `impl Into<Option<i64>>` Optional minimum output sequence length
+    /// * `max_length` - `impl Into<Option<i64>>` Optional maximum output sequence length
+    /// * `decoder_start_token_id` - Optional decoder start token id
+    /// * `callback` - Closure called with each `StreamedToken`, until the end of its sequence (included)
     ///
     /// # Returns
-    /// * `Vec<Vec<i64>>` Vector of Vector of generated token indices based on the prompts of length *number_of_prompts* x *num_return_sequences*.
+    /// * `Vec<String>` Vector of generated strings based on the prompts of length *number_of_prompts* x *num_return_sequences*.
     ///
     /// # Example
     ///
     /// ```no_run
-    /// # use std::path::PathBuf;
-    /// # use tch::Device;
     /// # fn main() -> anyhow::Result<()> {
-    /// use rust_bert::pipelines::generation_utils::{
-    ///     GPT2Generator, GenerateConfig, LanguageGenerator,
-    /// };
-    /// # let mut home: PathBuf = dirs::home_dir().unwrap();
-    /// # home.push("rustbert");
-    /// # home.push("gpt2");
-    /// # let config_path = &home.as_path().join("config.json");
-    /// # let vocab_path = &home.as_path().join("vocab.txt");
-    /// # let merges_path = &home.as_path().join("merges.txt");
-    /// # let weights_path = &home.as_path().join("model.ot");
-    /// let device = Device::cuda_if_available();
+    /// use rust_bert::pipelines::generation_utils::{GPT2Generator, GenerateConfig, LanguageGenerator};
+    /// use std::io::Write;
+    ///
     /// let generate_config = GenerateConfig {
-    ///     max_length: 30,
-    ///     do_sample: true,
-    ///     num_beams: 5,
-    ///     temperature: 1.1,
-    ///     num_return_sequences: 3,
+    ///     num_beams: 1,
+    ///     echo_prompt: false,
     ///     ..Default::default()
     /// };
-    /// let mut gpt2_generator = GPT2Generator::new(generate_config)?;
-    /// let input_context = "The dog";
-    /// let second_input_context = "The cat was";
-    /// let attention_mask = None;
-    /// let min_length = 32;
-    /// let max_length = 128;
-    /// let decoder_start_token_id = None;
-    ///
-    /// let output = gpt2_generator.generate_indices(
-    ///     Some(vec![input_context, second_input_context]),
-    ///     attention_mask,
-    ///     min_length,
-    ///     max_length,
-    ///     decoder_start_token_id,
-    /// );
+    /// let gpt2_generator = GPT2Generator::new(generate_config)?;
+    /// let output = gpt2_generator.generate_with_callback(
+    ///     Some(vec!["The dog"]),
+    ///     None,
+    ///     None,
+    ///     None,
+    ///     None,
+    ///     |token| {
+    ///         print!("{}", token.text);
+    ///         std::io::stdout().flush().unwrap();
+    ///     },
+    /// )?;
     /// # Ok(())
     /// # }
     /// ```
-    fn generate_indices<'a, S>(
+    fn generate_with_callback<'a, S, F>(
         &self,
         prompt_texts: Option<S>,
         attention_mask: Option<Tensor>,
         min_length: impl Into<Option<i64>>,
         max_length: impl Into<Option<i64>>,
         decoder_start_token_id: impl Into<Option<i64>>,
-    ) -> Vec<Vec<i64>>
+        callback: F,
+    ) -> Result<Vec<String>, RustBertError>
     where
         S: AsRef<[&'a str]>,
+        F: FnMut(StreamedToken),
     {
-        let eos_token_ids = PrivateLanguageGenerator::get_eos_ids(self).clone();
-
-        let config = PrivateLanguageGenerator::get_config(self);
-        let max_length = max_length.into().unwrap_or(config.max_length);
-        let encoding_max_len = if self.is_encoder_decoder() {
-            1024i64
-        } else {
-            max_length
-        };
-        let pad_token_id = match self.get_pad_id() {
-            Some(value) => Some(*value),
-            None => match &eos_token_ids {
-                Some(eos_ids) => Some(eos_ids[0]),
-                None => None,
-            },
-        };
-
-        let input_ids = match prompt_texts {
-            Some(text) => self.encode_prompt_text(text, encoding_max_len, pad_token_id),
-            None => match self.get_bos_id() {
-                Some(bos_id) => {
-                    Tensor::ones(&[1, 1], (Int64, self.get_var_store().device())) * *bos_id
-                }
-                None => panic!(
-                    "A model with a BOS token must be used to start generation with an empty input"
-                ),
-            },
-        };
-        self.generate_from_ids_and_past(
+        let max_length = max_length.into();
+        let input_ids = self.prepare_prompt_ids(prompt_texts, max_length);
+        let (generated, _) = self.generate_from_ids_with_callback(
             input_ids,
             attention_mask,
             min_length,
             max_length,
             decoder_start_token_id,
-        )
+            callback,
+        )?;
+        Ok(generated
+            .into_iter()
+            .map(|generated_sequence| self.get_tokenizer().decode(generated_sequence, true, true))
+            .collect())
     }
 
-    fn generate_from_ids_and_past(
-        &self,
-        input_ids: Tensor,
-        attention_mask: Option<Tensor>,
-        min_length: impl Into<Option<i64>>,
-        max_length: impl Into<Option<i64>>,
-        decoder_start_token_id: impl Into<Option<i64>>,
-    ) -> Vec<Vec<i64>> {
-        let eos_token_ids = PrivateLanguageGenerator::get_eos_ids(self).clone();
-
-        let config = PrivateLanguageGenerator::get_config(self);
-        let do_sample = config.do_sample;
-        let num_return_sequences = config.num_return_sequences;
-        let num_beams = config.num_beams;
-        let min_length = min_length.into().unwrap_or(config.min_length);
-        let max_length = max_length.into().unwrap_or(config.max_length);
-        let early_stopping = config.early_stopping;
-        let temperature = config.temperature;
-        let top_k = config.top_k;
-        let top_p = config.top_p;
-        let repetition_penalty = config.repetition_penalty;
-        let length_penalty = config.length_penalty;
-        let no_repeat_ngram_size = config.no_repeat_ngram_size;
-
-        let pad_token_id = match self.get_pad_id() {
-            Some(value) => Some(*value),
-            None => match &eos_token_ids {
-                Some(eos_ids) => Some(eos_ids[0]),
-                None => None,
-            },
-        };
-
-        let input_ids_len = *input_ids.size().last().unwrap();
-        let cur_len = if !self.is_encoder_decoder() {
-            *input_ids.size().last().unwrap()
-        } else {
-            1
-        };
-        let batch_size = *input_ids.size().first().unwrap();
-
-        let (effective_batch_size, effective_batch_mult) = match do_sample {
-            true => (
-                batch_size * num_return_sequences as i64,
-                num_return_sequences as i64,
-            ),
-            false => (batch_size, 1),
-        };
-
-        let attention_mask = match attention_mask {
-            Some(value) => value,
-            None => match self.get_pad_id() {
-                Some(pad_id) => input_ids.ne(*pad_id).to_kind(Int64),
-                None => input_ids.ones_like().to_kind(Int64),
-            },
-        };
-
-        let encoder_outputs = if self.is_encoder_decoder() {
-            let encoder_outputs = self.encode(&input_ids, Some(&attention_mask)).unwrap();
-            let expanded_batch_indices = Tensor::arange(batch_size, (Int64, input_ids.device()))
-                .view((-1, 1))
-                .repeat(&[1, num_beams as i64 * effective_batch_mult])
-                .view(-1);
-            Some(encoder_outputs.index_select(0, &expanded_batch_indices))
-        } else {
-            None
-        };
-
-        let (input_ids, attention_mask) = if !self.is_encoder_decoder() {
-            if (num_return_sequences > 1) | (num_beams > 1) {
-                (
-                    input_ids
-                        .unsqueeze(1)
-                        .expand(
-                            &[batch_size, effective_batch_mult * num_beams as i64, cur_len],
-                            true,
-                        )
-                        .contiguous()
-                        .view((effective_batch_size * num_beams as i64, cur_len)),
-                    attention_mask
-                        .unsqueeze(1)
-                        .expand(
-                            &[batch_size, effective_batch_mult * num_beams as i64, cur_len],
-                            true,
-                        )
-                        .contiguous()
-                        .view((effective_batch_size * num_beams as i64, cur_len)),
-                )
-            } else {
-                (input_ids, attention_mask)
-            }
-        } else {
-            let decoder_start_token_id = decoder_start_token_id.into().unwrap_or_else(|| {
-                self.get_decoder_start_id()
-                    .expect("decoder start id must be specified for encoder decoders")
-            });
-            let input_ids = Tensor::full(
-                &[effective_batch_size * num_beams as i64, 1],
-                decoder_start_token_id,
-                (Int64, input_ids.device()),
-            );
-            let attention_mask = if (num_return_sequences > 1) | (num_beams > 1) {
-                attention_mask
-                    .unsqueeze(1)
-                    .expand(
-                        &[
-                            batch_size,
-                            effective_batch_mult * num_beams as i64,
-                            input_ids_len,
-                        ],
-                        true,
-                    )
-                    .contiguous()
-                    .view((effective_batch_size * num_beams as i64, input_ids_len))
-            } else {
-                attention_mask
-            };
-            (input_ids, attention_mask)
-        };
-
-        let gen_opt = GenerateOptions {
-            min_length,
-            max_length,
-            do_sample,
-            temperature,
-            top_k,
-            top_p,
-            repetition_penalty,
-            no_repeat_ngram_size,
-            pad_token_id,
-            eos_token_ids,
-            num_return_sequences,
-            early_stopping,
-            num_beams,
-            length_penalty,
-        };
-
-        let decoded = no_grad(|| {
-            if num_beams > 1 {
-                self.generate_beam_search(
-                    input_ids,
-                    encoder_outputs,
-                    cur_len,
-                    effective_batch_size,
-                    attention_mask,
-                    gen_opt,
-                )
-            } else {
-                self.generate_no_beam_search(
-                    input_ids,
-                    encoder_outputs,
-                    cur_len,
-                    effective_batch_size,
-                    attention_mask,
-                    gen_opt,
-                )
-            }
-        });
-        let num_sequences = *decoded.size().first().unwrap();
-        let mut output_ids = Vec::with_capacity(num_sequences as usize);
-        for sequence_index in 0..num_sequences {
-            let sequence_output_ids = decoded
-                .as_ref()
-                .get(sequence_index)
-                .iter::<i64>()
-                .unwrap()
-                .collect::<Vec<i64>>();
-            output_ids.push(sequence_output_ids.clone());
-        }
-        output_ids
+    /// Replaces the weights of the generator model with the weights from the resource provided.
+    /// The weights must match the architecture of the current model (same variable names and shapes).
+    /// The tokenizer and generation configuration are left unchanged. The existing weights are kept if the
+    /// new weights cannot be loaded.
+    ///
+    /// # Arguments
+    ///
+    /// * `weights_resource` - `Resource` pointing to the new model weights (e.g. model.ot)
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// # fn main() -> anyhow::Result<()> {
+    /// use rust_bert::pipelines::generation_utils::{GPT2Generator, LanguageGenerator};
+    /// use rust_bert::resources::{LocalResource, Resource};
+    /// use std::path::PathBuf;
+    ///
+    /// let mut gpt2_generator = GPT2Generator::new(Default::default())?;
+    /// let new_weights = Resource::Local(LocalResource {
+    ///     local_path: PathBuf::from("path/to/fine_tuned/model.ot"),
+    /// });
+    /// gpt2_generator.reload_weights(&new_weights)?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    fn reload_weights(&mut self, weights_resource: &Resource) -> Result<(), RustBertError> {
+        reload_var_store(self.get_var_store_mut()?, weights_resource)
     }
 }
 
-#[derive(Debug)]
-struct BeamHypotheses {
-    max_length: i64,
-    length_penalty: f64,
-    early_stopping: bool,
-    num_beams: i64,
-    beams: Vec<(f64, Tensor)>,
-    worst_score: f64,
-}
-
-impl Clone for BeamHypotheses {
-    fn clone(&self) -> Self {
-        BeamHypotheses {
-            max_length: self.max_length,
-            length_penalty: self.length_penalty,
-            early_stopping: self.early_stopping,
-            num_beams: self.num_beams,
-            beams: self
-                .beams
-                .iter()
-                .map(|(score, tensor)| (*score, tensor.copy()))
-                .collect_vec(),
-            worst_score: self.worst_score,
-        }
+// Token ids of a word following a space in the middle of a text. The standalone word boundary token of
+// SentencePiece tokenizers is dropped, so that the word can also follow a sentence piece with a boundary.
+fn forced_word_ids(tokenizer: &TokenizerOption, word: &str) -> Vec<i64> {
+    let mut tokens = tokenizer.tokenize(&format!(" {}", word.trim()));
+    if tokens.first().map_or(false, |token| token == "\u{2581}") {
+        tokens.remove(0);
     }
+    tokenizer.convert_tokens_to_ids(&tokens)
 }
 
-impl BeamHypotheses {
-    fn new(
-        num_beams: i64,
-        max_length: i64,
-        length_penalty: f64,
-        early_stopping: bool,
-    ) -> BeamHypotheses {
-        BeamHypotheses {
-            max_length: max_length - 1,
-            length_penalty,
-            early_stopping,
-            num_beams,
-            beams: Vec::with_capacity(num_beams as usize + 1),
-            worst_score: 1e9f64,
+fn masked_mean_pooling(hidden_states: &Tensor, attention_mask: &Tensor) -> Tensor {
+    let mask = attention_mask.unsqueeze(-1).to_kind(hidden_states.kind());
+    (hidden_states * &mask).sum1(&[1], false, hidden_states.kind())
+        / mask.sum1(&[1], false, hidden_states.kind()).clamp_min(1.0)
+}
+
+/// Draws the sampled tokens from a xorshift generator, making the sampled outputs independent from the `tch` random
+/// state (shared by all threads)
+struct SeededSampler {
+    state: u64,
+}
+
+impl SeededSampler {
+    fn new(seed: u64) -> SeededSampler {
+        SeededSampler {
+            state: (seed ^ 0x9E37_79B9_7F4A_7C15) | 1,
         }
     }
 
-    fn len(&self) -> i64 {
-        self.beams.len() as i64
+    fn next_uniform(&mut self) -> f64 {
+        self.state ^= self.state >> 12;
+        self.state ^= self.state << 25;
+        self.state ^= self.state >> 27;
+        let value = self.state.wrapping_mul(0x2545_F491_4F6C_DD1D);
+        (value >> 11) as f64 / (1u64 << 53) as f64
     }
 
-    fn add(&mut self, hypothesis: Tensor, sum_log_probabilities: f64) {
-        let score =
-            sum_log_probabilities / ((hypothesis.size()[0] as f64).powf(self.length_penalty));
-        if (self.len() < self.num_beams) | (score > self.worst_score) {
-            self.beams.push((score, hypothesis));
-            if self.len() > self.num_beams {
-                let (worst_score_position, _) = self
-                    .beams
-                    .iter()
-                    .enumerate()
-                    .min_by_key(|(_, (score, _))| OrderedFloat(*score))
-                    .unwrap();
-                let _ = self.beams.remove(worst_score_position);
+    /// Samples `num_samples` indices without replacement from each row of the probabilities, of shape
+    /// (*batch size*, *number of classes*), returning a tensor of shape (*batch size*, *num_samples*)
+    fn multinomial(&mut self, probabilities: &Tensor, num_samples: i64) -> Tensor {
+        let batch_size = probabilities.size()[0];
+        let cpu_probabilities = probabilities.to_kind(Kind::Double).to(Device::Cpu);
+        let mut samples = Vec::with_capacity((batch_size * num_samples) as usize);
+        for row_index in 0..batch_size {
+            let mut row = Vec::<f64>::from(cpu_probabilities.get(row_index));
+            for _ in 0..num_samples {
+                let mut threshold = self.next_uniform() * row.iter().sum::<f64>();
+                let mut sample = 0;
+                for (index, probability) in row.iter().enumerate() {
+                    if *probability > 0.0 {
+                        sample = index;
+                        if threshold < *probability {
+                            break;
+                        }
+                        threshold -= probability;
+                    }
+                }
+                row[sample] = 0.0;
+                samples.push(sample as i64);
             }
-            self.worst_score = self
-                .beams
-                .iter()
-                .min_by_key(|(score, _)| OrderedFloat(*score))
-                .unwrap()
-                .0;
-        }
-    }
-
-    fn is_done(&self, best_sum_log_probabilities: f64, current_length: i64) -> bool {
-        if self.len() < self.num_beams {
-            false
-        } else if self.early_stopping {
-            true
-        } else {
-            self.worst_score
-                >= best_sum_log_probabilities / (current_length as f64).powf(self.length_penalty)
         }
+        Tensor::of_slice(&samples)
+            .view((batch_size, num_samples))
+            .to(probabilities.device())
     }
 }
 
@@ -3056,7 +6546,7 @@ pub trait LMHeadModel {
     /// # let vocab_path = Path::new("path/to/vocab.txt");
     /// # let device = Device::Cpu;
     /// # let vs = nn::VarStore::new(device);
-    /// # let config = Gpt2Config::from_file(config_path);
+    /// # let config = Gpt2Config::try_from_file(config_path)?;
     /// # let mut gpt2_model: GPT2LMHeadModel = GPT2LMHeadModel::new(&vs.root(), &config);
     /// let (batch_size, sequence_length, past_sequence_length) = (64, 128, 56);
     /// let input_tensor = Tensor::rand(&[batch_size, sequence_length], (Int64, device));
@@ -3108,6 +6598,57 @@ pub trait LMHeadModel {
     ) -> Result<LMModelOutput, RustBertError>;
 }
 
+/// # Encoder trait
+/// Shared trait for models computing encoder hidden states for a batch of inputs (e.g. the encoder of
+/// encoder-decoder models used for generation).
+pub trait EncoderOnlyModel {
+    /// Encodes a batch of inputs
+    ///
+    /// # Arguments
+    ///
+    /// * `input_ids` - Input tensor of shape (*batch size*, *source_sequence_length*).
+    /// * `attention_mask` - Optional attention mask of shape (*batch size*, *source_sequence_length*) for the encoder positions. Positions with a mask with value 0 will be masked.
+    ///
+    /// # Returns
+    ///
+    /// * `Tensor` of shape (*batch size*, *source_sequence_length*, *hidden_size*) containing the encoder hidden states
+    fn encode(&self, input_ids: &Tensor, attention_mask: Option<&Tensor>) -> Tensor;
+}
+
+/// # Encoder-decoder generation trait
+/// Interface required from an encoder-decoder model to be used for generation. It is implemented by the
+/// encoder-decoder models of the crate, and can be implemented by custom architectures to generate text using
+/// a `Seq2SeqGenerator`.
+pub trait Seq2SeqGenerationModel: EncoderOnlyModel {
+    /// Runs a decoding step, returning the logits for the next token and the updated cache
+    ///
+    /// # Arguments
+    ///
+    /// * `decoder_input_ids` - Tokens generated so far, of shape (*batch size*, *current_length*). Models making use of the cache may only process the last position.
+    /// * `encoder_outputs` - Encoder hidden states of shape (*batch size*, *source_sequence_length*, *hidden_size*)
+    /// * `attention_mask` - Optional attention mask of shape (*batch size*, *source_sequence_length*) for the encoder positions.
+    /// * `cache` - `Cache` returned by the previous decoding step (`Cache::None` for the first step)
+    ///
+    /// # Returns
+    ///
+    /// * `LMModelOutput` containing the logits of shape (*batch size*, *current_length* or 1, *vocab_size*) and the updated cache
+    fn decode_step(
+        &self,
+        decoder_input_ids: &Tensor,
+        encoder_outputs: &Tensor,
+        attention_mask: Option<&Tensor>,
+        cache: Cache,
+    ) -> Result<LMModelOutput, RustBertError>;
+
+    /// Reorders the cache along its batch dimension following the selection of the beams
+    ///
+    /// # Arguments
+    ///
+    /// * `cache` - `Cache` to reorder in place
+    /// * `beam_indices` - Tensor of shape (*batch size* x *num_beams*) with the index of the beam each new hypothesis extends
+    fn reorder_cache(&self, cache: &mut Cache, beam_indices: &Tensor);
+}
+
 /// Container holding a language model output for generation tasks
 pub struct LMModelOutput {
     /// Logits for each vocab item and position
@@ -3115,3 +6656,17 @@ pub struct LMModelOutput {
     /// cached state for improved efficiency during decoding
     pub cache: Cache,
 }
+
+impl ModelOutput for LMModelOutput {
+    fn hidden_states(&self) -> Option<Vec<&Tensor>> {
+        None
+    }
+
+    fn attentions(&self) -> Option<Vec<&Tensor>> {
+        None
+    }
+
+    fn logits(&self) -> Option<&Tensor> {
+        Some(&self.lm_logits)
+    }
+}
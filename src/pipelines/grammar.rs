@@ -0,0 +1,678 @@
+// Copyright 2021 Guillaume Becquin
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//     http://www.apache.org/licenses/LICENSE-2.0
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! # Grammar-constrained generation
+//! Context-free grammars restricting the text produced by the generation pipelines. When a `Grammar` is set in the
+//! generation configuration (`grammar` field of `GenerateConfig`, `TextGenerationConfig`, `SummarizationConfig`...),
+//! the tokens that cannot extend the text generated so far into a valid prefix of the grammar are masked at each
+//! step, and the end of sequence token is only allowed once the text is complete. The generated sequences are
+//! therefore guaranteed to be parsed by the grammar, provided that `max_length` leaves enough room to complete them.
+//!
+//! Grammars are written in a BNF-like syntax, close to the GBNF format of llama.cpp:
+//! - rules are defined as `name ::= alternative | alternative`, the rule `root` (or the first rule) being the start rule
+//! - literals are quoted (`"true"`), with the escapes `\"`, `\\`, `\n`, `\r`, `\t` and `\uXXXX`
+//! - character classes are written in brackets (`[a-zA-Z_]`), negated with `^` (`[^"]`) and `.` matches any character
+//! - parentheses group sequences, and the `?`, `*` and `+` operators repeat the previous element
+//! - comments start with `#`
+//!
+//! Grammars matching JSON values can be built with `Grammar::json` (any value) or `Grammar::from_json_schema`, following
+//! a `JsonSchema` of the structured output pipeline. The state of the grammar is tracked incrementally by an Earley
+//! recognizer over the characters of the decoded tokens.
+//!
+//! ```no_run
+//! # fn main() -> anyhow::Result<()> {
+//! use rust_bert::pipelines::grammar::Grammar;
+//! use rust_bert::pipelines::structured_output::JsonSchema;
+//! use rust_bert::pipelines::text_generation::{TextGenerationConfig, TextGenerationModel};
+//! use std::sync::Arc;
+//!
+//! let schema = JsonSchema::object(vec![
+//!     ("city", JsonSchema::String),
+//!     ("days", JsonSchema::Integer),
+//! ]);
+//! let model = TextGenerationModel::new(TextGenerationConfig {
+//!     max_length: 64,
+//!     grammar: Some(Arc::new(Grammar::from_json_schema(&schema))),
+//!     echo_prompt: false,
+//!     ..Default::default()
+//! })?;
+//! let output = model.generate(&["Weather tool call for Paris for the next 3 days:"], None);
+//!
+//! let yes_no = Grammar::new(r#"root ::= " "? ("yes" | "no") "."?"#)?;
+//! assert!(yes_no.accepts(" yes."));
+//! # Ok(())
+//! # }
+//! ```
+
+use crate::common::error::RustBertError;
+use crate::pipelines::structured_output::JsonSchema;
+use std::collections::HashMap;
+
+#[derive(Debug, Clone, PartialEq)]
+struct CharClass {
+    ranges: Vec<(char, char)>,
+    negated: bool,
+}
+
+impl CharClass {
+    fn single(c: char) -> CharClass {
+        CharClass {
+            ranges: vec![(c, c)],
+            negated: false,
+        }
+    }
+
+    fn matches(&self, c: char) -> bool {
+        self.ranges
+            .iter()
+            .any(|(start, end)| (*start <= c) & (c <= *end))
+            != self.negated
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Symbol {
+    Rule(usize),
+    Chars(CharClass),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct Rule {
+    lhs: usize,
+    rhs: Vec<Symbol>,
+}
+
+#[derive(Debug, Clone)]
+/// # Context-free grammar over characters
+pub struct Grammar {
+    rules: Vec<Rule>,
+    rules_by_symbol: Vec<Vec<usize>>,
+    nullable: Vec<bool>,
+    start: usize,
+}
+
+impl Grammar {
+    /// Parses a grammar from its definition (see the module documentation for the syntax)
+    ///
+    /// # Arguments
+    ///
+    /// * `definition` - rules of the grammar
+    ///
+    /// # Returns
+    ///
+    /// * `Grammar`, or a `RustBertError::ValueError` if the definition is invalid or refers to undefined rules
+    pub fn new(definition: &str) -> Result<Grammar, RustBertError> {
+        GrammarParser::new(definition).parse()
+    }
+
+    /// Returns a grammar matching any JSON value, optionally preceded by whitespace
+    pub fn json() -> Grammar {
+        Grammar::new(&format!("root ::= ws value\n{}", JSON_RULES)).unwrap()
+    }
+
+    /// Returns a grammar matching the JSON values following a schema, optionally preceded by whitespace. The fields of
+    /// the objects are generated in the order of the schema, and the optional fields can be omitted.
+    pub fn from_json_schema(schema: &JsonSchema) -> Grammar {
+        let mut compiler = SchemaCompiler { rules: vec![] };
+        let root = compiler.compile(schema);
+        let mut definition = format!("root ::= ws {}\n", root);
+        for rule in compiler.rules {
+            definition.push_str(&rule);
+            definition.push('\n');
+        }
+        definition.push_str(JSON_RULES);
+        Grammar::new(&definition).unwrap()
+    }
+
+    /// Returns true if the complete text is matched by the grammar
+    pub fn accepts(&self, text: &str) -> bool {
+        let mut chart = self.chart();
+        chart.push_str(text) && chart.is_complete()
+    }
+
+    /// Returns true if the text can be extended into a text matched by the grammar
+    pub fn accepts_prefix(&self, text: &str) -> bool {
+        self.chart().push_str(text)
+    }
+
+    pub(crate) fn chart(&self) -> GrammarChart<'_> {
+        let initial_items = self.rules_by_symbol[self.start]
+            .iter()
+            .map(|&rule| Item {
+                rule,
+                dot: 0,
+                origin: 0,
+            })
+            .collect();
+        let mut chart = GrammarChart {
+            grammar: self,
+            sets: vec![],
+        };
+        let initial_set = chart.close(initial_items);
+        chart.sets.push(initial_set);
+        chart
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+struct Item {
+    rule: usize,
+    dot: usize,
+    origin: usize,
+}
+
+/// Earley chart of a grammar, with one set of items per character pushed. Nullable rules are completed at prediction
+/// time (Aycock and Horspool, 2002).
+pub(crate) struct GrammarChart<'a> {
+    grammar: &'a Grammar,
+    sets: Vec<Vec<Item>>,
+}
+
+impl<'a> GrammarChart<'a> {
+    fn next_symbol(&self, item: &Item) -> Option<&'a Symbol> {
+        self.grammar.rules[item.rule].rhs.get(item.dot)
+    }
+
+    fn close(&self, mut items: Vec<Item>) -> Vec<Item> {
+        let position = self.sets.len();
+        let mut index = 0;
+        while index < items.len() {
+            let item = items[index];
+            index += 1;
+            let mut new_items = vec![];
+            match self.next_symbol(&item) {
+                None => {
+                    if item.origin < position {
+                        let lhs = self.grammar.rules[item.rule].lhs;
+                        for parent in &self.sets[item.origin] {
+                            if self.next_symbol(parent) == Some(&Symbol::Rule(lhs)) {
+                                new_items.push(Item {
+                                    dot: parent.dot + 1,
+                                    ..*parent
+                                });
+                            }
+                        }
+                    }
+                }
+                Some(Symbol::Rule(symbol)) => {
+                    for &rule in &self.grammar.rules_by_symbol[*symbol] {
+                        new_items.push(Item {
+                            rule,
+                            dot: 0,
+                            origin: position,
+                        });
+                    }
+                    if self.grammar.nullable[*symbol] {
+                        new_items.push(Item {
+                            dot: item.dot + 1,
+                            ..item
+                        });
+                    }
+                }
+                Some(Symbol::Chars(_)) => {}
+            }
+            for new_item in new_items {
+                if !items.contains(&new_item) {
+                    items.push(new_item);
+                }
+            }
+        }
+        items
+    }
+
+    fn scan(&self, c: char) -> Vec<Item> {
+        self.sets
+            .last()
+            .unwrap()
+            .iter()
+            .filter(|item| match self.next_symbol(item) {
+                Some(Symbol::Chars(class)) => class.matches(c),
+                _ => false,
+            })
+            .map(|item| Item {
+                dot: item.dot + 1,
+                ..*item
+            })
+            .collect()
+    }
+
+    /// Returns true if the character is a valid continuation of the characters pushed so far
+    pub(crate) fn accepts_next(&self, c: char) -> bool {
+        !self.scan(c).is_empty()
+    }
+
+    /// Pushes the characters of a text, returning false (and leaving the chart unchanged) if they do not form a
+    /// valid continuation
+    pub(crate) fn push_str(&mut self, text: &str) -> bool {
+        let length = self.sets.len();
+        for c in text.chars() {
+            let scanned = self.scan(c);
+            if scanned.is_empty() {
+                self.sets.truncate(length);
+                return false;
+            }
+            let set = self.close(scanned);
+            self.sets.push(set);
+        }
+        true
+    }
+
+    /// Returns true if the text is a valid continuation, without pushing it
+    pub(crate) fn accepts_str(&mut self, text: &str) -> bool {
+        let length = self.sets.len();
+        let accepted = self.push_str(text);
+        self.sets.truncate(length);
+        accepted
+    }
+
+    /// Returns true if the characters pushed so far are matched by the grammar
+    pub(crate) fn is_complete(&self) -> bool {
+        self.sets.last().unwrap().iter().any(|item| {
+            (item.origin == 0)
+                & (self.grammar.rules[item.rule].lhs == self.grammar.start)
+                & self.next_symbol(item).is_none()
+        })
+    }
+}
+
+struct GrammarParser {
+    chars: Vec<char>,
+    position: usize,
+    names: Vec<String>,
+    symbols: HashMap<String, usize>,
+    defined: Vec<bool>,
+    rules: Vec<Rule>,
+}
+
+impl GrammarParser {
+    fn new(definition: &str) -> GrammarParser {
+        GrammarParser {
+            chars: definition.chars().collect(),
+            position: 0,
+            names: vec![],
+            symbols: HashMap::new(),
+            defined: vec![],
+            rules: vec![],
+        }
+    }
+
+    fn error<T>(&self, message: &str) -> Result<T, RustBertError> {
+        Err(RustBertError::ValueError(format!(
+            "Invalid grammar at character {}: {}",
+            self.position, message
+        )))
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.position).cloned()
+    }
+
+    fn expect(&mut self, c: char) -> Result<(), RustBertError> {
+        if self.peek() == Some(c) {
+            self.position += 1;
+            Ok(())
+        } else {
+            self.error(&format!("expected '{}'", c))
+        }
+    }
+
+    fn skip_space(&mut self) {
+        while let Some(c) = self.peek() {
+            if c == '#' {
+                while self.peek().map_or(false, |c| c != '\n') {
+                    self.position += 1;
+                }
+            } else if c.is_whitespace() {
+                self.position += 1;
+            } else {
+                break;
+            }
+        }
+    }
+
+    fn parse_name(&mut self) -> Option<String> {
+        let start = self.position;
+        while self.peek().map_or(false, |c| {
+            c.is_ascii_alphanumeric() | (c == '_') | (c == '-')
+        }) {
+            self.position += 1;
+        }
+        if self.position > start {
+            Some(self.chars[start..self.position].iter().collect())
+        } else {
+            None
+        }
+    }
+
+    fn at_rule_definition(&mut self) -> bool {
+        let start = self.position;
+        let is_definition = self.parse_name().is_some() && {
+            self.skip_space();
+            self.chars[self.position..].starts_with(&[':', ':', '='])
+        };
+        self.position = start;
+        is_definition
+    }
+
+    fn symbol(&mut self, name: &str) -> usize {
+        if let Some(symbol) = self.symbols.get(name) {
+            return *symbol;
+        }
+        let symbol = self.names.len();
+        self.names.push(name.to_string());
+        self.symbols.insert(name.to_string(), symbol);
+        self.defined.push(false);
+        symbol
+    }
+
+    fn anonymous_rule(&mut self, alternatives: Vec<Vec<Symbol>>) -> Symbol {
+        let symbol = self.symbol(&format!("<anonymous {}>", self.names.len()));
+        self.defined[symbol] = true;
+        for rhs in alternatives {
+            self.rules.push(Rule { lhs: symbol, rhs });
+        }
+        Symbol::Rule(symbol)
+    }
+
+    fn parse_char(&mut self) -> Result<char, RustBertError> {
+        let c = match self.peek() {
+            Some(c) => c,
+            None => return self.error("unexpected end of the grammar"),
+        };
+        self.position += 1;
+        if c != '\\' {
+            return Ok(c);
+        }
+        let escaped = match self.peek() {
+            Some(c) => c,
+            None => return self.error("unexpected end of the grammar"),
+        };
+        self.position += 1;
+        Ok(match escaped {
+            'n' => '\n',
+            'r' => '\r',
+            't' => '\t',
+            'u' => {
+                let code: String = self
+                    .chars
+                    .iter()
+                    .skip(self.position)
+                    .take(4)
+                    .collect::<String>();
+                self.position += 4;
+                match u32::from_str_radix(&code, 16)
+                    .ok()
+                    .and_then(std::char::from_u32)
+                {
+                    Some(c) => c,
+                    None => return self.error("invalid unicode escape"),
+                }
+            }
+            other => other,
+        })
+    }
+
+    fn parse_class(&mut self) -> Result<CharClass, RustBertError> {
+        self.expect('[')?;
+        let negated = self.peek() == Some('^');
+        if negated {
+            self.position += 1;
+        }
+        let mut ranges = vec![];
+        while self.peek() != Some(']') {
+            let start = self.parse_char()?;
+            let end =
+                if (self.peek() == Some('-')) & (self.chars.get(self.position + 1) != Some(&']')) {
+                    self.position += 1;
+                    self.parse_char()?
+                } else {
+                    start
+                };
+            ranges.push((start, end));
+        }
+        self.position += 1;
+        Ok(CharClass { ranges, negated })
+    }
+
+    fn parse_element(&mut self) -> Result<Vec<Symbol>, RustBertError> {
+        Ok(match self.peek() {
+            Some('"') => {
+                self.position += 1;
+                let mut symbols = vec![];
+                while self.peek() != Some('"') {
+                    symbols.push(Symbol::Chars(CharClass::single(self.parse_char()?)));
+                }
+                self.position += 1;
+                symbols
+            }
+            Some('[') => vec![Symbol::Chars(self.parse_class()?)],
+            Some('.') => {
+                self.position += 1;
+                vec![Symbol::Chars(CharClass {
+                    ranges: vec![],
+                    negated: true,
+                })]
+            }
+            Some('(') => {
+                self.position += 1;
+                let alternatives = self.parse_alternatives()?;
+                self.skip_space();
+                self.expect(')')?;
+                vec![self.anonymous_rule(alternatives)]
+            }
+            _ => match self.parse_name() {
+                Some(name) => vec![Symbol::Rule(self.symbol(&name))],
+                None => return self.error("expected a literal, character class, group or rule"),
+            },
+        })
+    }
+
+    fn parse_sequence(&mut self) -> Result<Vec<Symbol>, RustBertError> {
+        let mut sequence = vec![];
+        loop {
+            self.skip_space();
+            if self.peek().map_or(true, |c| (c == '|') | (c == ')')) || self.at_rule_definition() {
+                break;
+            }
+            let mut element = self.parse_element()?;
+            while let Some(operator) = self.peek().filter(|c| matches!(c, '?' | '*' | '+')) {
+                self.position += 1;
+                let repeated = if element.len() == 1 {
+                    element.pop().unwrap()
+                } else {
+                    self.anonymous_rule(vec![element])
+                };
+                let symbol = self.symbol(&format!("<anonymous {}>", self.names.len()));
+                self.defined[symbol] = true;
+                let alternatives = match operator {
+                    '?' => vec![vec![repeated], vec![]],
+                    '*' => vec![vec![repeated, Symbol::Rule(symbol)], vec![]],
+                    _ => vec![vec![repeated.clone(), Symbol::Rule(symbol)], vec![repeated]],
+                };
+                for rhs in alternatives {
+                    self.rules.push(Rule { lhs: symbol, rhs });
+                }
+                element = vec![Symbol::Rule(symbol)];
+            }
+            sequence.extend(element);
+        }
+        Ok(sequence)
+    }
+
+    fn parse_alternatives(&mut self) -> Result<Vec<Vec<Symbol>>, RustBertError> {
+        let mut alternatives = vec![self.parse_sequence()?];
+        while self.peek() == Some('|') {
+            self.position += 1;
+            alternatives.push(self.parse_sequence()?);
+        }
+        Ok(alternatives)
+    }
+
+    fn parse(mut self) -> Result<Grammar, RustBertError> {
+        let mut first_rule = None;
+        loop {
+            self.skip_space();
+            if self.peek().is_none() {
+                break;
+            }
+            let name = match self.parse_name() {
+                Some(name) => name,
+                None => return self.error("expected a rule name"),
+            };
+            self.skip_space();
+            for c in "::=".chars() {
+                self.expect(c)?;
+            }
+            let symbol = self.symbol(&name);
+            self.defined[symbol] = true;
+            first_rule.get_or_insert(symbol);
+            for rhs in self.parse_alternatives()? {
+                self.rules.push(Rule { lhs: symbol, rhs });
+            }
+        }
+        if let Some(symbol) = self.defined.iter().position(|defined| !defined) {
+            return Err(RustBertError::ValueError(format!(
+                "Invalid grammar: the rule {} is not defined",
+                self.names[symbol]
+            )));
+        }
+        let start = match self.symbols.get("root").cloned().or(first_rule) {
+            Some(start) => start,
+            None => {
+                return Err(RustBertError::ValueError(
+                    "Invalid grammar: no rule is defined".to_string(),
+                ));
+            }
+        };
+
+        let mut rules_by_symbol = vec![vec![]; self.names.len()];
+        for (index, rule) in self.rules.iter().enumerate() {
+            rules_by_symbol[rule.lhs].push(index);
+        }
+        let mut nullable = vec![false; self.names.len()];
+        let mut changed = true;
+        while changed {
+            changed = false;
+            for rule in &self.rules {
+                if !nullable[rule.lhs]
+                    && rule.rhs.iter().all(|symbol| match symbol {
+                        Symbol::Rule(symbol) => nullable[*symbol],
+                        Symbol::Chars(_) => false,
+                    })
+                {
+                    nullable[rule.lhs] = true;
+                    changed = true;
+                }
+            }
+        }
+        Ok(Grammar {
+            rules: self.rules,
+            rules_by_symbol,
+            nullable,
+            start,
+        })
+    }
+}
+
+const JSON_RULES: &str = r#"
+value ::= object | array | string | number | boolean | null
+object ::= "{" ws (member ("," ws member)*)? "}"
+member ::= string ws ":" ws value ws
+array ::= "[" ws (value ws ("," ws value ws)*)? "]"
+string ::= "\"" char* "\""
+char ::= [^"\\\n\r\t] | "\\" (["\\/bfnrt] | "u" hex hex hex hex)
+hex ::= [0-9a-fA-F]
+integer ::= "-"? ("0" | [1-9] [0-9]*)
+number ::= integer ("." [0-9]+)? ([eE] [-+]? [0-9]+)?
+boolean ::= "true" | "false"
+null ::= "null"
+nothing ::= []
+ws ::= ([ \t\n] ([ \t\n] ([ \t\n] [ \t\n]?)?)?)?
+"#;
+
+/// Converts a JSON schema into rules referring to the JSON rules
+struct SchemaCompiler {
+    rules: Vec<String>,
+}
+
+impl SchemaCompiler {
+    fn new_rule(&mut self, alternatives: &[String]) -> String {
+        let name = format!("schema-{}", self.rules.len());
+        self.rules
+            .push(format!("{} ::= {}", name, alternatives.join(" | ")));
+        name
+    }
+
+    fn compile(&mut self, schema: &JsonSchema) -> String {
+        match schema {
+            JsonSchema::String => "string".to_string(),
+            JsonSchema::Number => "number".to_string(),
+            JsonSchema::Integer => "integer".to_string(),
+            JsonSchema::Boolean => "boolean".to_string(),
+            JsonSchema::Enum(values) if values.is_empty() => "nothing".to_string(),
+            JsonSchema::Enum(values) => {
+                let alternatives = values
+                    .iter()
+                    .map(|value| json_literal(value))
+                    .collect::<Vec<String>>();
+                self.new_rule(&alternatives)
+            }
+            JsonSchema::Array(schema) => {
+                let item = self.compile(schema);
+                self.new_rule(&[format!(
+                    "\"[\" ws ({item} ws (\",\" ws {item} ws)*)? \"]\"",
+                    item = item
+                )])
+            }
+            JsonSchema::Object(fields) => {
+                // Each field has two rules: one for the remaining fields when no field has been generated yet, and
+                // one when a previous field has been generated (and a separating comma is needed)
+                let mut first = "\"\"".to_string();
+                let mut rest = "\"\"".to_string();
+                for field in fields.iter().rev() {
+                    let value = self.compile(&field.schema);
+                    let member = format!("{} ws \":\" ws {} ws", json_literal(&field.name), value);
+                    let mut first_alternatives = vec![format!("{} {}", member, rest)];
+                    let mut rest_alternatives = vec![format!("\",\" ws {} {}", member, rest)];
+                    if !field.required {
+                        first_alternatives.push(first.clone());
+                        rest_alternatives.push(rest.clone());
+                    }
+                    first = self.new_rule(&first_alternatives);
+                    rest = self.new_rule(&rest_alternatives);
+                }
+                self.new_rule(&[format!("\"{{\" ws {} \"}}\"", first)])
+            }
+            JsonSchema::Nullable(schema) => {
+                let value = self.compile(schema);
+                self.new_rule(&[value, "null".to_string()])
+            }
+        }
+    }
+}
+
+/// Grammar literal matching a string serialized in JSON
+fn json_literal(value: &str) -> String {
+    let json = serde_json::to_string(value).unwrap();
+    let mut literal = String::with_capacity(json.len() + 2);
+    literal.push('"');
+    for c in json.chars() {
+        match c {
+            '"' | '\\' => {
+                literal.push('\\');
+                literal.push(c);
+            }
+            _ => literal.push(c),
+        }
+    }
+    literal.push('"');
+    literal
+}
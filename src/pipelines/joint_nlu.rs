@@ -0,0 +1,486 @@
+// Copyright 2021 Guillaume Becquin
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//     http://www.apache.org/licenses/LICENSE-2.0
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! # Joint intent detection and slot filling pipeline
+//! Natural language understanding for voice assistants and chatbots: a single BERT encoder is shared by an intent
+//! classification head, reading the pooled output, and a slot tagging head, labelling each word with a BIO tag
+//! (e.g. `B-city`, `I-city`, `O`). The pipeline returns the intent of each utterance with its confidence, and the
+//! slots with their type, text and character offsets.
+//!
+//! The weights layout follows the JointBERT checkpoints: encoder weights under `bert`, intent head under
+//! `intent_classifier.linear` and slot head under `slot_classifier.linear`. The head paths can be changed in the
+//! configuration for other checkpoints. The labels are given in the order of the head outputs, and can be read from
+//! the label files of these checkpoints (one label per line) with `read_labels`. Slot labels other than `B-` and `I-`
+//! tags (`O`, `PAD`, `UNK`) are considered outside of any slot.
+//!
+//! ```no_run
+//! # fn main() -> anyhow::Result<()> {
+//! use rust_bert::pipelines::joint_nlu::{read_labels, JointNLUConfig, JointNLUModel};
+//! use rust_bert::resources::{LocalResource, Resource};
+//! use std::path::PathBuf;
+//!
+//! let resource = |path: &str| {
+//!     Resource::Local(LocalResource {
+//!         local_path: PathBuf::from(path),
+//!     })
+//! };
+//! let config = JointNLUConfig::new(
+//!     resource("path/to/model.ot"),
+//!     resource("path/to/config.json"),
+//!     resource("path/to/vocab.txt"),
+//!     true,
+//!     read_labels(&resource("path/to/intent_label.txt"))?,
+//!     read_labels(&resource("path/to/slot_label.txt"))?,
+//! );
+//! let nlu_model = JointNLUModel::new(config)?;
+//!
+//! let output = nlu_model.predict(&["Wake me up at 7 am tomorrow", "Will it rain in New York?"]);
+//! println!("{} ({:.2})", output[1].intent.text, output[1].intent.score);
+//! for slot in &output[1].slots {
+//!     println!("{}: {}", slot.slot_type, slot.text);
+//! }
+//! # Ok(())
+//! # }
+//! ```
+
+use crate::bert::{BertConfig, BertEmbeddings, BertModel};
+use crate::common::dropout::Dropout;
+use crate::common::error::RustBertError;
+use crate::common::resources::Resource;
+use crate::common::weights::reload_var_store;
+use crate::pipelines::common::{ModelType, TokenizerOption};
+use crate::pipelines::sequence_classification::Label;
+use crate::Config;
+use rust_tokenizers::tokenizer::TruncationStrategy;
+use rust_tokenizers::{Mask, Offset, TokenizedInput};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use tch::nn::VarStore;
+use tch::{nn, no_grad, Device, Kind, Tensor};
+
+/// Reads the labels of a head from a file containing one label per line, in the order of the head outputs
+pub fn read_labels(resource: &Resource) -> Result<Vec<String>, RustBertError> {
+    Ok(fs::read_to_string(resource.get_local_path()?)?
+        .lines()
+        .map(str::trim)
+        .filter(|label| !label.is_empty())
+        .map(String::from)
+        .collect())
+}
+
+/// # Configuration for JointNLUModel
+/// Contains information regarding the model and labels to load and device to place the model on.
+pub struct JointNLUConfig {
+    /// Model weights resource, containing both the encoder and heads weights
+    pub model_resource: Resource,
+    /// Config resource for the shared encoder
+    pub config_resource: Resource,
+    /// Vocab resource
+    pub vocab_resource: Resource,
+    /// Automatically lower case all input upon tokenization (assumes a lower-cased model)
+    pub lower_case: bool,
+    /// Flag indicating if the tokenizer should strip accents (normalization)
+    pub strip_accents: Option<bool>,
+    /// Intent labels, in the order of the intent head outputs
+    pub intent_labels: Vec<String>,
+    /// Slot BIO labels, in the order of the slot head outputs
+    pub slot_labels: Vec<String>,
+    /// Path of the intent head weights (default: `intent_classifier.linear`)
+    pub intent_head_path: String,
+    /// Path of the slot head weights (default: `slot_classifier.linear`)
+    pub slot_head_path: String,
+    /// Maximum sequence length for the tokenized input (default: 128)
+    pub max_length: usize,
+    /// Device to place the model on (default: CUDA/GPU when available)
+    pub device: Device,
+}
+
+impl JointNLUConfig {
+    /// Instantiate a new joint intent detection and slot filling configuration.
+    ///
+    /// # Arguments
+    ///
+    /// * model - The `Resource` pointing to the model to load (e.g.  model.ot)
+    /// * config - The `Resource' pointing to the encoder configuration to load (e.g. config.json)
+    /// * vocab - The `Resource' pointing to the tokenizer's vocabulary to load (e.g.  vocab.txt)
+    /// * lower_case - A `bool' indicating whether the tokenizer should lower case all input (in case of a lower-cased model)
+    /// * intent_labels - `Vec<String>` intent labels, in the order of the intent head outputs
+    /// * slot_labels - `Vec<String>` slot labels, in the order of the slot head outputs
+    pub fn new(
+        model_resource: Resource,
+        config_resource: Resource,
+        vocab_resource: Resource,
+        lower_case: bool,
+        intent_labels: Vec<String>,
+        slot_labels: Vec<String>,
+    ) -> JointNLUConfig {
+        JointNLUConfig {
+            model_resource,
+            config_resource,
+            vocab_resource,
+            lower_case,
+            strip_accents: None,
+            intent_labels,
+            slot_labels,
+            intent_head_path: "intent_classifier.linear".to_string(),
+            slot_head_path: "slot_classifier.linear".to_string(),
+            max_length: 128,
+            device: Device::cuda_if_available(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+/// # Slot filled in an utterance
+pub struct Slot {
+    /// Slot type (BIO label without its prefix, e.g. `city`)
+    pub slot_type: String,
+    /// Text of the slot value
+    pub text: String,
+    /// Average confidence of the slot words
+    pub score: f64,
+    /// Character offsets of the value in the utterance
+    pub offset: Offset,
+}
+
+#[derive(Debug, Clone)]
+/// # Intent and slots of an utterance
+pub struct NLUOutput {
+    /// Intent of the utterance
+    pub intent: Label,
+    /// Slots filled in the utterance, in order
+    pub slots: Vec<Slot>,
+}
+
+struct WordTag {
+    label: String,
+    score: f64,
+    offset: Offset,
+}
+
+// Groups consecutive words tagged with the same slot type, `B-` tags starting a new slot. An `I-` tag following
+// another slot type or no slot starts a new slot.
+fn group_slots(words: &[WordTag], text: &[char]) -> Vec<Slot> {
+    let mut slots: Vec<(Slot, usize)> = vec![];
+    let mut in_slot = false;
+    for word in words {
+        let (begins, slot_type) = if let Some(slot_type) = word.label.strip_prefix("B-") {
+            (true, slot_type)
+        } else if let Some(slot_type) = word.label.strip_prefix("I-") {
+            (false, slot_type)
+        } else {
+            in_slot = false;
+            continue;
+        };
+        match slots.last_mut() {
+            Some((slot, word_count)) if in_slot && !begins && slot.slot_type == slot_type => {
+                slot.offset.end = word.offset.end;
+                slot.score += word.score;
+                *word_count += 1;
+            }
+            _ => slots.push((
+                Slot {
+                    slot_type: slot_type.to_string(),
+                    text: String::new(),
+                    score: word.score,
+                    offset: word.offset,
+                },
+                1,
+            )),
+        }
+        in_slot = true;
+    }
+    slots
+        .into_iter()
+        .map(|(mut slot, word_count)| {
+            let end = (slot.offset.end as usize).min(text.len());
+            let begin = (slot.offset.begin as usize).min(end);
+            slot.text = text[begin..end].iter().collect();
+            slot.score /= word_count as f64;
+            slot
+        })
+        .collect()
+}
+
+/// Creates a linear head at a dotted path (e.g. `intent_classifier.linear`) below `p`
+fn linear_head(p: &nn::Path, path: &[&str], in_dim: i64, out_dim: i64) -> nn::Linear {
+    match path.split_first() {
+        Some((name, sub_path)) => linear_head(&(p / *name), sub_path, in_dim, out_dim),
+        None => nn::linear(p, in_dim, out_dim, Default::default()),
+    }
+}
+
+/// # JointNLUModel detecting intents and filling slots with a shared encoder
+pub struct JointNLUModel {
+    tokenizer: TokenizerOption,
+    encoder: BertModel<BertEmbeddings>,
+    dropout: Dropout,
+    intent_classifier: nn::Linear,
+    slot_classifier: nn::Linear,
+    intent_labels: Vec<String>,
+    slot_labels: Vec<String>,
+    max_length: usize,
+    var_store: VarStore,
+}
+
+impl JointNLUModel {
+    /// Build a new `JointNLUModel`
+    ///
+    /// # Arguments
+    ///
+    /// * `config` - `JointNLUConfig` object containing the resource references (model, vocabulary, configuration), labels and device placement (CPU/GPU)
+    pub fn new(config: JointNLUConfig) -> Result<JointNLUModel, RustBertError> {
+        if config.intent_labels.is_empty() || config.slot_labels.is_empty() {
+            return Err(RustBertError::InvalidConfigurationError(
+                "At least one intent label and one slot label are required to build a JointNLUModel"
+                    .to_string(),
+            ));
+        }
+        let config_path = config.config_resource.get_local_path()?;
+        let vocab_path = config.vocab_resource.get_local_path()?;
+        let weights_path = config.model_resource.get_local_path()?;
+
+        let tokenizer = TokenizerOption::from_file(
+            ModelType::Bert,
+            vocab_path.to_str().unwrap(),
+            None,
+            config.lower_case,
+            config.strip_accents,
+            None,
+        )?;
+        let mut var_store = VarStore::new(config.device);
+        let encoder_config = BertConfig::try_from_file(config_path)?;
+        tokenizer.check_model_compatibility(ModelType::Bert, encoder_config.vocab_size)?;
+        let root = var_store.root();
+        let encoder = BertModel::new(&root / "bert", &encoder_config);
+        let intent_classifier = linear_head(
+            &root,
+            &config.intent_head_path.split('.').collect::<Vec<&str>>(),
+            encoder_config.hidden_size,
+            config.intent_labels.len() as i64,
+        );
+        let slot_classifier = linear_head(
+            &root,
+            &config.slot_head_path.split('.').collect::<Vec<&str>>(),
+            encoder_config.hidden_size,
+            config.slot_labels.len() as i64,
+        );
+        var_store.load(weights_path)?;
+
+        Ok(JointNLUModel {
+            tokenizer,
+            encoder,
+            dropout: Dropout::new(encoder_config.hidden_dropout_prob),
+            intent_classifier,
+            slot_classifier,
+            intent_labels: config.intent_labels,
+            slot_labels: config.slot_labels,
+            max_length: config.max_length,
+            var_store,
+        })
+    }
+
+    fn prepare_for_model(&self, input: &[&str]) -> (Vec<TokenizedInput>, Tensor, Tensor) {
+        let tokenized_input: Vec<TokenizedInput> = self.tokenizer.encode_list(
+            input,
+            self.max_length,
+            &TruncationStrategy::LongestFirst,
+            0,
+        );
+        let max_len = tokenized_input
+            .iter()
+            .map(|input| input.token_ids.len())
+            .max()
+            .unwrap();
+        let pad_id = self
+            .tokenizer
+            .get_pad_id()
+            .expect("The Tokenizer used for joint NLU should contain a PAD id");
+        let tokenized_input_tensors: Vec<tch::Tensor> = tokenized_input
+            .iter()
+            .map(|input| input.token_ids.clone())
+            .map(|mut input| {
+                input.extend(vec![pad_id; max_len - input.len()]);
+                input
+            })
+            .map(|input| Tensor::of_slice(&(input)))
+            .collect::<Vec<_>>();
+        let input_tensor =
+            Tensor::stack(tokenized_input_tensors.as_slice(), 0).to(self.var_store.device());
+        let attention_mask = input_tensor.ne(pad_id).to_kind(Kind::Int64);
+        (tokenized_input, input_tensor, attention_mask)
+    }
+
+    /// Detects the intent and fills the slots of utterances
+    ///
+    /// # Arguments
+    ///
+    /// * `input` - `&[&str]` Array of utterances
+    ///
+    /// # Returns
+    ///
+    /// * `Vec<NLUOutput>` containing the intent and slots of each utterance
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// # fn main() -> anyhow::Result<()> {
+    /// # use rust_bert::pipelines::joint_nlu::{JointNLUConfig, JointNLUModel};
+    /// # let config: JointNLUConfig = unimplemented!();
+    /// let nlu_model = JointNLUModel::new(config)?;
+    /// let output = nlu_model.predict(&["Play the latest album of Daft Punk"]);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn predict<'a, S>(&self, input: S) -> Vec<NLUOutput>
+    where
+        S: AsRef<[&'a str]>,
+    {
+        let input = input.as_ref();
+        if input.is_empty() {
+            return vec![];
+        }
+        let (tokenized_input, input_tensor, attention_mask) = self.prepare_for_model(input);
+        let (intent_scores, slot_scores) = no_grad(|| {
+            let encoder_output = self
+                .encoder
+                .forward_t(
+                    Some(input_tensor),
+                    Some(attention_mask),
+                    None,
+                    None,
+                    None,
+                    &None,
+                    &None,
+                    false,
+                )
+                .unwrap();
+            let intent_scores = encoder_output
+                .pooled_output
+                .unwrap()
+                .apply_t(&self.dropout, false)
+                .apply(&self.intent_classifier)
+                .softmax(-1, Kind::Float)
+                .to(Device::Cpu);
+            let slot_scores = encoder_output
+                .hidden_state
+                .apply_t(&self.dropout, false)
+                .apply(&self.slot_classifier)
+                .softmax(-1, Kind::Float)
+                .to(Device::Cpu);
+            (intent_scores, slot_scores)
+        });
+
+        let (intent_scores, intent_ids) = intent_scores.max2(-1, false);
+        let (slot_scores, slot_ids) = slot_scores.max2(-1, false);
+        tokenized_input
+            .iter()
+            .enumerate()
+            .map(|(sentence, tokens)| {
+                let intent_id = intent_ids.int64_value(&[sentence as i64]);
+                let intent = Label {
+                    text: self.intent_labels[intent_id as usize].clone(),
+                    score: intent_scores.double_value(&[sentence as i64]),
+                    id: intent_id,
+                    sentence,
+                };
+
+                // Words are tagged with the label of their first sub-token
+                let mut words: Vec<WordTag> = vec![];
+                for (position, (mask, offset)) in
+                    tokens.mask.iter().zip(&tokens.token_offsets).enumerate()
+                {
+                    let offset = match (mask, offset) {
+                        (Mask::Special, _) | (_, None) => continue,
+                        (_, Some(offset)) => *offset,
+                    };
+                    match (mask, words.last_mut()) {
+                        (Mask::Continuation, Some(word)) => word.offset.end = offset.end,
+                        _ => {
+                            let index = [sentence as i64, position as i64];
+                            words.push(WordTag {
+                                label: self.slot_labels[slot_ids.int64_value(&index) as usize]
+                                    .clone(),
+                                score: slot_scores.double_value(&index),
+                                offset,
+                            })
+                        }
+                    }
+                }
+                let text = input[sentence].chars().collect::<Vec<char>>();
+                NLUOutput {
+                    intent,
+                    slots: group_slots(&words, &text),
+                }
+            })
+            .collect()
+    }
+
+    /// Reloads the weights of the model from the resource provided, keeping the tokenizer, labels and
+    /// configuration unchanged. The current weights are kept if the new weights cannot be loaded.
+    ///
+    /// # Arguments
+    ///
+    /// * `weights_resource` - `Resource` pointing to the new model weights (e.g. model.ot)
+    pub fn reload_weights(&mut self, weights_resource: &Resource) -> Result<(), RustBertError> {
+        reload_var_store(&mut self.var_store, weights_resource)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::resources::LocalResource;
+    use std::path::PathBuf;
+
+    #[test]
+    #[ignore] // no need to run, compilation is enough to verify it is Send
+    fn test() {
+        let resource = Resource::Local(LocalResource {
+            local_path: PathBuf::from("model.ot"),
+        });
+        let config = JointNLUConfig::new(
+            resource.clone(),
+            resource.clone(),
+            resource,
+            false,
+            vec![],
+            vec![],
+        );
+        let _: Box<dyn Send> = Box::new(JointNLUModel::new(config));
+    }
+
+    #[test]
+    fn slot_grouping() {
+        let text = "fly from new york to paris".chars().collect::<Vec<char>>();
+        let word = |label: &str, score: f64, begin: u32, end: u32| WordTag {
+            label: label.to_string(),
+            score,
+            offset: Offset { begin, end },
+        };
+        let words = [
+            word("O", 0.9, 0, 3),
+            word("O", 0.9, 4, 8),
+            word("B-from_city", 0.8, 9, 12),
+            word("I-from_city", 0.6, 13, 17),
+            word("O", 0.9, 18, 20),
+            word("I-to_city", 0.7, 21, 26),
+        ];
+        let slots = group_slots(&words, &text);
+        assert_eq!(slots.len(), 2);
+        assert_eq!(slots[0].slot_type, "from_city");
+        assert_eq!(slots[0].text, "new york");
+        assert_eq!(slots[0].offset, Offset { begin: 9, end: 17 });
+        assert!((slots[0].score - 0.7).abs() < 1e-9);
+        assert_eq!(slots[1].slot_type, "to_city");
+        assert_eq!(slots[1].text, "paris");
+    }
+}
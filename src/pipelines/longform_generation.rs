@@ -0,0 +1,296 @@
+// Copyright 2021 Guillaume Becquin
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//     http://www.apache.org/licenses/LICENSE-2.0
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! # Long-form generation (plan-then-write)
+//! Generates long documents (reports, articles) in two stages:
+//! - planning: the outline model generates an outline for the topic, parsed into a list of section titles (one per
+//! line, list markers such as `1.`, `-` or `#` being removed)
+//! - writing: the section model generates each section from a prompt sharing the topic, complete outline and end
+//! of the previously written sections, so that the sections follow each other without repetitions
+//!
+//! The sections are then stitched into the final document, preceded by their titles. Both stages use a
+//! `TextCompletion` (e.g. a `TextGenerationModel`): generation parameters can differ per stage by using two models
+//! built from different configurations (for example a short `max_length` for the outline), or the same model can be
+//! shared by passing references. The prompts of both stages are `PromptTemplate`s.
+//!
+//! ```no_run
+//! # fn main() -> anyhow::Result<()> {
+//! use rust_bert::pipelines::longform_generation::LongformGenerator;
+//! use rust_bert::pipelines::text_generation::{TextGenerationConfig, TextGenerationModel};
+//!
+//! let outline_model = TextGenerationModel::new(TextGenerationConfig {
+//!     max_length: 64,
+//!     echo_prompt: false,
+//!     ..Default::default()
+//! })?;
+//! let section_model = TextGenerationModel::new(TextGenerationConfig {
+//!     max_length: 512,
+//!     do_sample: true,
+//!     echo_prompt: false,
+//!     ..Default::default()
+//! })?;
+//! let generator = LongformGenerator::new(outline_model, section_model).with_max_sections(5);
+//! let article = generator.generate("The history of the printing press")?;
+//! for section in &article.sections {
+//!     println!("{}: {} characters", section.title, section.text.len());
+//! }
+//! println!("{}", article.text);
+//! # Ok(())
+//! # }
+//! ```
+
+use crate::common::error::RustBertError;
+use crate::pipelines::prompt_template::PromptTemplate;
+use crate::pipelines::structured_output::TextCompletion;
+
+/// Default prompt of the planning stage, with the variable `{topic}`
+pub const DEFAULT_OUTLINE_TEMPLATE: &str =
+    "Topic: {topic}\nOutline of an article on this topic, with one section title per line:\n";
+
+/// Default prompt of the writing stage, with the variables `{topic}`, `{outline}`, `{previous}` and `{section}`
+pub const DEFAULT_SECTION_TEMPLATE: &str = "Topic: {topic}\nOutline:\n{outline}\n\nPreviously written:\n{previous}\n\nSection \"{section}\":\n";
+
+#[derive(Debug, Clone, PartialEq)]
+/// # Section of a generated document
+pub struct GeneratedSection {
+    /// Title of the section, from the outline
+    pub title: String,
+    /// Generated text of the section
+    pub text: String,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+/// # Document generated with the plan-then-write mode
+pub struct LongformOutput {
+    /// Section titles of the outline
+    pub outline: Vec<String>,
+    /// Generated sections, in the order of the outline
+    pub sections: Vec<GeneratedSection>,
+    /// Stitched document
+    pub text: String,
+}
+
+/// # Two-stage generator of long documents
+pub struct LongformGenerator<O: TextCompletion, S: TextCompletion> {
+    outline_model: O,
+    section_model: S,
+    outline_template: PromptTemplate,
+    section_template: PromptTemplate,
+    max_sections: usize,
+    context_sections: usize,
+    max_context_chars: usize,
+    include_titles: bool,
+    section_separator: String,
+}
+
+impl<O: TextCompletion, S: TextCompletion> LongformGenerator<O, S> {
+    /// Creates a new generator with the default prompts, up to 8 sections and the previous section as context
+    ///
+    /// # Arguments
+    ///
+    /// * `outline_model` - `TextCompletion` generating the outline
+    /// * `section_model` - `TextCompletion` generating the sections
+    pub fn new(outline_model: O, section_model: S) -> LongformGenerator<O, S> {
+        LongformGenerator {
+            outline_model,
+            section_model,
+            outline_template: PromptTemplate::new(DEFAULT_OUTLINE_TEMPLATE).unwrap(),
+            section_template: PromptTemplate::new(DEFAULT_SECTION_TEMPLATE).unwrap(),
+            max_sections: 8,
+            context_sections: 1,
+            max_context_chars: 2000,
+            include_titles: true,
+            section_separator: "\n\n".to_string(),
+        }
+    }
+
+    /// Sets the prompt of the planning stage, which can use the variable `{topic}`
+    pub fn with_outline_template(mut self, template: PromptTemplate) -> LongformGenerator<O, S> {
+        self.outline_template = template;
+        self
+    }
+
+    /// Sets the prompt of the writing stage, which can use the variables `{topic}`, `{outline}` (numbered section
+    /// titles, one per line), `{previous}` (end of the previously written sections) and `{section}` (title of the
+    /// section to write)
+    pub fn with_section_template(mut self, template: PromptTemplate) -> LongformGenerator<O, S> {
+        self.section_template = template;
+        self
+    }
+
+    /// Sets the maximum number of sections kept from the generated outline (default: 8)
+    pub fn with_max_sections(mut self, max_sections: usize) -> LongformGenerator<O, S> {
+        self.max_sections = max_sections;
+        self
+    }
+
+    /// Sets the number of previous sections shared with the prompt of each section (default: 1), and the maximum
+    /// number of characters kept from their end (default: 2000)
+    pub fn with_context(
+        mut self,
+        context_sections: usize,
+        max_context_chars: usize,
+    ) -> LongformGenerator<O, S> {
+        self.context_sections = context_sections;
+        self.max_context_chars = max_context_chars;
+        self
+    }
+
+    /// Sets whether the section titles precede the sections in the stitched document (default: true), and the
+    /// separator between the sections (default: an empty line)
+    pub fn with_stitching(
+        mut self,
+        include_titles: bool,
+        section_separator: &str,
+    ) -> LongformGenerator<O, S> {
+        self.include_titles = include_titles;
+        self.section_separator = section_separator.to_string();
+        self
+    }
+
+    /// Generates the outline of a topic
+    ///
+    /// # Arguments
+    ///
+    /// * `topic` - subject of the document
+    ///
+    /// # Returns
+    ///
+    /// * `Vec<String>` section titles, or a `RustBertError::ValueError` if the outline is empty
+    pub fn generate_outline(&self, topic: &str) -> Result<Vec<String>, RustBertError> {
+        let prompt = self.outline_template.render(&[("topic", topic)])?;
+        let outline = parse_outline(&self.outline_model.complete(&prompt)?, self.max_sections);
+        if outline.is_empty() {
+            return Err(RustBertError::ValueError(
+                "The generated outline does not contain any section".to_string(),
+            ));
+        }
+        Ok(outline)
+    }
+
+    /// Writes the sections of an outline (generated or provided by the user) and stitches them
+    ///
+    /// # Arguments
+    ///
+    /// * `topic` - subject of the document
+    /// * `outline` - section titles
+    ///
+    /// # Returns
+    ///
+    /// * `LongformOutput` with the outline, sections and stitched document
+    pub fn write_sections(
+        &self,
+        topic: &str,
+        outline: &[&str],
+    ) -> Result<LongformOutput, RustBertError> {
+        let numbered_outline = outline
+            .iter()
+            .enumerate()
+            .map(|(index, title)| format!("{}. {}", index + 1, title))
+            .collect::<Vec<_>>()
+            .join("\n");
+        let mut sections: Vec<GeneratedSection> = Vec::with_capacity(outline.len());
+        for title in outline {
+            let context_start = sections.len().saturating_sub(self.context_sections);
+            let previous = sections[context_start..]
+                .iter()
+                .map(|section| section.text.as_str())
+                .collect::<Vec<_>>()
+                .join("\n\n");
+            let prompt = self.section_template.render(&[
+                ("topic", topic),
+                ("outline", &numbered_outline),
+                ("previous", last_chars(&previous, self.max_context_chars)),
+                ("section", title),
+            ])?;
+            let text = self.section_model.complete(&prompt)?.trim().to_string();
+            sections.push(GeneratedSection {
+                title: title.to_string(),
+                text,
+            });
+        }
+
+        let text = sections
+            .iter()
+            .map(|section| {
+                if self.include_titles {
+                    format!("{}\n\n{}", section.title, section.text)
+                } else {
+                    section.text.clone()
+                }
+            })
+            .collect::<Vec<_>>()
+            .join(&self.section_separator);
+        Ok(LongformOutput {
+            outline: outline.iter().map(|title| title.to_string()).collect(),
+            sections,
+            text,
+        })
+    }
+
+    /// Generates a document: outline, then sections
+    ///
+    /// # Arguments
+    ///
+    /// * `topic` - subject of the document
+    ///
+    /// # Returns
+    ///
+    /// * `LongformOutput` with the outline, sections and stitched document
+    pub fn generate(&self, topic: &str) -> Result<LongformOutput, RustBertError> {
+        let outline = self.generate_outline(topic)?;
+        self.write_sections(
+            topic,
+            &outline.iter().map(String::as_str).collect::<Vec<_>>(),
+        )
+    }
+}
+
+/// Parses a generated outline into section titles: one title per non-empty line, without list markers (`1.`, `2)`,
+/// `-`, `*`, `#`...) and duplicates, keeping at most `max_sections` titles
+pub fn parse_outline(outline: &str, max_sections: usize) -> Vec<String> {
+    let mut titles: Vec<String> = vec![];
+    for line in outline.lines() {
+        let title = line
+            .trim()
+            .trim_start_matches(|c: char| matches!(c, '-' | '*' | '#' | '•'))
+            .trim_start();
+        let number_length =
+            title.len() - title.trim_start_matches(|c: char| c.is_ascii_digit()).len();
+        let title = match title[number_length..].chars().next() {
+            Some('.') | Some(')') if number_length > 0 => &title[number_length + 1..],
+            _ => title,
+        }
+        .trim();
+        if title.is_empty()
+            || titles
+                .iter()
+                .any(|existing| existing.eq_ignore_ascii_case(title))
+        {
+            continue;
+        }
+        if titles.len() == max_sections {
+            break;
+        }
+        titles.push(title.to_string());
+    }
+    titles
+}
+
+fn last_chars(text: &str, max_chars: usize) -> &str {
+    if max_chars == 0 {
+        return "";
+    }
+    match text.char_indices().rev().nth(max_chars - 1) {
+        Some((position, _)) => &text[position..],
+        None => text,
+    }
+}
@@ -343,15 +343,48 @@
 //! # ;
 //! ```
 
+pub mod attacks;
+pub mod audio_classification;
+pub mod augmentation;
+pub mod batch_jobs;
+pub mod chat_completions;
+pub mod clustering;
 pub mod common;
 pub mod conversation;
+pub mod custom_models;
+pub mod deduplication;
+pub mod differential_privacy;
+pub mod doc2query;
+pub mod document_parsing;
+pub mod document_store;
+pub mod entity_linking;
+pub mod feature_extraction;
 pub mod generation_utils;
+pub mod grammar;
+pub mod joint_nlu;
+pub mod longform_generation;
+pub mod multi_task;
+pub mod multiple_choice;
 pub mod ner;
+pub mod next_sentence_prediction;
+pub mod open_domain_qa;
+pub mod post_processing;
+pub mod prompt_template;
+pub mod punctuation_restoration;
 pub mod question_answering;
+pub mod rag;
+pub mod robustness;
+pub mod self_refine;
 pub mod sentiment;
 pub mod sequence_classification;
+pub mod shared_models;
+pub mod stance_detection;
+pub mod structured_output;
 pub mod summarization;
 pub mod text_generation;
+pub mod text_normalization;
+pub mod text_statistics;
+pub mod text_to_speech;
 pub mod token_classification;
 pub mod translation;
 pub mod zero_shot_classification;
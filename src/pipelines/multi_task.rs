@@ -0,0 +1,533 @@
+// Copyright 2019-present, the HuggingFace Inc. team, The Google AI Language Team and Facebook, Inc.
+// Copyright 2019-2021 Guillaume Becquin
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//     http://www.apache.org/licenses/LICENSE-2.0
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! # Multi-task pipeline
+//! Runs several task heads (for example named entity recognition, sentiment and topic classification)
+//! on top of a single shared BERT encoder. Inputs are tokenized once and the encoder is executed once per
+//! batch, the resulting hidden states being fed to every registered head.
+//!
+//! The weights file is expected to contain the encoder weights under `bert` and the weights of each head
+//! under `heads.<task name>.classifier`.
+//!
+//! ```no_run
+//! use rust_bert::pipelines::multi_task::{MultiTaskConfig, MultiTaskModel, TaskHead, TaskType};
+//! use rust_bert::resources::{LocalResource, Resource};
+//! use std::collections::HashMap;
+//! use std::path::PathBuf;
+//! # fn main() -> anyhow::Result<()> {
+//! let sentiment_labels: HashMap<i64, String> =
+//!     [(0, "NEGATIVE".to_string()), (1, "POSITIVE".to_string())]
+//!         .iter()
+//!         .cloned()
+//!         .collect();
+//! let ner_labels: HashMap<i64, String> = [
+//!     (0, "O".to_string()),
+//!     (1, "B-PER".to_string()),
+//!     (2, "I-PER".to_string()),
+//! ]
+//! .iter()
+//! .cloned()
+//! .collect();
+//!
+//! let config = MultiTaskConfig::new(
+//!     Resource::Local(LocalResource {
+//!         local_path: PathBuf::from("path/to/model.ot"),
+//!     }),
+//!     Resource::Local(LocalResource {
+//!         local_path: PathBuf::from("path/to/config.json"),
+//!     }),
+//!     Resource::Local(LocalResource {
+//!         local_path: PathBuf::from("path/to/vocab.txt"),
+//!     }),
+//!     false,
+//!     vec![
+//!         TaskHead::new("sentiment", TaskType::SequenceClassification, sentiment_labels),
+//!         TaskHead::new("ner", TaskType::TokenClassification, ner_labels),
+//!     ],
+//! );
+//! let multi_task_model = MultiTaskModel::new(config)?;
+//!
+//! let input = ["My name is Amy. I live in Paris and I love it."];
+//! let output = multi_task_model.predict(&input);
+//! # Ok(())
+//! # }
+//! ```
+
+use crate::bert::{BertConfig, BertEmbeddings, BertModel};
+use crate::common::dropout::Dropout;
+use crate::common::error::RustBertError;
+use crate::common::resources::Resource;
+use crate::common::weights::reload_var_store;
+use crate::pipelines::common::{ModelType, TokenizerOption};
+use crate::pipelines::sequence_classification::Label;
+use crate::pipelines::token_classification::Token;
+use crate::Config;
+use rust_tokenizers::tokenizer::TruncationStrategy;
+use rust_tokenizers::{Mask, TokenizedInput};
+use serde::{Deserialize, Serialize};
+use std::borrow::Borrow;
+use std::cmp::min;
+use std::collections::HashMap;
+use tch::nn::VarStore;
+use tch::{nn, no_grad, Device, Kind, Tensor};
+
+#[derive(Clone, Copy, Debug, Serialize, Deserialize, PartialEq)]
+/// # Type of prediction performed by a task head
+pub enum TaskType {
+    /// Sequence-level classification, using the pooled encoder output
+    SequenceClassification,
+    /// Token-level classification, using the encoder hidden state of every token
+    TokenClassification,
+}
+
+#[derive(Clone, Debug)]
+/// # Task head definition for a `MultiTaskModel`
+pub struct TaskHead {
+    /// Task name, used to retrieve the head weights (`heads.<name>.classifier`) and index the predictions
+    pub name: String,
+    /// Type of prediction performed by the head
+    pub task_type: TaskType,
+    /// Mapping between the head output indices and label strings
+    pub id2label: HashMap<i64, String>,
+}
+
+impl TaskHead {
+    /// Creates a new task head definition
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - Task name, should match the name used to store the head weights
+    /// * `task_type` - `TaskType` indicating if the head performs sequence or token classification
+    /// * `id2label` - Mapping between head output indices and labels. The number of labels defines the head output dimension
+    pub fn new<S: Into<String>>(
+        name: S,
+        task_type: TaskType,
+        id2label: HashMap<i64, String>,
+    ) -> TaskHead {
+        TaskHead {
+            name: name.into(),
+            task_type,
+            id2label,
+        }
+    }
+}
+
+/// # Configuration for MultiTaskModel
+/// Contains information regarding the encoder to load, the task heads to register and device to place the model on.
+pub struct MultiTaskConfig {
+    /// Model weights resource, containing both the encoder and heads weights
+    pub model_resource: Resource,
+    /// Config resource for the shared encoder
+    pub config_resource: Resource,
+    /// Vocab resource
+    pub vocab_resource: Resource,
+    /// Automatically lower case all input upon tokenization (assumes a lower-cased model)
+    pub lower_case: bool,
+    /// Flag indicating if the tokenizer should strip accents (normalization)
+    pub strip_accents: Option<bool>,
+    /// Task heads to build on top of the shared encoder
+    pub tasks: Vec<TaskHead>,
+    /// Maximum sequence length for the tokenized input (default: 128)
+    pub max_length: usize,
+    /// Device to place the model on (default: CUDA/GPU when available)
+    pub device: Device,
+}
+
+impl MultiTaskConfig {
+    /// Instantiate a new multi-task configuration.
+    ///
+    /// # Arguments
+    ///
+    /// * model - The `Resource` pointing to the model to load (e.g.  model.ot)
+    /// * config - The `Resource' pointing to the encoder configuration to load (e.g. config.json)
+    /// * vocab - The `Resource' pointing to the tokenizer's vocabulary to load (e.g.  vocab.txt)
+    /// * lower_case - A `bool' indicating whether the tokenizer should lower case all input (in case of a lower-cased model)
+    /// * tasks - `Vec<TaskHead>` task heads to build on top of the shared encoder
+    pub fn new(
+        model_resource: Resource,
+        config_resource: Resource,
+        vocab_resource: Resource,
+        lower_case: bool,
+        tasks: Vec<TaskHead>,
+    ) -> MultiTaskConfig {
+        MultiTaskConfig {
+            model_resource,
+            config_resource,
+            vocab_resource,
+            lower_case,
+            strip_accents: None,
+            tasks,
+            max_length: 128,
+            device: Device::cuda_if_available(),
+        }
+    }
+}
+
+/// # Linear classification head sharing the encoder of a `MultiTaskModel`
+struct ClassificationHead {
+    task_type: TaskType,
+    dropout: Dropout,
+    classifier: nn::Linear,
+    label_mapping: HashMap<i64, String>,
+}
+
+impl ClassificationHead {
+    fn new<'p, P>(p: P, config: &BertConfig, task: &TaskHead) -> ClassificationHead
+    where
+        P: Borrow<nn::Path<'p>>,
+    {
+        let p = p.borrow();
+        let dropout = Dropout::new(config.hidden_dropout_prob);
+        let classifier = nn::linear(
+            p / "classifier",
+            config.hidden_size,
+            task.id2label.len() as i64,
+            Default::default(),
+        );
+        ClassificationHead {
+            task_type: task.task_type,
+            dropout,
+            classifier,
+            label_mapping: task.id2label.clone(),
+        }
+    }
+
+    fn forward_t(&self, hidden_state: &Tensor, pooled_output: &Tensor, train: bool) -> Tensor {
+        match self.task_type {
+            TaskType::SequenceClassification => pooled_output,
+            TaskType::TokenClassification => hidden_state,
+        }
+        .apply_t(&self.dropout, train)
+        .apply(&self.classifier)
+    }
+}
+
+#[derive(Debug, Clone)]
+/// # Prediction of a single task head
+pub enum TaskOutput {
+    /// One label per input sequence
+    SequenceClassification(Vec<Label>),
+    /// One vector of labelled tokens per input sequence
+    TokenClassification(Vec<Vec<Token>>),
+}
+
+/// # MultiTaskModel running several heads on a shared encoder
+pub struct MultiTaskModel {
+    tokenizer: TokenizerOption,
+    encoder: BertModel<BertEmbeddings>,
+    heads: Vec<(String, ClassificationHead)>,
+    max_length: usize,
+    var_store: VarStore,
+}
+
+impl MultiTaskModel {
+    /// Build a new `MultiTaskModel`
+    ///
+    /// # Arguments
+    ///
+    /// * `config` - `MultiTaskConfig` object containing the resource references (model, vocabulary, configuration), task heads and device placement (CPU/GPU)
+    pub fn new(config: MultiTaskConfig) -> Result<MultiTaskModel, RustBertError> {
+        if config.tasks.is_empty() {
+            return Err(RustBertError::InvalidConfigurationError(
+                "At least one task head is required to build a MultiTaskModel".to_string(),
+            ));
+        }
+        for (index, task) in config.tasks.iter().enumerate() {
+            if task.id2label.is_empty() {
+                return Err(RustBertError::InvalidConfigurationError(format!(
+                    "Task {} does not define any label",
+                    task.name
+                )));
+            }
+            if config.tasks[..index]
+                .iter()
+                .any(|other| other.name == task.name)
+            {
+                return Err(RustBertError::InvalidConfigurationError(format!(
+                    "Task name {} is registered more than once",
+                    task.name
+                )));
+            }
+        }
+
+        let config_path = config.config_resource.get_local_path()?;
+        let vocab_path = config.vocab_resource.get_local_path()?;
+        let weights_path = config.model_resource.get_local_path()?;
+
+        let tokenizer = TokenizerOption::from_file(
+            ModelType::Bert,
+            vocab_path.to_str().unwrap(),
+            None,
+            config.lower_case,
+            config.strip_accents,
+            None,
+        )?;
+        let mut var_store = VarStore::new(config.device);
+        let encoder_config = BertConfig::try_from_file(config_path)?;
+        tokenizer.check_model_compatibility(ModelType::Bert, encoder_config.vocab_size)?;
+        let root = var_store.root();
+        let encoder = BertModel::new(&root / "bert", &encoder_config);
+        let heads_path = &root / "heads";
+        let heads = config
+            .tasks
+            .iter()
+            .map(|task| {
+                (
+                    task.name.clone(),
+                    ClassificationHead::new(
+                        &heads_path / task.name.as_str(),
+                        &encoder_config,
+                        task,
+                    ),
+                )
+            })
+            .collect();
+        var_store.load(weights_path)?;
+
+        Ok(MultiTaskModel {
+            tokenizer,
+            encoder,
+            heads,
+            max_length: config.max_length,
+            var_store,
+        })
+    }
+
+    fn prepare_for_model<'a, S>(&self, input: S) -> (Vec<TokenizedInput>, Tensor, Tensor)
+    where
+        S: AsRef<[&'a str]>,
+    {
+        let tokenized_input: Vec<TokenizedInput> = self.tokenizer.encode_list(
+            input.as_ref(),
+            self.max_length,
+            &TruncationStrategy::LongestFirst,
+            0,
+        );
+        let max_len = tokenized_input
+            .iter()
+            .map(|input| input.token_ids.len())
+            .max()
+            .unwrap();
+        let pad_id = self
+            .tokenizer
+            .get_pad_id()
+            .expect("The Tokenizer used for multi-task prediction should contain a PAD id");
+        let tokenized_input_tensors: Vec<tch::Tensor> = tokenized_input
+            .iter()
+            .map(|input| input.token_ids.clone())
+            .map(|mut input| {
+                input.extend(vec![pad_id; max_len - input.len()]);
+                input
+            })
+            .map(|input| Tensor::of_slice(&(input)))
+            .collect::<Vec<_>>();
+        let input_tensor =
+            Tensor::stack(tokenized_input_tensors.as_slice(), 0).to(self.var_store.device());
+        let attention_mask = input_tensor.ne(pad_id).to_kind(Kind::Int64);
+        (tokenized_input, input_tensor, attention_mask)
+    }
+
+    /// Runs all task heads on a batch of texts
+    ///
+    /// The input is tokenized and encoded once, the encoder output being shared by all heads.
+    ///
+    /// # Arguments
+    ///
+    /// * `input` - `&[&str]` Array of texts to process.
+    ///
+    /// # Returns
+    ///
+    /// * `HashMap<String, TaskOutput>` containing the predictions of every head, indexed by task name
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// # fn main() -> anyhow::Result<()> {
+    /// # use rust_bert::pipelines::multi_task::{MultiTaskModel, MultiTaskConfig};
+    /// # let config: MultiTaskConfig = unimplemented!();
+    /// let multi_task_model = MultiTaskModel::new(config)?;
+    /// let input = ["My name is Amy. I live in Paris.", "Paris is a city in France."];
+    /// let output = multi_task_model.predict(&input);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn predict<'a, S>(&self, input: S) -> HashMap<String, TaskOutput>
+    where
+        S: AsRef<[&'a str]>,
+    {
+        let (tokenized_input, input_tensor, attention_mask) =
+            self.prepare_for_model(input.as_ref());
+        let head_outputs = no_grad(|| {
+            let encoder_output = self
+                .encoder
+                .forward_t(
+                    Some(input_tensor.copy()),
+                    Some(attention_mask),
+                    None,
+                    None,
+                    None,
+                    &None,
+                    &None,
+                    false,
+                )
+                .unwrap();
+            let pooled_output = encoder_output.pooled_output.as_ref().unwrap();
+            self.heads
+                .iter()
+                .map(|(_, head)| {
+                    head.forward_t(&encoder_output.hidden_state, pooled_output, false)
+                        .softmax(-1, Kind::Float)
+                        .detach()
+                        .to(Device::Cpu)
+                })
+                .collect::<Vec<Tensor>>()
+        });
+
+        let input_tensor = input_tensor.to(Device::Cpu);
+        self.heads
+            .iter()
+            .zip(head_outputs.iter())
+            .map(|((name, head), scores)| {
+                let output = match head.task_type {
+                    TaskType::SequenceClassification => {
+                        TaskOutput::SequenceClassification(self.decode_labels(head, scores))
+                    }
+                    TaskType::TokenClassification => {
+                        TaskOutput::TokenClassification(self.decode_tokens(
+                            head,
+                            scores,
+                            input.as_ref(),
+                            &tokenized_input,
+                            &input_tensor,
+                        ))
+                    }
+                };
+                (name.clone(), output)
+            })
+            .collect()
+    }
+
+    fn decode_labels(&self, head: &ClassificationHead, scores: &Tensor) -> Vec<Label> {
+        let label_indices = scores.argmax(-1, true).squeeze1(1);
+        let label_scores = scores
+            .gather(1, &label_indices.unsqueeze(-1), false)
+            .squeeze1(1);
+        label_indices
+            .iter::<i64>()
+            .unwrap()
+            .zip(label_scores.iter::<f64>().unwrap())
+            .enumerate()
+            .map(|(sentence, (id, score))| Label {
+                text: head
+                    .label_mapping
+                    .get(&id)
+                    .expect("Index out of label mapping bounds.")
+                    .clone(),
+                score,
+                id,
+                sentence,
+            })
+            .collect()
+    }
+
+    fn decode_tokens(
+        &self,
+        head: &ClassificationHead,
+        scores: &Tensor,
+        input: &[&str],
+        tokenized_input: &[TokenizedInput],
+        input_tensor: &Tensor,
+    ) -> Vec<Vec<Token>> {
+        let label_indices = scores.argmax(-1, false);
+        let mut output = Vec::with_capacity(tokenized_input.len());
+        for (sentence_idx, sentence_tokens) in tokenized_input.iter().enumerate() {
+            let original_chars = input[sentence_idx].chars().collect::<Vec<char>>();
+            let mut sentence_output = vec![];
+            let mut word_idx: u16 = 0;
+            for position_idx in 0..sentence_tokens.token_ids.len() {
+                let mask = sentence_tokens.mask[position_idx];
+                if mask == Mask::Special {
+                    continue;
+                }
+                if mask != Mask::Continuation {
+                    word_idx += 1;
+                }
+                let label_id =
+                    label_indices.int64_value(&[sentence_idx as i64, position_idx as i64]);
+                let offset = sentence_tokens.token_offsets[position_idx];
+                let text = match offset {
+                    Some(offset) => {
+                        let end_char = min(offset.end as usize, original_chars.len());
+                        original_chars[offset.begin as usize..end_char]
+                            .iter()
+                            .collect()
+                    }
+                    None => self.tokenizer.decode(
+                        vec![input_tensor.int64_value(&[sentence_idx as i64, position_idx as i64])],
+                        false,
+                        false,
+                    ),
+                };
+                sentence_output.push(Token {
+                    text,
+                    score: scores.double_value(&[
+                        sentence_idx as i64,
+                        position_idx as i64,
+                        label_id,
+                    ]),
+                    label: head
+                        .label_mapping
+                        .get(&label_id)
+                        .expect("Index out of label mapping bounds.")
+                        .clone(),
+                    label_index: label_id,
+                    sentence: sentence_idx,
+                    index: position_idx as u16,
+                    word_index: word_idx - 1,
+                    offset,
+                    mask,
+                });
+            }
+            output.push(sentence_output);
+        }
+        output
+    }
+
+    /// Reloads the weights of the multi-task model from the resource provided, keeping the tokenizer and configuration
+    /// unchanged. The weights must match the architecture of the model currently loaded, the current weights
+    /// are kept if the new weights cannot be loaded.
+    ///
+    /// # Arguments
+    ///
+    /// * `weights_resource` - `Resource` pointing to the new model weights (e.g. model.ot)
+    pub fn reload_weights(&mut self, weights_resource: &Resource) -> Result<(), RustBertError> {
+        reload_var_store(&mut self.var_store, weights_resource)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::resources::LocalResource;
+    use std::path::PathBuf;
+
+    #[test]
+    #[ignore] // no need to run, compilation is enough to verify it is Send
+    fn test() {
+        let resource = Resource::Local(LocalResource {
+            local_path: PathBuf::from("model.ot"),
+        });
+        let config =
+            MultiTaskConfig::new(resource.clone(), resource.clone(), resource, false, vec![]);
+        let _: Box<dyn Send> = Box::new(MultiTaskModel::new(config));
+    }
+}
@@ -0,0 +1,484 @@
+// Copyright 2021 Guillaume Becquin
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//     http://www.apache.org/licenses/LICENSE-2.0
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! # Multiple choice pipeline
+//! Scores a set of candidate answers against a context (e.g. a question, or the beginning of a story for SWAG-style
+//! completion) and returns the candidates ranked by probability. Each (context, choice) pair is encoded as
+//! `[CLS] context [SEP] choice [SEP]` and scored by a `*ForMultipleChoice` model (BERT, RoBERTa, XLM-RoBERTa or ALBERT)
+//! fine-tuned on a multiple choice task.
+//!
+//! ```no_run
+//! # fn main() -> anyhow::Result<()> {
+//! use rust_bert::pipelines::common::ModelType;
+//! use rust_bert::pipelines::multiple_choice::{
+//!     MultipleChoiceConfig, MultipleChoiceInput, MultipleChoiceModel,
+//! };
+//! use rust_bert::resources::{LocalResource, Resource};
+//! use std::path::PathBuf;
+//!
+//! let config = MultipleChoiceConfig::new(
+//!     ModelType::Bert,
+//!     Resource::Local(LocalResource {
+//!         local_path: PathBuf::from("path/to/model.ot"),
+//!     }),
+//!     Resource::Local(LocalResource {
+//!         local_path: PathBuf::from("path/to/config.json"),
+//!     }),
+//!     Resource::Local(LocalResource {
+//!         local_path: PathBuf::from("path/to/vocab.txt"),
+//!     }),
+//!     None,
+//!     true,
+//!     None,
+//!     None,
+//! );
+//! let model = MultipleChoiceModel::new(config)?;
+//!
+//! let input = MultipleChoiceInput {
+//!     context: "Which gas do plants absorb from the atmosphere?".to_string(),
+//!     choices: vec![
+//!         "Oxygen".to_string(),
+//!         "Carbon dioxide".to_string(),
+//!         "Helium".to_string(),
+//!     ],
+//! };
+//! let output = model.predict(&[input], 128)?;
+//! # Ok(())
+//! # }
+//! ```
+
+use crate::albert::AlbertForMultipleChoice;
+use crate::bert::BertForMultipleChoice;
+use crate::common::error::RustBertError;
+use crate::common::resources::Resource;
+use crate::common::weights::reload_var_store;
+use crate::pipelines::common::{ConfigOption, ModelType, TokenizerOption};
+use crate::roberta::RobertaForMultipleChoice;
+use rust_tokenizers::tokenizer::TruncationStrategy;
+use rust_tokenizers::TokenizedInput;
+use serde::{Deserialize, Serialize};
+use std::borrow::Borrow;
+use tch::kind::Kind::Int64;
+use tch::nn::VarStore;
+use tch::{nn, no_grad, Device, Kind, Tensor};
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+/// # Input for multiple choice scoring
+pub struct MultipleChoiceInput {
+    /// Context shared by all choices (question, premise or beginning of a sentence)
+    pub context: String,
+    /// Candidate answers or continuations
+    pub choices: Vec<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+/// # Choice scored by a `MultipleChoiceModel`
+pub struct Choice {
+    /// Choice text
+    pub text: String,
+    /// Index of the choice in the input
+    pub index: usize,
+    /// Probability of the choice among the candidates provided
+    pub score: f64,
+}
+
+/// # Configuration for MultipleChoiceModel
+/// Contains information regarding the model to load and device to place the model on.
+pub struct MultipleChoiceConfig {
+    /// Model type
+    pub model_type: ModelType,
+    /// Model weights resource
+    pub model_resource: Resource,
+    /// Config resource
+    pub config_resource: Resource,
+    /// Vocab resource
+    pub vocab_resource: Resource,
+    /// Merges resource (default: None)
+    pub merges_resource: Option<Resource>,
+    /// Automatically lower case all input upon tokenization (assumes a lower-cased model)
+    pub lower_case: bool,
+    /// Flag indicating if the tokenizer should strip accents (normalization). Only used for BERT / ALBERT models
+    pub strip_accents: Option<bool>,
+    /// Flag indicating if the tokenizer should add a white space before each tokenized input (needed for some Roberta models)
+    pub add_prefix_space: Option<bool>,
+    /// Device to place the model on (default: CUDA/GPU when available)
+    pub device: Device,
+}
+
+impl MultipleChoiceConfig {
+    /// Instantiate a new multiple choice configuration of the supplied type.
+    ///
+    /// # Arguments
+    ///
+    /// * `model_type` - `ModelType` indicating the model type to load (must match with the actual data to be loaded!)
+    /// * model - The `Resource` pointing to the model to load (e.g.  model.ot)
+    /// * config - The `Resource' pointing to the model configuration to load (e.g. config.json)
+    /// * vocab - The `Resource' pointing to the tokenizer's vocabulary to load (e.g.  vocab.txt/vocab.json)
+    /// * merges - An optional `Resource` (`Option<Resource>`) pointing to the tokenizer's merge file to load (e.g.  merges.txt), needed only for Roberta.
+    /// * lower_case - A `bool' indicating whether the tokenizer should lower case all input (in case of a lower-cased model)
+    pub fn new(
+        model_type: ModelType,
+        model_resource: Resource,
+        config_resource: Resource,
+        vocab_resource: Resource,
+        merges_resource: Option<Resource>,
+        lower_case: bool,
+        strip_accents: impl Into<Option<bool>>,
+        add_prefix_space: impl Into<Option<bool>>,
+    ) -> MultipleChoiceConfig {
+        MultipleChoiceConfig {
+            model_type,
+            model_resource,
+            config_resource,
+            vocab_resource,
+            merges_resource,
+            lower_case,
+            strip_accents: strip_accents.into(),
+            add_prefix_space: add_prefix_space.into(),
+            device: Device::cuda_if_available(),
+        }
+    }
+}
+
+/// # Abstraction that holds one particular multiple choice model, for any of the supported models
+pub enum MultipleChoiceOption {
+    /// Bert for Multiple Choice
+    Bert(BertForMultipleChoice),
+    /// Roberta for Multiple Choice
+    Roberta(RobertaForMultipleChoice),
+    /// XLMRoberta for Multiple Choice
+    XLMRoberta(RobertaForMultipleChoice),
+    /// Albert for Multiple Choice
+    Albert(AlbertForMultipleChoice),
+}
+
+impl MultipleChoiceOption {
+    /// Instantiate a new multiple choice model of the supplied type.
+    ///
+    /// # Arguments
+    ///
+    /// * `model_type` - `ModelType` indicating the model type to load (must match with the actual data to be loaded)
+    /// * `p` - `tch::nn::Path` path to the model file to load (e.g. model.ot)
+    /// * `config` - A configuration (the model type of the configuration must be compatible with the value for
+    /// `model_type`)
+    pub fn new<'p, P>(
+        model_type: ModelType,
+        p: P,
+        config: &ConfigOption,
+    ) -> Result<Self, RustBertError>
+    where
+        P: Borrow<nn::Path<'p>>,
+    {
+        match model_type {
+            ModelType::Bert => {
+                if let ConfigOption::Bert(config) = config {
+                    Ok(MultipleChoiceOption::Bert(BertForMultipleChoice::new(
+                        p, config,
+                    )))
+                } else {
+                    Err(RustBertError::InvalidConfigurationError(
+                        "You can only supply a BertConfig for Bert!".to_string(),
+                    ))
+                }
+            }
+            ModelType::Roberta => {
+                if let ConfigOption::Bert(config) = config {
+                    Ok(MultipleChoiceOption::Roberta(
+                        RobertaForMultipleChoice::new(p, config),
+                    ))
+                } else {
+                    Err(RustBertError::InvalidConfigurationError(
+                        "You can only supply a BertConfig for Roberta!".to_string(),
+                    ))
+                }
+            }
+            ModelType::XLMRoberta => {
+                if let ConfigOption::Bert(config) = config {
+                    Ok(MultipleChoiceOption::XLMRoberta(
+                        RobertaForMultipleChoice::new(p, config),
+                    ))
+                } else {
+                    Err(RustBertError::InvalidConfigurationError(
+                        "You can only supply a BertConfig for XLMRoberta!".to_string(),
+                    ))
+                }
+            }
+            ModelType::Albert => {
+                if let ConfigOption::Albert(config) = config {
+                    Ok(MultipleChoiceOption::Albert(AlbertForMultipleChoice::new(
+                        p, config,
+                    )))
+                } else {
+                    Err(RustBertError::InvalidConfigurationError(
+                        "You can only supply an AlbertConfig for Albert!".to_string(),
+                    ))
+                }
+            }
+            _ => Err(RustBertError::InvalidConfigurationError(format!(
+                "Multiple choice not implemented for {:?}!",
+                model_type
+            ))),
+        }
+    }
+
+    /// Returns the `ModelType` for this MultipleChoiceOption
+    pub fn model_type(&self) -> ModelType {
+        match *self {
+            Self::Bert(_) => ModelType::Bert,
+            Self::Roberta(_) => ModelType::Roberta,
+            Self::XLMRoberta(_) => ModelType::XLMRoberta,
+            Self::Albert(_) => ModelType::Albert,
+        }
+    }
+
+    /// Interface method to forward_t() of the particular models, returning the logits of shape
+    /// (*batch size*, *num_choices*)
+    pub fn forward_t(
+        &self,
+        input_ids: Tensor,
+        mask: Option<Tensor>,
+        token_type_ids: Option<Tensor>,
+        train: bool,
+    ) -> Result<Tensor, RustBertError> {
+        Ok(match *self {
+            Self::Bert(ref model) => {
+                model
+                    .forward_t(input_ids, mask, token_type_ids, None, train)
+                    .logits
+            }
+            Self::Roberta(ref model) | Self::XLMRoberta(ref model) => {
+                model.forward_t(input_ids, mask, None, None, train).logits
+            }
+            Self::Albert(ref model) => {
+                model
+                    .forward_t(Some(input_ids), mask, token_type_ids, None, None, train)?
+                    .logits
+            }
+        })
+    }
+}
+
+/// # MultipleChoiceModel to rank candidate answers
+pub struct MultipleChoiceModel {
+    tokenizer: TokenizerOption,
+    multiple_choice_model: MultipleChoiceOption,
+    var_store: VarStore,
+}
+
+impl MultipleChoiceModel {
+    /// Build a new `MultipleChoiceModel`
+    ///
+    /// # Arguments
+    ///
+    /// * `config` - `MultipleChoiceConfig` object containing the resource references (model, vocabulary, configuration) and device placement (CPU/GPU)
+    pub fn new(config: MultipleChoiceConfig) -> Result<MultipleChoiceModel, RustBertError> {
+        let config_path = config.config_resource.get_local_path()?;
+        let vocab_path = config.vocab_resource.get_local_path()?;
+        let weights_path = config.model_resource.get_local_path()?;
+        let merges_path = if let Some(merges_resource) = &config.merges_resource {
+            Some(merges_resource.get_local_path()?)
+        } else {
+            None
+        };
+
+        let tokenizer = TokenizerOption::from_file(
+            config.model_type,
+            vocab_path.to_str().unwrap(),
+            merges_path.as_deref().map(|path| path.to_str().unwrap()),
+            config.lower_case,
+            config.strip_accents,
+            config.add_prefix_space,
+        )?;
+        let mut var_store = VarStore::new(config.device);
+        let model_config = ConfigOption::try_from_file(config.model_type, config_path)?;
+        tokenizer.check_model_compatibility(config.model_type, model_config.get_vocab_size())?;
+        let multiple_choice_model =
+            MultipleChoiceOption::new(config.model_type, &var_store.root(), &model_config)?;
+        var_store.load(weights_path)?;
+        Ok(MultipleChoiceModel {
+            tokenizer,
+            multiple_choice_model,
+            var_store,
+        })
+    }
+
+    fn prepare_for_model(
+        &self,
+        input: &MultipleChoiceInput,
+        max_len: usize,
+    ) -> (Tensor, Tensor, Tensor) {
+        let text_pair_list = input
+            .choices
+            .iter()
+            .map(|choice| (input.context.as_str(), choice.as_str()))
+            .collect::<Vec<(&str, &str)>>();
+        let tokenized_input: Vec<TokenizedInput> = self.tokenizer.encode_pair_list(
+            text_pair_list.as_ref(),
+            max_len,
+            &TruncationStrategy::OnlyFirst,
+            0,
+        );
+        let max_len = tokenized_input
+            .iter()
+            .map(|input| input.token_ids.len())
+            .max()
+            .unwrap();
+        let pad_id = self
+            .tokenizer
+            .get_pad_id()
+            .expect("The Tokenizer used for multiple choice should contain a PAD id");
+
+        let pad = |values: &[i64], pad_value: i64| {
+            let mut values = values.to_vec();
+            values.extend(vec![pad_value; max_len - values.len()]);
+            Tensor::of_slice(&values)
+        };
+        let input_ids = tokenized_input
+            .iter()
+            .map(|input| pad(&input.token_ids, pad_id))
+            .collect::<Vec<Tensor>>();
+        let token_type_ids = tokenized_input
+            .iter()
+            .map(|input| {
+                let segment_ids = input
+                    .segment_ids
+                    .iter()
+                    .map(|&segment_id| segment_id as i64)
+                    .collect::<Vec<i64>>();
+                pad(&segment_ids, 0)
+            })
+            .collect::<Vec<Tensor>>();
+        let mask = tokenized_input
+            .iter()
+            .map(|input| pad(&vec![1; input.token_ids.len()], 0))
+            .collect::<Vec<Tensor>>();
+
+        let device = self.var_store.device();
+        (
+            Tensor::stack(&input_ids, 0).unsqueeze(0).to(device),
+            Tensor::stack(&mask, 0)
+                .unsqueeze(0)
+                .to_kind(Int64)
+                .to(device),
+            Tensor::stack(&token_type_ids, 0).unsqueeze(0).to(device),
+        )
+    }
+
+    /// Scores the choices of each input and returns them ranked by decreasing probability
+    ///
+    /// # Arguments
+    ///
+    /// * `inputs` - `&[MultipleChoiceInput]` Array of contexts with their candidate choices
+    /// * `max_len` - `usize` maximum length of the encoded (context, choice) pairs. The context is truncated first.
+    ///
+    /// # Returns
+    ///
+    /// * `Vec<Vec<Choice>>` containing the ranked choices for each input
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// # fn main() -> anyhow::Result<()> {
+    /// # use rust_bert::pipelines::multiple_choice::{MultipleChoiceInput, MultipleChoiceModel};
+    /// # use rust_bert::pipelines::common::ModelType;
+    /// # use rust_bert::pipelines::multiple_choice::MultipleChoiceConfig;
+    /// # use rust_bert::resources::{LocalResource, Resource};
+    /// # use std::path::PathBuf;
+    /// # let resource = |path: &str| Resource::Local(LocalResource { local_path: PathBuf::from(path) });
+    /// # let config = MultipleChoiceConfig::new(ModelType::Bert, resource("model.ot"), resource("config.json"), resource("vocab.txt"), None, true, None, None);
+    /// let model = MultipleChoiceModel::new(config)?;
+    /// let input = MultipleChoiceInput {
+    ///     context: "A woman is outside with a bucket and a dog. The dog is running around trying to avoid a bath. She"
+    ///         .to_string(),
+    ///     choices: vec![
+    ///         "rinses the bucket off with soap and blow dry the dog's head.".to_string(),
+    ///         "uses a hose to keep it from getting soapy.".to_string(),
+    ///         "gets the dog wet, then it runs away again.".to_string(),
+    ///         "gets into a bath tub with the dog.".to_string(),
+    ///     ],
+    /// };
+    /// let output = model.predict(&[input], 128)?;
+    /// let best_choice = &output[0][0];
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn predict(
+        &self,
+        inputs: &[MultipleChoiceInput],
+        max_len: usize,
+    ) -> Result<Vec<Vec<Choice>>, RustBertError> {
+        let mut output = Vec::with_capacity(inputs.len());
+        for input in inputs {
+            if input.choices.is_empty() {
+                return Err(RustBertError::ValueError(
+                    "At least one choice must be provided for each input".into(),
+                ));
+            }
+            let (input_ids, mask, token_type_ids) = self.prepare_for_model(input, max_len);
+            let scores = no_grad(|| {
+                self.multiple_choice_model
+                    .forward_t(input_ids, Some(mask), Some(token_type_ids), false)
+                    .map(|logits| logits.softmax(-1, Kind::Float).get(0).to(Device::Cpu))
+            })?;
+            let mut choices = input
+                .choices
+                .iter()
+                .enumerate()
+                .map(|(index, text)| Choice {
+                    text: text.clone(),
+                    index,
+                    score: scores.double_value(&[index as i64]),
+                })
+                .collect::<Vec<Choice>>();
+            choices.sort_by(|a, b| b.score.partial_cmp(&a.score).unwrap());
+            output.push(choices);
+        }
+        Ok(output)
+    }
+
+    /// Reloads the weights of the multiple choice model from the resource provided, keeping the tokenizer and
+    /// configuration unchanged. The weights must match the architecture of the model currently loaded.
+    ///
+    /// # Arguments
+    ///
+    /// * `weights_resource` - `Resource` pointing to the new model weights (e.g. model.ot)
+    pub fn reload_weights(&mut self, weights_resource: &Resource) -> Result<(), RustBertError> {
+        reload_var_store(&mut self.var_store, weights_resource)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::resources::LocalResource;
+    use std::path::PathBuf;
+
+    #[test]
+    #[ignore] // no need to run, compilation is enough to verify it is Send
+    fn test() {
+        let resource = |path: &str| {
+            Resource::Local(LocalResource {
+                local_path: PathBuf::from(path),
+            })
+        };
+        let config = MultipleChoiceConfig::new(
+            ModelType::Bert,
+            resource("model.ot"),
+            resource("config.json"),
+            resource("vocab.txt"),
+            None,
+            true,
+            None,
+            None,
+        );
+        let _: Box<dyn Send> = Box::new(MultipleChoiceModel::new(config));
+    }
+}
@@ -118,6 +118,7 @@
 //! Dutch| XLM_ROBERTA_NER_NL |
 
 use crate::common::error::RustBertError;
+use crate::common::resources::Resource;
 use crate::pipelines::token_classification::{TokenClassificationConfig, TokenClassificationModel};
 
 #[derive(Debug)]
@@ -203,6 +204,16 @@ impl NERModel {
             })
             .collect()
     }
+
+    /// Reloads the weights of the underlying token classification model from the resource provided.
+    ///
+    /// # Arguments
+    ///
+    /// * `weights_resource` - `Resource` pointing to the new model weights (e.g. model.ot)
+    pub fn reload_weights(&mut self, weights_resource: &Resource) -> Result<(), RustBertError> {
+        self.token_classification_model
+            .reload_weights(weights_resource)
+    }
 }
 #[cfg(test)]
 mod test {
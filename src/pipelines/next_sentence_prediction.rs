@@ -0,0 +1,293 @@
+// Copyright 2021 Guillaume Becquin
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//     http://www.apache.org/licenses/LICENSE-2.0
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! # Next sentence prediction pipeline
+//! Scores the probability that a sentence follows another one using the next sentence prediction head of BERT. The
+//! pre-trained head of the base BERT model is used by default. The scores of consecutive sentences can serve as a
+//! coherence measure, or to detect topic shifts when segmenting a document (a low score between two consecutive
+//! sentences suggests a boundary).
+//!
+//! ```no_run
+//! # fn main() -> anyhow::Result<()> {
+//! use rust_bert::pipelines::next_sentence_prediction::NextSentencePredictionModel;
+//!
+//! let nsp_model = NextSentencePredictionModel::new(Default::default())?;
+//! let input = [
+//!     ("The cat sat on the mat.", "It then fell asleep."),
+//!     ("The cat sat on the mat.", "Interest rates rose by 0.5%."),
+//! ];
+//! let is_next_probabilities = nsp_model.predict(&input, 128);
+//! # Ok(())
+//! # }
+//! ```
+
+use crate::bert::{
+    BertConfig, BertConfigResources, BertForNextSentencePrediction, BertModelResources,
+    BertVocabResources,
+};
+use crate::common::error::RustBertError;
+use crate::common::resources::{RemoteResource, Resource};
+use crate::common::weights::reload_var_store;
+use crate::pipelines::common::{ModelType, TokenizerOption};
+use crate::Config;
+use rust_tokenizers::tokenizer::TruncationStrategy;
+use rust_tokenizers::TokenizedInput;
+use tch::kind::Kind::Int64;
+use tch::nn::VarStore;
+use tch::{no_grad, Device, Kind, Tensor};
+
+/// # Configuration for NextSentencePredictionModel
+/// Contains information regarding the model to load and device to place the model on.
+pub struct NextSentencePredictionConfig {
+    /// Model weights resource (default: pretrained BERT base uncased model)
+    pub model_resource: Resource,
+    /// Config resource (default: pretrained BERT base uncased model)
+    pub config_resource: Resource,
+    /// Vocab resource (default: pretrained BERT base uncased model)
+    pub vocab_resource: Resource,
+    /// Automatically lower case all input upon tokenization (assumes a lower-cased model)
+    pub lower_case: bool,
+    /// Flag indicating if the tokenizer should strip accents (normalization)
+    pub strip_accents: Option<bool>,
+    /// Device to place the model on (default: CUDA/GPU when available)
+    pub device: Device,
+}
+
+impl Default for NextSentencePredictionConfig {
+    /// Provides a BERT base uncased model (English) with its pre-trained next sentence prediction head
+    fn default() -> NextSentencePredictionConfig {
+        NextSentencePredictionConfig {
+            model_resource: Resource::Remote(RemoteResource::from_pretrained(
+                BertModelResources::BERT,
+            )),
+            config_resource: Resource::Remote(RemoteResource::from_pretrained(
+                BertConfigResources::BERT,
+            )),
+            vocab_resource: Resource::Remote(RemoteResource::from_pretrained(
+                BertVocabResources::BERT,
+            )),
+            lower_case: true,
+            strip_accents: None,
+            device: Device::cuda_if_available(),
+        }
+    }
+}
+
+/// # NextSentencePredictionModel to score sentence pairs
+pub struct NextSentencePredictionModel {
+    tokenizer: TokenizerOption,
+    nsp_model: BertForNextSentencePrediction,
+    var_store: VarStore,
+}
+
+impl NextSentencePredictionModel {
+    /// Build a new `NextSentencePredictionModel`
+    ///
+    /// # Arguments
+    ///
+    /// * `config` - `NextSentencePredictionConfig` object containing the resource references (model, vocabulary, configuration) and device placement (CPU/GPU)
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// # fn main() -> anyhow::Result<()> {
+    /// use rust_bert::pipelines::next_sentence_prediction::NextSentencePredictionModel;
+    ///
+    /// let nsp_model = NextSentencePredictionModel::new(Default::default())?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn new(
+        config: NextSentencePredictionConfig,
+    ) -> Result<NextSentencePredictionModel, RustBertError> {
+        let config_path = config.config_resource.get_local_path()?;
+        let vocab_path = config.vocab_resource.get_local_path()?;
+        let weights_path = config.model_resource.get_local_path()?;
+
+        let tokenizer = TokenizerOption::from_file(
+            ModelType::Bert,
+            vocab_path.to_str().unwrap(),
+            None,
+            config.lower_case,
+            config.strip_accents,
+            None,
+        )?;
+        let mut var_store = VarStore::new(config.device);
+        let model_config = BertConfig::try_from_file(config_path)?;
+        tokenizer.check_model_compatibility(ModelType::Bert, model_config.vocab_size)?;
+        let nsp_model = BertForNextSentencePrediction::new(&var_store.root(), &model_config);
+        var_store.load(weights_path)?;
+        Ok(NextSentencePredictionModel {
+            tokenizer,
+            nsp_model,
+            var_store,
+        })
+    }
+
+    fn prepare_for_model(
+        &self,
+        input: &[(&str, &str)],
+        max_len: usize,
+    ) -> (Tensor, Tensor, Tensor) {
+        let tokenized_input: Vec<TokenizedInput> =
+            self.tokenizer
+                .encode_pair_list(input, max_len, &TruncationStrategy::LongestFirst, 0);
+        let max_len = tokenized_input
+            .iter()
+            .map(|input| input.token_ids.len())
+            .max()
+            .unwrap();
+        let pad_id = self
+            .tokenizer
+            .get_pad_id()
+            .expect("The Tokenizer used for next sentence prediction should contain a PAD id");
+
+        let pad = |values: &[i64], pad_value: i64| {
+            let mut values = values.to_vec();
+            values.extend(vec![pad_value; max_len - values.len()]);
+            Tensor::of_slice(&values)
+        };
+        let input_ids = tokenized_input
+            .iter()
+            .map(|input| pad(&input.token_ids, pad_id))
+            .collect::<Vec<Tensor>>();
+        let token_type_ids = tokenized_input
+            .iter()
+            .map(|input| {
+                let segment_ids = input
+                    .segment_ids
+                    .iter()
+                    .map(|&segment_id| segment_id as i64)
+                    .collect::<Vec<i64>>();
+                pad(&segment_ids, 0)
+            })
+            .collect::<Vec<Tensor>>();
+        let mask = tokenized_input
+            .iter()
+            .map(|input| pad(&vec![1; input.token_ids.len()], 0))
+            .collect::<Vec<Tensor>>();
+
+        let device = self.var_store.device();
+        (
+            Tensor::stack(&input_ids, 0).to(device),
+            Tensor::stack(&mask, 0).to_kind(Int64).to(device),
+            Tensor::stack(&token_type_ids, 0).to(device),
+        )
+    }
+
+    /// Returns for each (first sentence, second sentence) pair the probability that the second sentence follows the
+    /// first one.
+    ///
+    /// # Arguments
+    ///
+    /// * `input` - `&[(&str, &str)]` Array of sentence pairs
+    /// * `max_len` - `usize` maximum length of the encoded pairs. The longest sentence of the pair is truncated first.
+    ///
+    /// # Returns
+    ///
+    /// * `Vec<f64>` containing the *is next* probability for each pair
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// # fn main() -> anyhow::Result<()> {
+    /// # use rust_bert::pipelines::next_sentence_prediction::NextSentencePredictionModel;
+    /// let nsp_model = NextSentencePredictionModel::new(Default::default())?;
+    /// let input = [("The cat sat on the mat.", "It then fell asleep.")];
+    /// let output = nsp_model.predict(&input, 128);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn predict(&self, input: &[(&str, &str)], max_len: usize) -> Vec<f64> {
+        if input.is_empty() {
+            return vec![];
+        }
+        let (input_ids, mask, token_type_ids) = self.prepare_for_model(input, max_len);
+        let output = no_grad(|| {
+            let logits = self
+                .nsp_model
+                .forward_t(
+                    Some(input_ids),
+                    Some(mask),
+                    Some(token_type_ids),
+                    None,
+                    None,
+                    false,
+                )
+                .logits;
+            logits.softmax(-1, Kind::Float).select(1, 0).to(Device::Cpu)
+        });
+        Vec::<f64>::from(output)
+    }
+
+    /// Returns the probability that each sentence follows the previous one, for a sequence of sentences (e.g. the
+    /// sentences of a document). The output has one element less than the input.
+    ///
+    /// # Arguments
+    ///
+    /// * `sentences` - `&[&str]` Array of consecutive sentences
+    /// * `max_len` - `usize` maximum length of the encoded pairs
+    ///
+    /// # Returns
+    ///
+    /// * `Vec<f64>` containing the *is next* probability of each pair of consecutive sentences
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// # fn main() -> anyhow::Result<()> {
+    /// # use rust_bert::pipelines::next_sentence_prediction::NextSentencePredictionModel;
+    /// let nsp_model = NextSentencePredictionModel::new(Default::default())?;
+    /// let sentences = [
+    ///     "The cat sat on the mat.",
+    ///     "It then fell asleep.",
+    ///     "Interest rates rose by 0.5%.",
+    /// ];
+    /// let scores = nsp_model.predict_consecutive(&sentences, 128);
+    /// // Split the document where the probability falls below a threshold
+    /// let boundaries: Vec<usize> = scores
+    ///     .iter()
+    ///     .enumerate()
+    ///     .filter(|(_, &score)| score < 0.5)
+    ///     .map(|(index, _)| index + 1)
+    ///     .collect();
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn predict_consecutive(&self, sentences: &[&str], max_len: usize) -> Vec<f64> {
+        let pairs = sentences
+            .windows(2)
+            .map(|window| (window[0], window[1]))
+            .collect::<Vec<(&str, &str)>>();
+        self.predict(&pairs, max_len)
+    }
+
+    /// Reloads the weights of the model from the resource provided, keeping the tokenizer and configuration unchanged.
+    ///
+    /// # Arguments
+    ///
+    /// * `weights_resource` - `Resource` pointing to the new model weights (e.g. model.ot)
+    pub fn reload_weights(&mut self, weights_resource: &Resource) -> Result<(), RustBertError> {
+        reload_var_store(&mut self.var_store, weights_resource)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    #[ignore] // no need to run, compilation is enough to verify it is Send
+    fn test() {
+        let config = NextSentencePredictionConfig::default();
+        let _: Box<dyn Send> = Box::new(NextSentencePredictionModel::new(config));
+    }
+}
@@ -0,0 +1,256 @@
+// Copyright 2021 Guillaume Becquin
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//     http://www.apache.org/licenses/LICENSE-2.0
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! # Open-domain question answering pipeline
+//! Answers questions over a passage collection in two stages:
+//! - a `rag::Retriever` (e.g. a dense `EmbeddingRetriever`) selects the most relevant passages for the question
+//! - an extractive reader (`QuestionAnsweringModel`) extracts answer spans from each retrieved passage
+//!
+//! The score of each answer combines the reader and retriever scores with a `ScoreFusion` strategy. Identical answers
+//! extracted from several passages are merged: the best scoring occurrence is kept, and the identifiers of all the
+//! passages containing the answer are returned as supporting passages.
+//!
+//! ```no_run
+//! # fn main() -> anyhow::Result<()> {
+//! use rust_bert::pipelines::feature_extraction::FeatureExtractionModel;
+//! use rust_bert::pipelines::open_domain_qa::OpenDomainQaModel;
+//! use rust_bert::pipelines::rag::{EmbeddingRetriever, Passage};
+//!
+//! let mut retriever = EmbeddingRetriever::new(FeatureExtractionModel::new(Default::default())?);
+//! retriever.add_passages(vec![
+//!     Passage::new("amy", "Amy lives in Amsterdam."),
+//!     Passage::new("eric", "Eric moved to The Hague last year."),
+//! ])?;
+//! let qa_model = OpenDomainQaModel::new(Default::default(), Box::new(retriever))?;
+//! let answers = qa_model.predict(&["Where does Eric live?"])?;
+//! for answer in &answers[0] {
+//!     println!("{} ({:.2}, from {})", answer.answer, answer.score, answer.passage.passage.id);
+//! }
+//! # Ok(())
+//! # }
+//! ```
+
+use crate::common::error::RustBertError;
+use crate::pipelines::question_answering::{
+    QaInput, QuestionAnsweringConfig, QuestionAnsweringModel,
+};
+use crate::pipelines::rag::{RetrievedPassage, Retriever};
+use std::collections::HashMap;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+/// # Combination of the reader and retriever scores of an answer
+pub enum ScoreFusion {
+    /// Reader score only
+    Reader,
+    /// Weighted sum of the reader and retriever scores: `(1 - retriever_weight) * reader + retriever_weight * retriever`
+    Linear { retriever_weight: f64 },
+    /// Product of the reader score and of the retriever score mapped to [0, 1] (`(1 + retriever) / 2` for cosine similarities)
+    Product,
+}
+
+impl ScoreFusion {
+    /// Combines the reader and retriever scores of an answer
+    pub fn fuse(&self, reader_score: f64, retriever_score: f64) -> f64 {
+        match *self {
+            ScoreFusion::Reader => reader_score,
+            ScoreFusion::Linear { retriever_weight } => {
+                (1.0 - retriever_weight) * reader_score + retriever_weight * retriever_score
+            }
+            ScoreFusion::Product => reader_score * ((1.0 + retriever_score) / 2.0).max(0.0),
+        }
+    }
+}
+
+/// # Configuration for open-domain question answering
+pub struct OpenDomainQaConfig {
+    /// Configuration of the extractive reader (default: DistilBERT fine-tuned on SQuAD)
+    pub reader_config: QuestionAnsweringConfig,
+    /// Number of passages retrieved for each question (default: 5)
+    pub num_passages: usize,
+    /// Number of answers extracted from each passage by the reader (default: 2)
+    pub answers_per_passage: i64,
+    /// Number of answers returned for each question (default: 3)
+    pub top_k: usize,
+    /// Combination of the reader and retriever scores (default: linear, with a retriever weight of 0.3)
+    pub score_fusion: ScoreFusion,
+    /// Batch size of the reader (default: 32)
+    pub batch_size: usize,
+}
+
+impl Default for OpenDomainQaConfig {
+    fn default() -> OpenDomainQaConfig {
+        OpenDomainQaConfig {
+            reader_config: QuestionAnsweringConfig::default(),
+            num_passages: 5,
+            answers_per_passage: 2,
+            top_k: 3,
+            score_fusion: ScoreFusion::Linear {
+                retriever_weight: 0.3,
+            },
+            batch_size: 32,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+/// # Answer extracted from the passage collection
+pub struct OpenDomainAnswer {
+    /// Answer span
+    pub answer: String,
+    /// Fused score of the answer
+    pub score: f64,
+    /// Score of the answer given by the reader
+    pub reader_score: f64,
+    /// Passage the answer was extracted from, with its retriever score
+    pub passage: RetrievedPassage,
+    /// Start position of the answer span in the passage text
+    pub start: usize,
+    /// End position of the answer span in the passage text
+    pub end: usize,
+    /// Identifiers of all the retrieved passages the reader extracted this answer from
+    pub supporting_passages: Vec<String>,
+}
+
+/// # OpenDomainQaModel to answer questions from a passage collection
+pub struct OpenDomainQaModel {
+    retriever: Box<dyn Retriever>,
+    reader: QuestionAnsweringModel,
+    num_passages: usize,
+    answers_per_passage: i64,
+    top_k: usize,
+    score_fusion: ScoreFusion,
+    batch_size: usize,
+}
+
+impl OpenDomainQaModel {
+    /// Build a new `OpenDomainQaModel`
+    ///
+    /// # Arguments
+    ///
+    /// * `config` - `OpenDomainQaConfig` object containing the reader configuration and retrieval settings
+    /// * `retriever` - `Retriever` returning the passages for each question
+    pub fn new(
+        config: OpenDomainQaConfig,
+        retriever: Box<dyn Retriever>,
+    ) -> Result<OpenDomainQaModel, RustBertError> {
+        let reader = QuestionAnsweringModel::new(config.reader_config)?;
+        Ok(OpenDomainQaModel {
+            retriever,
+            reader,
+            num_passages: config.num_passages,
+            answers_per_passage: config.answers_per_passage,
+            top_k: config.top_k,
+            score_fusion: config.score_fusion,
+            batch_size: config.batch_size,
+        })
+    }
+
+    /// Answers questions from the passages retrieved for each of them
+    ///
+    /// # Arguments
+    ///
+    /// * `questions` - `&[&str]` Array of questions
+    ///
+    /// # Returns
+    ///
+    /// * `Vec<Vec<OpenDomainAnswer>>` containing up to `top_k` answers for each question, sorted by decreasing score
+    pub fn predict(&self, questions: &[&str]) -> Result<Vec<Vec<OpenDomainAnswer>>, RustBertError> {
+        let mut output = Vec::with_capacity(questions.len());
+        for question in questions {
+            let passages = self.retriever.retrieve(question, self.num_passages)?;
+            if passages.is_empty() {
+                output.push(vec![]);
+                continue;
+            }
+            let qa_inputs = passages
+                .iter()
+                .map(|retrieved| QaInput {
+                    question: question.to_string(),
+                    context: retrieved.passage.text.clone(),
+                })
+                .collect::<Vec<_>>();
+            let reader_answers =
+                self.reader
+                    .predict(&qa_inputs, self.answers_per_passage, self.batch_size);
+
+            let mut answers: Vec<OpenDomainAnswer> = vec![];
+            let mut positions: HashMap<String, usize> = HashMap::new();
+            for (retrieved, passage_answers) in passages.iter().zip(reader_answers) {
+                for answer in passage_answers {
+                    let key = normalize_answer(&answer.answer);
+                    if key.is_empty() {
+                        continue;
+                    }
+                    let candidate = OpenDomainAnswer {
+                        score: self.score_fusion.fuse(answer.score, retrieved.score),
+                        reader_score: answer.score,
+                        passage: retrieved.clone(),
+                        start: answer.start,
+                        end: answer.end,
+                        supporting_passages: vec![retrieved.passage.id.clone()],
+                        answer: answer.answer,
+                    };
+                    match positions.get(&key) {
+                        Some(&position) => {
+                            let existing = &mut answers[position];
+                            let mut supporting_passages = existing.supporting_passages.clone();
+                            if !supporting_passages.contains(&retrieved.passage.id) {
+                                supporting_passages.push(retrieved.passage.id.clone());
+                            }
+                            if candidate.score > existing.score {
+                                *existing = candidate;
+                            }
+                            existing.supporting_passages = supporting_passages;
+                        }
+                        None => {
+                            positions.insert(key, answers.len());
+                            answers.push(candidate);
+                        }
+                    }
+                }
+            }
+            answers
+                .sort_by(|answer_1, answer_2| answer_2.score.partial_cmp(&answer_1.score).unwrap());
+            answers.truncate(self.top_k);
+            output.push(answers);
+        }
+        Ok(output)
+    }
+}
+
+fn normalize_answer(answer: &str) -> String {
+    answer
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .map(str::to_lowercase)
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    #[ignore] // no need to run, compilation is enough to verify it is Send
+    fn test() {
+        struct DummyRetriever;
+        impl Retriever for DummyRetriever {
+            fn retrieve(&self, _: &str, _: usize) -> Result<Vec<RetrievedPassage>, RustBertError> {
+                Ok(vec![])
+            }
+        }
+        let _: Box<dyn Send> = Box::new(OpenDomainQaModel::new(
+            OpenDomainQaConfig::default(),
+            Box::new(DummyRetriever),
+        ));
+    }
+}
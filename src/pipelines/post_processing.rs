@@ -0,0 +1,242 @@
+// Copyright 2021 Guillaume Becquin
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//     http://www.apache.org/licenses/LICENSE-2.0
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! # Output post-processing
+//! Post-processors are applied to the generated texts before they are returned by the summarization, translation and
+//! text generation pipelines. They are registered on a model with `add_post_processor` and run in registration order.
+//! Available post-processors:
+//! - `Detruecaser`: capitalizes the first letter of each sentence (e.g. for models trained on lowercased corpora)
+//! - `RegexReplacer`: replaces the matches of a regular expression
+//! - `ProfanityFilter`: masks the words of a block list
+//!
+//! Any closure taking a `&str` and returning a `String` can also be registered as a post-processor.
+//!
+//! ```no_run
+//! # fn main() -> anyhow::Result<()> {
+//! use rust_bert::pipelines::post_processing::{Detruecaser, ProfanityFilter, RegexReplacer};
+//! use rust_bert::pipelines::summarization::SummarizationModel;
+//!
+//! let mut summarization_model = SummarizationModel::new(Default::default())?;
+//! summarization_model.add_post_processor(RegexReplacer::new(r"\s+([.,;!?])", "$1")?);
+//! summarization_model.add_post_processor(Detruecaser::default());
+//! summarization_model.add_post_processor(ProfanityFilter::new(&["darn"]));
+//! summarization_model.add_post_processor(|text: &str| text.replace("U.S.", "United States"));
+//! # Ok(())
+//! # }
+//! ```
+
+use crate::common::error::RustBertError;
+use regex::Regex;
+use std::collections::HashSet;
+
+/// # Transformation applied to the generated texts
+pub trait PostProcessor: Send + Sync {
+    /// Returns the post-processed text
+    fn process(&self, text: &str) -> String;
+}
+
+impl<F> PostProcessor for F
+where
+    F: Fn(&str) -> String + Send + Sync,
+{
+    fn process(&self, text: &str) -> String {
+        self(text)
+    }
+}
+
+#[derive(Default)]
+/// # Sequence of post-processors, applied in order
+pub struct PostProcessors {
+    post_processors: Vec<Box<dyn PostProcessor>>,
+}
+
+impl PostProcessors {
+    /// Creates an empty sequence of post-processors
+    pub fn new() -> PostProcessors {
+        PostProcessors::default()
+    }
+
+    /// Appends a post-processor to the sequence
+    pub fn add<P: PostProcessor + 'static>(&mut self, post_processor: P) {
+        self.post_processors.push(Box::new(post_processor));
+    }
+
+    pub fn len(&self) -> usize {
+        self.post_processors.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.post_processors.is_empty()
+    }
+
+    /// Applies the post-processors to a text
+    pub fn process(&self, text: String) -> String {
+        self.post_processors
+            .iter()
+            .fold(text, |text, post_processor| post_processor.process(&text))
+    }
+
+    /// Applies the post-processors to a batch of texts
+    pub fn process_batch(&self, texts: Vec<String>) -> Vec<String> {
+        if self.is_empty() {
+            return texts;
+        }
+        texts.into_iter().map(|text| self.process(text)).collect()
+    }
+}
+
+#[derive(Debug, Clone)]
+/// # Capitalization of the first letter of each sentence
+pub struct Detruecaser {
+    /// Characters ending a sentence (default: `.`, `!` and `?`)
+    pub sentence_terminators: Vec<char>,
+    /// Capitalize the standalone English pronoun "i" (default: true)
+    pub capitalize_pronoun_i: bool,
+}
+
+impl Default for Detruecaser {
+    fn default() -> Detruecaser {
+        Detruecaser {
+            sentence_terminators: vec!['.', '!', '?'],
+            capitalize_pronoun_i: true,
+        }
+    }
+}
+
+impl PostProcessor for Detruecaser {
+    fn process(&self, text: &str) -> String {
+        let mut output = String::with_capacity(text.len());
+        let mut sentence_start = true;
+        for c in text.chars() {
+            if sentence_start && c.is_alphanumeric() {
+                output.extend(c.to_uppercase());
+                sentence_start = false;
+            } else {
+                output.push(c);
+                if self.sentence_terminators.contains(&c) {
+                    sentence_start = true;
+                }
+            }
+        }
+        if self.capitalize_pronoun_i {
+            output = map_words(&output, |word| match word {
+                "i" => Some("I".to_string()),
+                _ => word
+                    .strip_prefix("i'")
+                    .map(|contraction| format!("I'{}", contraction)),
+            });
+        }
+        output
+    }
+}
+
+#[derive(Debug, Clone)]
+/// # Replacement of the matches of a regular expression
+pub struct RegexReplacer {
+    pattern: Regex,
+    replacement: String,
+}
+
+impl RegexReplacer {
+    /// Creates a new `RegexReplacer`
+    ///
+    /// # Arguments
+    ///
+    /// * `pattern` - regular expression to match (`regex` crate syntax)
+    /// * `replacement` - replacement text, that can refer to the capture groups of the pattern (e.g. `$1`)
+    pub fn new(pattern: &str, replacement: &str) -> Result<RegexReplacer, RustBertError> {
+        let pattern = Regex::new(pattern).map_err(|error| {
+            RustBertError::ValueError(format!("Invalid post-processing pattern: {}", error))
+        })?;
+        Ok(RegexReplacer {
+            pattern,
+            replacement: replacement.to_string(),
+        })
+    }
+}
+
+impl PostProcessor for RegexReplacer {
+    fn process(&self, text: &str) -> String {
+        self.pattern
+            .replace_all(text, self.replacement.as_str())
+            .into_owned()
+    }
+}
+
+#[derive(Debug, Clone)]
+/// # Masking of the words of a block list
+/// Words are matched case-insensitively, and each of their characters is replaced by the mask character.
+pub struct ProfanityFilter {
+    words: HashSet<String>,
+    /// Character replacing the characters of the masked words (default: `*`)
+    pub mask: char,
+}
+
+impl ProfanityFilter {
+    /// Creates a new `ProfanityFilter` masking the words provided
+    pub fn new<S: AsRef<str>>(words: &[S]) -> ProfanityFilter {
+        ProfanityFilter {
+            words: words
+                .iter()
+                .map(|word| word.as_ref().to_lowercase())
+                .collect(),
+            mask: '*',
+        }
+    }
+}
+
+impl PostProcessor for ProfanityFilter {
+    fn process(&self, text: &str) -> String {
+        map_words(text, |word| {
+            if self.words.contains(&word.to_lowercase()) {
+                Some(
+                    std::iter::repeat(self.mask)
+                        .take(word.chars().count())
+                        .collect(),
+                )
+            } else {
+                None
+            }
+        })
+    }
+}
+
+// Applies a replacement to the words (sequences of alphanumeric characters and apostrophes) of a text, keeping the
+// words for which the replacement returns None
+fn map_words<F>(text: &str, replacement: F) -> String
+where
+    F: Fn(&str) -> Option<String>,
+{
+    let mut output = String::with_capacity(text.len());
+    let mut word_start = None;
+    for (position, c) in text
+        .char_indices()
+        .chain(std::iter::once((text.len(), ' ')))
+    {
+        let is_word_character = c.is_alphanumeric() || c == '\'';
+        match (word_start, is_word_character) {
+            (None, true) => word_start = Some(position),
+            (Some(start), false) => {
+                let word = &text[start..position];
+                match replacement(word) {
+                    Some(replaced) => output.push_str(&replaced),
+                    None => output.push_str(word),
+                }
+                word_start = None;
+            }
+            _ => {}
+        }
+        if !is_word_character && position < text.len() {
+            output.push(c);
+        }
+    }
+    output
+}
@@ -0,0 +1,304 @@
+// Copyright 2021 Guillaume Becquin
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//     http://www.apache.org/licenses/LICENSE-2.0
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! # Prompt templates
+//! Templates for the prompts of instruction models, with named variables written `{name}` (literal braces are
+//! escaped as `{{` and `}}`). A template can include:
+//! - few-shot examples: a variable of the template is filled with examples, each rendered with its own template
+//! - a `PromptFormat` wrapping the rendered prompt in the instruction format expected by the model
+//!
+//! Templates are rendered with `render`, or passed with their variables to
+//! `TextGenerationModel::generate_from_template`.
+//!
+//! ```no_run
+//! # fn main() -> anyhow::Result<()> {
+//! use rust_bert::pipelines::prompt_template::{FewShotExamples, PromptFormat, PromptTemplate};
+//! use rust_bert::pipelines::text_generation::TextGenerationModel;
+//!
+//! let examples = FewShotExamples::new(PromptTemplate::new("Review: {review}\nSentiment: {sentiment}")?)
+//!     .with_example(&[("review", "Great food!"), ("sentiment", "positive")])
+//!     .with_example(&[("review", "Cold and bland."), ("sentiment", "negative")]);
+//! let template = PromptTemplate::new("Classify the reviews.\n\n{examples}\n\nReview: {review}\nSentiment:")?
+//!     .with_examples("examples", examples)?
+//!     .with_format(PromptFormat::Alpaca);
+//!
+//! let model = TextGenerationModel::new(Default::default())?;
+//! let output = model.generate_from_template(&template, &[&[("review", "Lovely staff.")]])?;
+//! # Ok(())
+//! # }
+//! ```
+
+use crate::common::error::RustBertError;
+use std::collections::HashMap;
+
+#[derive(Debug, Clone, PartialEq)]
+enum Segment {
+    Text(String),
+    Variable(String),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+/// # Instruction format wrapping a rendered prompt
+pub enum PromptFormat {
+    /// The prompt is used as is
+    Plain,
+    /// `### Instruction:` / `### Response:` format of Alpaca-style models
+    Alpaca,
+    /// ChatML format, with the prompt as a user message followed by the start of the assistant message
+    ChatMl,
+    /// `[INST] ... [/INST]` format of Llama 2 chat models
+    Llama2,
+    /// Custom prefix and suffix
+    Custom { prefix: String, suffix: String },
+}
+
+impl PromptFormat {
+    /// Wraps a prompt in the format
+    pub fn apply(&self, prompt: &str) -> String {
+        match self {
+            PromptFormat::Plain => prompt.to_string(),
+            PromptFormat::Alpaca => format!("### Instruction:\n{}\n\n### Response:\n", prompt),
+            PromptFormat::ChatMl => format!(
+                "<|im_start|>user\n{}<|im_end|>\n<|im_start|>assistant\n",
+                prompt
+            ),
+            PromptFormat::Llama2 => format!("[INST] {} [/INST]", prompt),
+            PromptFormat::Custom { prefix, suffix } => format!("{}{}{}", prefix, prompt, suffix),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+/// # Few-shot examples filling a variable of a `PromptTemplate`
+pub struct FewShotExamples {
+    template: Vec<Segment>,
+    examples: Vec<HashMap<String, String>>,
+    /// Separator between the rendered examples (default: a blank line)
+    pub separator: String,
+}
+
+impl FewShotExamples {
+    /// Creates an empty set of examples, each example being rendered with the template provided (the examples and
+    /// format of the example template are ignored)
+    pub fn new(template: PromptTemplate) -> FewShotExamples {
+        FewShotExamples {
+            template: template.segments,
+            examples: vec![],
+            separator: "\n\n".to_string(),
+        }
+    }
+
+    /// Adds an example, given as the values of the variables of the example template
+    pub fn with_example(mut self, variables: &[(&str, &str)]) -> FewShotExamples {
+        self.examples.push(
+            variables
+                .iter()
+                .map(|(name, value)| (name.to_string(), value.to_string()))
+                .collect(),
+        );
+        self
+    }
+
+    pub fn len(&self) -> usize {
+        self.examples.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.examples.is_empty()
+    }
+
+    /// Renders the examples, joined by the separator
+    pub fn render(&self) -> Result<String, RustBertError> {
+        let rendered = self
+            .examples
+            .iter()
+            .map(|example| render_segments(&self.template, |name| example.get(name).cloned()))
+            .collect::<Result<Vec<String>, RustBertError>>()?;
+        Ok(rendered.join(&self.separator))
+    }
+}
+
+#[derive(Debug, Clone)]
+/// # Prompt template with named variables
+pub struct PromptTemplate {
+    segments: Vec<Segment>,
+    examples: Option<(String, FewShotExamples)>,
+    format: PromptFormat,
+}
+
+impl PromptTemplate {
+    /// Parses a template
+    ///
+    /// # Arguments
+    ///
+    /// * `template` - template text, with variables written `{name}`. Variable names are made of alphanumeric
+    /// characters and underscores.
+    ///
+    /// # Returns
+    ///
+    /// * `PromptTemplate`, or a `RustBertError::ValueError` for unbalanced braces or invalid variable names
+    pub fn new(template: &str) -> Result<PromptTemplate, RustBertError> {
+        let mut segments = vec![];
+        let mut text = String::new();
+        let mut chars = template.chars().peekable();
+        while let Some(c) = chars.next() {
+            match c {
+                '{' if chars.peek() == Some(&'{') => {
+                    chars.next();
+                    text.push('{');
+                }
+                '}' if chars.peek() == Some(&'}') => {
+                    chars.next();
+                    text.push('}');
+                }
+                '{' => {
+                    let mut name = String::new();
+                    loop {
+                        match chars.next() {
+                            Some('}') => break,
+                            Some(c) if c.is_alphanumeric() || c == '_' => name.push(c),
+                            Some(c) => {
+                                return Err(RustBertError::ValueError(format!(
+                                    "Invalid character '{}' in the name of a template variable",
+                                    c
+                                )));
+                            }
+                            None => {
+                                return Err(RustBertError::ValueError(
+                                    "Unclosed variable in the prompt template".to_string(),
+                                ));
+                            }
+                        }
+                    }
+                    if name.is_empty() {
+                        return Err(RustBertError::ValueError(
+                            "Empty variable name in the prompt template".to_string(),
+                        ));
+                    }
+                    if !text.is_empty() {
+                        segments.push(Segment::Text(std::mem::take(&mut text)));
+                    }
+                    segments.push(Segment::Variable(name));
+                }
+                '}' => {
+                    return Err(RustBertError::ValueError(
+                        "Unmatched '}' in the prompt template, literal braces must be written '}}'"
+                            .to_string(),
+                    ));
+                }
+                c => text.push(c),
+            }
+        }
+        if !text.is_empty() {
+            segments.push(Segment::Text(text));
+        }
+        Ok(PromptTemplate {
+            segments,
+            examples: None,
+            format: PromptFormat::Plain,
+        })
+    }
+
+    /// Fills a variable of the template with few-shot examples
+    ///
+    /// # Arguments
+    ///
+    /// * `variable` - name of the variable receiving the rendered examples
+    /// * `examples` - `FewShotExamples` to render
+    pub fn with_examples(
+        mut self,
+        variable: &str,
+        examples: FewShotExamples,
+    ) -> Result<PromptTemplate, RustBertError> {
+        if !self.variables().contains(&variable) {
+            return Err(RustBertError::ValueError(format!(
+                "The examples variable {} is not part of the template",
+                variable
+            )));
+        }
+        self.examples = Some((variable.to_string(), examples));
+        Ok(self)
+    }
+
+    /// Sets the instruction format wrapping the rendered prompt (default: `PromptFormat::Plain`)
+    pub fn with_format(mut self, format: PromptFormat) -> PromptTemplate {
+        self.format = format;
+        self
+    }
+
+    /// Returns the names of the variables of the template, in order of first appearance
+    pub fn variables(&self) -> Vec<&str> {
+        let mut variables: Vec<&str> = vec![];
+        for segment in &self.segments {
+            if let Segment::Variable(name) = segment {
+                if !variables.contains(&name.as_str()) {
+                    variables.push(name);
+                }
+            }
+        }
+        variables
+    }
+
+    /// Returns the names of the variables to provide to `render`, excluding the few-shot examples variable
+    pub fn input_variables(&self) -> Vec<&str> {
+        let examples_variable = self.examples.as_ref().map(|(name, _)| name.as_str());
+        self.variables()
+            .into_iter()
+            .filter(|&name| Some(name) != examples_variable)
+            .collect()
+    }
+
+    /// Renders the prompt
+    ///
+    /// # Arguments
+    ///
+    /// * `variables` - values of the input variables of the template
+    ///
+    /// # Returns
+    ///
+    /// * `String` prompt, wrapped in the format of the template, or a `RustBertError::ValueError` if a variable is missing
+    pub fn render(&self, variables: &[(&str, &str)]) -> Result<String, RustBertError> {
+        let examples = match &self.examples {
+            Some((name, examples)) => Some((name.as_str(), examples.render()?)),
+            None => None,
+        };
+        let prompt = render_segments(&self.segments, |name| match &examples {
+            Some((examples_name, rendered)) if *examples_name == name => Some(rendered.clone()),
+            _ => variables
+                .iter()
+                .find(|(variable, _)| *variable == name)
+                .map(|(_, value)| value.to_string()),
+        })?;
+        Ok(self.format.apply(&prompt))
+    }
+}
+
+fn render_segments<F>(segments: &[Segment], value: F) -> Result<String, RustBertError>
+where
+    F: Fn(&str) -> Option<String>,
+{
+    let mut output = String::new();
+    for segment in segments {
+        match segment {
+            Segment::Text(text) => output.push_str(text),
+            Segment::Variable(name) => match value(name) {
+                Some(value) => output.push_str(&value),
+                None => {
+                    return Err(RustBertError::ValueError(format!(
+                        "Missing value for the template variable {}",
+                        name
+                    )));
+                }
+            },
+        }
+    }
+    Ok(output)
+}
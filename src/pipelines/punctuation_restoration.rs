@@ -0,0 +1,297 @@
+// Copyright 2021 Guillaume Becquin
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//     http://www.apache.org/licenses/LICENSE-2.0
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! # True-casing and punctuation restoration pipeline
+//! Restores the capitalization and punctuation of lowercased, unpunctuated text such as the transcripts produced by
+//! speech recognition systems. A token classification model predicts for each word its casing and the punctuation
+//! mark following it, for example a BERT model fine-tuned for punctuation restoration (such as
+//! `felflare/bert-restore-punctuation`, converted with `utils/convert_model.py`).
+//!
+//! The labels of the model are mapped to a `RestorationLabel` by the `label_parser` of the configuration. The default
+//! parser, `parse_restoration_label`, supports labels made of a punctuation mark (or `O` for no punctuation) followed
+//! by a casing flag (`U` for a capitalized word, `O` otherwise), e.g. `,O` or `.U`, as well as labels made of a single
+//! punctuation mark (`O` or `0` for no punctuation).
+//!
+//! `restore` accepts slices of `&str` or `String`, so that the output of a transcription step can be passed directly.
+//!
+//! ```no_run
+//! # fn main() -> anyhow::Result<()> {
+//! use rust_bert::pipelines::common::ModelType;
+//! use rust_bert::pipelines::punctuation_restoration::{
+//!     PunctuationRestorationConfig, PunctuationRestorationModel,
+//! };
+//! use rust_bert::pipelines::token_classification::{
+//!     LabelAggregationOption, TokenClassificationConfig,
+//! };
+//! use rust_bert::resources::{LocalResource, Resource};
+//! use std::path::PathBuf;
+//!
+//! let resource = |path: &str| {
+//!     Resource::Local(LocalResource {
+//!         local_path: PathBuf::from(path),
+//!     })
+//! };
+//! let token_classification_config = TokenClassificationConfig::new(
+//!     ModelType::Bert,
+//!     resource("path/to/bert-restore-punctuation/rust_model.ot"),
+//!     resource("path/to/bert-restore-punctuation/config.json"),
+//!     resource("path/to/bert-restore-punctuation/vocab.txt"),
+//!     None,
+//!     true,
+//!     None,
+//!     None,
+//!     LabelAggregationOption::First,
+//! );
+//! let restoration_model =
+//!     PunctuationRestorationModel::new(PunctuationRestorationConfig::new(token_classification_config))?;
+//!
+//! let transcripts = ["my name is amy i live in paris do you live there too"];
+//! let output = restoration_model.restore(&transcripts);
+//! # Ok(())
+//! # }
+//! ```
+
+use crate::common::error::RustBertError;
+use crate::common::resources::Resource;
+use crate::pipelines::post_processing::{Detruecaser, PostProcessor};
+use crate::pipelines::token_classification::{TokenClassificationConfig, TokenClassificationModel};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+/// # Casing of a restored word
+pub enum Casing {
+    /// The word is kept as is
+    Unchanged,
+    /// The word is lowercased
+    Lower,
+    /// The first letter of the word is capitalized
+    Capitalized,
+    /// The word is uppercased (e.g. acronyms)
+    Upper,
+}
+
+impl Casing {
+    /// Applies the casing to a word
+    pub fn apply(&self, word: &str) -> String {
+        match self {
+            Casing::Unchanged => word.to_string(),
+            Casing::Lower => word.to_lowercase(),
+            Casing::Upper => word.to_uppercase(),
+            Casing::Capitalized => {
+                let mut chars = word.chars();
+                match chars.next() {
+                    Some(first) => first.to_uppercase().chain(chars).collect(),
+                    None => String::new(),
+                }
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+/// # Restoration predicted for a word
+pub struct RestorationLabel {
+    /// Casing of the word
+    pub casing: Casing,
+    /// Punctuation mark following the word, if any
+    pub punctuation: Option<char>,
+}
+
+/// Default label parser, supporting `<punctuation><casing>` labels (e.g. `OU`, `.O`, `?U`) and single punctuation
+/// mark labels (e.g. `0`, `,`, `?`). Unknown labels leave the word unchanged.
+pub fn parse_restoration_label(label: &str) -> RestorationLabel {
+    let chars = label.chars().collect::<Vec<char>>();
+    let punctuation = |c: char| match c {
+        'O' | '0' => None,
+        c if c.is_ascii_punctuation() => Some(c),
+        _ => None,
+    };
+    match chars.as_slice() {
+        [mark, casing] if *casing == 'U' || *casing == 'O' => RestorationLabel {
+            casing: if *casing == 'U' {
+                Casing::Capitalized
+            } else {
+                Casing::Unchanged
+            },
+            punctuation: punctuation(*mark),
+        },
+        [mark] => RestorationLabel {
+            casing: Casing::Unchanged,
+            punctuation: punctuation(*mark),
+        },
+        _ => RestorationLabel {
+            casing: Casing::Unchanged,
+            punctuation: None,
+        },
+    }
+}
+
+/// # Configuration for true-casing and punctuation restoration
+pub struct PunctuationRestorationConfig {
+    /// Configuration of the token classification model predicting the restoration labels
+    pub token_classification_config: TokenClassificationConfig,
+    /// Mapping from the model labels to the restoration of the words (default: `parse_restoration_label`)
+    pub label_parser: fn(&str) -> RestorationLabel,
+    /// Capitalize the first word of the text and the words following a sentence terminator, regardless of the
+    /// predicted casing (default: true)
+    pub capitalize_sentence_starts: bool,
+}
+
+impl PunctuationRestorationConfig {
+    /// Instantiate a new restoration configuration from a token classification configuration
+    pub fn new(
+        token_classification_config: TokenClassificationConfig,
+    ) -> PunctuationRestorationConfig {
+        PunctuationRestorationConfig {
+            token_classification_config,
+            label_parser: parse_restoration_label,
+            capitalize_sentence_starts: true,
+        }
+    }
+}
+
+struct RestoredWord {
+    word_index: u16,
+    begin: usize,
+    end: usize,
+    label: RestorationLabel,
+}
+
+/// # PunctuationRestorationModel to restore the casing and punctuation of texts
+pub struct PunctuationRestorationModel {
+    token_classification_model: TokenClassificationModel,
+    label_parser: fn(&str) -> RestorationLabel,
+    sentence_caser: Option<Detruecaser>,
+}
+
+impl PunctuationRestorationModel {
+    /// Build a new `PunctuationRestorationModel`
+    ///
+    /// # Arguments
+    ///
+    /// * `config` - `PunctuationRestorationConfig` object containing the token classification configuration and the label mapping
+    pub fn new(
+        config: PunctuationRestorationConfig,
+    ) -> Result<PunctuationRestorationModel, RustBertError> {
+        let token_classification_model =
+            TokenClassificationModel::new(config.token_classification_config)?;
+        let sentence_caser = if config.capitalize_sentence_starts {
+            Some(Detruecaser {
+                capitalize_pronoun_i: false,
+                ..Default::default()
+            })
+        } else {
+            None
+        };
+        Ok(PunctuationRestorationModel {
+            token_classification_model,
+            label_parser: config.label_parser,
+            sentence_caser,
+        })
+    }
+
+    /// Restores the casing and punctuation of texts
+    ///
+    /// # Arguments
+    ///
+    /// * `texts` - Array of texts (`&str` or `String`)
+    ///
+    /// # Returns
+    ///
+    /// * `Vec<String>` restored texts. The white spaces of the inputs are preserved
+    pub fn restore<S: AsRef<str>>(&self, texts: &[S]) -> Vec<String> {
+        let inputs = texts.iter().map(AsRef::as_ref).collect::<Vec<&str>>();
+        if inputs.is_empty() {
+            return vec![];
+        }
+        let mut words: Vec<Vec<RestoredWord>> = inputs.iter().map(|_| vec![]).collect();
+        // The casing is taken from the first sub-token of each word, the punctuation from its last sub-token
+        for token in self
+            .token_classification_model
+            .predict(&inputs, false, false)
+        {
+            let offset = match token.offset {
+                Some(offset) => offset,
+                None => continue,
+            };
+            let label = (self.label_parser)(&token.label);
+            let sentence_words = &mut words[token.sentence];
+            match sentence_words.last_mut() {
+                Some(word) if word.word_index == token.word_index => {
+                    word.end = offset.end as usize;
+                    word.label.punctuation = label.punctuation;
+                }
+                _ => sentence_words.push(RestoredWord {
+                    word_index: token.word_index,
+                    begin: offset.begin as usize,
+                    end: offset.end as usize,
+                    label,
+                }),
+            }
+        }
+        inputs
+            .iter()
+            .zip(words)
+            .map(|(text, words)| self.rebuild(text, &words))
+            .collect()
+    }
+
+    fn rebuild(&self, text: &str, words: &[RestoredWord]) -> String {
+        let chars = text.chars().collect::<Vec<char>>();
+        let mut output = String::with_capacity(text.len() + words.len());
+        let mut cursor = 0;
+        for word in words {
+            let (begin, end) = (word.begin.max(cursor), word.end.min(chars.len()));
+            if begin >= end {
+                continue;
+            }
+            output.extend(&chars[cursor..begin]);
+            output.push_str(
+                &word
+                    .label
+                    .casing
+                    .apply(&chars[begin..end].iter().collect::<String>()),
+            );
+            if let Some(punctuation) = word.label.punctuation {
+                if chars.get(end) != Some(&punctuation) {
+                    output.push(punctuation);
+                }
+            }
+            cursor = end;
+        }
+        output.extend(&chars[cursor..]);
+        match &self.sentence_caser {
+            Some(sentence_caser) => sentence_caser.process(&output),
+            None => output,
+        }
+    }
+
+    /// Reloads the weights of the underlying token classification model from the resource provided.
+    ///
+    /// # Arguments
+    ///
+    /// * `weights_resource` - `Resource` pointing to the new model weights (e.g. model.ot)
+    pub fn reload_weights(&mut self, weights_resource: &Resource) -> Result<(), RustBertError> {
+        self.token_classification_model
+            .reload_weights(weights_resource)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    #[ignore] // no need to run, compilation is enough to verify it is Send
+    fn test() {
+        let config = PunctuationRestorationConfig::new(TokenClassificationConfig::default());
+        let _: Box<dyn Send> = Box::new(PunctuationRestorationModel::new(config));
+    }
+}
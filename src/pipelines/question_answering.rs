@@ -47,14 +47,16 @@ use crate::albert::AlbertForQuestionAnswering;
 use crate::bert::BertForQuestionAnswering;
 use crate::common::error::RustBertError;
 use crate::common::resources::{RemoteResource, Resource};
+use crate::common::weights::reload_var_store;
 use crate::distilbert::{
     DistilBertConfigResources, DistilBertForQuestionAnswering, DistilBertModelResources,
     DistilBertVocabResources,
 };
 use crate::mobilebert::MobileBertForQuestionAnswering;
-use crate::pipelines::common::{ConfigOption, ModelType, TokenizerOption};
+use crate::pipelines::common::{ConfigOption, ModelType, TensorBatch, TokenizerOption};
 use crate::reformer::ReformerForQuestionAnswering;
 use crate::roberta::RobertaForQuestionAnswering;
+use crate::splinter::SplinterForQuestionAnswering;
 use crate::xlnet::XLNetForQuestionAnswering;
 use rust_tokenizers::tokenizer::{truncate_sequences, TruncationStrategy};
 use rust_tokenizers::{Mask, TokenIdsWithOffsets, TokenizedInput};
@@ -189,6 +191,9 @@ pub struct QuestionAnsweringConfig {
     pub merges_resource: Option<Resource>,
     /// Device to place the model on (default: CUDA/GPU when available)
     pub device: Device,
+    /// Copy the inputs to the device through pinned host memory, asynchronously (default: false). Only used for
+    /// CUDA devices, see `pipelines::common::to_device`
+    pub pinned_memory: bool,
     /// Model type
     pub model_type: ModelType,
     /// Flag indicating if the model expects a lower casing of the input
@@ -230,6 +235,7 @@ impl QuestionAnsweringConfig {
             strip_accents: strip_accents.into(),
             add_prefix_space: add_prefix_space.into(),
             device: Device::cuda_if_available(),
+            pinned_memory: false,
         }
     }
 }
@@ -248,6 +254,7 @@ impl Default for QuestionAnsweringConfig {
             )),
             merges_resource: None,
             device: Device::cuda_if_available(),
+            pinned_memory: false,
             model_type: ModelType::DistilBert,
             lower_case: false,
             add_prefix_space: None,
@@ -274,6 +281,8 @@ pub enum QuestionAnsweringOption {
     XLNet(XLNetForQuestionAnswering),
     /// Reformer for Question Answering
     Reformer(ReformerForQuestionAnswering),
+    /// Splinter (question-aware span selection) for Question Answering
+    Splinter(SplinterForQuestionAnswering),
 }
 
 impl QuestionAnsweringOption {
@@ -382,6 +391,17 @@ impl QuestionAnsweringOption {
                     ))
                 }
             }
+            ModelType::Splinter => {
+                if let ConfigOption::Bert(config) = config {
+                    Ok(QuestionAnsweringOption::Splinter(
+                        SplinterForQuestionAnswering::new(p, config),
+                    ))
+                } else {
+                    Err(RustBertError::InvalidConfigurationError(
+                        "You can only supply a BertConfig for Splinter!".to_string(),
+                    ))
+                }
+            }
             _ => Err(RustBertError::InvalidConfigurationError(format!(
                 "QuestionAnswering not implemented for {:?}!",
                 model_type
@@ -400,6 +420,7 @@ impl QuestionAnsweringOption {
             Self::Albert(_) => ModelType::Albert,
             Self::XLNet(_) => ModelType::XLNet,
             Self::Reformer(_) => ModelType::Reformer,
+            Self::Splinter(_) => ModelType::Splinter,
         }
     }
 
@@ -462,6 +483,11 @@ impl QuestionAnsweringOption {
                     .expect("Error in reformer forward pass");
                 (outputs.start_logits, outputs.end_logits)
             }
+            Self::Splinter(ref model) => {
+                let outputs =
+                    model.forward_t(input_ids, mask, None, None, input_embeds, None, train);
+                (outputs.start_logits, outputs.end_logits)
+            }
         }
     }
 }
@@ -477,6 +503,7 @@ pub struct QuestionAnsweringModel {
     max_answer_len: usize,
     qa_model: QuestionAnsweringOption,
     var_store: VarStore,
+    pinned_memory: bool,
 }
 
 impl QuestionAnsweringModel {
@@ -526,7 +553,11 @@ impl QuestionAnsweringModel {
             .expect("The Tokenizer used for Question Answering should contain a SEP id");
         let mut var_store = VarStore::new(device);
         let mut model_config =
-            ConfigOption::from_file(question_answering_config.model_type, config_path);
+            ConfigOption::try_from_file(question_answering_config.model_type, config_path)?;
+        tokenizer.check_model_compatibility(
+            question_answering_config.model_type,
+            model_config.get_vocab_size(),
+        )?;
 
         if let ConfigOption::DistilBert(ref mut config) = model_config {
             config.sinusoidal_pos_embds = false;
@@ -548,6 +579,7 @@ impl QuestionAnsweringModel {
             max_answer_len: 15,
             qa_model,
             var_store,
+            pinned_memory: question_answering_config.pinned_memory,
         })
     }
 
@@ -628,13 +660,17 @@ impl QuestionAnsweringModel {
                     attention_masks.push(Tensor::of_slice(&feature.attention_mask));
                 }
 
-                let input_ids = Tensor::stack(&input_ids, 0).to(self.var_store.device());
-                let attention_masks =
-                    Tensor::stack(&attention_masks, 0).to(self.var_store.device());
+                let input_batch =
+                    TensorBatch::new(Tensor::stack(&input_ids, 0), self.var_store.device())
+                        .with_attention_mask(Tensor::stack(&attention_masks, 0))
+                        .with_pinned_memory(self.pinned_memory);
 
-                let (start_logits, end_logits) =
-                    self.qa_model
-                        .forward_t(Some(input_ids), Some(attention_masks), None, false);
+                let (start_logits, end_logits) = self.qa_model.forward_t(
+                    Some(input_batch.input_ids()),
+                    input_batch.attention_mask(),
+                    None,
+                    false,
+                );
 
                 let start_logits = start_logits.detach();
                 let end_logits = end_logits.detach();
@@ -854,6 +890,16 @@ impl QuestionAnsweringModel {
     }
 
     fn prepare_query(&self, query: &str, max_query_length: usize) -> Vec<i64> {
+        // Splinter selects the answer span from the representation of a `[QUESTION]` token following the question
+        let question_suffix = match self.qa_model {
+            QuestionAnsweringOption::Splinter(ref model) => {
+                let mut question_suffix = vec![model.question_token_id()];
+                question_suffix.extend(self.tokenizer.convert_tokens_to_ids(&["."]));
+                question_suffix
+            }
+            _ => vec![],
+        };
+        let max_query_length = max_query_length.saturating_sub(question_suffix.len());
         let truncated_query = self
             .tokenizer
             .convert_tokens_to_ids(&self.tokenizer.tokenize(&query));
@@ -862,7 +908,7 @@ impl QuestionAnsweringModel {
         } else {
             0
         };
-        truncate_sequences(
+        let mut truncated_query = truncate_sequences(
             TokenIdsWithOffsets {
                 ids: truncated_query,
                 offsets: vec![],
@@ -876,7 +922,9 @@ impl QuestionAnsweringModel {
         )
         .unwrap()
         .0
-        .ids
+        .ids;
+        truncated_query.extend(question_suffix);
+        truncated_query
     }
 
     fn encode_qa_pair(
@@ -980,6 +1028,17 @@ impl QuestionAnsweringModel {
         }
         p_mask
     }
+
+    /// Reloads the weights of the question answering model from the resource provided, keeping the tokenizer and configuration
+    /// unchanged. The weights must match the architecture of the model currently loaded, the current weights
+    /// are kept if the new weights cannot be loaded.
+    ///
+    /// # Arguments
+    ///
+    /// * `weights_resource` - `Resource` pointing to the new model weights (e.g. model.ot)
+    pub fn reload_weights(&mut self, weights_resource: &Resource) -> Result<(), RustBertError> {
+        reload_var_store(&mut self.var_store, weights_resource)
+    }
 }
 
 pub fn squad_processor(file_path: PathBuf) -> Vec<QaInput> {
@@ -0,0 +1,604 @@
+// Copyright 2021 Guillaume Becquin
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//     http://www.apache.org/licenses/LICENSE-2.0
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! # Retrieval-augmented generation pipeline
+//! Answers questions with a sequence-to-sequence generator (BART or T5) conditioned on passages retrieved for the
+//! question. The pipeline chains:
+//! - a `Retriever` returning the most relevant passages for a question. `EmbeddingRetriever` is a dense retriever
+//! ranking passages by cosine similarity of their embeddings computed by a `TextEmbedder` (e.g. a
+//! `FeatureExtractionModel`), stored in a `document_store::DocumentStore`
+//! - the concatenation of the question and retrieved passages into the generator input, each passage being preceded
+//! by its citation marker (`[1]`, `[2]`...)
+//! - the generation of the answer, and the resolution of its citations. Markers generated by the model are returned as
+//! is; when the generator does not produce markers, the passages sharing most words with the answer are cited and
+//! their markers appended to the answer.
+//!
+//! With `RagConfig::sentence_citations`, the citations are also tracked for each sentence of the answer
+//! (`RagOutput::sentences`): a sentence is attributed to the markers it contains or, without markers, to the passage
+//! containing the largest share of its words (the passage with the highest retrieval score among ties), whose marker
+//! is appended to the sentence. `resolve_sentence_citations` applies the same attribution to any generated text, e.g. a
+//! summary of several passages.
+//!
+//! The default generator is a T5 base model, using the `question: ... context: ...` input format of its
+//! question-answering pre-training task.
+//!
+//! ```no_run
+//! # fn main() -> anyhow::Result<()> {
+//! use rust_bert::pipelines::feature_extraction::FeatureExtractionModel;
+//! use rust_bert::pipelines::rag::{EmbeddingRetriever, Passage, RagModel};
+//!
+//! let mut retriever = EmbeddingRetriever::new(FeatureExtractionModel::new(Default::default())?);
+//! retriever.add_passages(vec![
+//!     Passage::new("amsterdam", "Amsterdam is the capital of the Netherlands."),
+//!     Passage::new("paris", "Paris is the capital and most populous city of France."),
+//! ])?;
+//! let rag_model = RagModel::new(Default::default(), Box::new(retriever))?;
+//! let answers = rag_model.generate(&["What is the capital of France?"])?;
+//! println!("{} (sources: {:?})", answers[0].text, answers[0].citations);
+//! # Ok(())
+//! # }
+//! ```
+
+use crate::common::error::RustBertError;
+use crate::common::resources::{RemoteResource, Resource};
+use crate::pipelines::common::ModelType;
+use crate::pipelines::document_store::{
+    Document, DocumentStore, InMemoryDocumentStore, MetadataFilter,
+};
+use crate::pipelines::feature_extraction::TextEmbedder;
+use crate::pipelines::stance_detection::split_sentences;
+use crate::pipelines::summarization::{SummarizationConfig, SummarizationOption};
+use crate::t5::{T5ConfigResources, T5ModelResources, T5VocabResources};
+use serde_json::Value;
+use std::collections::HashSet;
+use tch::{Device, Kind};
+
+#[derive(Debug, Clone, PartialEq)]
+/// # Passage of a document collection
+pub struct Passage {
+    /// Identifier of the passage
+    pub id: String,
+    /// Optional title of the document the passage belongs to, prepended to the passage text in the generator input
+    pub title: Option<String>,
+    /// Text of the passage
+    pub text: String,
+}
+
+impl Passage {
+    pub fn new(id: &str, text: &str) -> Passage {
+        Passage {
+            id: id.to_string(),
+            title: None,
+            text: text.to_string(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+/// # Passage retrieved for a query
+pub struct RetrievedPassage {
+    pub passage: Passage,
+    /// Relevance score of the passage (cosine similarity for `EmbeddingRetriever`)
+    pub score: f64,
+}
+
+/// # Retriever returning the most relevant passages for a query
+pub trait Retriever: Send {
+    /// Retrieves passages for a query
+    ///
+    /// # Arguments
+    ///
+    /// * `query` - query text
+    /// * `top_k` - maximum number of passages to return
+    ///
+    /// # Returns
+    ///
+    /// * `Vec<RetrievedPassage>` sorted by decreasing relevance
+    fn retrieve(&self, query: &str, top_k: usize) -> Result<Vec<RetrievedPassage>, RustBertError>;
+}
+
+/// # Dense retriever based on text embeddings
+/// Stores the passages and their embeddings in a `DocumentStore` (by default an `InMemoryDocumentStore`) and ranks them
+/// by cosine similarity with the query. The passage titles are stored in the `title` metadata field of the documents.
+pub struct EmbeddingRetriever<E: TextEmbedder, S: DocumentStore = InMemoryDocumentStore> {
+    embedder: E,
+    store: S,
+    filter: Option<MetadataFilter>,
+    batch_size: usize,
+}
+
+impl<E: TextEmbedder> EmbeddingRetriever<E, InMemoryDocumentStore> {
+    /// Creates a retriever backed by an empty in-memory store
+    ///
+    /// # Arguments
+    ///
+    /// * `embedder` - `TextEmbedder` computing the embeddings of the passages and queries
+    pub fn new(embedder: E) -> EmbeddingRetriever<E, InMemoryDocumentStore> {
+        EmbeddingRetriever::with_store(embedder, InMemoryDocumentStore::new())
+    }
+}
+
+impl<E: TextEmbedder, S: DocumentStore> EmbeddingRetriever<E, S> {
+    /// Creates a retriever backed by the document store provided, which may already contain documents embedded with
+    /// the same embedder
+    ///
+    /// # Arguments
+    ///
+    /// * `embedder` - `TextEmbedder` computing the embeddings of the passages and queries
+    /// * `store` - `DocumentStore` holding the passages
+    pub fn with_store(embedder: E, store: S) -> EmbeddingRetriever<E, S> {
+        EmbeddingRetriever {
+            embedder,
+            store,
+            filter: None,
+            batch_size: 64,
+        }
+    }
+
+    /// Sets the number of passages embedded at once (default: 64)
+    pub fn with_batch_size(mut self, batch_size: usize) -> EmbeddingRetriever<E, S> {
+        self.batch_size = batch_size.max(1);
+        self
+    }
+
+    /// Restricts the retrieval to the documents matching a metadata filter
+    pub fn with_filter(mut self, filter: Option<MetadataFilter>) -> EmbeddingRetriever<E, S> {
+        self.filter = filter;
+        self
+    }
+
+    /// Returns the document store of the retriever
+    pub fn store(&self) -> &S {
+        &self.store
+    }
+
+    /// Returns the number of passages stored
+    pub fn count(&self) -> Result<usize, RustBertError> {
+        self.store.count()
+    }
+
+    /// Embeds and stores passages
+    ///
+    /// # Arguments
+    ///
+    /// * `passages` - passages to store
+    pub fn add_passages(&mut self, passages: Vec<Passage>) -> Result<(), RustBertError> {
+        for batch in passages.chunks(self.batch_size) {
+            let texts = batch.iter().map(passage_text).collect::<Vec<_>>();
+            let embeddings = self.embed(&texts.iter().map(String::as_str).collect::<Vec<_>>())?;
+            let documents = batch
+                .iter()
+                .zip(embeddings)
+                .map(|(passage, embedding)| {
+                    let document = Document::new(&passage.id, &passage.text, embedding);
+                    match &passage.title {
+                        Some(title) => document.with_metadata("title", Value::from(title.as_str())),
+                        None => document,
+                    }
+                })
+                .collect();
+            self.store.add(documents)?;
+        }
+        Ok(())
+    }
+
+    /// Retrieves passages for a query among the documents matching a metadata filter
+    ///
+    /// # Arguments
+    ///
+    /// * `query` - query text
+    /// * `top_k` - maximum number of passages to return
+    /// * `filter` - optional `MetadataFilter` the documents must match
+    pub fn retrieve_with_filter(
+        &self,
+        query: &str,
+        top_k: usize,
+        filter: Option<&MetadataFilter>,
+    ) -> Result<Vec<RetrievedPassage>, RustBertError> {
+        if top_k == 0 {
+            return Ok(vec![]);
+        }
+        let query_embedding = self.embed(&[query])?.remove(0);
+        Ok(self
+            .store
+            .search(&query_embedding, top_k, filter)?
+            .into_iter()
+            .map(|scored| RetrievedPassage {
+                passage: Passage {
+                    id: scored.document.id,
+                    title: scored
+                        .document
+                        .metadata
+                        .get("title")
+                        .and_then(Value::as_str)
+                        .map(str::to_string),
+                    text: scored.document.text,
+                },
+                score: scored.score,
+            })
+            .collect())
+    }
+
+    fn embed(&self, texts: &[&str]) -> Result<Vec<Vec<f32>>, RustBertError> {
+        let embeddings = self
+            .embedder
+            .embed(texts)?
+            .to(Device::Cpu)
+            .to_kind(Kind::Float);
+        Ok((0..embeddings.size()[0])
+            .map(|row| Vec::<f32>::from(embeddings.get(row)))
+            .collect())
+    }
+}
+
+impl<E: TextEmbedder, S: DocumentStore> Retriever for EmbeddingRetriever<E, S> {
+    fn retrieve(&self, query: &str, top_k: usize) -> Result<Vec<RetrievedPassage>, RustBertError> {
+        self.retrieve_with_filter(query, top_k, self.filter.as_ref())
+    }
+}
+
+fn passage_text(passage: &Passage) -> String {
+    match &passage.title {
+        Some(title) => format!("{}: {}", title, passage.text),
+        None => passage.text.clone(),
+    }
+}
+
+/// # Configuration for retrieval-augmented generation
+pub struct RagConfig {
+    /// Configuration of the sequence-to-sequence generator (BART or T5; default: T5 base)
+    pub generator_config: SummarizationConfig,
+    /// Number of passages retrieved for each question (default: 3)
+    pub top_k: usize,
+    /// Text preceding the question in the generator input (default: `question: `)
+    pub question_prefix: String,
+    /// Text separating the question from the passages in the generator input (default: ` context: `)
+    pub context_prefix: String,
+    /// Minimum share of the answer words found in a passage for the passage to be cited, when the generator does not produce citation markers (default: 0.5)
+    pub citation_threshold: f64,
+    /// Tracks the citations of each sentence of the answer, returned in `RagOutput::sentences` (default: false)
+    pub sentence_citations: bool,
+}
+
+impl Default for RagConfig {
+    fn default() -> RagConfig {
+        let generator_config = SummarizationConfig {
+            min_length: 0,
+            max_length: 64,
+            num_beams: 4,
+            no_repeat_ngram_size: 0,
+            ..SummarizationConfig::new(
+                ModelType::T5,
+                Resource::Remote(RemoteResource::from_pretrained(T5ModelResources::T5_BASE)),
+                Resource::Remote(RemoteResource::from_pretrained(T5ConfigResources::T5_BASE)),
+                Resource::Remote(RemoteResource::from_pretrained(T5VocabResources::T5_BASE)),
+                Resource::Remote(RemoteResource::from_pretrained(T5VocabResources::T5_BASE)),
+            )
+        };
+        RagConfig {
+            generator_config,
+            top_k: 3,
+            question_prefix: "question: ".to_string(),
+            context_prefix: " context: ".to_string(),
+            citation_threshold: 0.5,
+            sentence_citations: false,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+/// # Answer generated from retrieved passages
+pub struct RagOutput {
+    /// Generated answer, including its citation markers
+    pub text: String,
+    /// Citation markers (1-based indices in `passages`) supporting the answer, in increasing order
+    pub citations: Vec<usize>,
+    /// Passages retrieved for the question and passed to the generator
+    pub passages: Vec<RetrievedPassage>,
+    /// Sentences of the answer with their citations (empty unless `RagConfig::sentence_citations` is set)
+    pub sentences: Vec<CitedSentence>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+/// # Sentence of a generated text with its citations
+pub struct CitedSentence {
+    /// Text of the sentence, as generated (without the markers appended by the attribution)
+    pub text: String,
+    /// Citation markers (1-based indices in the passages) supporting the sentence, in increasing order
+    pub citations: Vec<usize>,
+}
+
+/// # RagModel to answer questions from retrieved passages
+pub struct RagModel {
+    retriever: Box<dyn Retriever>,
+    generator: SummarizationOption,
+    top_k: usize,
+    question_prefix: String,
+    context_prefix: String,
+    citation_threshold: f64,
+    sentence_citations: bool,
+}
+
+impl RagModel {
+    /// Build a new `RagModel`
+    ///
+    /// # Arguments
+    ///
+    /// * `config` - `RagConfig` object containing the generator configuration and retrieval settings
+    /// * `retriever` - `Retriever` returning the passages for each question
+    pub fn new(
+        config: RagConfig,
+        retriever: Box<dyn Retriever>,
+    ) -> Result<RagModel, RustBertError> {
+        let generator = SummarizationOption::new(config.generator_config)?;
+        Ok(RagModel {
+            retriever,
+            generator,
+            top_k: config.top_k,
+            question_prefix: config.question_prefix,
+            context_prefix: config.context_prefix,
+            citation_threshold: config.citation_threshold,
+            sentence_citations: config.sentence_citations,
+        })
+    }
+
+    /// Builds the generator input for a question and the passages retrieved for it
+    pub fn build_input(&self, question: &str, passages: &[RetrievedPassage]) -> String {
+        let context = passages
+            .iter()
+            .enumerate()
+            .map(|(index, retrieved)| {
+                format!("[{}] {}", index + 1, passage_text(&retrieved.passage))
+            })
+            .collect::<Vec<_>>()
+            .join(" ");
+        format!(
+            "{}{}{}{}",
+            self.question_prefix, question, self.context_prefix, context
+        )
+    }
+
+    /// Answers questions from the passages retrieved for each of them
+    ///
+    /// # Arguments
+    ///
+    /// * `questions` - `&[&str]` Array of questions
+    ///
+    /// # Returns
+    ///
+    /// * `Vec<RagOutput>` containing the answer, citations and retrieved passages of each question
+    pub fn generate(&self, questions: &[&str]) -> Result<Vec<RagOutput>, RustBertError> {
+        let mut retrieved_passages = Vec::with_capacity(questions.len());
+        let mut inputs = Vec::with_capacity(questions.len());
+        for question in questions {
+            let passages = self.retriever.retrieve(question, self.top_k)?;
+            inputs.push(self.build_input(question, &passages));
+            retrieved_passages.push(passages);
+        }
+        if inputs.is_empty() {
+            return Ok(vec![]);
+        }
+        let answers = self.generator.generate(
+            Some(inputs.iter().map(String::as_str).collect::<Vec<_>>()),
+            None,
+        );
+        Ok(answers
+            .into_iter()
+            .zip(retrieved_passages)
+            .map(|(answer, passages)| {
+                let (text, citations, sentences) = if self.sentence_citations {
+                    resolve_sentence_citations(&answer, &passages, self.citation_threshold)
+                } else {
+                    let (text, citations) =
+                        resolve_citations(&answer, &passages, self.citation_threshold);
+                    (text, citations, vec![])
+                };
+                RagOutput {
+                    text,
+                    citations,
+                    passages,
+                    sentences,
+                }
+            })
+            .collect())
+    }
+}
+
+/// Extracts the citation markers of a generated answer, or cites the passages containing at least
+/// `citation_threshold` of the answer words if the answer does not contain valid markers.
+///
+/// # Returns
+///
+/// * (`String`, `Vec<usize>`) answer text (with the markers appended when attributed by word overlap) and citations
+pub fn resolve_citations(
+    answer: &str,
+    passages: &[RetrievedPassage],
+    citation_threshold: f64,
+) -> (String, Vec<usize>) {
+    let mut citations = valid_citation_markers(answer, passages.len());
+    if !citations.is_empty() {
+        return (answer.to_string(), citations);
+    }
+
+    let answer_words = words(answer);
+    if answer_words.is_empty() {
+        return (answer.to_string(), citations);
+    }
+    for (index, retrieved) in passages.iter().enumerate() {
+        let passage_words = words(&passage_text(&retrieved.passage));
+        let overlap = answer_words.intersection(&passage_words).count() as f64;
+        if overlap / answer_words.len() as f64 >= citation_threshold {
+            citations.push(index + 1);
+        }
+    }
+    let text = citations
+        .iter()
+        .fold(answer.trim_end().to_string(), |text, marker| {
+            format!("{} [{}]", text, marker)
+        });
+    (text, citations)
+}
+
+/// Tracks the citations of each sentence of a generated text. The citation markers of a sentence are kept as is; a
+/// sentence without valid markers is attributed to the passage containing the largest share of its words (at least
+/// `citation_threshold`), preferring the passage with the highest retrieval score among ties, and the marker of the
+/// passage is appended to the sentence.
+///
+/// # Returns
+///
+/// * (`String`, `Vec<usize>`, `Vec<CitedSentence>`) text (with the markers appended by the attribution), citations
+/// of the whole text and sentences with their citations
+pub fn resolve_sentence_citations(
+    text: &str,
+    passages: &[RetrievedPassage],
+    citation_threshold: f64,
+) -> (String, Vec<usize>, Vec<CitedSentence>) {
+    let passage_words = passages
+        .iter()
+        .map(|retrieved| words(&passage_text(&retrieved.passage)))
+        .collect::<Vec<_>>();
+    // Markers following the end of a sentence (`Paris is in France. [2]`) belong to the previous sentence
+    let mut spans: Vec<(usize, usize, Vec<usize>)> = vec![];
+    for (start, end) in split_sentences(text) {
+        let markers = valid_citation_markers(&text[start..end], passages.len());
+        match spans.last_mut() {
+            Some(previous) if words(&strip_citation_markers(&text[start..end])).is_empty() => {
+                previous.2.extend(markers);
+                previous.2.sort_unstable();
+                previous.2.dedup();
+            }
+            _ => spans.push((start, end, markers)),
+        }
+    }
+
+    let mut output_text = String::with_capacity(text.len());
+    let mut sentences = Vec::with_capacity(spans.len());
+    let mut last_end = 0;
+    for (start, end, markers) in spans.iter() {
+        let sentence = &text[*start..*end];
+        let sentence_words = words(&strip_citation_markers(sentence));
+        output_text.push_str(&text[last_end..*start]);
+        output_text.push_str(sentence);
+        last_end = *end;
+        let citations = if !markers.is_empty() || sentence_words.is_empty() {
+            markers.clone()
+        } else {
+            let mut best: Option<(usize, f64, f64)> = None;
+            for (index, (retrieved, passage_words)) in
+                passages.iter().zip(passage_words.iter()).enumerate()
+            {
+                let support = sentence_words.intersection(passage_words).count() as f64
+                    / sentence_words.len() as f64;
+                let is_better = match best {
+                    Some((_, best_support, best_score)) => {
+                        support > best_support
+                            || (support == best_support && retrieved.score > best_score)
+                    }
+                    None => true,
+                };
+                if support >= citation_threshold && is_better {
+                    best = Some((index, support, retrieved.score));
+                }
+            }
+            match best {
+                Some((index, _, _)) => {
+                    output_text.push_str(&format!(" [{}]", index + 1));
+                    vec![index + 1]
+                }
+                None => vec![],
+            }
+        };
+        sentences.push(CitedSentence {
+            text: sentence.to_string(),
+            citations,
+        });
+    }
+    output_text.push_str(&text[last_end..]);
+
+    let mut citations = sentences
+        .iter()
+        .flat_map(|sentence| sentence.citations.iter().cloned())
+        .collect::<Vec<_>>();
+    citations.sort_unstable();
+    citations.dedup();
+    (output_text, citations, sentences)
+}
+
+fn valid_citation_markers(text: &str, num_passages: usize) -> Vec<usize> {
+    let mut citations = citation_markers(text)
+        .into_iter()
+        .filter(|&marker| marker >= 1 && marker <= num_passages)
+        .collect::<Vec<_>>();
+    citations.sort_unstable();
+    citations.dedup();
+    citations
+}
+
+fn strip_citation_markers(text: &str) -> String {
+    let mut stripped = String::with_capacity(text.len());
+    let mut remaining = text;
+    while let Some(start) = remaining.find('[') {
+        stripped.push_str(&remaining[..start]);
+        let after = &remaining[start + 1..];
+        match after.find(']') {
+            Some(end) if after[..end].trim().parse::<usize>().is_ok() => {
+                remaining = &after[end + 1..];
+            }
+            _ => {
+                stripped.push('[');
+                remaining = after;
+            }
+        }
+    }
+    stripped.push_str(remaining);
+    stripped
+}
+
+fn citation_markers(text: &str) -> Vec<usize> {
+    let mut markers = vec![];
+    let mut remaining = text;
+    while let Some(start) = remaining.find('[') {
+        remaining = &remaining[start + 1..];
+        if let Some(end) = remaining.find(']') {
+            if let Ok(marker) = remaining[..end].trim().parse::<usize>() {
+                markers.push(marker);
+            }
+        }
+    }
+    markers
+}
+
+fn words(text: &str) -> HashSet<String> {
+    text.split(|c: char| !c.is_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .map(str::to_lowercase)
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    #[ignore] // no need to run, compilation is enough to verify it is Send
+    fn test() {
+        struct DummyRetriever;
+        impl Retriever for DummyRetriever {
+            fn retrieve(&self, _: &str, _: usize) -> Result<Vec<RetrievedPassage>, RustBertError> {
+                Ok(vec![])
+            }
+        }
+        let _: Box<dyn Send> = Box::new(RagModel::new(
+            RagConfig::default(),
+            Box::new(DummyRetriever),
+        ));
+    }
+}
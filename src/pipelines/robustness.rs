@@ -0,0 +1,463 @@
+// Copyright 2021 Guillaume Becquin
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//     http://www.apache.org/licenses/LICENSE-2.0
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! # Robustness testing of classifiers with counterfactual inputs
+//! Perturbs the inputs of a classifier and reports the perturbations flipping its predictions. Each `Perturber`
+//! generates counterfactual versions of the inputs:
+//! - `NegationPerturber`: inserts or removes a negation after the first auxiliary verb ("is" / "is not", "don't" /
+//! "do"). The meaning of the input is reversed, and the prediction is expected to flip (contrast set)
+//! - `EntitySwapPerturber`: replaces the named entities recognized by a `NERModel` with other entities of the same type
+//! - `TextAugmenter`: substitutes words with the predictions of a masked language model
+//!
+//! Entity swaps and substitutions should not change the prediction. The `RobustnessReport` lists the prediction of
+//! every perturbed input, and the failures: unexpected flips, or negations not changing the prediction. Any
+//! classifier implementing `TextClassifier` can be tested, such as a `SequenceClassificationModel`.
+//!
+//! ```no_run
+//! # fn main() -> anyhow::Result<()> {
+//! use rust_bert::pipelines::ner::NERModel;
+//! use rust_bert::pipelines::robustness::{
+//!     EntitySwapPerturber, NegationPerturber, RobustnessTester,
+//! };
+//! use rust_bert::pipelines::sequence_classification::SequenceClassificationModel;
+//!
+//! let classifier = SequenceClassificationModel::new(Default::default())?;
+//! let tester = RobustnessTester::new(classifier)
+//!     .with_perturber(NegationPerturber::default())
+//!     .with_perturber(EntitySwapPerturber::new(NERModel::new(Default::default())?));
+//! let report = tester.evaluate(&[
+//!     "The staff in Paris was friendly.",
+//!     "John's answer was helpful.",
+//! ])?;
+//! println!("Failure rate: {:.2}", report.failure_rate(None));
+//! for failure in report.failures() {
+//!     println!(
+//!         "{} -> {} ({} -> {})",
+//!         failure.original_text,
+//!         failure.text,
+//!         failure.original_label.text,
+//!         failure.label.text
+//!     );
+//! }
+//! # Ok(())
+//! # }
+//! ```
+
+use crate::common::error::RustBertError;
+use crate::pipelines::augmentation::TextAugmenter;
+use crate::pipelines::ner::NERModel;
+use crate::pipelines::sequence_classification::{Label, SequenceClassificationModel};
+use std::collections::HashMap;
+
+/// # Classifier tested for robustness
+pub trait TextClassifier {
+    /// Returns the predicted label of each text
+    fn classify(&self, texts: &[&str]) -> Result<Vec<Label>, RustBertError>;
+}
+
+impl TextClassifier for SequenceClassificationModel {
+    fn classify(&self, texts: &[&str]) -> Result<Vec<Label>, RustBertError> {
+        Ok(self.predict(texts))
+    }
+}
+
+impl<T: TextClassifier + ?Sized> TextClassifier for &T {
+    fn classify(&self, texts: &[&str]) -> Result<Vec<Label>, RustBertError> {
+        (**self).classify(texts)
+    }
+}
+
+/// # Generator of counterfactual inputs
+pub trait Perturber {
+    /// Name of the perturbation, reported with its results
+    fn name(&self) -> &str;
+
+    /// Returns true if the perturbation is expected to change the prediction (default: false)
+    fn expects_flip(&self) -> bool {
+        false
+    }
+
+    /// Returns the perturbed versions of each text (possibly none)
+    fn perturb(&self, texts: &[&str]) -> Result<Vec<Vec<String>>, RustBertError>;
+}
+
+impl Perturber for TextAugmenter {
+    fn name(&self) -> &str {
+        "masked_substitution"
+    }
+
+    fn perturb(&self, texts: &[&str]) -> Result<Vec<Vec<String>>, RustBertError> {
+        self.augment(texts)
+    }
+}
+
+const AUXILIARIES: [&str; 21] = [
+    "am", "is", "are", "was", "were", "be", "been", "can", "could", "will", "would", "shall",
+    "should", "may", "might", "must", "do", "does", "did", "has", "have",
+];
+
+/// # Inserts or removes a negation
+/// Texts containing a negation ("not", "never", "cannot" or a "n't" contraction) have their first negation removed,
+/// other texts have a "not" inserted after their first auxiliary verb. Texts without auxiliary verbs are not
+/// perturbed.
+#[derive(Debug, Clone, Default)]
+pub struct NegationPerturber {}
+
+impl NegationPerturber {
+    /// Returns the text with its first negation removed or a negation inserted, if possible
+    pub fn negate(&self, text: &str) -> Option<String> {
+        let mut words = text.split(' ').map(String::from).collect::<Vec<String>>();
+        let bare_words = words
+            .iter()
+            .map(|word| {
+                word.trim_matches(|c: char| !c.is_alphanumeric() && c != '\'')
+                    .to_lowercase()
+            })
+            .collect::<Vec<String>>();
+
+        for (index, bare_word) in bare_words.iter().enumerate() {
+            let replacement = match bare_word.as_str() {
+                "not" | "never" => Some(""),
+                "cannot" => Some("can"),
+                "can't" => Some("can"),
+                "won't" => Some("will"),
+                "shan't" => Some("shall"),
+                _ if bare_word.ends_with("n't") => Some(&bare_word[..bare_word.len() - 3]),
+                _ => None,
+            };
+            if let Some(replacement) = replacement {
+                if replacement.is_empty() {
+                    let removed = words.remove(index);
+                    let punctuation = removed.trim_start_matches(|c: char| c.is_alphanumeric());
+                    if index > 0 {
+                        words[index - 1].push_str(punctuation);
+                    } else {
+                        return Some(capitalize_as(&words.join(" "), text));
+                    }
+                } else {
+                    let word = &words[index];
+                    let start = word.find(|c: char| c.is_alphanumeric()).unwrap_or(0);
+                    let end = start + bare_word.len();
+                    let replacement = capitalize_as(replacement, &word[start..]);
+                    words[index] = format!("{}{}{}", &word[..start], replacement, &word[end..]);
+                }
+                return Some(words.join(" "));
+            }
+        }
+
+        let index = bare_words
+            .iter()
+            .position(|word| AUXILIARIES.contains(&word.as_str()))?;
+        let word = &words[index];
+        let end = word
+            .rfind(|c: char| c.is_alphanumeric())
+            .map_or(word.len(), |position| position + 1);
+        words[index] = format!("{} not{}", &word[..end], &word[end..]);
+        Some(words.join(" "))
+    }
+}
+
+impl Perturber for NegationPerturber {
+    fn name(&self) -> &str {
+        "negation"
+    }
+
+    fn expects_flip(&self) -> bool {
+        true
+    }
+
+    fn perturb(&self, texts: &[&str]) -> Result<Vec<Vec<String>>, RustBertError> {
+        Ok(texts
+            .iter()
+            .map(|text| self.negate(text).into_iter().collect())
+            .collect())
+    }
+}
+
+/// # Replaces named entities with other entities of the same type
+/// Each entity recognized by the `NERModel` produces one perturbed text, in which its occurrences are replaced by a
+/// substitute of the same type (PER, LOC, ORG...).
+pub struct EntitySwapPerturber {
+    model: NERModel,
+    substitutes: HashMap<String, Vec<String>>,
+}
+
+impl EntitySwapPerturber {
+    /// Creates a new perturber with default substitutes for the person (PER), location (LOC) and organization (ORG)
+    /// entities
+    pub fn new(model: NERModel) -> EntitySwapPerturber {
+        let substitutes = [
+            ("PER", vec!["Maria", "Ahmed", "Mei", "John"]),
+            ("LOC", vec!["Lagos", "Osaka", "Lima", "Paris"]),
+            ("ORG", vec!["UNICEF", "Toyota", "Siemens", "Google"]),
+        ]
+        .iter()
+        .map(|(label, words)| {
+            (
+                label.to_string(),
+                words.iter().map(|word| word.to_string()).collect(),
+            )
+        })
+        .collect();
+        EntitySwapPerturber { model, substitutes }
+    }
+
+    /// Sets the substitutes of an entity type (e.g. "PER"), replacing the default ones
+    pub fn with_substitutes(mut self, label: &str, substitutes: &[&str]) -> EntitySwapPerturber {
+        self.substitutes.insert(
+            label.to_string(),
+            substitutes.iter().map(|word| word.to_string()).collect(),
+        );
+        self
+    }
+}
+
+impl Perturber for EntitySwapPerturber {
+    fn name(&self) -> &str {
+        "entity_swap"
+    }
+
+    fn perturb(&self, texts: &[&str]) -> Result<Vec<Vec<String>>, RustBertError> {
+        let mut perturbations = Vec::with_capacity(texts.len());
+        for text in texts {
+            let mut text_perturbations: Vec<String> = vec![];
+            for (entity_index, entity) in self.model.predict(&[*text]).iter().enumerate() {
+                let label = entity
+                    .label
+                    .trim_start_matches("B-")
+                    .trim_start_matches("I-");
+                let substitutes = match self.substitutes.get(label) {
+                    Some(substitutes) => substitutes
+                        .iter()
+                        .filter(|substitute| !substitute.eq_ignore_ascii_case(&entity.word))
+                        .collect::<Vec<&String>>(),
+                    None => continue,
+                };
+                if substitutes.is_empty() {
+                    continue;
+                }
+                let substitute = substitutes[entity_index % substitutes.len()];
+                let perturbed = replace_word(text, &entity.word, substitute);
+                if (perturbed != *text) & !text_perturbations.contains(&perturbed) {
+                    text_perturbations.push(perturbed);
+                }
+            }
+            perturbations.push(text_perturbations);
+        }
+        Ok(perturbations)
+    }
+}
+
+#[derive(Debug, Clone)]
+/// # Prediction of the classifier for a perturbed input
+pub struct PerturbationResult {
+    /// Index of the original input
+    pub input_index: usize,
+    /// Name of the perturbation
+    pub perturbation: String,
+    /// Original input
+    pub original_text: String,
+    /// Perturbed input
+    pub text: String,
+    /// Prediction for the original input
+    pub original_label: Label,
+    /// Prediction for the perturbed input
+    pub label: Label,
+    /// The perturbation is expected to change the prediction
+    pub expects_flip: bool,
+}
+
+impl PerturbationResult {
+    /// Returns true if the predicted labels of the original and perturbed inputs differ
+    pub fn is_flip(&self) -> bool {
+        self.original_label.id != self.label.id
+    }
+
+    /// Returns true if the prediction did not behave as expected: a flip for an invariant perturbation, or no flip
+    /// for a perturbation reversing the meaning of the input
+    pub fn is_failure(&self) -> bool {
+        self.is_flip() != self.expects_flip
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+/// # Results of the robustness tests
+pub struct RobustnessReport {
+    /// Predictions for each perturbed input, in the order of the inputs and perturbers
+    pub results: Vec<PerturbationResult>,
+}
+
+impl RobustnessReport {
+    fn filter<'a>(
+        &'a self,
+        perturbation: Option<&'a str>,
+    ) -> impl Iterator<Item = &'a PerturbationResult> {
+        self.results.iter().filter(move |result| {
+            perturbation.map_or(true, |perturbation| result.perturbation == perturbation)
+        })
+    }
+
+    /// Returns the results flipping the prediction
+    pub fn flips(&self) -> Vec<&PerturbationResult> {
+        self.results
+            .iter()
+            .filter(|result| result.is_flip())
+            .collect()
+    }
+
+    /// Returns the results not behaving as expected
+    pub fn failures(&self) -> Vec<&PerturbationResult> {
+        self.results
+            .iter()
+            .filter(|result| result.is_failure())
+            .collect()
+    }
+
+    /// Fraction of the perturbed inputs flipping the prediction, for a perturbation or all of them (0 if there are
+    /// no results)
+    pub fn flip_rate(&self, perturbation: Option<&str>) -> f64 {
+        rate(self.filter(perturbation).map(PerturbationResult::is_flip))
+    }
+
+    /// Fraction of the perturbed inputs not behaving as expected, for a perturbation or all of them (0 if there are
+    /// no results)
+    pub fn failure_rate(&self, perturbation: Option<&str>) -> f64 {
+        rate(
+            self.filter(perturbation)
+                .map(PerturbationResult::is_failure),
+        )
+    }
+}
+
+fn rate(values: impl Iterator<Item = bool>) -> f64 {
+    let (count, total) = values.fold((0usize, 0usize), |(count, total), value| {
+        (count + value as usize, total + 1)
+    });
+    if total == 0 {
+        0.0
+    } else {
+        count as f64 / total as f64
+    }
+}
+
+/// # Runs perturbations against a classifier
+pub struct RobustnessTester<C: TextClassifier> {
+    classifier: C,
+    perturbers: Vec<Box<dyn Perturber>>,
+}
+
+impl<C: TextClassifier> RobustnessTester<C> {
+    /// Creates a new tester without perturbers
+    ///
+    /// # Arguments
+    ///
+    /// * `classifier` - `TextClassifier` to test
+    pub fn new(classifier: C) -> RobustnessTester<C> {
+        RobustnessTester {
+            classifier,
+            perturbers: vec![],
+        }
+    }
+
+    /// Adds a perturbation to the tests
+    pub fn with_perturber<P: Perturber + 'static>(mut self, perturber: P) -> RobustnessTester<C> {
+        self.perturbers.push(Box::new(perturber));
+        self
+    }
+
+    /// Perturbs the inputs and compares the predictions of the classifier for the original and perturbed inputs
+    ///
+    /// # Arguments
+    ///
+    /// * `texts` - `&[&str]` Array of inputs to perturb
+    ///
+    /// # Returns
+    ///
+    /// * `RobustnessReport` with the prediction of each perturbed input
+    pub fn evaluate(&self, texts: &[&str]) -> Result<RobustnessReport, RustBertError> {
+        let original_labels = self.classifier.classify(texts)?;
+        let mut cases = vec![];
+        for (perturber_index, perturber) in self.perturbers.iter().enumerate() {
+            for (input_index, perturbations) in perturber.perturb(texts)?.into_iter().enumerate() {
+                for perturbation in perturbations {
+                    if perturbation != texts[input_index] {
+                        cases.push((perturber_index, input_index, perturbation));
+                    }
+                }
+            }
+        }
+        let labels = self.classifier.classify(
+            &cases
+                .iter()
+                .map(|(_, _, text)| text.as_str())
+                .collect::<Vec<&str>>(),
+        )?;
+
+        let results = cases
+            .into_iter()
+            .zip(labels)
+            .map(|((perturber_index, input_index, text), label)| {
+                let perturber = &self.perturbers[perturber_index];
+                PerturbationResult {
+                    input_index,
+                    perturbation: perturber.name().to_string(),
+                    original_text: texts[input_index].to_string(),
+                    text,
+                    original_label: original_labels[input_index].clone(),
+                    label,
+                    expects_flip: perturber.expects_flip(),
+                }
+            })
+            .collect();
+        Ok(RobustnessReport { results })
+    }
+}
+
+/// Applies the case of the first character of `reference` to `text`
+fn capitalize_as(text: &str, reference: &str) -> String {
+    let mut chars = text.chars();
+    match (chars.next(), reference.chars().next()) {
+        (Some(first), Some(reference_first)) if reference_first.is_uppercase() => {
+            first.to_uppercase().chain(chars).collect()
+        }
+        _ => text.to_string(),
+    }
+}
+
+/// Replaces the occurrences of a word, not surrounded by other alphanumeric characters
+fn replace_word(text: &str, word: &str, replacement: &str) -> String {
+    if word.is_empty() {
+        return text.to_string();
+    }
+    let mut output = String::with_capacity(text.len());
+    let mut position = 0;
+    while let Some(offset) = text[position..].find(word) {
+        let start = position + offset;
+        let end = start + word.len();
+        let boundary_before = text[..start]
+            .chars()
+            .next_back()
+            .map_or(true, |c| !c.is_alphanumeric());
+        let boundary_after = text[end..]
+            .chars()
+            .next()
+            .map_or(true, |c| !c.is_alphanumeric());
+        output.push_str(&text[position..start]);
+        if boundary_before & boundary_after {
+            output.push_str(replacement);
+        } else {
+            output.push_str(word);
+        }
+        position = end;
+    }
+    output.push_str(&text[position..]);
+    output
+}
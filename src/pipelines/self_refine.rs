@@ -0,0 +1,251 @@
+// Copyright 2021 Guillaume Becquin
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//     http://www.apache.org/licenses/LICENSE-2.0
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! # Iterative refinement (self-edit)
+//! Improves a generated text over several rounds of:
+//! - critique: the critic model reviews the current draft for the task, from a `PromptTemplate`
+//! - revision: the generator model rewrites the draft, given the task, the draft and its critique
+//!
+//! The first draft is generated by the generator from the task, or provided by the user. The loop ends after the
+//! maximum number of rounds, or earlier when a `RefinementStop` judges that the critique does not ask for changes:
+//! `KeywordStop` looks for phrases such as "no changes needed", and `SentimentStop` stops when the critique is
+//! classified as positive by a `SentimentModel`. The generator and critic are `TextCompletion`s, which can be the
+//! same model (passed by reference) or different models.
+//!
+//! ```no_run
+//! # fn main() -> anyhow::Result<()> {
+//! use rust_bert::pipelines::self_refine::{SelfRefiner, SentimentStop};
+//! use rust_bert::pipelines::sentiment::SentimentModel;
+//! use rust_bert::pipelines::text_generation::{TextGenerationConfig, TextGenerationModel};
+//!
+//! let model = TextGenerationModel::new(TextGenerationConfig {
+//!     max_length: 256,
+//!     echo_prompt: false,
+//!     ..Default::default()
+//! })?;
+//! let refiner = SelfRefiner::new(&model, &model)
+//!     .with_max_rounds(3)
+//!     .with_stop(SentimentStop::new(SentimentModel::new(Default::default())?, 0.9));
+//! let output = refiner.generate("Write a product description for a waterproof hiking backpack.")?;
+//! for round in &output.rounds {
+//!     println!("Critique: {}", round.critique);
+//! }
+//! println!("{}", output.text);
+//! # Ok(())
+//! # }
+//! ```
+
+use crate::common::error::RustBertError;
+use crate::pipelines::prompt_template::PromptTemplate;
+use crate::pipelines::sentiment::{SentimentModel, SentimentPolarity};
+use crate::pipelines::structured_output::TextCompletion;
+
+/// Default prompt of the critique, with the variables `{task}` and `{draft}`
+pub const DEFAULT_CRITIQUE_TEMPLATE: &str = "Task: {task}\nDraft:\n{draft}\n\nReview the draft and list what should be improved. If the draft is good, answer \"No changes needed\".\nReview:\n";
+
+/// Default prompt of the revision, with the variables `{task}`, `{draft}` and `{critique}`
+pub const DEFAULT_REVISION_TEMPLATE: &str =
+    "Task: {task}\nDraft:\n{draft}\n\nReview:\n{critique}\n\nImproved version of the draft:\n";
+
+/// # Stopping criterion of the refinement loop
+pub trait RefinementStop {
+    /// Returns true if the critique does not ask for further revisions
+    fn should_stop(&self, critique: &str) -> Result<bool, RustBertError>;
+}
+
+/// # Stops the refinement when the critique contains one of a list of phrases (case-insensitive)
+pub struct KeywordStop {
+    phrases: Vec<String>,
+}
+
+impl KeywordStop {
+    /// Creates a new criterion from the phrases signaling an accepted draft (e.g. "no changes needed")
+    pub fn new(phrases: &[&str]) -> KeywordStop {
+        KeywordStop {
+            phrases: phrases.iter().map(|phrase| phrase.to_lowercase()).collect(),
+        }
+    }
+}
+
+impl RefinementStop for KeywordStop {
+    fn should_stop(&self, critique: &str) -> Result<bool, RustBertError> {
+        let critique = critique.to_lowercase();
+        Ok(self
+            .phrases
+            .iter()
+            .any(|phrase| critique.contains(phrase.as_str())))
+    }
+}
+
+/// # Stops the refinement when the critique has a positive sentiment
+pub struct SentimentStop {
+    model: SentimentModel,
+    min_score: f64,
+}
+
+impl SentimentStop {
+    /// Creates a new criterion
+    ///
+    /// # Arguments
+    ///
+    /// * `model` - `SentimentModel` classifying the critiques
+    /// * `min_score` - minimum confidence of the positive polarity to stop the refinement
+    pub fn new(model: SentimentModel, min_score: f64) -> SentimentStop {
+        SentimentStop { model, min_score }
+    }
+}
+
+impl RefinementStop for SentimentStop {
+    fn should_stop(&self, critique: &str) -> Result<bool, RustBertError> {
+        Ok(self
+            .model
+            .predict(&[critique])
+            .first()
+            .map_or(false, |sentiment| {
+                (sentiment.polarity == SentimentPolarity::Positive)
+                    & (sentiment.score >= self.min_score)
+            }))
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+/// # Round of the refinement loop
+pub struct RefinementRound {
+    /// Draft reviewed in this round
+    pub draft: String,
+    /// Critique of the draft
+    pub critique: String,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+/// # Output of the refinement loop
+pub struct RefinementOutput {
+    /// Final text: the last revision, or the accepted draft
+    pub text: String,
+    /// Critique rounds, in order
+    pub rounds: Vec<RefinementRound>,
+    /// True if the loop ended on an accepted draft rather than the maximum number of rounds
+    pub converged: bool,
+}
+
+/// # Generate, critique and revise loop
+pub struct SelfRefiner<G: TextCompletion, C: TextCompletion> {
+    generator: G,
+    critic: C,
+    critique_template: PromptTemplate,
+    revision_template: PromptTemplate,
+    max_rounds: usize,
+    stop: Option<Box<dyn RefinementStop>>,
+}
+
+impl<G: TextCompletion, C: TextCompletion> SelfRefiner<G, C> {
+    /// Creates a new refiner with the default prompts, up to 2 rounds and no stopping criterion
+    ///
+    /// # Arguments
+    ///
+    /// * `generator` - `TextCompletion` generating the first draft and the revisions
+    /// * `critic` - `TextCompletion` generating the critiques
+    pub fn new(generator: G, critic: C) -> SelfRefiner<G, C> {
+        SelfRefiner {
+            generator,
+            critic,
+            critique_template: PromptTemplate::new(DEFAULT_CRITIQUE_TEMPLATE).unwrap(),
+            revision_template: PromptTemplate::new(DEFAULT_REVISION_TEMPLATE).unwrap(),
+            max_rounds: 2,
+            stop: None,
+        }
+    }
+
+    /// Sets the prompt of the critique, which can use the variables `{task}` and `{draft}`
+    pub fn with_critique_template(mut self, template: PromptTemplate) -> SelfRefiner<G, C> {
+        self.critique_template = template;
+        self
+    }
+
+    /// Sets the prompt of the revision, which can use the variables `{task}`, `{draft}` and `{critique}`
+    pub fn with_revision_template(mut self, template: PromptTemplate) -> SelfRefiner<G, C> {
+        self.revision_template = template;
+        self
+    }
+
+    /// Sets the maximum number of critique and revision rounds (default: 2)
+    pub fn with_max_rounds(mut self, max_rounds: usize) -> SelfRefiner<G, C> {
+        self.max_rounds = max_rounds;
+        self
+    }
+
+    /// Sets the criterion ending the loop on an accepted draft (default: none, all the rounds are run)
+    pub fn with_stop<R: RefinementStop + 'static>(mut self, stop: R) -> SelfRefiner<G, C> {
+        self.stop = Some(Box::new(stop));
+        self
+    }
+
+    /// Refines an existing draft
+    ///
+    /// # Arguments
+    ///
+    /// * `task` - instructions the text should fulfill
+    /// * `draft` - first version of the text
+    ///
+    /// # Returns
+    ///
+    /// * `RefinementOutput` with the final text and the critiques
+    pub fn refine(&self, task: &str, draft: &str) -> Result<RefinementOutput, RustBertError> {
+        let mut draft = draft.to_string();
+        let mut rounds = Vec::with_capacity(self.max_rounds);
+        let mut converged = false;
+        for _ in 0..self.max_rounds {
+            let prompt = self
+                .critique_template
+                .render(&[("task", task), ("draft", draft.as_str())])?;
+            let critique = self.critic.complete(&prompt)?.trim().to_string();
+            let accepted = match &self.stop {
+                Some(stop) => stop.should_stop(&critique)?,
+                None => false,
+            };
+            if accepted {
+                rounds.push(RefinementRound {
+                    draft: draft.clone(),
+                    critique,
+                });
+                converged = true;
+                break;
+            }
+            let prompt = self.revision_template.render(&[
+                ("task", task),
+                ("draft", draft.as_str()),
+                ("critique", critique.as_str()),
+            ])?;
+            let revision = self.generator.complete(&prompt)?.trim().to_string();
+            rounds.push(RefinementRound { draft, critique });
+            draft = revision;
+        }
+        Ok(RefinementOutput {
+            text: draft,
+            rounds,
+            converged,
+        })
+    }
+
+    /// Generates a first draft for the task with the generator, then refines it
+    ///
+    /// # Arguments
+    ///
+    /// * `task` - prompt of the first draft, also shared with the critique and revision prompts
+    ///
+    /// # Returns
+    ///
+    /// * `RefinementOutput` with the final text and the critiques
+    pub fn generate(&self, task: &str) -> Result<RefinementOutput, RustBertError> {
+        let draft = self.generator.complete(task)?.trim().to_string();
+        self.refine(task, &draft)
+    }
+}
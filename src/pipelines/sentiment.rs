@@ -55,6 +55,7 @@
 //! ```
 
 use crate::common::error::RustBertError;
+use crate::common::resources::Resource;
 use crate::pipelines::sequence_classification::{
     SequenceClassificationConfig, SequenceClassificationModel,
 };
@@ -152,6 +153,16 @@ impl SentimentModel {
         }
         sentiments
     }
+
+    /// Reloads the weights of the underlying sequence classification model from the resource provided.
+    ///
+    /// # Arguments
+    ///
+    /// * `weights_resource` - `Resource` pointing to the new model weights (e.g. model.ot)
+    pub fn reload_weights(&mut self, weights_resource: &Resource) -> Result<(), RustBertError> {
+        self.sequence_classification_model
+            .reload_weights(weights_resource)
+    }
 }
 #[cfg(test)]
 mod test {
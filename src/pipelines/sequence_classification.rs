@@ -13,6 +13,9 @@
 //! # Sequence classification pipeline (e.g. Sentiment Analysis)
 //! More generic sequence classification pipeline, works with multiple models (Bert, Roberta)
 //!
+//! Regression models (with a single output, e.g. STS-B models and cross-encoder rerankers) return raw continuous
+//! scores with `predict_regression` and, for text pairs, `predict_pair_regression`.
+//!
 //! ```no_run
 //! use rust_bert::pipelines::sequence_classification::SequenceClassificationConfig;
 //! use rust_bert::resources::{RemoteResource, Resource};
@@ -60,19 +63,21 @@
 use crate::albert::AlbertForSequenceClassification;
 use crate::bart::BartForSequenceClassification;
 use crate::bert::BertForSequenceClassification;
+use crate::common::classification_head::ClassificationHeadConfig;
 use crate::common::error::RustBertError;
 use crate::common::resources::{RemoteResource, Resource};
+use crate::common::weights::reload_var_store;
 use crate::distilbert::{
     DistilBertConfigResources, DistilBertModelClassifier, DistilBertModelResources,
     DistilBertVocabResources,
 };
 use crate::mobilebert::MobileBertForSequenceClassification;
-use crate::pipelines::common::{ConfigOption, ModelType, TokenizerOption};
+use crate::pipelines::common::{ConfigOption, ModelType, TensorBatch, TokenizerOption};
+use crate::pipelines::custom_models::{CustomModelRegistry, CustomSequenceClassifier};
 use crate::reformer::ReformerForSequenceClassification;
 use crate::roberta::RobertaForSequenceClassification;
 use crate::xlnet::XLNetForSequenceClassification;
 use rust_tokenizers::tokenizer::TruncationStrategy;
-use rust_tokenizers::TokenizedInput;
 use serde::{Deserialize, Serialize};
 use std::borrow::Borrow;
 use std::collections::HashMap;
@@ -114,6 +119,12 @@ pub struct SequenceClassificationConfig {
     pub add_prefix_space: Option<bool>,
     /// Device to place the model on (default: CUDA/GPU when available)
     pub device: Device,
+    /// Copy the inputs to the device through pinned host memory, asynchronously (default: false). Only used for
+    /// CUDA devices, see `pipelines::common::to_device`
+    pub pinned_memory: bool,
+    /// Architecture of the classification head, for checkpoints fine-tuned with a non-default head (default: None,
+    /// head of the original model). Only supported for BERT, RoBERTa, XLM-RoBERTa, DistilBERT and ALBERT.
+    pub classification_head: Option<ClassificationHeadConfig>,
 }
 
 impl SequenceClassificationConfig {
@@ -147,6 +158,8 @@ impl SequenceClassificationConfig {
             strip_accents: strip_accents.into(),
             add_prefix_space: add_prefix_space.into(),
             device: Device::cuda_if_available(),
+            pinned_memory: false,
+            classification_head: None,
         }
     }
 }
@@ -170,6 +183,8 @@ impl Default for SequenceClassificationConfig {
             strip_accents: None,
             add_prefix_space: None,
             device: Device::cuda_if_available(),
+            pinned_memory: false,
+            classification_head: None,
         }
     }
 }
@@ -194,6 +209,8 @@ pub enum SequenceClassificationOption {
     Bart(BartForSequenceClassification),
     /// Reformer for Sequence Classification
     Reformer(ReformerForSequenceClassification),
+    /// Model registered in a `CustomModelRegistry`, with the `ModelType` of its tokenizer
+    Custom(ModelType, Box<dyn CustomSequenceClassifier>),
 }
 
 impl SequenceClassificationOption {
@@ -213,12 +230,53 @@ impl SequenceClassificationOption {
     where
         P: Borrow<nn::Path<'p>>,
     {
+        SequenceClassificationOption::new_with_head(model_type, p, config, None)
+    }
+
+    /// Instantiate a new sequence classification model of the supplied type, with a custom classification head if
+    /// `head_config` is provided.
+    ///
+    /// # Arguments
+    ///
+    /// * `model_type` - `ModelType` indicating the model type to load (must match with the actual data to be loaded)
+    /// * `p` - `tch::nn::Path` path to the model file to load (e.g. model.ot)
+    /// * `config` - A configuration (the model type of the configuration must be compatible with the value for
+    /// `model_type`)
+    /// * `head_config` - Optional `ClassificationHeadConfig` defining the classification head architecture. Only
+    /// supported for BERT, RoBERTa, XLM-RoBERTa, DistilBERT and ALBERT.
+    pub fn new_with_head<'p, P>(
+        model_type: ModelType,
+        p: P,
+        config: &ConfigOption,
+        head_config: Option<&ClassificationHeadConfig>,
+    ) -> Result<Self, RustBertError>
+    where
+        P: Borrow<nn::Path<'p>>,
+    {
+        if head_config.is_some()
+            && !matches!(
+                model_type,
+                ModelType::Bert
+                    | ModelType::Roberta
+                    | ModelType::XLMRoberta
+                    | ModelType::DistilBert
+                    | ModelType::Albert
+            )
+        {
+            return Err(RustBertError::InvalidConfigurationError(format!(
+                "Custom classification heads are not supported for {:?}",
+                model_type
+            )));
+        }
         match model_type {
             ModelType::Bert => {
                 if let ConfigOption::Bert(config) = config {
-                    Ok(SequenceClassificationOption::Bert(
-                        BertForSequenceClassification::new(p, config),
-                    ))
+                    Ok(SequenceClassificationOption::Bert(match head_config {
+                        Some(head_config) => {
+                            BertForSequenceClassification::new_with_head(p, config, head_config)
+                        }
+                        None => BertForSequenceClassification::new(p, config),
+                    }))
                 } else {
                     Err(RustBertError::InvalidConfigurationError(
                         "You can only supply a BertConfig for Bert!".to_string(),
@@ -228,7 +286,12 @@ impl SequenceClassificationOption {
             ModelType::DistilBert => {
                 if let ConfigOption::DistilBert(config) = config {
                     Ok(SequenceClassificationOption::DistilBert(
-                        DistilBertModelClassifier::new(p, config),
+                        match head_config {
+                            Some(head_config) => {
+                                DistilBertModelClassifier::new_with_head(p, config, head_config)
+                            }
+                            None => DistilBertModelClassifier::new(p, config),
+                        },
                     ))
                 } else {
                     Err(RustBertError::InvalidConfigurationError(
@@ -249,9 +312,12 @@ impl SequenceClassificationOption {
             }
             ModelType::Roberta => {
                 if let ConfigOption::Bert(config) = config {
-                    Ok(SequenceClassificationOption::Roberta(
-                        RobertaForSequenceClassification::new(p, config),
-                    ))
+                    Ok(SequenceClassificationOption::Roberta(match head_config {
+                        Some(head_config) => {
+                            RobertaForSequenceClassification::new_with_head(p, config, head_config)
+                        }
+                        None => RobertaForSequenceClassification::new(p, config),
+                    }))
                 } else {
                     Err(RustBertError::InvalidConfigurationError(
                         "You can only supply a BertConfig for Roberta!".to_string(),
@@ -261,7 +327,14 @@ impl SequenceClassificationOption {
             ModelType::XLMRoberta => {
                 if let ConfigOption::Bert(config) = config {
                     Ok(SequenceClassificationOption::XLMRoberta(
-                        RobertaForSequenceClassification::new(p, config),
+                        match head_config {
+                            Some(head_config) => RobertaForSequenceClassification::new_with_head(
+                                p,
+                                config,
+                                head_config,
+                            ),
+                            None => RobertaForSequenceClassification::new(p, config),
+                        },
                     ))
                 } else {
                     Err(RustBertError::InvalidConfigurationError(
@@ -271,9 +344,12 @@ impl SequenceClassificationOption {
             }
             ModelType::Albert => {
                 if let ConfigOption::Albert(config) = config {
-                    Ok(SequenceClassificationOption::Albert(
-                        AlbertForSequenceClassification::new(p, config),
-                    ))
+                    Ok(SequenceClassificationOption::Albert(match head_config {
+                        Some(head_config) => {
+                            AlbertForSequenceClassification::new_with_head(p, config, head_config)
+                        }
+                        None => AlbertForSequenceClassification::new(p, config),
+                    }))
                 } else {
                     Err(RustBertError::InvalidConfigurationError(
                         "You can only supply an AlbertConfig for Albert!".to_string(),
@@ -332,6 +408,7 @@ impl SequenceClassificationOption {
             Self::XLNet(_) => ModelType::XLNet,
             Self::Bart(_) => ModelType::Bart,
             Self::Reformer(_) => ModelType::Reformer,
+            Self::Custom(model_type, _) => model_type,
         }
     }
 
@@ -433,6 +510,14 @@ impl SequenceClassificationOption {
                     .expect("Error in Reformer forward pass.")
                     .logits
             }
+            Self::Custom(_, ref model) => model.forward_t(
+                input_ids,
+                mask,
+                token_type_ids,
+                position_ids,
+                input_embeds,
+                train,
+            ),
         }
     }
 }
@@ -443,6 +528,7 @@ pub struct SequenceClassificationModel {
     sequence_classifier: SequenceClassificationOption,
     label_mapping: HashMap<i64, String>,
     var_store: VarStore,
+    pinned_memory: bool,
 }
 
 impl SequenceClassificationModel {
@@ -484,9 +570,14 @@ impl SequenceClassificationModel {
             config.add_prefix_space,
         )?;
         let mut var_store = VarStore::new(device);
-        let model_config = ConfigOption::from_file(config.model_type, config_path);
-        let sequence_classifier =
-            SequenceClassificationOption::new(config.model_type, &var_store.root(), &model_config)?;
+        let model_config = ConfigOption::try_from_file(config.model_type, config_path)?;
+        tokenizer.check_model_compatibility(config.model_type, model_config.get_vocab_size())?;
+        let sequence_classifier = SequenceClassificationOption::new_with_head(
+            config.model_type,
+            &var_store.root(),
+            &model_config,
+            config.classification_head.as_ref(),
+        )?;
         let label_mapping = model_config.get_label_mapping();
         var_store.load(weights_path)?;
         Ok(SequenceClassificationModel {
@@ -494,36 +585,73 @@ impl SequenceClassificationModel {
             sequence_classifier,
             label_mapping,
             var_store,
+            pinned_memory: config.pinned_memory,
+        })
+    }
+
+    /// Build a new `SequenceClassificationModel` from a model registered in a `CustomModelRegistry`. The tokenizer is
+    /// created from the `model_type` of the configuration, and the label mapping is provided by the custom model.
+    ///
+    /// # Arguments
+    ///
+    /// * `config` - `SequenceClassificationConfig` object containing the resource references (model, vocabulary, configuration) and device placement (CPU/GPU)
+    /// * `registry` - `CustomModelRegistry` holding the custom model constructors
+    /// * `name` - name the sequence classifier was registered with
+    pub fn new_with_custom_model(
+        config: SequenceClassificationConfig,
+        registry: &CustomModelRegistry,
+        name: &str,
+    ) -> Result<SequenceClassificationModel, RustBertError> {
+        let config_path = config.config_resource.get_local_path()?;
+        let vocab_path = config.vocab_resource.get_local_path()?;
+        let weights_path = config.model_resource.get_local_path()?;
+        let merges_path = if let Some(merges_resource) = &config.merges_resource {
+            Some(merges_resource.get_local_path()?)
+        } else {
+            None
+        };
+
+        let tokenizer = TokenizerOption::from_file(
+            config.model_type,
+            vocab_path.to_str().unwrap(),
+            merges_path.as_deref().map(|path| path.to_str().unwrap()),
+            config.lower_case,
+            config.strip_accents,
+            config.add_prefix_space,
+        )?;
+        let mut var_store = VarStore::new(config.device);
+        let custom_model =
+            registry.build_sequence_classifier(name, &var_store.root(), &config_path)?;
+        let label_mapping = custom_model.label_mapping();
+        var_store.load(weights_path)?;
+        Ok(SequenceClassificationModel {
+            tokenizer,
+            sequence_classifier: SequenceClassificationOption::Custom(
+                config.model_type,
+                custom_model,
+            ),
+            label_mapping,
+            var_store,
+            pinned_memory: config.pinned_memory,
         })
     }
 
-    fn prepare_for_model<'a, S>(&self, input: S) -> Tensor
+    fn prepare_for_model<'a, S>(&self, input: S) -> TensorBatch
     where
         S: AsRef<[&'a str]>,
     {
-        let tokenized_input: Vec<TokenizedInput> =
-            self.tokenizer
-                .encode_list(input.as_ref(), 128, &TruncationStrategy::LongestFirst, 0);
-        let max_len = tokenized_input
-            .iter()
-            .map(|input| input.token_ids.len())
-            .max()
-            .unwrap();
-        let tokenized_input_tensors: Vec<tch::Tensor> = tokenized_input
-            .iter()
-            .map(|input| input.token_ids.clone())
-            .map(|mut input| {
-                input.extend(vec![
-                    self.tokenizer.get_pad_id().expect(
-                        "The Tokenizer used for sequence classification should contain a PAD id"
-                    );
-                    max_len - input.len()
-                ]);
-                input
-            })
-            .map(|input| Tensor::of_slice(&(input)))
-            .collect::<Vec<_>>();
-        Tensor::stack(tokenized_input_tensors.as_slice(), 0).to(self.var_store.device())
+        let token_ids = self
+            .tokenizer
+            .encode_list(input.as_ref(), 128, &TruncationStrategy::LongestFirst, 0)
+            .into_iter()
+            .map(|input| input.token_ids)
+            .collect::<Vec<Vec<i64>>>();
+        let pad_id = self
+            .tokenizer
+            .get_pad_id()
+            .expect("The Tokenizer used for sequence classification should contain a PAD id");
+        TensorBatch::from_token_ids(&token_ids, pad_id, self.var_store.device())
+            .with_pinned_memory(self.pinned_memory)
     }
 
     /// Classify texts
@@ -556,10 +684,10 @@ impl SequenceClassificationModel {
     where
         S: AsRef<[&'a str]>,
     {
-        let input_tensor = self.prepare_for_model(input.as_ref());
+        let input_batch = self.prepare_for_model(input.as_ref());
         let output = no_grad(|| {
             let output = self.sequence_classifier.forward_t(
-                Some(input_tensor.copy()),
+                Some(input_batch.input_ids()),
                 None,
                 None,
                 None,
@@ -625,10 +753,10 @@ impl SequenceClassificationModel {
         input: &[&str],
         threshold: f64,
     ) -> Result<Vec<Vec<Label>>, RustBertError> {
-        let input_tensor = self.prepare_for_model(input.to_vec());
+        let input_batch = self.prepare_for_model(input.to_vec());
         let output = no_grad(|| {
             let output = self.sequence_classifier.forward_t(
-                Some(input_tensor.copy()),
+                Some(input_batch.input_ids()),
                 None,
                 None,
                 None,
@@ -668,6 +796,199 @@ impl SequenceClassificationModel {
         }
         Ok(labels)
     }
+
+    /// Returns the mapping from the label ids of the model to the label names
+    pub fn get_label_mapping(&self) -> &HashMap<i64, String> {
+        &self.label_mapping
+    }
+
+    /// Returns true if the model is a regression model, with a single output and no label
+    /// (e.g. STS-B models and cross-encoder rerankers)
+    pub fn is_regression(&self) -> bool {
+        self.label_mapping.len() == 1
+    }
+
+    fn prepare_pairs_for_model(&self, input: &[(&str, &str)]) -> TensorBatch {
+        let tokenized_input =
+            self.tokenizer
+                .encode_pair_list(input, 128, &TruncationStrategy::LongestFirst, 0);
+        let token_ids = tokenized_input
+            .iter()
+            .map(|input| input.token_ids.clone())
+            .collect::<Vec<Vec<i64>>>();
+        let pad_id = self
+            .tokenizer
+            .get_pad_id()
+            .expect("The Tokenizer used for sequence classification should contain a PAD id");
+        let input_batch = TensorBatch::from_token_ids(&token_ids, pad_id, self.var_store.device())
+            .with_pinned_memory(self.pinned_memory);
+        match self.sequence_classifier.model_type() {
+            ModelType::Bert | ModelType::Albert | ModelType::MobileBert | ModelType::XLNet => {
+                let max_len = token_ids.iter().map(Vec::len).max().unwrap_or(0);
+                let segment_ids = tokenized_input
+                    .iter()
+                    .map(|input| {
+                        let mut segment_ids = input
+                            .segment_ids
+                            .iter()
+                            .map(|&segment_id| segment_id as i64)
+                            .collect::<Vec<i64>>();
+                        segment_ids.resize(max_len, 0);
+                        Tensor::of_slice(&segment_ids)
+                    })
+                    .collect::<Vec<Tensor>>();
+                input_batch.with_token_type_ids(Tensor::stack(&segment_ids, 0))
+            }
+            _ => input_batch,
+        }
+    }
+
+    fn regression_scores(&self, input_batch: TensorBatch) -> Result<Vec<f64>, RustBertError> {
+        if input_batch.batch_size() == 0 {
+            return Ok(vec![]);
+        }
+        let output = no_grad(|| {
+            self.sequence_classifier
+                .forward_t(
+                    Some(input_batch.input_ids()),
+                    input_batch.attention_mask(),
+                    input_batch.token_type_ids(),
+                    None,
+                    None,
+                    false,
+                )
+                .detach()
+                .to(Device::Cpu)
+        });
+        let num_outputs = output.size()[1];
+        if num_outputs != 1 {
+            return Err(RustBertError::ValueError(format!(
+                "Regression scores require a model with a single output, got {} outputs",
+                num_outputs
+            )));
+        }
+        Ok(output
+            .squeeze1(1)
+            .iter::<f64>()
+            .unwrap()
+            .collect::<Vec<f64>>())
+    }
+
+    /// Returns the raw score of regression models (with a single output, e.g. STS-B models) for texts. No
+    /// softmax is applied to the output of the model.
+    ///
+    /// # Arguments
+    ///
+    /// * `input` - `&[&str]` Array of texts to score.
+    ///
+    /// # Returns
+    ///
+    /// * `Vec<f64>` containing the score of each text, or a `RustBertError::ValueError` if the model has more than one output
+    pub fn predict_regression<'a, S>(&self, input: S) -> Result<Vec<f64>, RustBertError>
+    where
+        S: AsRef<[&'a str]>,
+    {
+        self.regression_scores(self.prepare_for_model(input.as_ref()))
+    }
+
+    /// Returns the raw score of regression models for text pairs, e.g. the similarity of two sentences for STS-B
+    /// models or the relevance of a passage for a query for cross-encoder rerankers. The pairs are encoded jointly,
+    /// with the token type ids of the second text for the models using them.
+    ///
+    /// # Arguments
+    ///
+    /// * `input` - `&[(&str, &str)]` Array of text pairs to score.
+    ///
+    /// # Returns
+    ///
+    /// * `Vec<f64>` containing the score of each pair, or a `RustBertError::ValueError` if the model has more than one output
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// # fn main() -> anyhow::Result<()> {
+    /// # use rust_bert::pipelines::sequence_classification::{SequenceClassificationConfig, SequenceClassificationModel};
+    /// # let config: SequenceClassificationConfig = unimplemented!();
+    /// let sts_model = SequenceClassificationModel::new(config)?;
+    /// let input = [
+    ///     ("A man is playing a guitar.", "A person plays the guitar."),
+    ///     ("A man is playing a guitar.", "A chef is cooking pasta."),
+    /// ];
+    /// let similarities = sts_model.predict_pair_regression(&input)?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn predict_pair_regression(
+        &self,
+        input: &[(&str, &str)],
+    ) -> Result<Vec<f64>, RustBertError> {
+        if input.is_empty() {
+            return Ok(vec![]);
+        }
+        self.regression_scores(self.prepare_pairs_for_model(input))
+    }
+
+    /// Returns the probabilities of all labels for text pairs, e.g. the entailment, neutral and contradiction
+    /// probabilities of (premise, hypothesis) pairs for Natural Language Inference models. The pairs are encoded
+    /// jointly, with the token type ids of the second text for the models using them.
+    ///
+    /// # Arguments
+    ///
+    /// * `input` - `&[(&str, &str)]` Array of text pairs to classify.
+    ///
+    /// # Returns
+    ///
+    /// * `Vec<Vec<Label>>` containing the labels of each pair, ordered by label id, with their softmax probability
+    pub fn predict_pairs(&self, input: &[(&str, &str)]) -> Vec<Vec<Label>> {
+        if input.is_empty() {
+            return vec![];
+        }
+        let input_batch = self.prepare_pairs_for_model(input);
+        let output = no_grad(|| {
+            let output = self.sequence_classifier.forward_t(
+                Some(input_batch.input_ids()),
+                input_batch.attention_mask(),
+                input_batch.token_type_ids(),
+                None,
+                None,
+                false,
+            );
+            output.softmax(-1, Kind::Float).detach().to(Device::Cpu)
+        });
+        let num_labels = output.size()[1] as usize;
+        let scores = Vec::<f64>::from(output.view(-1));
+        scores
+            .chunks(num_labels)
+            .enumerate()
+            .map(|(sentence, pair_scores)| {
+                pair_scores
+                    .iter()
+                    .enumerate()
+                    .map(|(id, &score)| Label {
+                        text: self
+                            .label_mapping
+                            .get(&(id as i64))
+                            .cloned()
+                            .unwrap_or_else(|| id.to_string()),
+                        score,
+                        id: id as i64,
+                        sentence,
+                    })
+                    .collect()
+            })
+            .collect()
+    }
+
+    /// Reloads the weights of the sequence classification model from the resource provided, keeping the tokenizer and configuration
+    /// unchanged. The weights must match the architecture of the model currently loaded, the current weights
+    /// are kept if the new weights cannot be loaded.
+    ///
+    /// # Arguments
+    ///
+    /// * `weights_resource` - `Resource` pointing to the new model weights (e.g. model.ot)
+    pub fn reload_weights(&mut self, weights_resource: &Resource) -> Result<(), RustBertError> {
+        reload_var_store(&mut self.var_store, weights_resource)
+    }
 }
 
 #[cfg(test)]
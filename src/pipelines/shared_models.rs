@@ -0,0 +1,211 @@
+// Copyright 2021 Guillaume Becquin
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//     http://www.apache.org/licenses/LICENSE-2.0
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! # Registry of models shared across pipelines
+//! Loading the same weights for several pipelines (for example a T5 model used for both summarization and translation)
+//! duplicates the model weights and tokenizer in memory. The `SharedModelRegistry` keeps track of the encoder-decoder
+//! generators already loaded, and creates new generators sharing the same weights and tokenizer (reference-counted)
+//! with their own generation configuration. Each generator owns a model and variable store, whose variables share the
+//! memory of the weights loaded: the generators can be moved across threads, and reloading the weights of one of them
+//! (`reload_weights`) updates the weights of all the generators created from the same model.
+//!
+//! ```no_run
+//! # fn main() -> anyhow::Result<()> {
+//! use rust_bert::pipelines::common::ModelType;
+//! use rust_bert::pipelines::shared_models::SharedModelRegistry;
+//! use rust_bert::pipelines::summarization::{SummarizationConfig, SummarizationModel};
+//! use rust_bert::pipelines::translation::{TranslationConfig, TranslationModel};
+//! use rust_bert::resources::{RemoteResource, Resource};
+//! use rust_bert::t5::{T5ConfigResources, T5ModelResources, T5VocabResources};
+//! use tch::Device;
+//!
+//! let mut registry = SharedModelRegistry::new();
+//!
+//! let model_resource = Resource::Remote(RemoteResource::from_pretrained(T5ModelResources::T5_BASE));
+//! let config_resource =
+//!     Resource::Remote(RemoteResource::from_pretrained(T5ConfigResources::T5_BASE));
+//! let vocab_resource = Resource::Remote(RemoteResource::from_pretrained(T5VocabResources::T5_BASE));
+//!
+//! let summarization_config = SummarizationConfig {
+//!     model_type: ModelType::T5,
+//!     model_resource: model_resource.clone(),
+//!     config_resource: config_resource.clone(),
+//!     vocab_resource: vocab_resource.clone(),
+//!     merges_resource: vocab_resource.clone(),
+//!     ..Default::default()
+//! };
+//! let summarization_model =
+//!     SummarizationModel::new_with_registry(summarization_config, &mut registry)?;
+//!
+//! let translation_config = TranslationConfig::new_from_resources(
+//!     model_resource,
+//!     config_resource,
+//!     vocab_resource.clone(),
+//!     vocab_resource,
+//!     Some("translate English to French: ".to_string()),
+//!     Device::cuda_if_available(),
+//!     ModelType::T5,
+//! );
+//! // The translation model shares the weights loaded for the summarization model
+//! let translation_model = TranslationModel::new_with_registry(translation_config, &mut registry)?;
+//! # Ok(())
+//! # }
+//! ```
+
+use crate::common::error::RustBertError;
+use crate::pipelines::common::ModelType;
+use crate::pipelines::generation_utils::{
+    BartGenerator, GenerateConfig, MarianGenerator, T5Generator,
+};
+use std::collections::HashMap;
+use std::path::PathBuf;
+
+enum SharedGenerator {
+    Bart(BartGenerator),
+    Marian(MarianGenerator),
+    T5(T5Generator),
+}
+
+#[derive(Hash, PartialEq, Eq)]
+struct SharedModelKey {
+    model_type: String,
+    weights_path: PathBuf,
+    device: String,
+}
+
+impl SharedModelKey {
+    fn new(
+        model_type: ModelType,
+        generate_config: &GenerateConfig,
+    ) -> Result<SharedModelKey, RustBertError> {
+        Ok(SharedModelKey {
+            model_type: format!("{:?}", model_type),
+            weights_path: generate_config.model_resource.get_local_path()?,
+            device: format!("{:?}", generate_config.device),
+        })
+    }
+}
+
+/// # Registry of generators sharing their weights and tokenizer
+/// Generators are identified by their model type, weights resource and device. The first request for a given
+/// model loads it, subsequent requests return generators sharing the same underlying weights.
+#[derive(Default)]
+pub struct SharedModelRegistry {
+    generators: HashMap<SharedModelKey, SharedGenerator>,
+}
+
+impl SharedModelRegistry {
+    /// Creates an empty registry
+    pub fn new() -> SharedModelRegistry {
+        SharedModelRegistry {
+            generators: HashMap::new(),
+        }
+    }
+
+    /// Returns the number of distinct models loaded in the registry
+    pub fn len(&self) -> usize {
+        self.generators.len()
+    }
+
+    /// Returns `true` if no model has been loaded in the registry
+    pub fn is_empty(&self) -> bool {
+        self.generators.is_empty()
+    }
+
+    /// Returns a `BartGenerator` with the generation configuration provided, loading the model if it is not already
+    /// available in the registry.
+    ///
+    /// # Arguments
+    ///
+    /// * `generate_config` - `GenerateConfig` containing the model resources and generation options
+    pub fn bart_generator(
+        &mut self,
+        generate_config: GenerateConfig,
+    ) -> Result<BartGenerator, RustBertError> {
+        let key = SharedModelKey::new(ModelType::Bart, &generate_config)?;
+        match self.generators.get(&key) {
+            Some(SharedGenerator::Bart(generator)) => {
+                generator.with_generate_config(generate_config)
+            }
+            _ => {
+                let generator = BartGenerator::new(generate_config.clone())?;
+                let output = generator.with_generate_config(generate_config)?;
+                self.generators
+                    .insert(key, SharedGenerator::Bart(generator));
+                Ok(output)
+            }
+        }
+    }
+
+    /// Returns a `MarianGenerator` with the generation configuration provided, loading the model if it is not already
+    /// available in the registry.
+    ///
+    /// # Arguments
+    ///
+    /// * `generate_config` - `GenerateConfig` containing the model resources and generation options
+    pub fn marian_generator(
+        &mut self,
+        generate_config: GenerateConfig,
+    ) -> Result<MarianGenerator, RustBertError> {
+        let key = SharedModelKey::new(ModelType::Marian, &generate_config)?;
+        match self.generators.get(&key) {
+            Some(SharedGenerator::Marian(generator)) => {
+                generator.with_generate_config(generate_config)
+            }
+            _ => {
+                let generator = MarianGenerator::new(generate_config.clone())?;
+                let output = generator.with_generate_config(generate_config)?;
+                self.generators
+                    .insert(key, SharedGenerator::Marian(generator));
+                Ok(output)
+            }
+        }
+    }
+
+    /// Returns a `T5Generator` with the generation configuration provided, loading the model if it is not already
+    /// available in the registry.
+    ///
+    /// # Arguments
+    ///
+    /// * `generate_config` - `GenerateConfig` containing the model resources and generation options
+    pub fn t5_generator(
+        &mut self,
+        generate_config: GenerateConfig,
+    ) -> Result<T5Generator, RustBertError> {
+        let key = SharedModelKey::new(ModelType::T5, &generate_config)?;
+        match self.generators.get(&key) {
+            Some(SharedGenerator::T5(generator)) => generator.with_generate_config(generate_config),
+            _ => {
+                let generator = T5Generator::new(generate_config.clone())?;
+                let output = generator.with_generate_config(generate_config)?;
+                self.generators.insert(key, SharedGenerator::T5(generator));
+                Ok(output)
+            }
+        }
+    }
+
+    /// Removes all models from the registry. Generators previously returned by the registry remain valid,
+    /// the memory of a model is released once all generators sharing it are dropped.
+    pub fn clear(&mut self) {
+        self.generators.clear();
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    #[ignore] // no need to run, compilation is enough to verify it is Send
+    fn test() {
+        let _: Box<dyn Send> = Box::new(SharedModelRegistry::new());
+    }
+}
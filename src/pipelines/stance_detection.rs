@@ -0,0 +1,371 @@
+// Copyright 2021 Guillaume Becquin
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//     http://www.apache.org/licenses/LICENSE-2.0
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! # Stance detection and claim verification pipeline
+//! Classifies the stance of a text toward a claim as `Agree`, `Disagree`, `Discuss` or `Unrelated` (the classes of
+//! the Fake News Challenge), using a model fine-tuned for Natural Language Inference (by default a BART model
+//! fine-tuned on MNLI). The text can be a long document:
+//! - the document is split into sentences, and the sentences sharing the most words with the claim are selected as
+//! evidence (at most `max_evidence_sentences`, with a relevance of at least `min_relevance`). A document without
+//! relevant sentence is `Unrelated` to the claim.
+//! - each evidence sentence is used as the premise and the claim as the hypothesis of the NLI model. The document
+//! `Agree`s with the claim if an evidence sentence entails it, and `Disagree`s if an evidence sentence contradicts it,
+//! with a probability of at least `min_confidence`. Otherwise the document `Discuss`es the claim.
+//!
+//! The evidence sentences are returned with their offsets in the document and NLI probabilities, for fact-checking
+//! tools displaying the passages supporting or refuting a claim.
+//!
+//! ```no_run
+//! # fn main() -> anyhow::Result<()> {
+//! use rust_bert::pipelines::stance_detection::{StanceDetectionConfig, StanceDetectionModel};
+//!
+//! let model = StanceDetectionModel::new(StanceDetectionConfig::default())?;
+//! let claim = "K2-18b is the first habitable zone planet with water vapour in its atmosphere.";
+//! let document = "Astronomers confirmed the presence of water vapour in the atmosphere of K2-18b. \
+//!                 This is the first such discovery in a planet in its star's habitable zone.";
+//! let output = model.predict(&[(claim, document)])?;
+//! println!("{:?}: {}", output[0].stance, output[0].score);
+//! # Ok(())
+//! # }
+//! ```
+
+use crate::bart::{
+    BartConfigResources, BartMergesResources, BartModelResources, BartVocabResources,
+};
+use crate::common::error::RustBertError;
+use crate::pipelines::common::ModelType;
+use crate::pipelines::sequence_classification::{
+    SequenceClassificationConfig, SequenceClassificationModel,
+};
+use crate::resources::{RemoteResource, Resource};
+use std::cmp::Ordering;
+use std::collections::HashSet;
+
+const STOP_WORDS: [&str; 32] = [
+    "about", "also", "and", "are", "been", "but", "can", "for", "from", "had", "has", "have",
+    "her", "his", "into", "its", "not", "our", "than", "that", "the", "their", "then", "they",
+    "this", "was", "were", "which", "who", "will", "with", "you",
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+/// # Stance of a text toward a claim
+pub enum Stance {
+    /// The text supports the claim
+    Agree,
+    /// The text refutes the claim
+    Disagree,
+    /// The text is about the claim without taking position
+    Discuss,
+    /// The text is not about the claim
+    Unrelated,
+}
+
+/// # Configuration for StanceDetectionModel
+pub struct StanceDetectionConfig {
+    /// Natural Language Inference model, with `entailment` and `contradiction` labels (default: BART fine-tuned on MNLI)
+    pub nli_config: SequenceClassificationConfig,
+    /// Maximum number of evidence sentences selected in each document (default: 3)
+    pub max_evidence_sentences: usize,
+    /// Minimum proportion of the claim words present in a sentence for it to be used as evidence (default: 0.2)
+    pub min_relevance: f64,
+    /// Minimum entailment or contradiction probability for a document to agree or disagree with the claim (default: 0.5)
+    pub min_confidence: f64,
+}
+
+impl Default for StanceDetectionConfig {
+    fn default() -> StanceDetectionConfig {
+        StanceDetectionConfig {
+            nli_config: SequenceClassificationConfig::new(
+                ModelType::Bart,
+                Resource::Remote(RemoteResource::from_pretrained(
+                    BartModelResources::BART_MNLI,
+                )),
+                Resource::Remote(RemoteResource::from_pretrained(
+                    BartConfigResources::BART_MNLI,
+                )),
+                Resource::Remote(RemoteResource::from_pretrained(
+                    BartVocabResources::BART_MNLI,
+                )),
+                Some(Resource::Remote(RemoteResource::from_pretrained(
+                    BartMergesResources::BART_MNLI,
+                ))),
+                false,
+                None,
+                None,
+            ),
+            max_evidence_sentences: 3,
+            min_relevance: 0.2,
+            min_confidence: 0.5,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+/// # Sentence of a document selected as evidence for or against a claim
+pub struct Evidence {
+    /// Text of the sentence
+    pub text: String,
+    /// Byte offsets (start, end) of the sentence in the document
+    pub offsets: (usize, usize),
+    /// Proportion of the claim words present in the sentence
+    pub relevance: f64,
+    /// Probability that the sentence entails the claim
+    pub entailment: f64,
+    /// Probability that the sentence contradicts the claim
+    pub contradiction: f64,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+/// # Stance of a document toward a claim
+pub struct StanceOutput {
+    /// Predicted stance
+    pub stance: Stance,
+    /// Confidence of the prediction: maximum entailment (`Agree`) or contradiction (`Disagree`) probability of the
+    /// evidence, one minus these probabilities for `Discuss`, and one minus the best relevance for `Unrelated`
+    pub score: f64,
+    /// Evidence sentences, by decreasing relevance
+    pub evidence: Vec<Evidence>,
+}
+
+/// # StanceDetectionModel for stance detection and claim verification
+pub struct StanceDetectionModel {
+    nli_model: SequenceClassificationModel,
+    entailment_id: usize,
+    contradiction_id: usize,
+    max_evidence_sentences: usize,
+    min_relevance: f64,
+    min_confidence: f64,
+}
+
+impl StanceDetectionModel {
+    /// Build a new `StanceDetectionModel`
+    ///
+    /// # Arguments
+    ///
+    /// * `config` - `StanceDetectionConfig` object containing the NLI model configuration and evidence selection settings
+    pub fn new(config: StanceDetectionConfig) -> Result<StanceDetectionModel, RustBertError> {
+        let nli_model = SequenceClassificationModel::new(config.nli_config)?;
+        StanceDetectionModel::new_with_model(
+            nli_model,
+            config.max_evidence_sentences,
+            config.min_relevance,
+            config.min_confidence,
+        )
+    }
+
+    /// Build a new `StanceDetectionModel` from a loaded Natural Language Inference model
+    ///
+    /// # Arguments
+    ///
+    /// * `nli_model` - `SequenceClassificationModel` with `entailment` and `contradiction` labels (case insensitive)
+    /// * `max_evidence_sentences` - Maximum number of evidence sentences selected in each document
+    /// * `min_relevance` - Minimum proportion of the claim words present in an evidence sentence
+    /// * `min_confidence` - Minimum entailment or contradiction probability to agree or disagree with the claim
+    pub fn new_with_model(
+        nli_model: SequenceClassificationModel,
+        max_evidence_sentences: usize,
+        min_relevance: f64,
+        min_confidence: f64,
+    ) -> Result<StanceDetectionModel, RustBertError> {
+        let label_mapping = nli_model.get_label_mapping();
+        let label_id = |name: &str| {
+            label_mapping
+                .iter()
+                .find(|(_, label)| label.eq_ignore_ascii_case(name))
+                .map(|(&id, _)| id as usize)
+                .ok_or_else(|| {
+                    RustBertError::InvalidConfigurationError(format!(
+                        "The stance detection model must have a `{}` label",
+                        name
+                    ))
+                })
+        };
+        Ok(StanceDetectionModel {
+            entailment_id: label_id("entailment")?,
+            contradiction_id: label_id("contradiction")?,
+            nli_model,
+            max_evidence_sentences,
+            min_relevance,
+            min_confidence,
+        })
+    }
+
+    /// Predicts the stance of documents toward claims
+    ///
+    /// # Arguments
+    ///
+    /// * `input` - `&[(&str, &str)]` Array of (claim, document) pairs
+    ///
+    /// # Returns
+    ///
+    /// * `Vec<StanceOutput>` containing the stance and evidence of each document
+    pub fn predict(&self, input: &[(&str, &str)]) -> Result<Vec<StanceOutput>, RustBertError> {
+        let (best_relevance, mut evidence): (Vec<f64>, Vec<Vec<Evidence>>) = input
+            .iter()
+            .map(|(claim, document)| self.select_evidence(claim, document))
+            .unzip();
+        let pairs = input
+            .iter()
+            .zip(evidence.iter())
+            .flat_map(|((claim, _), document_evidence)| {
+                document_evidence
+                    .iter()
+                    .map(move |sentence| (sentence.text.as_str(), *claim))
+            })
+            .collect::<Vec<(&str, &str)>>();
+        let mut scores = self.nli_model.predict_pairs(&pairs).into_iter();
+        for sentence in evidence.iter_mut().flatten() {
+            let labels = scores.next().ok_or_else(|| {
+                RustBertError::ValueError("Missing NLI prediction for an evidence sentence".into())
+            })?;
+            sentence.entailment = labels[self.entailment_id].score;
+            sentence.contradiction = labels[self.contradiction_id].score;
+        }
+
+        Ok(best_relevance
+            .into_iter()
+            .zip(evidence.into_iter())
+            .map(|(best_relevance, evidence)| self.aggregate(best_relevance, evidence))
+            .collect())
+    }
+
+    /// Verifies a claim against documents, returning the documents supporting or refuting it with their stance,
+    /// by decreasing score
+    ///
+    /// # Arguments
+    ///
+    /// * `claim` - Claim to verify
+    /// * `documents` - `&[&str]` Array of documents to check the claim against
+    ///
+    /// # Returns
+    ///
+    /// * `Vec<(usize, StanceOutput)>` containing the index of each `Agree` or `Disagree` document and its stance
+    pub fn verify(
+        &self,
+        claim: &str,
+        documents: &[&str],
+    ) -> Result<Vec<(usize, StanceOutput)>, RustBertError> {
+        let input = documents
+            .iter()
+            .map(|document| (claim, *document))
+            .collect::<Vec<(&str, &str)>>();
+        let mut output = self
+            .predict(&input)?
+            .into_iter()
+            .enumerate()
+            .filter(|(_, output)| matches!(output.stance, Stance::Agree | Stance::Disagree))
+            .collect::<Vec<(usize, StanceOutput)>>();
+        output.sort_by(|(_, a), (_, b)| b.score.partial_cmp(&a.score).unwrap_or(Ordering::Equal));
+        Ok(output)
+    }
+
+    // Returns the best relevance of the document sentences and the evidence sentences
+    fn select_evidence(&self, claim: &str, document: &str) -> (f64, Vec<Evidence>) {
+        let claim_words = content_words(claim);
+        let mut evidence = split_sentences(document)
+            .into_iter()
+            .map(|(start, end)| {
+                let text = &document[start..end];
+                Evidence {
+                    text: text.to_string(),
+                    offsets: (start, end),
+                    relevance: relevance(&claim_words, text),
+                    entailment: 0.0,
+                    contradiction: 0.0,
+                }
+            })
+            .collect::<Vec<Evidence>>();
+        evidence.sort_by(|a, b| {
+            b.relevance
+                .partial_cmp(&a.relevance)
+                .unwrap_or(Ordering::Equal)
+        });
+        let best_relevance = evidence.first().map_or(0.0, |sentence| sentence.relevance);
+        evidence.retain(|sentence| {
+            sentence.relevance > 0.0 && sentence.relevance >= self.min_relevance
+        });
+        evidence.truncate(self.max_evidence_sentences);
+        (best_relevance, evidence)
+    }
+
+    fn aggregate(&self, best_relevance: f64, evidence: Vec<Evidence>) -> StanceOutput {
+        if evidence.is_empty() {
+            return StanceOutput {
+                stance: Stance::Unrelated,
+                score: 1.0 - best_relevance,
+                evidence,
+            };
+        }
+        let entailment = evidence
+            .iter()
+            .map(|sentence| sentence.entailment)
+            .fold(0.0, f64::max);
+        let contradiction = evidence
+            .iter()
+            .map(|sentence| sentence.contradiction)
+            .fold(0.0, f64::max);
+        let (stance, score) = if entailment.max(contradiction) < self.min_confidence {
+            (Stance::Discuss, 1.0 - entailment.max(contradiction))
+        } else if entailment >= contradiction {
+            (Stance::Agree, entailment)
+        } else {
+            (Stance::Disagree, contradiction)
+        };
+        StanceOutput {
+            stance,
+            score,
+            evidence,
+        }
+    }
+}
+
+// Lower-cased words of at least 3 characters, excluding common English stop words
+fn content_words(text: &str) -> HashSet<String> {
+    text.split(|c: char| !c.is_alphanumeric())
+        .filter(|word| word.chars().count() >= 3)
+        .map(str::to_lowercase)
+        .filter(|word| !STOP_WORDS.contains(&word.as_str()))
+        .collect()
+}
+
+// Proportion of the claim words present in the text
+fn relevance(claim_words: &HashSet<String>, text: &str) -> f64 {
+    if claim_words.is_empty() {
+        return 0.0;
+    }
+    let text_words = content_words(text);
+    claim_words.intersection(&text_words).count() as f64 / claim_words.len() as f64
+}
+
+/// Splits a text into sentences, ending at sentence terminators (`.`, `!` or `?`) followed by a white space or the end
+/// of the text, and returns the byte offsets (start, end) of the sentences without their surrounding white spaces
+pub fn split_sentences(text: &str) -> Vec<(usize, usize)> {
+    let mut sentences = vec![];
+    let mut start = 0;
+    let mut chars = text.char_indices().peekable();
+    while let Some((position, c)) = chars.next() {
+        let is_end = match chars.peek() {
+            Some((_, next)) => matches!(c, '.' | '!' | '?') && next.is_whitespace(),
+            None => true,
+        };
+        if is_end {
+            let end = position + c.len_utf8();
+            let sentence = &text[start..end];
+            let trimmed_start = start + (sentence.len() - sentence.trim_start().len());
+            let trimmed_end = start + sentence.trim_end().len();
+            if trimmed_start < trimmed_end {
+                sentences.push((trimmed_start, trimmed_end));
+            }
+            start = end;
+        }
+    }
+    sentences
+}
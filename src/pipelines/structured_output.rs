@@ -0,0 +1,316 @@
+// Copyright 2021 Guillaume Becquin
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//     http://www.apache.org/licenses/LICENSE-2.0
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! # Structured output generation
+//! Produces typed values (e.g. function or tool calls) from the output of instruction models. The JSON value
+//! generated by the model is extracted from the output (ignoring surrounding text or code fences), optionally
+//! validated against a `JsonSchema` given at runtime, and deserialized with `serde` into any type implementing
+//! `Deserialize` (for example with `#[derive(Deserialize)]`). When the output cannot be parsed, the model is prompted
+//! again with its previous answer and the parsing error, up to `max_retries` times.
+//!
+//! The generation itself is delegated to a `TextCompletion`, implemented for `TextGenerationModel`. Combined with a
+//! model restricting the tokens it generates (`grammar` of the generation configuration, for example built with
+//! `Grammar::from_json_schema`, or `prefix_allowed_tokens_fn`), parsing failures become rare and the retries act as a
+//! safety net.
+//!
+//! ```no_run
+//! # fn main() -> anyhow::Result<()> {
+//! use rust_bert::pipelines::structured_output::{JsonSchema, StructuredOutputGenerator};
+//! use rust_bert::pipelines::text_generation::{TextGenerationConfig, TextGenerationModel};
+//! use serde::Deserialize;
+//!
+//! #[derive(Deserialize)]
+//! struct WeatherCall {
+//!     city: String,
+//!     days: u32,
+//! }
+//!
+//! let model = TextGenerationModel::new(TextGenerationConfig {
+//!     echo_prompt: false,
+//!     ..Default::default()
+//! })?;
+//! let generator = StructuredOutputGenerator::new(model)
+//!     .with_schema(JsonSchema::object(vec![
+//!         ("city", JsonSchema::String),
+//!         ("days", JsonSchema::Integer),
+//!     ]))
+//!     .with_max_retries(2);
+//! let call: WeatherCall = generator.generate("Call the weather tool for Paris for the next 3 days.")?;
+//! # Ok(())
+//! # }
+//! ```
+
+use crate::common::error::RustBertError;
+use crate::pipelines::text_generation::TextGenerationModel;
+use serde::de::DeserializeOwned;
+use serde_json::{json, Map, Value};
+
+/// # Generation of a completion for a prompt
+pub trait TextCompletion {
+    /// Returns the text generated after the prompt
+    fn complete(&self, prompt: &str) -> Result<String, RustBertError>;
+}
+
+impl TextCompletion for TextGenerationModel {
+    fn complete(&self, prompt: &str) -> Result<String, RustBertError> {
+        let output = self
+            .generate(&[prompt], None)
+            .into_iter()
+            .next()
+            .unwrap_or_default();
+        Ok(match output.strip_prefix(prompt) {
+            Some(completion) => completion.to_string(),
+            None => output,
+        })
+    }
+}
+
+impl<T: TextCompletion + ?Sized> TextCompletion for &T {
+    fn complete(&self, prompt: &str) -> Result<String, RustBertError> {
+        (**self).complete(prompt)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+/// # Schema of a JSON value
+pub enum JsonSchema {
+    String,
+    Number,
+    Integer,
+    Boolean,
+    /// One of the strings provided
+    Enum(Vec<String>),
+    /// Array of values matching the schema
+    Array(Box<JsonSchema>),
+    /// Object with the fields provided
+    Object(Vec<SchemaField>),
+    /// Value matching the schema, or null
+    Nullable(Box<JsonSchema>),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+/// # Field of a JSON object schema
+pub struct SchemaField {
+    pub name: String,
+    pub schema: JsonSchema,
+    /// The field must be present
+    pub required: bool,
+}
+
+impl JsonSchema {
+    /// Object schema with the required fields provided
+    pub fn object(fields: Vec<(&str, JsonSchema)>) -> JsonSchema {
+        JsonSchema::Object(
+            fields
+                .into_iter()
+                .map(|(name, schema)| SchemaField {
+                    name: name.to_string(),
+                    schema,
+                    required: true,
+                })
+                .collect(),
+        )
+    }
+
+    /// Checks that a value matches the schema, returning a description of the first mismatch otherwise
+    pub fn validate(&self, value: &Value) -> Result<(), String> {
+        self.validate_at("$", value)
+    }
+
+    fn validate_at(&self, path: &str, value: &Value) -> Result<(), String> {
+        let valid = match (self, value) {
+            (JsonSchema::Nullable(_), Value::Null) => true,
+            (JsonSchema::Nullable(schema), value) => return schema.validate_at(path, value),
+            (JsonSchema::String, Value::String(_)) => true,
+            (JsonSchema::Number, Value::Number(_)) => true,
+            (JsonSchema::Integer, Value::Number(number)) => number.is_i64() || number.is_u64(),
+            (JsonSchema::Boolean, Value::Bool(_)) => true,
+            (JsonSchema::Enum(values), Value::String(value)) => values.contains(value),
+            (JsonSchema::Array(schema), Value::Array(values)) => {
+                for (index, value) in values.iter().enumerate() {
+                    schema.validate_at(&format!("{}[{}]", path, index), value)?;
+                }
+                true
+            }
+            (JsonSchema::Object(fields), Value::Object(object)) => {
+                for field in fields {
+                    let field_path = format!("{}.{}", path, field.name);
+                    match object.get(&field.name) {
+                        Some(value) => field.schema.validate_at(&field_path, value)?,
+                        None if field.required => {
+                            return Err(format!("missing required field {}", field_path));
+                        }
+                        None => {}
+                    }
+                }
+                true
+            }
+            _ => false,
+        };
+        if valid {
+            Ok(())
+        } else {
+            Err(format!(
+                "expected {} at {}, got {}",
+                self.to_json_schema(),
+                path,
+                value
+            ))
+        }
+    }
+
+    /// Returns the schema in the JSON Schema format, as included in the prompts
+    pub fn to_json_schema(&self) -> Value {
+        match self {
+            JsonSchema::String => json!({"type": "string"}),
+            JsonSchema::Number => json!({"type": "number"}),
+            JsonSchema::Integer => json!({"type": "integer"}),
+            JsonSchema::Boolean => json!({"type": "boolean"}),
+            JsonSchema::Enum(values) => json!({"type": "string", "enum": values}),
+            JsonSchema::Array(schema) => json!({"type": "array", "items": schema.to_json_schema()}),
+            JsonSchema::Object(fields) => {
+                let properties = fields
+                    .iter()
+                    .map(|field| (field.name.clone(), field.schema.to_json_schema()))
+                    .collect::<Map<String, Value>>();
+                let required = fields
+                    .iter()
+                    .filter(|field| field.required)
+                    .map(|field| field.name.as_str())
+                    .collect::<Vec<&str>>();
+                json!({"type": "object", "properties": properties, "required": required})
+            }
+            JsonSchema::Nullable(schema) => {
+                json!({"anyOf": [schema.to_json_schema(), {"type": "null"}]})
+            }
+        }
+    }
+}
+
+/// Returns the first balanced JSON object or array of a text, skipping any text before it (e.g. an introduction or
+/// the opening of a code fence). Braces within JSON strings are ignored.
+pub fn extract_json(text: &str) -> Option<&str> {
+    let start = text.find(|c: char| c == '{' || c == '[')?;
+    let mut depth = 0usize;
+    let mut in_string = false;
+    let mut escaped = false;
+    for (position, c) in text[start..].char_indices() {
+        if in_string {
+            match c {
+                _ if escaped => escaped = false,
+                '\\' => escaped = true,
+                '"' => in_string = false,
+                _ => {}
+            }
+            continue;
+        }
+        match c {
+            '"' => in_string = true,
+            '{' | '[' => depth += 1,
+            '}' | ']' => {
+                depth -= 1;
+                if depth == 0 {
+                    return Some(&text[start..start + position + 1]);
+                }
+            }
+            _ => {}
+        }
+    }
+    None
+}
+
+/// # Generator of typed values from a text completion model
+pub struct StructuredOutputGenerator<G: TextCompletion> {
+    generator: G,
+    schema: Option<JsonSchema>,
+    max_retries: usize,
+}
+
+impl<G: TextCompletion> StructuredOutputGenerator<G> {
+    /// Creates a new generator, without schema and retrying twice on parsing failures
+    pub fn new(generator: G) -> StructuredOutputGenerator<G> {
+        StructuredOutputGenerator {
+            generator,
+            schema: None,
+            max_retries: 2,
+        }
+    }
+
+    /// Sets the schema the output must match. The schema is appended to the prompts.
+    pub fn with_schema(mut self, schema: JsonSchema) -> StructuredOutputGenerator<G> {
+        self.schema = Some(schema);
+        self
+    }
+
+    /// Sets the maximum number of generations after the first one failed to parse
+    pub fn with_max_retries(mut self, max_retries: usize) -> StructuredOutputGenerator<G> {
+        self.max_retries = max_retries;
+        self
+    }
+
+    /// Builds the prompt passed to the model for the first attempt
+    pub fn build_prompt(&self, prompt: &str) -> String {
+        match &self.schema {
+            Some(schema) => format!(
+                "{}\nAnswer with a JSON value matching this JSON schema: {}\n",
+                prompt,
+                schema.to_json_schema()
+            ),
+            None => format!("{}\nAnswer with a JSON value.\n", prompt),
+        }
+    }
+
+    /// Parses the output of the model
+    pub fn parse<T: DeserializeOwned>(&self, output: &str) -> Result<T, String> {
+        let json = extract_json(output).ok_or_else(|| "no JSON value found".to_string())?;
+        let value: Value =
+            serde_json::from_str(json).map_err(|error| format!("invalid JSON: {}", error))?;
+        if let Some(schema) = &self.schema {
+            schema.validate(&value)?;
+        }
+        serde_json::from_value(value).map_err(|error| format!("unexpected value: {}", error))
+    }
+
+    /// Generates a typed value for a prompt
+    ///
+    /// # Arguments
+    ///
+    /// * `prompt` - instruction given to the model
+    ///
+    /// # Returns
+    ///
+    /// * Deserialized value, or a `RustBertError::ValueError` with the last parsing error if no attempt succeeded
+    pub fn generate<T: DeserializeOwned>(&self, prompt: &str) -> Result<T, RustBertError> {
+        let base_prompt = self.build_prompt(prompt);
+        let mut current_prompt = base_prompt.clone();
+        let mut last_error = String::new();
+        for _ in 0..=self.max_retries {
+            let output = self.generator.complete(&current_prompt)?;
+            match self.parse(&output) {
+                Ok(value) => return Ok(value),
+                Err(error) => {
+                    current_prompt = format!(
+                        "{}{}\nThis answer is invalid ({}). Answer again with a valid JSON value only.\n",
+                        base_prompt,
+                        output.trim(),
+                        error
+                    );
+                    last_error = error;
+                }
+            }
+        }
+        Err(RustBertError::ValueError(format!(
+            "Could not parse the model output after {} attempts: {}",
+            self.max_retries + 1,
+            last_error
+        )))
+    }
+}
@@ -63,17 +63,39 @@
 //! ```
 
 use crate::bart::{
-    BartConfigResources, BartMergesResources, BartModelResources, BartVocabResources,
+    BartConfig, BartConfigResources, BartMergesResources, BartModelResources, BartVocabResources,
 };
 use crate::common::error::RustBertError;
 use crate::common::resources::{RemoteResource, Resource};
+use crate::common::task_registry::TaskRegistry;
 use crate::pipelines::common::ModelType;
+use crate::pipelines::custom_models::{CustomModelRegistry, CustomTextGenerator};
 use crate::pipelines::generation_utils::{
-    BartGenerator, GenerateConfig, LanguageGenerator, T5Generator,
+    BartGenerator, GenerateConfig, GenerateOptions, GenerationUsage, LanguageGenerator,
+    PrefixAllowedTokensFn, StoppingCriteria, T5Generator,
 };
+use crate::pipelines::grammar::Grammar;
+use crate::pipelines::post_processing::{PostProcessor, PostProcessors};
+use crate::pipelines::shared_models::SharedModelRegistry;
+use crate::pipelines::text_normalization::TextNormalizer;
+use crate::t5::T5Config;
+use crate::Config;
 use itertools::Itertools;
+use std::sync::Arc;
 use tch::{Device, Tensor};
 
+#[derive(Debug, Clone, PartialEq)]
+/// # Task prefix prepended to the texts to summarize
+pub enum SummarizationPrefix {
+    /// Prefix of the `summarization` task of the model configuration (`task_specific_params`) if present, otherwise
+    /// `summarize: ` for T5 models and no prefix for other models
+    Default,
+    /// No prefix
+    None,
+    /// Custom prefix, e.g. for models fine-tuned with a different instruction
+    Custom(String),
+}
+
 /// # Configuration for text summarization
 /// Contains information regarding the model to load, mirrors the GenerationConfig, with a
 /// different set of default parameters and sets the device to place the model on.
@@ -92,18 +114,34 @@ pub struct SummarizationConfig {
     pub min_length: i64,
     /// Maximum sequence length (default: 20)
     pub max_length: i64,
+    /// Maximum number of generated tokens, excluding the prompt. Takes precedence over `max_length` when set (default: None)
+    pub max_new_tokens: Option<i64>,
+    /// Minimum number of generated tokens, excluding the prompt. Takes precedence over `min_length` when set (default: None)
+    pub min_new_tokens: Option<i64>,
     /// Sampling flag. If true, will perform top-k and/or nucleus sampling on generated tokens, otherwise greedy (deterministic) decoding (default: true)
     pub do_sample: bool,
+    /// Seed of the sampling. When set, the sampled tokens are drawn from a generator seeded at each call instead of the `tch` random state, so that sampled outputs are reproducible across runs and threads for the same inputs (default: None)
+    pub seed: Option<u64>,
     /// Early stopping flag indicating if the beam search should stop as soon as `num_beam` hypotheses have been generated (default: false)
     pub early_stopping: bool,
     /// Number of beams for beam search (default: 5)
     pub num_beams: i64,
+    /// Number of groups of beams for [diverse beam search, Vijayakumar et al.](https://arxiv.org/abs/1610.02424). `num_beams` must be a multiple of the number of groups (default: 1)
+    pub num_beam_groups: i64,
+    /// Penalty subtracted from the scores of the tokens selected by the previous groups of beams at the same step, for diverse beam search (default: 0.0)
+    pub diversity_penalty: f64,
     /// Temperature setting. Values higher than 1 will improve originality at the risk of reducing relevance (default: 1.0)
     pub temperature: f64,
     /// Top_k values for sampling tokens. Value higher than 0 will enable the feature (default: 0)
     pub top_k: i64,
     /// Top_p value for [Nucleus sampling, Holtzman et al.](http://arxiv.org/abs/1904.09751). Keep top tokens until cumulative probability reaches top_p (default: 0.9)
     pub top_p: f64,
+    /// Probability mass of [typical decoding, Meister et al.](https://arxiv.org/abs/2202.00666): only the tokens with an information content closest to the entropy of the distribution are sampled, up to this cumulative probability. Values lower than 1 enable the feature (default: 1.0)
+    pub typical_p: f64,
+    /// Cutoff of [epsilon sampling, Hewitt et al.](https://arxiv.org/abs/2210.15191): tokens with a probability lower than the cutoff are not sampled. Values higher than 0 enable the feature, typically between 3e-4 and 9e-4 (default: 0.0)
+    pub epsilon_cutoff: f64,
+    /// Cutoff of [eta sampling, Hewitt et al.](https://arxiv.org/abs/2210.15191): tokens with a probability lower than `min(eta_cutoff, sqrt(eta_cutoff) * exp(-entropy))` are not sampled. Values higher than 0 enable the feature, typically between 3e-4 and 2e-3 (default: 0.0)
+    pub eta_cutoff: f64,
     /// Repetition penalty (mostly useful for CTRL decoders). Values higher than 1 will penalize tokens that have been already generated. (default: 1.0)
     pub repetition_penalty: f64,
     /// Exponential penalty based on the length of the hypotheses generated (default: 1.0)
@@ -112,6 +150,36 @@ pub struct SummarizationConfig {
     pub no_repeat_ngram_size: i64,
     /// Number of sequences to return for each prompt text (default: 1)
     pub num_return_sequences: i64,
+    /// Token ids allowed at each generation step, restricting the vocabulary of the generated sequences (default: None)
+    pub allowed_token_ids: Option<Vec<i64>>,
+    /// Function returning the token ids allowed at the next step given the prompt index and the ids generated so far (default: None)
+    pub prefix_allowed_tokens_fn: Option<PrefixAllowedTokensFn>,
+    /// Criteria ending the generation of a sequence before the end of sequence token, e.g. `StopSequences` stopping on user-provided strings or token sequences (default: None)
+    pub stopping_criteria: Option<Arc<dyn StoppingCriteria>>,
+    /// Token sequences that must all appear in the generated sequences (e.g. the tokens of required terms), enforced by a constrained beam search (default: None)
+    pub force_words_ids: Option<Vec<Vec<i64>>>,
+    /// Token sequences that may not appear in the generated sequences (e.g. profanity or personal information markers): the last token of a sequence is banned whenever its previous tokens have just been generated (default: None)
+    pub bad_words_ids: Option<Vec<Vec<i64>>>,
+    /// Grammar the generated text must follow (e.g. `Grammar::from_json_schema` for structured outputs), enforced by masking the tokens that cannot extend the text into a valid prefix of the grammar (default: None)
+    pub grammar: Option<Arc<Grammar>>,
+    /// Bias added to the scores of the tokens present in the source (prompt) at each generation step, favouring copying from the input over hallucinated tokens (default: 0.0)
+    pub source_copy_bias: f64,
+    /// Size of the n-grams of the source (encoder input) that may not be copied in the generated sequences, for encoder-decoder models (default: 0)
+    pub encoder_no_repeat_ngram_size: i64,
+    /// Multiplier of the DRY ("don't repeat yourself") penalty applied to tokens extending a repetition of a previous sequence (default: 0.0, disabled)
+    pub dry_multiplier: f64,
+    /// Base of the DRY penalty, growing exponentially with the length of the repeated sequence (default: 1.75)
+    pub dry_base: f64,
+    /// Length of the repeated sequences not penalized by the DRY penalty (default: 2)
+    pub dry_allowed_length: i64,
+    /// Token ids interrupting the matching of repeated sequences for the DRY penalty, e.g. newlines or punctuation (default: None)
+    pub dry_sequence_breakers: Option<Vec<i64>>,
+    /// Remove the last token of the prompts and constrain the first generated token to start with it, for decoder-only models. Avoids starting the generation with a broken word when the prompt ends mid-token (default: false)
+    pub token_healing: bool,
+    /// Normalization (e.g. HTML entities decoding and white space collapsing) applied to the inputs before summarization (default: None)
+    pub text_normalizer: Option<TextNormalizer>,
+    /// Task prefix prepended to the inputs (default: `SummarizationPrefix::Default`, read from the model configuration)
+    pub prefix: SummarizationPrefix,
     /// Device to place the model on (default: CUDA/GPU when available)
     pub device: Device,
 }
@@ -143,6 +211,41 @@ impl SummarizationConfig {
             ..Default::default()
         }
     }
+
+    /// Instantiate a summarization configuration for dialogues and meeting transcripts, with generation defaults suited
+    /// to the short summaries of BART checkpoints fine-tuned on SAMSum (e.g. `philschmid/bart-large-cnn-samsum` once
+    /// converted with `utils/convert_model.py`). Inputs should be formatted with a `DialoguePreprocessor`, or
+    /// summarized with `SummarizationModel::summarize_dialogues`.
+    ///
+    /// # Arguments
+    ///
+    /// * model_resource - The `Resource` pointing to the model to load (e.g.  model.ot)
+    /// * config_resource - The `Resource' pointing to the model configuration to load (e.g. config.json)
+    /// * vocab_resource - The `Resource' pointing to the tokenizer's vocabulary to load (e.g.  vocab.json)
+    /// * merges_resource - The `Resource`  pointing to the tokenizer's merge file to load (e.g.  merges.txt).
+    pub fn dialogue(
+        model_resource: Resource,
+        config_resource: Resource,
+        vocab_resource: Resource,
+        merges_resource: Resource,
+    ) -> SummarizationConfig {
+        SummarizationConfig {
+            model_type: ModelType::Bart,
+            model_resource,
+            config_resource,
+            vocab_resource,
+            merges_resource,
+            min_length: 10,
+            max_length: 80,
+            max_new_tokens: None,
+            min_new_tokens: None,
+            num_beams: 4,
+            num_beam_groups: 1,
+            diversity_penalty: 0.0,
+            device: Device::cuda_if_available(),
+            ..Default::default()
+        }
+    }
 }
 
 impl Default for SummarizationConfig {
@@ -163,21 +266,70 @@ impl Default for SummarizationConfig {
             )),
             min_length: 56,
             max_length: 142,
+            max_new_tokens: None,
+            min_new_tokens: None,
             do_sample: false,
+            seed: None,
             early_stopping: true,
             num_beams: 3,
+            num_beam_groups: 1,
+            diversity_penalty: 0.0,
             temperature: 1.0,
             top_k: 50,
             top_p: 1.0,
+            typical_p: 1.0,
+            epsilon_cutoff: 0.0,
+            eta_cutoff: 0.0,
             repetition_penalty: 1.0,
             length_penalty: 1.0,
             no_repeat_ngram_size: 3,
             num_return_sequences: 1,
+            allowed_token_ids: None,
+            prefix_allowed_tokens_fn: None,
+            stopping_criteria: None,
+            force_words_ids: None,
+            bad_words_ids: None,
+            grammar: None,
+            source_copy_bias: 0.0,
+            encoder_no_repeat_ngram_size: 0,
+            dry_multiplier: 0.0,
+            dry_base: 1.75,
+            dry_allowed_length: 2,
+            dry_sequence_breakers: None,
+            token_healing: false,
+            text_normalizer: None,
+            prefix: SummarizationPrefix::Default,
             device: Device::cuda_if_available(),
         }
     }
 }
 
+impl SummarizationConfig {
+    // Resolves the prefix prepended to the inputs, reading the default from the model configuration
+    fn resolve_prefix(&self) -> Result<Option<String>, RustBertError> {
+        match &self.prefix {
+            SummarizationPrefix::None => Ok(None),
+            SummarizationPrefix::Custom(prefix) => Ok(Some(prefix.clone())),
+            SummarizationPrefix::Default => {
+                let task_registry = match self.model_type {
+                    ModelType::Bart => {
+                        BartConfig::from_file(self.config_resource.get_local_path()?)
+                            .task_registry()?
+                    }
+                    ModelType::T5 => T5Config::from_file(self.config_resource.get_local_path()?)
+                        .task_registry()?,
+                    _ => TaskRegistry::new(),
+                };
+                Ok(match task_registry.prefix("summarization") {
+                    Some(prefix) => Some(prefix.to_string()),
+                    None if self.model_type == ModelType::T5 => Some("summarize: ".to_string()),
+                    None => None,
+                })
+            }
+        }
+    }
+}
+
 impl From<SummarizationConfig> for GenerateConfig {
     fn from(config: SummarizationConfig) -> GenerateConfig {
         GenerateConfig {
@@ -187,27 +339,241 @@ impl From<SummarizationConfig> for GenerateConfig {
             vocab_resource: config.vocab_resource,
             min_length: config.min_length,
             max_length: config.max_length,
+            max_new_tokens: config.max_new_tokens,
+            min_new_tokens: config.min_new_tokens,
             do_sample: config.do_sample,
+            seed: config.seed,
             early_stopping: config.early_stopping,
             num_beams: config.num_beams,
+            num_beam_groups: config.num_beam_groups,
+            diversity_penalty: config.diversity_penalty,
             temperature: config.temperature,
             top_k: config.top_k,
             top_p: config.top_p,
+            typical_p: config.typical_p,
+            epsilon_cutoff: config.epsilon_cutoff,
+            eta_cutoff: config.eta_cutoff,
             repetition_penalty: config.repetition_penalty,
             length_penalty: config.length_penalty,
             no_repeat_ngram_size: config.no_repeat_ngram_size,
             num_return_sequences: config.num_return_sequences,
+            allowed_token_ids: config.allowed_token_ids,
+            prefix_allowed_tokens_fn: config.prefix_allowed_tokens_fn,
+            stopping_criteria: config.stopping_criteria,
+            force_words_ids: config.force_words_ids,
+            bad_words_ids: config.bad_words_ids,
+            grammar: config.grammar,
+            source_copy_bias: config.source_copy_bias,
+            encoder_no_repeat_ngram_size: config.encoder_no_repeat_ngram_size,
+            dry_multiplier: config.dry_multiplier,
+            dry_base: config.dry_base,
+            dry_allowed_length: config.dry_allowed_length,
+            dry_sequence_breakers: config.dry_sequence_breakers,
+            token_healing: config.token_healing,
+            echo_prompt: true,
+            bos_token_id: None,
+            eos_token_ids: None,
+            pad_token_id: None,
+            decoder_start_token_id: None,
+            cache_check_tolerance: None,
             device: config.device,
         }
     }
 }
 
+/// # Turn of a dialogue
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DialogueTurn {
+    /// Name of the speaker
+    pub speaker: String,
+    /// Text of the turn
+    pub utterance: String,
+}
+
+impl DialogueTurn {
+    pub fn new(speaker: &str, utterance: &str) -> DialogueTurn {
+        DialogueTurn {
+            speaker: speaker.to_string(),
+            utterance: utterance.to_string(),
+        }
+    }
+}
+
+/// # Dialogue preprocessor
+/// Formats dialogues and transcripts as the `Speaker: utterance` lines the SAMSum checkpoints are trained on.
+/// Transcripts are parsed line by line: a line starting with a speaker tag (`John: ...`, `[John] ...` or
+/// `<John> ...`) opens a new turn, other non-empty lines continue the previous turn.
+#[derive(Debug, Clone)]
+pub struct DialoguePreprocessor {
+    /// Merge consecutive turns of the same speaker into a single turn (default: true)
+    pub join_consecutive_turns: bool,
+    /// Normalize the speaker names: surrounding brackets and extra whitespace are removed and upper-case
+    /// names are capitalized (`JOHN SMITH` becomes `John Smith`) (default: true)
+    pub normalize_speakers: bool,
+    /// Maximum length (in characters) of a speaker tag. Longer prefixes before a colon are considered part of the
+    /// utterance (default: 40)
+    pub max_speaker_length: usize,
+    /// Separator inserted between turns (default: `\n`)
+    pub turn_separator: String,
+}
+
+impl Default for DialoguePreprocessor {
+    fn default() -> DialoguePreprocessor {
+        DialoguePreprocessor {
+            join_consecutive_turns: true,
+            normalize_speakers: true,
+            max_speaker_length: 40,
+            turn_separator: "\n".to_string(),
+        }
+    }
+}
+
+impl DialoguePreprocessor {
+    /// Parses a transcript into dialogue turns. Lines preceding the first speaker tag are attributed to an empty
+    /// speaker.
+    ///
+    /// # Arguments
+    ///
+    /// * `transcript` - transcript with one or more lines per turn
+    pub fn parse(&self, transcript: &str) -> Vec<DialogueTurn> {
+        let mut turns: Vec<DialogueTurn> = vec![];
+        for line in transcript
+            .lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty())
+        {
+            match self.split_speaker(line) {
+                Some((speaker, utterance)) => turns.push(DialogueTurn::new(speaker, utterance)),
+                None => match turns.last_mut() {
+                    Some(turn) => {
+                        if !turn.utterance.is_empty() {
+                            turn.utterance.push(' ');
+                        }
+                        turn.utterance.push_str(line);
+                    }
+                    None => turns.push(DialogueTurn::new("", line)),
+                },
+            }
+        }
+        turns
+    }
+
+    fn split_speaker<'a>(&self, line: &'a str) -> Option<(&'a str, &'a str)> {
+        let (open, close) = match line.chars().next() {
+            Some('[') => ('[', ']'),
+            Some('<') => ('<', '>'),
+            _ => {
+                let position = line.find(':')?;
+                let speaker = line[..position].trim();
+                // Excludes URLs and times (e.g. `https://...` or `10:30`)
+                let is_tag = !speaker.is_empty()
+                    && speaker.chars().count() <= self.max_speaker_length
+                    && !line[position + 1..].starts_with("//")
+                    && !speaker.chars().all(|c| c.is_ascii_digit());
+                return if is_tag {
+                    Some((speaker, line[position + 1..].trim()))
+                } else {
+                    None
+                };
+            }
+        };
+        let position = line.find(close)?;
+        let speaker = line[open.len_utf8()..position].trim();
+        if speaker.is_empty() || speaker.chars().count() > self.max_speaker_length {
+            return None;
+        }
+        let utterance = line[position + close.len_utf8()..].trim_start();
+        let utterance = utterance.strip_prefix(':').unwrap_or(utterance).trim();
+        Some((speaker, utterance))
+    }
+
+    /// Normalizes a speaker name: removes surrounding brackets, collapses whitespace and capitalizes upper-case names
+    pub fn normalize_speaker(&self, speaker: &str) -> String {
+        let speaker = speaker
+            .trim()
+            .trim_matches(|c| matches!(c, '[' | ']' | '<' | '>' | '(' | ')'))
+            .split_whitespace()
+            .collect::<Vec<&str>>()
+            .join(" ");
+        if speaker.chars().any(char::is_lowercase) {
+            return speaker;
+        }
+        speaker
+            .split(' ')
+            .map(|word| {
+                let mut chars = word.chars();
+                match chars.next() {
+                    Some(first) => first
+                        .to_uppercase()
+                        .chain(chars.flat_map(char::to_lowercase))
+                        .collect::<String>(),
+                    None => String::new(),
+                }
+            })
+            .collect::<Vec<String>>()
+            .join(" ")
+    }
+
+    /// Formats dialogue turns as a summarization input. Turns without utterance are dropped.
+    ///
+    /// # Arguments
+    ///
+    /// * `turns` - dialogue turns, in order
+    pub fn format(&self, turns: &[DialogueTurn]) -> String {
+        let mut formatted_turns: Vec<DialogueTurn> = Vec::with_capacity(turns.len());
+        for turn in turns {
+            let utterance = turn
+                .utterance
+                .split_whitespace()
+                .collect::<Vec<&str>>()
+                .join(" ");
+            if utterance.is_empty() {
+                continue;
+            }
+            let speaker = if self.normalize_speakers {
+                self.normalize_speaker(&turn.speaker)
+            } else {
+                turn.speaker.trim().to_string()
+            };
+            match formatted_turns.last_mut() {
+                Some(previous) if self.join_consecutive_turns && previous.speaker == speaker => {
+                    previous.utterance.push(' ');
+                    previous.utterance.push_str(&utterance);
+                }
+                _ => formatted_turns.push(DialogueTurn { speaker, utterance }),
+            }
+        }
+        formatted_turns
+            .iter()
+            .map(|turn| {
+                if turn.speaker.is_empty() {
+                    turn.utterance.clone()
+                } else {
+                    format!("{}: {}", turn.speaker, turn.utterance)
+                }
+            })
+            .collect::<Vec<String>>()
+            .join(&self.turn_separator)
+    }
+
+    /// Parses and formats a transcript as a summarization input
+    ///
+    /// # Arguments
+    ///
+    /// * `transcript` - transcript with one or more lines per turn
+    pub fn preprocess(&self, transcript: &str) -> String {
+        self.format(&self.parse(transcript))
+    }
+}
+
 /// # Abstraction that holds one particular summarization model, for any of the supported models
 pub enum SummarizationOption {
     /// Summarizer based on BART model
     Bart(BartGenerator),
     /// Summarizer based on T5 model
     T5(T5Generator),
+    /// Summarizer based on a model registered in a `CustomModelRegistry`
+    Custom(ModelType, Box<dyn CustomTextGenerator>),
 }
 
 impl SummarizationOption {
@@ -224,11 +590,44 @@ impl SummarizationOption {
         }
     }
 
+    /// Instantiate a new summarization model, sharing the weights of a model already loaded in the registry if available.
+    pub fn new_with_registry(
+        config: SummarizationConfig,
+        registry: &mut SharedModelRegistry,
+    ) -> Result<Self, RustBertError> {
+        match config.model_type {
+            ModelType::Bart => Ok(SummarizationOption::Bart(
+                registry.bart_generator(config.into())?,
+            )),
+            ModelType::T5 => Ok(SummarizationOption::T5(
+                registry.t5_generator(config.into())?,
+            )),
+            _ => Err(RustBertError::InvalidConfigurationError(format!(
+                "Summarization not implemented for {:?}!",
+                config.model_type
+            ))),
+        }
+    }
+
+    /// Instantiate a new summarization model from a text generator registered in a `CustomModelRegistry`
+    pub fn new_with_custom_model(
+        config: SummarizationConfig,
+        registry: &CustomModelRegistry,
+        name: &str,
+    ) -> Result<Self, RustBertError> {
+        let model_type = config.model_type;
+        Ok(SummarizationOption::Custom(
+            model_type,
+            registry.build_text_generator(name, config.into())?,
+        ))
+    }
+
     /// Returns the `ModelType` for this SummarizationOption
     pub fn model_type(&self) -> ModelType {
         match *self {
             Self::Bart(_) => ModelType::Bart,
             Self::T5(_) => ModelType::T5,
+            Self::Custom(model_type, _) => model_type,
         }
     }
 
@@ -244,6 +643,123 @@ impl SummarizationOption {
         match *self {
             Self::Bart(ref model) => model.generate(prompt_texts, attention_mask, None, None, None),
             Self::T5(ref model) => model.generate(prompt_texts, attention_mask, None, None, None),
+            Self::Custom(_, ref model) => model.generate(
+                prompt_texts.as_ref().map(|texts| texts.as_ref()),
+                attention_mask,
+            ),
+        }
+    }
+
+    /// Interface method to generate_with_options() of the particular models.
+    pub fn generate_with_options<'a, S>(
+        &self,
+        prompt_texts: Option<S>,
+        attention_mask: Option<Tensor>,
+        generate_options: &GenerateOptions,
+    ) -> Vec<String>
+    where
+        S: AsRef<[&'a str]>,
+    {
+        match *self {
+            Self::Bart(ref model) => {
+                model.generate_with_options(prompt_texts, attention_mask, generate_options)
+            }
+            Self::T5(ref model) => {
+                model.generate_with_options(prompt_texts, attention_mask, generate_options)
+            }
+            Self::Custom(_, ref model) => model.generate_with_options(
+                prompt_texts.as_ref().map(|texts| texts.as_ref()),
+                attention_mask,
+                generate_options,
+            ),
+        }
+    }
+
+    /// Interface method to generate_with_encoder_embeddings() of the particular models.
+    pub fn generate_with_encoder_embeddings<'a, S>(
+        &self,
+        prompt_texts: Option<S>,
+        attention_mask: Option<Tensor>,
+    ) -> Result<(Vec<String>, Tensor), RustBertError>
+    where
+        S: AsRef<[&'a str]>,
+    {
+        match *self {
+            Self::Bart(ref model) => model.generate_with_encoder_embeddings(
+                prompt_texts,
+                attention_mask,
+                None,
+                None,
+                None,
+            ),
+            Self::T5(ref model) => model.generate_with_encoder_embeddings(
+                prompt_texts,
+                attention_mask,
+                None,
+                None,
+                None,
+            ),
+            Self::Custom(_, ref model) => model.generate_with_encoder_embeddings(
+                prompt_texts.as_ref().map(|texts| texts.as_ref()),
+                attention_mask,
+            ),
+        }
+    }
+
+    /// Interface method to generate_with_source_copy_bias() of the particular models.
+    pub fn generate_with_source_copy_bias<'a, S>(
+        &self,
+        prompt_texts: Option<S>,
+        attention_mask: Option<Tensor>,
+        source_copy_bias: f64,
+    ) -> Vec<String>
+    where
+        S: AsRef<[&'a str]>,
+    {
+        match *self {
+            Self::Bart(ref model) => {
+                model.generate_with_source_copy_bias(prompt_texts, attention_mask, source_copy_bias)
+            }
+            Self::T5(ref model) => {
+                model.generate_with_source_copy_bias(prompt_texts, attention_mask, source_copy_bias)
+            }
+            Self::Custom(_, ref model) => model.generate_with_source_copy_bias(
+                prompt_texts.as_ref().map(|texts| texts.as_ref()),
+                attention_mask,
+                source_copy_bias,
+            ),
+        }
+    }
+
+    /// Interface method to generate_with_usage() of the particular models.
+    pub fn generate_with_usage<'a, S>(
+        &self,
+        prompt_texts: Option<S>,
+        attention_mask: Option<Tensor>,
+    ) -> (Vec<String>, GenerationUsage)
+    where
+        S: AsRef<[&'a str]>,
+    {
+        match *self {
+            Self::Bart(ref model) => {
+                model.generate_with_usage(prompt_texts, attention_mask, None, None, None)
+            }
+            Self::T5(ref model) => {
+                model.generate_with_usage(prompt_texts, attention_mask, None, None, None)
+            }
+            Self::Custom(_, ref model) => model.generate_with_usage(
+                prompt_texts.as_ref().map(|texts| texts.as_ref()),
+                attention_mask,
+            ),
+        }
+    }
+
+    /// Interface method to reload_weights() of the particular models.
+    pub fn reload_weights(&mut self, weights_resource: &Resource) -> Result<(), RustBertError> {
+        match self {
+            Self::Bart(model_ref) => model_ref.reload_weights(weights_resource),
+            Self::T5(model_ref) => model_ref.reload_weights(weights_resource),
+            Self::Custom(_, model_ref) => model_ref.reload_weights(weights_resource),
         }
     }
 }
@@ -252,6 +768,8 @@ impl SummarizationOption {
 pub struct SummarizationModel {
     model: SummarizationOption,
     prefix: Option<String>,
+    text_normalizer: Option<TextNormalizer>,
+    post_processors: PostProcessors,
 }
 
 impl SummarizationModel {
@@ -274,13 +792,68 @@ impl SummarizationModel {
     pub fn new(
         summarization_config: SummarizationConfig,
     ) -> Result<SummarizationModel, RustBertError> {
-        let prefix = match summarization_config.model_type {
-            ModelType::T5 => Some("summarize: ".to_string()),
+        let prefix = summarization_config.resolve_prefix()?;
+        let text_normalizer = summarization_config.text_normalizer.clone();
+        let model = SummarizationOption::new(summarization_config)?;
+
+        Ok(SummarizationModel {
+            model,
+            prefix,
+            text_normalizer,
+            post_processors: PostProcessors::new(),
+        })
+    }
+
+    /// Build a new `SummarizationModel`, re-using the weights and tokenizer of a model already loaded in the registry
+    /// if available (e.g. when the same T5 model is used for summarization and translation).
+    ///
+    /// # Arguments
+    ///
+    /// * `summarization_config` - `SummarizationConfig` object containing the resource references (model, vocabulary, configuration), summarization options and device placement (CPU/GPU)
+    /// * `registry` - `SharedModelRegistry` holding the models loaded so far
+    pub fn new_with_registry(
+        summarization_config: SummarizationConfig,
+        registry: &mut SharedModelRegistry,
+    ) -> Result<SummarizationModel, RustBertError> {
+        let prefix = summarization_config.resolve_prefix()?;
+        let text_normalizer = summarization_config.text_normalizer.clone();
+        let model = SummarizationOption::new_with_registry(summarization_config, registry)?;
+
+        Ok(SummarizationModel {
+            model,
+            prefix,
+            text_normalizer,
+            post_processors: PostProcessors::new(),
+        })
+    }
+
+    /// Build a new `SummarizationModel` from a text generator registered in a `CustomModelRegistry`. Only a
+    /// `SummarizationPrefix::Custom` prefix is added to the inputs of custom models.
+    ///
+    /// # Arguments
+    ///
+    /// * `summarization_config` - `SummarizationConfig` object containing the resource references (model, vocabulary, configuration), summarization options and device placement (CPU/GPU)
+    /// * `registry` - `CustomModelRegistry` holding the custom model constructors
+    /// * `name` - name the text generator was registered with
+    pub fn new_with_custom_model(
+        summarization_config: SummarizationConfig,
+        registry: &CustomModelRegistry,
+        name: &str,
+    ) -> Result<SummarizationModel, RustBertError> {
+        let prefix = match &summarization_config.prefix {
+            SummarizationPrefix::Custom(prefix) => Some(prefix.clone()),
             _ => None,
         };
-        let model = SummarizationOption::new(summarization_config)?;
+        let text_normalizer = summarization_config.text_normalizer.clone();
+        let model =
+            SummarizationOption::new_with_custom_model(summarization_config, registry, name)?;
 
-        Ok(SummarizationModel { model, prefix })
+        Ok(SummarizationModel {
+            model,
+            prefix,
+            text_normalizer,
+            post_processors: PostProcessors::new(),
+        })
     }
 
     /// Summarize texts provided
@@ -331,20 +904,268 @@ impl SummarizationModel {
     where
         S: AsRef<[&'a str]>,
     {
-        match &self.prefix {
-            None => self.model.generate(Some(texts), None),
-            Some(prefix) => {
-                let texts = texts
-                    .as_ref()
-                    .iter()
-                    .map(|text| format!("{}{}", prefix, text))
-                    .collect_vec();
-                self.model.generate(
-                    Some(texts.iter().map(|x| &**x).collect::<Vec<&str>>()),
-                    None,
-                )
-            }
-        }
+        let texts = self.prepare_inputs(texts.as_ref());
+        let summaries = self.model.generate(
+            Some(texts.iter().map(|x| &**x).collect::<Vec<&str>>()),
+            None,
+        );
+        self.post_processors.process_batch(summaries)
+    }
+
+    /// Summarize texts provided, overriding the generation configuration of the pipeline for this call with the
+    /// options provided (for example to produce shorter summaries for some of the requests)
+    ///
+    /// # Arguments
+    ///
+    /// * `input` - `&[&str]` Array of texts to summarize.
+    /// * `generate_options` - `&GenerateOptions` options overriding the configuration of the pipeline
+    ///
+    /// # Returns
+    /// * `Vec<String>` Summarized texts
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// # fn main() -> anyhow::Result<()> {
+    /// use rust_bert::pipelines::generation_utils::GenerateOptions;
+    /// use rust_bert::pipelines::summarization::SummarizationModel;
+    /// let model = SummarizationModel::new(Default::default())?;
+    ///
+    /// let input = ["In findings published Tuesday in Cornell University's arXiv by a team of scientists
+    /// from the University of Montreal, the presence of water vapour was confirmed in the atmosphere of K2-18b,
+    /// a planet circling a star in the constellation Leo."];
+    ///
+    /// let generate_options = GenerateOptions {
+    ///     min_length: Some(10),
+    ///     max_length: Some(40),
+    ///     num_beams: Some(2),
+    ///     ..Default::default()
+    /// };
+    /// let output = model.summarize_with_options(&input, &generate_options);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn summarize_with_options<'a, S>(
+        &self,
+        texts: S,
+        generate_options: &GenerateOptions,
+    ) -> Vec<String>
+    where
+        S: AsRef<[&'a str]>,
+    {
+        let texts = self.prepare_inputs(texts.as_ref());
+        let summaries = self.model.generate_with_options(
+            Some(texts.iter().map(|x| &**x).collect::<Vec<&str>>()),
+            None,
+            generate_options,
+        );
+        self.post_processors.process_batch(summaries)
+    }
+
+    /// Summarize texts provided, and returns the encoder embeddings of each input (mean of the encoder hidden states
+    /// over the input tokens) computed during the summarization. The embeddings can be used for example for the
+    /// retrieval or de-duplication of the inputs without an additional encoder pass.
+    ///
+    /// # Arguments
+    ///
+    /// * `input` - `&[&str]` Array of texts to summarize.
+    ///
+    /// # Returns
+    /// * `Vec<String>` Summarized texts
+    /// * `Tensor` of shape (*number of texts*, *hidden_size*) with the pooled encoder embeddings of each input
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// # fn main() -> anyhow::Result<()> {
+    /// use rust_bert::pipelines::summarization::SummarizationModel;
+    /// let model = SummarizationModel::new(Default::default())?;
+    ///
+    /// let input = ["In findings published Tuesday in Cornell University's arXiv by a team of scientists
+    /// from the University of Montreal and a separate report published Wednesday in Nature Astronomy by a team
+    /// from University College London (UCL), the presence of water vapour was confirmed in the atmosphere of K2-18b,
+    /// a planet circling a star in the constellation Leo."];
+    ///
+    /// let (summaries, encoder_embeddings) = model.summarize_with_encoder_embeddings(&input)?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn summarize_with_encoder_embeddings<'a, S>(
+        &self,
+        texts: S,
+    ) -> Result<(Vec<String>, Tensor), RustBertError>
+    where
+        S: AsRef<[&'a str]>,
+    {
+        let texts = self.prepare_inputs(texts.as_ref());
+        let (summaries, encoder_embeddings) = self.model.generate_with_encoder_embeddings(
+            Some(texts.iter().map(|x| &**x).collect::<Vec<&str>>()),
+            None,
+        )?;
+        Ok((
+            self.post_processors.process_batch(summaries),
+            encoder_embeddings,
+        ))
+    }
+
+    /// Summarize texts provided, and returns the usage statistics of the request (number of input and generated
+    /// tokens, generation time and throughput)
+    ///
+    /// # Arguments
+    ///
+    /// * `input` - `&[&str]` Array of texts to summarize.
+    ///
+    /// # Returns
+    /// * `Vec<String>` Summarized texts
+    /// * `GenerationUsage` usage statistics of the request
+    pub fn summarize_with_usage<'a, S>(&self, texts: S) -> (Vec<String>, GenerationUsage)
+    where
+        S: AsRef<[&'a str]>,
+    {
+        let texts = self.prepare_inputs(texts.as_ref());
+        let (summaries, usage) = self.model.generate_with_usage(
+            Some(texts.iter().map(|x| &**x).collect::<Vec<&str>>()),
+            None,
+        );
+        (self.post_processors.process_batch(summaries), usage)
+    }
+
+    /// Summarize texts provided, favouring the tokens of each input text in the summary (extractive bias). Positive
+    /// values of `source_copy_bias` reduce the generation of words absent from the source document, such as
+    /// hallucinated entities, at the cost of a more extractive summary. The bias overrides the `source_copy_bias`
+    /// of the `SummarizationConfig` for this call.
+    ///
+    /// # Arguments
+    ///
+    /// * `input` - `&[&str]` Array of texts to summarize.
+    /// * `source_copy_bias` - `f64` bias added to the scores of the source tokens at each generation step (0.0 disables the bias)
+    ///
+    /// # Returns
+    /// * `Vec<String>` Summarized texts
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// # fn main() -> anyhow::Result<()> {
+    /// use rust_bert::pipelines::summarization::SummarizationModel;
+    /// let model = SummarizationModel::new(Default::default())?;
+    ///
+    /// let input = ["In findings published Tuesday in Cornell University's arXiv by a team of scientists
+    /// from the University of Montreal, the presence of water vapour was confirmed in the atmosphere of K2-18b,
+    /// a planet circling a star in the constellation Leo."];
+    ///
+    /// let output = model.summarize_with_source_copy_bias(&input, 2.0);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn summarize_with_source_copy_bias<'a, S>(
+        &self,
+        texts: S,
+        source_copy_bias: f64,
+    ) -> Vec<String>
+    where
+        S: AsRef<[&'a str]>,
+    {
+        let texts = self.prepare_inputs(texts.as_ref());
+        let summaries = self.model.generate_with_source_copy_bias(
+            Some(texts.iter().map(|x| &**x).collect::<Vec<&str>>()),
+            None,
+            source_copy_bias,
+        );
+        self.post_processors.process_batch(summaries)
+    }
+
+    /// Summarize dialogues or meeting transcripts. The transcripts are parsed and formatted by the preprocessor
+    /// before summarization, and are best summarized by a model loaded with `SummarizationConfig::dialogue`.
+    ///
+    /// # Arguments
+    ///
+    /// * `transcripts` - `&[&str]` Array of transcripts to summarize, with one or more lines per turn.
+    /// * `preprocessor` - `DialoguePreprocessor` parsing the speaker tags and formatting the turns
+    ///
+    /// # Returns
+    /// * `Vec<String>` Summarized dialogues
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// # fn main() -> anyhow::Result<()> {
+    /// use rust_bert::pipelines::summarization::{
+    ///     DialoguePreprocessor, SummarizationConfig, SummarizationModel,
+    /// };
+    /// use rust_bert::resources::{LocalResource, Resource};
+    /// use std::path::PathBuf;
+    ///
+    /// let resource = |path: &str| {
+    ///     Resource::Local(LocalResource {
+    ///         local_path: PathBuf::from(path),
+    ///     })
+    /// };
+    /// let config = SummarizationConfig::dialogue(
+    ///     resource("path/to/bart-samsum/rust_model.ot"),
+    ///     resource("path/to/bart-samsum/config.json"),
+    ///     resource("path/to/bart-samsum/vocab.json"),
+    ///     resource("path/to/bart-samsum/merges.txt"),
+    /// );
+    /// let model = SummarizationModel::new(config)?;
+    ///
+    /// let transcript = "AMANDA: I baked cookies. Do you want some?
+    /// JERRY: Sure!
+    /// AMANDA: I'll bring you tomorrow :-)";
+    /// let output = model.summarize_dialogues(&[transcript], &DialoguePreprocessor::default());
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn summarize_dialogues<'a, S>(
+        &self,
+        transcripts: S,
+        preprocessor: &DialoguePreprocessor,
+    ) -> Vec<String>
+    where
+        S: AsRef<[&'a str]>,
+    {
+        let dialogues = transcripts
+            .as_ref()
+            .iter()
+            .map(|transcript| preprocessor.preprocess(transcript))
+            .collect_vec();
+        self.summarize(dialogues.iter().map(|x| &**x).collect::<Vec<&str>>())
+    }
+
+    /// Registers a post-processor applied to the generated summaries, after the post-processors registered so far
+    ///
+    /// # Arguments
+    ///
+    /// * `post_processor` - `PostProcessor` to apply (e.g. a `RegexReplacer` or a closure)
+    pub fn add_post_processor<P: PostProcessor + 'static>(&mut self, post_processor: P) {
+        self.post_processors.add(post_processor);
+    }
+
+    // Normalizes the inputs (if a text normalizer is configured) and prepends the task prefix
+    fn prepare_inputs(&self, texts: &[&str]) -> Vec<String> {
+        texts
+            .iter()
+            .map(|text| {
+                let text = match &self.text_normalizer {
+                    Some(text_normalizer) => text_normalizer.normalize(text),
+                    None => text.to_string(),
+                };
+                match &self.prefix {
+                    Some(prefix) => format!("{}{}", prefix, text),
+                    None => text,
+                }
+            })
+            .collect_vec()
+    }
+
+    /// Reloads the weights of the summarization model from the resource provided, keeping the tokenizer and configuration
+    /// unchanged. The weights must match the architecture of the model currently loaded.
+    ///
+    /// # Arguments
+    ///
+    /// * `weights_resource` - `Resource` pointing to the new model weights (e.g. model.ot)
+    pub fn reload_weights(&mut self, weights_resource: &Resource) -> Result<(), RustBertError> {
+        self.model.reload_weights(weights_resource)
     }
 }
 
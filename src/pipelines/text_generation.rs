@@ -25,11 +25,16 @@ use crate::gpt2::{
 use crate::pipelines::common::{ModelType, TokenizerOption};
 use crate::pipelines::generation_utils::private_generation_utils::PrivateLanguageGenerator;
 use crate::pipelines::generation_utils::{
-    GPT2Generator, GenerateConfig, LanguageGenerator, OpenAIGenerator, ReformerGenerator,
-    XLNetGenerator,
+    GPT2Generator, GenerateConfig, GenerateOptions, GenerationUsage, LanguageGenerator,
+    OpenAIGenerator, PrefixAllowedTokensFn, ReformerGenerator, ScoredGeneration, StoppingCriteria,
+    StreamedToken, XLNetGenerator,
 };
+use crate::pipelines::grammar::Grammar;
+use crate::pipelines::post_processing::{PostProcessor, PostProcessors};
+use crate::pipelines::prompt_template::PromptTemplate;
 use crate::resources::Resource;
 use itertools::Itertools;
+use std::sync::Arc;
 use tch::{Device, Tensor};
 
 /// # Configuration for text generation
@@ -50,18 +55,34 @@ pub struct TextGenerationConfig {
     pub min_length: i64,
     /// Maximum sequence length (default: 20)
     pub max_length: i64,
+    /// Maximum number of generated tokens, excluding the prompt. Takes precedence over `max_length` when set (default: None)
+    pub max_new_tokens: Option<i64>,
+    /// Minimum number of generated tokens, excluding the prompt. Takes precedence over `min_length` when set (default: None)
+    pub min_new_tokens: Option<i64>,
     /// Sampling flag. If true, will perform top-k and/or nucleus sampling on generated tokens, otherwise greedy (deterministic) decoding (default: true)
     pub do_sample: bool,
+    /// Seed of the sampling. When set, the sampled tokens are drawn from a generator seeded at each call instead of the `tch` random state, so that sampled outputs are reproducible across runs and threads for the same inputs (default: None)
+    pub seed: Option<u64>,
     /// Early stopping flag indicating if the beam search should stop as soon as `num_beam` hypotheses have been generated (default: false)
     pub early_stopping: bool,
     /// Number of beams for beam search (default: 5)
     pub num_beams: i64,
+    /// Number of groups of beams for [diverse beam search, Vijayakumar et al.](https://arxiv.org/abs/1610.02424). `num_beams` must be a multiple of the number of groups (default: 1)
+    pub num_beam_groups: i64,
+    /// Penalty subtracted from the scores of the tokens selected by the previous groups of beams at the same step, for diverse beam search (default: 0.0)
+    pub diversity_penalty: f64,
     /// Temperature setting. Values higher than 1 will improve originality at the risk of reducing relevance (default: 1.0)
     pub temperature: f64,
     /// Top_k values for sampling tokens. Value higher than 0 will enable the feature (default: 0)
     pub top_k: i64,
     /// Top_p value for [Nucleus sampling, Holtzman et al.](http://arxiv.org/abs/1904.09751). Keep top tokens until cumulative probability reaches top_p (default: 0.9)
     pub top_p: f64,
+    /// Probability mass of [typical decoding, Meister et al.](https://arxiv.org/abs/2202.00666): only the tokens with an information content closest to the entropy of the distribution are sampled, up to this cumulative probability. Values lower than 1 enable the feature (default: 1.0)
+    pub typical_p: f64,
+    /// Cutoff of [epsilon sampling, Hewitt et al.](https://arxiv.org/abs/2210.15191): tokens with a probability lower than the cutoff are not sampled. Values higher than 0 enable the feature, typically between 3e-4 and 9e-4 (default: 0.0)
+    pub epsilon_cutoff: f64,
+    /// Cutoff of [eta sampling, Hewitt et al.](https://arxiv.org/abs/2210.15191): tokens with a probability lower than `min(eta_cutoff, sqrt(eta_cutoff) * exp(-entropy))` are not sampled. Values higher than 0 enable the feature, typically between 3e-4 and 2e-3 (default: 0.0)
+    pub eta_cutoff: f64,
     /// Repetition penalty (mostly useful for CTRL decoders). Values higher than 1 will penalize tokens that have been already generated. (default: 1.0)
     pub repetition_penalty: f64,
     /// Exponential penalty based on the length of the hypotheses generated (default: 1.0)
@@ -70,6 +91,34 @@ pub struct TextGenerationConfig {
     pub no_repeat_ngram_size: i64,
     /// Number of sequences to return for each prompt text (default: 1)
     pub num_return_sequences: i64,
+    /// Token ids allowed at each generation step, restricting the vocabulary of the generated sequences (default: None)
+    pub allowed_token_ids: Option<Vec<i64>>,
+    /// Function returning the token ids allowed at the next step given the prompt index and the ids generated so far (default: None)
+    pub prefix_allowed_tokens_fn: Option<PrefixAllowedTokensFn>,
+    /// Criteria ending the generation of a sequence before the end of sequence token, e.g. `StopSequences` stopping on user-provided strings or token sequences (default: None)
+    pub stopping_criteria: Option<Arc<dyn StoppingCriteria>>,
+    /// Token sequences that must all appear in the generated sequences (e.g. the tokens of required terms), enforced by a constrained beam search (default: None)
+    pub force_words_ids: Option<Vec<Vec<i64>>>,
+    /// Token sequences that may not appear in the generated sequences (e.g. profanity or personal information markers): the last token of a sequence is banned whenever its previous tokens have just been generated (default: None)
+    pub bad_words_ids: Option<Vec<Vec<i64>>>,
+    /// Grammar the generated text must follow (e.g. `Grammar::from_json_schema` for structured outputs), enforced by masking the tokens that cannot extend the text into a valid prefix of the grammar (default: None)
+    pub grammar: Option<Arc<Grammar>>,
+    /// Bias added to the scores of the tokens present in the source (prompt) at each generation step, favouring copying from the input over hallucinated tokens (default: 0.0)
+    pub source_copy_bias: f64,
+    /// Size of the n-grams of the source (encoder input) that may not be copied in the generated sequences, for encoder-decoder models (default: 0)
+    pub encoder_no_repeat_ngram_size: i64,
+    /// Multiplier of the DRY ("don't repeat yourself") penalty applied to tokens extending a repetition of a previous sequence (default: 0.0, disabled)
+    pub dry_multiplier: f64,
+    /// Base of the DRY penalty, growing exponentially with the length of the repeated sequence (default: 1.75)
+    pub dry_base: f64,
+    /// Length of the repeated sequences not penalized by the DRY penalty (default: 2)
+    pub dry_allowed_length: i64,
+    /// Token ids interrupting the matching of repeated sequences for the DRY penalty, e.g. newlines or punctuation (default: None)
+    pub dry_sequence_breakers: Option<Vec<i64>>,
+    /// Remove the last token of the prompts and constrain the first generated token to start with it, for decoder-only models. Avoids starting the generation with a broken word when the prompt ends mid-token (default: false)
+    pub token_healing: bool,
+    /// Include the prompt in the generated sequences of decoder-only models (default: true)
+    pub echo_prompt: bool,
     /// Device to place the model on (default: CUDA/GPU when available)
     pub device: Device,
 }
@@ -121,16 +170,38 @@ impl Default for TextGenerationConfig {
             )),
             min_length: 0,
             max_length: 20,
+            max_new_tokens: None,
+            min_new_tokens: None,
             do_sample: true,
+            seed: None,
             early_stopping: false,
             num_beams: 5,
+            num_beam_groups: 1,
+            diversity_penalty: 0.0,
             temperature: 1.0,
             top_k: 0,
             top_p: 0.9,
+            typical_p: 1.0,
+            epsilon_cutoff: 0.0,
+            eta_cutoff: 0.0,
             repetition_penalty: 1.0,
             length_penalty: 1.0,
             no_repeat_ngram_size: 3,
             num_return_sequences: 1,
+            allowed_token_ids: None,
+            prefix_allowed_tokens_fn: None,
+            stopping_criteria: None,
+            force_words_ids: None,
+            bad_words_ids: None,
+            grammar: None,
+            source_copy_bias: 0.0,
+            encoder_no_repeat_ngram_size: 0,
+            dry_multiplier: 0.0,
+            dry_base: 1.75,
+            dry_allowed_length: 2,
+            dry_sequence_breakers: None,
+            token_healing: false,
+            echo_prompt: true,
             device: Device::cuda_if_available(),
         }
     }
@@ -145,16 +216,43 @@ impl From<TextGenerationConfig> for GenerateConfig {
             vocab_resource: config.vocab_resource,
             min_length: config.min_length,
             max_length: config.max_length,
+            max_new_tokens: config.max_new_tokens,
+            min_new_tokens: config.min_new_tokens,
             do_sample: config.do_sample,
+            seed: config.seed,
             early_stopping: config.early_stopping,
             num_beams: config.num_beams,
+            num_beam_groups: config.num_beam_groups,
+            diversity_penalty: config.diversity_penalty,
             temperature: config.temperature,
             top_k: config.top_k,
             top_p: config.top_p,
+            typical_p: config.typical_p,
+            epsilon_cutoff: config.epsilon_cutoff,
+            eta_cutoff: config.eta_cutoff,
             repetition_penalty: config.repetition_penalty,
             length_penalty: config.length_penalty,
             no_repeat_ngram_size: config.no_repeat_ngram_size,
             num_return_sequences: config.num_return_sequences,
+            allowed_token_ids: config.allowed_token_ids,
+            prefix_allowed_tokens_fn: config.prefix_allowed_tokens_fn,
+            stopping_criteria: config.stopping_criteria,
+            force_words_ids: config.force_words_ids,
+            bad_words_ids: config.bad_words_ids,
+            grammar: config.grammar,
+            source_copy_bias: config.source_copy_bias,
+            encoder_no_repeat_ngram_size: config.encoder_no_repeat_ngram_size,
+            dry_multiplier: config.dry_multiplier,
+            dry_base: config.dry_base,
+            dry_allowed_length: config.dry_allowed_length,
+            dry_sequence_breakers: config.dry_sequence_breakers,
+            token_healing: config.token_healing,
+            echo_prompt: config.echo_prompt,
+            bos_token_id: None,
+            eos_token_ids: None,
+            pad_token_id: None,
+            decoder_start_token_id: None,
+            cache_check_tolerance: None,
             device: config.device,
         }
     }
@@ -240,6 +338,189 @@ impl TextGenerationOption {
             }
         }
     }
+
+    /// Interface method to generate_indices_with_options() of the particular models.
+    pub fn generate_indices_with_options<'a, S>(
+        &self,
+        prompt_texts: Option<S>,
+        attention_mask: Option<Tensor>,
+        generate_options: &GenerateOptions,
+    ) -> Vec<Vec<i64>>
+    where
+        S: AsRef<[&'a str]>,
+    {
+        match *self {
+            Self::GPT2(ref model) => {
+                model.generate_indices_with_options(prompt_texts, attention_mask, generate_options)
+            }
+            Self::GPT(ref model) => {
+                model.generate_indices_with_options(prompt_texts, attention_mask, generate_options)
+            }
+            Self::XLNet(ref model) => {
+                model.generate_indices_with_options(prompt_texts, attention_mask, generate_options)
+            }
+            Self::Reformer(ref model) => {
+                model.generate_indices_with_options(prompt_texts, attention_mask, generate_options)
+            }
+        }
+    }
+
+    /// Interface method to generate_indices_with_scores() of the particular models.
+    pub fn generate_indices_with_scores<'a, S>(
+        &self,
+        prompt_texts: Option<S>,
+        attention_mask: Option<Tensor>,
+        min_length: Option<i64>,
+        max_length: Option<i64>,
+    ) -> (Vec<Vec<i64>>, Vec<Vec<f64>>)
+    where
+        S: AsRef<[&'a str]>,
+    {
+        match *self {
+            Self::GPT2(ref model) => model.generate_indices_with_scores(
+                prompt_texts,
+                attention_mask,
+                min_length,
+                max_length,
+                None,
+            ),
+            Self::GPT(ref model) => model.generate_indices_with_scores(
+                prompt_texts,
+                attention_mask,
+                min_length,
+                max_length,
+                None,
+            ),
+            Self::XLNet(ref model) => model.generate_indices_with_scores(
+                prompt_texts,
+                attention_mask,
+                min_length,
+                max_length,
+                None,
+            ),
+            Self::Reformer(ref model) => model.generate_indices_with_scores(
+                prompt_texts,
+                attention_mask,
+                min_length,
+                max_length,
+                None,
+            ),
+        }
+    }
+
+    /// Interface method to generate_indices_with_usage() of the particular models.
+    pub fn generate_indices_with_usage<'a, S>(
+        &self,
+        prompt_texts: Option<S>,
+        attention_mask: Option<Tensor>,
+        min_length: Option<i64>,
+        max_length: Option<i64>,
+    ) -> (Vec<Vec<i64>>, GenerationUsage)
+    where
+        S: AsRef<[&'a str]>,
+    {
+        match *self {
+            Self::GPT2(ref model) => model.generate_indices_with_usage(
+                prompt_texts,
+                attention_mask,
+                min_length,
+                max_length,
+                None,
+            ),
+            Self::GPT(ref model) => model.generate_indices_with_usage(
+                prompt_texts,
+                attention_mask,
+                min_length,
+                max_length,
+                None,
+            ),
+            Self::XLNet(ref model) => model.generate_indices_with_usage(
+                prompt_texts,
+                attention_mask,
+                min_length,
+                max_length,
+                None,
+            ),
+            Self::Reformer(ref model) => model.generate_indices_with_usage(
+                prompt_texts,
+                attention_mask,
+                min_length,
+                max_length,
+                None,
+            ),
+        }
+    }
+
+    /// Interface method to generate_from_ids_with_callback() of the particular models.
+    pub fn generate_indices_with_callback<'a, S, F>(
+        &self,
+        prompt_texts: Option<S>,
+        attention_mask: Option<Tensor>,
+        min_length: Option<i64>,
+        max_length: Option<i64>,
+        callback: F,
+    ) -> Result<(Vec<Vec<i64>>, GenerationUsage), RustBertError>
+    where
+        S: AsRef<[&'a str]>,
+        F: FnMut(StreamedToken),
+    {
+        match *self {
+            Self::GPT2(ref model) => {
+                let input_ids = model.prepare_prompt_ids(prompt_texts, max_length);
+                model.generate_from_ids_with_callback(
+                    input_ids,
+                    attention_mask,
+                    min_length,
+                    max_length,
+                    None,
+                    callback,
+                )
+            }
+            Self::GPT(ref model) => {
+                let input_ids = model.prepare_prompt_ids(prompt_texts, max_length);
+                model.generate_from_ids_with_callback(
+                    input_ids,
+                    attention_mask,
+                    min_length,
+                    max_length,
+                    None,
+                    callback,
+                )
+            }
+            Self::XLNet(ref model) => {
+                let input_ids = model.prepare_prompt_ids(prompt_texts, max_length);
+                model.generate_from_ids_with_callback(
+                    input_ids,
+                    attention_mask,
+                    min_length,
+                    max_length,
+                    None,
+                    callback,
+                )
+            }
+            Self::Reformer(ref model) => {
+                let input_ids = model.prepare_prompt_ids(prompt_texts, max_length);
+                model.generate_from_ids_with_callback(
+                    input_ids,
+                    attention_mask,
+                    min_length,
+                    max_length,
+                    None,
+                    callback,
+                )
+            }
+        }
+    }
+
+    /// Interface method to reload_weights() of the particular models.
+    pub fn reload_weights(&mut self, weights_resource: &Resource) -> Result<(), RustBertError> {
+        match self {
+            Self::GPT2(model_ref) => model_ref.reload_weights(weights_resource),
+            Self::GPT(model_ref) => model_ref.reload_weights(weights_resource),
+            Self::XLNet(model_ref) => model_ref.reload_weights(weights_resource),
+            Self::Reformer(model_ref) => model_ref.reload_weights(weights_resource),
+        }
+    }
 }
 
 /// # TextGenerationModel to generate texts from a prompt
@@ -249,6 +530,11 @@ pub struct TextGenerationModel {
     prefix_length: Option<i64>,
     min_length: i64,
     max_length: i64,
+    min_new_tokens: Option<i64>,
+    max_new_tokens: Option<i64>,
+    echo_prompt: bool,
+    length_penalty: f64,
+    post_processors: PostProcessors,
 }
 
 impl TextGenerationModel {
@@ -292,6 +578,10 @@ with people, even a bishop, begging for his blessing. <eod> </s> <eos>"
 
         let min_length = generation_config.min_length;
         let max_length = generation_config.max_length;
+        let min_new_tokens = generation_config.min_new_tokens;
+        let max_new_tokens = generation_config.max_new_tokens;
+        let echo_prompt = generation_config.echo_prompt;
+        let length_penalty = generation_config.length_penalty;
         let model = TextGenerationOption::new(generation_config)?;
         let prefix_length = if let Some(prefix) = &prefix {
             Some(model.get_tokenizer().tokenize(prefix).len() as i64)
@@ -305,6 +595,11 @@ with people, even a bishop, begging for his blessing. <eod> </s> <eos>"
             prefix_length,
             min_length,
             max_length,
+            min_new_tokens,
+            max_new_tokens,
+            echo_prompt,
+            length_penalty,
+            post_processors: PostProcessors::new(),
         })
     }
 
@@ -338,7 +633,233 @@ with people, even a bishop, begging for his blessing. <eod> </s> <eos>"
     where
         S: AsRef<[&'a str]>,
     {
-        let (prefix, prefix_length) = match (prefix.into(), &self.prefix) {
+        self.generate_with_usage(texts, prefix).0
+    }
+
+    /// Generate texts from provided prompts, overriding the generation configuration of the pipeline for this call
+    /// with the options provided. The lengths set in the options exclude the prefix, as for the `TextGenerationConfig`.
+    ///
+    /// # Arguments
+    ///
+    /// * `input` - `&[&str]` Array of prompts.
+    /// * `prefix` - `impl Into<Option<&'a str>>`: Optional string to pass as a prefix for generation. Will be excluded from generated sequences.
+    /// * `generate_options` - `&GenerateOptions` options overriding the configuration of the pipeline
+    ///
+    /// # Returns
+    /// * `Vec<String>` Generated texts
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// # fn main() -> anyhow::Result<()> {
+    /// use rust_bert::pipelines::generation_utils::GenerateOptions;
+    /// use rust_bert::pipelines::text_generation::TextGenerationModel;
+    ///
+    /// let model = TextGenerationModel::new(Default::default())?;
+    ///
+    /// let generate_options = GenerateOptions {
+    ///     max_length: Some(32),
+    ///     temperature: Some(0.8),
+    ///     ..Default::default()
+    /// };
+    /// let output = model.generate_with_options(&["The dog", "The cat was"], None, &generate_options);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn generate_with_options<'a, S>(
+        &self,
+        texts: S,
+        prefix: impl Into<Option<&'a str>>,
+        generate_options: &GenerateOptions,
+    ) -> Vec<String>
+    where
+        S: AsRef<[&'a str]>,
+    {
+        let (prompts, prefix_length) = self.prepare_prompts(texts.as_ref(), prefix.into());
+        let (min_length, max_length) = self.length_bounds(prefix_length, generate_options);
+        let generate_options = GenerateOptions {
+            min_length: min_length.or(generate_options.min_length),
+            max_length: max_length.or(generate_options.max_length),
+            ..generate_options.clone()
+        };
+        let generated_indices = self.model.generate_indices_with_options(
+            Some(prompts.iter().map(String::as_str).collect::<Vec<&str>>()),
+            None,
+            &generate_options,
+        );
+        self.post_processors
+            .process_batch(self.decode_outputs(generated_indices, prefix_length))
+    }
+
+    /// Generate texts from provided prompts, and returns the usage statistics of the request (number of prompt and
+    /// generated tokens, generation time and throughput). The prompt tokens include the prefix.
+    ///
+    /// # Arguments
+    ///
+    /// * `input` - `&[&str]` Array of texts to summarize.
+    /// * `prefix` - `impl Into<Option<&'a str>>`: Optional string to pass as a prefix for generation. Will be excluded from generated sequences.
+    ///
+    /// # Returns
+    /// * `Vec<String>` Generated texts
+    /// * `GenerationUsage` usage statistics of the request
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// # fn main() -> anyhow::Result<()> {
+    /// use rust_bert::pipelines::text_generation::{TextGenerationConfig, TextGenerationModel};
+    ///
+    /// let config = TextGenerationConfig {
+    ///     echo_prompt: false,
+    ///     ..Default::default()
+    /// };
+    /// let model = TextGenerationModel::new(config)?;
+    ///
+    /// let input = ["The dog", "The cat was"];
+    /// let (output, usage) = model.generate_with_usage(&input, None);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn generate_with_usage<'a, S>(
+        &self,
+        texts: S,
+        prefix: impl Into<Option<&'a str>>,
+    ) -> (Vec<String>, GenerationUsage)
+    where
+        S: AsRef<[&'a str]>,
+    {
+        let (prompts, prefix_length) = self.prepare_prompts(texts.as_ref(), prefix.into());
+        let (min_length, max_length) =
+            self.length_bounds(prefix_length, &GenerateOptions::default());
+        let (generated_indices, usage) = self.model.generate_indices_with_usage(
+            Some(prompts.iter().map(String::as_str).collect::<Vec<&str>>()),
+            None,
+            min_length,
+            max_length,
+        );
+        (
+            self.post_processors
+                .process_batch(self.decode_outputs(generated_indices, prefix_length)),
+            usage,
+        )
+    }
+
+    /// Generate texts from provided prompts, and returns the log-probability of each generated token with the sequence
+    /// scores (see `LanguageGenerator::generate_with_scores`). The scores do not include the prompt and prefix.
+    ///
+    /// # Arguments
+    ///
+    /// * `input` - `&[&str]` Array of texts to summarize.
+    /// * `prefix` - `impl Into<Option<&'a str>>`: Optional string to pass as a prefix for generation. Will be excluded from generated sequences.
+    ///
+    /// # Returns
+    /// * `Vec<ScoredGeneration>` Generated texts with their scores
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// # fn main() -> anyhow::Result<()> {
+    /// use rust_bert::pipelines::text_generation::TextGenerationModel;
+    ///
+    /// let model = TextGenerationModel::new(Default::default())?;
+    ///
+    /// let output = model.generate_with_scores(&["The dog", "The cat was"], None);
+    /// for generation in output {
+    ///     println!("{} ({:.2})", generation.text, generation.normalized_score);
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn generate_with_scores<'a, S>(
+        &self,
+        texts: S,
+        prefix: impl Into<Option<&'a str>>,
+    ) -> Vec<ScoredGeneration>
+    where
+        S: AsRef<[&'a str]>,
+    {
+        let (prompts, prefix_length) = self.prepare_prompts(texts.as_ref(), prefix.into());
+        let (min_length, max_length) =
+            self.length_bounds(prefix_length, &GenerateOptions::default());
+        let (generated_indices, token_scores) = self.model.generate_indices_with_scores(
+            Some(prompts.iter().map(String::as_str).collect::<Vec<&str>>()),
+            None,
+            min_length,
+            max_length,
+        );
+        self.post_processors
+            .process_batch(self.decode_outputs(generated_indices, prefix_length))
+            .into_iter()
+            .zip(token_scores)
+            .map(|(text, token_scores)| {
+                ScoredGeneration::from_token_scores(text, token_scores, self.length_penalty)
+            })
+            .collect()
+    }
+
+    /// Generate texts from provided prompts, calling `callback` with each generated token as soon as it is sampled
+    /// (e.g. to display the output of a chat or command line application as it is produced). The streamed tokens do
+    /// not include the prompt and prefix, and the post-processors only apply to the returned texts. Streaming is not
+    /// available with beam search (`num_beams` must be 1).
+    ///
+    /// # Arguments
+    ///
+    /// * `input` - `&[&str]` Array of prompts.
+    /// * `prefix` - `impl Into<Option<&'a str>>`: Optional string to pass as a prefix for generation. Will be excluded from generated sequences.
+    /// * `callback` - Closure called with each `StreamedToken`, whose `sequence_index` is the index of the output text
+    ///
+    /// # Returns
+    /// * `Vec<String>` Generated texts
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// # fn main() -> anyhow::Result<()> {
+    /// use rust_bert::pipelines::text_generation::{TextGenerationConfig, TextGenerationModel};
+    /// use std::io::Write;
+    ///
+    /// let config = TextGenerationConfig {
+    ///     num_beams: 1,
+    ///     echo_prompt: false,
+    ///     ..Default::default()
+    /// };
+    /// let model = TextGenerationModel::new(config)?;
+    ///
+    /// let output = model.generate_with_callback(&["The dog"], None, |token| {
+    ///     print!("{}", token.text);
+    ///     std::io::stdout().flush().unwrap();
+    /// })?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn generate_with_callback<'a, S, F>(
+        &self,
+        texts: S,
+        prefix: impl Into<Option<&'a str>>,
+        callback: F,
+    ) -> Result<Vec<String>, RustBertError>
+    where
+        S: AsRef<[&'a str]>,
+        F: FnMut(StreamedToken),
+    {
+        let (prompts, prefix_length) = self.prepare_prompts(texts.as_ref(), prefix.into());
+        let (min_length, max_length) =
+            self.length_bounds(prefix_length, &GenerateOptions::default());
+        let (generated_indices, _) = self.model.generate_indices_with_callback(
+            Some(prompts.iter().map(String::as_str).collect::<Vec<&str>>()),
+            None,
+            min_length,
+            max_length,
+            callback,
+        )?;
+        Ok(self
+            .post_processors
+            .process_batch(self.decode_outputs(generated_indices, prefix_length)))
+    }
+
+    // Prepends the query or pipeline prefix to the prompts, returning the prompts and the prefix length in tokens
+    fn prepare_prompts(&self, texts: &[&str], prefix: Option<&str>) -> (Vec<String>, Option<i64>) {
+        let (prefix, prefix_length) = match (prefix, &self.prefix) {
             (Some(query_prefix), _) => (
                 Some(query_prefix),
                 Some(self.model.get_tokenizer().tokenize(query_prefix).len() as i64),
@@ -346,40 +867,117 @@ with people, even a bishop, begging for his blessing. <eod> </s> <eos>"
             (None, Some(pipeline_prefix)) => (Some(pipeline_prefix.as_str()), self.prefix_length),
             (None, None) => (None, None),
         };
-        let generated_indices = match (prefix, prefix_length) {
-            (None, _) => self.model.generate_indices(Some(texts), None, None, None),
-            (Some(prefix), Some(prefix_length)) => {
-                let texts = texts
-                    .as_ref()
-                    .iter()
-                    .map(|text| format!("{} {}", prefix, text))
-                    .collect_vec();
-                self.model.generate_indices(
-                    Some(texts.iter().map(|x| &**x).collect::<Vec<&str>>()),
-                    None,
-                    Some(self.min_length + prefix_length),
-                    Some(self.max_length + prefix_length),
-                )
+        let prompts = match prefix {
+            Some(prefix) => texts
+                .iter()
+                .map(|text| format!("{} {}", prefix, text))
+                .collect_vec(),
+            None => texts.iter().map(|text| text.to_string()).collect_vec(),
+        };
+        (prompts, prefix_length)
+    }
+
+    // Sequence length bounds of the generation, extended by the length of the prefix. The bounds relative to the
+    // prompt (`min_new_tokens` and `max_new_tokens`) already account for the prefix and are left to the generator.
+    fn length_bounds(
+        &self,
+        prefix_length: Option<i64>,
+        generate_options: &GenerateOptions,
+    ) -> (Option<i64>, Option<i64>) {
+        let prefix_length = match prefix_length {
+            Some(prefix_length) => prefix_length,
+            None => return (None, None),
+        };
+        let bound = |length: Option<i64>,
+                     new_tokens: Option<i64>,
+                     default_length: i64,
+                     default_new_tokens: Option<i64>| {
+            match (length, new_tokens) {
+                (_, Some(_)) => None,
+                (Some(length), None) => Some(length + prefix_length),
+                (None, None) if default_new_tokens.is_some() => None,
+                (None, None) => Some(default_length + prefix_length),
             }
-            _ => panic!("Prefix length not defined but prefix provided!"),
         };
+        (
+            bound(
+                generate_options.min_length,
+                generate_options.min_new_tokens,
+                self.min_length,
+                self.min_new_tokens,
+            ),
+            bound(
+                generate_options.max_length,
+                generate_options.max_new_tokens,
+                self.max_length,
+                self.max_new_tokens,
+            ),
+        )
+    }
 
-        let mut output = Vec::with_capacity(generated_indices.len());
-        for generated_sequence in generated_indices {
-            output.push(self.model.get_tokenizer().decode(
-                if prefix_length.is_some() {
-                    generated_sequence
-                        .into_iter()
-                        .skip(prefix_length.unwrap_or(0) as usize)
-                        .collect_vec()
-                } else {
-                    generated_sequence
-                },
-                true,
-                true,
-            ));
-        }
-        output
+    // Decodes the generated sequences, removing the prefix from the echoed prompts
+    fn decode_outputs(
+        &self,
+        generated_indices: Vec<Vec<i64>>,
+        prefix_length: Option<i64>,
+    ) -> Vec<String> {
+        generated_indices
+            .into_iter()
+            .map(|generated_sequence| {
+                self.model.get_tokenizer().decode(
+                    if prefix_length.is_some() & self.echo_prompt {
+                        generated_sequence
+                            .into_iter()
+                            .skip(prefix_length.unwrap_or(0) as usize)
+                            .collect_vec()
+                    } else {
+                        generated_sequence
+                    },
+                    true,
+                    true,
+                )
+            })
+            .collect()
+    }
+
+    /// Generate texts from prompts rendered with a `PromptTemplate`
+    ///
+    /// # Arguments
+    ///
+    /// * `template` - `PromptTemplate` to render
+    /// * `variables` - values of the template variables for each prompt
+    ///
+    /// # Returns
+    /// * `Vec<String>` Generated texts, or a `RustBertError::ValueError` if a template variable is missing
+    pub fn generate_from_template(
+        &self,
+        template: &PromptTemplate,
+        variables: &[&[(&str, &str)]],
+    ) -> Result<Vec<String>, RustBertError> {
+        let prompts = variables
+            .iter()
+            .map(|prompt_variables| template.render(prompt_variables))
+            .collect::<Result<Vec<String>, RustBertError>>()?;
+        Ok(self.generate(prompts.iter().map(|x| &**x).collect::<Vec<&str>>(), None))
+    }
+
+    /// Registers a post-processor applied to the generated texts, after the post-processors registered so far
+    ///
+    /// # Arguments
+    ///
+    /// * `post_processor` - `PostProcessor` to apply (e.g. a `RegexReplacer` or a closure)
+    pub fn add_post_processor<P: PostProcessor + 'static>(&mut self, post_processor: P) {
+        self.post_processors.add(post_processor);
+    }
+
+    /// Reloads the weights of the text generation model from the resource provided, keeping the tokenizer and configuration
+    /// unchanged. The weights must match the architecture of the model currently loaded.
+    ///
+    /// # Arguments
+    ///
+    /// * `weights_resource` - `Resource` pointing to the new model weights (e.g. model.ot)
+    pub fn reload_weights(&mut self, weights_resource: &Resource) -> Result<(), RustBertError> {
+        self.model.reload_weights(weights_resource)
     }
 }
 
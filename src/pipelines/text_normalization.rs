@@ -0,0 +1,224 @@
+// Copyright 2021 Guillaume Becquin
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//     http://www.apache.org/licenses/LICENSE-2.0
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! # Input text normalization
+//! Cleaning of raw inputs (e.g. scraped web pages or e-mails) before they are passed to a sequence-to-sequence
+//! pipeline. A `TextNormalizer` can be set in the `SummarizationConfig` and `TranslationConfig`, or used on its own.
+//! The following steps are applied, in this order, and can be enabled individually:
+//! - decoding of HTML entities (named entities such as `&amp;` or `&nbsp;`, and numeric entities such as `&#233;`)
+//! - removal of the short lines containing one of the boilerplate patterns (e.g. cookie notices or newsletter prompts)
+//! - unicode canonical composition (NFC)
+//! - removal of the control and invisible formatting characters (e.g. zero width spaces or byte order marks)
+//! - collapsing of the white space sequences into a single space
+//!
+//! ```no_run
+//! use rust_bert::pipelines::text_normalization::TextNormalizer;
+//!
+//! let text_normalizer = TextNormalizer {
+//!     remove_boilerplate: true,
+//!     ..Default::default()
+//! };
+//! let text = "Fish &amp; chips\u{200B} are   served.\nAccept all cookies";
+//! assert_eq!(text_normalizer.normalize(text), "Fish & chips are served.");
+//! ```
+
+use unicode_normalization::UnicodeNormalization;
+
+const DEFAULT_BOILERPLATE_PATTERNS: [&str; 12] = [
+    "accept all cookies",
+    "this website uses cookies",
+    "we use cookies",
+    "all rights reserved",
+    "sign up for our newsletter",
+    "subscribe to our newsletter",
+    "click here to",
+    "share this article",
+    "follow us on",
+    "advertisement",
+    "skip to content",
+    "privacy policy",
+];
+
+#[derive(Debug, Clone)]
+/// # Normalization applied to the pipeline inputs
+pub struct TextNormalizer {
+    /// Apply the unicode canonical composition (NFC) (default: true)
+    pub unicode_nfc: bool,
+    /// Remove the control and invisible formatting characters (default: true)
+    pub strip_control_characters: bool,
+    /// Replace the white space sequences (including line breaks) by a single space and trim the text (default: true)
+    pub collapse_whitespace: bool,
+    /// Decode the HTML entities (default: true)
+    pub decode_html_entities: bool,
+    /// Remove the lines containing one of the `boilerplate_patterns` (default: false)
+    pub remove_boilerplate: bool,
+    /// Case-insensitive patterns identifying boilerplate lines (default: common cookie, copyright, newsletter and sharing notices)
+    pub boilerplate_patterns: Vec<String>,
+    /// Maximum length (in characters) of the lines that can be removed as boilerplate, so that paragraphs merely
+    /// mentioning a pattern are kept (default: 120)
+    pub max_boilerplate_line_length: usize,
+}
+
+impl Default for TextNormalizer {
+    fn default() -> TextNormalizer {
+        TextNormalizer {
+            unicode_nfc: true,
+            strip_control_characters: true,
+            collapse_whitespace: true,
+            decode_html_entities: true,
+            remove_boilerplate: false,
+            boilerplate_patterns: DEFAULT_BOILERPLATE_PATTERNS
+                .iter()
+                .map(|pattern| pattern.to_string())
+                .collect(),
+            max_boilerplate_line_length: 120,
+        }
+    }
+}
+
+impl TextNormalizer {
+    /// Normalizes a text
+    ///
+    /// # Arguments
+    ///
+    /// * `text` - text to normalize
+    ///
+    /// # Returns
+    ///
+    /// * `String` normalized text
+    pub fn normalize(&self, text: &str) -> String {
+        let mut output = if self.decode_html_entities {
+            decode_html_entities(text)
+        } else {
+            text.to_string()
+        };
+        if self.remove_boilerplate {
+            output = self.remove_boilerplate_lines(&output);
+        }
+        if self.unicode_nfc {
+            output = output.nfc().collect();
+        }
+        if self.strip_control_characters {
+            output = output
+                .chars()
+                .filter(|&c| !is_invisible_character(c))
+                .collect();
+        }
+        if self.collapse_whitespace {
+            output = output.split_whitespace().collect::<Vec<&str>>().join(" ");
+        }
+        output
+    }
+
+    fn remove_boilerplate_lines(&self, text: &str) -> String {
+        let patterns = self
+            .boilerplate_patterns
+            .iter()
+            .map(|pattern| pattern.to_lowercase())
+            .collect::<Vec<String>>();
+        text.lines()
+            .filter(|line| {
+                let line = line.trim().to_lowercase();
+                line.chars().count() > self.max_boilerplate_line_length
+                    || !patterns
+                        .iter()
+                        .any(|pattern| line.contains(pattern.as_str()))
+            })
+            .collect::<Vec<&str>>()
+            .join("\n")
+    }
+}
+
+// Control characters other than white spaces, and formatting characters rendered as nothing
+fn is_invisible_character(c: char) -> bool {
+    (c.is_control() && !c.is_whitespace())
+        || matches!(
+            c,
+            '\u{00AD}' | '\u{200B}' | '\u{200E}' | '\u{200F}' | '\u{2060}' | '\u{FEFF}'
+        )
+}
+
+fn named_entity(name: &str) -> Option<char> {
+    Some(match name {
+        "amp" => '&',
+        "lt" => '<',
+        "gt" => '>',
+        "quot" => '"',
+        "apos" => '\'',
+        "nbsp" => '\u{00A0}',
+        "ndash" => '–',
+        "mdash" => '—',
+        "hellip" => '…',
+        "lsquo" => '‘',
+        "rsquo" => '’',
+        "ldquo" => '“',
+        "rdquo" => '”',
+        "laquo" => '«',
+        "raquo" => '»',
+        "middot" => '·',
+        "bull" => '•',
+        "copy" => '©',
+        "reg" => '®',
+        "trade" => '™',
+        "deg" => '°',
+        "times" => '×',
+        "divide" => '÷',
+        "euro" => '€',
+        "pound" => '£',
+        "yen" => '¥',
+        "cent" => '¢',
+        "sect" => '§',
+        "para" => '¶',
+        _ => return None,
+    })
+}
+
+fn decode_entity(entity: &str) -> Option<char> {
+    if let Some(number) = entity.strip_prefix('#') {
+        let code_point = match number
+            .strip_prefix('x')
+            .or_else(|| number.strip_prefix('X'))
+        {
+            Some(hexadecimal) => u32::from_str_radix(hexadecimal, 16).ok()?,
+            None => number.parse::<u32>().ok()?,
+        };
+        std::char::from_u32(code_point)
+    } else {
+        named_entity(entity)
+    }
+}
+
+/// Decodes the HTML entities of a text. Unknown or malformed entities are left unchanged, and the text is decoded
+/// only once (`&amp;lt;` becomes `&lt;`).
+pub fn decode_html_entities(text: &str) -> String {
+    let mut output = String::with_capacity(text.len());
+    let mut remaining = text;
+    while let Some(start) = remaining.find('&') {
+        output.push_str(&remaining[..start]);
+        remaining = &remaining[start..];
+        let decoded = remaining[1..]
+            .find(';')
+            .filter(|&end| end > 0 && end <= 10)
+            .and_then(|end| decode_entity(&remaining[1..end + 1]).map(|c| (c, end + 2)));
+        match decoded {
+            Some((c, length)) => {
+                output.push(c);
+                remaining = &remaining[length..];
+            }
+            None => {
+                output.push('&');
+                remaining = &remaining[1..];
+            }
+        }
+    }
+    output.push_str(remaining);
+    output
+}
@@ -0,0 +1,310 @@
+// Copyright 2021 Guillaume Becquin
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//     http://www.apache.org/licenses/LICENSE-2.0
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! # Text statistics and readability scores
+//! Cheap statistics computed before running a model, for example to reject inputs that are too long or poorly
+//! covered by the vocabulary of the model before an expensive inference:
+//! - `TokenStatistics`: length of the text, number of tokens, rate of unknown tokens and subword fertility (average
+//! number of tokens per word) for the tokenizer of a model
+//! - `ReadabilityScores`: classical readability formulas (Flesch reading ease, Flesch-Kincaid grade, Gunning fog,
+//! SMOG, Coleman-Liau and automated readability index). The syllable counts are estimated with an English heuristic.
+//! - `InputValidator`: thresholds on the token statistics, returning an error for the inputs outside of them
+//!
+//! ```no_run
+//! # fn main() -> anyhow::Result<()> {
+//! use rust_bert::pipelines::common::{ModelType, TokenizerOption};
+//! use rust_bert::pipelines::text_statistics::{InputValidator, ReadabilityScores};
+//!
+//! let tokenizer = TokenizerOption::from_file(
+//!     ModelType::Bert,
+//!     "path/to/vocab.txt",
+//!     None,
+//!     true,
+//!     None,
+//!     None,
+//! )?;
+//! let validator = InputValidator {
+//!     max_tokens: Some(512),
+//!     max_unknown_rate: Some(0.1),
+//!     ..Default::default()
+//! };
+//! let text = "The cat sat on the mat. It was happy.";
+//! let token_statistics = validator.validate(&tokenizer, text)?;
+//! let readability = ReadabilityScores::compute(text);
+//! println!(
+//!     "{} tokens, Flesch reading ease: {:.1}",
+//!     token_statistics.num_tokens, readability.flesch_reading_ease
+//! );
+//! # Ok(())
+//! # }
+//! ```
+
+use crate::common::error::RustBertError;
+use crate::pipelines::common::TokenizerOption;
+
+fn words(text: &str) -> impl Iterator<Item = &str> {
+    text.split_whitespace()
+        .map(|word| word.trim_matches(|c: char| !c.is_alphanumeric()))
+        .filter(|word| !word.is_empty())
+}
+
+fn ratio(numerator: usize, denominator: usize) -> f64 {
+    if denominator == 0 {
+        0.0
+    } else {
+        numerator as f64 / denominator as f64
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+/// # Token-level statistics of a text for a given tokenizer
+pub struct TokenStatistics {
+    /// Number of characters
+    pub num_characters: usize,
+    /// Number of words (white space separated, ignoring the sequences of punctuation marks)
+    pub num_words: usize,
+    /// Number of tokens, excluding the special tokens added by the model
+    pub num_tokens: usize,
+    /// Number of tokens mapped to the unknown token of the vocabulary
+    pub num_unknown_tokens: usize,
+    /// Proportion of unknown tokens
+    pub unknown_rate: f64,
+    /// Average number of tokens per word
+    pub fertility: f64,
+}
+
+impl TokenStatistics {
+    /// Computes the token statistics of a text
+    ///
+    /// # Arguments
+    ///
+    /// * `tokenizer` - `TokenizerOption` of the model the text would be passed to
+    /// * `text` - text to analyze
+    pub fn compute(tokenizer: &TokenizerOption, text: &str) -> TokenStatistics {
+        let tokens = tokenizer.tokenize(text);
+        let unknown_id = tokenizer.get_unk_id();
+        let num_unknown_tokens = tokenizer
+            .convert_tokens_to_ids(&tokens)
+            .into_iter()
+            .filter(|&token_id| token_id == unknown_id)
+            .count();
+        let num_words = words(text).count();
+        TokenStatistics {
+            num_characters: text.chars().count(),
+            num_words,
+            num_tokens: tokens.len(),
+            num_unknown_tokens,
+            unknown_rate: ratio(num_unknown_tokens, tokens.len()),
+            fertility: ratio(tokens.len(), num_words),
+        }
+    }
+}
+
+/// Estimates the number of syllables of an English word, counting the groups of vowels (with a correction for the
+/// silent final e). Words are counted as at least one syllable.
+pub fn count_syllables(word: &str) -> usize {
+    let letters = word
+        .chars()
+        .filter(|c| c.is_alphabetic())
+        .flat_map(char::to_lowercase)
+        .collect::<Vec<char>>();
+    if letters.is_empty() {
+        return 0;
+    }
+    let is_vowel = |c: char| matches!(c, 'a' | 'e' | 'i' | 'o' | 'u' | 'y');
+    let mut count = 0;
+    let mut previous_vowel = false;
+    for &c in letters.iter() {
+        let vowel = is_vowel(c);
+        if vowel && !previous_vowel {
+            count += 1;
+        }
+        previous_vowel = vowel;
+    }
+    let length = letters.len();
+    if length > 2
+        && letters[length - 1] == 'e'
+        && !is_vowel(letters[length - 2])
+        && letters[length - 2] != 'l'
+    {
+        count -= 1;
+    }
+    count.max(1)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+/// # Readability scores of a text
+/// The scores are 0 for texts without words.
+pub struct ReadabilityScores {
+    /// Number of sentences
+    pub num_sentences: usize,
+    /// Number of words
+    pub num_words: usize,
+    /// Estimated number of syllables
+    pub num_syllables: usize,
+    /// Number of words with three syllables or more
+    pub num_complex_words: usize,
+    /// Flesch reading ease (higher is easier, 60-70 corresponds to plain English)
+    pub flesch_reading_ease: f64,
+    /// Flesch-Kincaid grade level (US school grade)
+    pub flesch_kincaid_grade: f64,
+    /// Gunning fog index (years of formal education)
+    pub gunning_fog: f64,
+    /// SMOG index (years of formal education)
+    pub smog_index: f64,
+    /// Coleman-Liau index (US school grade, based on the number of letters)
+    pub coleman_liau_index: f64,
+    /// Automated readability index (US school grade, based on the number of characters)
+    pub automated_readability_index: f64,
+}
+
+impl ReadabilityScores {
+    /// Computes the readability scores of a text
+    pub fn compute(text: &str) -> ReadabilityScores {
+        let syllables = words(text).map(count_syllables).collect::<Vec<usize>>();
+        let num_words = syllables.len();
+        if num_words == 0 {
+            return ReadabilityScores {
+                num_sentences: 0,
+                num_words: 0,
+                num_syllables: 0,
+                num_complex_words: 0,
+                flesch_reading_ease: 0.0,
+                flesch_kincaid_grade: 0.0,
+                gunning_fog: 0.0,
+                smog_index: 0.0,
+                coleman_liau_index: 0.0,
+                automated_readability_index: 0.0,
+            };
+        }
+        let num_sentences = count_sentences(text).max(1);
+        let num_syllables = syllables.iter().sum::<usize>();
+        let num_complex_words = syllables.iter().filter(|&&count| count >= 3).count();
+        let num_letters = words(text)
+            .flat_map(str::chars)
+            .filter(|c| c.is_alphabetic())
+            .count();
+        let num_characters = words(text)
+            .flat_map(str::chars)
+            .filter(|c| c.is_alphanumeric())
+            .count();
+
+        let words_per_sentence = ratio(num_words, num_sentences);
+        let syllables_per_word = ratio(num_syllables, num_words);
+        ReadabilityScores {
+            num_sentences,
+            num_words,
+            num_syllables,
+            num_complex_words,
+            flesch_reading_ease: 206.835 - 1.015 * words_per_sentence - 84.6 * syllables_per_word,
+            flesch_kincaid_grade: 0.39 * words_per_sentence + 11.8 * syllables_per_word - 15.59,
+            gunning_fog: 0.4 * (words_per_sentence + 100.0 * ratio(num_complex_words, num_words)),
+            smog_index: 1.043 * (num_complex_words as f64 * 30.0 / num_sentences as f64).sqrt()
+                + 3.1291,
+            coleman_liau_index: 0.0588 * 100.0 * ratio(num_letters, num_words)
+                - 0.296 * 100.0 * ratio(num_sentences, num_words)
+                - 15.8,
+            automated_readability_index: 4.71 * ratio(num_characters, num_words)
+                + 0.5 * words_per_sentence
+                - 21.43,
+        }
+    }
+}
+
+// Number of sequences of sentence terminators followed by a white space or the end of the text
+fn count_sentences(text: &str) -> usize {
+    let mut count = 0;
+    let mut chars = text.chars().peekable();
+    while let Some(c) = chars.next() {
+        if matches!(c, '.' | '!' | '?') {
+            match chars.peek() {
+                Some(&'.') | Some(&'!') | Some(&'?') => {}
+                Some(next) if !next.is_whitespace() => {}
+                _ => count += 1,
+            }
+        }
+    }
+    count
+}
+
+#[derive(Debug, Clone, Default)]
+/// # Validation of the inputs of a model
+/// Each threshold is checked only if it is set.
+pub struct InputValidator {
+    /// Minimum number of words
+    pub min_words: Option<usize>,
+    /// Maximum number of tokens (e.g. the maximum sequence length of the model)
+    pub max_tokens: Option<usize>,
+    /// Maximum proportion of unknown tokens
+    pub max_unknown_rate: Option<f64>,
+    /// Maximum average number of tokens per word, high values indicating a text in a language or script poorly
+    /// covered by the vocabulary
+    pub max_fertility: Option<f64>,
+}
+
+impl InputValidator {
+    /// Checks the token statistics against the thresholds of the validator
+    pub fn check(&self, statistics: &TokenStatistics) -> Result<(), RustBertError> {
+        if let Some(min_words) = self.min_words {
+            if statistics.num_words < min_words {
+                return Err(RustBertError::ValueError(format!(
+                    "Input has {} words, expected at least {}",
+                    statistics.num_words, min_words
+                )));
+            }
+        }
+        if let Some(max_tokens) = self.max_tokens {
+            if statistics.num_tokens > max_tokens {
+                return Err(RustBertError::ValueError(format!(
+                    "Input has {} tokens, expected at most {}",
+                    statistics.num_tokens, max_tokens
+                )));
+            }
+        }
+        if let Some(max_unknown_rate) = self.max_unknown_rate {
+            if statistics.unknown_rate > max_unknown_rate {
+                return Err(RustBertError::ValueError(format!(
+                    "Input has an unknown token rate of {:.3}, expected at most {:.3}",
+                    statistics.unknown_rate, max_unknown_rate
+                )));
+            }
+        }
+        if let Some(max_fertility) = self.max_fertility {
+            if statistics.fertility > max_fertility {
+                return Err(RustBertError::ValueError(format!(
+                    "Input has a fertility of {:.2} tokens per word, expected at most {:.2}",
+                    statistics.fertility, max_fertility
+                )));
+            }
+        }
+        Ok(())
+    }
+
+    /// Computes the token statistics of a text and checks them against the thresholds of the validator
+    ///
+    /// # Arguments
+    ///
+    /// * `tokenizer` - `TokenizerOption` of the model the text would be passed to
+    /// * `text` - text to validate
+    ///
+    /// # Returns
+    ///
+    /// * `TokenStatistics` of the text if it is valid, a `RustBertError::ValueError` otherwise
+    pub fn validate(
+        &self,
+        tokenizer: &TokenizerOption,
+        text: &str,
+    ) -> Result<TokenStatistics, RustBertError> {
+        let statistics = TokenStatistics::compute(tokenizer, text);
+        self.check(&statistics)?;
+        Ok(statistics)
+    }
+}
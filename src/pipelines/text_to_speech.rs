@@ -0,0 +1,253 @@
+// Copyright 2021 Guillaume Becquin
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//     http://www.apache.org/licenses/LICENSE-2.0
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! # Text-to-speech pipeline
+//! Generates waveforms from text in two stages:
+//! - an acoustic model predicts a log-mel spectrogram from the text, optionally conditioned on a speaker embedding
+//! (e.g. the 512-dimensional x-vectors used by SpeechT5)
+//! - a HiFi-GAN vocoder (`hifigan::HifiGan`) converts the spectrogram into waveform samples
+//!
+//! The crate does not provide an acoustic model yet: it is supplied by implementing the `SpectrogramGenerator` trait,
+//! and the pipeline takes care of the vocoder stage. End-to-end models generating waveforms directly (such as VITS)
+//! are not supported.
+//!
+//! ```no_run
+//! # fn main() -> anyhow::Result<()> {
+//! use rust_bert::pipelines::text_to_speech::{
+//!     SpectrogramGenerator, TextToSpeechConfig, TextToSpeechModel,
+//! };
+//! use rust_bert::resources::{LocalResource, Resource};
+//! use rust_bert::RustBertError;
+//! use std::path::PathBuf;
+//! use tch::{Device, Kind, Tensor};
+//!
+//! struct MySpeechT5;
+//!
+//! impl SpectrogramGenerator for MySpeechT5 {
+//!     fn generate_spectrogram(
+//!         &self,
+//!         text: &str,
+//!         speaker_embeddings: Option<&Tensor>,
+//!     ) -> Result<Tensor, RustBertError> {
+//!         unimplemented!()
+//!     }
+//! }
+//!
+//! let config = TextToSpeechConfig::new(
+//!     Resource::Local(LocalResource {
+//!         local_path: PathBuf::from("path/to/vocoder/model.ot"),
+//!     }),
+//!     Resource::Local(LocalResource {
+//!         local_path: PathBuf::from("path/to/vocoder/config.json"),
+//!     }),
+//! );
+//! let tts_model = TextToSpeechModel::new(config, Box::new(MySpeechT5))?;
+//! let speaker_embeddings = Tensor::zeros(&[512], (Kind::Float, Device::Cpu));
+//! let waveforms = tts_model.predict(&["Hello, world!"], Some(&speaker_embeddings))?;
+//! let sampling_rate = tts_model.sampling_rate();
+//! # Ok(())
+//! # }
+//! ```
+
+use crate::common::error::RustBertError;
+use crate::common::resources::Resource;
+use crate::common::weights::reload_var_store;
+use crate::hifigan::{HifiGan, HifiGanConfig};
+use crate::Config;
+use tch::nn::VarStore;
+use tch::{no_grad, Device, Kind, Tensor};
+
+/// # Acoustic model generating log-mel spectrograms from text
+pub trait SpectrogramGenerator: Send {
+    /// Generates the spectrogram for a text
+    ///
+    /// # Arguments
+    ///
+    /// * `text` - text to synthesize
+    /// * `speaker_embeddings` - optional speaker embedding of shape (*speaker_embedding_dim*), required by multi-speaker models
+    ///
+    /// # Returns
+    ///
+    /// * `Tensor` of shape (*num_frames*, *num_mel_bins*)
+    fn generate_spectrogram(
+        &self,
+        text: &str,
+        speaker_embeddings: Option<&Tensor>,
+    ) -> Result<Tensor, RustBertError>;
+}
+
+/// # Configuration for TextToSpeechModel
+/// Contains information regarding the vocoder to load and device to place it on.
+pub struct TextToSpeechConfig {
+    /// Vocoder weights resource
+    pub vocoder_model_resource: Resource,
+    /// Vocoder config resource
+    pub vocoder_config_resource: Resource,
+    /// Device to place the vocoder on (default: CUDA/GPU when available)
+    pub device: Device,
+}
+
+impl TextToSpeechConfig {
+    /// Instantiate a new text-to-speech configuration from the vocoder weights and configuration.
+    ///
+    /// # Arguments
+    ///
+    /// * `vocoder_model_resource` - The `Resource` pointing to the vocoder weights to load (e.g.  model.ot)
+    /// * `vocoder_config_resource` - The `Resource' pointing to the vocoder configuration to load (e.g. config.json)
+    pub fn new(
+        vocoder_model_resource: Resource,
+        vocoder_config_resource: Resource,
+    ) -> TextToSpeechConfig {
+        TextToSpeechConfig {
+            vocoder_model_resource,
+            vocoder_config_resource,
+            device: Device::cuda_if_available(),
+        }
+    }
+}
+
+/// # TextToSpeechModel to synthesize speech from text
+pub struct TextToSpeechModel {
+    spectrogram_generator: Box<dyn SpectrogramGenerator>,
+    vocoder: HifiGan,
+    vocoder_config: HifiGanConfig,
+    var_store: VarStore,
+}
+
+impl TextToSpeechModel {
+    /// Build a new `TextToSpeechModel`
+    ///
+    /// # Arguments
+    ///
+    /// * `config` - `TextToSpeechConfig` object containing the vocoder resource references and device placement (CPU/GPU)
+    /// * `spectrogram_generator` - acoustic model generating the spectrograms
+    pub fn new(
+        config: TextToSpeechConfig,
+        spectrogram_generator: Box<dyn SpectrogramGenerator>,
+    ) -> Result<TextToSpeechModel, RustBertError> {
+        let config_path = config.vocoder_config_resource.get_local_path()?;
+        let weights_path = config.vocoder_model_resource.get_local_path()?;
+
+        let vocoder_config = HifiGanConfig::try_from_file(config_path)?;
+        let mut var_store = VarStore::new(config.device);
+        let vocoder = HifiGan::new(&var_store.root(), &vocoder_config);
+        var_store.load(weights_path)?;
+        Ok(TextToSpeechModel {
+            spectrogram_generator,
+            vocoder,
+            vocoder_config,
+            var_store,
+        })
+    }
+
+    /// Sampling rate of the generated waveforms
+    pub fn sampling_rate(&self) -> i64 {
+        self.vocoder_config.sampling_rate
+    }
+
+    /// Synthesize speech for a batch of texts
+    ///
+    /// # Arguments
+    ///
+    /// * `input` - `&[&str]` Array of texts to synthesize
+    /// * `speaker_embeddings` - optional speaker embedding passed to the acoustic model for all texts
+    ///
+    /// # Returns
+    ///
+    /// * `Vec<Vec<f32>>` containing the waveform samples in [-1, 1] for each text, at `sampling_rate()`
+    pub fn predict(
+        &self,
+        input: &[&str],
+        speaker_embeddings: Option<&Tensor>,
+    ) -> Result<Vec<Vec<f32>>, RustBertError> {
+        let mut waveforms = Vec::with_capacity(input.len());
+        for text in input {
+            let spectrogram = self
+                .spectrogram_generator
+                .generate_spectrogram(text, speaker_embeddings)?;
+            if spectrogram.size().len() != 2
+                || spectrogram.size()[1] != self.vocoder_config.model_in_dim
+            {
+                return Err(RustBertError::ValueError(format!(
+                    "Expected a spectrogram of shape (num_frames, {}), got {:?}",
+                    self.vocoder_config.model_in_dim,
+                    spectrogram.size()
+                )));
+            }
+            waveforms.push(self.vocode(&spectrogram));
+        }
+        Ok(waveforms)
+    }
+
+    /// Converts a spectrogram into waveform samples using the vocoder
+    ///
+    /// # Arguments
+    ///
+    /// * `spectrogram` - log-mel spectrogram of shape (*num_frames*, *num_mel_bins*)
+    ///
+    /// # Returns
+    ///
+    /// * `Vec<f32>` containing *num_frames* x *hop_length* samples
+    pub fn vocode(&self, spectrogram: &Tensor) -> Vec<f32> {
+        let waveform = no_grad(|| {
+            self.vocoder
+                .forward(
+                    &spectrogram
+                        .to_kind(Kind::Float)
+                        .to(self.var_store.device())
+                        .unsqueeze(0),
+                )
+                .squeeze1(0)
+                .to(Device::Cpu)
+        });
+        Vec::<f32>::from(waveform)
+    }
+
+    /// Reloads the weights of the vocoder from the resource provided, keeping the configuration unchanged.
+    ///
+    /// # Arguments
+    ///
+    /// * `weights_resource` - `Resource` pointing to the new vocoder weights (e.g. model.ot)
+    pub fn reload_weights(&mut self, weights_resource: &Resource) -> Result<(), RustBertError> {
+        reload_var_store(&mut self.var_store, weights_resource)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::resources::LocalResource;
+    use std::path::PathBuf;
+
+    #[test]
+    #[ignore] // no need to run, compilation is enough to verify it is Send
+    fn test() {
+        struct DummyModel;
+        impl SpectrogramGenerator for DummyModel {
+            fn generate_spectrogram(
+                &self,
+                _: &str,
+                _: Option<&Tensor>,
+            ) -> Result<Tensor, RustBertError> {
+                Ok(Tensor::zeros(&[1, 80], (Kind::Float, Device::Cpu)))
+            }
+        }
+        let config = TextToSpeechConfig::new(
+            Resource::Local(LocalResource {
+                local_path: PathBuf::from("path/to/model.ot"),
+            }),
+            Resource::Local(LocalResource {
+                local_path: PathBuf::from("path/to/config.json"),
+            }),
+        );
+        let _: Box<dyn Send> = Box::new(TextToSpeechModel::new(config, Box::new(DummyModel)));
+    }
+}
@@ -14,6 +14,10 @@
 //! # Token classification pipeline (Named Entity Recognition, Part-of-Speech tagging)
 //! More generic token classification pipeline, works with multiple models (Bert, Roberta)
 //!
+//! By default each token takes its most likely label. The labels can instead be decoded as a sequence, with the CRF
+//! layer of the checkpoint (`crf`) and/or under the constraints of a BIO or BILOU tagging scheme (`label_constraints`),
+//! so that no invalid entity span is returned.
+//!
 //! ```no_run
 //! use rust_bert::pipelines::token_classification::{TokenClassificationModel,TokenClassificationConfig};
 //! use rust_bert::resources::{Resource,RemoteResource};
@@ -114,12 +118,14 @@ use crate::albert::AlbertForTokenClassification;
 use crate::bert::{
     BertConfigResources, BertForTokenClassification, BertModelResources, BertVocabResources,
 };
+use crate::common::crf::{TransitionScores, CRF};
 use crate::common::error::RustBertError;
 use crate::common::resources::{RemoteResource, Resource};
+use crate::common::weights::reload_var_store;
 use crate::distilbert::DistilBertForTokenClassification;
 use crate::electra::ElectraForTokenClassification;
 use crate::mobilebert::MobileBertForTokenClassification;
-use crate::pipelines::common::{ConfigOption, ModelType, TokenizerOption};
+use crate::pipelines::common::{ConfigOption, ModelType, TensorBatch, TokenizerOption};
 use crate::roberta::RobertaForTokenClassification;
 use crate::xlnet::XLNetForTokenClassification;
 use itertools::Itertools;
@@ -131,7 +137,7 @@ use serde::{Deserialize, Serialize};
 use std::borrow::Borrow;
 use std::cmp::min;
 use std::collections::HashMap;
-use tch::kind::Kind::Float;
+use tch::kind::Kind::{Double, Float};
 use tch::nn::VarStore;
 use tch::{nn, no_grad, Device, Tensor};
 
@@ -178,6 +184,205 @@ impl ConsolidatableTokens<Token> for Vec<Token> {
     }
 }
 
+#[derive(Debug, Clone, Serialize, Deserialize)]
+/// # Word labelled by a `TokenClassificationModel`
+/// Aggregates the predictions of the sub-tokens of a word, see `TokenClassificationModel::predict_words`.
+pub struct Word {
+    /// Text of the word in the input
+    pub text: String,
+    /// Confidence score of the word label
+    pub score: f64,
+    /// Word label (e.g. ORG, LOC in case of NER)
+    pub label: String,
+    /// Label index
+    pub label_index: i64,
+    /// Sentence index
+    pub sentence: usize,
+    /// Word position index
+    pub word_index: u16,
+    /// Character offsets of the word in the input
+    pub offset: Option<Offset>,
+    /// Byte offsets (start, end) of the word in the input, for slicing the input string
+    pub byte_offset: Option<(usize, usize)>,
+    /// Probability of the word label for each sub-token of the word
+    pub sub_token_scores: Vec<f64>,
+}
+
+/// # Aggregation of the sub-token predictions of a word
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum WordAggregationStrategy {
+    /// The word takes the label and score of its first sub-token
+    First,
+    /// The word takes the label with the highest probability averaged over its sub-tokens
+    Average,
+    /// The word takes the label and score of its most confident sub-token
+    Max,
+}
+
+// Returns the label index and score of a word given the label probabilities of its sub-tokens
+fn aggregate_word_probabilities(
+    probabilities: &[Vec<f64>],
+    strategy: WordAggregationStrategy,
+) -> (usize, f64) {
+    let argmax = |values: &[f64]| {
+        values.iter().cloned().enumerate().fold(
+            (0, std::f64::NEG_INFINITY),
+            |best, (index, value)| {
+                if value > best.1 {
+                    (index, value)
+                } else {
+                    best
+                }
+            },
+        )
+    };
+    match strategy {
+        WordAggregationStrategy::First => argmax(&probabilities[0]),
+        WordAggregationStrategy::Average => {
+            let mut average = vec![0f64; probabilities[0].len()];
+            for sub_token_probabilities in probabilities {
+                for (sum, probability) in average.iter_mut().zip(sub_token_probabilities) {
+                    *sum += probability / probabilities.len() as f64;
+                }
+            }
+            argmax(&average)
+        }
+        WordAggregationStrategy::Max => probabilities
+            .iter()
+            .map(|sub_token_probabilities| argmax(sub_token_probabilities.as_slice()))
+            .fold((0, std::f64::NEG_INFINITY), |best, candidate| {
+                if candidate.1 > best.1 {
+                    candidate
+                } else {
+                    best
+                }
+            }),
+    }
+}
+
+/// # Tagging scheme of the entity labels, defining the valid label sequences
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
+pub enum LabelScheme {
+    /// `B-X` begins an entity of type `X` and `I-X` continues it: `I-X` may only follow `B-X` or `I-X`
+    BIO,
+    /// `B-X` begins a multi-token entity, `I-X` continues it and `L-X` ends it, `U-X` is a single-token entity: an
+    /// entity started by `B-X` must be continued by `I-X` or ended by `L-X`. The `E-` and `S-` prefixes of the BIOES
+    /// scheme are accepted for `L-` and `U-`.
+    BILOU,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Tag<'a> {
+    Begin(&'a str),
+    Inside(&'a str),
+    Last(&'a str),
+    Unit(&'a str),
+    Outside,
+    // Label outside of the scheme, not constrained
+    Other,
+}
+
+impl LabelScheme {
+    fn tag<'a>(&self, label: &'a str) -> Tag<'a> {
+        if label == "O" {
+            return Tag::Outside;
+        }
+        let (prefix, entity_type) = match label.find('-') {
+            Some(position) => (&label[..position], &label[position + 1..]),
+            None => return Tag::Other,
+        };
+        match (self, prefix) {
+            (_, "B") => Tag::Begin(entity_type),
+            (_, "I") => Tag::Inside(entity_type),
+            (LabelScheme::BILOU, "L") | (LabelScheme::BILOU, "E") => Tag::Last(entity_type),
+            (LabelScheme::BILOU, "U") | (LabelScheme::BILOU, "S") => Tag::Unit(entity_type),
+            _ => Tag::Other,
+        }
+    }
+
+    fn is_allowed(&self, previous: Option<Tag>, next: Option<Tag>) -> bool {
+        match self {
+            LabelScheme::BIO => match next {
+                Some(Tag::Inside(entity_type)) => match previous {
+                    Some(Tag::Begin(previous_type)) | Some(Tag::Inside(previous_type)) => {
+                        previous_type == entity_type
+                    }
+                    Some(Tag::Other) => true,
+                    _ => false,
+                },
+                _ => true,
+            },
+            LabelScheme::BILOU => match (previous, next) {
+                (Some(Tag::Other), _) | (_, Some(Tag::Other)) => true,
+                (Some(Tag::Begin(previous_type)), Some(Tag::Inside(entity_type)))
+                | (Some(Tag::Begin(previous_type)), Some(Tag::Last(entity_type)))
+                | (Some(Tag::Inside(previous_type)), Some(Tag::Inside(entity_type)))
+                | (Some(Tag::Inside(previous_type)), Some(Tag::Last(entity_type))) => {
+                    previous_type == entity_type
+                }
+                (Some(Tag::Begin(_)), _) | (Some(Tag::Inside(_)), _) => false,
+                (_, Some(Tag::Inside(_))) | (_, Some(Tag::Last(_))) => false,
+                _ => true,
+            },
+        }
+    }
+
+    /// Returns the transition scores forbidding the invalid label sequences of the scheme, with a score of 0 for the
+    /// allowed transitions and `f64::NEG_INFINITY` for the forbidden ones. Labels outside of the scheme (e.g. without
+    /// prefix) are not constrained.
+    ///
+    /// # Arguments
+    ///
+    /// * `labels` - Labels of the model, ordered by label index
+    ///
+    /// # Returns
+    ///
+    /// * `TransitionScores` constraining the decoding of the labels
+    pub fn transition_constraints(&self, labels: &[&str]) -> TransitionScores {
+        let tags = labels
+            .iter()
+            .map(|label| self.tag(label))
+            .collect::<Vec<Tag>>();
+        let score = |allowed: bool| if allowed { 0.0 } else { f64::NEG_INFINITY };
+        TransitionScores {
+            transitions: tags
+                .iter()
+                .map(|&previous| {
+                    tags.iter()
+                        .map(|&next| score(self.is_allowed(Some(previous), Some(next))))
+                        .collect()
+                })
+                .collect(),
+            start: tags
+                .iter()
+                .map(|&next| score(self.is_allowed(None, Some(next))))
+                .collect(),
+            end: tags
+                .iter()
+                .map(|&previous| score(self.is_allowed(Some(previous), None)))
+                .collect(),
+        }
+    }
+}
+
+/// # Algorithm decoding the labels under the constraints of a `LabelScheme`
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
+pub enum ConstrainedDecodingStrategy {
+    /// Most likely valid label sequence of the whole input (Viterbi algorithm)
+    Viterbi,
+    /// Labels picked from left to right, each being the most likely valid label given the previous one
+    Greedy,
+}
+
+/// # Constraints on the token labels
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
+pub struct LabelConstraints {
+    /// Tagging scheme of the labels
+    pub scheme: LabelScheme,
+    /// Decoding algorithm
+    pub strategy: ConstrainedDecodingStrategy,
+}
+
 type LabelAggregationFunction = Box<fn(&[Token]) -> (i64, String)>;
 
 /// # Enum defining the label aggregation method for sub tokens
@@ -214,8 +419,19 @@ pub struct TokenClassificationConfig {
     pub add_prefix_space: Option<bool>,
     /// Device to place the model on (default: CUDA/GPU when available)
     pub device: Device,
+    /// Copy the inputs to the device through pinned host memory, asynchronously (default: false). Only used for
+    /// CUDA devices, see `pipelines::common::to_device`
+    pub pinned_memory: bool,
     /// Sub-tokens aggregation method (default: `LabelAggregationOption::First`)
     pub label_aggregation_function: LabelAggregationOption,
+    /// Decode the token labels of `predict` with a linear-chain CRF layer instead of taking the most likely label of
+    /// each token (default: false). The CRF weights are loaded from the `crf` variables of the model checkpoint, see
+    /// `crf::CRF`.
+    pub crf: bool,
+    /// Decode the token labels of `predict` under the constraints of a tagging scheme, so that no invalid entity
+    /// span (e.g. `I-PER` following `O`) is returned (default: None). Combined with the transition scores of the CRF
+    /// layer if `crf` is set.
+    pub label_constraints: Option<LabelConstraints>,
 }
 
 impl TokenClassificationConfig {
@@ -250,7 +466,10 @@ impl TokenClassificationConfig {
             strip_accents: strip_accents.into(),
             add_prefix_space: add_prefix_space.into(),
             device: Device::cuda_if_available(),
+            pinned_memory: false,
             label_aggregation_function,
+            crf: false,
+            label_constraints: None,
         }
     }
 }
@@ -274,7 +493,10 @@ impl Default for TokenClassificationConfig {
             strip_accents: None,
             add_prefix_space: None,
             device: Device::cuda_if_available(),
+            pinned_memory: false,
             label_aggregation_function: LabelAggregationOption::First,
+            crf: false,
+            label_constraints: None,
         }
     }
 }
@@ -528,6 +750,9 @@ pub struct TokenClassificationModel {
     label_mapping: HashMap<i64, String>,
     var_store: VarStore,
     label_aggregation_function: LabelAggregationOption,
+    pinned_memory: bool,
+    crf: Option<CRF>,
+    label_constraints: Option<(ConstrainedDecodingStrategy, TransitionScores)>,
 }
 
 impl TokenClassificationModel {
@@ -570,10 +795,26 @@ impl TokenClassificationModel {
             config.add_prefix_space,
         )?;
         let mut var_store = VarStore::new(device);
-        let model_config = ConfigOption::from_file(config.model_type, config_path);
+        let model_config = ConfigOption::try_from_file(config.model_type, config_path)?;
+        tokenizer.check_model_compatibility(config.model_type, model_config.get_vocab_size())?;
+        let root = var_store.root();
         let token_sequence_classifier =
-            TokenClassificationOption::new(config.model_type, &var_store.root(), &model_config)?;
+            TokenClassificationOption::new(config.model_type, &root, &model_config)?;
         let label_mapping = model_config.get_label_mapping();
+        let crf = if config.crf {
+            Some(CRF::new(&root / "crf", label_mapping.len() as i64))
+        } else {
+            None
+        };
+        let label_constraints = config.label_constraints.map(|constraints| {
+            let labels = (0..label_mapping.len() as i64)
+                .map(|label_index| label_mapping.get(&label_index).map_or("", String::as_str))
+                .collect::<Vec<&str>>();
+            (
+                constraints.strategy,
+                constraints.scheme.transition_constraints(&labels),
+            )
+        });
         var_store.load(weights_path)?;
         Ok(TokenClassificationModel {
             tokenizer,
@@ -581,39 +822,30 @@ impl TokenClassificationModel {
             label_mapping,
             var_store,
             label_aggregation_function,
+            pinned_memory: config.pinned_memory,
+            crf,
+            label_constraints,
         })
     }
 
-    fn prepare_for_model<'a, S>(&self, input: S) -> (Vec<TokenizedInput>, Tensor)
+    fn prepare_for_model<'a, S>(&self, input: S) -> (Vec<TokenizedInput>, TensorBatch)
     where
         S: AsRef<[&'a str]>,
     {
         let tokenized_input: Vec<TokenizedInput> =
             self.tokenizer
                 .encode_list(input.as_ref(), 128, &TruncationStrategy::LongestFirst, 0);
-        let max_len = tokenized_input
-            .iter()
-            .map(|input| input.token_ids.len())
-            .max()
-            .unwrap();
-        let tokenized_input_tensors: Vec<tch::Tensor> = tokenized_input
+        let token_ids = tokenized_input
             .iter()
             .map(|input| input.token_ids.clone())
-            .map(|mut input| {
-                input.extend(vec![
-                    self.tokenizer.get_pad_id().expect(
-                        "The Tokenizer used for token classification should contain a PAD id"
-                    );
-                    max_len - input.len()
-                ]);
-                input
-            })
-            .map(|input| Tensor::of_slice(&(input)))
-            .collect::<Vec<_>>();
-        (
-            tokenized_input,
-            Tensor::stack(tokenized_input_tensors.as_slice(), 0).to(self.var_store.device()),
-        )
+            .collect::<Vec<Vec<i64>>>();
+        let pad_id = self
+            .tokenizer
+            .get_pad_id()
+            .expect("The Tokenizer used for token classification should contain a PAD id");
+        let input_batch = TensorBatch::from_token_ids(&token_ids, pad_id, self.var_store.device())
+            .with_pinned_memory(self.pinned_memory);
+        (tokenized_input, input_batch)
     }
 
     /// Classify tokens in a text sequence
@@ -652,20 +884,8 @@ impl TokenClassificationModel {
     where
         S: AsRef<[&'a str]>,
     {
-        let (tokenized_input, input_tensor) = self.prepare_for_model(input.as_ref());
-        let output = no_grad(|| {
-            self.token_sequence_classifier.forward_t(
-                Some(input_tensor.copy()),
-                None,
-                None,
-                None,
-                None,
-                false,
-            )
-        });
-        let output = output.detach().to(Device::Cpu);
-        let score: Tensor = output.exp() / output.exp().sum1(&[-1], true, Float);
-        let labels_idx = &score.argmax(-1, true);
+        let (tokenized_input, input_tensor, score) = self.label_probabilities(input.as_ref());
+        let labels_idx = &self.decode_labels(&tokenized_input, &score);
         let mut tokens: Vec<Token> = vec![];
         for sentence_idx in 0..labels_idx.size()[0] {
             let labels = labels_idx.get(sentence_idx);
@@ -701,6 +921,188 @@ impl TokenClassificationModel {
         tokens
     }
 
+    // Label indices of shape (batch size, sequence length, 1), decoded with the CRF layer and under the label
+    // constraints if the model has them
+    fn decode_labels(&self, tokenized_input: &[TokenizedInput], score: &Tensor) -> Tensor {
+        if self.crf.is_none() && self.label_constraints.is_none() {
+            return score.argmax(-1, true);
+        }
+        let (batch_size, sequence_length, num_labels) = score.size3().unwrap();
+        let mut transition_scores = match &self.crf {
+            Some(crf) => crf.transition_scores(),
+            None => TransitionScores::zeros(num_labels as usize),
+        };
+        let strategy = match &self.label_constraints {
+            Some((strategy, constraints)) => {
+                transition_scores.add(constraints);
+                *strategy
+            }
+            None => ConstrainedDecodingStrategy::Viterbi,
+        };
+
+        let mut labels = Vec::with_capacity((batch_size * sequence_length) as usize);
+        for (sentence_idx, sentence_tokens) in tokenized_input.iter().enumerate() {
+            // The log-probabilities differ from the logits by a constant at each position, which does not change
+            // the best label sequence
+            let emissions = Vec::<f64>::from(
+                score
+                    .get(sentence_idx as i64)
+                    .narrow(0, 0, sentence_tokens.token_ids.len() as i64)
+                    .log()
+                    .to_kind(Double),
+            )
+            .chunks(num_labels as usize)
+            .map(<[f64]>::to_vec)
+            .collect::<Vec<Vec<f64>>>();
+            let sentence_labels = match strategy {
+                ConstrainedDecodingStrategy::Viterbi => {
+                    transition_scores.viterbi_decode(&emissions)
+                }
+                ConstrainedDecodingStrategy::Greedy => transition_scores.greedy_decode(&emissions),
+            };
+            labels.extend(sentence_labels.into_iter().map(|label| label as i64));
+            labels.resize((sentence_idx + 1) * sequence_length as usize, 0);
+        }
+        Tensor::of_slice(&labels).view((batch_size, sequence_length, 1))
+    }
+
+    // Runs the model, returning the tokenized input, the input ids and the label probabilities (on the CPU)
+    fn label_probabilities(&self, input: &[&str]) -> (Vec<TokenizedInput>, Tensor, Tensor) {
+        let (tokenized_input, input_batch) = self.prepare_for_model(input);
+        let input_tensor = input_batch.input_ids();
+        let output = no_grad(|| {
+            self.token_sequence_classifier.forward_t(
+                Some(input_batch.input_ids()),
+                None,
+                None,
+                None,
+                None,
+                false,
+            )
+        });
+        let output = output.detach().to(Device::Cpu);
+        let score: Tensor = output.exp() / output.exp().sum1(&[-1], true, Float);
+        (tokenized_input, input_tensor, score)
+    }
+
+    /// Classify the words of text sequences, aggregating the predictions of their sub-tokens. Special tokens are
+    /// skipped. Each word is returned with its text and its character and byte offsets in the input, so that it can be
+    /// highlighted or extracted without further alignment.
+    ///
+    /// # Arguments
+    ///
+    /// * `input` - `&[&str]` Array of texts to extract entities from.
+    /// * `strategy` - `WordAggregationStrategy` aggregating the label probabilities of the sub-tokens of each word
+    ///
+    /// # Returns
+    ///
+    /// * `Vec<Word>` containing the words of the input texts with their label
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// # fn main() -> anyhow::Result<()> {
+    /// # use rust_bert::pipelines::token_classification::{TokenClassificationModel, WordAggregationStrategy};
+    ///
+    /// let ner_model = TokenClassificationModel::new(Default::default())?;
+    /// let input = ["My name is Amélie. I live in Paris."];
+    /// let words = ner_model.predict_words(&input, WordAggregationStrategy::Average);
+    /// for word in words.iter().filter(|word| word.label != "O") {
+    ///     let (start, end) = word.byte_offset.unwrap();
+    ///     println!("{}: {} ({:.2})", word.label, &input[word.sentence][start..end], word.score);
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn predict_words<'a, S>(&self, input: S, strategy: WordAggregationStrategy) -> Vec<Word>
+    where
+        S: AsRef<[&'a str]>,
+    {
+        let (tokenized_input, input_tensor, score) = self.label_probabilities(input.as_ref());
+        let mut words: Vec<Word> = vec![];
+        for (sentence_idx, sentence_tokens) in tokenized_input.iter().enumerate() {
+            let text = input.as_ref()[sentence_idx];
+            let byte_positions = text
+                .char_indices()
+                .map(|(byte_position, _)| byte_position)
+                .chain(std::iter::once(text.len()))
+                .collect::<Vec<usize>>();
+            let sentence_scores = score.get(sentence_idx as i64);
+
+            let mut word_positions: Vec<Vec<usize>> = vec![];
+            for (position_idx, mask) in sentence_tokens.mask.iter().enumerate() {
+                match mask {
+                    Mask::Special => {}
+                    Mask::Continuation if !word_positions.is_empty() => {
+                        word_positions.last_mut().unwrap().push(position_idx)
+                    }
+                    _ => word_positions.push(vec![position_idx]),
+                }
+            }
+
+            for (word_index, positions) in word_positions.into_iter().enumerate() {
+                let probabilities = positions
+                    .iter()
+                    .map(|&position| {
+                        sentence_scores
+                            .get(position as i64)
+                            .iter::<f64>()
+                            .unwrap()
+                            .collect::<Vec<f64>>()
+                    })
+                    .collect::<Vec<Vec<f64>>>();
+                let (label_index, word_score) =
+                    aggregate_word_probabilities(&probabilities, strategy);
+                let offsets = positions
+                    .iter()
+                    .map(|&position| sentence_tokens.token_offsets[position])
+                    .collect::<Option<Vec<Offset>>>();
+                let offset = offsets.map(|offsets| {
+                    Offset::new(offsets.first().unwrap().begin, offsets.last().unwrap().end)
+                });
+                let byte_offset = offset.map(|offset| {
+                    let end = min(offset.end as usize, byte_positions.len() - 1);
+                    (
+                        byte_positions[min(offset.begin as usize, end)],
+                        byte_positions[end],
+                    )
+                });
+                let word_text = match byte_offset {
+                    Some((start, end)) => text[start..end].to_string(),
+                    None => self.tokenizer.decode(
+                        positions
+                            .iter()
+                            .map(|&position| {
+                                input_tensor.int64_value(&[sentence_idx as i64, position as i64])
+                            })
+                            .collect(),
+                        false,
+                        true,
+                    ),
+                };
+                words.push(Word {
+                    text: word_text,
+                    score: word_score,
+                    label: self
+                        .label_mapping
+                        .get(&(label_index as i64))
+                        .expect("Index out of vocabulary bounds.")
+                        .to_owned(),
+                    label_index: label_index as i64,
+                    sentence: sentence_idx,
+                    word_index: word_index as u16,
+                    offset,
+                    byte_offset,
+                    sub_token_scores: probabilities
+                        .iter()
+                        .map(|sub_token_probabilities| sub_token_probabilities[label_index])
+                        .collect(),
+                });
+            }
+        }
+        words
+    }
+
     fn decode_token(
         &self,
         original_sentence_chars: &[char],
@@ -854,4 +1256,15 @@ impl TokenClassificationModel {
             LabelAggregationOption::Custom(function) => function(tokens),
         }
     }
+
+    /// Reloads the weights of the token classification model from the resource provided, keeping the tokenizer and configuration
+    /// unchanged. The weights must match the architecture of the model currently loaded, the current weights
+    /// are kept if the new weights cannot be loaded.
+    ///
+    /// # Arguments
+    ///
+    /// * `weights_resource` - `Resource` pointing to the new model weights (e.g. model.ot)
+    pub fn reload_weights(&mut self, weights_resource: &Resource) -> Result<(), RustBertError> {
+        reload_var_store(&mut self.var_store, weights_resource)
+    }
 }
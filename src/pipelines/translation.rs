@@ -61,10 +61,20 @@ use crate::marian::{
     MarianVocabResources,
 };
 use crate::pipelines::common::ModelType;
+use crate::pipelines::custom_models::{CustomModelRegistry, CustomTextGenerator};
 use crate::pipelines::generation_utils::{
-    GenerateConfig, LanguageGenerator, MarianGenerator, T5Generator,
+    GenerateConfig, GenerationUsage, LanguageGenerator, MarianGenerator, PrefixAllowedTokensFn,
+    ScoredGeneration, StoppingCriteria, T5Generator,
 };
+use crate::pipelines::grammar::Grammar;
+use crate::pipelines::post_processing::{PostProcessor, PostProcessors};
+use crate::pipelines::shared_models::SharedModelRegistry;
+use crate::pipelines::stance_detection::split_sentences;
+use crate::pipelines::text_normalization::TextNormalizer;
 use crate::t5::{T5ConfigResources, T5ModelResources, T5Prefix, T5VocabResources};
+use lazy_static::lazy_static;
+use regex::Regex;
+use std::sync::Arc;
 use tch::{Device, Tensor};
 
 /// Pretrained languages available for direct use
@@ -279,18 +289,34 @@ pub struct TranslationConfig {
     pub min_length: i64,
     /// Maximum sequence length (default: 20)
     pub max_length: i64,
+    /// Maximum number of generated tokens, excluding the prompt. Takes precedence over `max_length` when set (default: None)
+    pub max_new_tokens: Option<i64>,
+    /// Minimum number of generated tokens, excluding the prompt. Takes precedence over `min_length` when set (default: None)
+    pub min_new_tokens: Option<i64>,
     /// Sampling flag. If true, will perform top-k and/or nucleus sampling on generated tokens, otherwise greedy (deterministic) decoding (default: true)
     pub do_sample: bool,
+    /// Seed of the sampling. When set, the sampled tokens are drawn from a generator seeded at each call instead of the `tch` random state, so that sampled outputs are reproducible across runs and threads for the same inputs (default: None)
+    pub seed: Option<u64>,
     /// Early stopping flag indicating if the beam search should stop as soon as `num_beam` hypotheses have been generated (default: false)
     pub early_stopping: bool,
     /// Number of beams for beam search (default: 5)
     pub num_beams: i64,
+    /// Number of groups of beams for [diverse beam search, Vijayakumar et al.](https://arxiv.org/abs/1610.02424). `num_beams` must be a multiple of the number of groups (default: 1)
+    pub num_beam_groups: i64,
+    /// Penalty subtracted from the scores of the tokens selected by the previous groups of beams at the same step, for diverse beam search (default: 0.0)
+    pub diversity_penalty: f64,
     /// Temperature setting. Values higher than 1 will improve originality at the risk of reducing relevance (default: 1.0)
     pub temperature: f64,
     /// Top_k values for sampling tokens. Value higher than 0 will enable the feature (default: 0)
     pub top_k: i64,
     /// Top_p value for [Nucleus sampling, Holtzman et al.](http://arxiv.org/abs/1904.09751). Keep top tokens until cumulative probability reaches top_p (default: 0.9)
     pub top_p: f64,
+    /// Probability mass of [typical decoding, Meister et al.](https://arxiv.org/abs/2202.00666): only the tokens with an information content closest to the entropy of the distribution are sampled, up to this cumulative probability. Values lower than 1 enable the feature (default: 1.0)
+    pub typical_p: f64,
+    /// Cutoff of [epsilon sampling, Hewitt et al.](https://arxiv.org/abs/2210.15191): tokens with a probability lower than the cutoff are not sampled. Values higher than 0 enable the feature, typically between 3e-4 and 9e-4 (default: 0.0)
+    pub epsilon_cutoff: f64,
+    /// Cutoff of [eta sampling, Hewitt et al.](https://arxiv.org/abs/2210.15191): tokens with a probability lower than `min(eta_cutoff, sqrt(eta_cutoff) * exp(-entropy))` are not sampled. Values higher than 0 enable the feature, typically between 3e-4 and 2e-3 (default: 0.0)
+    pub eta_cutoff: f64,
     /// Repetition penalty (mostly useful for CTRL decoders). Values higher than 1 will penalize tokens that have been already generated. (default: 1.0)
     pub repetition_penalty: f64,
     /// Exponential penalty based on the length of the hypotheses generated (default: 1.0)
@@ -299,10 +325,42 @@ pub struct TranslationConfig {
     pub no_repeat_ngram_size: i64,
     /// Number of sequences to return for each prompt text (default: 1)
     pub num_return_sequences: i64,
+    /// Token ids allowed at each generation step, restricting the vocabulary of the generated sequences (default: None)
+    pub allowed_token_ids: Option<Vec<i64>>,
+    /// Function returning the token ids allowed at the next step given the prompt index and the ids generated so far (default: None)
+    pub prefix_allowed_tokens_fn: Option<PrefixAllowedTokensFn>,
+    /// Criteria ending the generation of a sequence before the end of sequence token, e.g. `StopSequences` stopping on user-provided strings or token sequences (default: None)
+    pub stopping_criteria: Option<Arc<dyn StoppingCriteria>>,
+    /// Token sequences that must all appear in the generated sequences (e.g. the tokens of required terms), enforced by a constrained beam search (default: None)
+    pub force_words_ids: Option<Vec<Vec<i64>>>,
+    /// Token sequences that may not appear in the generated sequences (e.g. profanity or personal information markers): the last token of a sequence is banned whenever its previous tokens have just been generated (default: None)
+    pub bad_words_ids: Option<Vec<Vec<i64>>>,
+    /// Grammar the generated text must follow (e.g. `Grammar::from_json_schema` for structured outputs), enforced by masking the tokens that cannot extend the text into a valid prefix of the grammar (default: None)
+    pub grammar: Option<Arc<Grammar>>,
+    /// Bias added to the scores of the tokens present in the source (prompt) at each generation step, favouring copying from the input over hallucinated tokens (default: 0.0)
+    pub source_copy_bias: f64,
+    /// Size of the n-grams of the source (encoder input) that may not be copied in the generated sequences, for encoder-decoder models (default: 0)
+    pub encoder_no_repeat_ngram_size: i64,
+    /// Multiplier of the DRY ("don't repeat yourself") penalty applied to tokens extending a repetition of a previous sequence (default: 0.0, disabled)
+    pub dry_multiplier: f64,
+    /// Base of the DRY penalty, growing exponentially with the length of the repeated sequence (default: 1.75)
+    pub dry_base: f64,
+    /// Length of the repeated sequences not penalized by the DRY penalty (default: 2)
+    pub dry_allowed_length: i64,
+    /// Token ids interrupting the matching of repeated sequences for the DRY penalty, e.g. newlines or punctuation (default: None)
+    pub dry_sequence_breakers: Option<Vec<i64>>,
+    /// Remove the last token of the prompts and constrain the first generated token to start with it, for decoder-only models. Avoids starting the generation with a broken word when the prompt ends mid-token (default: false)
+    pub token_healing: bool,
     /// Device to place the model on (default: CUDA/GPU when available)
     pub device: Device,
     /// Prefix to append translation inputs with
     pub prefix: Option<String>,
+    /// Normalization (e.g. HTML entities decoding and white space collapsing) applied to the inputs before translation (default: None)
+    pub text_normalizer: Option<TextNormalizer>,
+    /// Translates the texts sentence by sentence, in batches of at most this number of sentences of similar lengths, and reassembles the translated sentences with the white spaces and list markers of the texts (default: None, each text is translated as a single sequence)
+    pub sentence_batch_size: Option<usize>,
+    /// Protects the inline markup of the texts (HTML or XML tags and placeholders such as `{name}` or `{{name}}`) by translating masks in their place, and restores the markup in the translations (default: false)
+    pub preserve_markup: bool,
     /// Model type used for translation
     pub model_type: ModelType,
 }
@@ -376,18 +434,42 @@ impl TranslationConfig {
             merges_resource,
             min_length: 0,
             max_length: 512,
+            max_new_tokens: None,
+            min_new_tokens: None,
             do_sample: false,
+            seed: None,
             early_stopping: true,
             num_beams: 6,
+            num_beam_groups: 1,
+            diversity_penalty: 0.0,
             temperature: 1.0,
             top_k: 50,
             top_p: 1.0,
+            typical_p: 1.0,
+            epsilon_cutoff: 0.0,
+            eta_cutoff: 0.0,
             repetition_penalty: 1.0,
             length_penalty: 1.0,
             no_repeat_ngram_size: 0,
             num_return_sequences: 1,
+            allowed_token_ids: None,
+            prefix_allowed_tokens_fn: None,
+            stopping_criteria: None,
+            force_words_ids: None,
+            bad_words_ids: None,
+            grammar: None,
+            source_copy_bias: 0.0,
+            encoder_no_repeat_ngram_size: 0,
+            dry_multiplier: 0.0,
+            dry_base: 1.75,
+            dry_allowed_length: 2,
+            dry_sequence_breakers: None,
+            token_healing: false,
             device,
             prefix,
+            text_normalizer: None,
+            sentence_batch_size: None,
+            preserve_markup: false,
             model_type: translation_resource.model_type,
         }
     }
@@ -453,18 +535,42 @@ impl TranslationConfig {
             merges_resource: sentence_piece_resource,
             min_length: 0,
             max_length: 512,
+            max_new_tokens: None,
+            min_new_tokens: None,
             do_sample: false,
+            seed: None,
             early_stopping: true,
             num_beams: 6,
+            num_beam_groups: 1,
+            diversity_penalty: 0.0,
             temperature: 1.0,
             top_k: 50,
             top_p: 1.0,
+            typical_p: 1.0,
+            epsilon_cutoff: 0.0,
+            eta_cutoff: 0.0,
             repetition_penalty: 1.0,
             length_penalty: 1.0,
             no_repeat_ngram_size: 0,
             num_return_sequences: 1,
+            allowed_token_ids: None,
+            prefix_allowed_tokens_fn: None,
+            stopping_criteria: None,
+            force_words_ids: None,
+            bad_words_ids: None,
+            grammar: None,
+            source_copy_bias: 0.0,
+            encoder_no_repeat_ngram_size: 0,
+            dry_multiplier: 0.0,
+            dry_base: 1.75,
+            dry_allowed_length: 2,
+            dry_sequence_breakers: None,
+            token_healing: false,
             device,
             prefix,
+            text_normalizer: None,
+            sentence_batch_size: None,
+            preserve_markup: false,
             model_type,
         }
     }
@@ -479,27 +585,281 @@ impl From<TranslationConfig> for GenerateConfig {
             vocab_resource: config.vocab_resource,
             min_length: config.min_length,
             max_length: config.max_length,
+            max_new_tokens: config.max_new_tokens,
+            min_new_tokens: config.min_new_tokens,
             do_sample: config.do_sample,
+            seed: config.seed,
             early_stopping: config.early_stopping,
             num_beams: config.num_beams,
+            num_beam_groups: config.num_beam_groups,
+            diversity_penalty: config.diversity_penalty,
             temperature: config.temperature,
             top_k: config.top_k,
             top_p: config.top_p,
+            typical_p: config.typical_p,
+            epsilon_cutoff: config.epsilon_cutoff,
+            eta_cutoff: config.eta_cutoff,
             repetition_penalty: config.repetition_penalty,
             length_penalty: config.length_penalty,
             no_repeat_ngram_size: config.no_repeat_ngram_size,
             num_return_sequences: config.num_return_sequences,
+            allowed_token_ids: config.allowed_token_ids,
+            prefix_allowed_tokens_fn: config.prefix_allowed_tokens_fn,
+            stopping_criteria: config.stopping_criteria,
+            force_words_ids: config.force_words_ids,
+            bad_words_ids: config.bad_words_ids,
+            grammar: config.grammar,
+            source_copy_bias: config.source_copy_bias,
+            encoder_no_repeat_ngram_size: config.encoder_no_repeat_ngram_size,
+            dry_multiplier: config.dry_multiplier,
+            dry_base: config.dry_base,
+            dry_allowed_length: config.dry_allowed_length,
+            dry_sequence_breakers: config.dry_sequence_breakers,
+            token_healing: config.token_healing,
+            echo_prompt: true,
+            bos_token_id: None,
+            eos_token_ids: None,
+            pad_token_id: None,
+            decoder_start_token_id: None,
+            cache_check_tolerance: None,
             device: config.device,
         }
     }
 }
 
+#[derive(Debug, Clone, Default)]
+/// # Terminology constraints for translation
+/// Maps source terms to the target terms required in their translation. Source terms are matched in the input texts
+/// case-insensitively and on word boundaries, a term contained in a longer matched term being ignored.
+pub struct Glossary {
+    entries: Vec<(String, String)>,
+}
+
+impl Glossary {
+    /// Creates a new empty glossary
+    pub fn new() -> Glossary {
+        Glossary { entries: vec![] }
+    }
+
+    /// Creates a glossary from (source term, target term) pairs
+    pub fn from_pairs(entries: &[(&str, &str)]) -> Glossary {
+        let mut glossary = Glossary::new();
+        for (source_term, target_term) in entries {
+            glossary.add(source_term, target_term);
+        }
+        glossary
+    }
+
+    /// Adds an entry to the glossary, replacing the target term of an existing source term
+    pub fn add(&mut self, source_term: &str, target_term: &str) {
+        let source_term = source_term.trim().to_lowercase();
+        match self
+            .entries
+            .iter_mut()
+            .find(|(existing, _)| *existing == source_term)
+        {
+            Some(entry) => entry.1 = target_term.trim().to_string(),
+            None => self
+                .entries
+                .push((source_term, target_term.trim().to_string())),
+        }
+    }
+
+    /// Returns the target terms required in the translation of a text, in order of first occurrence of their
+    /// source term
+    pub fn required_terms(&self, text: &str) -> Vec<&str> {
+        let text = text.to_lowercase();
+        let mut entries = self.entries.iter().collect::<Vec<&(String, String)>>();
+        entries.sort_by_key(|(source_term, _)| std::cmp::Reverse(source_term.len()));
+
+        let mut matches: Vec<(usize, usize, &str)> = vec![];
+        for (source_term, target_term) in entries {
+            if source_term.is_empty() || target_term.is_empty() {
+                continue;
+            }
+            for (start, _) in text.match_indices(source_term.as_str()) {
+                let end = start + source_term.len();
+                let is_word = !text[..start]
+                    .chars()
+                    .next_back()
+                    .map_or(false, char::is_alphanumeric)
+                    & !text[end..]
+                        .chars()
+                        .next()
+                        .map_or(false, char::is_alphanumeric);
+                let overlaps = matches
+                    .iter()
+                    .any(|&(other_start, other_end, _)| (start < other_end) & (other_start < end));
+                if is_word & !overlaps {
+                    matches.push((start, end, target_term.as_str()));
+                }
+            }
+        }
+        matches.sort_by_key(|&(start, _, _)| start);
+        let mut terms: Vec<&str> = vec![];
+        for (_, _, target_term) in matches {
+            if !terms.contains(&target_term) {
+                terms.push(target_term);
+            }
+        }
+        terms
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+/// # Translation with its confidence estimate
+/// The confidence is derived from the probabilities of the generated tokens under the translation model (no
+/// reference is needed): translations with a low confidence can be routed to a human review.
+pub struct ScoredTranslation {
+    /// Translated text
+    pub text: String,
+    /// Geometric mean of the probabilities of the generated tokens, between 0 and 1
+    pub confidence: f64,
+    /// Lowest probability of a generated token, between 0 and 1
+    pub min_token_probability: f64,
+    /// Log-probability of each generated token
+    pub token_scores: Vec<f64>,
+}
+
+impl ScoredTranslation {
+    /// Creates a scored translation from a generation scored by the translation model. Empty generations have a
+    /// confidence of 0.
+    pub fn from_scored_generation(generation: ScoredGeneration) -> ScoredTranslation {
+        let (confidence, min_token_probability) = if generation.token_scores.is_empty() {
+            (0.0, 0.0)
+        } else {
+            (
+                (generation.score / generation.token_scores.len() as f64).exp(),
+                generation
+                    .token_scores
+                    .iter()
+                    .cloned()
+                    .fold(f64::INFINITY, f64::min)
+                    .exp(),
+            )
+        };
+        ScoredTranslation {
+            text: generation.text,
+            confidence,
+            min_token_probability,
+            token_scores: generation.token_scores,
+        }
+    }
+
+    /// Returns true if the confidence of the translation is lower than the threshold provided
+    pub fn needs_review(&self, min_confidence: f64) -> bool {
+        self.confidence < min_confidence
+    }
+}
+
+lazy_static! {
+    static ref MARKUP_PATTERN: Regex =
+        Regex::new(r"</?[A-Za-z][^<>]*>|\{\{[^{}]*\}\}|\{[A-Za-z0-9_.]*\}").unwrap();
+    static ref MASK_PATTERN: Regex = Regex::new(r"\[\[\s*(\d+)\s*\]\]").unwrap();
+}
+
+#[derive(Debug, Clone)]
+/// # Text with its inline markup masked for translation
+/// Each HTML or XML tag and placeholder (`{name}`, `{{name}}`) of the text is replaced by a mask `[[i]]`, copied by
+/// translation models like numbers. The markup is restored in the place of the masks found in the translation, and
+/// the markup of the masks dropped by the model is inserted at the position of the translation aligned with its
+/// relative position in the source text.
+pub struct MaskedMarkup {
+    /// Text with the masks in place of the markup
+    pub text: String,
+    /// Masked markup, in order of appearance
+    pub markup: Vec<String>,
+    relative_positions: Vec<f64>,
+}
+
+impl MaskedMarkup {
+    /// Masks the markup of a text
+    pub fn new(text: &str) -> MaskedMarkup {
+        let mut masked_text = String::with_capacity(text.len());
+        let mut markup = vec![];
+        let mut positions = vec![];
+        let mut last_end = 0;
+        for markup_match in MARKUP_PATTERN.find_iter(text) {
+            masked_text.push_str(&text[last_end..markup_match.start()]);
+            positions.push(masked_text.chars().count());
+            masked_text.push_str(&format!("[[{}]]", markup.len()));
+            markup.push(markup_match.as_str().to_string());
+            last_end = markup_match.end();
+        }
+        masked_text.push_str(&text[last_end..]);
+        let text_length = masked_text.chars().count().max(1) as f64;
+        MaskedMarkup {
+            text: masked_text,
+            markup,
+            relative_positions: positions
+                .into_iter()
+                .map(|position| position as f64 / text_length)
+                .collect(),
+        }
+    }
+
+    /// Restores the markup in the translation of the masked text. Masks repeated by the model are removed and masks
+    /// that do not belong to the text are kept unchanged.
+    pub fn restore(&self, translation: &str) -> String {
+        let mut restored = String::with_capacity(translation.len());
+        let mut restored_markup = vec![false; self.markup.len()];
+        let mut last_end = 0;
+        for mask in MASK_PATTERN.captures_iter(translation) {
+            let mask_match = mask.get(0).unwrap();
+            restored.push_str(&translation[last_end..mask_match.start()]);
+            match mask[1].parse::<usize>() {
+                Ok(index) if index < self.markup.len() => {
+                    if !restored_markup[index] {
+                        restored.push_str(&self.markup[index]);
+                        restored_markup[index] = true;
+                    }
+                }
+                _ => restored.push_str(mask_match.as_str()),
+            }
+            last_end = mask_match.end();
+        }
+        restored.push_str(&translation[last_end..]);
+
+        //            Markup of the missing masks, inserted at the word boundary following their aligned position
+        let mut insertions = vec![];
+        let restored_length = restored.chars().count() as f64;
+        for (index, markup) in self.markup.iter().enumerate() {
+            if restored_markup[index] {
+                continue;
+            }
+            let target = (self.relative_positions[index] * restored_length).round() as usize;
+            //            Closing tags end the previous word, other markup starts the next word
+            let is_closing = markup.starts_with("</");
+            let position = restored
+                .char_indices()
+                .skip(target)
+                .find(|(_, c)| c.is_whitespace())
+                .map_or(restored.len(), |(position, c)| {
+                    if is_closing {
+                        position
+                    } else {
+                        position + c.len_utf8()
+                    }
+                });
+            let position = if target == 0 { 0 } else { position };
+            insertions.push((position, index, markup.as_str()));
+        }
+        insertions.sort_by_key(|&(position, index, _)| (position, index));
+        for (position, _, markup) in insertions.into_iter().rev() {
+            restored.insert_str(position, markup);
+        }
+        restored
+    }
+}
+
 /// # Abstraction that holds one particular translation model, for any of the supported models
 pub enum TranslationOption {
     /// Translator based on Marian model
     Marian(MarianGenerator),
     /// Translator based on T5 model
     T5(T5Generator),
+    /// Translator based on a model registered in a `CustomModelRegistry`
+    Custom(ModelType, Box<dyn CustomTextGenerator>),
 }
 
 impl TranslationOption {
@@ -516,11 +876,42 @@ impl TranslationOption {
         }
     }
 
+    /// Instantiate a new translation model, sharing the weights of a model already loaded in the registry if available.
+    pub fn new_with_registry(
+        config: TranslationConfig,
+        registry: &mut SharedModelRegistry,
+    ) -> Result<Self, RustBertError> {
+        match config.model_type {
+            ModelType::Marian => Ok(TranslationOption::Marian(
+                registry.marian_generator(config.into())?,
+            )),
+            ModelType::T5 => Ok(TranslationOption::T5(registry.t5_generator(config.into())?)),
+            _ => Err(RustBertError::InvalidConfigurationError(format!(
+                "Translation not implemented for {:?}!",
+                config.model_type
+            ))),
+        }
+    }
+
+    /// Instantiate a new translation model from a text generator registered in a `CustomModelRegistry`
+    pub fn new_with_custom_model(
+        config: TranslationConfig,
+        registry: &CustomModelRegistry,
+        name: &str,
+    ) -> Result<Self, RustBertError> {
+        let model_type = config.model_type;
+        Ok(TranslationOption::Custom(
+            model_type,
+            registry.build_text_generator(name, config.into())?,
+        ))
+    }
+
     /// Returns the `ModelType` for this TranslationOption
     pub fn model_type(&self) -> ModelType {
         match *self {
             Self::Marian(_) => ModelType::Marian,
             Self::T5(_) => ModelType::T5,
+            Self::Custom(model_type, _) => model_type,
         }
     }
 
@@ -538,6 +929,121 @@ impl TranslationOption {
                 model.generate(prompt_texts, attention_mask, None, None, None)
             }
             Self::T5(ref model) => model.generate(prompt_texts, attention_mask, None, None, None),
+            Self::Custom(_, ref model) => model.generate(
+                prompt_texts.as_ref().map(|texts| texts.as_ref()),
+                attention_mask,
+            ),
+        }
+    }
+
+    /// Interface method to generate_with_encoder_embeddings() of the particular models.
+    pub fn generate_with_encoder_embeddings<'a, S>(
+        &self,
+        prompt_texts: Option<S>,
+        attention_mask: Option<Tensor>,
+    ) -> Result<(Vec<String>, Tensor), RustBertError>
+    where
+        S: AsRef<[&'a str]>,
+    {
+        match *self {
+            Self::Marian(ref model) => model.generate_with_encoder_embeddings(
+                prompt_texts,
+                attention_mask,
+                None,
+                None,
+                None,
+            ),
+            Self::T5(ref model) => model.generate_with_encoder_embeddings(
+                prompt_texts,
+                attention_mask,
+                None,
+                None,
+                None,
+            ),
+            Self::Custom(_, ref model) => model.generate_with_encoder_embeddings(
+                prompt_texts.as_ref().map(|texts| texts.as_ref()),
+                attention_mask,
+            ),
+        }
+    }
+
+    /// Interface method to generate_with_forced_words() of the particular models.
+    pub fn generate_with_forced_words<'a, S>(
+        &self,
+        prompt_texts: Option<S>,
+        attention_mask: Option<Tensor>,
+        forced_words: &[&str],
+    ) -> Result<Vec<String>, RustBertError>
+    where
+        S: AsRef<[&'a str]>,
+    {
+        match *self {
+            Self::Marian(ref model) => {
+                model.generate_with_forced_words(prompt_texts, attention_mask, forced_words)
+            }
+            Self::T5(ref model) => {
+                model.generate_with_forced_words(prompt_texts, attention_mask, forced_words)
+            }
+            Self::Custom(_, ref model) => model.generate_with_forced_words(
+                prompt_texts.as_ref().map(|texts| texts.as_ref()),
+                attention_mask,
+                forced_words,
+            ),
+        }
+    }
+
+    /// Interface method to generate_with_scores() of the particular models.
+    pub fn generate_with_scores<'a, S>(
+        &self,
+        prompt_texts: Option<S>,
+        attention_mask: Option<Tensor>,
+    ) -> Vec<ScoredGeneration>
+    where
+        S: AsRef<[&'a str]>,
+    {
+        match *self {
+            Self::Marian(ref model) => {
+                model.generate_with_scores(prompt_texts, attention_mask, None, None, None)
+            }
+            Self::T5(ref model) => {
+                model.generate_with_scores(prompt_texts, attention_mask, None, None, None)
+            }
+            Self::Custom(_, ref model) => model.generate_with_scores(
+                prompt_texts.as_ref().map(|texts| texts.as_ref()),
+                attention_mask,
+            ),
+        }
+    }
+
+    /// Interface method to generate_with_usage() of the particular models.
+    pub fn generate_with_usage<'a, S>(
+        &self,
+        prompt_texts: Option<S>,
+        attention_mask: Option<Tensor>,
+    ) -> (Vec<String>, GenerationUsage)
+    where
+        S: AsRef<[&'a str]>,
+    {
+        match *self {
+            Self::Marian(ref model) => {
+                model.generate_with_usage(prompt_texts, attention_mask, None, None, None)
+            }
+            Self::T5(ref model) => {
+                model.generate_with_usage(prompt_texts, attention_mask, None, None, None)
+            }
+            Self::Custom(_, ref model) => model.generate_with_usage(
+                prompt_texts.as_ref().map(|texts| texts.as_ref()),
+                attention_mask,
+            ),
+        }
+    }
+
+    /// Interface method to reload_weights() of the particular models.
+    pub fn reload_weights(&mut self, weights_resource: &Resource) -> Result<(), RustBertError> {
+        match self {
+            Self::Marian(model_ref) => model_ref.reload_weights(weights_resource),
+            Self::T5(model_ref) => model_ref.reload_weights(weights_resource),
+            Self::Custom(_, model_ref) => model_ref.reload_weights(weights_resource),
         }
     }
 }
@@ -546,6 +1052,10 @@ impl TranslationOption {
 pub struct TranslationModel {
     model: TranslationOption,
     prefix: Option<String>,
+    text_normalizer: Option<TextNormalizer>,
+    sentence_batch_size: Option<usize>,
+    preserve_markup: bool,
+    post_processors: PostProcessors,
 }
 
 impl TranslationModel {
@@ -570,9 +1080,74 @@ impl TranslationModel {
     /// ```
     pub fn new(translation_config: TranslationConfig) -> Result<TranslationModel, RustBertError> {
         let prefix = translation_config.prefix.clone();
+        let text_normalizer = translation_config.text_normalizer.clone();
+        let sentence_batch_size = translation_config.sentence_batch_size;
+        let preserve_markup = translation_config.preserve_markup;
         let model = TranslationOption::new(translation_config)?;
 
-        Ok(TranslationModel { model, prefix })
+        Ok(TranslationModel {
+            model,
+            prefix,
+            text_normalizer,
+            sentence_batch_size,
+            preserve_markup,
+            post_processors: PostProcessors::new(),
+        })
+    }
+
+    /// Build a new `TranslationModel`, re-using the weights and tokenizer of a model already loaded in the registry
+    /// if available (e.g. when the same T5 model is used for summarization and translation).
+    ///
+    /// # Arguments
+    ///
+    /// * `translation_config` - `TranslationConfig` object containing the resource references (model, vocabulary, configuration), translation options and device placement (CPU/GPU)
+    /// * `registry` - `SharedModelRegistry` holding the models loaded so far
+    pub fn new_with_registry(
+        translation_config: TranslationConfig,
+        registry: &mut SharedModelRegistry,
+    ) -> Result<TranslationModel, RustBertError> {
+        let prefix = translation_config.prefix.clone();
+        let text_normalizer = translation_config.text_normalizer.clone();
+        let sentence_batch_size = translation_config.sentence_batch_size;
+        let preserve_markup = translation_config.preserve_markup;
+        let model = TranslationOption::new_with_registry(translation_config, registry)?;
+
+        Ok(TranslationModel {
+            model,
+            prefix,
+            text_normalizer,
+            sentence_batch_size,
+            preserve_markup,
+            post_processors: PostProcessors::new(),
+        })
+    }
+
+    /// Build a new `TranslationModel` from a text generator registered in a `CustomModelRegistry`
+    ///
+    /// # Arguments
+    ///
+    /// * `translation_config` - `TranslationConfig` object containing the resource references (model, vocabulary, configuration), translation options and device placement (CPU/GPU)
+    /// * `registry` - `CustomModelRegistry` holding the custom model constructors
+    /// * `name` - name the text generator was registered with
+    pub fn new_with_custom_model(
+        translation_config: TranslationConfig,
+        registry: &CustomModelRegistry,
+        name: &str,
+    ) -> Result<TranslationModel, RustBertError> {
+        let prefix = translation_config.prefix.clone();
+        let text_normalizer = translation_config.text_normalizer.clone();
+        let sentence_batch_size = translation_config.sentence_batch_size;
+        let preserve_markup = translation_config.preserve_markup;
+        let model = TranslationOption::new_with_custom_model(translation_config, registry, name)?;
+
+        Ok(TranslationModel {
+            model,
+            prefix,
+            text_normalizer,
+            sentence_batch_size,
+            preserve_markup,
+            post_processors: PostProcessors::new(),
+        })
     }
 
     /// Translates texts provided
@@ -606,22 +1181,388 @@ impl TranslationModel {
     where
         S: AsRef<[&'a str]>,
     {
-        match &self.prefix {
-            Some(value) => {
-                let texts = texts
-                    .as_ref()
+        let texts = texts.as_ref();
+        let translations = if self.preserve_markup {
+            let masked_texts = texts
+                .iter()
+                .map(|text| MaskedMarkup::new(text))
+                .collect::<Vec<MaskedMarkup>>();
+            let translations = self.translate_texts(
+                &masked_texts
                     .iter()
-                    .map(|&v| format!("{}{}", value, v))
-                    .collect::<Vec<String>>();
-                self.model.generate(
-                    Some(texts.iter().map(AsRef::as_ref).collect::<Vec<&str>>()),
+                    .map(|masked| masked.text.as_str())
+                    .collect::<Vec<&str>>(),
+            );
+            let translations_per_text = translations.len() / texts.len().max(1);
+            translations
+                .iter()
+                .enumerate()
+                .map(|(index, translation)| {
+                    masked_texts[index / translations_per_text.max(1)].restore(translation)
+                })
+                .collect()
+        } else {
+            self.translate_texts(texts)
+        };
+        self.post_processors.process_batch(translations)
+    }
+
+    // Translates the texts, sentence by sentence if sentence batching is configured, without post-processing
+    fn translate_texts(&self, texts: &[&str]) -> Vec<String> {
+        if let Some(sentence_batch_size) = self.sentence_batch_size {
+            return self.translate_by_sentences(texts, sentence_batch_size);
+        }
+        let texts = self.prepare_inputs(texts);
+        self.model.generate(
+            Some(texts.iter().map(AsRef::as_ref).collect::<Vec<&str>>()),
+            None,
+        )
+    }
+
+    // Translates the sentences of the texts in batches of sentences sorted by length, and reassembles the texts from
+    // the translated sentences and the untranslated segments (white spaces, list markers and segments without
+    // letters or digits). The first translation of each sentence is used if several sequences are returned.
+    fn translate_by_sentences(&self, texts: &[&str], batch_size: usize) -> Vec<String> {
+        let mut sentences: Vec<&str> = vec![];
+        let segmented_texts = texts
+            .iter()
+            .map(|text| segment_text(text, &mut sentences))
+            .collect::<Vec<Vec<TextSegment>>>();
+
+        let prepared_sentences = self.prepare_inputs(&sentences);
+        let mut sorted_indices = (0..sentences.len()).collect::<Vec<usize>>();
+        sorted_indices.sort_by_key(|&index| prepared_sentences[index].chars().count());
+        let mut translated_sentences = vec![String::new(); sentences.len()];
+        for batch_indices in sorted_indices.chunks(batch_size.max(1)) {
+            let batch = batch_indices
+                .iter()
+                .map(|&index| prepared_sentences[index].as_str())
+                .collect::<Vec<&str>>();
+            let translations = self.model.generate(Some(&batch), None);
+            let translations_per_sentence = translations.len() / batch.len();
+            for (&index, translation) in batch_indices.iter().zip(
+                translations
+                    .into_iter()
+                    .step_by(translations_per_sentence.max(1)),
+            ) {
+                translated_sentences[index] = translation.trim().to_string();
+            }
+        }
+
+        segmented_texts
+            .into_iter()
+            .map(|segments| {
+                segments
+                    .into_iter()
+                    .map(|segment| match segment {
+                        TextSegment::Verbatim(text) => text,
+                        TextSegment::Sentence(index) => translated_sentences[index].as_str(),
+                    })
+                    .collect::<String>()
+            })
+            .collect()
+    }
+
+    /// Translates texts provided, and returns the encoder embeddings of each input (mean of the encoder hidden states
+    /// over the input tokens) computed during the translation.
+    ///
+    /// # Arguments
+    ///
+    /// * `input` - `&[&str]` Array of texts to translate.
+    ///
+    /// # Returns
+    /// * `Vec<String>` Translated texts
+    /// * `Tensor` of shape (*number of texts*, *hidden_size*) with the pooled encoder embeddings of each input
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// # fn main() -> anyhow::Result<()> {
+    /// use rust_bert::pipelines::translation::{Language, TranslationConfig, TranslationModel};
+    /// use tch::Device;
+    ///
+    /// let translation_config =
+    ///     TranslationConfig::new(Language::EnglishToFrench, Device::cuda_if_available());
+    /// let model = TranslationModel::new(translation_config)?;
+    ///
+    /// let input = ["This is a sentence to be translated"];
+    ///
+    /// let (translations, encoder_embeddings) = model.translate_with_encoder_embeddings(&input)?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn translate_with_encoder_embeddings<'a, S>(
+        &self,
+        texts: S,
+    ) -> Result<(Vec<String>, Tensor), RustBertError>
+    where
+        S: AsRef<[&'a str]>,
+    {
+        let texts = self.prepare_inputs(texts.as_ref());
+        let (translations, encoder_embeddings) = self.model.generate_with_encoder_embeddings(
+            Some(texts.iter().map(AsRef::as_ref).collect::<Vec<&str>>()),
+            None,
+        )?;
+        Ok((
+            self.post_processors.process_batch(translations),
+            encoder_embeddings,
+        ))
+    }
+
+    /// Translates texts provided, and returns the usage statistics of the request (number of input and generated
+    /// tokens, generation time and throughput)
+    ///
+    /// # Arguments
+    ///
+    /// * `input` - `&[&str]` Array of texts to translate.
+    ///
+    /// # Returns
+    /// * `Vec<String>` Translated texts
+    /// * `GenerationUsage` usage statistics of the request
+    pub fn translate_with_usage<'a, S>(&self, texts: S) -> (Vec<String>, GenerationUsage)
+    where
+        S: AsRef<[&'a str]>,
+    {
+        let texts = self.prepare_inputs(texts.as_ref());
+        let (translations, usage) = self.model.generate_with_usage(
+            Some(texts.iter().map(AsRef::as_ref).collect::<Vec<&str>>()),
+            None,
+        );
+        (self.post_processors.process_batch(translations), usage)
+    }
+
+    /// Translates texts provided, enforcing the terminology of a glossary: the translation of a text containing a
+    /// source term of the glossary must contain the corresponding target term. The target terms are enforced by a
+    /// constrained beam search (the configuration must use `num_beams` > 1 without sampling), the texts with
+    /// glossary terms being translated one at a time.
+    ///
+    /// # Arguments
+    ///
+    /// * `input` - `&[&str]` Array of texts to translate.
+    /// * `glossary` - `Glossary` mapping the source terms to their required translation
+    ///
+    /// # Returns
+    /// * `Vec<String>` Translated texts
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// # fn main() -> anyhow::Result<()> {
+    /// use rust_bert::pipelines::translation::{
+    ///     Glossary, Language, TranslationConfig, TranslationModel,
+    /// };
+    /// use tch::Device;
+    ///
+    /// let translation_config =
+    ///     TranslationConfig::new(Language::EnglishToFrench, Device::cuda_if_available());
+    /// let model = TranslationModel::new(translation_config)?;
+    /// let glossary = Glossary::from_pairs(&[("purchase order", "bon de commande")]);
+    ///
+    /// let output = model.translate_with_glossary(&["Please send the purchase order."], &glossary)?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn translate_with_glossary<'a, S>(
+        &self,
+        texts: S,
+        glossary: &Glossary,
+    ) -> Result<Vec<String>, RustBertError>
+    where
+        S: AsRef<[&'a str]>,
+    {
+        let texts = texts.as_ref();
+        let prepared_texts = self.prepare_inputs(texts);
+        let required_terms = texts
+            .iter()
+            .map(|text| glossary.required_terms(text))
+            .collect::<Vec<Vec<&str>>>();
+
+        let unconstrained_texts = prepared_texts
+            .iter()
+            .zip(required_terms.iter())
+            .filter(|(_, terms)| terms.is_empty())
+            .map(|(text, _)| text.as_str())
+            .collect::<Vec<&str>>();
+        let mut unconstrained_translations = if unconstrained_texts.is_empty() {
+            vec![]
+        } else {
+            self.model.generate(Some(&unconstrained_texts), None)
+        }
+        .into_iter();
+        let translations_per_text = if unconstrained_texts.is_empty() {
+            0
+        } else {
+            unconstrained_translations.len() / unconstrained_texts.len()
+        };
+
+        let mut translations = vec![];
+        for (text, terms) in prepared_texts.iter().zip(required_terms.iter()) {
+            if terms.is_empty() {
+                translations.extend(
+                    unconstrained_translations
+                        .by_ref()
+                        .take(translations_per_text),
+                );
+            } else {
+                translations.extend(self.model.generate_with_forced_words(
+                    Some(&[text.as_str()]),
                     None,
-                )
+                    terms,
+                )?);
             }
-            None => self.model.generate(Some(texts), None),
         }
+        Ok(self.post_processors.process_batch(translations))
     }
+
+    /// Translates texts provided, and returns a confidence estimate of each translation from the probabilities of its
+    /// tokens under the model
+    ///
+    /// # Arguments
+    ///
+    /// * `input` - `&[&str]` Array of texts to translate.
+    ///
+    /// # Returns
+    /// * `Vec<ScoredTranslation>` Translated texts with their confidence
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// # fn main() -> anyhow::Result<()> {
+    /// use rust_bert::pipelines::translation::{Language, TranslationConfig, TranslationModel};
+    /// use tch::Device;
+    ///
+    /// let translation_config =
+    ///     TranslationConfig::new(Language::EnglishToFrench, Device::cuda_if_available());
+    /// let model = TranslationModel::new(translation_config)?;
+    ///
+    /// for translation in model.translate_with_confidence(&["This is a sentence to be translated"]) {
+    ///     if translation.needs_review(0.5) {
+    ///         println!("Low confidence ({:.2}): {}", translation.confidence, translation.text);
+    ///     }
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn translate_with_confidence<'a, S>(&self, texts: S) -> Vec<ScoredTranslation>
+    where
+        S: AsRef<[&'a str]>,
+    {
+        let texts = self.prepare_inputs(texts.as_ref());
+        self.model
+            .generate_with_scores(
+                Some(texts.iter().map(AsRef::as_ref).collect::<Vec<&str>>()),
+                None,
+            )
+            .into_iter()
+            .map(|generation| {
+                let mut translation = ScoredTranslation::from_scored_generation(generation);
+                translation.text = self.post_processors.process(translation.text);
+                translation
+            })
+            .collect()
+    }
+
+    /// Registers a post-processor applied to the generated translations, after the post-processors registered so far
+    ///
+    /// # Arguments
+    ///
+    /// * `post_processor` - `PostProcessor` to apply (e.g. a `RegexReplacer` or a closure)
+    pub fn add_post_processor<P: PostProcessor + 'static>(&mut self, post_processor: P) {
+        self.post_processors.add(post_processor);
+    }
+
+    // Normalizes the inputs (if a text normalizer is configured) and prepends the language prefix
+    fn prepare_inputs(&self, texts: &[&str]) -> Vec<String> {
+        texts
+            .iter()
+            .map(|&text| {
+                let text = match &self.text_normalizer {
+                    Some(text_normalizer) => text_normalizer.normalize(text),
+                    None => text.to_string(),
+                };
+                match &self.prefix {
+                    Some(prefix) => format!("{}{}", prefix, text),
+                    None => text,
+                }
+            })
+            .collect::<Vec<String>>()
+    }
+
+    /// Reloads the weights of the translation model from the resource provided, keeping the tokenizer and configuration
+    /// unchanged. The weights must match the architecture of the model currently loaded.
+    ///
+    /// # Arguments
+    ///
+    /// * `weights_resource` - `Resource` pointing to the new model weights (e.g. model.ot)
+    pub fn reload_weights(&mut self, weights_resource: &Resource) -> Result<(), RustBertError> {
+        self.model.reload_weights(weights_resource)
+    }
+}
+
+enum TextSegment<'a> {
+    Verbatim(&'a str),
+    Sentence(usize),
+}
+
+// Splits a text into sentences (appended to `sentences` and referred to by their index) and segments kept verbatim:
+// line breaks, white spaces between sentences, list markers starting a line and segments without letters or digits
+fn segment_text<'a>(text: &'a str, sentences: &mut Vec<&'a str>) -> Vec<TextSegment<'a>> {
+    let mut segments = vec![];
+    let mut line_start = 0;
+    while line_start < text.len() {
+        let line_end = text[line_start..]
+            .find('\n')
+            .map_or(text.len(), |position| line_start + position + 1);
+        let line = &text[line_start..line_end];
+        let content = line.trim_start();
+        let body_start = line_start + line.len() - content.len() + list_marker_length(content);
+        let mut position = line_start;
+        for (start, end) in split_sentences(&text[body_start..line_end]) {
+            let (start, end) = (body_start + start, body_start + end);
+            if position < start {
+                segments.push(TextSegment::Verbatim(&text[position..start]));
+            }
+            let sentence = &text[start..end];
+            if sentence.chars().any(char::is_alphanumeric) {
+                segments.push(TextSegment::Sentence(sentences.len()));
+                sentences.push(sentence);
+            } else {
+                segments.push(TextSegment::Verbatim(sentence));
+            }
+            position = end;
+        }
+        if position < line_end {
+            segments.push(TextSegment::Verbatim(&text[position..line_end]));
+        }
+        line_start = line_end;
+    }
+    segments
+}
+
+// Byte length of the list or heading marker starting a line (e.g. `- `, `* `, `## ` or `2. `), including the
+// following white spaces
+fn list_marker_length(line: &str) -> usize {
+    let symbols_length = line.len()
+        - line
+            .trim_start_matches(|c: char| matches!(c, '-' | '*' | '•' | '#'))
+            .len();
+    let marker_length = if symbols_length > 0 {
+        symbols_length
+    } else {
+        let digits_length =
+            line.len() - line.trim_start_matches(|c: char| c.is_ascii_digit()).len();
+        match line[digits_length..].chars().next() {
+            Some('.') | Some(')') if digits_length > 0 => digits_length + 1,
+            _ => 0,
+        }
+    };
+    let rest = &line[marker_length..];
+    if (marker_length == 0) | !rest.starts_with(char::is_whitespace) {
+        return 0;
+    }
+    marker_length + rest.len() - rest.trim_start().len()
 }
+
 #[cfg(test)]
 mod test {
     use super::*;
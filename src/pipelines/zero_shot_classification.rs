@@ -21,6 +21,25 @@
 //! - `predict` performs single-class classification (one and exactly one label must be true for each provided input)
 //! - `predict_multilabel` performs multi-label classification (zero, one or more labels may be true for each provided input)
 //!
+//! Inputs in other languages can be classified with a multilingual model fine-tuned on XNLI.
+//! `ZeroShotClassificationConfig::multilingual` loads a XLM-RoBERTa model fine-tuned on XNLI, with the default template
+//! translated to the language of the inputs (see `hypothesis_template` for the available languages). A custom default
+//! template can also be set with the `hypothesis_template` field of the configuration.
+//!
+//! ```no_run
+//! # use rust_bert::pipelines::zero_shot_classification::{ZeroShotClassificationConfig, ZeroShotClassificationModel};
+//! # fn main() -> anyhow::Result<()> {
+//! let model = ZeroShotClassificationModel::new(ZeroShotClassificationConfig::multilingual("fr")?)?;
+//! let output = model.predict(
+//!     &["Le Premier ministre a annoncé un plan de relance."],
+//!     &["politique", "santé", "sport"],
+//!     None,
+//!     128,
+//! );
+//! # Ok(())
+//! # }
+//! ```
+//!
 //! ```no_run
 //! # use rust_bert::pipelines::zero_shot_classification::ZeroShotClassificationModel;
 //! # fn main() -> anyhow::Result<()> {
@@ -104,12 +123,16 @@ use crate::bart::{
     BartVocabResources,
 };
 use crate::bert::BertForSequenceClassification;
+use crate::common::weights::reload_var_store;
 use crate::distilbert::DistilBertModelClassifier;
 use crate::mobilebert::MobileBertForSequenceClassification;
-use crate::pipelines::common::{ConfigOption, ModelType, TokenizerOption};
+use crate::pipelines::common::{ConfigOption, ModelType, TensorBatch, TokenizerOption};
 use crate::pipelines::sequence_classification::Label;
 use crate::resources::{RemoteResource, Resource};
-use crate::roberta::RobertaForSequenceClassification;
+use crate::roberta::{
+    RobertaConfigResources, RobertaForSequenceClassification, RobertaModelResources,
+    RobertaVocabResources,
+};
 use crate::xlnet::XLNetForSequenceClassification;
 use crate::RustBertError;
 use itertools::Itertools;
@@ -139,8 +162,13 @@ pub struct ZeroShotClassificationConfig {
     pub strip_accents: Option<bool>,
     /// Flag indicating if the tokenizer should add a white space before each tokenized input (needed for some Roberta models)
     pub add_prefix_space: Option<bool>,
+    /// Template of the hypotheses used when no template is passed to the predictions, with `{}` replaced by the label (default: None, `This example is about {}.`)
+    pub hypothesis_template: Option<String>,
     /// Device to place the model on (default: CUDA/GPU when available)
     pub device: Device,
+    /// Copy the inputs to the device through pinned host memory, asynchronously (default: false). Only used for
+    /// CUDA devices, see `pipelines::common::to_device`
+    pub pinned_memory: bool,
 }
 
 impl ZeroShotClassificationConfig {
@@ -173,9 +201,80 @@ impl ZeroShotClassificationConfig {
             lower_case,
             strip_accents: strip_accents.into(),
             add_prefix_space: add_prefix_space.into(),
+            hypothesis_template: None,
             device: Device::cuda_if_available(),
+            pinned_memory: false,
         }
     }
+
+    /// Configuration of a multilingual XLM-RoBERTa model fine-tuned on XNLI, using the default hypothesis template of
+    /// the language of the inputs.
+    ///
+    /// # Arguments
+    ///
+    /// * `language` - ISO 639-1 code of the language of the inputs and labels (e.g. `fr`), see `hypothesis_template`
+    pub fn multilingual(language: &str) -> Result<ZeroShotClassificationConfig, RustBertError> {
+        let hypothesis_template = hypothesis_template(language).ok_or_else(|| {
+            RustBertError::InvalidConfigurationError(format!(
+                "No default hypothesis template for the language `{}`, set `hypothesis_template` instead",
+                language
+            ))
+        })?;
+        Ok(ZeroShotClassificationConfig {
+            hypothesis_template: Some(hypothesis_template.to_string()),
+            ..ZeroShotClassificationConfig::new(
+                ModelType::XLMRoberta,
+                Resource::Remote(RemoteResource::from_pretrained(
+                    RobertaModelResources::XLM_ROBERTA_LARGE_XNLI,
+                )),
+                Resource::Remote(RemoteResource::from_pretrained(
+                    RobertaConfigResources::XLM_ROBERTA_LARGE_XNLI,
+                )),
+                Resource::Remote(RemoteResource::from_pretrained(
+                    RobertaVocabResources::XLM_ROBERTA_LARGE_XNLI,
+                )),
+                None,
+                false,
+                None,
+                None,
+            )
+        })
+    }
+}
+
+const HYPOTHESIS_TEMPLATES: [(&str, &str); 18] = [
+    ("ar", "هذا المثال عن {}."),
+    ("bg", "Този пример е за {}."),
+    ("de", "Dieses Beispiel handelt von {}."),
+    ("el", "Αυτό το παράδειγμα αφορά {}."),
+    ("en", "This example is about {}."),
+    ("es", "Este ejemplo trata sobre {}."),
+    ("fr", "Cet exemple parle de {}."),
+    ("hi", "यह उदाहरण {} के बारे में है।"),
+    ("it", "Questo esempio riguarda {}."),
+    ("nl", "Dit voorbeeld gaat over {}."),
+    ("pt", "Este exemplo é sobre {}."),
+    ("ru", "Этот пример на тему: {}."),
+    ("sw", "Mfano huu unahusu {}."),
+    ("th", "ตัวอย่างนี้เกี่ยวกับ{}"),
+    ("tr", "Bu örnek {} hakkındadır."),
+    ("ur", "یہ مثال {} کے بارے میں ہے۔"),
+    ("vi", "Ví dụ này nói về {}."),
+    ("zh", "这个例子是关于{}的。"),
+];
+
+/// Returns the default hypothesis template of a language, with `{}` standing for the label. Templates are available
+/// for the XNLI languages (`ar`, `bg`, `de`, `el`, `en`, `es`, `fr`, `hi`, `ru`, `sw`, `th`, `tr`, `ur`, `vi`, `zh`)
+/// as well as `it`, `nl` and `pt`.
+///
+/// # Arguments
+///
+/// * `language` - ISO 639-1 code of the language
+pub fn hypothesis_template(language: &str) -> Option<&'static str> {
+    HYPOTHESIS_TEMPLATES
+        .iter()
+        .find(|(code, _)| code.eq_ignore_ascii_case(language))
+        .map(|(_, template)| *template)
 }
 
 impl Default for ZeroShotClassificationConfig {
@@ -198,7 +297,9 @@ impl Default for ZeroShotClassificationConfig {
             lower_case: false,
             strip_accents: None,
             add_prefix_space: None,
+            hypothesis_template: None,
             device: Device::cuda_if_available(),
+            pinned_memory: false,
         }
     }
 }
@@ -454,6 +555,8 @@ pub struct ZeroShotClassificationModel {
     tokenizer: TokenizerOption,
     zero_shot_classifier: ZeroShotClassificationOption,
     var_store: VarStore,
+    hypothesis_template: String,
+    pinned_memory: bool,
 }
 
 impl ZeroShotClassificationModel {
@@ -476,6 +579,15 @@ impl ZeroShotClassificationModel {
     pub fn new(
         config: ZeroShotClassificationConfig,
     ) -> Result<ZeroShotClassificationModel, RustBertError> {
+        let hypothesis_template = config
+            .hypothesis_template
+            .unwrap_or_else(|| "This example is about {}.".to_string());
+        if !hypothesis_template.contains("{}") {
+            return Err(RustBertError::InvalidConfigurationError(format!(
+                "The hypothesis template `{}` must contain a `{{}}` placeholder for the label",
+                hypothesis_template
+            )));
+        }
         let config_path = config.config_resource.get_local_path()?;
         let vocab_path = config.vocab_resource.get_local_path()?;
         let weights_path = config.model_resource.get_local_path()?;
@@ -495,7 +607,8 @@ impl ZeroShotClassificationModel {
             config.add_prefix_space,
         )?;
         let mut var_store = VarStore::new(device);
-        let model_config = ConfigOption::from_file(config.model_type, config_path);
+        let model_config = ConfigOption::try_from_file(config.model_type, config_path)?;
+        tokenizer.check_model_compatibility(config.model_type, model_config.get_vocab_size())?;
         let zero_shot_classifier =
             ZeroShotClassificationOption::new(config.model_type, &var_store.root(), &model_config)?;
         var_store.load(weights_path)?;
@@ -503,6 +616,8 @@ impl ZeroShotClassificationModel {
             tokenizer,
             zero_shot_classifier,
             var_store,
+            hypothesis_template,
+            pinned_memory: config.pinned_memory,
         })
     }
 
@@ -512,7 +627,7 @@ impl ZeroShotClassificationModel {
         labels: T,
         template: Option<Box<dyn Fn(&str) -> String>>,
         max_len: usize,
-    ) -> (Tensor, Tensor)
+    ) -> TensorBatch
     where
         S: AsRef<[&'a str]>,
         T: AsRef<[&'a str]>,
@@ -526,7 +641,7 @@ impl ZeroShotClassificationModel {
             None => labels
                 .as_ref()
                 .iter()
-                .map(|label| format!("This example is about {}.", label))
+                .map(|label| self.hypothesis_template.replace("{}", label))
                 .collect(),
         };
 
@@ -543,35 +658,16 @@ impl ZeroShotClassificationModel {
             &TruncationStrategy::LongestFirst,
             0,
         );
-        let max_len = tokenized_input
-            .iter()
-            .map(|input| input.token_ids.len())
-            .max()
-            .unwrap();
-        let tokenized_input_tensors: Vec<tch::Tensor> =
-            tokenized_input
-                .iter()
-                .map(|input| input.token_ids.clone())
-                .map(|mut input| {
-                    input.extend(vec![self.tokenizer.get_pad_id().expect(
-                        "The Tokenizer used for zero shot classification should contain a PAD id"
-                    ); max_len - input.len()]);
-                    input
-                })
-                .map(|input| Tensor::of_slice(&(input)))
-                .collect::<Vec<_>>();
-
-        let tokenized_input_tensors =
-            Tensor::stack(tokenized_input_tensors.as_slice(), 0).to(self.var_store.device());
-
-        let mask = tokenized_input_tensors
-            .ne(self
-                .tokenizer
-                .get_pad_id()
-                .expect("The Tokenizer used for zero shot classification should contain a PAD id"))
-            .to_kind(Bool);
-
-        (tokenized_input_tensors, mask)
+        let token_ids = tokenized_input
+            .into_iter()
+            .map(|input| input.token_ids)
+            .collect::<Vec<Vec<i64>>>();
+        let pad_id = self
+            .tokenizer
+            .get_pad_id()
+            .expect("The Tokenizer used for zero shot classification should contain a PAD id");
+        TensorBatch::from_token_ids(&token_ids, pad_id, self.var_store.device())
+            .with_pinned_memory(self.pinned_memory)
     }
 
     /// Zero shot classification with 1 (and exactly 1) true label.
@@ -580,7 +676,7 @@ impl ZeroShotClassificationModel {
     ///
     /// * `input` - `&[&str]` Array of texts to classify.
     /// * `labels` - `&[&str]` Possible labels for the inputs.
-    /// * `template` - `Option<Box<dyn Fn(&str) -> String>>` closure to build label propositions. If None, will default to `"This example is {}."`.
+    /// * `template` - `Option<Box<dyn Fn(&str) -> String>>` closure to build label propositions. If None, will default to the `hypothesis_template` of the configuration (`"This example is about {}."` unless set).
     /// * `max_length` -`usize` Maximum sequence length for the inputs. If needed, the input sequence will be truncated before the label template.
     ///
     /// # Returns
@@ -640,12 +736,12 @@ impl ZeroShotClassificationModel {
         T: AsRef<[&'a str]>,
     {
         let num_inputs = inputs.as_ref().len();
-        let (input_tensor, mask) =
+        let input_batch =
             self.prepare_for_model(inputs.as_ref(), labels.as_ref(), template, max_length);
         let output = no_grad(|| {
             let output = self.zero_shot_classifier.forward_t(
-                Some(input_tensor),
-                Some(mask),
+                Some(input_batch.input_ids()),
+                input_batch.attention_mask_as(Bool),
                 None,
                 None,
                 None,
@@ -682,7 +778,7 @@ impl ZeroShotClassificationModel {
     ///
     /// * `input` - `&[&str]` Array of texts to classify.
     /// * `labels` - `&[&str]` Possible labels for the inputs.
-    /// * `template` - `Option<Box<dyn Fn(&str) -> String>>` closure to build label propositions. If None, will default to `"This example is about {}."`.
+    /// * `template` - `Option<Box<dyn Fn(&str) -> String>>` closure to build label propositions. If None, will default to the `hypothesis_template` of the configuration (`"This example is about {}."` unless set).
     /// * `max_length` -`usize` Maximum sequence length for the inputs. If needed, the input sequence will be truncated before the label template.
     ///
     /// # Returns
@@ -781,12 +877,12 @@ impl ZeroShotClassificationModel {
         T: AsRef<[&'a str]>,
     {
         let num_inputs = inputs.as_ref().len();
-        let (input_tensor, mask) =
+        let input_batch =
             self.prepare_for_model(inputs.as_ref(), labels.as_ref(), template, max_length);
         let output = no_grad(|| {
             let output = self.zero_shot_classifier.forward_t(
-                Some(input_tensor),
-                Some(mask),
+                Some(input_batch.input_ids()),
+                input_batch.attention_mask_as(Bool),
                 None,
                 None,
                 None,
@@ -819,6 +915,17 @@ impl ZeroShotClassificationModel {
         }
         output_labels
     }
+
+    /// Reloads the weights of the zero-shot classification model from the resource provided, keeping the tokenizer and configuration
+    /// unchanged. The weights must match the architecture of the model currently loaded, the current weights
+    /// are kept if the new weights cannot be loaded.
+    ///
+    /// # Arguments
+    ///
+    /// * `weights_resource` - `Resource` pointing to the new model weights (e.g. model.ot)
+    pub fn reload_weights(&mut self, weights_resource: &Resource) -> Result<(), RustBertError> {
+        reload_var_store(&mut self.var_store, weights_resource)
+    }
 }
 #[cfg(test)]
 mod test {
@@ -12,7 +12,9 @@
 // limitations under the License.
 
 use crate::common::activations::Activation;
+use crate::common::config::{check_positive, check_token_id, deserialize_id2label};
 use crate::common::dropout::Dropout;
+use crate::common::model_output::{tensor_refs, ModelOutput};
 use crate::pipelines::generation_utils::{Cache, LMHeadModel, LMModelOutput};
 use crate::reformer::attention::{AttentionType, LayerState};
 use crate::reformer::attention_utils::{get_least_common_mult_chunk_len, get_min_chunk_len};
@@ -20,7 +22,7 @@ use crate::reformer::embeddings::ReformerEmbeddings;
 use crate::reformer::encoder::{ReformerEncoder, ReformerModelOutput};
 use crate::{Config, RustBertError};
 use serde::{Deserialize, Serialize};
-use serde_json::Value;
+use serde_json::{Map, Value};
 use std::borrow::Borrow;
 use std::collections::HashMap;
 use tch::{nn, Device, Kind, Tensor};
@@ -97,13 +99,37 @@ pub struct ReformerConfig {
     pub num_hashes: i64,
     pub num_hidden_layers: i64,
     pub use_cache: Option<bool>,
+    #[serde(default, deserialize_with = "deserialize_id2label")]
     pub id2label: Option<HashMap<i64, String>>,
     pub label2id: Option<HashMap<String, i64>>,
     pub output_attentions: Option<bool>,
     pub output_hidden_states: Option<bool>,
+    /// Additional fields of the configuration file not used by the model
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
 }
 
-impl Config<ReformerConfig> for ReformerConfig {}
+impl Config<ReformerConfig> for ReformerConfig {
+    fn extra_fields(&self) -> Option<&Map<String, Value>> {
+        Some(&self.extra)
+    }
+
+    fn validate(&self) -> Result<(), RustBertError> {
+        check_positive("num_attention_heads", self.num_attention_heads)?;
+        check_positive("attention_head_size", self.attention_head_size)?;
+        if self.axial_pos_embds {
+            let axial_pos_embds_dim = self.axial_pos_embds_dim.iter().sum::<i64>();
+            if axial_pos_embds_dim != self.hidden_size {
+                return Err(RustBertError::InvalidConfigurationError(format!(
+                    "The sum of `axial_pos_embds_dim` ({}) must be equal to `hidden_size` ({})",
+                    axial_pos_embds_dim, self.hidden_size
+                )));
+            }
+        }
+        check_token_id("eos_token_id", Some(self.eos_token_id), self.vocab_size)?;
+        check_token_id("pad_token_id", Some(self.pad_token_id), self.vocab_size)
+    }
+}
 
 pub struct ReformerLMHead {
     decoder: nn::Linear,
@@ -984,6 +1010,20 @@ pub struct ReformerLMModelOutput {
     pub next_cache: Option<Vec<Option<LayerState>>>,
 }
 
+impl ModelOutput for ReformerLMModelOutput {
+    fn hidden_states(&self) -> Option<Vec<&Tensor>> {
+        tensor_refs(&self.all_hidden_states)
+    }
+
+    fn attentions(&self) -> Option<Vec<&Tensor>> {
+        tensor_refs(&self.all_attentions)
+    }
+
+    fn logits(&self) -> Option<&Tensor> {
+        Some(&self.logits)
+    }
+}
+
 ///Container holding a Reformer model with classification head
 pub struct ReformerClassificationOutput {
     /// logits
@@ -994,6 +1034,20 @@ pub struct ReformerClassificationOutput {
     pub all_attentions: Option<Vec<Tensor>>,
 }
 
+impl ModelOutput for ReformerClassificationOutput {
+    fn hidden_states(&self) -> Option<Vec<&Tensor>> {
+        tensor_refs(&self.all_hidden_states)
+    }
+
+    fn attentions(&self) -> Option<Vec<&Tensor>> {
+        tensor_refs(&self.all_attentions)
+    }
+
+    fn logits(&self) -> Option<&Tensor> {
+        Some(&self.logits)
+    }
+}
+
 ///Container holding a Reformer model with question answering head
 pub struct ReformerQuestionAnsweringModelOutput {
     /// start logits
@@ -1005,3 +1059,21 @@ pub struct ReformerQuestionAnsweringModelOutput {
     /// Attention weights for all intermediate layers
     pub all_attentions: Option<Vec<Tensor>>,
 }
+
+impl ModelOutput for ReformerQuestionAnsweringModelOutput {
+    fn hidden_states(&self) -> Option<Vec<&Tensor>> {
+        tensor_refs(&self.all_hidden_states)
+    }
+
+    fn attentions(&self) -> Option<Vec<&Tensor>> {
+        tensor_refs(&self.all_attentions)
+    }
+
+    fn logits(&self) -> Option<&Tensor> {
+        None
+    }
+
+    fn start_end_logits(&self) -> Option<(&Tensor, &Tensor)> {
+        Some((&self.start_logits, &self.end_logits))
+    }
+}
@@ -13,6 +13,7 @@
 
 use crate::bert::{BertConfig, BertEmbedding};
 use crate::common::dropout::Dropout;
+use crate::common::weights::resize_token_dimension;
 use crate::RustBertError;
 use std::borrow::Borrow;
 use tch::nn::{embedding, EmbeddingConfig};
@@ -28,6 +29,7 @@ pub struct RobertaEmbeddings {
     layer_norm: nn::LayerNorm,
     dropout: Dropout,
     padding_index: i64,
+    initializer_range: f64,
 }
 
 impl RobertaEmbeddings {
@@ -117,6 +119,7 @@ impl BertEmbedding for RobertaEmbeddings {
             layer_norm,
             dropout,
             padding_index: 1,
+            initializer_range: config.initializer_range as f64,
         }
     }
 
@@ -219,4 +222,12 @@ impl BertEmbedding for RobertaEmbeddings {
             .apply(&self.layer_norm)
             .apply_t(&self.dropout, train))
     }
+
+    fn resize_word_embeddings(&mut self, new_num_tokens: i64) -> Result<(), RustBertError> {
+        resize_token_dimension(
+            &mut self.word_embeddings.ws,
+            new_num_tokens,
+            Some(self.initializer_range),
+        )
+    }
 }
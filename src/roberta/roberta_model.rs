@@ -13,8 +13,14 @@
 
 use crate::bert::{BertConfig, BertModel};
 use crate::common::activations::_gelu;
+use crate::common::adapters::Adapters;
+use crate::common::classification_head::{
+    ClassificationHead, ClassificationHeadConfig, ClassifierOption,
+};
 use crate::common::dropout::Dropout;
+use crate::common::hooks::ForwardHooks;
 use crate::common::linear::{linear_no_bias, LinearNoBias};
+use crate::common::model_output::{tensor_refs, ModelOutput};
 use crate::roberta::embeddings::RobertaEmbeddings;
 use std::borrow::Borrow;
 use tch::nn::Init;
@@ -68,6 +74,11 @@ impl RobertaModelResources {
         "xlm-roberta-ner-es/model",
         "https://huggingface.co/xlm-roberta-large-finetuned-conll02-spanish/resolve/main/rust_model.ot",
     );
+    /// Shared under MIT license by [Joe Davison](https://huggingface.co/joeddav) at https://huggingface.co/joeddav/xlm-roberta-large-xnli. Modified with conversion to C-array format.
+    pub const XLM_ROBERTA_LARGE_XNLI: (&'static str, &'static str) = (
+        "xlm-roberta-large-xnli/model",
+        "https://huggingface.co/joeddav/xlm-roberta-large-xnli/resolve/main/rust_model.ot",
+    );
 }
 
 impl RobertaConfigResources {
@@ -106,6 +117,11 @@ impl RobertaConfigResources {
         "xlm-roberta-ner-es/config",
         "https://huggingface.co/xlm-roberta-large-finetuned-conll02-spanish/resolve/main/config.json",
     );
+    /// Shared under MIT license by [Joe Davison](https://huggingface.co/joeddav) at https://huggingface.co/joeddav/xlm-roberta-large-xnli. Modified with conversion to C-array format.
+    pub const XLM_ROBERTA_LARGE_XNLI: (&'static str, &'static str) = (
+        "xlm-roberta-large-xnli/config",
+        "https://huggingface.co/joeddav/xlm-roberta-large-xnli/resolve/main/config.json",
+    );
 }
 
 impl RobertaVocabResources {
@@ -144,6 +160,11 @@ impl RobertaVocabResources {
         "xlm-roberta-ner-es/spiece",
         "https://huggingface.co/xlm-roberta-large-finetuned-conll02-spanish/resolve/main/sentencepiece.bpe.model",
     );
+    /// Shared under MIT license by [Joe Davison](https://huggingface.co/joeddav) at https://huggingface.co/joeddav/xlm-roberta-large-xnli. Modified with conversion to C-array format.
+    pub const XLM_ROBERTA_LARGE_XNLI: (&'static str, &'static str) = (
+        "xlm-roberta-large-xnli/spiece",
+        "https://huggingface.co/joeddav/xlm-roberta-large-xnli/resolve/main/sentencepiece.bpe.model",
+    );
 }
 
 impl RobertaMergesResources {
@@ -348,6 +369,16 @@ impl RobertaForMaskedLM {
             all_attentions: base_model_output.all_attentions,
         }
     }
+
+    /// Returns the adapters registered on the base model, allowing to add, remove or switch the active adapter
+    pub fn adapters_mut(&mut self) -> &mut Adapters {
+        self.roberta.adapters_mut()
+    }
+
+    /// Returns the forward hooks registered on the base model, allowing to register or remove hooks
+    pub fn hooks_mut(&mut self) -> &mut ForwardHooks {
+        self.roberta.hooks_mut()
+    }
 }
 
 pub struct RobertaClassificationHead {
@@ -403,10 +434,11 @@ impl RobertaClassificationHead {
 /// Base RoBERTa model with a classifier head to perform sentence or document-level classification
 /// It is made of the following blocks:
 /// - `roberta`: Base RoBERTa model
-/// - `classifier`: RoBERTa classification head made of 2 linear layers
+/// - `classifier`: RoBERTa classification head made of 2 linear layers, or a `ClassificationHead` configured with
+/// `new_with_head`
 pub struct RobertaForSequenceClassification {
     roberta: BertModel<RobertaEmbeddings>,
-    classifier: RobertaClassificationHead,
+    classifier: ClassifierOption<RobertaClassificationHead>,
 }
 
 impl RobertaForSequenceClassification {
@@ -436,10 +468,54 @@ impl RobertaForSequenceClassification {
     where
         P: Borrow<nn::Path<'p>>,
     {
-        let p = p.borrow();
+        RobertaForSequenceClassification::build(p.borrow(), config, None)
+    }
+
+    /// Build a new `RobertaForSequenceClassification` with a custom classification head, for checkpoints fine-tuned
+    /// with a head different from the default RoBERTa classification head
+    ///
+    /// # Arguments
+    ///
+    /// * `p` - Variable store path for the root of the RobertaForSequenceClassification model
+    /// * `config` - `BertConfig` object defining the model architecture and vocab size
+    /// * `head_config` - `ClassificationHeadConfig` defining the classification head architecture
+    pub fn new_with_head<'p, P>(
+        p: P,
+        config: &BertConfig,
+        head_config: &ClassificationHeadConfig,
+    ) -> RobertaForSequenceClassification
+    where
+        P: Borrow<nn::Path<'p>>,
+    {
+        RobertaForSequenceClassification::build(p.borrow(), config, Some(head_config))
+    }
+
+    fn build(
+        p: &nn::Path,
+        config: &BertConfig,
+        head_config: Option<&ClassificationHeadConfig>,
+    ) -> RobertaForSequenceClassification {
         let roberta =
             BertModel::<RobertaEmbeddings>::new_with_optional_pooler(p / "roberta", config, false);
-        let classifier = RobertaClassificationHead::new(p / "classifier", config);
+        let classifier = match head_config {
+            Some(head_config) => {
+                let num_labels = config
+                    .id2label
+                    .as_ref()
+                    .expect("num_labels not provided in configuration")
+                    .len() as i64;
+                ClassifierOption::Custom(ClassificationHead::new(
+                    p / "classifier",
+                    config.hidden_size,
+                    num_labels,
+                    head_config,
+                    config.hidden_dropout_prob,
+                ))
+            }
+            None => {
+                ClassifierOption::Default(RobertaClassificationHead::new(p / "classifier", config))
+            }
+        };
 
         RobertaForSequenceClassification {
             roberta,
@@ -507,6 +583,7 @@ impl RobertaForSequenceClassification {
         input_embeds: Option<Tensor>,
         train: bool,
     ) -> RobertaSequenceClassificationOutput {
+        let head_mask = mask.as_ref().map(Tensor::shallow_clone);
         let base_model_output = self
             .roberta
             .forward_t(
@@ -521,15 +598,30 @@ impl RobertaForSequenceClassification {
             )
             .unwrap();
 
-        let logits = self
-            .classifier
-            .forward_t(&base_model_output.hidden_state, train);
+        let logits = match &self.classifier {
+            ClassifierOption::Default(classifier) => {
+                classifier.forward_t(&base_model_output.hidden_state, train)
+            }
+            ClassifierOption::Custom(head) => {
+                head.forward_t(&base_model_output.hidden_state, head_mask.as_ref(), train)
+            }
+        };
         RobertaSequenceClassificationOutput {
             logits,
             all_hidden_states: base_model_output.all_hidden_states,
             all_attentions: base_model_output.all_attentions,
         }
     }
+
+    /// Returns the adapters registered on the base model, allowing to add, remove or switch the active adapter
+    pub fn adapters_mut(&mut self) -> &mut Adapters {
+        self.roberta.adapters_mut()
+    }
+
+    /// Returns the forward hooks registered on the base model, allowing to register or remove hooks
+    pub fn hooks_mut(&mut self) -> &mut ForwardHooks {
+        self.roberta.hooks_mut()
+    }
 }
 
 /// # RoBERTa for multiple choices
@@ -676,6 +768,16 @@ impl RobertaForMultipleChoice {
             all_attentions: base_model_output.all_attentions,
         }
     }
+
+    /// Returns the adapters registered on the base model, allowing to add, remove or switch the active adapter
+    pub fn adapters_mut(&mut self) -> &mut Adapters {
+        self.roberta.adapters_mut()
+    }
+
+    /// Returns the forward hooks registered on the base model, allowing to register or remove hooks
+    pub fn hooks_mut(&mut self) -> &mut ForwardHooks {
+        self.roberta.hooks_mut()
+    }
 }
 
 /// # RoBERTa for token classification (e.g. NER, POS)
@@ -825,6 +927,16 @@ impl RobertaForTokenClassification {
             all_attentions: base_model_output.all_attentions,
         }
     }
+
+    /// Returns the adapters registered on the base model, allowing to add, remove or switch the active adapter
+    pub fn adapters_mut(&mut self) -> &mut Adapters {
+        self.roberta.adapters_mut()
+    }
+
+    /// Returns the forward hooks registered on the base model, allowing to register or remove hooks
+    pub fn hooks_mut(&mut self) -> &mut ForwardHooks {
+        self.roberta.hooks_mut()
+    }
 }
 
 /// # RoBERTa for question answering
@@ -971,6 +1083,16 @@ impl RobertaForQuestionAnswering {
             all_attentions: base_model_output.all_attentions,
         }
     }
+
+    /// Returns the adapters registered on the base model, allowing to add, remove or switch the active adapter
+    pub fn adapters_mut(&mut self) -> &mut Adapters {
+        self.roberta.adapters_mut()
+    }
+
+    /// Returns the forward hooks registered on the base model, allowing to register or remove hooks
+    pub fn hooks_mut(&mut self) -> &mut ForwardHooks {
+        self.roberta.hooks_mut()
+    }
 }
 
 /// Container for the RoBERTa masked LM model output.
@@ -983,6 +1105,20 @@ pub struct RobertaMaskedLMOutput {
     pub all_attentions: Option<Vec<Tensor>>,
 }
 
+impl ModelOutput for RobertaMaskedLMOutput {
+    fn hidden_states(&self) -> Option<Vec<&Tensor>> {
+        tensor_refs(&self.all_hidden_states)
+    }
+
+    fn attentions(&self) -> Option<Vec<&Tensor>> {
+        tensor_refs(&self.all_attentions)
+    }
+
+    fn logits(&self) -> Option<&Tensor> {
+        Some(&self.prediction_scores)
+    }
+}
+
 /// Container for the RoBERTa sequence classification model output.
 pub struct RobertaSequenceClassificationOutput {
     /// Logits for each input (sequence) for each target class
@@ -993,6 +1129,20 @@ pub struct RobertaSequenceClassificationOutput {
     pub all_attentions: Option<Vec<Tensor>>,
 }
 
+impl ModelOutput for RobertaSequenceClassificationOutput {
+    fn hidden_states(&self) -> Option<Vec<&Tensor>> {
+        tensor_refs(&self.all_hidden_states)
+    }
+
+    fn attentions(&self) -> Option<Vec<&Tensor>> {
+        tensor_refs(&self.all_attentions)
+    }
+
+    fn logits(&self) -> Option<&Tensor> {
+        Some(&self.logits)
+    }
+}
+
 /// Container for the RoBERTa token classification model output.
 pub struct RobertaTokenClassificationOutput {
     /// Logits for each sequence item (token) for each target class
@@ -1003,6 +1153,20 @@ pub struct RobertaTokenClassificationOutput {
     pub all_attentions: Option<Vec<Tensor>>,
 }
 
+impl ModelOutput for RobertaTokenClassificationOutput {
+    fn hidden_states(&self) -> Option<Vec<&Tensor>> {
+        tensor_refs(&self.all_hidden_states)
+    }
+
+    fn attentions(&self) -> Option<Vec<&Tensor>> {
+        tensor_refs(&self.all_attentions)
+    }
+
+    fn logits(&self) -> Option<&Tensor> {
+        Some(&self.logits)
+    }
+}
+
 /// Container for the RoBERTa question answering model output.
 pub struct RobertaQuestionAnsweringOutput {
     /// Logits for the start position for token of each input sequence
@@ -1014,3 +1178,21 @@ pub struct RobertaQuestionAnsweringOutput {
     /// Attention weights for all intermediate layers
     pub all_attentions: Option<Vec<Tensor>>,
 }
+
+impl ModelOutput for RobertaQuestionAnsweringOutput {
+    fn hidden_states(&self) -> Option<Vec<&Tensor>> {
+        tensor_refs(&self.all_hidden_states)
+    }
+
+    fn attentions(&self) -> Option<Vec<&Tensor>> {
+        tensor_refs(&self.all_attentions)
+    }
+
+    fn logits(&self) -> Option<&Tensor> {
+        None
+    }
+
+    fn start_end_logits(&self) -> Option<(&Tensor, &Tensor)> {
+        Some((&self.start_logits, &self.end_logits))
+    }
+}
@@ -0,0 +1,56 @@
+//! # Splinter: Few-Shot Question Answering by Pretraining Span Selection (Ram et al.)
+//!
+//! Implementation of the Splinter language model ([https://arxiv.org/abs/2101.00438](https://arxiv.org/abs/2101.00438) Ram, Kirstain, Berant, Globerson, Levy, 2021).
+//! Splinter is pre-trained by masking recurring spans of a passage with a `[QUESTION]` token and selecting the span
+//! matching each `[QUESTION]` token, making it well suited to extractive question answering with few training examples.
+//! The base model is the BERT encoder (`bert::BertModel`, without pooling layer). The question-aware span selection
+//! (QASS) head is implemented in `splinter::SplinterForQuestionAnswering`, and can be used in the question answering
+//! pipeline with `ModelType::Splinter`.
+//!
+//! # Model set-up and pre-trained weights loading
+//!
+//! All models expect the following resources:
+//! - Configuration file expected to have a structure following the [Transformers library](https://github.com/huggingface/transformers). The `question_token_id` field of the configuration is used to locate the `[QUESTION]` token (default: 104)
+//! - Model weights are expected to have a structure and parameter names following the [Transformers library](https://github.com/huggingface/transformers). A conversion using the Python utility scripts is required to convert the `.bin` weights to the `.ot` format.
+//! - `BertTokenizer` using a `vocab.txt` vocabulary containing the `[QUESTION]` token
+//!
+//! ```no_run
+//! # fn main() -> anyhow::Result<()> {
+//! #
+//! use tch::{nn, Device};
+//! # use std::path::PathBuf;
+//! use rust_bert::bert::BertConfig;
+//! use rust_bert::resources::{LocalResource, Resource};
+//! use rust_bert::splinter::SplinterForQuestionAnswering;
+//! use rust_bert::Config;
+//! use rust_tokenizers::tokenizer::BertTokenizer;
+//!
+//! let config_resource = Resource::Local(LocalResource {
+//!     local_path: PathBuf::from("path/to/config.json"),
+//! });
+//! let vocab_resource = Resource::Local(LocalResource {
+//!     local_path: PathBuf::from("path/to/vocab.txt"),
+//! });
+//! let weights_resource = Resource::Local(LocalResource {
+//!     local_path: PathBuf::from("path/to/model.ot"),
+//! });
+//! let config_path = config_resource.get_local_path()?;
+//! let vocab_path = vocab_resource.get_local_path()?;
+//! let weights_path = weights_resource.get_local_path()?;
+//!
+//! let device = Device::cuda_if_available();
+//! let mut vs = nn::VarStore::new(device);
+//! let tokenizer = BertTokenizer::from_file(vocab_path.to_str().unwrap(), false, false)?;
+//! let config = BertConfig::from_file(config_path);
+//! let splinter_model = SplinterForQuestionAnswering::new(&vs.root(), &config);
+//! vs.load(weights_path)?;
+//!
+//! # Ok(())
+//! # }
+//! ```
+
+mod splinter_model;
+
+pub use splinter_model::{
+    QuestionAwareSpanSelectionHead, SplinterForQuestionAnswering, SplinterQuestionAnsweringOutput,
+};
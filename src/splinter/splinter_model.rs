@@ -0,0 +1,389 @@
+// Copyright 2021 Tel Aviv University, AllenAI and The HuggingFace Inc. team.
+// Copyright 2021 Guillaume Becquin
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//     http://www.apache.org/licenses/LICENSE-2.0
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use crate::bert::{BertConfig, BertEmbeddings, BertModel};
+use crate::common::activations::TensorFunction;
+use crate::common::adapters::Adapters;
+use crate::common::hooks::ForwardHooks;
+use crate::common::linear::{linear_no_bias, LinearNoBias};
+use crate::common::model_output::{tensor_refs, ModelOutput};
+use crate::Config;
+use std::borrow::Borrow;
+use tch::kind::Kind::Int64;
+use tch::{nn, Tensor};
+
+/// Default id of the `[QUESTION]` token in the Splinter vocabulary, used if the configuration does not define a
+/// `question_token_id`
+const DEFAULT_QUESTION_TOKEN_ID: i64 = 104;
+
+pub struct SplinterFullyConnectedLayer {
+    dense: nn::Linear,
+    activation: TensorFunction,
+    layer_norm: nn::LayerNorm,
+}
+
+impl SplinterFullyConnectedLayer {
+    pub fn new<'p, P>(p: P, config: &BertConfig) -> SplinterFullyConnectedLayer
+    where
+        P: Borrow<nn::Path<'p>>,
+    {
+        let p = p.borrow();
+
+        let dense = nn::linear(
+            p / "dense",
+            config.hidden_size,
+            config.hidden_size,
+            Default::default(),
+        );
+        let activation = config.hidden_act.get_function();
+        let layer_norm = nn::layer_norm(
+            p / "LayerNorm",
+            vec![config.hidden_size],
+            Default::default(),
+        );
+
+        SplinterFullyConnectedLayer {
+            dense,
+            activation,
+            layer_norm,
+        }
+    }
+
+    pub fn forward(&self, hidden_states: &Tensor) -> Tensor {
+        ((&self.activation.get_fn())(&hidden_states.apply(&self.dense))).apply(&self.layer_norm)
+    }
+}
+
+/// # Question-aware span selection (QASS) head
+/// Computes the start and end logits of the answer to each question as the bilinear product of the representation
+/// of the `[QUESTION]` token with the representation of every token of the sequence.
+pub struct QuestionAwareSpanSelectionHead {
+    query_start_transform: SplinterFullyConnectedLayer,
+    query_end_transform: SplinterFullyConnectedLayer,
+    start_transform: SplinterFullyConnectedLayer,
+    end_transform: SplinterFullyConnectedLayer,
+    start_classifier: LinearNoBias,
+    end_classifier: LinearNoBias,
+}
+
+impl QuestionAwareSpanSelectionHead {
+    /// Build a new `QuestionAwareSpanSelectionHead`
+    ///
+    /// # Arguments
+    ///
+    /// * `p` - Variable store path for the root of the head
+    /// * `config` - `BertConfig` object defining the model architecture
+    pub fn new<'p, P>(p: P, config: &BertConfig) -> QuestionAwareSpanSelectionHead
+    where
+        P: Borrow<nn::Path<'p>>,
+    {
+        let p = p.borrow();
+
+        let query_start_transform =
+            SplinterFullyConnectedLayer::new(p / "query_start_transform", config);
+        let query_end_transform =
+            SplinterFullyConnectedLayer::new(p / "query_end_transform", config);
+        let start_transform = SplinterFullyConnectedLayer::new(p / "start_transform", config);
+        let end_transform = SplinterFullyConnectedLayer::new(p / "end_transform", config);
+        let start_classifier = linear_no_bias(
+            p / "start_classifier",
+            config.hidden_size,
+            config.hidden_size,
+            Default::default(),
+        );
+        let end_classifier = linear_no_bias(
+            p / "end_classifier",
+            config.hidden_size,
+            config.hidden_size,
+            Default::default(),
+        );
+
+        QuestionAwareSpanSelectionHead {
+            query_start_transform,
+            query_end_transform,
+            start_transform,
+            end_transform,
+            start_classifier,
+            end_classifier,
+        }
+    }
+
+    /// Forward pass through the head
+    ///
+    /// # Arguments
+    ///
+    /// * `hidden_states` - Sequence output of the encoder of shape (*batch size*, *sequence_length*, *hidden_size*)
+    /// * `question_positions` - Positions of the `[QUESTION]` tokens of shape (*batch size*, *num_questions*)
+    ///
+    /// # Returns
+    ///
+    /// * `(start_logits, end_logits)` - `Tensor`s of shape (*batch size*, *num_questions*, *sequence_length*)
+    pub fn forward(&self, hidden_states: &Tensor, question_positions: &Tensor) -> (Tensor, Tensor) {
+        let hidden_size = hidden_states.size()[2];
+        let index = question_positions
+            .unsqueeze(-1)
+            .repeat(&[1, 1, hidden_size]);
+        let question_states = hidden_states.gather(1, &index, false);
+
+        let query_start = self.query_start_transform.forward(&question_states);
+        let query_end = self.query_end_transform.forward(&question_states);
+        let start = self.start_transform.forward(hidden_states);
+        let end = self.end_transform.forward(hidden_states);
+
+        let start_logits = query_start
+            .apply(&self.start_classifier)
+            .matmul(&start.transpose(1, 2));
+        let end_logits = query_end
+            .apply(&self.end_classifier)
+            .matmul(&end.transpose(1, 2));
+        (start_logits, end_logits)
+    }
+}
+
+/// # Splinter for question answering
+/// Extractive question answering model using the question-aware span selection head of Splinter. The answer span is
+/// selected based on the representation of the `[QUESTION]` token, expected to follow the question in the input
+/// (`[CLS] Question [QUESTION] . [SEP] Context [SEP]`).
+/// It is made of the following blocks:
+/// - `splinter`: Base BertModel (without pooling layer)
+/// - `splinter_qass`: Question-aware span selection head
+pub struct SplinterForQuestionAnswering {
+    splinter: BertModel<BertEmbeddings>,
+    splinter_qass: QuestionAwareSpanSelectionHead,
+    question_token_id: i64,
+}
+
+impl SplinterForQuestionAnswering {
+    /// Build a new `SplinterForQuestionAnswering`
+    ///
+    /// # Arguments
+    ///
+    /// * `p` - Variable store path for the root of the SplinterForQuestionAnswering model
+    /// * `config` - `BertConfig` object defining the model architecture. The id of the `[QUESTION]` token is read from the `question_token_id` field if present.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use rust_bert::bert::BertConfig;
+    /// use rust_bert::splinter::SplinterForQuestionAnswering;
+    /// use rust_bert::Config;
+    /// use std::path::Path;
+    /// use tch::{nn, Device};
+    ///
+    /// let config_path = Path::new("path/to/config.json");
+    /// let device = Device::Cpu;
+    /// let p = nn::VarStore::new(device);
+    /// let config = BertConfig::from_file(config_path);
+    /// let splinter = SplinterForQuestionAnswering::new(&p.root(), &config);
+    /// ```
+    pub fn new<'p, P>(p: P, config: &BertConfig) -> SplinterForQuestionAnswering
+    where
+        P: Borrow<nn::Path<'p>>,
+    {
+        let p = p.borrow();
+
+        let splinter =
+            BertModel::<BertEmbeddings>::new_with_optional_pooler(p / "splinter", config, false);
+        let splinter_qass = QuestionAwareSpanSelectionHead::new(p / "splinter_qass", config);
+        let question_token_id = config
+            .get_extra_i64("question_token_id")
+            .unwrap_or(DEFAULT_QUESTION_TOKEN_ID);
+
+        SplinterForQuestionAnswering {
+            splinter,
+            splinter_qass,
+            question_token_id,
+        }
+    }
+
+    /// Returns the id of the `[QUESTION]` token
+    pub fn question_token_id(&self) -> i64 {
+        self.question_token_id
+    }
+
+    /// Forward pass through the model
+    ///
+    /// # Arguments
+    ///
+    /// * `input_ids` - Optional input tensor of shape (*batch size*, *sequence_length*). If None, pre-computed embeddings must be provided (see `input_embeds`)
+    /// * `mask` - Optional mask of shape (*batch size*, *sequence_length*). Masked position have value 0, non-masked value 1. If None set to 1
+    /// * `token_type_ids` -Optional segment id of shape (*batch size*, *sequence_length*). Convention is value of 0 for the first sentence (incl. *[SEP]*) and 1 for the second sentence. If None set to 0.
+    /// * `position_ids` - Optional position ids of shape (*batch size*, *sequence_length*). If None, will be incremented from 0.
+    /// * `input_embeds` - Optional pre-computed input embeddings of shape (*batch size*, *sequence_length*, *hidden_size*). If None, input ids must be provided (see `input_ids`)
+    /// * `question_positions` - Optional positions of the `[QUESTION]` tokens of shape (*batch size*, *num_questions*). If None, the position of the first `[QUESTION]` token of each input is used (or the first position if the input embeddings are provided).
+    /// * `train` - boolean flag to turn on/off the dropout layers in the model. Should be set to false for inference.
+    ///
+    /// # Returns
+    ///
+    /// * `SplinterQuestionAnsweringOutput` containing:
+    ///   - `start_logits` - `Tensor` of shape (*batch size*, *sequence_length*) containing the logits for start of the answer, or of shape (*batch size*, *num_questions*, *sequence_length*) if `question_positions` are provided
+    ///   - `end_logits` - `Tensor` of shape (*batch size*, *sequence_length*) containing the logits for end of the answer, or of shape (*batch size*, *num_questions*, *sequence_length*) if `question_positions` are provided
+    ///   - `all_hidden_states` - `Option<Vec<Tensor>>` of length *num_hidden_layers* with shape (*batch size*, *sequence_length*, *hidden_size*)
+    ///   - `all_attentions` - `Option<Vec<Tensor>>` of length *num_hidden_layers* with shape (*batch size*, *sequence_length*, *hidden_size*)
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// # use rust_bert::bert::BertConfig;
+    /// # use tch::{nn, Device, Tensor, no_grad};
+    /// # use rust_bert::Config;
+    /// # use std::path::Path;
+    /// # use tch::kind::Kind::Int64;
+    /// use rust_bert::splinter::SplinterForQuestionAnswering;
+    /// # let config_path = Path::new("path/to/config.json");
+    /// # let device = Device::Cpu;
+    /// # let vs = nn::VarStore::new(device);
+    /// # let config = BertConfig::from_file(config_path);
+    /// # let splinter_model = SplinterForQuestionAnswering::new(&vs.root(), &config);
+    /// let (batch_size, sequence_length) = (64, 128);
+    /// let input_tensor = Tensor::rand(&[batch_size, sequence_length], (Int64, device));
+    /// let mask = Tensor::ones(&[batch_size, sequence_length], (Int64, device));
+    ///
+    /// let model_output = no_grad(|| {
+    ///     splinter_model.forward_t(
+    ///         Some(input_tensor),
+    ///         Some(mask),
+    ///         None,
+    ///         None,
+    ///         None,
+    ///         None,
+    ///         false,
+    ///     )
+    /// });
+    /// ```
+    pub fn forward_t(
+        &self,
+        input_ids: Option<Tensor>,
+        mask: Option<Tensor>,
+        token_type_ids: Option<Tensor>,
+        position_ids: Option<Tensor>,
+        input_embeds: Option<Tensor>,
+        question_positions: Option<Tensor>,
+        train: bool,
+    ) -> SplinterQuestionAnsweringOutput {
+        let (question_positions, single_question) = match question_positions {
+            Some(question_positions) => (question_positions, false),
+            None => {
+                let question_positions = match (&input_ids, &input_embeds) {
+                    (Some(input_ids), _) => input_ids
+                        .eq(self.question_token_id)
+                        .to_kind(Int64)
+                        .argmax(-1, true),
+                    (None, Some(input_embeds)) => {
+                        Tensor::zeros(&[input_embeds.size()[0], 1], (Int64, input_embeds.device()))
+                    }
+                    (None, None) => {
+                        panic!("At least one of input ids or input embeddings must be set")
+                    }
+                };
+                (question_positions, true)
+            }
+        };
+
+        let base_model_output = self
+            .splinter
+            .forward_t(
+                input_ids,
+                mask.as_ref().map(|mask| mask.copy()),
+                token_type_ids,
+                position_ids,
+                input_embeds,
+                &None,
+                &None,
+                train,
+            )
+            .unwrap();
+
+        let (mut start_logits, mut end_logits) = self
+            .splinter_qass
+            .forward(&base_model_output.hidden_state, &question_positions);
+        if single_question {
+            start_logits = start_logits.squeeze1(1);
+            end_logits = end_logits.squeeze1(1);
+        }
+        if let Some(mask) = mask {
+            let padding_mask = if single_question {
+                mask.eq(0)
+            } else {
+                mask.eq(0).unsqueeze(1)
+            };
+            start_logits = start_logits.masked_fill(&padding_mask, -10000.0);
+            end_logits = end_logits.masked_fill(&padding_mask, -10000.0);
+        }
+
+        SplinterQuestionAnsweringOutput {
+            start_logits,
+            end_logits,
+            all_hidden_states: base_model_output.all_hidden_states,
+            all_attentions: base_model_output.all_attentions,
+        }
+    }
+
+    /// Returns the adapters registered on the base model, allowing to add, remove or switch the active adapter
+    pub fn adapters_mut(&mut self) -> &mut Adapters {
+        self.splinter.adapters_mut()
+    }
+
+    /// Returns the forward hooks registered on the base model, allowing to register or remove hooks
+    pub fn hooks_mut(&mut self) -> &mut ForwardHooks {
+        self.splinter.hooks_mut()
+    }
+}
+
+/// Container for the Splinter question answering model output.
+pub struct SplinterQuestionAnsweringOutput {
+    /// Logits for the start position for token of each input sequence
+    pub start_logits: Tensor,
+    /// Logits for the end position for token of each input sequence
+    pub end_logits: Tensor,
+    /// Hidden states for all intermediate layers
+    pub all_hidden_states: Option<Vec<Tensor>>,
+    /// Attention weights for all intermediate layers
+    pub all_attentions: Option<Vec<Tensor>>,
+}
+
+impl ModelOutput for SplinterQuestionAnsweringOutput {
+    fn hidden_states(&self) -> Option<Vec<&Tensor>> {
+        tensor_refs(&self.all_hidden_states)
+    }
+
+    fn attentions(&self) -> Option<Vec<&Tensor>> {
+        tensor_refs(&self.all_attentions)
+    }
+
+    fn logits(&self) -> Option<&Tensor> {
+        None
+    }
+
+    fn start_end_logits(&self) -> Option<(&Tensor, &Tensor)> {
+        Some((&self.start_logits, &self.end_logits))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::bert::BertConfigResources;
+    use crate::resources::{RemoteResource, Resource};
+    use tch::Device;
+
+    #[test]
+    #[ignore] // no need to run, compilation is enough to verify it is Send
+    fn test() {
+        let config_resource =
+            Resource::Remote(RemoteResource::from_pretrained(BertConfigResources::BERT));
+        let config_path = config_resource.get_local_path().expect("");
+        let vs = nn::VarStore::new(Device::Cpu);
+        let config = BertConfig::from_file(config_path);
+        let _: Box<dyn Send> = Box::new(SplinterForQuestionAnswering::new(&vs.root(), &config));
+    }
+}
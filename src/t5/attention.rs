@@ -11,9 +11,13 @@
 // limitations under the License.
 
 use crate::common::dropout::Dropout;
+use crate::common::profiling;
+use crate::common::weights::weights_version;
 use crate::t5::layer_norm::T5LayerNorm;
 use crate::t5::T5Config;
 use std::borrow::Borrow;
+use std::collections::HashMap;
+use std::sync::Mutex;
 use tch::nn::LinearConfig;
 use tch::{nn, Device, Kind, Tensor};
 
@@ -43,6 +47,17 @@ impl LayerState {
     }
 }
 
+// Maximum number of relative attention biases kept in the cache of a layer, cleared when exceeded
+const MAX_CACHED_BIASES: usize = 512;
+
+/// Relative attention biases computed without gradient tracking, keyed on the device and the query and key lengths,
+/// with the address and version of the relative attention bias weights they were computed from
+#[derive(Debug, Default)]
+struct BiasCache {
+    weights: Option<(usize, usize)>,
+    biases: HashMap<(Device, i64, i64, i64), Tensor>,
+}
+
 #[derive(Debug)]
 pub struct T5Attention {
     is_decoder: bool,
@@ -61,6 +76,7 @@ pub struct T5Attention {
     value: nn::Linear,
     output: nn::Linear,
     relative_attention_bias: Option<nn::Embedding>,
+    bias_cache: Mutex<BiasCache>,
 }
 
 impl T5Attention {
@@ -118,6 +134,7 @@ impl T5Attention {
             value,
             output,
             relative_attention_bias,
+            bias_cache: Mutex::new(BiasCache::default()),
         }
     }
 
@@ -195,11 +212,22 @@ impl T5Attention {
         let mut scores = Tensor::einsum("bnqd,bnkd->bnqk", &[q, k]);
 
         let calculated_position_bias = if position_bias.is_none() {
-            let mut temp_value =
-                self.compute_bias(real_query_length, key_length, hidden_states.device());
-            if layer_state.is_some() {
-                let length = temp_value.size()[2];
-                temp_value = temp_value.slice(2, length - 1, length, 1);
+            // When decoding with a cache, only the bias of the new query positions is required
+            let new_positions = if layer_state.is_some() {
+                q_len
+            } else {
+                real_query_length
+            };
+            let mut temp_value = {
+                let _scope =
+                    profiling::scope(|| "relative_attention_bias".to_string(), &[hidden_states]);
+                self.get_bias(
+                    real_query_length,
+                    key_length,
+                    new_positions,
+                    hidden_states.device(),
+                    train,
+                )
             };
             if let Some(attention_mask) = attention_mask {
                 temp_value = temp_value + attention_mask
@@ -271,8 +299,89 @@ impl T5Attention {
         ret
     }
 
-    fn compute_bias(&self, q_len: i64, k_len: i64, device: Device) -> Tensor {
-        let context_position = Tensor::arange(q_len, (Kind::Int64, device)).unsqueeze(1);
+    /// Returns the relative attention bias of the last `new_positions` queries out of `q_len`, attending `k_len` keys
+    /// (zeros for the layers without relative attention bias weights).
+    /// In evaluation mode, the biases computed without gradient tracking (e.g. during generation) are cached, keyed
+    /// on the device and the query and key lengths, and reused for the following inputs and generation steps. The
+    /// cache is cleared when the relative attention bias weights are re-allocated or when the weights of a model are
+    /// reloaded (see `reload_weights`).
+    fn get_bias(
+        &self,
+        q_len: i64,
+        k_len: i64,
+        new_positions: i64,
+        device: Device,
+        train: bool,
+    ) -> Tensor {
+        let relative_attention_bias = match &self.relative_attention_bias {
+            Some(relative_attention_bias) => relative_attention_bias,
+            None => {
+                return Tensor::zeros(
+                    &[1, self.n_heads, new_positions, k_len],
+                    (Kind::Float, device),
+                );
+            }
+        };
+        let q_start = q_len - new_positions;
+        if train {
+            return self.compute_bias(
+                relative_attention_bias,
+                q_start,
+                new_positions,
+                k_len,
+                device,
+            );
+        }
+        let key = (device, q_len, k_len, new_positions);
+        let mut cache = match self.bias_cache.lock() {
+            Ok(cache) => cache,
+            Err(_) => {
+                return self.compute_bias(
+                    relative_attention_bias,
+                    q_start,
+                    new_positions,
+                    k_len,
+                    device,
+                );
+            }
+        };
+        let weights = Some((
+            relative_attention_bias.ws.data_ptr() as usize,
+            weights_version(),
+        ));
+        if cache.weights != weights {
+            cache.biases.clear();
+            cache.weights = weights;
+        }
+        if let Some(bias) = cache.biases.get(&key) {
+            return bias.shallow_clone();
+        }
+        let bias = self.compute_bias(
+            relative_attention_bias,
+            q_start,
+            new_positions,
+            k_len,
+            device,
+        );
+        if !bias.requires_grad() {
+            if cache.biases.len() >= MAX_CACHED_BIASES {
+                cache.biases.clear();
+            }
+            cache.biases.insert(key, bias.shallow_clone());
+        }
+        bias
+    }
+
+    fn compute_bias(
+        &self,
+        relative_attention_bias: &nn::Embedding,
+        q_start: i64,
+        q_len: i64,
+        k_len: i64,
+        device: Device,
+    ) -> Tensor {
+        let context_position =
+            (Tensor::arange(q_len, (Kind::Int64, device)) + q_start).unsqueeze(1);
         let memory_position = Tensor::arange(k_len, (Kind::Int64, device)).unsqueeze(0);
         let relative_position = memory_position - context_position;
 
@@ -283,7 +392,7 @@ impl T5Attention {
             128,
         );
         rp_bucket
-            .apply(self.relative_attention_bias.as_ref().unwrap())
+            .apply(relative_attention_bias)
             .permute(&[2, 0, 1])
             .unsqueeze(0)
     }
@@ -338,6 +447,7 @@ impl T5LayerSelfAttention {
         layer_state: Option<LayerState>,
         train: bool,
     ) -> (Tensor, Option<Tensor>, Option<Tensor>, Option<LayerState>) {
+        let _scope = profiling::scope(|| "self_attention".to_string(), &[hidden_states]);
         let norm_x = hidden_states.apply(&self.layer_norm);
         let (y, attention_weights, position_bias, layer_state) = self.self_attention.forward_t(
             &norm_x,
@@ -406,6 +516,7 @@ impl T5LayerCrossAttention {
         query_length: Option<i64>,
         train: bool,
     ) -> (Tensor, Option<Tensor>, Option<Tensor>, Option<LayerState>) {
+        let _scope = profiling::scope(|| "cross_attention".to_string(), &[hidden_states]);
         let norm_x = hidden_states.apply(&self.layer_norm);
 
         let (y, attention_weights, position_bias, layer_state) =
@@ -10,10 +10,12 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+use crate::common::activations::_gelu_new;
 use crate::common::dropout::Dropout;
+use crate::common::profiling;
 use crate::t5::attention::{LayerState, T5LayerCrossAttention, T5LayerSelfAttention};
 use crate::t5::layer_norm::T5LayerNorm;
-use crate::t5::T5Config;
+use crate::t5::{FeedForwardProj, T5Config};
 use crate::RustBertError;
 use std::borrow::{Borrow, BorrowMut};
 use tch::nn::LinearConfig;
@@ -51,8 +53,73 @@ impl T5DenseReluDense {
     }
 }
 
+pub struct T5DenseGatedGeluDense {
+    wi_0: nn::Linear,
+    wi_1: nn::Linear,
+    wo: nn::Linear,
+    dropout: Dropout,
+}
+
+impl T5DenseGatedGeluDense {
+    pub fn new<'p, P>(p: P, config: &T5Config) -> T5DenseGatedGeluDense
+    where
+        P: Borrow<nn::Path<'p>>,
+    {
+        let p = p.borrow();
+        let linear_config = LinearConfig {
+            bias: false,
+            ..Default::default()
+        };
+        let wi_0 = nn::linear(p / "wi_0", config.d_model, config.d_ff, linear_config);
+        let wi_1 = nn::linear(p / "wi_1", config.d_model, config.d_ff, linear_config);
+        let wo = nn::linear(p / "wo", config.d_ff, config.d_model, linear_config);
+        let dropout = Dropout::new(config.dropout_rate);
+
+        T5DenseGatedGeluDense {
+            wi_0,
+            wi_1,
+            wo,
+            dropout,
+        }
+    }
+
+    pub fn forward_t(&self, hidden_states: &Tensor, train: bool) -> Tensor {
+        let hidden_gelu = _gelu_new(&hidden_states.apply(&self.wi_0));
+        let hidden_linear = hidden_states.apply(&self.wi_1);
+        (hidden_gelu * hidden_linear)
+            .apply_t(&self.dropout, train)
+            .apply(&self.wo)
+    }
+}
+
+pub enum T5FeedForward {
+    ReluDense(T5DenseReluDense),
+    GatedGeluDense(T5DenseGatedGeluDense),
+}
+
+impl T5FeedForward {
+    pub fn new<'p, P>(p: P, config: &T5Config) -> T5FeedForward
+    where
+        P: Borrow<nn::Path<'p>>,
+    {
+        match config.feed_forward_proj.unwrap_or(FeedForwardProj::Relu) {
+            FeedForwardProj::Relu => T5FeedForward::ReluDense(T5DenseReluDense::new(p, config)),
+            FeedForwardProj::GatedGelu => {
+                T5FeedForward::GatedGeluDense(T5DenseGatedGeluDense::new(p, config))
+            }
+        }
+    }
+
+    pub fn forward_t(&self, hidden_states: &Tensor, train: bool) -> Tensor {
+        match self {
+            T5FeedForward::ReluDense(module) => module.forward_t(hidden_states, train),
+            T5FeedForward::GatedGeluDense(module) => module.forward_t(hidden_states, train),
+        }
+    }
+}
+
 pub struct T5LayerFF {
-    dense_relu_dense: T5DenseReluDense,
+    dense_relu_dense: T5FeedForward,
     layer_norm: T5LayerNorm,
     dropout: Dropout,
 }
@@ -64,7 +131,7 @@ impl T5LayerFF {
     {
         let p = p.borrow();
 
-        let dense_relu_dense = T5DenseReluDense::new(p / "DenseReluDense", config);
+        let dense_relu_dense = T5FeedForward::new(p / "DenseReluDense", config);
         let layer_norm =
             T5LayerNorm::new(p / "layer_norm", config.d_model, config.layer_norm_epsilon);
         let dropout = Dropout::new(config.dropout_rate);
@@ -77,6 +144,7 @@ impl T5LayerFF {
     }
 
     pub fn forward_t(&self, hidden_states: &Tensor, train: bool) -> Tensor {
+        let _scope = profiling::scope(|| "feed_forward".to_string(), &[hidden_states]);
         let y = &self
             .dense_relu_dense
             .forward_t(&hidden_states.apply(&self.layer_norm), train);
@@ -255,6 +323,10 @@ impl T5Stack {
         }
     }
 
+    pub fn num_layers(&self) -> usize {
+        self.blocks.len()
+    }
+
     pub fn forward_t(
         &self,
         input_ids: Option<&Tensor>,
@@ -288,6 +360,16 @@ impl T5Stack {
             },
         };
 
+        let _scope = profiling::scope(
+            || {
+                if self.is_decoder {
+                    "decoder".to_string()
+                } else {
+                    "encoder".to_string()
+                }
+            },
+            &[&input_embeddings],
+        );
         let (batch_size, sequence_length) = (input_shape[0], input_shape[1]);
 
         let mask_seq_length = if old_layer_states.is_some() {
@@ -382,6 +464,12 @@ impl T5Stack {
         } else {
             None
         };
+        let mut all_cross_attentions: Option<Vec<Tensor>> =
+            if self.output_attentions & self.is_decoder & encoder_hidden_states.is_some() {
+                Some(Vec::with_capacity(self.blocks.len()))
+            } else {
+                None
+            };
         let mut next_cache: Option<Vec<(Option<LayerState>, Option<LayerState>)>> =
             if self.store_cache {
                 if old_layer_states.is_some() {
@@ -395,6 +483,7 @@ impl T5Stack {
         let mut position_bias = None;
         let mut encoder_decoder_position_bias = None;
         let mut attention_weights: Option<Tensor>;
+        let mut cross_attention_weights: Option<Tensor>;
         let mut hidden_state = input_embeddings.apply_t(&self.dropout, train);
 
         for (layer_idx, layer) in self.blocks.iter().enumerate() {
@@ -402,6 +491,8 @@ impl T5Stack {
                 Some(values) => values[layer_idx].to_owned(),
                 None => (None, None),
             };
+            let _block_scope =
+                profiling::scope(|| format!("block.{}", layer_idx), &[&hidden_state]);
             let block_output = layer.forward_t(
                 &hidden_state,
                 position_bias.as_ref(),
@@ -417,13 +508,17 @@ impl T5Stack {
                 encoder_decoder_position_bias = block_output.cross_attention_position_bias;
             }
             hidden_state = block_output.hidden_states;
-            attention_weights = block_output.cross_attention_weights;
+            attention_weights = block_output.self_attention_weights;
+            cross_attention_weights = block_output.cross_attention_weights;
             if let Some(hidden_states) = all_hidden_states.borrow_mut() {
                 hidden_states.push(hidden_state.as_ref().copy().transpose(0, 1));
             };
             if let Some(attentions) = all_attentions.borrow_mut() {
                 attentions.push(attention_weights.as_ref().unwrap().copy());
             };
+            if let Some(cross_attentions) = all_cross_attentions.borrow_mut() {
+                cross_attentions.push(cross_attention_weights.as_ref().unwrap().copy());
+            };
             if let Some(value) = &mut next_cache {
                 value[layer_idx] = block_output.cache
             };
@@ -437,6 +532,7 @@ impl T5Stack {
             hidden_state,
             all_hidden_states,
             all_attentions,
+            all_cross_attentions,
             next_cache,
         })
     }
@@ -455,5 +551,6 @@ pub struct T5StackOutput {
     pub hidden_state: Tensor,
     pub all_hidden_states: Option<Vec<Tensor>>,
     pub all_attentions: Option<Vec<Tensor>>,
+    pub all_cross_attentions: Option<Vec<Tensor>>,
     pub next_cache: Option<Vec<(Option<LayerState>, Option<LayerState>)>>,
 }
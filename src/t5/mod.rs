@@ -47,14 +47,19 @@
 //! # Ok(())
 //! # }
 //! ```
+//!
+//! For fine-tuning, the decoder input ids are built from the target labels with `t5::shift_tokens_right`. T5 uses its
+//! padding token as decoder start token: `shift_tokens_right(&labels, 0, 0)` for the original checkpoints.
 
 mod attention;
 mod encoder;
 mod layer_norm;
 mod t5_model;
 
+pub use crate::bart::shift_tokens_right;
 pub use attention::LayerState;
 pub use t5_model::{
-    T5Config, T5ConfigResources, T5ForConditionalGeneration, T5Model, T5ModelOutput,
-    T5ModelResources, T5Prefix, T5VocabResources,
+    prefix_lm_attention_mask, FeedForwardProj, T5Config, T5ConfigResources,
+    T5ForConditionalGeneration, T5Model, T5ModelOutput, T5ModelResources, T5Prefix,
+    T5VocabResources,
 };
@@ -9,14 +9,22 @@
 // WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
 // See the License for the specific language governing permissions and
 // limitations under the License.
-use crate::pipelines::generation_utils::{Cache, LMHeadModel, LMModelOutput};
+use crate::common::config::{check_positive, check_token_id};
+use crate::common::lm_head::LMHead;
+use crate::common::model_output::{tensor_refs, ModelOutput};
+use crate::common::prefix_tuning::PrefixTuning;
+use crate::common::weights::resize_token_dimension;
+use crate::pipelines::generation_utils::{
+    Cache, EncoderOnlyModel, LMHeadModel, LMModelOutput, Seq2SeqGenerationModel,
+};
 use crate::t5::attention::LayerState;
 use crate::t5::encoder::T5Stack;
 use crate::{Config, RustBertError};
 use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
 use std::borrow::Borrow;
 use tch::nn::embedding;
-use tch::{nn, Tensor};
+use tch::{nn, no_grad, Kind, Tensor};
 
 /// # T5 Pretrained model weight files
 pub struct T5ModelResources;
@@ -28,6 +36,8 @@ pub struct T5ConfigResources;
 pub struct T5VocabResources;
 
 /// # T5 optional prefixes
+/// Prefixes of the pre-trained T5 translation tasks. The prefixes and generation defaults of all tasks of a checkpoint
+/// are available from its configuration with `Config::task_registry`.
 pub struct T5Prefix;
 
 impl T5ModelResources {
@@ -94,71 +104,58 @@ pub struct T5Config {
     pub pad_token_id: Option<i64>,
     pub relative_attention_num_buckets: i64,
     pub vocab_size: i64,
-    task_specific_params: TaskSpecificParams,
+    pub feed_forward_proj: Option<FeedForwardProj>,
+    pub tie_word_embeddings: Option<bool>,
+    /// Additional fields of the configuration file not used by the model
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
 }
 
-/// # T5 task-specific configurations
-/// Defines the T5 configuration for summarization and translation tasks
-#[derive(Debug, Serialize, Deserialize, Clone)]
-pub struct TaskSpecificParams {
-    summarization: Summarization,
-    translation_en_to_de: TranslationEnToDe,
-    translation_en_to_fr: TranslationEnToFr,
-    translation_en_to_ro: TranslationEnToRo,
-}
-
-/// # T5 summarization configuration
-#[derive(Debug, Serialize, Deserialize, Clone)]
-pub struct Summarization {
-    early_stopping: bool,
-    length_penalty: f64,
-    max_length: i64,
-    min_length: i64,
-    no_repeat_ngram_size: i64,
-    num_beams: i64,
-    prefix: String,
-}
-
-/// # T5 English to German configuration
-#[derive(Debug, Serialize, Deserialize, Clone)]
-pub struct TranslationEnToDe {
-    early_stopping: bool,
-    max_length: i64,
-    num_beams: i64,
-    prefix: String,
+#[derive(Clone, Copy, Debug, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "kebab-case")]
+/// # Feed-forward layer type used in the T5 blocks
+pub enum FeedForwardProj {
+    /// ReLU activated feed-forward layer (original T5 checkpoints)
+    Relu,
+    /// Gated GELU feed-forward layer (T5 v1.1 and LM-adapted checkpoints)
+    GatedGelu,
 }
 
-/// # T5 English to French configuration
-#[derive(Debug, Serialize, Deserialize, Clone)]
-pub struct TranslationEnToFr {
-    early_stopping: bool,
-    max_length: i64,
-    num_beams: i64,
-    prefix: String,
-}
+impl Config<T5Config> for T5Config {
+    fn extra_fields(&self) -> Option<&Map<String, Value>> {
+        Some(&self.extra)
+    }
 
-/// # T5 English to Romanian configuration
-#[derive(Debug, Serialize, Deserialize, Clone)]
-pub struct TranslationEnToRo {
-    early_stopping: bool,
-    max_length: i64,
-    num_beams: i64,
-    prefix: String,
+    fn validate(&self) -> Result<(), RustBertError> {
+        check_positive("num_heads", self.num_heads)?;
+        check_positive("d_kv", self.d_kv)?;
+        check_positive(
+            "relative_attention_num_buckets",
+            self.relative_attention_num_buckets,
+        )?;
+        check_token_id("eos_token_id", self.eos_token_id, self.vocab_size)?;
+        check_token_id("pad_token_id", self.pad_token_id, self.vocab_size)?;
+        check_token_id(
+            "decoder_start_token_id",
+            self.decoder_start_token_id,
+            self.vocab_size,
+        )
+    }
 }
 
-impl Config<T5Config> for T5Config {}
-
 /// # T5 Base model
 /// Base architecture for T5 model. Usually complemented with a task-specific head, such as a language model head.
 /// It is made of the following blocks:
 /// - `encoder`: `T5Stack` (transformer) made of a vector of encoding layers
 /// - `decoder`: `T5Stack` (transformer)  made of a vector of decoding layers with self attention and encoder cross-attention.
-/// caching is implemented for the decoder to avoid recalculating static states (encoder key/values and previously calculated decoder key/values)
+/// caching is implemented for the decoder to avoid recalculating static states (encoder key/values and previously calculated decoder key/values).
+/// The decoder is optional and may be loaded lazily for encoder-only usage (see `new_encoder_only` and `load_decoder`)
 /// - `embeddings`: `nn::Embedding` Shared embeddings for the encoder and decoder.
 pub struct T5Model {
     pub(crate) encoder: T5Stack,
-    decoder: T5Stack,
+    decoder: Option<T5Stack>,
     pub(crate) embeddings: nn::Embedding,
+    initializer_factor: f64,
 }
 
 impl T5Model {
@@ -218,22 +215,119 @@ impl T5Model {
             output_attentions,
             output_hidden_states,
         );
-        let decoder = T5Stack::new(
+        let decoder = Some(T5Stack::new(
             p / "decoder",
             config,
             true,
             true,
             output_attentions,
             output_hidden_states,
-        );
+        ));
 
         T5Model {
             encoder,
             decoder,
             embeddings,
+            initializer_factor: config.initializer_factor,
+        }
+    }
+
+    /// Build a new `T5Model` without decoder. The decoder variables are not created in the variable store,
+    /// halving the memory footprint for encoder-only usage. Weights loaded with `VarStore::load` from a full
+    /// checkpoint are then only materialized for the encoder and embeddings. The decoder can be added later using `load_decoder`.
+    ///
+    /// # Arguments
+    ///
+    /// * `p` - Variable store path for the root of the T5 model
+    /// * `config` - `T5Config` object defining the model architecture
+    /// * `output_attention` - flag indicating if the model should output the attention weights of intermediate layers
+    /// * `output_hidden_states` - flag indicating if the model should output the hidden states weights of intermediate layers
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use rust_bert::t5::{T5Config, T5Model};
+    /// use rust_bert::Config;
+    /// use std::path::Path;
+    /// use tch::{nn, Device};
+    ///
+    /// let config_path = Path::new("path/to/config.json");
+    /// let device = Device::Cpu;
+    /// let p = nn::VarStore::new(device);
+    /// let config = T5Config::from_file(config_path);
+    /// let t5: T5Model = T5Model::new_encoder_only(&p.root() / "t5", &config, false, false);
+    /// ```
+    pub fn new_encoder_only<'p, P>(
+        p: P,
+        config: &T5Config,
+        output_attentions: bool,
+        output_hidden_states: bool,
+    ) -> T5Model
+    where
+        P: Borrow<nn::Path<'p>>,
+    {
+        let p = p.borrow();
+
+        let embeddings: nn::Embedding = embedding(
+            p / "shared",
+            config.vocab_size,
+            config.d_model,
+            Default::default(),
+        );
+
+        let encoder = T5Stack::new(
+            p / "encoder",
+            config,
+            false,
+            false,
+            output_attentions,
+            output_hidden_states,
+        );
+
+        T5Model {
+            encoder,
+            decoder: None,
+            embeddings,
+            initializer_factor: config.initializer_factor,
         }
     }
 
+    /// Creates the decoder of a model built with `new_encoder_only`. This has no effect if the decoder is already loaded.
+    /// The decoder variables are created with their default initialization: the weights need to be loaded
+    /// into the variable store (e.g. using `VarStore::load`) after calling this method.
+    ///
+    /// # Arguments
+    ///
+    /// * `p` - Variable store path for the root of the T5 model (should match the path used for the model creation)
+    /// * `config` - `T5Config` object defining the model architecture
+    /// * `output_attention` - flag indicating if the decoder should output the attention weights of intermediate layers
+    /// * `output_hidden_states` - flag indicating if the decoder should output the hidden states weights of intermediate layers
+    pub fn load_decoder<'p, P>(
+        &mut self,
+        p: P,
+        config: &T5Config,
+        output_attentions: bool,
+        output_hidden_states: bool,
+    ) where
+        P: Borrow<nn::Path<'p>>,
+    {
+        if self.decoder.is_none() {
+            self.decoder = Some(T5Stack::new(
+                p.borrow() / "decoder",
+                config,
+                true,
+                true,
+                output_attentions,
+                output_hidden_states,
+            ));
+        }
+    }
+
+    /// Returns `true` if the decoder of the model is loaded
+    pub fn has_decoder(&self) -> bool {
+        self.decoder.is_some()
+    }
+
     /// Forward pass through the model
     ///
     /// # Arguments
@@ -243,7 +337,7 @@ impl T5Model {
     /// * `decoder_input_ids` - Optional input tensor of shape (*batch size*, *target_sequence_length*). This or `decoder_input_embeds` must be provided.
     /// * `encoder_outputs` - Optional tuple made of a tensor of shape (*batch size*, *source_sequence_length*, *encoder_hidden_dim*) and optional vectors of tensors of length *num_encoder_layers* with shape (*batch size*, *source_sequence_length*, *hidden_size*).
     /// These correspond to the encoder last hidden state and optional hidden states/attention weights for encoder layers. When provided, the encoder hidden state will not be recalculated. Useful for generation tasks.
-    /// * `decoder_attention_mask` - Optional attention mask of shape (*batch size*, *target_sequence_length*) for the decoder positions. Positions with a mask with value 0 will be masked.
+    /// * `decoder_attention_mask` - Optional attention mask of shape (*batch size*, *target_sequence_length*) for the decoder positions. Positions with a mask with value 0 will be masked. A mask of shape (*batch size*, *target_sequence_length*, *target_sequence_length*) replaces the causal mask (e.g. prefix-LM mask created with `prefix_lm_attention_mask`).
     /// * `input_embeds` - Optional input tensor of shape (*batch size*, *source_sequence_length*, *embeddings dimension*). This or `input_ids` must be provided.
     /// * `decoder_input_embeds` - Optional input tensor of shape (*batch size*, *target_sequence_length*, *embeddings dimension*). This or `decoder_input_ids` must be provided.
     /// * `old_layer_states` - Optional vector of length `num_layers` containing tuples of optional `LayerStates` containing the last calculated key and value pairs for the decoder. This avoids recomputing attention weights at past positions and speeds up decoding.
@@ -259,6 +353,7 @@ impl T5Model {
     ///   - `all_encoder_attentions` - `Option<Vec<Tensor>>` of length *num_encoder_layers* with shape (*batch size*, *source_sequence_length*, *hidden_size*)
     ///   - `all_decoder_hidden_states` - `Option<Vec<Tensor>>` of length *num_decoder_layers* with shape (*batch size*, *target_sequence_length*, *hidden_size*)
     ///   - `all_decoder_attentions` - `Option<Vec<Tensor>>` of length *num_decoder_layers* with shape (*batch size*, *target_sequence_length*, *hidden_size*)
+    ///   - `all_cross_attentions` - `Option<Vec<Tensor>>` of length *num_decoder_layers* with shape (*batch size*, *num_heads*, *target_sequence_length*, *source_sequence_length*)
     ///
     /// # Example
     ///
@@ -343,6 +438,8 @@ impl T5Model {
 
         let decoder_output = self
             .decoder
+            .as_ref()
+            .expect("The T5 decoder must be loaded (see `load_decoder`)")
             .forward_t(
                 decoder_input_ids,
                 decoder_attention_mask,
@@ -360,10 +457,26 @@ impl T5Model {
             next_cache: decoder_output.next_cache,
             all_decoder_hidden_states: decoder_output.all_hidden_states,
             all_decoder_attentions: decoder_output.all_attentions,
+            all_cross_attentions: decoder_output.all_cross_attentions,
             all_encoder_hidden_states,
             all_encoder_attentions,
         }
     }
+
+    /// Resizes the shared embeddings matrix of the encoder and decoder, e.g. after adding domain-specific tokens to
+    /// the vocabulary. The embeddings of the existing tokens are kept and the new embeddings are initialized from a
+    /// normal distribution with the standard deviation `initializer_factor` of the configuration.
+    ///
+    /// # Arguments
+    ///
+    /// * `new_num_tokens` - new size of the vocabulary (smaller sizes remove the last tokens of the vocabulary)
+    pub fn resize_token_embeddings(&mut self, new_num_tokens: i64) -> Result<(), RustBertError> {
+        resize_token_dimension(
+            &mut self.embeddings.ws,
+            new_num_tokens,
+            Some(self.initializer_factor),
+        )
+    }
 }
 
 /// # T5 Model for conditional generation
@@ -371,9 +484,14 @@ impl T5Model {
 /// It is made of the following blocks:
 /// - `base_model`: `T5Model` Base T5 model
 /// - `model_dim`: `f64` representation of the model dimension for scaling of the generated logits
+/// - `lm_head`: `LMHead` language model head, tied to the shared embeddings or a linear layer for checkpoints
+/// with untied word embeddings (e.g. T5 v1.1 and LM-adapted checkpoints)
+/// - `prefix`: Optional prefix-tuning keys and values prepended to the self-attention past of each decoder layer
 pub struct T5ForConditionalGeneration {
     base_model: T5Model,
     model_dim: f64,
+    lm_head: LMHead,
+    prefix: Option<PrefixTuning>,
 }
 
 impl T5ForConditionalGeneration {
@@ -419,11 +537,171 @@ impl T5ForConditionalGeneration {
         let p = p.borrow();
 
         let base_model = T5Model::new(p, config, output_attentions, output_hidden_states);
+        let lm_head = T5ForConditionalGeneration::build_lm_head(p, config);
+
+        T5ForConditionalGeneration {
+            base_model,
+            model_dim: config.d_model as f64,
+            lm_head,
+            prefix: None,
+        }
+    }
+
+    /// Build a new `T5ForConditionalGeneration` without decoder (see `T5Model::new_encoder_only`).
+    /// The model can be used for encoding but the decoder needs to be loaded with `load_decoder` before generating.
+    ///
+    /// # Arguments
+    ///
+    /// * `p` - Variable store path for the root of the T5 model
+    /// * `config` - `T5Config` object defining the model architecture
+    /// * `output_attention` - flag indicating if the model should output the attention weights of intermediate layers
+    /// * `output_hidden_states` - flag indicating if the model should output the hidden states weights of intermediate layers
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use rust_bert::t5::{T5Config, T5ForConditionalGeneration};
+    /// use rust_bert::Config;
+    /// use std::path::Path;
+    /// use tch::{nn, Device};
+    ///
+    /// let config_path = Path::new("path/to/config.json");
+    /// let weights_path = Path::new("path/to/model.ot");
+    /// let device = Device::Cpu;
+    /// let mut vs = nn::VarStore::new(device);
+    /// let config = T5Config::from_file(config_path);
+    /// let mut t5 = T5ForConditionalGeneration::new_encoder_only(&vs.root(), &config, false, false);
+    /// vs.load(weights_path)?;
+    /// // ... encoder-only usage ...
+    /// t5.load_decoder(&vs.root(), &config, false, false);
+    /// vs.load(weights_path)?;
+    /// # Ok::<(), tch::TchError>(())
+    /// ```
+    pub fn new_encoder_only<'p, P>(
+        p: P,
+        config: &T5Config,
+        output_attentions: bool,
+        output_hidden_states: bool,
+    ) -> T5ForConditionalGeneration
+    where
+        P: Borrow<nn::Path<'p>>,
+    {
+        let p = p.borrow();
+        let base_model =
+            T5Model::new_encoder_only(p, config, output_attentions, output_hidden_states);
+        let lm_head = T5ForConditionalGeneration::build_lm_head(p, config);
 
         T5ForConditionalGeneration {
             base_model,
             model_dim: config.d_model as f64,
+            lm_head,
+            prefix: None,
+        }
+    }
+
+    fn build_lm_head(p: &nn::Path, config: &T5Config) -> LMHead {
+        if config.tie_word_embeddings.unwrap_or(true) {
+            LMHead::Tied
+        } else {
+            LMHead::Linear(nn::linear(
+                p / "lm_head",
+                config.d_model,
+                config.vocab_size,
+                nn::LinearConfig {
+                    bias: false,
+                    ..Default::default()
+                },
+            ))
+        }
+    }
+
+    fn lm_logits(&self, decoder_output: &Tensor) -> Tensor {
+        let lm_logits = self
+            .lm_head
+            .forward(decoder_output, &self.base_model.embeddings.ws);
+        // the decoder output is rescaled when projected on the shared embeddings
+        if self.lm_head.is_tied() {
+            lm_logits * (self.model_dim.powf(-0.5))
+        } else {
+            lm_logits
+        }
+    }
+
+    /// Returns the language model head of the model
+    pub fn lm_head(&self) -> &LMHead {
+        &self.lm_head
+    }
+
+    /// Ties the language model head to the shared embeddings of the encoder and decoder. As for the original T5
+    /// checkpoints, the decoder output is rescaled by `d_model^-0.5` before the projection on the embeddings.
+    pub fn tie_lm_head(&mut self) {
+        self.lm_head = LMHead::Tied;
+    }
+
+    /// Unties the language model head from the shared embeddings, creating a linear layer initialized with the
+    /// embeddings scaled by `d_model^-0.5` so that the logits of the model are unchanged. The variables of the head are
+    /// created under the path provided (`{p}.weight`). This has no effect if the head is not tied.
+    ///
+    /// # Arguments
+    ///
+    /// * `p` - Variable store path for the new language model head
+    pub fn untie_lm_head<'p, P>(&mut self, p: P) -> Result<(), RustBertError>
+    where
+        P: Borrow<nn::Path<'p>>,
+    {
+        if self.lm_head.is_tied() {
+            let scaled_embeddings =
+                no_grad(|| &self.base_model.embeddings.ws * self.model_dim.powf(-0.5));
+            self.lm_head = LMHead::untied_from(p, &scaled_embeddings)?;
+        }
+        Ok(())
+    }
+
+    /// Replaces the language model head with the linear layer provided, of shape (*output_vocabulary_size*, *d_model*).
+    /// The decoder output is not rescaled for a linear head. With an output vocabulary different from the input
+    /// vocabulary, the generated ids are positions in the output vocabulary.
+    ///
+    /// # Arguments
+    ///
+    /// * `lm_head` - Linear layer projecting the decoder output on the output vocabulary
+    pub fn set_lm_head(&mut self, lm_head: nn::Linear) -> Result<(), RustBertError> {
+        LMHead::check_linear(&lm_head, self.base_model.embeddings.ws.size()[1])?;
+        self.lm_head = LMHead::Linear(lm_head);
+        Ok(())
+    }
+
+    /// Creates the decoder of a model built with `new_encoder_only` (see `T5Model::load_decoder`).
+    /// The weights need to be loaded into the variable store after calling this method.
+    pub fn load_decoder<'p, P>(
+        &mut self,
+        p: P,
+        config: &T5Config,
+        output_attentions: bool,
+        output_hidden_states: bool,
+    ) where
+        P: Borrow<nn::Path<'p>>,
+    {
+        self.base_model
+            .load_decoder(p, config, output_attentions, output_hidden_states);
+    }
+
+    /// Returns `true` if the decoder of the model is loaded
+    pub fn has_decoder(&self) -> bool {
+        self.base_model.has_decoder()
+    }
+
+    /// Sets (or removes if `None`) the prefix-tuning keys and values used for generation. When a prefix is set,
+    /// its keys and values are used as the initial self-attention cache of each decoder layer.
+    ///
+    /// # Arguments
+    ///
+    /// * `prefix` - Optional `PrefixTuning` with one key/value pair per decoder layer
+    pub fn set_prefix(&mut self, prefix: Option<PrefixTuning>) -> Result<(), RustBertError> {
+        if let Some(prefix) = &prefix {
+            prefix.check_num_layers(self.base_model.encoder.num_layers())?;
         }
+        self.prefix = prefix;
+        Ok(())
     }
 
     /// Forward pass through the model
@@ -435,7 +713,7 @@ impl T5ForConditionalGeneration {
     /// * `decoder_input_ids` - Optional input tensor of shape (*batch size*, *target_sequence_length*). This or `decoder_input_embeds` must be provided.
     /// * `encoder_outputs` - Optional tuple made of a tensor of shape (*batch size*, *source_sequence_length*, *encoder_hidden_dim*) and optional vectors of tensors of length *num_encoder_layers* with shape (*batch size*, *source_sequence_length*, *hidden_size*).
     /// These correspond to the encoder last hidden state and optional hidden states/attention weights for encoder layers. When provided, the encoder hidden state will not be recalculated. Useful for generation tasks.
-    /// * `decoder_attention_mask` - Optional attention mask of shape (*batch size*, *target_sequence_length*) for the decoder positions. Positions with a mask with value 0 will be masked.
+    /// * `decoder_attention_mask` - Optional attention mask of shape (*batch size*, *target_sequence_length*) for the decoder positions. Positions with a mask with value 0 will be masked. A mask of shape (*batch size*, *target_sequence_length*, *target_sequence_length*) replaces the causal mask (e.g. prefix-LM mask created with `prefix_lm_attention_mask`).
     /// * `input_embeds` - Optional input tensor of shape (*batch size*, *source_sequence_length*, *embeddings dimension*). This or `input_ids` must be provided.
     /// * `decoder_input_embeds` - Optional input tensor of shape (*batch size*, *target_sequence_length*, *embeddings dimension*). This or `decoder_input_ids` must be provided.
     /// * `old_layer_states` - Optional vector of length `num_layers` containing tuples of optional `LayerStates` containing th elast calculated key and value pairs for the decoder. This avoids recomputing attention weights at past positions and speeds up decoding.
@@ -451,6 +729,7 @@ impl T5ForConditionalGeneration {
     ///   - `all_encoder_attentions` - `Option<Vec<Tensor>>` of length *num_encoder_layers* with shape (*batch size*, *source_sequence_length*, *hidden_size*)
     ///   - `all_decoder_hidden_states` - `Option<Vec<Tensor>>` of length *num_decoder_layers* with shape (*batch size*, *target_sequence_length*, *hidden_size*)
     ///   - `all_decoder_attentions` - `Option<Vec<Tensor>>` of length *num_decoder_layers* with shape (*batch size*, *target_sequence_length*, *hidden_size*)
+    ///   - `all_cross_attentions` - `Option<Vec<Tensor>>` of length *num_decoder_layers* with shape (*batch size*, *num_heads*, *target_sequence_length*, *source_sequence_length*)
     ///
     /// # Example
     ///
@@ -511,10 +790,7 @@ impl T5ForConditionalGeneration {
             old_layer_states,
             train,
         );
-        let lm_logits = base_model_output
-            .decoder_output
-            .linear::<Tensor>(&self.base_model.embeddings.ws, None)
-            * (self.model_dim.powf(-0.5));
+        let lm_logits = self.lm_logits(&base_model_output.decoder_output);
 
         T5ModelOutput {
             decoder_output: lm_logits,
@@ -538,6 +814,32 @@ impl T5ForConditionalGeneration {
             .unwrap()
             .hidden_state
     }
+
+    /// Resizes the shared embeddings matrix and, for checkpoints with untied word embeddings, the language model head
+    /// of the model to `new_num_tokens` tokens, keeping the weights of the existing tokens.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// # fn main() -> anyhow::Result<()> {
+    /// use rust_bert::t5::{T5Config, T5ForConditionalGeneration};
+    /// use rust_bert::Config;
+    /// use std::path::Path;
+    /// use tch::{nn, Device};
+    ///
+    /// let mut vs = nn::VarStore::new(Device::Cpu);
+    /// let config = T5Config::from_file(Path::new("path/to/config.json"));
+    /// let mut t5_model = T5ForConditionalGeneration::new(&vs.root(), &config, false, false);
+    /// vs.load("path/to/model.ot")?;
+    /// t5_model.resize_token_embeddings(config.vocab_size + 100)?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn resize_token_embeddings(&mut self, new_num_tokens: i64) -> Result<(), RustBertError> {
+        self.base_model.resize_token_embeddings(new_num_tokens)?;
+        self.lm_head
+            .resize(new_num_tokens, self.base_model.initializer_factor)
+    }
 }
 
 impl LMHeadModel for T5ForConditionalGeneration {
@@ -610,6 +912,39 @@ impl LMHeadModel for T5ForConditionalGeneration {
         decoder_input_ids: &Option<Tensor>,
         train: bool,
     ) -> Result<LMModelOutput, RustBertError> {
+        if !self.base_model.has_decoder() {
+            return Err(RustBertError::ValueError(
+                "The T5 decoder must be loaded (see `load_decoder`) before generating".into(),
+            ));
+        }
+        let cache = match (&self.prefix, cache) {
+            (Some(prefix), Cache::T5Cache(None)) | (Some(prefix), Cache::None) => {
+                let batch_size = match decoder_input_ids {
+                    Some(decoder_input_ids) => decoder_input_ids.size()[0],
+                    None => {
+                        return Err(RustBertError::ValueError(
+                            "Decoder input ids must be provided when using a prefix".into(),
+                        ));
+                    }
+                };
+                Cache::T5Cache(Some(
+                    prefix
+                        .batch_key_values(batch_size)
+                        .into_iter()
+                        .map(|(prev_key, prev_value)| {
+                            (
+                                Some(LayerState {
+                                    prev_key,
+                                    prev_value,
+                                }),
+                                None,
+                            )
+                        })
+                        .collect(),
+                ))
+            }
+            (_, cache) => cache,
+        };
         let base_model_output = match cache {
             Cache::T5Cache(cached_layer_states) => self.base_model.forward_t(
                 input_ids.as_ref(),
@@ -640,10 +975,7 @@ impl LMHeadModel for T5ForConditionalGeneration {
             }
         };
 
-        let lm_logits = base_model_output
-            .decoder_output
-            .linear::<Tensor>(&self.base_model.embeddings.ws, None)
-            * (self.model_dim.powf(-0.5));
+        let lm_logits = self.lm_logits(&base_model_output.decoder_output);
 
         Ok(LMModelOutput {
             lm_logits,
@@ -652,6 +984,110 @@ impl LMHeadModel for T5ForConditionalGeneration {
     }
 }
 
+impl EncoderOnlyModel for T5ForConditionalGeneration {
+    fn encode(&self, input_ids: &Tensor, attention_mask: Option<&Tensor>) -> Tensor {
+        T5ForConditionalGeneration::encode(self, input_ids, attention_mask)
+    }
+}
+
+impl Seq2SeqGenerationModel for T5ForConditionalGeneration {
+    fn decode_step(
+        &self,
+        decoder_input_ids: &Tensor,
+        encoder_outputs: &Tensor,
+        attention_mask: Option<&Tensor>,
+        cache: Cache,
+    ) -> Result<LMModelOutput, RustBertError> {
+        let (decoder_input_ids, cache) = match cache {
+            Cache::T5Cache(Some(past)) => (
+                decoder_input_ids.narrow(1, -1, 1),
+                Cache::T5Cache(Some(past)),
+            ),
+            Cache::T5Cache(None) | Cache::None => {
+                (decoder_input_ids.shallow_clone(), Cache::T5Cache(None))
+            }
+            _ => {
+                return Err(RustBertError::ValueError(
+                    "Cache type incompatible with T5".into(),
+                ));
+            }
+        };
+        LMHeadModel::forward_t(
+            self,
+            &None,
+            cache,
+            &attention_mask.map(|mask| mask.shallow_clone()),
+            &None,
+            &None,
+            &None,
+            Some(encoder_outputs),
+            &Some(decoder_input_ids),
+            false,
+        )
+    }
+
+    fn reorder_cache(&self, cache: &mut Cache, beam_indices: &Tensor) {
+        match cache {
+            Cache::T5Cache(Some(old_cache)) => {
+                for (self_layer_state, encoder_layer_state) in old_cache.iter_mut() {
+                    if let Some(self_layer_state) = self_layer_state {
+                        self_layer_state.reorder_cache(beam_indices)
+                    };
+                    if let Some(encoder_layer_state) = encoder_layer_state {
+                        encoder_layer_state.reorder_cache(beam_indices)
+                    };
+                }
+            }
+            Cache::T5Cache(None) | Cache::None => {}
+            _ => {
+                panic!("Invalid cache for T5 model");
+            }
+        };
+    }
+}
+
+/// Builds a prefix-LM attention mask: positions attend bidirectionally over the prefix (prompt) and causally over
+/// the continuation. The resulting 3-dimensional mask is used as-is by the T5 stacks, in place of the default
+/// (bidirectional for the encoder, causal for the decoder) mask, for example as the `decoder_attention_mask` of
+/// `T5Model::forward_t` to run LM-adapted checkpoints in prefix-LM mode. The mask covers full sequences and is not
+/// compatible with incremental decoding using cached key/value states.
+///
+/// # Arguments
+///
+/// * `attention_mask` - Padding mask of shape (*batch size*, *sequence_length*). Positions with value 0 are masked.
+/// * `prefix_lengths` - Length of the prefix for each sequence of the batch
+///
+/// # Returns
+///
+/// * `Tensor` of shape (*batch size*, *sequence_length*, *sequence_length*) with value 1 for the key positions
+/// (last dimension) visible from each query position (second dimension), 0 otherwise
+///
+/// # Example
+///
+/// ```no_run
+/// use rust_bert::t5::prefix_lm_attention_mask;
+/// use tch::{Device, Kind, Tensor};
+///
+/// let attention_mask = Tensor::ones(&[2, 12], (Kind::Int64, Device::Cpu));
+/// let mask = prefix_lm_attention_mask(&attention_mask, &[4, 7]);
+/// ```
+pub fn prefix_lm_attention_mask(attention_mask: &Tensor, prefix_lengths: &[i64]) -> Tensor {
+    let sequence_length = attention_mask.size()[1];
+    let device = attention_mask.device();
+    let positions = Tensor::arange(sequence_length, (Kind::Int64, device));
+    let causal_mask = positions
+        .unsqueeze(0)
+        .le1(&positions.unsqueeze(-1))
+        .unsqueeze(0)
+        .to_kind(Kind::Int64);
+    let prefix_mask = positions
+        .unsqueeze(0)
+        .lt1(&Tensor::of_slice(prefix_lengths).to(device).unsqueeze(-1))
+        .unsqueeze(1)
+        .to_kind(Kind::Int64);
+    (causal_mask + prefix_mask).clamp_max(1i64) * attention_mask.to_kind(Kind::Int64).unsqueeze(1)
+}
+
 /// Container holding a T5 model output. The decoder output may hold the hidden state of
 /// the last layer of the decoder, or may hold logits for a custom head module after the
 /// decoder (e.g. for language modeling tasks)
@@ -667,8 +1103,24 @@ pub struct T5ModelOutput {
     pub all_decoder_hidden_states: Option<Vec<Tensor>>,
     /// Attention weights for all layers of the decoder
     pub all_decoder_attentions: Option<Vec<Tensor>>,
+    /// Cross-attention weights of the decoder over the encoder positions for all layers of the decoder
+    pub all_cross_attentions: Option<Vec<Tensor>>,
     /// Hidden states for all layers of the encoder
     pub all_encoder_hidden_states: Option<Vec<Tensor>>,
     /// Attention weights for all layers of the encoder
     pub all_encoder_attentions: Option<Vec<Tensor>>,
 }
+
+impl ModelOutput for T5ModelOutput {
+    fn hidden_states(&self) -> Option<Vec<&Tensor>> {
+        tensor_refs(&self.all_decoder_hidden_states)
+    }
+
+    fn attentions(&self) -> Option<Vec<&Tensor>> {
+        tensor_refs(&self.all_decoder_attentions)
+    }
+
+    fn logits(&self) -> Option<&Tensor> {
+        Some(&self.decoder_output)
+    }
+}
@@ -0,0 +1,396 @@
+// Copyright 2021 The Fairseq Authors and the HuggingFace Inc. team.
+// Copyright 2021 Guillaume Becquin
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//     http://www.apache.org/licenses/LICENSE-2.0
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use crate::common::activations::{Activation, TensorFunction};
+use crate::common::dropout::Dropout;
+use crate::wav2vec2::wav2vec2_model::Wav2Vec2Config;
+use std::borrow::{Borrow, BorrowMut};
+use tch::kind::Kind::Float;
+use tch::{nn, Tensor};
+
+/// Convolutional relative position embedding. The convolution weights are stored with weight normalization
+/// (`weight_g`, `weight_v`) as in the reference checkpoints.
+pub struct Wav2Vec2PositionalConvEmbedding {
+    weight_g: Tensor,
+    weight_v: Tensor,
+    bias: Tensor,
+    padding: i64,
+    groups: i64,
+    remove_last_frame: bool,
+    activation: TensorFunction,
+}
+
+impl Wav2Vec2PositionalConvEmbedding {
+    pub fn new<'p, P>(p: P, config: &Wav2Vec2Config) -> Wav2Vec2PositionalConvEmbedding
+    where
+        P: Borrow<nn::Path<'p>>,
+    {
+        let p = p.borrow() / "conv";
+        let kernel_size = config.num_conv_pos_embeddings;
+        let groups = config.num_conv_pos_embedding_groups;
+
+        let weight_g = p.ones("weight_g", &[1, 1, kernel_size]);
+        let weight_v = p.randn_standard(
+            "weight_v",
+            &[config.hidden_size, config.hidden_size / groups, kernel_size],
+        );
+        let bias = p.zeros("bias", &[config.hidden_size]);
+
+        Wav2Vec2PositionalConvEmbedding {
+            weight_g,
+            weight_v,
+            bias,
+            padding: kernel_size / 2,
+            groups,
+            remove_last_frame: kernel_size % 2 == 0,
+            activation: Activation::gelu.get_function(),
+        }
+    }
+
+    pub fn forward(&self, hidden_states: &Tensor) -> Tensor {
+        // Weight normalization over the kernel dimension: w = g * v / ||v||
+        let weight = &self.weight_v * &self.weight_g
+            / (&self.weight_v * &self.weight_v)
+                .sum1(&[0, 1], true, Float)
+                .sqrt()
+                .clamp_min(1e-12);
+        let hidden_states = hidden_states.transpose(1, 2).conv1d(
+            &weight,
+            Some(&self.bias),
+            &[1],
+            &[self.padding],
+            &[1],
+            self.groups,
+        );
+        let hidden_states = if self.remove_last_frame {
+            let num_frames = hidden_states.size()[2];
+            hidden_states.narrow(2, 0, num_frames - 1)
+        } else {
+            hidden_states
+        };
+        (self.activation.get_fn())(&hidden_states).transpose(1, 2)
+    }
+}
+
+pub struct Wav2Vec2Attention {
+    num_heads: i64,
+    head_dim: i64,
+    scaling: f64,
+    q_proj: nn::Linear,
+    k_proj: nn::Linear,
+    v_proj: nn::Linear,
+    out_proj: nn::Linear,
+    dropout: Dropout,
+    output_attentions: bool,
+}
+
+impl Wav2Vec2Attention {
+    pub fn new<'p, P>(p: P, config: &Wav2Vec2Config) -> Wav2Vec2Attention
+    where
+        P: Borrow<nn::Path<'p>>,
+    {
+        let p = p.borrow();
+        let embed_dim = config.hidden_size;
+
+        let q_proj = nn::linear(p / "q_proj", embed_dim, embed_dim, Default::default());
+        let k_proj = nn::linear(p / "k_proj", embed_dim, embed_dim, Default::default());
+        let v_proj = nn::linear(p / "v_proj", embed_dim, embed_dim, Default::default());
+        let out_proj = nn::linear(p / "out_proj", embed_dim, embed_dim, Default::default());
+        let head_dim = embed_dim / config.num_attention_heads;
+
+        Wav2Vec2Attention {
+            num_heads: config.num_attention_heads,
+            head_dim,
+            scaling: (head_dim as f64).powf(-0.5),
+            q_proj,
+            k_proj,
+            v_proj,
+            out_proj,
+            dropout: Dropout::new(config.attention_dropout.unwrap_or(0.1)),
+            output_attentions: config.output_attentions.unwrap_or(false),
+        }
+    }
+
+    fn split_heads(&self, x: Tensor, batch_size: i64) -> Tensor {
+        x.view((batch_size, -1, self.num_heads, self.head_dim))
+            .transpose(1, 2)
+    }
+
+    pub fn forward_t(
+        &self,
+        hidden_states: &Tensor,
+        attention_mask: Option<&Tensor>,
+        train: bool,
+    ) -> (Tensor, Option<Tensor>) {
+        let (batch_size, num_frames, embed_dim) = hidden_states.size3().unwrap();
+
+        let query = self.split_heads(hidden_states.apply(&self.q_proj) * self.scaling, batch_size);
+        let key = self.split_heads(hidden_states.apply(&self.k_proj), batch_size);
+        let value = self.split_heads(hidden_states.apply(&self.v_proj), batch_size);
+
+        let mut scores = query.matmul(&key.transpose(-1, -2));
+        if let Some(mask) = attention_mask {
+            scores = scores + mask;
+        }
+        let weights = scores.softmax(-1, Float);
+        let probs = weights.apply_t(&self.dropout, train);
+        let context = probs
+            .matmul(&value)
+            .transpose(1, 2)
+            .contiguous()
+            .view((batch_size, num_frames, embed_dim))
+            .apply(&self.out_proj);
+
+        let attention_weights = if self.output_attentions {
+            Some(weights)
+        } else {
+            None
+        };
+        (context, attention_weights)
+    }
+}
+
+pub struct Wav2Vec2FeedForward {
+    intermediate_dense: nn::Linear,
+    output_dense: nn::Linear,
+    activation: TensorFunction,
+    intermediate_dropout: Dropout,
+    output_dropout: Dropout,
+}
+
+impl Wav2Vec2FeedForward {
+    pub fn new<'p, P>(p: P, config: &Wav2Vec2Config) -> Wav2Vec2FeedForward
+    where
+        P: Borrow<nn::Path<'p>>,
+    {
+        let p = p.borrow();
+
+        let intermediate_dense = nn::linear(
+            p / "intermediate_dense",
+            config.hidden_size,
+            config.intermediate_size,
+            Default::default(),
+        );
+        let output_dense = nn::linear(
+            p / "output_dense",
+            config.intermediate_size,
+            config.hidden_size,
+            Default::default(),
+        );
+
+        Wav2Vec2FeedForward {
+            intermediate_dense,
+            output_dense,
+            activation: config.hidden_act.get_function(),
+            intermediate_dropout: Dropout::new(config.activation_dropout.unwrap_or(0.1)),
+            output_dropout: Dropout::new(config.hidden_dropout.unwrap_or(0.1)),
+        }
+    }
+
+    pub fn forward_t(&self, hidden_states: &Tensor, train: bool) -> Tensor {
+        let hidden_states = hidden_states.apply(&self.intermediate_dense);
+        (self.activation.get_fn())(&hidden_states)
+            .apply_t(&self.intermediate_dropout, train)
+            .apply(&self.output_dense)
+            .apply_t(&self.output_dropout, train)
+    }
+}
+
+pub struct Wav2Vec2EncoderLayer {
+    attention: Wav2Vec2Attention,
+    dropout: Dropout,
+    layer_norm: nn::LayerNorm,
+    feed_forward: Wav2Vec2FeedForward,
+    final_layer_norm: nn::LayerNorm,
+    pre_layer_norm: bool,
+}
+
+impl Wav2Vec2EncoderLayer {
+    pub fn new<'p, P>(p: P, config: &Wav2Vec2Config) -> Wav2Vec2EncoderLayer
+    where
+        P: Borrow<nn::Path<'p>>,
+    {
+        let p = p.borrow();
+
+        let layer_norm_config = nn::LayerNormConfig {
+            eps: config.layer_norm_eps.unwrap_or(1e-5),
+            ..Default::default()
+        };
+        let attention = Wav2Vec2Attention::new(p / "attention", config);
+        let layer_norm = nn::layer_norm(
+            p / "layer_norm",
+            vec![config.hidden_size],
+            layer_norm_config,
+        );
+        let feed_forward = Wav2Vec2FeedForward::new(p / "feed_forward", config);
+        let final_layer_norm = nn::layer_norm(
+            p / "final_layer_norm",
+            vec![config.hidden_size],
+            layer_norm_config,
+        );
+
+        Wav2Vec2EncoderLayer {
+            attention,
+            dropout: Dropout::new(config.hidden_dropout.unwrap_or(0.1)),
+            layer_norm,
+            feed_forward,
+            final_layer_norm,
+            pre_layer_norm: config.do_stable_layer_norm,
+        }
+    }
+
+    pub fn forward_t(
+        &self,
+        hidden_states: &Tensor,
+        attention_mask: Option<&Tensor>,
+        train: bool,
+    ) -> (Tensor, Option<Tensor>) {
+        if self.pre_layer_norm {
+            let (attention_output, attention_weights) = self.attention.forward_t(
+                &hidden_states.apply(&self.layer_norm),
+                attention_mask,
+                train,
+            );
+            let hidden_states = hidden_states + attention_output.apply_t(&self.dropout, train);
+            let hidden_states = &hidden_states
+                + self
+                    .feed_forward
+                    .forward_t(&hidden_states.apply(&self.final_layer_norm), train);
+            (hidden_states, attention_weights)
+        } else {
+            let (attention_output, attention_weights) =
+                self.attention
+                    .forward_t(hidden_states, attention_mask, train);
+            let hidden_states = (hidden_states + attention_output.apply_t(&self.dropout, train))
+                .apply(&self.layer_norm);
+            let hidden_states = (&hidden_states
+                + self.feed_forward.forward_t(&hidden_states, train))
+            .apply(&self.final_layer_norm);
+            (hidden_states, attention_weights)
+        }
+    }
+}
+
+pub struct Wav2Vec2Encoder {
+    pos_conv_embed: Wav2Vec2PositionalConvEmbedding,
+    layer_norm: nn::LayerNorm,
+    dropout: Dropout,
+    layers: Vec<Wav2Vec2EncoderLayer>,
+    pre_layer_norm: bool,
+    output_attentions: bool,
+    output_hidden_states: bool,
+}
+
+impl Wav2Vec2Encoder {
+    pub fn new<'p, P>(p: P, config: &Wav2Vec2Config) -> Wav2Vec2Encoder
+    where
+        P: Borrow<nn::Path<'p>>,
+    {
+        let p = p.borrow();
+
+        let pos_conv_embed = Wav2Vec2PositionalConvEmbedding::new(p / "pos_conv_embed", config);
+        let layer_norm_config = nn::LayerNormConfig {
+            eps: config.layer_norm_eps.unwrap_or(1e-5),
+            ..Default::default()
+        };
+        let layer_norm = nn::layer_norm(
+            p / "layer_norm",
+            vec![config.hidden_size],
+            layer_norm_config,
+        );
+        let p_layers = p / "layers";
+        let layers = (0..config.num_hidden_layers)
+            .map(|layer_index| Wav2Vec2EncoderLayer::new(&p_layers / layer_index, config))
+            .collect();
+
+        Wav2Vec2Encoder {
+            pos_conv_embed,
+            layer_norm,
+            dropout: Dropout::new(config.hidden_dropout.unwrap_or(0.1)),
+            layers,
+            pre_layer_norm: config.do_stable_layer_norm,
+            output_attentions: config.output_attentions.unwrap_or(false),
+            output_hidden_states: config.output_hidden_states.unwrap_or(false),
+        }
+    }
+
+    pub fn forward_t(
+        &self,
+        hidden_states: &Tensor,
+        frame_mask: Option<&Tensor>,
+        train: bool,
+    ) -> Wav2Vec2EncoderOutput {
+        let (hidden_states, attention_mask) = match frame_mask {
+            Some(frame_mask) => {
+                let hidden_states =
+                    hidden_states * frame_mask.to_kind(hidden_states.kind()).unsqueeze(-1);
+                let attention_mask = ((frame_mask.to_kind(Float).ones_like()
+                    - frame_mask.to_kind(Float))
+                    * -10000.0)
+                    .unsqueeze(1)
+                    .unsqueeze(1);
+                (hidden_states, Some(attention_mask))
+            }
+            None => (hidden_states.shallow_clone(), None),
+        };
+
+        let mut hidden_state = &hidden_states + self.pos_conv_embed.forward(&hidden_states);
+        if !self.pre_layer_norm {
+            hidden_state = hidden_state.apply(&self.layer_norm);
+        }
+        hidden_state = hidden_state.apply_t(&self.dropout, train);
+
+        let mut all_hidden_states: Option<Vec<Tensor>> = if self.output_hidden_states {
+            Some(vec![])
+        } else {
+            None
+        };
+        let mut all_attentions: Option<Vec<Tensor>> = if self.output_attentions {
+            Some(vec![])
+        } else {
+            None
+        };
+
+        for layer in &self.layers {
+            if let Some(hidden_states) = all_hidden_states.borrow_mut() {
+                hidden_states.push(hidden_state.shallow_clone());
+            };
+            let temp = layer.forward_t(&hidden_state, attention_mask.as_ref(), train);
+            hidden_state = temp.0;
+            if let Some(attentions) = all_attentions.borrow_mut() {
+                attentions.push(temp.1.unwrap());
+            };
+        }
+        if self.pre_layer_norm {
+            hidden_state = hidden_state.apply(&self.layer_norm);
+        }
+        if let Some(hidden_states) = all_hidden_states.borrow_mut() {
+            hidden_states.push(hidden_state.shallow_clone());
+        };
+
+        Wav2Vec2EncoderOutput {
+            hidden_state,
+            all_hidden_states,
+            all_attentions,
+        }
+    }
+}
+
+/// Container for the Wav2Vec2 encoder output.
+pub struct Wav2Vec2EncoderOutput {
+    /// Last hidden states from the encoder
+    pub hidden_state: Tensor,
+    /// Hidden states for all intermediate layers
+    pub all_hidden_states: Option<Vec<Tensor>>,
+    /// Attention weights for all intermediate layers
+    pub all_attentions: Option<Vec<Tensor>>,
+}
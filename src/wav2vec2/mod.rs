@@ -0,0 +1,54 @@
+//! # Wav2Vec2 (Baevski et al.)
+//!
+//! Implementation of the Wav2Vec2 speech model ([wav2vec 2.0: A Framework for Self-Supervised Learning of Speech Representations](https://arxiv.org/abs/2006.11477) Baevski, Zhou, Mohamed, Auli, 2020).
+//! The base model is implemented in the `wav2vec2_model::Wav2Vec2Model` struct. The model operates on raw 16kHz mono waveforms
+//! and does not use a tokenizer. The following task-specific model is available:
+//! - `Wav2Vec2ForSequenceClassification` for utterance classification (keyword spotting, language identification, audio event detection)
+//!
+//! Both the base (`feat_extract_norm: "group"`) and large/stable layer norm (`feat_extract_norm: "layer"`, `do_stable_layer_norm: true`)
+//! variants are supported. Derived architectures sharing the same weights layout (e.g. XLS-R) can be loaded as well.
+//!
+//! # Model set-up and pre-trained weights loading
+//!
+//! No pretrained weights are currently provided as remote resources: the weights of a `Wav2Vec2ForSequenceClassification`
+//! checkpoint need to be converted using the `utils/convert_model.py` script (keeping the `wav2vec2.` prefix of the variable names).
+//! The use of the `AudioClassificationModel` pipeline is recommended, as it takes care of the input normalization and padding.
+//!
+//! ```no_run
+//! # fn main() -> anyhow::Result<()> {
+//! #
+//! use tch::{nn, no_grad, Device, Kind, Tensor};
+//! #
+//! use rust_bert::resources::{LocalResource, Resource};
+//! use rust_bert::wav2vec2::{Wav2Vec2Config, Wav2Vec2ForSequenceClassification};
+//! use rust_bert::Config;
+//! use std::path::PathBuf;
+//!
+//! let config_resource = Resource::Local(LocalResource {
+//!     local_path: PathBuf::from("path/to/config.json"),
+//! });
+//! let weights_resource = Resource::Local(LocalResource {
+//!     local_path: PathBuf::from("path/to/model.ot"),
+//! });
+//! let config_path = config_resource.get_local_path()?;
+//! let weights_path = weights_resource.get_local_path()?;
+//! let device = Device::cuda_if_available();
+//! let mut vs = nn::VarStore::new(device);
+//! let config = Wav2Vec2Config::from_file(config_path);
+//! let wav2vec2_model = Wav2Vec2ForSequenceClassification::new(&vs.root(), &config);
+//! vs.load(weights_path)?;
+//!
+//! let input_values = Tensor::zeros(&[1, 16000], (Kind::Float, device));
+//! let logits = no_grad(|| wav2vec2_model.forward_t(&input_values, None, false)).logits;
+//! # Ok(())
+//! # }
+//! ```
+
+mod encoder;
+mod wav2vec2_model;
+
+pub use encoder::Wav2Vec2EncoderOutput;
+pub use wav2vec2_model::{
+    FeatureExtractNorm, Wav2Vec2Config, Wav2Vec2FeatureEncoder, Wav2Vec2ForSequenceClassification,
+    Wav2Vec2Model, Wav2Vec2ModelOutput, Wav2Vec2SequenceClassificationOutput,
+};
@@ -0,0 +1,596 @@
+// Copyright 2021 The Fairseq Authors and the HuggingFace Inc. team.
+// Copyright 2021 Guillaume Becquin
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//     http://www.apache.org/licenses/LICENSE-2.0
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use crate::common::activations::{Activation, TensorFunction};
+use crate::common::config::{check_divisible, check_positive, deserialize_id2label};
+use crate::common::dropout::Dropout;
+use crate::common::model_output::{tensor_refs, ModelOutput};
+use crate::wav2vec2::encoder::Wav2Vec2Encoder;
+use crate::{Config, RustBertError};
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use std::borrow::Borrow;
+use std::collections::HashMap;
+use tch::kind::Kind::{Float, Int64};
+use tch::{nn, Kind, Tensor};
+
+#[allow(non_camel_case_types)]
+#[derive(Clone, Debug, Serialize, Deserialize, Copy, PartialEq)]
+/// # Normalization applied to the convolutional feature encoder
+pub enum FeatureExtractNorm {
+    /// Group normalization of the first convolutional layer only (wav2vec2 base models)
+    group,
+    /// Layer normalization of every convolutional layer (wav2vec2 large models and their derivatives)
+    layer,
+}
+
+fn default_conv_dim() -> Vec<i64> {
+    vec![512; 7]
+}
+
+fn default_conv_kernel() -> Vec<i64> {
+    vec![10, 3, 3, 3, 3, 2, 2]
+}
+
+fn default_conv_stride() -> Vec<i64> {
+    vec![5, 2, 2, 2, 2, 2, 2]
+}
+
+fn default_num_conv_pos_embeddings() -> i64 {
+    128
+}
+
+fn default_num_conv_pos_embedding_groups() -> i64 {
+    16
+}
+
+fn default_classifier_proj_size() -> i64 {
+    256
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+/// # Wav2Vec2 model configuration
+/// Defines the Wav2Vec2 model architecture (e.g. convolutional feature encoder, number of layers, hidden layer size, label mapping...)
+pub struct Wav2Vec2Config {
+    pub hidden_size: i64,
+    pub num_hidden_layers: i64,
+    pub num_attention_heads: i64,
+    pub intermediate_size: i64,
+    pub hidden_act: Activation,
+    pub hidden_dropout: Option<f64>,
+    pub attention_dropout: Option<f64>,
+    pub activation_dropout: Option<f64>,
+    pub feat_proj_dropout: Option<f64>,
+    pub layer_norm_eps: Option<f64>,
+    pub feat_extract_norm: FeatureExtractNorm,
+    pub feat_extract_activation: Option<Activation>,
+    #[serde(default = "default_conv_dim")]
+    pub conv_dim: Vec<i64>,
+    #[serde(default = "default_conv_kernel")]
+    pub conv_kernel: Vec<i64>,
+    #[serde(default = "default_conv_stride")]
+    pub conv_stride: Vec<i64>,
+    #[serde(default)]
+    pub conv_bias: bool,
+    #[serde(default = "default_num_conv_pos_embeddings")]
+    pub num_conv_pos_embeddings: i64,
+    #[serde(default = "default_num_conv_pos_embedding_groups")]
+    pub num_conv_pos_embedding_groups: i64,
+    /// Apply the layer normalization before the attention and feed-forward blocks (wav2vec2 large models)
+    #[serde(default)]
+    pub do_stable_layer_norm: bool,
+    /// Size of the projection applied before pooling for sequence classification
+    #[serde(default = "default_classifier_proj_size")]
+    pub classifier_proj_size: i64,
+    /// Use a learned weighted average of the hidden states of all layers for sequence classification
+    #[serde(default)]
+    pub use_weighted_layer_sum: bool,
+    pub output_attentions: Option<bool>,
+    pub output_hidden_states: Option<bool>,
+    #[serde(default, deserialize_with = "deserialize_id2label")]
+    pub id2label: Option<HashMap<i64, String>>,
+    pub label2id: Option<HashMap<String, i64>>,
+    /// Additional fields of the configuration file not used by the model
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+}
+
+impl Config<Wav2Vec2Config> for Wav2Vec2Config {
+    fn extra_fields(&self) -> Option<&Map<String, Value>> {
+        Some(&self.extra)
+    }
+
+    fn validate(&self) -> Result<(), RustBertError> {
+        check_divisible(
+            "hidden_size",
+            self.hidden_size,
+            "num_attention_heads",
+            self.num_attention_heads,
+        )?;
+        check_divisible(
+            "hidden_size",
+            self.hidden_size,
+            "num_conv_pos_embedding_groups",
+            self.num_conv_pos_embedding_groups,
+        )?;
+        if (self.conv_dim.len() != self.conv_kernel.len())
+            | (self.conv_dim.len() != self.conv_stride.len())
+            | self.conv_dim.is_empty()
+        {
+            return Err(RustBertError::InvalidConfigurationError(format!(
+                "`conv_dim` ({}), `conv_kernel` ({}) and `conv_stride` ({}) must have the same non-zero length",
+                self.conv_dim.len(),
+                self.conv_kernel.len(),
+                self.conv_stride.len()
+            )));
+        }
+        for (kernel, stride) in self.conv_kernel.iter().zip(self.conv_stride.iter()) {
+            check_positive("conv_kernel", *kernel)?;
+            check_positive("conv_stride", *stride)?;
+        }
+        Ok(())
+    }
+}
+
+impl Wav2Vec2Config {
+    /// Returns the number of frames produced by the convolutional feature encoder for inputs of the lengths provided
+    /// (number of samples)
+    ///
+    /// # Arguments
+    ///
+    /// * `input_lengths` - Tensor of shape (*batch size*) containing the number of samples of each input
+    pub fn feature_lengths(&self, input_lengths: &Tensor) -> Tensor {
+        let mut lengths = input_lengths.to_kind(Int64);
+        for (kernel, stride) in self.conv_kernel.iter().zip(self.conv_stride.iter()) {
+            lengths = (lengths - *kernel).floor_divide1(*stride) + 1;
+        }
+        lengths
+    }
+}
+
+struct Wav2Vec2FeatureEncoderLayer {
+    conv: nn::Conv1D,
+    norm: Option<(Tensor, Tensor)>,
+    norm_type: FeatureExtractNorm,
+    activation: TensorFunction,
+}
+
+impl Wav2Vec2FeatureEncoderLayer {
+    fn new<'p, P>(p: P, config: &Wav2Vec2Config, layer_index: usize) -> Wav2Vec2FeatureEncoderLayer
+    where
+        P: Borrow<nn::Path<'p>>,
+    {
+        let p = p.borrow();
+
+        let in_channels = if layer_index > 0 {
+            config.conv_dim[layer_index - 1]
+        } else {
+            1
+        };
+        let out_channels = config.conv_dim[layer_index];
+        let conv_config = nn::ConvConfig {
+            stride: config.conv_stride[layer_index],
+            bias: config.conv_bias,
+            ..Default::default()
+        };
+        let conv = nn::conv1d(
+            p / "conv",
+            in_channels,
+            out_channels,
+            config.conv_kernel[layer_index],
+            conv_config,
+        );
+        let norm = if (config.feat_extract_norm == FeatureExtractNorm::layer) | (layer_index == 0) {
+            let p_norm = p / "layer_norm";
+            Some((
+                p_norm.ones("weight", &[out_channels]),
+                p_norm.zeros("bias", &[out_channels]),
+            ))
+        } else {
+            None
+        };
+        let activation = config
+            .feat_extract_activation
+            .unwrap_or(Activation::gelu)
+            .get_function();
+
+        Wav2Vec2FeatureEncoderLayer {
+            conv,
+            norm,
+            norm_type: config.feat_extract_norm,
+            activation,
+        }
+    }
+
+    fn forward(&self, hidden_states: &Tensor) -> Tensor {
+        let hidden_states = hidden_states.apply(&self.conv);
+        let hidden_states = match (&self.norm, self.norm_type) {
+            (Some((weight, bias)), FeatureExtractNorm::group) => {
+                let num_channels = weight.size()[0];
+                hidden_states.group_norm(num_channels, Some(weight), Some(bias), 1e-5, true)
+            }
+            (Some((weight, bias)), FeatureExtractNorm::layer) => hidden_states
+                .transpose(1, 2)
+                .layer_norm(&[weight.size()[0]], Some(weight), Some(bias), 1e-5, true)
+                .transpose(1, 2),
+            (None, _) => hidden_states,
+        };
+        (self.activation.get_fn())(&hidden_states)
+    }
+}
+
+/// # Wav2Vec2 convolutional feature encoder
+/// Converts raw waveforms into a sequence of latent feature vectors (one frame every 20ms for the default
+/// configuration at 16kHz).
+pub struct Wav2Vec2FeatureEncoder {
+    conv_layers: Vec<Wav2Vec2FeatureEncoderLayer>,
+}
+
+impl Wav2Vec2FeatureEncoder {
+    pub fn new<'p, P>(p: P, config: &Wav2Vec2Config) -> Wav2Vec2FeatureEncoder
+    where
+        P: Borrow<nn::Path<'p>>,
+    {
+        let p = p.borrow() / "conv_layers";
+        let conv_layers = (0..config.conv_dim.len())
+            .map(|layer_index| {
+                Wav2Vec2FeatureEncoderLayer::new(&p / layer_index as i64, config, layer_index)
+            })
+            .collect();
+        Wav2Vec2FeatureEncoder { conv_layers }
+    }
+
+    /// Forward pass through the feature encoder
+    ///
+    /// # Arguments
+    ///
+    /// * `input_values` - Raw waveforms of shape (*batch size*, *num_samples*)
+    ///
+    /// # Returns
+    ///
+    /// * `Tensor` of shape (*batch size*, *num_frames*, *conv_dim*)
+    pub fn forward(&self, input_values: &Tensor) -> Tensor {
+        let mut hidden_states = input_values.unsqueeze(1);
+        for layer in &self.conv_layers {
+            hidden_states = layer.forward(&hidden_states);
+        }
+        hidden_states.transpose(1, 2)
+    }
+}
+
+struct Wav2Vec2FeatureProjection {
+    layer_norm: nn::LayerNorm,
+    projection: nn::Linear,
+    dropout: Dropout,
+}
+
+impl Wav2Vec2FeatureProjection {
+    fn new<'p, P>(p: P, config: &Wav2Vec2Config) -> Wav2Vec2FeatureProjection
+    where
+        P: Borrow<nn::Path<'p>>,
+    {
+        let p = p.borrow();
+        let feature_size = *config.conv_dim.last().unwrap();
+
+        let layer_norm_config = nn::LayerNormConfig {
+            eps: config.layer_norm_eps.unwrap_or(1e-5),
+            ..Default::default()
+        };
+        let layer_norm = nn::layer_norm(p / "layer_norm", vec![feature_size], layer_norm_config);
+        let projection = nn::linear(
+            p / "projection",
+            feature_size,
+            config.hidden_size,
+            Default::default(),
+        );
+        let dropout = Dropout::new(config.feat_proj_dropout.unwrap_or(0.0));
+
+        Wav2Vec2FeatureProjection {
+            layer_norm,
+            projection,
+            dropout,
+        }
+    }
+
+    fn forward_t(&self, features: &Tensor, train: bool) -> Tensor {
+        features
+            .apply(&self.layer_norm)
+            .apply(&self.projection)
+            .apply_t(&self.dropout, train)
+    }
+}
+
+/// # Wav2Vec2 Base model
+/// Base architecture for Wav2Vec2 models. Task-specific models will be built from this common base model
+/// It is made of the following blocks:
+/// - `feature_extractor`: convolutional feature encoder, converting the raw waveform into latent frames
+/// - `feature_projection`: layer normalization and projection of the latent frames to the hidden size
+/// - `encoder`: transformer encoder with convolutional relative position embeddings
+pub struct Wav2Vec2Model {
+    feature_extractor: Wav2Vec2FeatureEncoder,
+    feature_projection: Wav2Vec2FeatureProjection,
+    encoder: Wav2Vec2Encoder,
+}
+
+impl Wav2Vec2Model {
+    /// Build a new `Wav2Vec2Model`
+    ///
+    /// # Arguments
+    ///
+    /// * `p` - Variable store path for the root of the Wav2Vec2 model
+    /// * `config` - `Wav2Vec2Config` object defining the model architecture
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use rust_bert::wav2vec2::{Wav2Vec2Config, Wav2Vec2Model};
+    /// use rust_bert::Config;
+    /// use std::path::Path;
+    /// use tch::{nn, Device};
+    ///
+    /// let config_path = Path::new("path/to/config.json");
+    /// let device = Device::Cpu;
+    /// let p = nn::VarStore::new(device);
+    /// let config = Wav2Vec2Config::from_file(config_path);
+    /// let wav2vec2 = Wav2Vec2Model::new(&p.root() / "wav2vec2", &config);
+    /// ```
+    pub fn new<'p, P>(p: P, config: &Wav2Vec2Config) -> Wav2Vec2Model
+    where
+        P: Borrow<nn::Path<'p>>,
+    {
+        let p = p.borrow();
+
+        let feature_extractor = Wav2Vec2FeatureEncoder::new(p / "feature_extractor", config);
+        let feature_projection = Wav2Vec2FeatureProjection::new(p / "feature_projection", config);
+        let encoder = Wav2Vec2Encoder::new(p / "encoder", config);
+
+        Wav2Vec2Model {
+            feature_extractor,
+            feature_projection,
+            encoder,
+        }
+    }
+
+    /// Forward pass through the model
+    ///
+    /// # Arguments
+    ///
+    /// * `input_values` - Raw waveforms of shape (*batch size*, *num_samples*), normalized to zero mean and unit variance
+    /// * `frame_mask` - Optional mask of shape (*batch size*, *num_frames*) for the frames produced by the feature encoder (see `Wav2Vec2Config::feature_lengths`). Masked position have value 0, non-masked value 1. If None set to 1
+    /// * `train` - boolean flag to turn on/off the dropout layers in the model. Should be set to false for inference.
+    ///
+    /// # Returns
+    ///
+    /// * `Wav2Vec2ModelOutput` containing:
+    ///   - `hidden_state` - `Tensor` of shape (*batch size*, *num_frames*, *hidden_size*)
+    ///   - `extract_features` - `Tensor` of shape (*batch size*, *num_frames*, *conv_dim*) containing the output of the feature encoder
+    ///   - `all_hidden_states` - `Option<Vec<Tensor>>` of length *num_hidden_layers + 1* with shape (*batch size*, *num_frames*, *hidden_size*)
+    ///   - `all_attentions` - `Option<Vec<Tensor>>` of length *num_hidden_layers* with shape (*batch size*, *num_heads*, *num_frames*, *num_frames*)
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// # use rust_bert::wav2vec2::{Wav2Vec2Config, Wav2Vec2Model};
+    /// # use tch::{nn, Device, Tensor, no_grad, Kind};
+    /// # use rust_bert::Config;
+    /// # use std::path::Path;
+    /// # let config_path = Path::new("path/to/config.json");
+    /// # let device = Device::Cpu;
+    /// # let vs = nn::VarStore::new(device);
+    /// # let config = Wav2Vec2Config::from_file(config_path);
+    /// # let wav2vec2_model = Wav2Vec2Model::new(&vs.root(), &config);
+    /// let (batch_size, num_samples) = (4, 16000);
+    /// let input_values = Tensor::randn(&[batch_size, num_samples], (Kind::Float, device));
+    ///
+    /// let model_output = no_grad(|| wav2vec2_model.forward_t(&input_values, None, false));
+    /// ```
+    pub fn forward_t(
+        &self,
+        input_values: &Tensor,
+        frame_mask: Option<&Tensor>,
+        train: bool,
+    ) -> Wav2Vec2ModelOutput {
+        let extract_features = self.feature_extractor.forward(input_values);
+        let hidden_states = self.feature_projection.forward_t(&extract_features, train);
+        let encoder_output = self.encoder.forward_t(&hidden_states, frame_mask, train);
+
+        Wav2Vec2ModelOutput {
+            hidden_state: encoder_output.hidden_state,
+            extract_features,
+            all_hidden_states: encoder_output.all_hidden_states,
+            all_attentions: encoder_output.all_attentions,
+        }
+    }
+}
+
+/// # Wav2Vec2 for sequence classification
+/// Wav2Vec2 model with a classification head for utterance-level classification (e.g. keyword spotting, spoken
+/// language identification, audio event detection). The frames are projected, averaged over the non-padded positions
+/// and classified.
+/// It is made of the following blocks:
+/// - `wav2vec2`: Base Wav2Vec2Model
+/// - `layer_weights`: Optional weights of the hidden states of each layer, if `use_weighted_layer_sum` is set
+/// - `projector`: Linear projection of the frames before pooling
+/// - `classifier`: Linear layer for classification
+pub struct Wav2Vec2ForSequenceClassification {
+    wav2vec2: Wav2Vec2Model,
+    layer_weights: Option<Tensor>,
+    projector: nn::Linear,
+    classifier: nn::Linear,
+}
+
+impl Wav2Vec2ForSequenceClassification {
+    /// Build a new `Wav2Vec2ForSequenceClassification`
+    ///
+    /// # Arguments
+    ///
+    /// * `p` - Variable store path for the root of the Wav2Vec2ForSequenceClassification model
+    /// * `config` - `Wav2Vec2Config` object defining the model architecture and number of classes
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use rust_bert::wav2vec2::{Wav2Vec2Config, Wav2Vec2ForSequenceClassification};
+    /// use rust_bert::Config;
+    /// use std::path::Path;
+    /// use tch::{nn, Device};
+    ///
+    /// let config_path = Path::new("path/to/config.json");
+    /// let device = Device::Cpu;
+    /// let p = nn::VarStore::new(device);
+    /// let config = Wav2Vec2Config::from_file(config_path);
+    /// let wav2vec2 = Wav2Vec2ForSequenceClassification::new(&p.root(), &config);
+    /// ```
+    pub fn new<'p, P>(p: P, config: &Wav2Vec2Config) -> Wav2Vec2ForSequenceClassification
+    where
+        P: Borrow<nn::Path<'p>>,
+    {
+        let p = p.borrow();
+
+        let (wav2vec2, layer_weights) = if config.use_weighted_layer_sum {
+            let mut config = config.clone();
+            config.output_hidden_states = Some(true);
+            let num_layers = config.num_hidden_layers + 1;
+            (
+                Wav2Vec2Model::new(p / "wav2vec2", &config),
+                Some(p.var(
+                    "layer_weights",
+                    &[num_layers],
+                    nn::Init::Const(1.0 / num_layers as f64),
+                )),
+            )
+        } else {
+            (Wav2Vec2Model::new(p / "wav2vec2", config), None)
+        };
+        let num_labels = config
+            .id2label
+            .as_ref()
+            .expect("num_labels not provided in configuration")
+            .len() as i64;
+        let projector = nn::linear(
+            p / "projector",
+            config.hidden_size,
+            config.classifier_proj_size,
+            Default::default(),
+        );
+        let classifier = nn::linear(
+            p / "classifier",
+            config.classifier_proj_size,
+            num_labels,
+            Default::default(),
+        );
+
+        Wav2Vec2ForSequenceClassification {
+            wav2vec2,
+            layer_weights,
+            projector,
+            classifier,
+        }
+    }
+
+    /// Forward pass through the model
+    ///
+    /// # Arguments
+    ///
+    /// * `input_values` - Raw waveforms of shape (*batch size*, *num_samples*), normalized to zero mean and unit variance
+    /// * `frame_mask` - Optional mask of shape (*batch size*, *num_frames*) for the frames produced by the feature encoder. Masked frames are excluded from the attention and pooling. If None all frames are used
+    /// * `train` - boolean flag to turn on/off the dropout layers in the model. Should be set to false for inference.
+    ///
+    /// # Returns
+    ///
+    /// * `Wav2Vec2SequenceClassificationOutput` containing:
+    ///   - `logits` - `Tensor` of shape (*batch size*, *num_labels*)
+    ///   - `all_hidden_states` - `Option<Vec<Tensor>>` of length *num_hidden_layers + 1* with shape (*batch size*, *num_frames*, *hidden_size*)
+    ///   - `all_attentions` - `Option<Vec<Tensor>>` of length *num_hidden_layers* with shape (*batch size*, *num_heads*, *num_frames*, *num_frames*)
+    pub fn forward_t(
+        &self,
+        input_values: &Tensor,
+        frame_mask: Option<&Tensor>,
+        train: bool,
+    ) -> Wav2Vec2SequenceClassificationOutput {
+        let base_model_output = self.wav2vec2.forward_t(input_values, frame_mask, train);
+
+        let hidden_states = match (&self.layer_weights, &base_model_output.all_hidden_states) {
+            (Some(layer_weights), Some(all_hidden_states)) => {
+                let weights = layer_weights.softmax(-1, Float).view((-1, 1, 1, 1));
+                (Tensor::stack(all_hidden_states, 0) * weights).sum1(&[0], false, Float)
+            }
+            _ => base_model_output.hidden_state.shallow_clone(),
+        };
+        let hidden_states = hidden_states.apply(&self.projector);
+        let pooled_output = match frame_mask {
+            Some(frame_mask) => {
+                let frame_mask = frame_mask.to_kind(hidden_states.kind()).unsqueeze(-1);
+                (hidden_states * &frame_mask).sum1(&[1], false, Kind::Float)
+                    / frame_mask.sum1(&[1], false, Kind::Float).clamp_min(1.0)
+            }
+            None => hidden_states.mean1(&[1], false, Kind::Float),
+        };
+        let logits = pooled_output.apply(&self.classifier);
+
+        Wav2Vec2SequenceClassificationOutput {
+            logits,
+            all_hidden_states: base_model_output.all_hidden_states,
+            all_attentions: base_model_output.all_attentions,
+        }
+    }
+}
+
+/// Container for the Wav2Vec2 model output.
+pub struct Wav2Vec2ModelOutput {
+    /// Last hidden states from the model
+    pub hidden_state: Tensor,
+    /// Output of the convolutional feature encoder
+    pub extract_features: Tensor,
+    /// Hidden states for all intermediate layers
+    pub all_hidden_states: Option<Vec<Tensor>>,
+    /// Attention weights for all intermediate layers
+    pub all_attentions: Option<Vec<Tensor>>,
+}
+
+impl ModelOutput for Wav2Vec2ModelOutput {
+    fn hidden_states(&self) -> Option<Vec<&Tensor>> {
+        tensor_refs(&self.all_hidden_states)
+    }
+
+    fn attentions(&self) -> Option<Vec<&Tensor>> {
+        tensor_refs(&self.all_attentions)
+    }
+
+    fn logits(&self) -> Option<&Tensor> {
+        None
+    }
+}
+
+/// Container for the Wav2Vec2 sequence classification model output.
+pub struct Wav2Vec2SequenceClassificationOutput {
+    /// Logits for each input (utterance) for each target class
+    pub logits: Tensor,
+    /// Hidden states for all intermediate layers
+    pub all_hidden_states: Option<Vec<Tensor>>,
+    /// Attention weights for all intermediate layers
+    pub all_attentions: Option<Vec<Tensor>>,
+}
+
+impl ModelOutput for Wav2Vec2SequenceClassificationOutput {
+    fn hidden_states(&self) -> Option<Vec<&Tensor>> {
+        tensor_refs(&self.all_hidden_states)
+    }
+
+    fn attentions(&self) -> Option<Vec<&Tensor>> {
+        tensor_refs(&self.all_attentions)
+    }
+
+    fn logits(&self) -> Option<&Tensor> {
+        Some(&self.logits)
+    }
+}
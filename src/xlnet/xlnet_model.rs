@@ -13,13 +13,16 @@
 // limitations under the License.
 
 use crate::common::activations::Activation;
+use crate::common::config::{check_divisible, check_token_id, deserialize_id2label};
 use crate::common::dropout::Dropout;
+use crate::common::model_output::ModelOutput;
 use crate::common::summary::{SequenceSummary, SummaryConfig, SummaryType};
 use crate::pipelines::generation_utils::{Cache, LMHeadModel, LMModelOutput};
 use crate::xlnet::attention::LayerState;
 use crate::xlnet::encoder::XLNetLayer;
 use crate::{Config, RustBertError};
 use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
 use std::borrow::{Borrow, BorrowMut};
 use std::collections::HashMap;
 use tch::nn::Init;
@@ -101,14 +104,36 @@ pub struct XLNetConfig {
     pub bos_token_id: i64,
     pub eos_token_id: i64,
     pub pad_token_id: i64,
+    #[serde(default, deserialize_with = "deserialize_id2label")]
     pub id2label: Option<HashMap<i64, String>>,
     pub label2id: Option<HashMap<String, i64>>,
     pub output_attentions: Option<bool>,
     pub output_hidden_states: Option<bool>,
     pub chunk_size_feed_forward: Option<i64>,
+    /// Additional fields of the configuration file not used by the model
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
 }
 
-impl Config<XLNetConfig> for XLNetConfig {}
+impl Config<XLNetConfig> for XLNetConfig {
+    fn extra_fields(&self) -> Option<&Map<String, Value>> {
+        Some(&self.extra)
+    }
+
+    fn validate(&self) -> Result<(), RustBertError> {
+        check_divisible("d_model", self.d_model, "n_head", self.n_head)?;
+        if self.d_head != self.d_model / self.n_head {
+            return Err(RustBertError::InvalidConfigurationError(format!(
+                "`d_head` ({}) must be equal to `d_model` / `n_head` ({})",
+                self.d_head,
+                self.d_model / self.n_head
+            )));
+        }
+        check_token_id("bos_token_id", Some(self.bos_token_id), self.vocab_size)?;
+        check_token_id("eos_token_id", Some(self.eos_token_id), self.vocab_size)?;
+        check_token_id("pad_token_id", Some(self.pad_token_id), self.vocab_size)
+    }
+}
 
 /// # XLNet Base model
 /// Base architecture for XLNet models. Task-specific models will be built from this common base model
@@ -1502,6 +1527,24 @@ pub struct XLNetModelOutput {
     pub all_attentions: Option<Vec<(Tensor, Option<Tensor>)>>,
 }
 
+impl ModelOutput for XLNetModelOutput {
+    fn hidden_states(&self) -> Option<Vec<&Tensor>> {
+        self.all_hidden_states
+            .as_ref()
+            .map(|values| values.iter().map(|(value, _)| value).collect())
+    }
+
+    fn attentions(&self) -> Option<Vec<&Tensor>> {
+        self.all_attentions
+            .as_ref()
+            .map(|values| values.iter().map(|(value, _)| value).collect())
+    }
+
+    fn logits(&self) -> Option<&Tensor> {
+        None
+    }
+}
+
 /// Container for the XLNet sequence classification model output.
 pub struct XLNetSequenceClassificationOutput {
     /// Logits for each input (sequence) for each target class
@@ -1514,6 +1557,24 @@ pub struct XLNetSequenceClassificationOutput {
     pub all_attentions: Option<Vec<(Tensor, Option<Tensor>)>>,
 }
 
+impl ModelOutput for XLNetSequenceClassificationOutput {
+    fn hidden_states(&self) -> Option<Vec<&Tensor>> {
+        self.all_hidden_states
+            .as_ref()
+            .map(|values| values.iter().map(|(value, _)| value).collect())
+    }
+
+    fn attentions(&self) -> Option<Vec<&Tensor>> {
+        self.all_attentions
+            .as_ref()
+            .map(|values| values.iter().map(|(value, _)| value).collect())
+    }
+
+    fn logits(&self) -> Option<&Tensor> {
+        Some(&self.logits)
+    }
+}
+
 /// Container for the XLNet token classification model output.
 pub struct XLNetTokenClassificationOutput {
     /// Logits for each sequence item (token) for each target class
@@ -1526,6 +1587,24 @@ pub struct XLNetTokenClassificationOutput {
     pub all_attentions: Option<Vec<(Tensor, Option<Tensor>)>>,
 }
 
+impl ModelOutput for XLNetTokenClassificationOutput {
+    fn hidden_states(&self) -> Option<Vec<&Tensor>> {
+        self.all_hidden_states
+            .as_ref()
+            .map(|values| values.iter().map(|(value, _)| value).collect())
+    }
+
+    fn attentions(&self) -> Option<Vec<&Tensor>> {
+        self.all_attentions
+            .as_ref()
+            .map(|values| values.iter().map(|(value, _)| value).collect())
+    }
+
+    fn logits(&self) -> Option<&Tensor> {
+        Some(&self.logits)
+    }
+}
+
 /// Container for the XLNet question answering model output.
 pub struct XLNetQuestionAnsweringOutput {
     /// Logits for the start position for token of each input sequence
@@ -1539,3 +1618,25 @@ pub struct XLNetQuestionAnsweringOutput {
     /// Attention weights for all intermediate layers
     pub all_attentions: Option<Vec<(Tensor, Option<Tensor>)>>,
 }
+
+impl ModelOutput for XLNetQuestionAnsweringOutput {
+    fn hidden_states(&self) -> Option<Vec<&Tensor>> {
+        self.all_hidden_states
+            .as_ref()
+            .map(|values| values.iter().map(|(value, _)| value).collect())
+    }
+
+    fn attentions(&self) -> Option<Vec<&Tensor>> {
+        self.all_attentions
+            .as_ref()
+            .map(|values| values.iter().map(|(value, _)| value).collect())
+    }
+
+    fn logits(&self) -> Option<&Tensor> {
+        None
+    }
+
+    fn start_end_logits(&self) -> Option<(&Tensor, &Tensor)> {
+        Some((&self.start_logits, &self.end_logits))
+    }
+}
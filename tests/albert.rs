@@ -344,3 +344,45 @@ fn albert_for_question_answering() -> anyhow::Result<()> {
 
     Ok(())
 }
+
+#[test]
+fn albert_layer_groups() -> anyhow::Result<()> {
+    //    Resources paths
+    let config_resource = Resource::Remote(RemoteResource::from_pretrained(
+        AlbertConfigResources::ALBERT_BASE_V2,
+    ));
+    let config_path = config_resource.get_local_path()?;
+
+    //    Set-up model with 2 groups of 2 shared layers, spread over 5 layers
+    let device = Device::Cpu;
+    let vs = nn::VarStore::new(device);
+    let mut config = AlbertConfig::from_file(config_path);
+    config.num_hidden_layers = 5;
+    config.num_hidden_groups = 2;
+    config.inner_group_num = 2;
+    config.output_attentions = Some(true);
+    let albert_model = AlbertForMaskedLM::new(&vs.root(), &config);
+
+    let variables = vs.variables();
+    assert!(
+        variables.contains_key("albert.encoder.albert_layer_groups.1.albert_layers.1.ffn.weight")
+    );
+    assert!(
+        !variables.contains_key("albert.encoder.albert_layer_groups.2.albert_layers.0.ffn.weight")
+    );
+
+    //    Forward pass
+    let input_tensor = Tensor::of_slice(&[2i64, 1001, 1002, 1003, 3]).unsqueeze(0);
+    let model_output =
+        no_grad(|| albert_model.forward_t(Some(input_tensor), None, None, None, None, false));
+
+    let all_attentions = model_output.all_attentions.unwrap();
+    assert_eq!(all_attentions.len(), 5);
+    for layer_attentions in all_attentions.iter() {
+        assert_eq!(layer_attentions.len(), 2);
+        assert_eq!(layer_attentions[0].size(), &[1, 12, 5, 5]);
+    }
+    assert!(model_output.all_hidden_states.is_none());
+
+    Ok(())
+}
@@ -0,0 +1,81 @@
+use rust_bert::pipelines::attacks::{
+    AttackConfig, SynonymDictionary, SynonymProvider, WordSubstitutionAttack,
+};
+use rust_bert::pipelines::robustness::TextClassifier;
+use rust_bert::pipelines::sequence_classification::Label;
+use rust_bert::RustBertError;
+
+struct KeywordClassifier;
+
+impl TextClassifier for KeywordClassifier {
+    fn classify(&self, texts: &[&str]) -> Result<Vec<Label>, RustBertError> {
+        Ok(texts
+            .iter()
+            .enumerate()
+            .map(|(sentence, text)| {
+                let text = text.to_lowercase();
+                let count = ["terrible", "awful"]
+                    .iter()
+                    .filter(|keyword| text.contains(*keyword))
+                    .count();
+                let (label, score) = if count > 0 {
+                    ("NEGATIVE", 0.5 + 0.2 * count as f64)
+                } else {
+                    ("POSITIVE", 0.8)
+                };
+                Label {
+                    text: label.to_string(),
+                    score,
+                    id: (count > 0) as i64,
+                    sentence,
+                }
+            })
+            .collect())
+    }
+}
+
+#[test]
+fn attacks_synonym_dictionary() {
+    let mut dictionary = SynonymDictionary::new();
+    dictionary.insert("Terrible", &["awful", "dreadful"]);
+    dictionary.insert("terrible", &["Awful", "bad"]);
+
+    let words = ["Terrible", "food"]
+        .iter()
+        .map(|word| word.to_string())
+        .collect::<Vec<String>>();
+    assert_eq!(
+        dictionary.synonyms(&words, 0).unwrap(),
+        vec!["Awful", "Dreadful", "Bad"]
+    );
+    assert!(dictionary.synonyms(&words, 1).unwrap().is_empty());
+}
+
+#[test]
+fn attacks_word_substitution() -> anyhow::Result<()> {
+    let mut dictionary = SynonymDictionary::new();
+    dictionary.insert("terrible", &["awful", "dreadful"]);
+    dictionary.insert("awful", &["terrible"]);
+    let attack =
+        WordSubstitutionAttack::new(KeywordClassifier, dictionary, AttackConfig::default());
+
+    let report = attack.attack(&["The food was terrible.", "Awful and terrible service"])?;
+
+    let success = &report.results[0];
+    assert!(success.is_success());
+    assert_eq!(success.perturbed_text, "The food was dreadful.");
+    assert_eq!(success.perturbed_label.text, "POSITIVE");
+    assert_eq!(success.num_substitutions, 1);
+    assert_eq!(success.num_queries, 5);
+
+    let failure = &report.results[1];
+    assert!(!failure.is_success());
+    assert_eq!(failure.perturbed_text, "Terrible and terrible service");
+    assert_eq!(failure.num_substitutions, 1);
+
+    assert_eq!(report.successes().len(), 1);
+    assert_eq!(report.success_rate(), 0.5);
+    assert_eq!(report.mean_perturbation_rate(), 0.25);
+
+    Ok(())
+}
@@ -0,0 +1,65 @@
+use rust_bert::pipelines::augmentation::{
+    random_deletion, random_swap, AugmentationConfig, AugmentationRng, TextAugmenter,
+};
+use tch::Device;
+
+fn words(text: &str) -> Vec<String> {
+    text.split_whitespace().map(String::from).collect()
+}
+
+#[test]
+fn augmentation_random_operations() {
+    let original = words("the quick brown fox jumps over the lazy dog");
+
+    let mut swapped = original.clone();
+    random_swap(&mut swapped, 2, &mut AugmentationRng::new(3));
+    let mut seeded_swapped = original.clone();
+    random_swap(&mut seeded_swapped, 2, &mut AugmentationRng::new(3));
+    assert_eq!(swapped, seeded_swapped);
+    assert_ne!(swapped, original);
+    let (mut sorted_swapped, mut sorted_original) = (swapped.clone(), original.clone());
+    sorted_swapped.sort();
+    sorted_original.sort();
+    assert_eq!(sorted_swapped, sorted_original);
+
+    let deleted = random_deletion(original.clone(), 0.3, &mut AugmentationRng::new(3));
+    assert!(deleted.len() < original.len());
+    assert!(deleted.iter().all(|word| original.contains(word)));
+    assert_eq!(
+        random_deletion(original.clone(), 1.0, &mut AugmentationRng::new(3)).len(),
+        1
+    );
+    assert_eq!(
+        random_deletion(original.clone(), 0.0, &mut AugmentationRng::new(3)),
+        original
+    );
+
+    let indices = AugmentationRng::new(5).sample_indices(10, 4);
+    assert_eq!(indices.len(), 4);
+    assert!(indices.iter().all(|index| *index < 10));
+    assert!((1..indices.len()).all(|position| !indices[..position].contains(&indices[position])));
+}
+
+#[test]
+fn augmentation_contextual_substitution() -> anyhow::Result<()> {
+    let augmenter = TextAugmenter::new(AugmentationConfig {
+        substitution_rate: 0.3,
+        num_augmentations: 3,
+        device: Device::Cpu,
+        ..Default::default()
+    })?;
+    let input = ["The delivery was late and the package was damaged"];
+    let augmentations = augmenter.augment(&input)?;
+
+    assert_eq!(augmentations.len(), 1);
+    assert_eq!(augmentations[0].len(), 3);
+    let original = words(input[0]);
+    for augmentation in &augmentations[0] {
+        let augmented = words(augmentation);
+        assert_eq!(augmented.len(), original.len());
+        assert!(augmented != original);
+    }
+    assert_eq!(augmenter.augment(&input)?, augmentations);
+
+    Ok(())
+}
@@ -1,8 +1,12 @@
 use rust_bert::bart::{
-    BartConfig, BartConfigResources, BartMergesResources, BartModel, BartModelResources,
-    BartVocabResources,
+    shift_tokens_right, BartConfig, BartConfigResources, BartMergesResources, BartModel,
+    BartModelResources, BartVocabResources,
+};
+use rust_bert::pipelines::common::{ModelType, TokenizerOption};
+use rust_bert::pipelines::generation_utils::{GenerateConfig, SpecialTokenIds};
+use rust_bert::pipelines::summarization::{
+    DialoguePreprocessor, DialogueTurn, SummarizationConfig, SummarizationModel,
 };
-use rust_bert::pipelines::summarization::{SummarizationConfig, SummarizationModel};
 use rust_bert::pipelines::zero_shot_classification::{
     ZeroShotClassificationConfig, ZeroShotClassificationModel,
 };
@@ -195,6 +199,46 @@ habitable zone. The presence of water vapour was confirmed in the atmosphere of
     Ok(())
 }
 
+#[test]
+fn bart_summarization_diverse_beam_search() -> anyhow::Result<()> {
+    let summarization_config = SummarizationConfig {
+        model_resource: Resource::Remote(RemoteResource::from_pretrained(
+            BartModelResources::DISTILBART_CNN_6_6,
+        )),
+        config_resource: Resource::Remote(RemoteResource::from_pretrained(
+            BartConfigResources::DISTILBART_CNN_6_6,
+        )),
+        vocab_resource: Resource::Remote(RemoteResource::from_pretrained(
+            BartVocabResources::DISTILBART_CNN_6_6,
+        )),
+        merges_resource: Resource::Remote(RemoteResource::from_pretrained(
+            BartMergesResources::DISTILBART_CNN_6_6,
+        )),
+        min_length: 10,
+        max_length: 40,
+        num_beams: 4,
+        num_beam_groups: 2,
+        diversity_penalty: 2.0,
+        num_return_sequences: 2,
+        device: Device::Cpu,
+        ..Default::default()
+    };
+    let model = SummarizationModel::new(summarization_config)?;
+
+    let input = ["The presence of water vapour was confirmed in the atmosphere of K2-18b, a planet circling \
+a star in the constellation Leo. This is the first such discovery in a planet in its star's habitable zone, not too \
+hot and not too cold for liquid water to exist. K2-18b was first identified in 2015 by the Kepler space telescope. \
+It is about 110 light-years from Earth and larger but less dense."];
+    let output = model.summarize(&input);
+
+    assert_eq!(output.len(), 2);
+    assert!(!output[0].is_empty());
+    assert!(!output[1].is_empty());
+    assert_ne!(output[0], output[1]);
+
+    Ok(())
+}
+
 #[test]
 #[cfg_attr(not(feature = "all-tests"), ignore)]
 fn bart_zero_shot_classification() -> anyhow::Result<()> {
@@ -274,3 +318,167 @@ fn bart_zero_shot_classification_multilabel() -> anyhow::Result<()> {
     assert!((output[1][3].score - 0.0004).abs() < 1e-4);
     Ok(())
 }
+
+#[test]
+fn bart_special_token_ids() -> anyhow::Result<()> {
+    let vocab_resource = Resource::Remote(RemoteResource::from_pretrained(
+        BartVocabResources::BART_CNN,
+    ));
+    let merges_resource = Resource::Remote(RemoteResource::from_pretrained(
+        BartMergesResources::BART_CNN,
+    ));
+    let tokenizer = TokenizerOption::from_file(
+        ModelType::Bart,
+        vocab_resource.get_local_path()?.to_str().unwrap(),
+        Some(merges_resource.get_local_path()?.to_str().unwrap()),
+        false,
+        None,
+        false,
+    )?;
+
+    //    Ids from the tokenizer, overridden by the generation options
+    let generate_config = GenerateConfig {
+        pad_token_id: Some(2),
+        ..Default::default()
+    };
+    let token_ids = SpecialTokenIds::from_generate_config(&generate_config)
+        .or(SpecialTokenIds::from_tokenizer(&tokenizer));
+    assert_eq!(token_ids.bos_token_id, Some(0));
+    assert_eq!(token_ids.eos_token_ids, Some(vec![2]));
+    assert_eq!(token_ids.pad_token_id, Some(2));
+
+    //    Encoder-decoder models require padding and decoder start ids
+    assert!(token_ids.clone().resolve(false, 50265).is_ok());
+    assert!(token_ids.clone().resolve(true, 50265).is_err());
+    assert!(SpecialTokenIds {
+        pad_token_id: None,
+        decoder_start_id: Some(2),
+        ..token_ids.clone()
+    }
+    .resolve(true, 50265)
+    .is_err());
+    let token_ids = token_ids.or(SpecialTokenIds {
+        decoder_start_id: Some(2),
+        ..Default::default()
+    });
+    assert!(token_ids.clone().resolve(true, 50265).is_ok());
+
+    //    Ids must be valid for the model vocabulary
+    assert!(token_ids.resolve(true, 2).is_err());
+
+    Ok(())
+}
+
+#[test]
+fn bart_tokenizer_model_compatibility() -> anyhow::Result<()> {
+    let vocab_resource = Resource::Remote(RemoteResource::from_pretrained(
+        BartVocabResources::BART_CNN,
+    ));
+    let merges_resource = Resource::Remote(RemoteResource::from_pretrained(
+        BartMergesResources::BART_CNN,
+    ));
+    let tokenizer = TokenizerOption::from_file(
+        ModelType::Bart,
+        vocab_resource.get_local_path()?.to_str().unwrap(),
+        Some(merges_resource.get_local_path()?.to_str().unwrap()),
+        false,
+        None,
+        false,
+    )?;
+
+    assert_eq!(tokenizer.vocab_size(), 50265);
+    assert!(tokenizer
+        .check_model_compatibility(ModelType::Bart, 50265)
+        .is_ok());
+    assert!(tokenizer
+        .check_model_compatibility(ModelType::Roberta, 50265)
+        .is_ok());
+
+    //    Vocabulary larger than the model embeddings
+    assert!(tokenizer
+        .check_model_compatibility(ModelType::Bart, 32000)
+        .is_err());
+    //    SentencePiece model with a byte-level BPE tokenizer
+    assert!(tokenizer
+        .check_model_compatibility(ModelType::T5, 50265)
+        .is_err());
+
+    Ok(())
+}
+
+#[test]
+fn bart_dialogue_preprocessing() -> anyhow::Result<()> {
+    let preprocessor = DialoguePreprocessor::default();
+    let transcript = "AMANDA: I baked cookies.
+AMANDA:   Do you want some?
+[jerry] Sure!
+<Amanda>: I'll bring you
+tomorrow, see https://cookies.example at 10:30";
+
+    let turns = preprocessor.parse(transcript);
+    assert_eq!(turns.len(), 4);
+    assert_eq!(turns[2], DialogueTurn::new("jerry", "Sure!"));
+    assert_eq!(
+        turns[3].utterance,
+        "I'll bring you tomorrow, see https://cookies.example at 10:30"
+    );
+
+    assert_eq!(
+        preprocessor.format(&turns),
+        "Amanda: I baked cookies. Do you want some?\njerry: Sure!\nAmanda: I'll bring you tomorrow, \
+         see https://cookies.example at 10:30"
+    );
+
+    let raw_preprocessor = DialoguePreprocessor {
+        join_consecutive_turns: false,
+        normalize_speakers: false,
+        turn_separator: " ".to_string(),
+        ..Default::default()
+    };
+    assert_eq!(
+        raw_preprocessor.preprocess("JERRY SMITH: Sure!\nJERRY SMITH: Thanks"),
+        "JERRY SMITH: Sure! JERRY SMITH: Thanks"
+    );
+    assert_eq!(
+        preprocessor.normalize_speaker(" JERRY  SMITH "),
+        "Jerry Smith"
+    );
+
+    let config = SummarizationConfig::dialogue(
+        Resource::Remote(RemoteResource::from_pretrained(BartModelResources::BART)),
+        Resource::Remote(RemoteResource::from_pretrained(BartConfigResources::BART)),
+        Resource::Remote(RemoteResource::from_pretrained(BartVocabResources::BART)),
+        Resource::Remote(RemoteResource::from_pretrained(BartMergesResources::BART)),
+    );
+    assert_eq!(config.model_type, ModelType::Bart);
+    assert!(config.max_length < SummarizationConfig::default().max_length);
+
+    Ok(())
+}
+
+#[test]
+fn bart_shift_tokens_right() {
+    let labels = Tensor::of_slice(&[0, 31414, 232, 2, -100, 0, 713, 16, 10, 2]).view((2, 5));
+
+    let decoder_input_ids = shift_tokens_right(&labels, 1, 2);
+
+    assert_eq!(decoder_input_ids.size(), vec![2, 5]);
+    assert_eq!(
+        decoder_input_ids
+            .view_(&[-1])
+            .iter::<i64>()
+            .unwrap()
+            .collect::<Vec<i64>>(),
+        vec![2, 0, 31414, 232, 2, 2, 0, 713, 16, 10]
+    );
+    let shifted_padding =
+        shift_tokens_right(&Tensor::of_slice(&[5, -100, -100]).view((1, 3)), 1, 2);
+    assert_eq!(
+        shifted_padding
+            .view_(&[-1])
+            .iter::<i64>()
+            .unwrap()
+            .collect::<Vec<i64>>(),
+        vec![2, 5, 1]
+    );
+}
@@ -0,0 +1,169 @@
+use rust_bert::pipelines::batch_jobs::{
+    run_batch_job, split_csv_record, BatchJobCheckpoint, BatchJobConfig, InputFormat,
+};
+use rust_bert::RustBertError;
+use serde_json::Value;
+use std::fs;
+use std::io::Write;
+
+fn read_output(path: &std::path::Path) -> anyhow::Result<Vec<Value>> {
+    Ok(fs::read_to_string(path)?
+        .lines()
+        .map(serde_json::from_str)
+        .collect::<Result<Vec<Value>, _>>()?)
+}
+
+#[test]
+fn batch_job_json_lines() -> anyhow::Result<()> {
+    let directory = tempfile::tempdir()?;
+    let input_path = directory.path().join("input.jsonl");
+    let output_path = directory.path().join("output.jsonl");
+    fs::write(
+        &input_path,
+        "{\"id\": 1, \"text\": \"one\"}\n\n{\"id\": 2, \"text\": \"three\"}\n{\"id\": 3, \"text\": \"seven\"}\n",
+    )?;
+    let config = BatchJobConfig {
+        id_field: Some("id".to_string()),
+        batch_size: 2,
+        ..Default::default()
+    };
+
+    let mut batch_sizes = vec![];
+    let report = run_batch_job(&input_path, &output_path, &config, |texts| {
+        batch_sizes.push(texts.len());
+        Ok(texts.iter().map(|text| text.len()).collect())
+    })?;
+
+    assert_eq!(batch_sizes, vec![2, 1]);
+    assert_eq!(report.processed_records, 3);
+    assert_eq!(report.processed_batches, 2);
+    assert_eq!(report.resumed_records, 0);
+    let output = read_output(&output_path)?;
+    assert_eq!(output.len(), 3);
+    assert_eq!(output[1]["index"], 1);
+    assert_eq!(output[1]["id"], 2);
+    assert_eq!(output[1]["output"], 5);
+    Ok(())
+}
+
+#[test]
+fn batch_job_resumes_from_checkpoint() -> anyhow::Result<()> {
+    let directory = tempfile::tempdir()?;
+    let input_path = directory.path().join("input.jsonl");
+    let output_path = directory.path().join("output.jsonl");
+    let input = (0..5)
+        .map(|index| format!("{{\"text\": \"text {}\"}}\n", index))
+        .collect::<String>();
+    fs::write(&input_path, input)?;
+    let config = BatchJobConfig {
+        batch_size: 2,
+        ..Default::default()
+    };
+
+    // The second batch fails, as if the job crashed
+    let mut calls = 0;
+    let result = run_batch_job(&input_path, &output_path, &config, |texts| {
+        calls += 1;
+        if calls == 2 {
+            Err(RustBertError::ValueError("out of memory".to_string()))
+        } else {
+            Ok(texts.iter().map(|text| text.to_uppercase()).collect())
+        }
+    });
+    assert!(result.is_err());
+    let checkpoint_path = directory.path().join("output.jsonl.checkpoint");
+    let checkpoint = BatchJobCheckpoint::load(&checkpoint_path)?.unwrap();
+    assert_eq!(checkpoint.records, 2);
+    assert_eq!(read_output(&output_path)?.len(), 2);
+
+    // Partial output written after the checkpoint is discarded on resume
+    fs::OpenOptions::new()
+        .append(true)
+        .open(&output_path)?
+        .write_all(b"{\"index\": 2, \"out")?;
+    let report = run_batch_job(&input_path, &output_path, &config, |texts| {
+        Ok(texts.iter().map(|text| text.to_uppercase()).collect())
+    })?;
+    assert_eq!(report.resumed_records, 2);
+    assert_eq!(report.processed_records, 3);
+    let output = read_output(&output_path)?;
+    assert_eq!(output.len(), 5);
+    for (index, line) in output.iter().enumerate() {
+        assert_eq!(line["index"], index);
+        assert_eq!(line["output"], format!("TEXT {}", index));
+    }
+
+    // A completed job is not processed again
+    let report = run_batch_job(&input_path, &output_path, &config, |_| {
+        Ok(Vec::<String>::new())
+    })?;
+    assert_eq!(report.processed_records, 0);
+    assert_eq!(report.resumed_records, 5);
+    Ok(())
+}
+
+#[test]
+fn batch_job_csv() -> anyhow::Result<()> {
+    let directory = tempfile::tempdir()?;
+    let input_path = directory.path().join("input.csv");
+    let output_path = directory.path().join("output.jsonl");
+    fs::write(
+        &input_path,
+        "name;review\nalice;\"Great; would \"\"buy\"\" again\"\nbob;\"Two\nlines\"\n",
+    )?;
+    let config = BatchJobConfig {
+        input_format: InputFormat::Csv { delimiter: ';' },
+        text_field: "review".to_string(),
+        id_field: Some("name".to_string()),
+        ..Default::default()
+    };
+
+    run_batch_job(&input_path, &output_path, &config, |texts| {
+        Ok(texts.iter().map(|text| text.to_string()).collect())
+    })?;
+
+    let output = read_output(&output_path)?;
+    assert_eq!(output.len(), 2);
+    assert_eq!(output[0]["id"], "alice");
+    assert_eq!(output[0]["output"], "Great; would \"buy\" again");
+    assert_eq!(output[1]["id"], "bob");
+    assert_eq!(output[1]["output"], "Two\nlines");
+    Ok(())
+}
+
+#[test]
+fn batch_job_invalid_records() -> anyhow::Result<()> {
+    let directory = tempfile::tempdir()?;
+    let input_path = directory.path().join("input.jsonl");
+    let output_path = directory.path().join("output.jsonl");
+    fs::write(
+        &input_path,
+        "{\"text\": \"valid\"}\n{\"body\": \"missing\"}\n",
+    )?;
+
+    let result = run_batch_job(
+        &input_path,
+        &output_path,
+        &BatchJobConfig::default(),
+        |texts| Ok(texts.iter().map(|text| text.to_string()).collect()),
+    );
+    match result {
+        Err(RustBertError::ValueError(message)) => assert!(message.starts_with("Line 2")),
+        _ => panic!("expected a ValueError for the missing text field"),
+    }
+    Ok(())
+}
+
+#[test]
+fn csv_record_splitting() {
+    assert_eq!(
+        split_csv_record("a,\"b,c\",,\"d\"\"e\"", ','),
+        Some(vec![
+            "a".to_string(),
+            "b,c".to_string(),
+            "".to_string(),
+            "d\"e".to_string()
+        ])
+    );
+    assert_eq!(split_csv_record("a,\"unclosed", ','), None);
+}
@@ -7,15 +7,20 @@ use rust_bert::bert::{
     BertModelResources, BertVocabResources,
 };
 use rust_bert::pipelines::common::ModelType;
+use rust_bert::pipelines::feature_extraction::{FeatureExtractionConfig, FeatureExtractionModel};
 use rust_bert::pipelines::ner::NERModel;
 use rust_bert::pipelines::question_answering::{
     QaInput, QuestionAnsweringConfig, QuestionAnsweringModel,
 };
+use rust_bert::pipelines::token_classification::{
+    TokenClassificationModel, WordAggregationStrategy,
+};
 use rust_bert::resources::{RemoteResource, Resource};
 use rust_bert::Config;
 use rust_tokenizers::tokenizer::{BertTokenizer, MultiThreadedTokenizer, TruncationStrategy};
 use rust_tokenizers::vocab::Vocab;
 use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
 use tch::{nn, no_grad, Device, Tensor};
 
 #[test]
@@ -167,6 +172,130 @@ fn bert_for_sequence_classification() -> anyhow::Result<()> {
     Ok(())
 }
 
+#[test]
+fn bert_forward_hooks() -> anyhow::Result<()> {
+    //    Resources paths
+    let config_resource =
+        Resource::Remote(RemoteResource::from_pretrained(BertConfigResources::BERT));
+    let config_path = config_resource.get_local_path()?;
+
+    //    Set-up model
+    let device = Device::Cpu;
+    let vs = nn::VarStore::new(device);
+    let mut config = BertConfig::from_file(config_path);
+    let mut dummy_label_mapping = HashMap::new();
+    dummy_label_mapping.insert(0, String::from("Positive"));
+    dummy_label_mapping.insert(1, String::from("Negative"));
+    config.id2label = Some(dummy_label_mapping);
+    config.output_attentions = Some(true);
+    let mut bert_model = BertForSequenceClassification::new(&vs.root(), &config);
+
+    let captured: Arc<Mutex<Vec<(String, Vec<i64>)>>> = Arc::new(Mutex::new(vec![]));
+    let layer_outputs = captured.clone();
+    let layer_hook = bert_model
+        .hooks_mut()
+        .register("encoder.layer.*", move |name, tensor| {
+            layer_outputs
+                .lock()
+                .unwrap()
+                .push((name.to_string(), tensor.size()));
+        });
+    let pooler_output = captured.clone();
+    bert_model
+        .hooks_mut()
+        .register("pooler", move |name, tensor| {
+            pooler_output
+                .lock()
+                .unwrap()
+                .push((name.to_string(), tensor.size()));
+        });
+
+    //    Forward pass
+    let input_tensor = Tensor::of_slice(&[101i64, 2023, 2003, 1037, 3231, 102]).unsqueeze(0);
+    let _ =
+        no_grad(|| bert_model.forward_t(Some(input_tensor.copy()), None, None, None, None, false));
+
+    {
+        let captured = captured.lock().unwrap();
+        assert_eq!(captured.len(), 2 * config.num_hidden_layers as usize + 1);
+        assert_eq!(
+            captured[0],
+            ("encoder.layer.0".to_string(), vec![1, 6, 768])
+        );
+        assert_eq!(
+            captured[1],
+            (
+                "encoder.layer.0.attention_weights".to_string(),
+                vec![1, 12, 6, 6]
+            )
+        );
+        assert_eq!(
+            captured.last().unwrap(),
+            &("pooler".to_string(), vec![1, 768])
+        );
+    }
+
+    //    Removed hooks are no longer called
+    assert!(bert_model.hooks_mut().remove(layer_hook));
+    captured.lock().unwrap().clear();
+    let _ = no_grad(|| bert_model.forward_t(Some(input_tensor), None, None, None, None, false));
+    assert_eq!(captured.lock().unwrap().len(), 1);
+
+    Ok(())
+}
+
+#[test]
+fn bert_feature_extraction() -> anyhow::Result<()> {
+    //    Set-up model
+    let config = FeatureExtractionConfig {
+        device: Device::Cpu,
+        layers: vec![-2, -1],
+        ..Default::default()
+    };
+    let feature_extraction_model = FeatureExtractionModel::new(config)?;
+
+    //    Define input
+    let input = ["Hello world!", "Tokenization"];
+
+    //    Run model
+    let features = feature_extraction_model.extract_features(&input)?;
+
+    assert_eq!(features.len(), 2);
+    // [CLS] hello world ! [SEP]
+    assert_eq!(features[0].token_features.size(), &[5, 2 * 768]);
+    assert_eq!(
+        features[0].word_ids,
+        vec![None, Some(0), Some(1), Some(2), None]
+    );
+    assert_eq!(features[0].word_features.size(), &[3, 2 * 768]);
+    // [CLS] token ##ization [SEP]
+    assert_eq!(features[1].word_ids, vec![None, Some(0), Some(0), None]);
+    assert_eq!(features[1].word_features.size(), &[1, 2 * 768]);
+
+    //    Static embeddings averaged over the corpus
+    let corpus = ["Hello world!", "Hello there"];
+    let embeddings = feature_extraction_model.contextual_embeddings(&corpus, 1, 1)?;
+    assert_eq!(embeddings.words[0], "Hello");
+    assert_eq!(embeddings.words.len(), 4);
+    assert_eq!(embeddings.vectors.size(), &[4, 2 * 768]);
+
+    let mut exported = vec![];
+    embeddings.write(&mut exported)?;
+    let exported = String::from_utf8(exported)?;
+    let mut lines = exported.lines();
+    assert_eq!(lines.next(), Some("4 1536"));
+    let first_line = lines.next().unwrap().split(' ').collect::<Vec<&str>>();
+    assert_eq!(first_line[0], "Hello");
+    assert_eq!(first_line.len(), 1537);
+
+    //    Input embeddings of the vocabulary
+    let input_embeddings = feature_extraction_model.input_embeddings()?;
+    assert_eq!(input_embeddings.vectors.size(), &[30522, 768]);
+    assert_eq!(input_embeddings.words[0], "[PAD]");
+
+    Ok(())
+}
+
 #[test]
 fn bert_for_multiple_choice() -> anyhow::Result<()> {
     //    Resources paths
@@ -387,6 +516,51 @@ fn bert_pre_trained_ner() -> anyhow::Result<()> {
     Ok(())
 }
 
+#[test]
+fn bert_token_classification_words() -> anyhow::Result<()> {
+    //    Set-up model
+    let token_classification_model = TokenClassificationModel::new(Default::default())?;
+
+    //    Define input
+    let input = ["My name is Amélie Poulain. I live in Montmartre, Paris."];
+
+    //    Run model
+    let words = token_classification_model.predict_words(&input, WordAggregationStrategy::Average);
+
+    let word_texts = words
+        .iter()
+        .map(|word| word.text.as_str())
+        .collect::<Vec<&str>>();
+    assert_eq!(
+        word_texts,
+        vec![
+            "My",
+            "name",
+            "is",
+            "Amélie",
+            "Poulain",
+            ".",
+            "I",
+            "live",
+            "in",
+            "Montmartre",
+            ",",
+            "Paris",
+            "."
+        ]
+    );
+    for word in &words {
+        let (start, end) = word.byte_offset.unwrap();
+        assert_eq!(&input[0][start..end], word.text);
+        assert!(!word.sub_token_scores.is_empty());
+    }
+    assert_eq!(words[3].label, "I-PER");
+    assert_eq!(words[11].label, "I-LOC");
+    assert!(words[11].score > 0.9);
+
+    Ok(())
+}
+
 #[test]
 fn bert_question_answering() -> anyhow::Result<()> {
     //    Set-up question answering model
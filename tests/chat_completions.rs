@@ -0,0 +1,86 @@
+use rust_bert::pipelines::chat_completions::{
+    truncate_at_stop_sequences, ChatCompletionChunk, ChatCompletionRequest, ChatMessage, ChatRole,
+    ChatTemplate,
+};
+use rust_bert::pipelines::generation_utils::GenerateConfig;
+
+#[test]
+fn test_chat_completion_request() -> anyhow::Result<()> {
+    let request: ChatCompletionRequest = serde_json::from_str(
+        r#"{
+            "model": "local",
+            "messages": [
+                {"role": "system", "content": "Be brief."},
+                {"role": "user", "content": "Hi"}
+            ],
+            "temperature": 0.7,
+            "top_p": 0.9,
+            "n": 2,
+            "max_tokens": 20,
+            "stop": ["\n", "User:"],
+            "logit_bias": {"3": -100, "5": -100},
+            "stream": true
+        }"#,
+    )?;
+    assert!(request.stream);
+    assert_eq!(request.stop_sequences(), vec!["\n", "User:"]);
+
+    let config = request.generate_config(&GenerateConfig::default(), 10, 8)?;
+    assert!(config.do_sample);
+    assert_eq!(config.temperature, 0.7);
+    assert_eq!(config.top_p, 0.9);
+    assert_eq!(config.num_return_sequences, 2);
+    assert_eq!(config.max_length, 30);
+    assert_eq!(config.allowed_token_ids, Some(vec![0, 1, 2, 4, 6, 7]));
+
+    let greedy: ChatCompletionRequest = serde_json::from_str(
+        r#"{"model": "local", "messages": [], "temperature": 0, "logit_bias": {"4": 100}}"#,
+    )?;
+    let config = greedy.generate_config(&GenerateConfig::default(), 0, 8)?;
+    assert!(!config.do_sample);
+    assert_eq!(config.allowed_token_ids, Some(vec![4]));
+
+    let unsupported: ChatCompletionRequest =
+        serde_json::from_str(r#"{"model": "local", "messages": [], "logit_bias": {"4": 2.5}}"#)?;
+    assert!(unsupported
+        .generate_config(&GenerateConfig::default(), 0, 8)
+        .is_err());
+    Ok(())
+}
+
+#[test]
+fn test_chat_templates_and_streaming() -> anyhow::Result<()> {
+    let messages = vec![
+        ChatMessage::new(ChatRole::System, "Be brief."),
+        ChatMessage::new(ChatRole::User, "Hi"),
+    ];
+    assert_eq!(
+        ChatTemplate::ChatMl.render(&messages),
+        "<|im_start|>system\nBe brief.<|im_end|>\n<|im_start|>user\nHi<|im_end|>\n<|im_start|>assistant\n"
+    );
+    assert_eq!(
+        ChatTemplate::Llama2.render(&messages),
+        "<s>[INST] <<SYS>>\nBe brief.\n<</SYS>>\n\nHi [/INST]"
+    );
+    assert_eq!(
+        ChatTemplate::Plain.render(&messages),
+        "System: Be brief.\nUser: Hi\nAssistant:"
+    );
+
+    assert_eq!(
+        truncate_at_stop_sequences(
+            "Hello!\nUser: next",
+            &["User:".to_string(), "\n".to_string()]
+        ),
+        ("Hello!".to_string(), true)
+    );
+    assert_eq!(
+        truncate_at_stop_sequences("Hello!", &["\n".to_string()]),
+        ("Hello!".to_string(), false)
+    );
+    assert_eq!(
+        ChatCompletionChunk::SERVER_SENT_EVENTS_END,
+        "data: [DONE]\n\n"
+    );
+    Ok(())
+}
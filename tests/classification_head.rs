@@ -0,0 +1,73 @@
+use rust_bert::classification_head::{
+    ClassificationHead, ClassificationHeadConfig, PoolingStrategy,
+};
+use rust_bert::Activation;
+use tch::{nn, Device, Kind, Tensor};
+
+fn head(pooling: PoolingStrategy, hidden_dims: Vec<i64>) -> (nn::VarStore, ClassificationHead) {
+    let vs = nn::VarStore::new(Device::Cpu);
+    let config = ClassificationHeadConfig {
+        pooling,
+        hidden_dims,
+        activation: Activation::relu,
+        dropout: None,
+    };
+    let head = ClassificationHead::new(&vs.root() / "classifier", 4, 3, &config, 0.1);
+    (vs, head)
+}
+
+#[test]
+fn classification_head_pooling_ignores_padding() {
+    let hidden_states = Tensor::rand(&[2, 3, 4], (Kind::Float, Device::Cpu));
+    let padded_hidden_states = hidden_states.copy();
+    let _ = padded_hidden_states.get(0).get(2).fill_(100.0);
+    let mask = Tensor::of_slice(&[1i64, 1, 0, 1, 1, 1]).view((2, 3));
+
+    for &pooling in &[
+        PoolingStrategy::Mean,
+        PoolingStrategy::Max,
+        PoolingStrategy::Attention,
+    ] {
+        let (_vs, head) = head(pooling, vec![]);
+        let pooled = head.pool(&hidden_states, Some(&mask));
+        let padded_pooled = head.pool(&padded_hidden_states, Some(&mask));
+        assert_eq!(pooled.size(), vec![2, 4]);
+        assert!(
+            (pooled - padded_pooled).abs().max().double_value(&[]) < 1e-5,
+            "{:?} pooling should ignore the padding positions",
+            pooling
+        );
+    }
+
+    let (_vs, mean_head) = head(PoolingStrategy::Mean, vec![]);
+    let expected = hidden_states
+        .get(0)
+        .narrow(0, 0, 2)
+        .mean1(&[0], false, Kind::Float);
+    let pooled = mean_head.pool(&hidden_states, Some(&mask)).get(0);
+    assert!((pooled - expected).abs().max().double_value(&[]) < 1e-5);
+}
+
+#[test]
+fn classification_head_layers() {
+    let (vs, head) = head(PoolingStrategy::Attention, vec![8, 5]);
+    let mut names = vs.variables().keys().cloned().collect::<Vec<String>>();
+    names.sort();
+    assert_eq!(
+        names,
+        vec![
+            "classifier.attention.bias",
+            "classifier.attention.weight",
+            "classifier.layers.0.bias",
+            "classifier.layers.0.weight",
+            "classifier.layers.1.bias",
+            "classifier.layers.1.weight",
+            "classifier.out_proj.bias",
+            "classifier.out_proj.weight",
+        ]
+    );
+
+    let hidden_states = Tensor::rand(&[2, 3, 4], (Kind::Float, Device::Cpu));
+    let logits = head.forward_t(&hidden_states, None, false);
+    assert_eq!(logits.size(), vec![2, 3]);
+}
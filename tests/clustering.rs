@@ -0,0 +1,40 @@
+use rust_bert::pipelines::clustering::{
+    cluster_embeddings, ClusteringAlgorithm, ClusteringOptions,
+};
+use tch::Tensor;
+
+#[test]
+fn clustering_number_of_clusters_selection() -> anyhow::Result<()> {
+    let embeddings = Tensor::of_slice(&[
+        0.0f32, 0.0, 0.1, 0.0, 5.0, 5.0, 0.0, 0.1, 5.1, 5.0, 10.0, 0.0, 10.0, 0.2,
+    ])
+    .view((7, 2));
+
+    for algorithm in &[
+        ClusteringAlgorithm::KMeans,
+        ClusteringAlgorithm::Agglomerative,
+    ] {
+        let options = ClusteringOptions {
+            algorithm: *algorithm,
+            max_clusters: 5,
+            normalize: false,
+            ..Default::default()
+        };
+        let clustering = cluster_embeddings(&embeddings, &options)?;
+        assert_eq!(clustering.num_clusters, 3);
+        assert_eq!(clustering.assignments, vec![0, 0, 1, 0, 1, 2, 2]);
+        assert_eq!(clustering.exemplars, vec![0, 4, 5]);
+        assert!(clustering.silhouette_score > 0.9);
+    }
+
+    let options = ClusteringOptions {
+        num_clusters: Some(2),
+        normalize: false,
+        ..Default::default()
+    };
+    let clustering = cluster_embeddings(&embeddings, &options)?;
+    assert_eq!(clustering.num_clusters, 2);
+    assert_eq!(clustering.assignments, vec![0, 0, 1, 0, 1, 1, 1]);
+
+    Ok(())
+}
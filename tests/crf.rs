@@ -0,0 +1,146 @@
+use rust_bert::crf::{TransitionScores, CRF};
+use rust_bert::pipelines::token_classification::LabelScheme;
+use tch::{nn, Device, Kind, Tensor};
+
+#[test]
+fn viterbi_forbidden_transitions() {
+    // Labels: O, B, I. I cannot start a sequence nor follow O.
+    let forbidden = f64::NEG_INFINITY;
+    let constraints = TransitionScores {
+        transitions: vec![
+            vec![0.0, 0.0, forbidden],
+            vec![0.0, 0.0, 0.0],
+            vec![0.0, 0.0, 0.0],
+        ],
+        start: vec![0.0, 0.0, forbidden],
+        end: vec![0.0; 3],
+    };
+    let emissions = vec![
+        vec![0.1, 0.3, 0.6],
+        vec![0.8, 0.1, 0.1],
+        vec![0.1, 0.2, 0.7],
+    ];
+
+    let unconstrained = TransitionScores::zeros(3);
+    assert_eq!(unconstrained.viterbi_decode(&emissions), vec![2, 0, 2]);
+    assert_eq!(unconstrained.greedy_decode(&emissions), vec![2, 0, 2]);
+    assert_eq!(constraints.viterbi_decode(&emissions), vec![1, 0, 1]);
+    assert_eq!(constraints.greedy_decode(&emissions), vec![1, 0, 1]);
+    assert!(constraints.viterbi_decode(&[]).is_empty());
+
+    // Greedy decoding commits to the best first label, Viterbi finds the best sequence
+    let emissions = vec![vec![0.0, 0.6, 0.0], vec![0.5, -1.0, -1.0]];
+    let mut scores = TransitionScores::zeros(3);
+    scores.transitions[1] = vec![forbidden, 0.0, 0.0];
+    scores.add(&constraints);
+    assert_eq!(scores.greedy_decode(&emissions), vec![1, 1]);
+    assert_eq!(scores.viterbi_decode(&emissions), vec![0, 0]);
+}
+
+#[test]
+fn label_scheme_constraints() {
+    let forbidden = f64::NEG_INFINITY;
+    let labels = ["O", "B-PER", "I-PER", "B-LOC", "I-LOC"];
+    let constraints = LabelScheme::BIO.transition_constraints(&labels);
+    assert_eq!(constraints.start, vec![0.0, 0.0, forbidden, 0.0, forbidden]);
+    assert_eq!(
+        constraints.transitions[1],
+        vec![0.0, 0.0, 0.0, 0.0, forbidden]
+    );
+    assert_eq!(constraints.transitions[0][2], forbidden);
+    assert_eq!(constraints.end, vec![0.0; 5]);
+
+    // The most likely label of each token gives the invalid sequence O I-PER I-LOC
+    let emissions = vec![
+        vec![0.9, 0.0, 0.1, 0.0, 0.0],
+        vec![0.1, 0.4, 0.5, 0.0, 0.0],
+        vec![0.0, 0.0, 0.4, 0.0, 0.6],
+    ];
+    assert_eq!(constraints.viterbi_decode(&emissions), vec![0, 1, 2]);
+    assert_eq!(constraints.greedy_decode(&emissions), vec![0, 1, 2]);
+
+    let labels = ["O", "B-PER", "I-PER", "E-PER", "S-PER", "MISC"];
+    let constraints = LabelScheme::BILOU.transition_constraints(&labels);
+    assert_eq!(
+        constraints.start,
+        vec![0.0, 0.0, forbidden, forbidden, 0.0, 0.0]
+    );
+    assert_eq!(
+        constraints.end,
+        vec![0.0, forbidden, forbidden, 0.0, 0.0, 0.0]
+    );
+    assert_eq!(
+        constraints.transitions[1],
+        vec![forbidden, forbidden, 0.0, 0.0, forbidden, 0.0]
+    );
+    assert_eq!(
+        constraints.transitions[3],
+        vec![0.0, 0.0, forbidden, forbidden, 0.0, 0.0]
+    );
+    // A single token cannot begin an entity without ending it
+    assert_eq!(
+        constraints.viterbi_decode(&[vec![0.1, 0.6, 0.0, 0.0, 0.3, 0.0]]),
+        vec![4]
+    );
+}
+
+#[test]
+fn crf_likelihood_and_decoding() {
+    tch::manual_seed(42);
+    let vs = nn::VarStore::new(Device::Cpu);
+    let crf = CRF::new(&vs.root() / "crf", 2);
+    let emissions = Tensor::randn(&[1, 3, 2], (Kind::Float, Device::Cpu));
+
+    // The sequence probabilities sum to 1, and the decoded sequence is the most likely one
+    let sequences = (0..8)
+        .map(|index| vec![(index >> 2) & 1, (index >> 1) & 1, index & 1])
+        .collect::<Vec<Vec<i64>>>();
+    let log_likelihoods = sequences
+        .iter()
+        .map(|labels| {
+            crf.log_likelihood(&emissions, &Tensor::of_slice(labels).view((1, 3)), None)
+                .double_value(&[0])
+        })
+        .collect::<Vec<f64>>();
+    let total: f64 = log_likelihoods.iter().map(|value| value.exp()).sum();
+    assert!((total - 1.0).abs() < 1e-5);
+    let best = log_likelihoods
+        .iter()
+        .enumerate()
+        .max_by(|a, b| a.1.partial_cmp(b.1).unwrap())
+        .unwrap()
+        .0;
+    assert_eq!(crf.decode(&emissions, None), vec![sequences[best].clone()]);
+
+    // Padding positions are ignored
+    let padded_emissions = Tensor::cat(
+        &[
+            &emissions,
+            &Tensor::randn(&[1, 2, 2], (Kind::Float, Device::Cpu)),
+        ],
+        1,
+    );
+    let padded_labels = Tensor::of_slice(&[1i64, 0, 1, 1, 1]).view((1, 5));
+    let mask = Tensor::of_slice(&[1i64, 1, 1, 0, 0]).view((1, 5));
+    let padded = crf
+        .log_likelihood(&padded_emissions, &padded_labels, Some(&mask))
+        .double_value(&[0]);
+    assert!((padded - log_likelihoods[5]).abs() < 1e-5);
+    assert_eq!(
+        crf.decode(&padded_emissions, Some(&mask)),
+        vec![sequences[best].clone()]
+    );
+}
+
+#[test]
+fn crf_training() {
+    let vs = nn::VarStore::new(Device::Cpu);
+    let crf = CRF::new(&vs.root() / "crf", 3);
+    let emissions = Tensor::randn(&[2, 4, 3], (Kind::Float, Device::Cpu)).set_requires_grad(true);
+    let labels = Tensor::of_slice(&[0i64, 1, 2, 0, 1, 2, 2, 0]).view((2, 4));
+    let loss = crf.loss(&emissions, &labels, None);
+    assert!(loss.double_value(&[]) > 0.0);
+    loss.backward();
+    assert_eq!(emissions.grad().size(), vec![2, 4, 3]);
+    assert_eq!(vs.variables().len(), 3);
+}
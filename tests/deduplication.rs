@@ -0,0 +1,87 @@
+use rust_bert::pipelines::deduplication::{
+    find_near_duplicates, DeduplicationConfig, Deduplicator, DuplicateCluster, LshIndex,
+};
+use rust_bert::pipelines::feature_extraction::TextEmbedder;
+use rust_bert::RustBertError;
+use tch::Tensor;
+
+struct CharacterEmbedder;
+
+impl TextEmbedder for CharacterEmbedder {
+    fn embed(&self, texts: &[&str]) -> Result<Tensor, RustBertError> {
+        let embeddings = texts
+            .iter()
+            .map(|text| {
+                let mut counts = [0f32; 26];
+                for c in text.to_lowercase().chars().filter(char::is_ascii_lowercase) {
+                    counts[(c as u8 - b'a') as usize] += 1.0;
+                }
+                Tensor::of_slice(&counts)
+            })
+            .collect::<Vec<_>>();
+        Ok(Tensor::stack(&embeddings, 0))
+    }
+}
+
+#[test]
+fn lsh_index_search() -> anyhow::Result<()> {
+    let mut index = LshIndex::new(3, 4, 8, 42)?;
+    index.add(&[1.0, 0.0, 0.0])?;
+    index.add(&[0.0, 1.0, 0.0])?;
+    index.add(&[2.0, 0.01, 0.0])?;
+    assert_eq!(index.len(), 3);
+
+    let neighbours = index.search(&[1.0, 0.0, 0.0], 0.9)?;
+    assert_eq!(
+        neighbours.iter().map(|(id, _)| *id).collect::<Vec<_>>(),
+        vec![0, 2]
+    );
+    assert!((neighbours[0].1 - 1.0).abs() < 1e-6);
+    assert!(index.search(&[1.0, 0.0], 0.9).is_err());
+    assert!(LshIndex::new(3, 4, 65, 42).is_err());
+
+    Ok(())
+}
+
+#[test]
+fn near_duplicate_clusters() -> anyhow::Result<()> {
+    let documents = [
+        "The cat sat on the mat.",
+        "A dog barked at the mailman",
+        "the cat sat on the mat!",
+        "Quick brown fox",
+        "A dog barked at the mailman!!",
+        "THE CAT SAT ON THE MAT",
+    ];
+    let config = DeduplicationConfig {
+        similarity_threshold: 0.99,
+        batch_size: 4,
+        num_tables: 16,
+        ..Default::default()
+    };
+
+    let clusters = find_near_duplicates(&CharacterEmbedder, documents.iter().copied(), config)?;
+    assert_eq!(
+        clusters,
+        vec![
+            DuplicateCluster {
+                documents: vec![0, 2, 5]
+            },
+            DuplicateCluster {
+                documents: vec![1, 4]
+            },
+        ]
+    );
+    assert_eq!(clusters[0].representative(), 0);
+    assert_eq!(clusters[0].duplicates(), &[2, 5]);
+
+    let mut deduplicator = Deduplicator::new(config);
+    let first_matches = deduplicator.add_documents(&CharacterEmbedder, &documents[..3])?;
+    assert_eq!(first_matches, vec![None, None, Some(0)]);
+    let second_matches = deduplicator.add_documents(&CharacterEmbedder, &documents[3..])?;
+    assert_eq!(second_matches, vec![None, Some(1), Some(0)]);
+    assert_eq!(deduplicator.len(), 6);
+    assert_eq!(deduplicator.duplicates(), vec![2, 4, 5]);
+
+    Ok(())
+}
@@ -0,0 +1,59 @@
+use rust_bert::pipelines::differential_privacy::{
+    clip_embeddings, GaussianMechanism, PrivacyAccountant, PrivateEmbedder,
+};
+use rust_bert::pipelines::feature_extraction::TextEmbedder;
+use rust_bert::RustBertError;
+use tch::{Device, Kind, Tensor};
+
+struct ConstantEmbedder;
+
+impl TextEmbedder for ConstantEmbedder {
+    fn embed(&self, texts: &[&str]) -> Result<Tensor, RustBertError> {
+        Ok(Tensor::full(
+            &[texts.len() as i64, 1000],
+            0.5,
+            (Kind::Float, Device::Cpu),
+        ))
+    }
+}
+
+#[test]
+fn differential_privacy_clipping() {
+    let embeddings = Tensor::of_slice(&[3.0f32, 4.0, 0.3, 0.4]).view((2, 2));
+    let clipped = clip_embeddings(&embeddings, 1.0);
+    let expected = Tensor::of_slice(&[0.6f32, 0.8, 0.3, 0.4]).view((2, 2));
+    assert!((clipped - expected).abs().max().double_value(&[]) < 1e-6);
+}
+
+#[test]
+fn differential_privacy_accounting() -> anyhow::Result<()> {
+    let mechanism = GaussianMechanism::for_budget(2.0, 1.0, 1e-5)?;
+    assert!((mechanism.epsilon(1e-5) - 1.0).abs() < 1e-9);
+    assert!((mechanism.noise_std() - 4.0 * mechanism.noise_multiplier).abs() < 1e-9);
+
+    let mut accountant = PrivacyAccountant::new();
+    accountant.record(&mechanism, 4);
+    assert!((accountant.rho() - 4.0 * mechanism.rho()).abs() < 1e-12);
+    //    Composition is sub-linear in epsilon
+    assert!(accountant.epsilon(1e-5) > 1.0);
+    assert!(accountant.epsilon(1e-5) < 4.0);
+
+    assert!(GaussianMechanism::new(0.0, 1.0).is_err());
+    assert!(GaussianMechanism::for_budget(1.0, 1.0, 1.0).is_err());
+    Ok(())
+}
+
+#[test]
+fn differential_privacy_embedder() -> anyhow::Result<()> {
+    let mechanism = GaussianMechanism::new(1.0, 0.5)?;
+    let embedder = PrivateEmbedder::new(ConstantEmbedder, mechanism);
+    let embeddings = embedder.embed(&["first text", "second text"])?;
+    assert_eq!(embeddings.size(), vec![2, 1000]);
+
+    //    The noise has the standard deviation of the mechanism around the clipped embeddings
+    let clipped = clip_embeddings(&ConstantEmbedder.embed(&["text"])?, 1.0);
+    let noise = embeddings - clipped;
+    let noise_std = noise.std(true).double_value(&[]);
+    assert!((noise_std - mechanism.noise_std()).abs() < 0.1);
+    Ok(())
+}
@@ -3,10 +3,12 @@ use rust_bert::distilbert::{
     DistilBertForTokenClassification, DistilBertModelMaskedLM, DistilBertModelResources,
     DistilBertVocabResources,
 };
+use rust_bert::pipelines::open_domain_qa::{OpenDomainQaConfig, OpenDomainQaModel, ScoreFusion};
 use rust_bert::pipelines::question_answering::{QaInput, QuestionAnsweringModel};
+use rust_bert::pipelines::rag::{Passage, RetrievedPassage, Retriever};
 use rust_bert::pipelines::sentiment::{SentimentModel, SentimentPolarity};
 use rust_bert::resources::{RemoteResource, Resource};
-use rust_bert::Config;
+use rust_bert::{Config, RustBertError};
 use rust_tokenizers::tokenizer::{BertTokenizer, MultiThreadedTokenizer, TruncationStrategy};
 use rust_tokenizers::vocab::Vocab;
 use std::collections::HashMap;
@@ -276,3 +278,46 @@ fn distilbert_question_answering() -> anyhow::Result<()> {
 
     Ok(())
 }
+
+struct FixedRetriever(Vec<RetrievedPassage>);
+
+impl Retriever for FixedRetriever {
+    fn retrieve(&self, _: &str, top_k: usize) -> Result<Vec<RetrievedPassage>, RustBertError> {
+        Ok(self.0.iter().take(top_k).cloned().collect())
+    }
+}
+
+#[test]
+fn distilbert_open_domain_question_answering() -> anyhow::Result<()> {
+    let passages = vec![
+        RetrievedPassage {
+            passage: Passage::new(
+                "eric",
+                "While Amy lives in Amsterdam, Eric is in The Hague.",
+            ),
+            score: 0.6,
+        },
+        RetrievedPassage {
+            passage: Passage::new("amy", "Amy lives in Amsterdam"),
+            score: 0.9,
+        },
+    ];
+    let config = OpenDomainQaConfig {
+        answers_per_passage: 1,
+        ..Default::default()
+    };
+    let qa_model = OpenDomainQaModel::new(config, Box::new(FixedRetriever(passages)))?;
+
+    let answers = qa_model.predict(&["Where does Amy live ?"])?;
+    assert_eq!(answers.len(), 1);
+    assert_eq!(answers[0].len(), 1);
+    assert_eq!(answers[0][0].answer, "Amsterdam");
+    assert_eq!(answers[0][0].passage.passage.id, "amy");
+    assert_eq!(answers[0][0].supporting_passages, vec!["eric", "amy"]);
+    assert!(answers[0][0].score > 0.9);
+
+    assert_eq!(ScoreFusion::Reader.fuse(0.8, 0.2), 0.8);
+    assert!((ScoreFusion::Product.fuse(0.8, 0.5) - 0.6).abs() < 1e-9);
+
+    Ok(())
+}
@@ -0,0 +1,119 @@
+use rust_bert::pipelines::entity_linking::{
+    CrossEncoder, EntityLinker, EntityLinkingConfig, Mention,
+};
+use rust_bert::pipelines::ner::Entity;
+use rust_bert::pipelines::rag::{Passage, RetrievedPassage, Retriever};
+use rust_bert::RustBertError;
+
+struct StaticRetriever;
+
+impl Retriever for StaticRetriever {
+    fn retrieve(&self, query: &str, top_k: usize) -> Result<Vec<RetrievedPassage>, RustBertError> {
+        if !query.contains("Paris") {
+            return Ok(vec![]);
+        }
+        let candidates = vec![
+            RetrievedPassage {
+                passage: Passage {
+                    title: Some("Paris Hilton".to_string()),
+                    ..Passage::new("Q47899", "American media personality.")
+                },
+                score: 0.9,
+            },
+            RetrievedPassage {
+                passage: Passage {
+                    title: Some("Paris".to_string()),
+                    ..Passage::new("Q90", "Capital city of France.")
+                },
+                score: 0.8,
+            },
+        ];
+        Ok(candidates.into_iter().take(top_k).collect())
+    }
+}
+
+// Scores the entries by the number of their words found in the mention context
+struct OverlapCrossEncoder;
+
+impl CrossEncoder for OverlapCrossEncoder {
+    fn score_pairs(&self, pairs: &[(&str, &str)]) -> Result<Vec<f64>, RustBertError> {
+        Ok(pairs
+            .iter()
+            .map(|(mention, entry)| {
+                entry
+                    .split(|c: char| !c.is_alphanumeric())
+                    .filter(|word| word.len() > 3 && mention.contains(word))
+                    .count() as f64
+            })
+            .collect())
+    }
+}
+
+fn entity(word: &str, label: &str) -> Entity {
+    Entity {
+        word: word.to_string(),
+        score: 0.99,
+        label: label.to_string(),
+    }
+}
+
+#[test]
+fn entity_linking_mentions_from_entities() {
+    let context = "Amy moved from New York to Paris, France.";
+    let entities = [
+        entity("Amy", "I-PER"),
+        entity("New", "I-LOC"),
+        entity("York", "I-LOC"),
+        entity("Paris", "I-LOC"),
+        entity("France", "B-LOC"),
+    ];
+
+    let mentions = Mention::from_entities(&entities, context);
+
+    let texts = mentions
+        .iter()
+        .map(|mention| mention.text.as_str())
+        .collect::<Vec<&str>>();
+    assert_eq!(texts, vec!["Amy", "New York", "Paris", "France"]);
+    assert_eq!(mentions[1].label.as_deref(), Some("LOC"));
+    assert_eq!(mentions[1].offset, Some((15, 23)));
+    assert_eq!(mentions[2].query(6), "rk to [START] Paris [END], Fran");
+}
+
+#[test]
+fn entity_linking_disambiguation() -> anyhow::Result<()> {
+    let mentions = [
+        Mention::new("Paris", "The capital city of France is Paris."),
+        Mention::new("Lyon", "Lyon is in France."),
+    ];
+
+    let retrieval_only =
+        EntityLinker::new(EntityLinkingConfig::default(), Box::new(StaticRetriever));
+    let linked = retrieval_only.link(&mentions)?;
+    assert_eq!(linked[0].kb_id.as_deref(), Some("Q47899"));
+    assert_eq!(linked[1].kb_id, None);
+    assert!(linked[1].candidates.is_empty());
+
+    let linker = EntityLinker::new(EntityLinkingConfig::default(), Box::new(StaticRetriever))
+        .with_cross_encoder(Box::new(OverlapCrossEncoder));
+    let linked = linker.link(&mentions)?;
+    assert_eq!(linked[0].kb_id.as_deref(), Some("Q90"));
+    assert_eq!(
+        linked[0].entry().unwrap().passage.title.as_deref(),
+        Some("Paris")
+    );
+    assert_eq!(linked[0].candidates.len(), 2);
+    assert!(linked[0].candidates[0].score > linked[0].candidates[1].score);
+
+    let config = EntityLinkingConfig {
+        min_score: Some(5.0),
+        ..Default::default()
+    };
+    let linker = EntityLinker::new(config, Box::new(StaticRetriever))
+        .with_cross_encoder(Box::new(OverlapCrossEncoder));
+    let linked = linker.link(&mentions[..1])?;
+    assert_eq!(linked[0].kb_id, None);
+    assert!(linked[0].entry().is_none());
+    assert_eq!(linked[0].candidates.len(), 2);
+    Ok(())
+}
@@ -6,11 +6,18 @@ use rust_bert::pipelines::common::ModelType;
 use rust_bert::pipelines::conversation::{
     ConversationConfig, ConversationManager, ConversationModel,
 };
-use rust_bert::pipelines::generation_utils::{Cache, LMHeadModel};
+use rust_bert::pipelines::generation_utils::{
+    Cache, GPT2Generator, GenerateConfig, GenerateOptions, GenerationPreset, LMHeadModel,
+    LanguageGenerator, PrefixTree, ScoredGeneration, StopSequences, StoppingCriteria,
+};
+use rust_bert::pipelines::grammar::Grammar;
+use rust_bert::pipelines::structured_output::JsonSchema;
 use rust_bert::pipelines::text_generation::{TextGenerationConfig, TextGenerationModel};
-use rust_bert::resources::{RemoteResource, Resource};
+use rust_bert::resources::{LocalResource, RemoteResource, Resource};
 use rust_bert::Config;
 use rust_tokenizers::tokenizer::{Gpt2Tokenizer, Tokenizer, TruncationStrategy};
+use std::io::Write;
+use std::sync::Arc;
 use tch::{nn, Device, Tensor};
 
 #[test]
@@ -480,3 +487,554 @@ fn dialogpt_multiple_multi_turn_conversation_with_conversation_deletion() -> any
 
     Ok(())
 }
+
+#[test]
+fn gpt2_generation_preset() -> anyhow::Result<()> {
+    let mut generation_config_file = tempfile::NamedTempFile::new()?;
+    generation_config_file.write_all(
+        br#"{
+            "bos_token_id": 50256,
+            "eos_token_id": [50256, 198],
+            "do_sample": false,
+            "early_stopping": "never",
+            "num_beams": 3,
+            "max_new_tokens": 32,
+            "transformers_version": "4.26.0"
+        }"#,
+    )?;
+    let preset = GenerationPreset::from_resource(&Resource::Local(LocalResource {
+        local_path: generation_config_file.path().into(),
+    }))?;
+
+    let generate_config = GenerateConfig {
+        num_beams: 4,
+        ..GenerateConfig::default().with_preset(&preset)
+    };
+
+    assert_eq!(generate_config.bos_token_id, Some(50256));
+    assert_eq!(generate_config.eos_token_ids, Some(vec![50256, 198]));
+    assert_eq!(generate_config.pad_token_id, None);
+    assert!(!generate_config.do_sample);
+    assert!(!generate_config.early_stopping);
+    assert_eq!(generate_config.num_beams, 4);
+    assert_eq!(generate_config.max_length, 20);
+    assert!(preset.extra.contains_key("max_new_tokens"));
+
+    let mut generation_config_file = tempfile::NamedTempFile::new()?;
+    generation_config_file.write_all(br#"{"early_stopping": "sometimes"}"#)?;
+    assert!(
+        GenerationPreset::from_resource(&Resource::Local(LocalResource {
+            local_path: generation_config_file.path().into(),
+        }))
+        .is_err()
+    );
+
+    Ok(())
+}
+
+#[test]
+fn gpt2_generation_cache_check() -> anyhow::Result<()> {
+    let generate_config = GenerateConfig {
+        max_length: 24,
+        do_sample: false,
+        num_beams: 3,
+        cache_check_tolerance: Some(1e-3),
+        ..Default::default()
+    };
+    let model = GPT2Generator::new(generate_config)?;
+
+    let output = model.generate(
+        Some(&["The dog", "The cat was sitting on"]),
+        None,
+        None,
+        None,
+        None,
+    );
+
+    assert_eq!(output.len(), 2);
+
+    Ok(())
+}
+
+#[test]
+fn gpt2_generation_streaming() -> anyhow::Result<()> {
+    let generate_config = GenerateConfig {
+        max_length: 24,
+        do_sample: false,
+        num_beams: 1,
+        echo_prompt: false,
+        ..Default::default()
+    };
+    let model = GPT2Generator::new(generate_config)?;
+
+    let prompts = ["The dog", "The cat was sitting on"];
+    let mut streamed = vec![String::new(); 2];
+    let output = model.generate_with_callback(Some(&prompts), None, None, None, None, |token| {
+        streamed[token.sequence_index].push_str(&token.text)
+    })?;
+
+    assert_eq!(
+        output,
+        model.generate(Some(&prompts), None, None, None, None)
+    );
+    // The streamed texts are decoded without cleaning up the tokenization spaces
+    let without_spaces = |text: &str| text.split_whitespace().collect::<String>();
+    for (streamed_text, output_text) in streamed.iter().zip(output.iter()) {
+        assert_eq!(without_spaces(streamed_text), without_spaces(output_text));
+    }
+
+    let beam_search_model = GPT2Generator::new(GenerateConfig {
+        num_beams: 3,
+        ..Default::default()
+    })?;
+    assert!(beam_search_model
+        .generate_with_callback(Some(&prompts), None, None, None, None, |_| {})
+        .is_err());
+
+    Ok(())
+}
+
+#[test]
+fn stop_sequences_positions() -> anyhow::Result<()> {
+    let vocabulary = ["Hello", " world", ".", "#", "##", " again"];
+    let decode = |ids: &[i64]| {
+        ids.iter()
+            .map(|&id| vocabulary[id as usize])
+            .collect::<String>()
+    };
+
+    let stop_sequences = StopSequences {
+        texts: vec!["###".to_string()],
+        token_ids: vec![vec![2, 5]],
+    };
+    // The stop text starts within the token, which is excluded
+    assert_eq!(
+        stop_sequences.stop_position(&[0, 1, 4, 3, 5], &decode),
+        Some(2)
+    );
+    assert_eq!(stop_sequences.stop_position(&[0, 3, 4], &decode), Some(1));
+    // The earliest stop sequence is used
+    assert_eq!(
+        stop_sequences.stop_position(&[0, 2, 5, 4, 3], &decode),
+        Some(1)
+    );
+    assert_eq!(stop_sequences.stop_position(&[0, 1, 2, 4], &decode), None);
+    assert_eq!(
+        StopSequences::default().stop_position(&[0, 3, 4], &decode),
+        None
+    );
+
+    Ok(())
+}
+
+#[test]
+fn gpt2_generation_stop_sequences() -> anyhow::Result<()> {
+    let prompts = ["The dog", "The cat was sitting on"];
+    let generate_config = GenerateConfig {
+        max_length: 32,
+        do_sample: false,
+        num_beams: 1,
+        echo_prompt: false,
+        ..Default::default()
+    };
+    let model = GPT2Generator::new(generate_config)?;
+    let unconstrained_output = model.generate(Some(&prompts), None, None, None, None);
+
+    for num_beams in &[1, 3] {
+        let model = GPT2Generator::new(GenerateConfig {
+            max_length: 32,
+            do_sample: false,
+            num_beams: *num_beams,
+            echo_prompt: false,
+            stopping_criteria: Some(Arc::new(StopSequences::from_texts(&["."]))),
+            ..Default::default()
+        })?;
+        let output = model.generate(Some(&prompts), None, None, None, None);
+        assert_eq!(output.len(), 2);
+        for text in output.iter() {
+            assert!(!text.contains('.'));
+        }
+        if *num_beams == 1 {
+            for (text, unconstrained_text) in output.iter().zip(unconstrained_output.iter()) {
+                assert!(unconstrained_text.starts_with(text.trim_end()));
+            }
+        }
+    }
+
+    Ok(())
+}
+
+#[test]
+fn gpt2_generation_force_words() -> anyhow::Result<()> {
+    let vocab_resource =
+        Resource::Remote(RemoteResource::from_pretrained(Gpt2VocabResources::GPT2));
+    let merges_resource =
+        Resource::Remote(RemoteResource::from_pretrained(Gpt2MergesResources::GPT2));
+    let tokenizer: Gpt2Tokenizer = Gpt2Tokenizer::from_file(
+        vocab_resource.get_local_path()?.to_str().unwrap(),
+        merges_resource.get_local_path()?.to_str().unwrap(),
+        false,
+    )?;
+    let force_words_ids = [" elephant", " umbrella"]
+        .iter()
+        .map(|word| tokenizer.convert_tokens_to_ids(&tokenizer.tokenize(word)))
+        .collect::<Vec<Vec<i64>>>();
+
+    let model = GPT2Generator::new(GenerateConfig {
+        max_length: 40,
+        do_sample: false,
+        num_beams: 4,
+        echo_prompt: false,
+        force_words_ids: Some(force_words_ids),
+        ..Default::default()
+    })?;
+    let output = model.generate(
+        Some(&["The dog", "Yesterday I went to the park and"]),
+        None,
+        None,
+        None,
+        None,
+    );
+
+    assert_eq!(output.len(), 2);
+    for text in output.iter() {
+        assert!(text.contains(" elephant"));
+        assert!(text.contains(" umbrella"));
+    }
+
+    Ok(())
+}
+
+#[test]
+fn gpt2_generation_prefix_tree() -> anyhow::Result<()> {
+    let vocab_resource =
+        Resource::Remote(RemoteResource::from_pretrained(Gpt2VocabResources::GPT2));
+    let merges_resource =
+        Resource::Remote(RemoteResource::from_pretrained(Gpt2MergesResources::GPT2));
+    let tokenizer: Gpt2Tokenizer = Gpt2Tokenizer::from_file(
+        vocab_resource.get_local_path()?.to_str().unwrap(),
+        merges_resource.get_local_path()?.to_str().unwrap(),
+        false,
+    )?;
+    let eos_token_id = 50256;
+    let entities = [" Paris", " Lyon", " the city of Marseille"];
+    let mut prefix_tree = PrefixTree::new();
+    for entity in entities.iter() {
+        let mut token_ids = tokenizer.convert_tokens_to_ids(&tokenizer.tokenize(entity));
+        token_ids.push(eos_token_id);
+        prefix_tree.insert(&token_ids);
+    }
+    let paris_ids = tokenizer.convert_tokens_to_ids(&tokenizer.tokenize(" Paris"));
+    assert_eq!(prefix_tree.next_tokens(&[]).len(), 3);
+    assert_eq!(prefix_tree.next_tokens(&paris_ids), vec![eos_token_id]);
+    assert!(prefix_tree.next_tokens(&[eos_token_id]).is_empty());
+
+    let prompt = "The capital of France is";
+    let prompt_length = tokenizer.tokenize(prompt).len();
+    for num_beams in 1..3 {
+        let model = GPT2Generator::new(GenerateConfig {
+            max_length: 16,
+            do_sample: false,
+            num_beams,
+            echo_prompt: false,
+            prefix_allowed_tokens_fn: Some(
+                prefix_tree
+                    .clone()
+                    .into_prefix_allowed_tokens_fn(prompt_length),
+            ),
+            ..Default::default()
+        })?;
+        let output = model.generate(Some(&[prompt]), None, None, None, None);
+
+        assert_eq!(output.len(), 1);
+        assert!(entities
+            .iter()
+            .any(|entity| entity.trim() == output[0].trim()));
+    }
+
+    Ok(())
+}
+
+#[test]
+fn gpt2_generation_bad_words() -> anyhow::Result<()> {
+    let vocab_resource =
+        Resource::Remote(RemoteResource::from_pretrained(Gpt2VocabResources::GPT2));
+    let merges_resource =
+        Resource::Remote(RemoteResource::from_pretrained(Gpt2MergesResources::GPT2));
+    let tokenizer: Gpt2Tokenizer = Gpt2Tokenizer::from_file(
+        vocab_resource.get_local_path()?.to_str().unwrap(),
+        merges_resource.get_local_path()?.to_str().unwrap(),
+        false,
+    )?;
+    let generate_config = GenerateConfig {
+        max_length: 24,
+        do_sample: false,
+        echo_prompt: false,
+        ..Default::default()
+    };
+    let prompts = ["The dog", "The cat was sitting on"];
+    let unconstrained_output = GPT2Generator::new(generate_config.clone())?.generate(
+        Some(&prompts),
+        None,
+        None,
+        None,
+        None,
+    );
+
+    // Bans the first two tokens generated for each prompt, as a multi-token phrase
+    let bad_words_ids = unconstrained_output
+        .iter()
+        .map(|text| tokenizer.convert_tokens_to_ids(&tokenizer.tokenize(text))[..2].to_vec())
+        .collect::<Vec<Vec<i64>>>();
+    let model = GPT2Generator::new(GenerateConfig {
+        bad_words_ids: Some(bad_words_ids.clone()),
+        ..generate_config
+    })?;
+    let output = model.generate(Some(&prompts), None, None, None, None);
+
+    assert_eq!(output.len(), 2);
+    for text in output.iter() {
+        let token_ids = tokenizer.convert_tokens_to_ids(&tokenizer.tokenize(text));
+        for bad_word_ids in bad_words_ids.iter() {
+            assert!(!token_ids
+                .windows(bad_word_ids.len())
+                .any(|window| window == bad_word_ids.as_slice()));
+        }
+    }
+    assert_ne!(output, unconstrained_output);
+
+    Ok(())
+}
+
+#[test]
+fn gpt2_generation_grammar() -> anyhow::Result<()> {
+    let schema = JsonSchema::object(vec![
+        (
+            "answer",
+            JsonSchema::Enum(vec!["yes".to_string(), "no".to_string()]),
+        ),
+        ("confident", JsonSchema::Boolean),
+    ]);
+    let model = GPT2Generator::new(GenerateConfig {
+        max_length: 64,
+        do_sample: false,
+        no_repeat_ngram_size: 0,
+        echo_prompt: false,
+        grammar: Some(Arc::new(Grammar::from_json_schema(&schema))),
+        ..Default::default()
+    })?;
+    let output = model.generate(
+        Some(&["Is the sky blue? Answer in JSON:", "Do cats fly?"]),
+        None,
+        None,
+        None,
+        None,
+    );
+
+    assert_eq!(output.len(), 2);
+    for text in output.iter() {
+        let value: serde_json::Value = serde_json::from_str(text)?;
+        assert!(schema.validate(&value).is_ok());
+    }
+
+    Ok(())
+}
+
+#[test]
+fn gpt2_generation_seed() -> anyhow::Result<()> {
+    let prompts = ["The dog", "The cat was sitting on"];
+    for num_beams in 1..3 {
+        let model = GPT2Generator::new(GenerateConfig {
+            max_length: 24,
+            do_sample: true,
+            num_beams,
+            seed: Some(42),
+            echo_prompt: false,
+            ..Default::default()
+        })?;
+        tch::manual_seed(0);
+        let output = model.generate(Some(&prompts), None, None, None, None);
+        tch::manual_seed(1);
+        let seeded_output = model.generate(Some(&prompts), None, None, None, None);
+
+        assert_eq!(output.len(), 2);
+        assert_eq!(output, seeded_output);
+    }
+
+    Ok(())
+}
+
+#[test]
+fn gpt2_generation_options() -> anyhow::Result<()> {
+    let prompts = ["The dog", "The cat was sitting on"];
+    let model = GPT2Generator::new(GenerateConfig {
+        max_length: 24,
+        do_sample: false,
+        num_beams: 1,
+        ..Default::default()
+    })?;
+    let beam_model = GPT2Generator::new(GenerateConfig {
+        max_length: 16,
+        do_sample: false,
+        num_beams: 3,
+        num_return_sequences: 2,
+        ..Default::default()
+    })?;
+
+    let beam_options = GenerateOptions {
+        max_length: Some(16),
+        num_beams: Some(3),
+        num_return_sequences: Some(2),
+        ..Default::default()
+    };
+    let output = model.generate_with_options(Some(&prompts), None, &beam_options);
+    assert_eq!(output.len(), 4);
+    assert_eq!(
+        output,
+        beam_model.generate(Some(&prompts), None, None, None, None)
+    );
+
+    let short_options = GenerateOptions {
+        max_length: Some(8),
+        ..Default::default()
+    };
+    for sequence in model.generate_indices_with_options(Some(&prompts), None, &short_options) {
+        assert!(sequence.len() <= 8);
+    }
+    assert_eq!(
+        model.generate_with_options(Some(&prompts), None, &GenerateOptions::default()),
+        model.generate(Some(&prompts), None, None, None, None)
+    );
+
+    Ok(())
+}
+
+#[test]
+fn gpt2_generation_max_new_tokens() -> anyhow::Result<()> {
+    let prompt = ["The cat was sitting on"];
+    //    The maximum length is shorter than the prompt: only the number of new tokens bounds the output
+    let model = GPT2Generator::new(GenerateConfig {
+        max_length: 3,
+        max_new_tokens: Some(6),
+        do_sample: false,
+        num_beams: 1,
+        ..Default::default()
+    })?;
+    let vocab_resource =
+        Resource::Remote(RemoteResource::from_pretrained(Gpt2VocabResources::GPT2));
+    let merges_resource =
+        Resource::Remote(RemoteResource::from_pretrained(Gpt2MergesResources::GPT2));
+    let tokenizer: Gpt2Tokenizer = Gpt2Tokenizer::from_file(
+        vocab_resource.get_local_path()?.to_str().unwrap(),
+        merges_resource.get_local_path()?.to_str().unwrap(),
+        false,
+    )?;
+    let prompt_length = tokenizer.tokenize(prompt[0]).len();
+
+    let output = model.generate_indices(Some(&prompt), None, None, None, None);
+    assert_eq!(output[0].len(), prompt_length + 6);
+
+    let options = GenerateOptions {
+        max_new_tokens: Some(2),
+        ..Default::default()
+    };
+    let output = model.generate_indices_with_options(Some(&prompt), None, &options);
+    assert_eq!(output[0].len(), prompt_length + 2);
+
+    let options = GenerateOptions {
+        max_length: Some(prompt_length as i64 + 4),
+        ..Default::default()
+    };
+    let output = model.generate_indices_with_options(Some(&prompt), None, &options);
+    assert_eq!(output[0].len(), prompt_length + 4);
+
+    Ok(())
+}
+
+#[test]
+fn gpt2_generation_typical_epsilon_eta_sampling() -> anyhow::Result<()> {
+    let prompts = ["The dog", "The cat was sitting on"];
+    let greedy_model = GPT2Generator::new(GenerateConfig {
+        max_length: 24,
+        do_sample: false,
+        num_beams: 1,
+        ..Default::default()
+    })?;
+    let greedy_output = greedy_model.generate(Some(&prompts), None, None, None, None);
+
+    //    Only the most likely token is above the cutoff: sampling is equivalent to greedy decoding
+    let epsilon_model = GPT2Generator::new(GenerateConfig {
+        max_length: 24,
+        do_sample: true,
+        num_beams: 1,
+        top_p: 1.0,
+        epsilon_cutoff: 0.99,
+        ..Default::default()
+    })?;
+    let epsilon_output = epsilon_model.generate(Some(&prompts), None, None, None, None);
+    assert_eq!(epsilon_output, greedy_output);
+
+    for num_beams in &[1, 3] {
+        let model = GPT2Generator::new(GenerateConfig {
+            max_length: 24,
+            do_sample: true,
+            num_beams: *num_beams,
+            typical_p: 0.3,
+            eta_cutoff: 1e-3,
+            ..Default::default()
+        })?;
+        let output = model.generate(Some(&prompts), None, None, None, None);
+        assert_eq!(output.len(), 2);
+        assert!(output[0].starts_with("The dog"));
+        assert!(output[1].starts_with("The cat was sitting on"));
+    }
+
+    Ok(())
+}
+
+#[test]
+fn scored_generation_normalization() -> anyhow::Result<()> {
+    let scored =
+        ScoredGeneration::from_token_scores("text".to_string(), vec![-1.0, -2.0, -3.0, -2.0], 0.5);
+    assert!((scored.score + 8.0).abs() < 1e-9);
+    assert!((scored.normalized_score + 4.0).abs() < 1e-9);
+    let empty = ScoredGeneration::from_token_scores(String::new(), vec![], 1.0);
+    assert_eq!(empty.score, 0.0);
+    assert_eq!(empty.normalized_score, 0.0);
+
+    Ok(())
+}
+
+#[test]
+fn gpt2_generation_scores() -> anyhow::Result<()> {
+    let prompts = ["The dog", "The cat was sitting on"];
+    let model = TextGenerationModel::new(TextGenerationConfig {
+        max_length: 24,
+        do_sample: false,
+        num_beams: 1,
+        echo_prompt: false,
+        ..Default::default()
+    })?;
+
+    let output = model.generate_with_scores(&prompts, None);
+    assert_eq!(output.len(), 2);
+    for (scored, text) in output.iter().zip(model.generate(&prompts, None)) {
+        assert_eq!(scored.text, text);
+        assert!(!scored.token_scores.is_empty());
+        assert!(scored.token_scores.iter().all(|&score| score <= 0.0));
+        assert!((scored.score - scored.token_scores.iter().sum::<f64>()).abs() < 1e-6);
+    }
+
+    let beam_search_model = GPT2Generator::new(GenerateConfig {
+        max_length: 24,
+        do_sample: false,
+        num_beams: 3,
+        num_return_sequences: 3,
+        ..Default::default()
+    })?;
+    let output = beam_search_model.generate_with_scores(Some(&prompts), None, None, None, None);
+    assert_eq!(output.len(), 6);
+    assert!(output.iter().all(|scored| scored.normalized_score < 0.0));
+
+    Ok(())
+}
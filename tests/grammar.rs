@@ -0,0 +1,70 @@
+use rust_bert::pipelines::grammar::Grammar;
+use rust_bert::pipelines::structured_output::{JsonSchema, SchemaField};
+
+#[test]
+fn grammar_parsing() -> anyhow::Result<()> {
+    let grammar = Grammar::new(
+        r#"
+        # comma-separated list of numbers
+        root ::= number ("," " "? number)*
+        number ::= "-"? [0-9]+
+        "#,
+    )?;
+    assert!(grammar.accepts("1, -22,333"));
+    assert!(!grammar.accepts("1,"));
+    assert!(grammar.accepts_prefix("1,"));
+    assert!(!grammar.accepts_prefix("1,,"));
+
+    let grammar = Grammar::new(r#"root ::= " "? ("yes" | "no") "."?"#)?;
+    assert!(grammar.accepts(" yes."));
+    assert!(grammar.accepts("no"));
+    assert!(!grammar.accepts("ye"));
+    assert!(!grammar.accepts_prefix("maybe"));
+
+    assert!(Grammar::new("root ::= undefined").is_err());
+    assert!(Grammar::new(r#"root ::= "unterminated"#).is_err());
+    assert!(Grammar::new("root ::= [a-z").is_err());
+
+    Ok(())
+}
+
+#[test]
+fn grammar_json_schema() -> anyhow::Result<()> {
+    let grammar = Grammar::json();
+    assert!(grammar.accepts(r#" {"a": [1, 2.5e3, -0], "b": {"c": null, "d": "x\"yé"}}"#));
+    assert!(grammar.accepts("[]"));
+    assert!(!grammar.accepts("[1,]"));
+    assert!(!grammar.accepts("01"));
+    assert!(!grammar.accepts(r#"{"a" 1}"#));
+
+    let schema = JsonSchema::Object(vec![
+        SchemaField {
+            name: "city".to_string(),
+            schema: JsonSchema::String,
+            required: true,
+        },
+        SchemaField {
+            name: "unit".to_string(),
+            schema: JsonSchema::Enum(vec!["celsius".to_string(), "fahrenheit".to_string()]),
+            required: false,
+        },
+        SchemaField {
+            name: "days".to_string(),
+            schema: JsonSchema::Array(Box::new(JsonSchema::Nullable(Box::new(
+                JsonSchema::Integer,
+            )))),
+            required: false,
+        },
+    ]);
+    let grammar = Grammar::from_json_schema(&schema);
+    assert!(grammar.accepts(r#"{"city": "Paris"}"#));
+    assert!(grammar.accepts(r#"{"city": "Paris", "days": [1, null]}"#));
+    assert!(grammar.accepts(r#"{"city":"Paris","unit":"celsius","days":[]}"#));
+    assert!(grammar.accepts_prefix(r#"{"city": "Paris", "un"#));
+    assert!(!grammar.accepts(r#"{"unit": "celsius"}"#));
+    assert!(!grammar.accepts(r#"{"city": "Paris", "unit": "kelvin"}"#));
+    assert!(!grammar.accepts(r#"{"city": "Paris", "days": [1.5]}"#));
+    assert!(!grammar.accepts(r#"{"city": "Paris",}"#));
+
+    Ok(())
+}
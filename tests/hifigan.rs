@@ -0,0 +1,38 @@
+use rust_bert::hifigan::{HifiGan, HifiGanConfig};
+use rust_bert::Config;
+use tch::{nn, no_grad, Device, Kind, Tensor};
+
+#[test]
+fn hifigan_vocoder() -> anyhow::Result<()> {
+    //    Set-up vocoder with random weights
+    let config: HifiGanConfig = serde_json::from_str(
+        r#"{
+            "model_in_dim": 80,
+            "sampling_rate": 16000,
+            "upsample_initial_channel": 32,
+            "upsample_rates": [4, 4, 4, 4],
+            "upsample_kernel_sizes": [8, 8, 8, 8],
+            "resblock_kernel_sizes": [3, 7, 11],
+            "resblock_dilation_sizes": [[1, 3, 5], [1, 3, 5], [1, 3, 5]]
+        }"#,
+    )?;
+    config.validate()?;
+    let device = Device::Cpu;
+    let vs = nn::VarStore::new(device);
+    let vocoder = HifiGan::new(&vs.root(), &config);
+
+    let variables = vs.variables();
+    assert!(variables.contains_key("upsampler.3.weight"));
+    assert!(variables.contains_key("resblocks.11.convs2.2.weight"));
+    assert!(variables.contains_key("mean"));
+
+    //    Forward pass
+    let spectrogram = Tensor::randn(&[2, 10, 80], (Kind::Float, device));
+    let waveform = no_grad(|| vocoder.forward(&spectrogram));
+
+    assert_eq!(config.hop_length(), 256);
+    assert_eq!(waveform.size(), &[2, 10 * 256]);
+    assert!(waveform.abs().max().double_value(&[]) <= 1.0);
+
+    Ok(())
+}
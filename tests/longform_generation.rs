@@ -0,0 +1,85 @@
+use rust_bert::pipelines::longform_generation::{parse_outline, LongformGenerator};
+use rust_bert::pipelines::prompt_template::PromptTemplate;
+use rust_bert::pipelines::structured_output::TextCompletion;
+use rust_bert::RustBertError;
+use std::cell::RefCell;
+
+struct RecordingCompletion {
+    prompts: RefCell<Vec<String>>,
+}
+
+impl TextCompletion for RecordingCompletion {
+    fn complete(&self, prompt: &str) -> Result<String, RustBertError> {
+        self.prompts.borrow_mut().push(prompt.to_string());
+        Ok(if prompt.starts_with("Outline") {
+            "1. Origins\n2) Spread in Europe\n- Origins\n\n## Legacy\n3. Conclusion".to_string()
+        } else {
+            format!(" Text {} ", self.prompts.borrow().len())
+        })
+    }
+}
+
+#[test]
+fn longform_outline_parsing() -> anyhow::Result<()> {
+    assert_eq!(
+        parse_outline(
+            "1. Introduction\n 2) Methods \n* Results\n\n# 2020 results\n- introduction",
+            10
+        ),
+        vec!["Introduction", "Methods", "Results", "2020 results"]
+    );
+    assert_eq!(parse_outline("A\nB\nC", 2), vec!["A", "B"]);
+    assert!(parse_outline("\n - \n", 3).is_empty());
+
+    Ok(())
+}
+
+#[test]
+fn longform_plan_then_write() -> anyhow::Result<()> {
+    let outline_model = RecordingCompletion {
+        prompts: RefCell::new(vec![]),
+    };
+    let section_model = RecordingCompletion {
+        prompts: RefCell::new(vec![]),
+    };
+    let generator = LongformGenerator::new(&outline_model, &section_model)
+        .with_outline_template(PromptTemplate::new("Outline about {topic}:\n")?)
+        .with_section_template(PromptTemplate::new(
+            "{topic} | {outline} | {previous} | {section}",
+        )?)
+        .with_max_sections(3)
+        .with_context(1, 4);
+
+    let output = generator.generate("printing")?;
+    assert_eq!(
+        outline_model.prompts.borrow().as_slice(),
+        &["Outline about printing:\n"]
+    );
+    assert_eq!(
+        output.outline,
+        vec!["Origins", "Spread in Europe", "Legacy"]
+    );
+    assert_eq!(output.sections.len(), 3);
+    assert_eq!(output.sections[0].text, "Text 1");
+    assert_eq!(output.sections[2].title, "Legacy");
+
+    let prompts = section_model.prompts.borrow().clone();
+    let outline = "1. Origins\n2. Spread in Europe\n3. Legacy";
+    assert_eq!(prompts[0], format!("printing | {} |  | Origins", outline));
+    // The previous section is shared, truncated to its last 4 characters
+    assert_eq!(
+        prompts[1],
+        format!("printing | {} | xt 1 | Spread in Europe", outline)
+    );
+    assert_eq!(
+        output.text,
+        "Origins\n\nText 1\n\nSpread in Europe\n\nText 2\n\nLegacy\n\nText 3"
+    );
+
+    let generator =
+        LongformGenerator::new(&outline_model, &section_model).with_stitching(false, "\n");
+    let output = generator.write_sections("printing", &["Origins", "Legacy"])?;
+    assert_eq!(output.text, "Text 4\nText 5");
+
+    Ok(())
+}
@@ -1,4 +1,7 @@
-use rust_bert::pipelines::translation::{Language, TranslationConfig, TranslationModel};
+use rust_bert::pipelines::generation_utils::ScoredGeneration;
+use rust_bert::pipelines::translation::{
+    Glossary, Language, MaskedMarkup, ScoredTranslation, TranslationConfig, TranslationModel,
+};
 use tch::Device;
 
 #[test]
@@ -22,3 +25,159 @@ fn test_translation() -> anyhow::Result<()> {
 
     Ok(())
 }
+
+#[test]
+fn translation_glossary_terms() -> anyhow::Result<()> {
+    let mut glossary = Glossary::from_pairs(&[
+        ("purchase order", "bon de commande"),
+        ("order", "commande"),
+        ("invoice", "facture"),
+    ]);
+    assert_eq!(
+        glossary.required_terms("The Purchase Order and the invoice of the order"),
+        vec!["bon de commande", "facture", "commande"]
+    );
+    assert!(glossary
+        .required_terms("The orders are in disorder")
+        .is_empty());
+
+    glossary.add("Order", "ordre");
+    assert_eq!(glossary.required_terms("An order"), vec!["ordre"]);
+
+    Ok(())
+}
+
+#[test]
+#[cfg_attr(not(feature = "all-tests"), ignore)]
+fn test_translation_glossary() -> anyhow::Result<()> {
+    let translation_config = TranslationConfig::new(Language::EnglishToFrench, Device::Cpu);
+    let model = TranslationModel::new(translation_config)?;
+    let glossary = Glossary::from_pairs(&[("dog", "toutou")]);
+
+    let output = model.translate_with_glossary(
+        &[
+            "The quick brown fox jumps over the lazy dog",
+            "The cat is asleep",
+        ],
+        &glossary,
+    )?;
+
+    assert_eq!(output.len(), 2);
+    assert!(output[0].contains("toutou"));
+    assert!(!output[1].contains("toutou"));
+
+    Ok(())
+}
+
+#[test]
+#[cfg_attr(not(feature = "all-tests"), ignore)]
+fn test_translation_sentence_batching() -> anyhow::Result<()> {
+    let translation_config = TranslationConfig {
+        sentence_batch_size: Some(2),
+        ..TranslationConfig::new(Language::EnglishToFrench, Device::Cpu)
+    };
+    let model = TranslationModel::new(translation_config)?;
+
+    let input =
+        "The dog did not wake up. The cat is asleep.\n\n- The house is red.\n- It is raining";
+    let output = model.translate(&[input, "Hello."]);
+
+    assert_eq!(output.len(), 2);
+    let paragraphs = output[0].split("\n\n").collect::<Vec<&str>>();
+    assert_eq!(paragraphs.len(), 2);
+    assert!(paragraphs[0].starts_with("Le chien"));
+    let items = paragraphs[1].lines().collect::<Vec<&str>>();
+    assert_eq!(items.len(), 2);
+    assert!(items.iter().all(|item| item.starts_with("- ")));
+
+    Ok(())
+}
+
+#[test]
+fn translation_markup_masking() -> anyhow::Result<()> {
+    let masked = MaskedMarkup::new("Click <a href=\"/home\">here</a> to {action}, {{user}}.");
+    assert_eq!(masked.text, "Click [[0]]here[[1]] to [[2]], [[3]].");
+    assert_eq!(
+        masked.markup,
+        vec!["<a href=\"/home\">", "</a>", "{action}", "{{user}}"]
+    );
+
+    assert_eq!(
+        masked.restore("Cliquez [[0]]ici[[ 1 ]] pour [[2]], [[3]] [[3]] [[7]]."),
+        "Cliquez <a href=\"/home\">ici</a> pour {action}, {{user}}  [[7]]."
+    );
+    //    Dropped masks are inserted at the aligned word boundary
+    assert_eq!(
+        masked.restore("Cliquez ici pour [[2]], [[3]]."),
+        "Cliquez <a href=\"/home\">ici pour</a> {action}, {{user}}."
+    );
+    let unmasked = MaskedMarkup::new("No markup < 3");
+    assert_eq!(unmasked.text, "No markup < 3");
+    assert_eq!(unmasked.restore("Pas de balise"), "Pas de balise");
+
+    Ok(())
+}
+
+#[test]
+#[cfg_attr(not(feature = "all-tests"), ignore)]
+fn test_translation_preserve_markup() -> anyhow::Result<()> {
+    let translation_config = TranslationConfig {
+        preserve_markup: true,
+        ..TranslationConfig::new(Language::EnglishToFrench, Device::Cpu)
+    };
+    let model = TranslationModel::new(translation_config)?;
+
+    let output = model.translate(&["The <b>dog</b> did not wake up, {name}."]);
+
+    assert_eq!(output.len(), 1);
+    for markup in &["<b>", "</b>", "{name}"] {
+        assert_eq!(output[0].matches(markup).count(), 1);
+    }
+    assert!(!output[0].contains("[["));
+
+    Ok(())
+}
+
+#[test]
+fn translation_confidence_aggregation() -> anyhow::Result<()> {
+    let generation = ScoredGeneration::from_token_scores(
+        "Le chien".to_string(),
+        vec![0.5f64.ln(), 0.8f64.ln(), 0.2f64.ln()],
+        1.0,
+    );
+    let translation = ScoredTranslation::from_scored_generation(generation);
+    assert_eq!(translation.text, "Le chien");
+    assert!((translation.confidence - 0.08f64.powf(1.0 / 3.0)).abs() < 1e-9);
+    assert!((translation.min_token_probability - 0.2).abs() < 1e-9);
+    assert!(translation.needs_review(0.5));
+    assert!(!translation.needs_review(0.4));
+
+    let empty = ScoredTranslation::from_scored_generation(ScoredGeneration::from_token_scores(
+        String::new(),
+        vec![],
+        1.0,
+    ));
+    assert_eq!(empty.confidence, 0.0);
+
+    Ok(())
+}
+
+#[test]
+#[cfg_attr(not(feature = "all-tests"), ignore)]
+fn test_translation_confidence() -> anyhow::Result<()> {
+    let translation_config = TranslationConfig::new(Language::EnglishToFrench, Device::Cpu);
+    let model = TranslationModel::new(translation_config)?;
+
+    let output = model
+        .translate_with_confidence(&["The dog did not wake up", "Xqz vlorp gribble fnord wubbaz"]);
+
+    assert_eq!(output.len(), 2);
+    assert_eq!(output[0].text, " Le chien ne s'est pas réveillé");
+    for translation in output.iter() {
+        assert!((translation.confidence > 0.0) & (translation.confidence <= 1.0));
+        assert!(translation.min_token_probability <= translation.confidence);
+    }
+    assert!(output[0].confidence > output[1].confidence);
+
+    Ok(())
+}
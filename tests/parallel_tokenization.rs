@@ -0,0 +1,43 @@
+use rust_bert::pipelines::common::prefetch_batches;
+use rust_bert::RustBertError;
+
+#[test]
+fn prefetch_batches_in_order() -> anyhow::Result<()> {
+    let inputs = ["a", "bb", "ccc", "dddd", "eeeee"];
+    let mut processed = vec![];
+    let outputs = prefetch_batches(
+        &inputs,
+        2,
+        |batch| batch.iter().map(|text| text.len()).collect::<Vec<usize>>(),
+        |batch, lengths| {
+            processed.push(batch.len());
+            Ok(lengths.iter().sum::<usize>())
+        },
+    )?;
+
+    assert_eq!(outputs, vec![3, 7, 5]);
+    assert_eq!(processed, vec![2, 2, 1]);
+    Ok(())
+}
+
+#[test]
+fn prefetch_batches_stops_on_error() {
+    let inputs = ["a", "b", "c", "d"];
+    let mut calls = 0;
+    let result = prefetch_batches(
+        &inputs,
+        1,
+        |batch| batch.len(),
+        |_, _| {
+            calls += 1;
+            if calls == 2 {
+                Err(RustBertError::ValueError("failed".to_string()))
+            } else {
+                Ok(())
+            }
+        },
+    );
+
+    assert!(result.is_err());
+    assert_eq!(calls, 2);
+}
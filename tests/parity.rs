@@ -0,0 +1,85 @@
+extern crate anyhow;
+
+use rust_bert::bert::{BertConfig, BertEmbeddings, BertModel};
+use rust_bert::testing::{
+    compare_activations, named_outputs, run_parity_test, seeded_input_ids, set_var_store_kind,
+    Tolerance,
+};
+use tch::{nn, Device, Kind, Tensor};
+
+#[test]
+fn bert_float_double_parity() -> anyhow::Result<()> {
+    let config: BertConfig = serde_json::from_str(
+        r#"{
+            "hidden_act": "gelu",
+            "attention_probs_dropout_prob": 0.1,
+            "hidden_dropout_prob": 0.1,
+            "hidden_size": 32,
+            "initializer_range": 0.02,
+            "intermediate_size": 64,
+            "max_position_embeddings": 64,
+            "num_attention_heads": 4,
+            "num_hidden_layers": 2,
+            "type_vocab_size": 2,
+            "vocab_size": 100,
+            "output_attentions": true,
+            "output_hidden_states": true
+        }"#,
+    )?;
+
+    //    Randomly initialized weights are identical for both runs
+    let report = run_parity_test(
+        (Device::Cpu, Kind::Double),
+        (Device::Cpu, Kind::Float),
+        Tolerance::for_kind(Kind::Float),
+        42,
+        |device, kind| {
+            let vs = nn::VarStore::new(device);
+            let model = BertModel::<BertEmbeddings>::new(&vs.root(), &config);
+            set_var_store_kind(&vs, kind)?;
+            let input_ids = seeded_input_ids(&[2, 7], config.vocab_size).to(device);
+            let output =
+                model.forward_t(Some(input_ids), None, None, None, None, &None, &None, false)?;
+            let mut activations = named_outputs(&output);
+            activations.push(("last_hidden_state".to_string(), output.hidden_state));
+            Ok(activations)
+        },
+    )?;
+
+    assert_eq!(report.diffs.len(), 5);
+    assert_eq!(report.diffs[0].name, "hidden_states.0");
+    assert_eq!(report.diffs[3].shape, vec![2, 4, 7, 7]);
+    assert_eq!(report.diffs[4].name, "last_hidden_state");
+    assert!(report.passed(), "{}", report);
+
+    Ok(())
+}
+
+#[test]
+fn parity_report_failures() -> anyhow::Result<()> {
+    let reference = vec![
+        ("layer.0".to_string(), Tensor::of_slice(&[1.0f64, 2.0, 3.0])),
+        ("layer.1".to_string(), Tensor::of_slice(&[1.0f64, 2.0, 3.0])),
+    ];
+    let candidate = vec![
+        ("layer.0".to_string(), Tensor::of_slice(&[1.0f64, 2.0, 3.0])),
+        (
+            "layer.1".to_string(),
+            Tensor::of_slice(&[1.0f64, 2.5, f64::NAN]),
+        ),
+    ];
+
+    let report = compare_activations(&reference, &candidate, Tolerance::for_kind(Kind::Float))?;
+    assert!(!report.passed());
+    assert_eq!(report.first_failure().unwrap().name, "layer.1");
+    assert_eq!(report.failures().len(), 1);
+
+    //    Activations with different shapes cannot be compared
+    let candidate = vec![
+        ("layer.0".to_string(), Tensor::of_slice(&[1.0f64, 2.0])),
+        ("layer.1".to_string(), Tensor::of_slice(&[1.0f64, 2.0, 3.0])),
+    ];
+    assert!(compare_activations(&reference, &candidate, Tolerance::for_kind(Kind::Float)).is_err());
+
+    Ok(())
+}
@@ -0,0 +1,36 @@
+use rust_bert::pipelines::post_processing::{
+    Detruecaser, PostProcessor, PostProcessors, ProfanityFilter, RegexReplacer,
+};
+
+#[test]
+fn test_post_processors() -> anyhow::Result<()> {
+    let detruecaser = Detruecaser::default();
+    assert_eq!(
+        detruecaser.process("the museum opens at 9. i'm sure it will be busy! is it free?"),
+        "The museum opens at 9. I'm sure it will be busy! Is it free?"
+    );
+
+    let profanity_filter = ProfanityFilter::new(&["darn", "heck"]);
+    assert_eq!(
+        profanity_filter.process("Darn, what the heck happened to the darning needle?"),
+        "****, what the **** happened to the darning needle?"
+    );
+
+    let regex_replacer = RegexReplacer::new(r"\s+([.,!?])", "$1")?;
+    assert_eq!(regex_replacer.process("Hello , world !"), "Hello, world!");
+    assert!(RegexReplacer::new(r"(unclosed", "").is_err());
+
+    let mut post_processors = PostProcessors::new();
+    post_processors.add(regex_replacer);
+    post_processors.add(detruecaser);
+    post_processors.add(|text: &str| text.replace("Paris", "PARIS"));
+    assert_eq!(post_processors.len(), 3);
+    assert_eq!(
+        post_processors.process_batch(vec![
+            "this is paris .".to_string(),
+            "we visited Paris . it rained".to_string()
+        ]),
+        vec!["This is paris.", "We visited PARIS. It rained"]
+    );
+    Ok(())
+}
@@ -0,0 +1,81 @@
+use rust_bert::profiling::{scope, Profiler};
+use std::thread::sleep;
+use std::time::Duration;
+use tch::{Device, Kind, Tensor};
+
+fn layer(index: usize, input: &Tensor) -> Tensor {
+    let _scope = scope(|| format!("layer.{}", index), &[input]);
+    sleep(Duration::from_millis(2));
+    {
+        let _scope = scope(|| "attention".to_string(), &[input]);
+        sleep(Duration::from_millis(3));
+    }
+    input + 1
+}
+
+#[test]
+fn profiler_records_nested_scopes() {
+    let input = Tensor::zeros(&[2, 3], (Kind::Float, Device::Cpu));
+    let (output, report) = Profiler::default().profile(|| {
+        let _scope = scope(|| "encoder".to_string(), &[&input]);
+        let hidden = layer(0, &input);
+        layer(1, &hidden)
+    });
+
+    assert_eq!(output.sum(Kind::Float).double_value(&[]), 12.0);
+    let paths = report
+        .events
+        .iter()
+        .map(|event| event.path.join(";"))
+        .collect::<Vec<String>>();
+    assert_eq!(
+        paths,
+        vec![
+            "encoder",
+            "encoder;layer.0",
+            "encoder;layer.0;attention",
+            "encoder;layer.1",
+            "encoder;layer.1;attention",
+        ]
+    );
+    assert_eq!(report.events[1].input_shapes, vec![vec![2, 3]]);
+
+    let layer_0 = &report.events[1];
+    let attention_0 = &report.events[2];
+    assert!(attention_0.duration >= Duration::from_millis(3));
+    assert!(layer_0.duration >= layer_0.self_duration + attention_0.duration);
+    assert!(layer_0.self_duration >= Duration::from_millis(2));
+
+    let summary = report.summary();
+    assert_eq!(summary.len(), 5);
+    assert!(summary.iter().all(|module| module.calls == 1));
+
+    let folded = report.to_folded_stacks();
+    let lines = folded.lines().collect::<Vec<&str>>();
+    assert_eq!(lines.len(), 5);
+    assert!(lines[0].starts_with("encoder "));
+    assert!(lines[2].starts_with("encoder;layer.0;attention "));
+
+    let json = report.to_json();
+    assert_eq!(json["events"].as_array().unwrap().len(), 5);
+    assert_eq!(json["summary"].as_array().unwrap().len(), 5);
+}
+
+#[test]
+fn scopes_are_not_recorded_outside_of_a_profiler() {
+    let input = Tensor::zeros(&[1], (Kind::Float, Device::Cpu));
+    let mut named = false;
+    {
+        let _scope = scope(
+            || {
+                named = true;
+                "unused".to_string()
+            },
+            &[&input],
+        );
+    }
+    assert!(!named);
+
+    let (_, report) = Profiler::default().profile(|| ());
+    assert!(report.events.is_empty());
+}
@@ -0,0 +1,34 @@
+use rust_bert::pipelines::prompt_template::{FewShotExamples, PromptFormat, PromptTemplate};
+
+#[test]
+fn test_prompt_template() -> anyhow::Result<()> {
+    let template = PromptTemplate::new("Translate {text} to {language}. {{literal}} {text}")?;
+    assert_eq!(template.variables(), vec!["text", "language"]);
+    assert_eq!(
+        template.render(&[("language", "French"), ("text", "hello")])?,
+        "Translate hello to French. {literal} hello"
+    );
+    assert!(template.render(&[("text", "hello")]).is_err());
+
+    assert!(PromptTemplate::new("Unclosed {text").is_err());
+    assert!(PromptTemplate::new("Unmatched } brace").is_err());
+    assert!(PromptTemplate::new("Invalid {variable name}").is_err());
+
+    let examples = FewShotExamples::new(PromptTemplate::new("Q: {question}\nA: {answer}")?)
+        .with_example(&[("question", "2 + 2?"), ("answer", "4")])
+        .with_example(&[("question", "3 + 5?"), ("answer", "8")]);
+    let template = PromptTemplate::new("{examples}\n\nQ: {question}\nA:")?
+        .with_examples("examples", examples)?
+        .with_format(PromptFormat::Llama2);
+    assert_eq!(template.input_variables(), vec!["question"]);
+    assert_eq!(
+        template.render(&[("question", "1 + 6?")])?,
+        "[INST] Q: 2 + 2?\nA: 4\n\nQ: 3 + 5?\nA: 8\n\nQ: 1 + 6?\nA: [/INST]"
+    );
+
+    let examples = FewShotExamples::new(PromptTemplate::new("{input}")?);
+    assert!(PromptTemplate::new("{question}")?
+        .with_examples("examples", examples)
+        .is_err());
+    Ok(())
+}
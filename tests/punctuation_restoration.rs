@@ -0,0 +1,28 @@
+use rust_bert::pipelines::punctuation_restoration::{
+    parse_restoration_label, Casing, RestorationLabel,
+};
+
+#[test]
+fn test_restoration_labels() {
+    assert_eq!(
+        parse_restoration_label(".U"),
+        RestorationLabel {
+            casing: Casing::Capitalized,
+            punctuation: Some('.'),
+        }
+    );
+    assert_eq!(
+        parse_restoration_label("OO"),
+        RestorationLabel {
+            casing: Casing::Unchanged,
+            punctuation: None,
+        }
+    );
+    assert_eq!(parse_restoration_label("?").punctuation, Some('?'));
+    assert_eq!(parse_restoration_label("0").punctuation, None);
+    assert_eq!(parse_restoration_label("B-PER").casing, Casing::Unchanged);
+
+    assert_eq!(Casing::Capitalized.apply("élise"), "Élise");
+    assert_eq!(Casing::Upper.apply("nasa"), "NASA");
+    assert_eq!(Casing::Lower.apply("Paris"), "paris");
+}
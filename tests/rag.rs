@@ -0,0 +1,205 @@
+use rust_bert::pipelines::document_store::{
+    Document, DocumentStore, InMemoryDocumentStore, MetadataFilter,
+};
+use rust_bert::pipelines::feature_extraction::TextEmbedder;
+use rust_bert::pipelines::rag::{
+    resolve_citations, resolve_sentence_citations, CitedSentence, EmbeddingRetriever, Passage,
+    RetrievedPassage, Retriever,
+};
+use rust_bert::RustBertError;
+use serde_json::{json, Value};
+use tch::Tensor;
+
+struct KeywordEmbedder;
+
+impl TextEmbedder for KeywordEmbedder {
+    fn embed(&self, texts: &[&str]) -> Result<Tensor, RustBertError> {
+        let keywords = ["paris", "france", "amsterdam", "netherlands"];
+        let embeddings = texts
+            .iter()
+            .map(|text| {
+                let text = text.to_lowercase();
+                let mut values = keywords
+                    .iter()
+                    .map(|keyword| text.matches(keyword).count() as f32)
+                    .collect::<Vec<_>>();
+                values.push(0.1);
+                Tensor::of_slice(&values)
+            })
+            .collect::<Vec<_>>();
+        Ok(Tensor::stack(&embeddings, 0))
+    }
+}
+
+#[test]
+fn rag_embedding_retriever() -> anyhow::Result<()> {
+    let mut retriever = EmbeddingRetriever::new(KeywordEmbedder).with_batch_size(2);
+    retriever.add_passages(vec![
+        Passage::new("amsterdam", "Amsterdam is the capital of the Netherlands."),
+        Passage::new("paris", "Paris is the capital of France."),
+        Passage {
+            title: Some("Lyon".to_string()),
+            ..Passage::new("lyon", "A city in France.")
+        },
+    ])?;
+    assert_eq!(retriever.count()?, 3);
+
+    let passages = retriever.retrieve("What is the capital of France? Paris?", 2)?;
+    assert_eq!(passages.len(), 2);
+    assert_eq!(passages[0].passage.id, "paris");
+    assert_eq!(passages[1].passage.id, "lyon");
+    assert!(passages[0].score > passages[1].score);
+    assert_eq!(retriever.retrieve("Netherlands", 10)?.len(), 3);
+
+    let filtered = retriever.retrieve_with_filter(
+        "Paris, France",
+        3,
+        Some(&MetadataFilter::Not(Box::new(MetadataFilter::Equals(
+            "id".to_string(),
+            Value::from("ignored"),
+        )))),
+    )?;
+    assert_eq!(filtered.len(), 3);
+    let filtered = retriever.retrieve_with_filter(
+        "Paris, France",
+        3,
+        Some(&MetadataFilter::Exists("title".to_string())),
+    )?;
+    assert_eq!(filtered.len(), 1);
+    assert_eq!(filtered[0].passage.title.as_deref(), Some("Lyon"));
+
+    Ok(())
+}
+
+#[test]
+fn rag_citations() -> anyhow::Result<()> {
+    let passages = vec![
+        RetrievedPassage {
+            passage: Passage::new("amsterdam", "Amsterdam is the capital of the Netherlands."),
+            score: 0.9,
+        },
+        RetrievedPassage {
+            passage: Passage::new("paris", "Paris is the capital of France."),
+            score: 0.8,
+        },
+    ];
+
+    let (text, citations) = resolve_citations("Paris [2][7]", &passages, 0.5);
+    assert_eq!(text, "Paris [2][7]");
+    assert_eq!(citations, vec![2]);
+
+    let (text, citations) = resolve_citations("the capital of France", &passages, 0.8);
+    assert_eq!(text, "the capital of France [2]");
+    assert_eq!(citations, vec![2]);
+
+    let (text, citations) = resolve_citations("Berlin", &passages, 0.5);
+    assert_eq!(text, "Berlin");
+    assert!(citations.is_empty());
+
+    Ok(())
+}
+
+#[test]
+fn rag_sentence_citations() -> anyhow::Result<()> {
+    let passages = vec![
+        RetrievedPassage {
+            passage: Passage::new("amsterdam", "Amsterdam is the capital of the Netherlands."),
+            score: 0.9,
+        },
+        RetrievedPassage {
+            passage: Passage::new("paris", "Paris is the capital of France."),
+            score: 0.8,
+        },
+        RetrievedPassage {
+            passage: Passage::new("paris_copy", "Paris is the capital of France."),
+            score: 0.85,
+        },
+    ];
+
+    let (text, citations, sentences) = resolve_sentence_citations(
+        "Paris is the capital of France. Amsterdam is in the Netherlands [1]. Berlin is large.",
+        &passages,
+        0.5,
+    );
+    assert_eq!(
+        text,
+        "Paris is the capital of France. [3] Amsterdam is in the Netherlands [1]. Berlin is large."
+    );
+    assert_eq!(citations, vec![1, 3]);
+    assert_eq!(
+        sentences,
+        vec![
+            CitedSentence {
+                text: "Paris is the capital of France.".to_string(),
+                citations: vec![3],
+            },
+            CitedSentence {
+                text: "Amsterdam is in the Netherlands [1].".to_string(),
+                citations: vec![1],
+            },
+            CitedSentence {
+                text: "Berlin is large.".to_string(),
+                citations: vec![],
+            },
+        ]
+    );
+
+    let (text, citations, sentences) =
+        resolve_sentence_citations("Paris is in France. [2][9]", &passages, 0.5);
+    assert_eq!(text, "Paris is in France. [2][9]");
+    assert_eq!(citations, vec![2]);
+    assert_eq!(sentences.len(), 1);
+    assert_eq!(sentences[0].citations, vec![2]);
+
+    Ok(())
+}
+
+#[test]
+fn in_memory_document_store() -> anyhow::Result<()> {
+    let mut store = InMemoryDocumentStore::new();
+    store.add(vec![
+        Document::new("1", "first", vec![1.0, 0.0]).with_metadata("year", json!(2019)),
+        Document::new("2", "second", vec![0.8, 0.6]).with_metadata("year", json!(2021)),
+        Document::new("3", "third", vec![0.0, 1.0]).with_metadata("lang", json!("fr")),
+    ])?;
+    assert_eq!(store.count()?, 3);
+    assert!(store
+        .add(vec![Document::new("4", "fourth", vec![1.0])])
+        .is_err());
+
+    let results = store.search(&[2.0, 0.0], 2, None)?;
+    assert_eq!(
+        results
+            .iter()
+            .map(|r| r.document.id.as_str())
+            .collect::<Vec<_>>(),
+        vec!["1", "2"]
+    );
+    assert!((results[0].score - 1.0).abs() < 1e-6);
+    assert!((results[1].score - 0.8).abs() < 1e-6);
+
+    let recent = MetadataFilter::Range {
+        key: "year".to_string(),
+        min: Some(2020.0),
+        max: None,
+    };
+    let results = store.search(&[1.0, 0.0], 5, Some(&recent))?;
+    assert_eq!(results.len(), 1);
+    assert_eq!(results[0].document.id, "2");
+    let filter = MetadataFilter::Or(vec![
+        recent,
+        MetadataFilter::In("lang".to_string(), vec![json!("fr"), json!("de")]),
+    ]);
+    assert_eq!(store.search(&[1.0, 0.0], 5, Some(&filter))?.len(), 2);
+
+    store.add(vec![Document::new("1", "first, updated", vec![0.0, 1.0])])?;
+    assert_eq!(store.count()?, 3);
+    assert_eq!(store.get("1")?.unwrap().text, "first, updated");
+    assert!(store.delete("1")?);
+    assert!(!store.delete("1")?);
+    assert!(store.get("1")?.is_none());
+    assert_eq!(store.get("3")?.unwrap().text, "third");
+    assert_eq!(store.count()?, 2);
+
+    Ok(())
+}
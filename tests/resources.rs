@@ -0,0 +1,203 @@
+use rust_bert::resources::{
+    collect_garbage, register_backend, verify_cache_dir, CacheManifest, RemoteResource, Resource,
+    ResourceBackend,
+};
+use rust_bert::RustBertError;
+use std::collections::HashMap;
+use std::fs;
+use std::io::Write;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{Arc, RwLock};
+
+#[derive(Default)]
+struct InMemoryBackend {
+    objects: Arc<RwLock<HashMap<&'static str, (u32, Vec<u8>)>>>,
+    downloads: Arc<AtomicUsize>,
+    offline: Arc<AtomicBool>,
+}
+
+impl InMemoryBackend {
+    fn object(&self, url: &str) -> Result<(u32, Vec<u8>), RustBertError> {
+        if self.offline.load(Ordering::SeqCst) {
+            return Err(RustBertError::FileDownloadError(format!(
+                "{}: backend unreachable",
+                url
+            )));
+        }
+        self.objects
+            .read()
+            .unwrap()
+            .get(url)
+            .cloned()
+            .ok_or_else(|| RustBertError::FileDownloadError(format!("{} not found", url)))
+    }
+}
+
+impl ResourceBackend for InMemoryBackend {
+    fn scheme(&self) -> &str {
+        "memory"
+    }
+
+    fn version(&self, url: &str) -> Result<Option<String>, RustBertError> {
+        Ok(Some(self.object(url)?.0.to_string()))
+    }
+
+    fn download(&self, url: &str, writer: &mut dyn Write) -> Result<Option<String>, RustBertError> {
+        self.downloads.fetch_add(1, Ordering::SeqCst);
+        let (version, content) = self.object(url)?;
+        writer.write_all(&content)?;
+        Ok(Some(version.to_string()))
+    }
+}
+
+fn content_address(url_digit: char, etag_digit: Option<char>) -> String {
+    let url_digest = url_digit.to_string().repeat(64);
+    match etag_digit {
+        Some(etag_digit) => format!("{}.{}", url_digest, etag_digit.to_string().repeat(64)),
+        None => url_digest,
+    }
+}
+
+#[test]
+fn cache_manifest_verification() -> anyhow::Result<()> {
+    let cache_dir = tempfile::tempdir()?;
+    let weights_name = content_address('a', Some('b'));
+    let weights_path = cache_dir.path().join(&weights_name);
+    let config_path = cache_dir.path().join(content_address('c', None));
+    fs::write(&weights_path, b"model weights")?;
+    fs::write(&config_path, b"{\"vocab_size\": 8}")?;
+    fs::write(
+        cache_dir.path().join(format!("{}.json", weights_name)),
+        b"{}",
+    )?;
+    fs::write(cache_dir.path().join("notes.txt"), b"not a cached resource")?;
+
+    let mut manifest = CacheManifest::default();
+    manifest.record(&weights_path)?;
+    manifest.record(&config_path)?;
+    manifest.save(cache_dir.path())?;
+    assert_eq!(CacheManifest::load(cache_dir.path())?, manifest);
+    assert_eq!(manifest.entries[&weights_name].size, 13);
+
+    let verification = verify_cache_dir(cache_dir.path())?;
+    assert!(verification.is_valid());
+    assert_eq!(verification.verified.len(), 2);
+
+    fs::write(&weights_path, b"truncated")?;
+    fs::remove_file(&config_path)?;
+    let untracked_path = cache_dir.path().join(content_address('d', None));
+    fs::write(&untracked_path, b"unknown file")?;
+    let verification = verify_cache_dir(cache_dir.path())?;
+    assert!(!verification.is_valid());
+    assert_eq!(verification.corrupted, vec![weights_path.clone()]);
+    assert_eq!(verification.missing, vec![config_path]);
+    assert_eq!(verification.untracked, vec![untracked_path.clone()]);
+
+    let removed_files = collect_garbage(&verification)?;
+    assert_eq!(removed_files.len(), 3);
+    assert!(!weights_path.exists());
+    assert!(!untracked_path.exists());
+    assert!(cache_dir.path().join("notes.txt").exists());
+    assert!(CacheManifest::load(cache_dir.path())?.entries.is_empty());
+    assert!(verify_cache_dir(cache_dir.path())?.is_valid());
+
+    Ok(())
+}
+
+#[test]
+fn cache_manifest_concurrent_updates() -> anyhow::Result<()> {
+    let cache_dir = tempfile::tempdir()?;
+    let file_names = (0..8)
+        .map(|index| content_address(std::char::from_digit(index, 16).unwrap(), None))
+        .collect::<Vec<String>>();
+    let handles = file_names
+        .iter()
+        .map(|file_name| {
+            let path = cache_dir.path().join(file_name);
+            fs::write(&path, file_name.as_bytes())?;
+            let cache_dir = cache_dir.path().to_path_buf();
+            Ok(std::thread::spawn(move || {
+                CacheManifest::update(&cache_dir, |manifest| manifest.record(&path))
+            }))
+        })
+        .collect::<std::io::Result<Vec<_>>>()?;
+    for handle in handles {
+        handle.join().unwrap()?;
+    }
+
+    let manifest = CacheManifest::load(cache_dir.path())?;
+    assert_eq!(manifest.entries.len(), file_names.len());
+    assert_eq!(verify_cache_dir(cache_dir.path())?.verified.len(), 8);
+
+    // A file recorded after the verification is not removed as untracked
+    let late_path = cache_dir.path().join(content_address('e', Some('f')));
+    fs::write(&late_path, b"late download")?;
+    let verification = verify_cache_dir(cache_dir.path())?;
+    assert_eq!(verification.untracked, vec![late_path.clone()]);
+    CacheManifest::update(cache_dir.path(), |manifest| manifest.record(&late_path))?;
+    assert!(collect_garbage(&verification)?.is_empty());
+    assert!(late_path.exists());
+
+    Ok(())
+}
+
+#[test]
+fn custom_resource_backend() -> anyhow::Result<()> {
+    let cache_dir = tempfile::tempdir()?;
+    let backend = InMemoryBackend::default();
+    let (objects, downloads, offline) = (
+        backend.objects.clone(),
+        backend.downloads.clone(),
+        backend.offline.clone(),
+    );
+    let config_url = "memory://models/config.json";
+    objects
+        .write()
+        .unwrap()
+        .insert(config_url, (1, b"{\"vocab_size\": 8}".to_vec()));
+    register_backend(backend);
+
+    let config_resource = Resource::Remote(RemoteResource::new(config_url, "in-memory-model"));
+    let config_path = config_resource.get_local_path_in(cache_dir.path())?;
+    assert!(config_path.starts_with(cache_dir.path().join("in-memory-model")));
+    assert_eq!(fs::read(&config_path)?, b"{\"vocab_size\": 8}");
+
+    // Cached resources are not downloaded again while their version is unchanged
+    assert_eq!(
+        config_resource.get_local_path_in(cache_dir.path())?,
+        config_path
+    );
+    assert_eq!(downloads.load(Ordering::SeqCst), 1);
+
+    // The cached version is used if the backend cannot be reached
+    offline.store(true, Ordering::SeqCst);
+    assert_eq!(
+        config_resource.get_local_path_in(cache_dir.path())?,
+        config_path
+    );
+    offline.store(false, Ordering::SeqCst);
+
+    // A new version of the resource is downloaded next to the previous one
+    objects
+        .write()
+        .unwrap()
+        .insert(config_url, (2, b"{\"vocab_size\": 16}".to_vec()));
+    let updated_config_path = config_resource.get_local_path_in(cache_dir.path())?;
+    assert_ne!(updated_config_path, config_path);
+    assert_eq!(fs::read(&updated_config_path)?, b"{\"vocab_size\": 16}");
+    assert_eq!(downloads.load(Ordering::SeqCst), 2);
+
+    let missing_resource = Resource::Remote(RemoteResource::new(
+        "memory://models/missing.json",
+        "in-memory-model",
+    ));
+    assert!(missing_resource
+        .get_local_path_in(cache_dir.path())
+        .is_err());
+    assert_eq!(
+        fs::read_dir(cache_dir.path().join("in-memory-model"))?.count(),
+        2
+    );
+
+    Ok(())
+}
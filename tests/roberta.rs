@@ -5,6 +5,9 @@ use rust_bert::pipelines::question_answering::{
     QaInput, QuestionAnsweringConfig, QuestionAnsweringModel,
 };
 use rust_bert::pipelines::token_classification::TokenClassificationConfig;
+use rust_bert::pipelines::zero_shot_classification::{
+    hypothesis_template, ZeroShotClassificationConfig, ZeroShotClassificationModel,
+};
 use rust_bert::resources::{RemoteResource, Resource};
 use rust_bert::roberta::{
     RobertaConfigResources, RobertaForMaskedLM, RobertaForMultipleChoice,
@@ -422,3 +425,49 @@ fn xlm_roberta_german_ner() -> anyhow::Result<()> {
 
     Ok(())
 }
+
+#[test]
+fn xlm_roberta_zero_shot_hypothesis_templates() -> anyhow::Result<()> {
+    assert_eq!(hypothesis_template("FR"), Some("Cet exemple parle de {}."));
+    assert_eq!(hypothesis_template("xx"), None);
+    assert!(ZeroShotClassificationConfig::multilingual("xx").is_err());
+
+    let config = ZeroShotClassificationConfig::multilingual("de")?;
+    assert_eq!(config.model_type, ModelType::XLMRoberta);
+    assert_eq!(
+        config.hypothesis_template.as_deref(),
+        Some("Dieses Beispiel handelt von {}.")
+    );
+
+    let invalid_config = ZeroShotClassificationConfig {
+        hypothesis_template: Some("Dieses Beispiel handelt von Politik.".to_string()),
+        ..config
+    };
+    assert!(ZeroShotClassificationModel::new(invalid_config).is_err());
+    Ok(())
+}
+
+#[test]
+#[cfg_attr(not(feature = "all-tests"), ignore)]
+fn xlm_roberta_multilingual_zero_shot_classification() -> anyhow::Result<()> {
+    let zero_shot_config = ZeroShotClassificationConfig {
+        device: Device::Cpu,
+        ..ZeroShotClassificationConfig::multilingual("fr")?
+    };
+    let model = ZeroShotClassificationModel::new(zero_shot_config)?;
+
+    let output = model.predict(
+        &[
+            "Le Premier ministre a annoncé un plan de relance critiqué par l'opposition.",
+            "L'équipe a remporté le match grâce à un but dans les dernières minutes.",
+        ],
+        &["politique", "santé", "sport"],
+        None,
+        128,
+    );
+
+    assert_eq!(output.len(), 2);
+    assert_eq!(output[0].text, "politique");
+    assert_eq!(output[1].text, "sport");
+    Ok(())
+}
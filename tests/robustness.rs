@@ -0,0 +1,85 @@
+use rust_bert::pipelines::robustness::{
+    NegationPerturber, Perturber, RobustnessTester, TextClassifier,
+};
+use rust_bert::pipelines::sequence_classification::Label;
+use rust_bert::RustBertError;
+
+struct KeywordClassifier;
+
+impl TextClassifier for KeywordClassifier {
+    fn classify(&self, texts: &[&str]) -> Result<Vec<Label>, RustBertError> {
+        Ok(texts
+            .iter()
+            .enumerate()
+            .map(|(sentence, text)| {
+                let negative = text.contains("not") | text.contains("bad");
+                Label {
+                    text: if negative { "NEGATIVE" } else { "POSITIVE" }.to_string(),
+                    score: 1.0,
+                    id: negative as i64,
+                    sentence,
+                }
+            })
+            .collect())
+    }
+}
+
+struct SuffixPerturber;
+
+impl Perturber for SuffixPerturber {
+    fn name(&self) -> &str {
+        "suffix"
+    }
+
+    fn perturb(&self, texts: &[&str]) -> Result<Vec<Vec<String>>, RustBertError> {
+        Ok(texts
+            .iter()
+            .map(|text| vec![format!("{} Truly.", text), format!("{} Too bad.", text)])
+            .collect())
+    }
+}
+
+#[test]
+fn robustness_negation() {
+    let perturber = NegationPerturber::default();
+    assert_eq!(
+        perturber.negate("The room was clean."),
+        Some("The room was not clean.".to_string())
+    );
+    assert_eq!(
+        perturber.negate("The staff wasn't helpful"),
+        Some("The staff was helpful".to_string())
+    );
+    assert_eq!(
+        perturber.negate("I would never come back."),
+        Some("I would come back.".to_string())
+    );
+    assert_eq!(perturber.negate("Great value"), None);
+}
+
+#[test]
+fn robustness_report() -> anyhow::Result<()> {
+    let tester = RobustnessTester::new(KeywordClassifier)
+        .with_perturber(NegationPerturber::default())
+        .with_perturber(SuffixPerturber);
+    let report = tester.evaluate(&["The room is clean.", "Great value", "It is bad."])?;
+
+    assert_eq!(report.results.len(), 2 + 6);
+    let negations = report
+        .results
+        .iter()
+        .filter(|result| result.perturbation == "negation")
+        .collect::<Vec<_>>();
+    assert_eq!(negations[0].text, "The room is not clean.");
+    assert!(negations[0].is_flip() & !negations[0].is_failure());
+    assert_eq!(negations[1].input_index, 2);
+    assert!(!negations[1].is_flip() & negations[1].is_failure());
+
+    assert_eq!(report.flip_rate(Some("negation")), 0.5);
+    assert_eq!(report.flips().len(), 1 + 2);
+    assert_eq!(report.failure_rate(Some("suffix")), 2.0 / 6.0);
+    assert_eq!(report.failures().len(), 1 + 2);
+    assert_eq!(report.failure_rate(Some("unknown")), 0.0);
+
+    Ok(())
+}
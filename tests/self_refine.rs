@@ -0,0 +1,80 @@
+use rust_bert::pipelines::prompt_template::PromptTemplate;
+use rust_bert::pipelines::self_refine::{KeywordStop, RefinementStop, SelfRefiner};
+use rust_bert::pipelines::structured_output::TextCompletion;
+use rust_bert::RustBertError;
+use std::cell::RefCell;
+
+struct ScriptedCompletion {
+    outputs: RefCell<Vec<&'static str>>,
+    prompts: RefCell<Vec<String>>,
+}
+
+impl ScriptedCompletion {
+    fn new(outputs: &[&'static str]) -> ScriptedCompletion {
+        ScriptedCompletion {
+            outputs: RefCell::new(outputs.iter().rev().cloned().collect()),
+            prompts: RefCell::new(vec![]),
+        }
+    }
+}
+
+impl TextCompletion for ScriptedCompletion {
+    fn complete(&self, prompt: &str) -> Result<String, RustBertError> {
+        self.prompts.borrow_mut().push(prompt.to_string());
+        Ok(self.outputs.borrow_mut().pop().unwrap().to_string())
+    }
+}
+
+#[test]
+fn self_refine_keyword_stop() -> anyhow::Result<()> {
+    let stop = KeywordStop::new(&["No changes needed"]);
+    assert!(stop.should_stop("Looks great, no changes NEEDED.")?);
+    assert!(!stop.should_stop("Add a conclusion.")?);
+
+    let generator = ScriptedCompletion::new(&[" First draft ", "Second draft"]);
+    let critic = ScriptedCompletion::new(&["Too short.", "No changes needed."]);
+    let refiner = SelfRefiner::new(&generator, &critic)
+        .with_critique_template(PromptTemplate::new("{task} | {draft}")?)
+        .with_revision_template(PromptTemplate::new("{task} | {draft} | {critique}")?)
+        .with_max_rounds(5)
+        .with_stop(KeywordStop::new(&["no changes needed"]));
+
+    let output = refiner.generate("Describe")?;
+    assert_eq!(output.text, "Second draft");
+    assert!(output.converged);
+    assert_eq!(output.rounds.len(), 2);
+    assert_eq!(output.rounds[0].draft, "First draft");
+    assert_eq!(output.rounds[0].critique, "Too short.");
+    assert_eq!(output.rounds[1].draft, "Second draft");
+    assert_eq!(
+        generator.prompts.borrow().clone(),
+        vec!["Describe", "Describe | First draft | Too short."]
+    );
+    assert_eq!(
+        critic.prompts.borrow().clone(),
+        vec!["Describe | First draft", "Describe | Second draft"]
+    );
+
+    Ok(())
+}
+
+#[test]
+fn self_refine_max_rounds() -> anyhow::Result<()> {
+    let generator = ScriptedCompletion::new(&["Revision 1", "Revision 2"]);
+    let critic = ScriptedCompletion::new(&["Fix a.", "Fix b."]);
+    let refiner = SelfRefiner::new(&generator, &critic).with_max_rounds(2);
+
+    let output = refiner.refine("Describe", "Draft")?;
+    assert_eq!(output.text, "Revision 2");
+    assert!(!output.converged);
+    assert_eq!(
+        output
+            .rounds
+            .iter()
+            .map(|round| round.draft.as_str())
+            .collect::<Vec<&str>>(),
+        vec!["Draft", "Revision 1"]
+    );
+
+    Ok(())
+}
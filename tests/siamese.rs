@@ -0,0 +1,95 @@
+use rust_bert::siamese::{
+    cosine_embedding_loss, multiple_negatives_ranking_loss, triplet_loss, BatchBuilder,
+    InputExample, TripletDistance,
+};
+use tch::{Device, Kind, Tensor};
+
+fn embeddings(values: &[f32], dim: i64) -> Tensor {
+    Tensor::of_slice(values).view((-1, dim))
+}
+
+#[test]
+fn siamese_losses() {
+    let anchors = embeddings(&[1.0, 0.0, 0.0, 1.0], 2);
+    let aligned = embeddings(&[2.0, 0.0, 0.0, 3.0], 2);
+    let swapped = embeddings(&[0.0, 3.0, 2.0, 0.0], 2);
+
+    let aligned_loss = multiple_negatives_ranking_loss(&anchors, &aligned, 20.0);
+    let swapped_loss = multiple_negatives_ranking_loss(&anchors, &swapped, 20.0);
+    assert!(aligned_loss.double_value(&[]) < 1e-6);
+    assert!(swapped_loss.double_value(&[]) > 10.0);
+
+    let labels = Tensor::of_slice(&[1.0f32, -1.0]);
+    let loss = cosine_embedding_loss(&anchors, &swapped, &labels, 0.0);
+    // The similar pair is orthogonal (loss 1), the dissimilar pair as well (loss 0)
+    assert!((loss.double_value(&[]) - 0.5).abs() < 1e-6);
+
+    let negatives = embeddings(&[3.0, 0.0, 0.0, 1.0], 2);
+    let loss = triplet_loss(
+        &anchors,
+        &aligned,
+        &negatives,
+        TripletDistance::Euclidean,
+        1.0,
+    );
+    // Distances: (1, 2) and (2, 0) for the anchor-positive and anchor-negative pairs
+    assert!((loss.double_value(&[]) - 1.5).abs() < 1e-5);
+    let loss = triplet_loss(&anchors, &aligned, &swapped, TripletDistance::Cosine, 0.5);
+    assert!(loss.double_value(&[]).abs() < 1e-6);
+}
+
+#[test]
+fn siamese_loss_gradients() {
+    let anchors = Tensor::rand(&[4, 8], (Kind::Float, Device::Cpu)).set_requires_grad(true);
+    let positives = Tensor::rand(&[4, 8], (Kind::Float, Device::Cpu));
+    let loss = multiple_negatives_ranking_loss(&anchors, &positives, 20.0);
+    loss.backward();
+    assert_eq!(anchors.grad().size(), vec![4, 8]);
+}
+
+#[test]
+fn siamese_batches_without_duplicates() -> anyhow::Result<()> {
+    let examples = vec![
+        InputExample::new(&["a", "x"], 1.0),
+        InputExample::new(&["a", "y"], 1.0),
+        InputExample::new(&["b", "z"], 1.0),
+        InputExample::new(&["c", "x"], -1.0),
+        InputExample::new(&["d", "w"], 1.0),
+    ];
+
+    let batches = BatchBuilder::new(2).batches(&examples)?;
+    assert_eq!(batches.len(), 3);
+    assert_eq!(batches[0].columns[0], vec!["a", "a"]);
+
+    let batches = BatchBuilder::new(2)
+        .with_no_duplicates(true)
+        .batches(&examples)?;
+    let anchors = batches
+        .iter()
+        .map(|batch| batch.columns[0].clone())
+        .collect::<Vec<Vec<String>>>();
+    assert_eq!(anchors, vec![vec!["a", "b"], vec!["a", "c"], vec!["d"]]);
+    assert_eq!(batches[1].columns[1], vec!["y", "x"]);
+    assert_eq!(
+        batches[1]
+            .labels
+            .iter::<f64>()
+            .unwrap()
+            .collect::<Vec<f64>>(),
+        vec![1.0, -1.0]
+    );
+
+    let batches = BatchBuilder::new(2)
+        .with_no_duplicates(true)
+        .with_drop_last(true)
+        .batches(&examples)?;
+    assert_eq!(batches.len(), 2);
+
+    assert!(BatchBuilder::new(2)
+        .batches(&[
+            InputExample::new(&["a"], 1.0),
+            InputExample::new(&["a", "b"], 1.0)
+        ])
+        .is_err());
+    Ok(())
+}
@@ -0,0 +1,56 @@
+use rust_bert::pipelines::stance_detection::{
+    split_sentences, Stance, StanceDetectionConfig, StanceDetectionModel,
+};
+use tch::Device;
+
+#[test]
+fn stance_detection_sentence_splitting() {
+    let text = "  Water was found on K2-18b. Is it habitable?  Maybe!\nVersion 2.0 is out";
+    let sentences = split_sentences(text)
+        .into_iter()
+        .map(|(start, end)| &text[start..end])
+        .collect::<Vec<&str>>();
+    assert_eq!(
+        sentences,
+        vec![
+            "Water was found on K2-18b.",
+            "Is it habitable?",
+            "Maybe!",
+            "Version 2.0 is out"
+        ]
+    );
+    assert!(split_sentences("   ").is_empty());
+}
+
+#[test]
+#[cfg_attr(not(feature = "all-tests"), ignore)]
+fn stance_detection_claim_verification() -> anyhow::Result<()> {
+    let mut config = StanceDetectionConfig::default();
+    config.nli_config.device = Device::Cpu;
+    let model = StanceDetectionModel::new(config)?;
+
+    let claim = "Water vapour was found in the atmosphere of the planet K2-18b.";
+    let documents = [
+        "Astronomers announced on Wednesday that K2-18b has an atmosphere. Water vapour was confirmed in the \
+atmosphere of the planet K2-18b by two teams.",
+        "The atmosphere of the planet K2-18b is completely dry: no water vapour was found.",
+        "The local football team won the championship after a penalty shoot-out.",
+    ];
+    let input = documents
+        .iter()
+        .map(|document| (claim, *document))
+        .collect::<Vec<(&str, &str)>>();
+    let output = model.predict(&input)?;
+
+    assert_eq!(output.len(), 3);
+    assert_eq!(output[0].stance, Stance::Agree);
+    assert_eq!(output[1].stance, Stance::Disagree);
+    assert_eq!(output[2].stance, Stance::Unrelated);
+    assert!(output[2].evidence.is_empty());
+    let (start, end) = output[0].evidence[0].offsets;
+    assert_eq!(&documents[0][start..end], output[0].evidence[0].text);
+
+    let verified = model.verify(claim, &documents)?;
+    assert_eq!(verified.len(), 2);
+    Ok(())
+}
@@ -0,0 +1,78 @@
+use rust_bert::pipelines::structured_output::{
+    extract_json, JsonSchema, StructuredOutputGenerator, TextCompletion,
+};
+use rust_bert::RustBertError;
+use serde::Deserialize;
+use serde_json::json;
+use std::cell::RefCell;
+
+struct ScriptedCompletion {
+    outputs: RefCell<Vec<&'static str>>,
+}
+
+impl TextCompletion for ScriptedCompletion {
+    fn complete(&self, _prompt: &str) -> Result<String, RustBertError> {
+        Ok(self.outputs.borrow_mut().remove(0).to_string())
+    }
+}
+
+#[derive(Debug, Deserialize, PartialEq)]
+struct ToolCall {
+    tool: String,
+    city: String,
+    days: u32,
+}
+
+#[test]
+fn test_structured_output() -> anyhow::Result<()> {
+    assert_eq!(
+        extract_json("Sure! ```json\n{\"a\": \"}\", \"b\": [1, {}]}\n``` done"),
+        Some("{\"a\": \"}\", \"b\": [1, {}]}")
+    );
+    assert_eq!(extract_json("no json here"), None);
+
+    let schema = JsonSchema::object(vec![
+        (
+            "tool",
+            JsonSchema::Enum(vec!["weather".to_string(), "search".to_string()]),
+        ),
+        ("city", JsonSchema::String),
+        ("days", JsonSchema::Integer),
+    ]);
+    assert!(schema
+        .validate(&json!({"tool": "weather", "city": "Paris", "days": 3}))
+        .is_ok());
+    assert!(schema
+        .validate(&json!({"tool": "weather", "city": "Paris", "days": 2.5}))
+        .is_err());
+    assert!(schema
+        .validate(&json!({"tool": "weather", "city": "Paris"}))
+        .is_err());
+
+    let completion = ScriptedCompletion {
+        outputs: RefCell::new(vec![
+            "{\"tool\": \"weather\", \"city\": \"Paris\"",
+            "{\"tool\": \"calendar\", \"city\": \"Paris\", \"days\": 3}",
+            "Here you go: {\"tool\": \"weather\", \"city\": \"Paris\", \"days\": 3}",
+        ]),
+    };
+    let generator = StructuredOutputGenerator::new(completion).with_schema(schema);
+    let call: ToolCall = generator.generate("Weather in Paris for 3 days?")?;
+    assert_eq!(
+        call,
+        ToolCall {
+            tool: "weather".to_string(),
+            city: "Paris".to_string(),
+            days: 3
+        }
+    );
+
+    let completion = ScriptedCompletion {
+        outputs: RefCell::new(vec!["not json", "still not json"]),
+    };
+    let generator = StructuredOutputGenerator::new(completion).with_max_retries(1);
+    assert!(generator
+        .generate::<ToolCall>("Weather in Paris for 3 days?")
+        .is_err());
+    Ok(())
+}
@@ -1,9 +1,17 @@
 use rust_bert::pipelines::common::ModelType;
+use rust_bert::pipelines::doc2query::deduplicate_queries;
+use rust_bert::pipelines::generation_utils::{
+    GenerateConfig, GenerateOptions, LanguageGenerator, T5Generator,
+};
 use rust_bert::pipelines::summarization::{SummarizationConfig, SummarizationModel};
 use rust_bert::pipelines::translation::{TranslationConfig, TranslationModel};
-use rust_bert::resources::{RemoteResource, Resource};
-use rust_bert::t5::{T5ConfigResources, T5ModelResources, T5VocabResources};
-use tch::Device;
+use rust_bert::resources::{LocalResource, RemoteResource, Resource};
+use rust_bert::t5::{
+    T5Config, T5ConfigResources, T5ForConditionalGeneration, T5ModelResources, T5VocabResources,
+};
+use rust_bert::task_registry::TaskRegistry;
+use rust_bert::Config;
+use tch::{nn, Device};
 
 #[test]
 fn test_translation_t5() -> anyhow::Result<()> {
@@ -90,3 +98,158 @@ about exoplanets like K2-18b."];
 
     Ok(())
 }
+
+#[test]
+fn t5_generation_from_encoded_prompts() -> anyhow::Result<()> {
+    let generate_config = GenerateConfig {
+        model_resource: Resource::Remote(RemoteResource::from_pretrained(
+            T5ModelResources::T5_SMALL,
+        )),
+        config_resource: Resource::Remote(RemoteResource::from_pretrained(
+            T5ConfigResources::T5_SMALL,
+        )),
+        vocab_resource: Resource::Remote(RemoteResource::from_pretrained(
+            T5VocabResources::T5_SMALL,
+        )),
+        merges_resource: Resource::Remote(RemoteResource::from_pretrained(
+            T5VocabResources::T5_SMALL,
+        )),
+        max_length: 32,
+        do_sample: false,
+        num_beams: 1,
+        device: Device::Cpu,
+        ..Default::default()
+    };
+    let generator = T5Generator::new(generate_config)?;
+
+    let prompts = [
+        "translate English to French: The table is big.",
+        "translate English to German: The cat is sleeping.",
+    ];
+    let encoded_prompts = generator.encode_prompts(&prompts, None)?;
+    assert_eq!(encoded_prompts.hidden_states.size()[0], 2);
+
+    let greedy_options = GenerateOptions {
+        num_beams: Some(1),
+        ..Default::default()
+    };
+    let greedy_output = generator.generate_from_encoded_prompts(&encoded_prompts, &greedy_options);
+    assert_eq!(
+        greedy_output,
+        generator.generate_with_options(Some(&prompts), None, &greedy_options)
+    );
+
+    let beam_options = GenerateOptions {
+        num_beams: Some(3),
+        num_return_sequences: Some(2),
+        ..Default::default()
+    };
+    let beam_output = generator.generate_from_encoded_prompts(&encoded_prompts, &beam_options);
+    assert_eq!(beam_output.len(), 4);
+
+    Ok(())
+}
+
+#[test]
+fn t5_relative_attention_bias_reloaded_weights() -> anyhow::Result<()> {
+    let config_resource =
+        Resource::Remote(RemoteResource::from_pretrained(T5ConfigResources::T5_SMALL));
+    let config = T5Config::from_file(config_resource.get_local_path()?);
+
+    // Randomly initialized weights, with the variable names of the generator
+    let random_var_store = nn::VarStore::new(Device::Cpu);
+    let _ = T5ForConditionalGeneration::new(&random_var_store.root(), &config, false, false);
+    let weights_dir = tempfile::tempdir()?;
+    let random_weights = Resource::Local(LocalResource {
+        local_path: weights_dir.path().join("rust_model.ot"),
+    });
+    random_var_store.save(random_weights.get_local_path()?)?;
+
+    let generate_config = |model_resource| GenerateConfig {
+        model_resource,
+        config_resource: config_resource.clone(),
+        vocab_resource: Resource::Remote(RemoteResource::from_pretrained(
+            T5VocabResources::T5_SMALL,
+        )),
+        merges_resource: Resource::Remote(RemoteResource::from_pretrained(
+            T5VocabResources::T5_SMALL,
+        )),
+        max_length: 16,
+        do_sample: false,
+        num_beams: 1,
+        device: Device::Cpu,
+        ..Default::default()
+    };
+    let prompts = ["translate English to German: The house is wonderful."];
+    let generate =
+        |generator: &T5Generator| generator.generate(Some(&prompts), None, None, None, None);
+
+    let mut generator = T5Generator::new(generate_config(Resource::Remote(
+        RemoteResource::from_pretrained(T5ModelResources::T5_SMALL),
+    )))?;
+    let initial_output = generate(&generator);
+    generator.reload_weights(&random_weights)?;
+    let reloaded_output = generate(&generator);
+    let expected_output = generate(&T5Generator::new(generate_config(random_weights))?);
+
+    // The relative attention biases cached before the reload are not used with the new weights
+    assert_ne!(reloaded_output, initial_output);
+    assert_eq!(reloaded_output, expected_output);
+
+    Ok(())
+}
+
+#[test]
+fn test_task_registry_t5() -> anyhow::Result<()> {
+    let config_resource =
+        Resource::Remote(RemoteResource::from_pretrained(T5ConfigResources::T5_SMALL));
+    let config = T5Config::from_file(config_resource.get_local_path()?);
+    let task_registry = config.task_registry()?;
+
+    assert_eq!(
+        task_registry.tasks(),
+        vec![
+            "summarization",
+            "translation_en_to_de",
+            "translation_en_to_fr",
+            "translation_en_to_ro"
+        ]
+    );
+    assert_eq!(task_registry.prefix("summarization"), Some("summarize: "));
+    assert_eq!(
+        task_registry.prefix(&TaskRegistry::translation_task("en", "fr")),
+        Some("translate English to French: ")
+    );
+
+    let mut generate_config = GenerateConfig::default();
+    task_registry
+        .get("summarization")
+        .unwrap()
+        .update_generate_config(&mut generate_config);
+    assert_eq!(generate_config.num_beams, 4);
+    assert_eq!(generate_config.max_length, 200);
+    assert_eq!(generate_config.no_repeat_ngram_size, 3);
+
+    Ok(())
+}
+
+#[test]
+fn test_doc2query_deduplication() -> anyhow::Result<()> {
+    let queries = deduplicate_queries(&[
+        "what was the manhattan project?",
+        " What was the Manhattan project ",
+        "",
+        "?",
+        "who produced the first nuclear weapons",
+        "what was the manhattan project",
+    ]);
+    assert_eq!(
+        queries,
+        vec![
+            "what was the manhattan project?",
+            "who produced the first nuclear weapons"
+        ]
+    );
+
+    Ok(())
+}
@@ -0,0 +1,66 @@
+use rust_bert::pipelines::common::TensorBatch;
+use tch::{Device, Kind, Tensor};
+
+#[test]
+fn tensor_batch_from_token_ids() {
+    let token_ids = vec![vec![101, 7, 8, 102], vec![101, 9, 102]];
+    let input_batch = TensorBatch::from_token_ids(&token_ids, 0, Device::Cpu);
+
+    assert_eq!(input_batch.batch_size(), 2);
+    let input_ids = input_batch.input_ids();
+    assert_eq!(input_ids.size(), vec![2, 4]);
+    assert_eq!(
+        input_ids
+            .get(1)
+            .iter::<i64>()
+            .unwrap()
+            .collect::<Vec<i64>>(),
+        vec![101, 9, 102, 0]
+    );
+    let attention_mask = input_batch.attention_mask().unwrap();
+    assert_eq!(attention_mask.kind(), Kind::Int64);
+    assert_eq!(
+        attention_mask
+            .get(1)
+            .iter::<i64>()
+            .unwrap()
+            .collect::<Vec<i64>>(),
+        vec![1, 1, 1, 0]
+    );
+    let attention_mask = input_batch.attention_mask_as(Kind::Bool).unwrap();
+    assert_eq!(attention_mask.kind(), Kind::Bool);
+    assert!(input_batch.token_type_ids().is_none());
+}
+
+#[test]
+fn tensor_batch_casts_inputs() {
+    let input_ids = Tensor::of_slice(&[1i32, 2, 3]).view((1, 3));
+    let input_batch = TensorBatch::new(input_ids, Device::Cpu)
+        .with_attention_mask(Tensor::ones(&[1, 3], (Kind::Float, Device::Cpu)))
+        .with_token_type_ids(Tensor::zeros(&[1, 3], (Kind::Int, Device::Cpu)));
+
+    assert_eq!(input_batch.input_ids().kind(), Kind::Int64);
+    assert_eq!(input_batch.attention_mask().unwrap().kind(), Kind::Float);
+    assert_eq!(input_batch.token_type_ids().unwrap().kind(), Kind::Int64);
+    assert_eq!(input_batch.device(), Device::Cpu);
+
+    let empty_batch = TensorBatch::from_token_ids(&[], 0, Device::Cpu);
+    assert_eq!(empty_batch.batch_size(), 0);
+}
+
+#[test]
+fn tensor_batch_converts_inputs_once() {
+    let input_ids = Tensor::of_slice(&[1i32, 2, 3]).view((1, 3));
+    let input_batch = TensorBatch::new(input_ids, Device::Cpu)
+        .with_token_type_ids(Tensor::zeros(&[1, 3], (Kind::Int, Device::Cpu)));
+
+    // The converted tensors are cached and returned by the following accesses
+    assert_eq!(
+        input_batch.input_ids().data_ptr(),
+        input_batch.input_ids().data_ptr()
+    );
+    assert_eq!(
+        input_batch.token_type_ids().unwrap().data_ptr(),
+        input_batch.token_type_ids().unwrap().data_ptr()
+    );
+}
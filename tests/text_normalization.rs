@@ -0,0 +1,42 @@
+use rust_bert::pipelines::text_normalization::{decode_html_entities, TextNormalizer};
+
+#[test]
+fn test_html_entities_decoding() {
+    assert_eq!(
+        decode_html_entities("Fish &amp; chips &#8211; &#x20AC;5 &lt;b&gt;"),
+        "Fish & chips – €5 <b>"
+    );
+    assert_eq!(
+        decode_html_entities("&amp;lt; &unknown; R&D;"),
+        "&lt; &unknown; R&D;"
+    );
+    assert_eq!(decode_html_entities("AT&T &"), "AT&T &");
+}
+
+#[test]
+fn test_text_normalization() {
+    let text_normalizer = TextNormalizer::default();
+    assert_eq!(
+        text_normalizer
+            .normalize("  Cafe\u{0301}&nbsp;opens\u{200B} at\t9.\u{0007}\n\nAll rights reserved. "),
+        "Caf\u{00E9} opens at 9. All rights reserved."
+    );
+
+    let text_normalizer = TextNormalizer {
+        remove_boilerplate: true,
+        ..Default::default()
+    };
+    let text = "Skip to content\nThe council approved the budget on Monday.\n\
+        We use cookies to improve your experience. Accept all cookies\n\
+        The vote passed 7 to 2.\n© 2021 The Daily. All rights reserved.";
+    assert_eq!(
+        text_normalizer.normalize(text),
+        "The council approved the budget on Monday. The vote passed 7 to 2."
+    );
+
+    let text_normalizer = TextNormalizer {
+        collapse_whitespace: false,
+        ..Default::default()
+    };
+    assert_eq!(text_normalizer.normalize("a\u{FEFF}  b\n"), "a  b\n");
+}
@@ -0,0 +1,61 @@
+use rust_bert::pipelines::common::{ModelType, TokenizerOption};
+use rust_bert::pipelines::text_statistics::{
+    count_syllables, InputValidator, ReadabilityScores, TokenStatistics,
+};
+use std::fs;
+
+#[test]
+fn test_readability_scores() {
+    assert_eq!(count_syllables("cake"), 1);
+    assert_eq!(count_syllables("table"), 2);
+    assert_eq!(count_syllables("Readability,"), 5);
+    assert_eq!(count_syllables("42"), 0);
+
+    let scores = ReadabilityScores::compute("The cat sat on the mat. It was happy.");
+    assert_eq!(scores.num_sentences, 2);
+    assert_eq!(scores.num_words, 9);
+    assert_eq!(scores.num_syllables, 10);
+    assert_eq!(scores.num_complex_words, 0);
+    assert!((scores.flesch_reading_ease - 108.2675).abs() < 1e-3);
+    assert!((scores.flesch_kincaid_grade - (-0.7239)).abs() < 1e-3);
+
+    let scores = ReadabilityScores::compute("  ...  ");
+    assert_eq!(scores.num_words, 0);
+    assert_eq!(scores.flesch_reading_ease, 0.0);
+}
+
+#[test]
+fn test_token_statistics() -> anyhow::Result<()> {
+    let vocab_path = std::env::temp_dir().join("rust_bert_text_statistics_vocab.txt");
+    fs::write(
+        &vocab_path,
+        "[PAD]\n[UNK]\n[CLS]\n[SEP]\n[MASK]\nthe\ncat\nsat\non\n.\n##s\n",
+    )?;
+    let tokenizer = TokenizerOption::from_file(
+        ModelType::Bert,
+        vocab_path.to_str().unwrap(),
+        None,
+        true,
+        None,
+        None,
+    )?;
+
+    let statistics = TokenStatistics::compute(&tokenizer, "The cats sat on the zebra.");
+    assert_eq!(statistics.num_words, 6);
+    assert_eq!(statistics.num_tokens, 8);
+    assert_eq!(statistics.num_unknown_tokens, 1);
+    assert!((statistics.unknown_rate - 0.125).abs() < 1e-6);
+    assert!((statistics.fertility - 8.0 / 6.0).abs() < 1e-6);
+
+    let validator = InputValidator {
+        max_tokens: Some(10),
+        ..Default::default()
+    };
+    assert!(validator.validate(&tokenizer, "The cats sat.").is_ok());
+    let validator = InputValidator {
+        max_unknown_rate: Some(0.1),
+        ..Default::default()
+    };
+    assert!(validator.validate(&tokenizer, "The zebra sat.").is_err());
+    Ok(())
+}
@@ -0,0 +1,61 @@
+extern crate anyhow;
+
+use rust_bert::wav2vec2::{Wav2Vec2Config, Wav2Vec2ForSequenceClassification};
+use rust_bert::Config;
+use tch::{nn, no_grad, Device, Kind, Tensor};
+
+fn tiny_config(stable_layer_norm: bool) -> anyhow::Result<Wav2Vec2Config> {
+    let config: Wav2Vec2Config = serde_json::from_str(&format!(
+        r#"{{
+            "hidden_size": 32,
+            "num_hidden_layers": 2,
+            "num_attention_heads": 4,
+            "intermediate_size": 64,
+            "hidden_act": "gelu",
+            "feat_extract_norm": "{}",
+            "do_stable_layer_norm": {},
+            "conv_dim": [16, 16, 16],
+            "conv_kernel": [10, 3, 3],
+            "conv_stride": [5, 2, 2],
+            "num_conv_pos_embeddings": 16,
+            "num_conv_pos_embedding_groups": 4,
+            "classifier_proj_size": 8,
+            "use_weighted_layer_sum": true,
+            "output_hidden_states": true,
+            "id2label": {{"0": "yes", "1": "no", "2": "_silence_"}}
+        }}"#,
+        if stable_layer_norm { "layer" } else { "group" },
+        stable_layer_norm
+    ))?;
+    config.validate()?;
+    Ok(config)
+}
+
+#[test]
+fn wav2vec2_sequence_classification() -> anyhow::Result<()> {
+    for &stable_layer_norm in &[false, true] {
+        //    Set-up model with random weights
+        let device = Device::Cpu;
+        let vs = nn::VarStore::new(device);
+        let config = tiny_config(stable_layer_norm)?;
+        let model = Wav2Vec2ForSequenceClassification::new(&vs.root(), &config);
+        assert!(vs
+            .variables()
+            .contains_key("wav2vec2.encoder.pos_conv_embed.conv.weight_g"));
+
+        //    Forward pass
+        let input_values = Tensor::randn(&[2, 1600], (Kind::Float, device));
+        let lengths = config.feature_lengths(&Tensor::of_slice(&[1600i64, 800]));
+        assert_eq!(Vec::<i64>::from(&lengths), vec![79, 39]);
+        let frame_mask = Tensor::arange(79, (Kind::Int64, device))
+            .unsqueeze(0)
+            .lt1(&lengths.unsqueeze(1));
+        let model_output = no_grad(|| model.forward_t(&input_values, Some(&frame_mask), false));
+
+        assert_eq!(model_output.logits.size(), &[2, 3]);
+        assert_eq!(model_output.all_hidden_states.unwrap().len(), 3);
+        assert!(model_output.all_attentions.is_none());
+    }
+
+    Ok(())
+}